@@ -0,0 +1,750 @@
+// Converts drag-and-drop actions from GameMaker's built-in library into GML,
+// so events and timelines can be read, searched and diffed as code.
+//
+// Actions are grouped the same way the runner groups them: a question applies to the action
+// or block after it (and an else after that), and a repeat applies to the action or block after it.
+// Runs of convertible actions become a single Execute Code action each.
+// Anything else, such as actions from extension libraries, is left as it is.
+
+use gm8exe::{
+    asset::{code_action::PARAM_COUNT, CodeAction, PascalString},
+    GameAssets,
+};
+use gml_parser::{
+    lexer::Lexer,
+    token::{Keyword, Token},
+};
+use std::mem;
+
+mod kind {
+    pub const NORMAL: u32 = 0;
+    pub const BEGIN_GROUP: u32 = 1;
+    pub const END_GROUP: u32 = 2;
+    pub const ELSE: u32 = 3;
+    pub const EXIT: u32 = 4;
+    pub const REPEAT: u32 = 5;
+    pub const VARIABLE: u32 = 6;
+    pub const CODE: u32 = 7;
+}
+
+mod execution_type {
+    pub const NONE: u32 = 0;
+    pub const FUNCTION: u32 = 1;
+}
+
+const LIB_ID: u32 = 1;
+const COMMENT_ID: u32 = 605;
+const SELF: i32 = -1;
+const OTHER: i32 = -2;
+
+/// Local used to carry a question's result out of a `with` block.
+const RESULT: &str = "__dnd";
+
+/// How a function's "relative" checkbox can be expressed in its arguments instead.
+enum Relative {
+    /// Each listed argument is added to an expression. `{N}` in it stands for argument N.
+    Add(&'static [(usize, &'static str)]),
+    /// Another function does the same relatively, with the same arguments.
+    Call(&'static str),
+}
+
+const XY: &[(usize, &str)] = &[(0, "x"), (1, "y")];
+const XY1: &[(usize, &str)] = &[(1, "x"), (2, "y")];
+const XYXY: &[(usize, &str)] = &[(0, "x"), (1, "y"), (2, "x"), (3, "y")];
+
+/// Every built-in action function that reads `argument_relative`.
+const RELATIVE: &[(&str, Relative)] = &[
+    ("action_set_motion", Relative::Call("motion_add")),
+    ("action_set_hspeed", Relative::Add(&[(0, "hspeed")])),
+    ("action_set_vspeed", Relative::Add(&[(0, "vspeed")])),
+    ("action_set_gravity", Relative::Add(&[(0, "gravity_direction"), (1, "gravity")])),
+    ("action_set_friction", Relative::Add(&[(0, "friction")])),
+    ("action_move_point", Relative::Add(XY)),
+    ("action_move_to", Relative::Add(XY)),
+    ("action_path_position", Relative::Add(&[(0, "path_position")])),
+    ("action_path_speed", Relative::Add(&[(0, "path_speed")])),
+    ("action_linear_step", Relative::Add(XY)),
+    ("action_potential_step", Relative::Add(XY)),
+    ("action_create_object", Relative::Add(XY1)),
+    ("action_create_object_motion", Relative::Add(XY1)),
+    ("action_create_object_random", Relative::Add(&[(4, "x"), (5, "y")])),
+    ("action_kill_position", Relative::Add(XY)),
+    ("action_set_alarm", Relative::Add(&[(0, "alarm[{1}]")])),
+    ("action_set_timeline_position", Relative::Add(&[(0, "timeline_position")])),
+    ("action_set_timeline_speed", Relative::Add(&[(0, "timeline_speed")])),
+    ("action_if_empty", Relative::Add(XY)),
+    ("action_if_collision", Relative::Add(XY)),
+    ("action_if_object", Relative::Add(XY1)),
+    ("action_draw_variable", Relative::Add(XY1)),
+    ("action_set_score", Relative::Add(&[(0, "score")])),
+    ("action_draw_score", Relative::Add(XY)),
+    ("action_set_life", Relative::Add(&[(0, "lives")])),
+    ("action_draw_life", Relative::Add(XY)),
+    ("action_draw_life_images", Relative::Add(XY)),
+    ("action_set_health", Relative::Add(&[(0, "health")])),
+    ("action_draw_health", Relative::Add(XYXY)),
+    ("action_draw_sprite", Relative::Add(XY1)),
+    ("action_draw_background", Relative::Add(XY1)),
+    ("action_draw_text", Relative::Add(XY1)),
+    ("action_draw_text_transformed", Relative::Add(XY1)),
+    ("action_draw_rectangle", Relative::Add(XYXY)),
+    ("action_draw_gradient_hor", Relative::Add(XYXY)),
+    ("action_draw_gradient_vert", Relative::Add(XYXY)),
+    ("action_draw_ellipse", Relative::Add(XYXY)),
+    ("action_draw_ellipse_gradient", Relative::Add(XYXY)),
+    ("action_draw_line", Relative::Add(XYXY)),
+    ("action_draw_arrow", Relative::Add(XYXY)),
+];
+
+/// The standard Execute Code action, as GameMaker creates it.
+pub fn code_action(code: PascalString) -> CodeAction {
+    let mut param_types = [0; PARAM_COUNT];
+    param_types[0] = 1;
+    let mut param_strings: [PascalString; PARAM_COUNT] = Default::default();
+    param_strings[0] = code;
+    CodeAction {
+        id: 603,
+        applies_to: SELF,
+        is_condition: false,
+        invert_condition: false,
+        is_relative: false,
+        lib_id: LIB_ID,
+        action_kind: kind::CODE,
+        execution_type: 2,
+        can_be_relative: 0,
+        applies_to_something: true,
+        fn_name: PascalString::default(),
+        fn_code: PascalString::default(),
+        param_count: 1,
+        param_types,
+        param_strings,
+    }
+}
+
+/// Converts all the built-in drag-and-drop actions in a game's objects and timelines into code.
+/// Returns how many actions were replaced.
+pub fn process(assets: &mut GameAssets) -> usize {
+    let objects = assets.objects.iter_mut().flatten().flat_map(|x| x.events.iter_mut().flatten());
+    let moments = assets.timelines.iter_mut().flatten().flat_map(|x| x.moments.iter_mut());
+    objects.chain(moments).map(|(_, actions)| convert(actions)).sum()
+}
+
+/// Converts a single action list, returning how many actions were replaced.
+pub fn convert(actions: &mut Vec<CodeAction>) -> usize {
+    let plan = {
+        let stmts = Parser { actions, pos: 0 }.list(false).0;
+        // exit in a converted block only ends that block, so it's only safe if the whole list becomes one
+        let mut planner = Planner { actions, allow_exit: true, out: Vec::new() };
+        if !stmts.iter().all(|s| planner.convertible(s)) {
+            planner.allow_exit = false;
+        }
+        planner.list(&stmts);
+        planner.out
+    };
+    if plan.iter().all(|p| matches!(p, Planned::Keep(_))) {
+        return 0
+    }
+
+    let mut old = mem::take(actions).into_iter().map(Some).collect::<Vec<_>>();
+    let mut kept = 0;
+    for planned in plan {
+        actions.push(match planned {
+            Planned::Keep(i) => {
+                kept += 1;
+                old[i].take().unwrap()
+            },
+            Planned::Code(code) => code_action(PascalString(code.into())),
+        });
+    }
+    old.len() - kept
+}
+
+/// An action and what it applies to, by index into the action list.
+enum Stmt {
+    /// An action that's skipped when running, such as a comment.
+    Comment(usize),
+    Action(usize),
+    Block {
+        begin: usize,
+        body: Vec<Stmt>,
+        end: Option<usize>,
+    },
+    If {
+        test: usize,
+        then: Vec<Stmt>,
+        otherwise: Option<(usize, Vec<Stmt>)>,
+    },
+    Repeat {
+        repeat: usize,
+        body: Vec<Stmt>,
+    },
+}
+
+struct Parser<'a> {
+    actions: &'a [CodeAction],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn is_comment(action: &CodeAction) -> bool {
+        (action.action_kind == kind::NORMAL && action.execution_type == execution_type::NONE)
+            || action.action_kind > kind::CODE
+    }
+
+    fn next_kind(&self) -> Option<u32> {
+        self.actions.get(self.pos).map(|a| a.action_kind)
+    }
+
+    /// Reads statements until the end of a block, returning them and the end of block action.
+    fn list(&mut self, in_block: bool) -> (Vec<Stmt>, Option<usize>) {
+        let mut stmts = Vec::new();
+        while let Some(action_kind) = self.next_kind() {
+            if in_block && action_kind == kind::END_GROUP {
+                self.pos += 1;
+                return (stmts, Some(self.pos - 1))
+            }
+            stmts.push(self.statement());
+        }
+        (stmts, None)
+    }
+
+    /// Reads the statement a question or repeat applies to, along with any comments before it.
+    fn single(&mut self) -> Vec<Stmt> {
+        let mut stmts = self.comments();
+        if self.pos < self.actions.len() {
+            stmts.push(self.statement());
+        }
+        stmts
+    }
+
+    fn comments(&mut self) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        while self.actions.get(self.pos).is_some_and(Self::is_comment) {
+            stmts.push(Stmt::Comment(self.pos));
+            self.pos += 1;
+        }
+        stmts
+    }
+
+    fn statement(&mut self) -> Stmt {
+        let i = self.pos;
+        let action = &self.actions[i];
+        self.pos += 1;
+        if Self::is_comment(action) {
+            return Stmt::Comment(i)
+        }
+        match action.action_kind {
+            kind::BEGIN_GROUP => {
+                let (body, end) = self.list(true);
+                Stmt::Block { begin: i, body, end }
+            },
+            kind::REPEAT => Stmt::Repeat { repeat: i, body: self.single() },
+            kind::NORMAL if action.is_condition => {
+                let mut then = self.single();
+                let start = self.pos;
+                let mut between = self.comments();
+                let otherwise = if self.next_kind() == Some(kind::ELSE) {
+                    then.append(&mut between);
+                    self.pos += 1;
+                    Some((self.pos - 1, self.single()))
+                } else {
+                    self.pos = start;
+                    None
+                };
+                Stmt::If { test: i, then, otherwise }
+            },
+            _ => Stmt::Action(i),
+        }
+    }
+}
+
+enum Planned {
+    Keep(usize),
+    Code(Vec<u8>),
+}
+
+struct Planner<'a> {
+    actions: &'a [CodeAction],
+    allow_exit: bool,
+    out: Vec<Planned>,
+}
+
+impl Planner<'_> {
+    fn list(&mut self, stmts: &[Stmt]) {
+        let mut run = Vec::new();
+        for stmt in stmts {
+            if self.convertible(stmt) {
+                run.push(stmt);
+            } else {
+                self.flush(&mut run);
+                self.keep(stmt);
+            }
+        }
+        self.flush(&mut run);
+    }
+
+    /// Turns a run of convertible statements into one code action, unless it's only comments.
+    fn flush(&mut self, run: &mut Vec<&Stmt>) {
+        if run.iter().all(|s| matches!(s, Stmt::Comment(_))) {
+            for stmt in run.drain(..) {
+                self.keep(stmt);
+            }
+        } else {
+            let mut gml = Gml::default();
+            for stmt in run.drain(..) {
+                gml.stmt(self.actions, stmt);
+            }
+            self.out.push(Planned::Code(gml.finish()));
+        }
+    }
+
+    /// Keeps a statement's own actions, converting what it applies to where possible.
+    fn keep(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Comment(i) | Stmt::Action(i) => self.out.push(Planned::Keep(*i)),
+            Stmt::Block { begin, body, end } => {
+                self.out.push(Planned::Keep(*begin));
+                self.list(body);
+                self.out.extend(end.map(Planned::Keep));
+            },
+            Stmt::If { test, then, otherwise } => {
+                self.out.push(Planned::Keep(*test));
+                self.single(then);
+                if let Some((else_action, otherwise)) = otherwise {
+                    self.out.push(Planned::Keep(*else_action));
+                    self.single(otherwise);
+                }
+            },
+            Stmt::Repeat { repeat, body } => {
+                self.out.push(Planned::Keep(*repeat));
+                self.single(body);
+            },
+        }
+    }
+
+    /// Handles what a kept question or repeat applies to. A code action counts as one action there,
+    /// so the statement after any comments becomes one if it can, while the comments stay as they are.
+    fn single(&mut self, stmts: &[Stmt]) {
+        for stmt in stmts {
+            match stmt {
+                Stmt::Comment(_) => self.keep(stmt),
+                _ if self.convertible(stmt) => {
+                    let mut gml = Gml::default();
+                    gml.stmt(self.actions, stmt);
+                    self.out.push(Planned::Code(gml.finish()));
+                },
+                _ => self.keep(stmt),
+            }
+        }
+    }
+
+    fn convertible(&self, stmt: &Stmt) -> bool {
+        let all = |stmts: &[Stmt]| stmts.iter().all(|s| self.convertible(s));
+        match stmt {
+            Stmt::Comment(i) => {
+                let action = &self.actions[*i];
+                action.lib_id == LIB_ID && action.id == COMMENT_ID
+            },
+            Stmt::Action(i) => self.action_convertible(&self.actions[*i]),
+            Stmt::Block { begin, body, end } => {
+                self.actions[*begin].lib_id == LIB_ID
+                    && end.map_or(true, |i| self.actions[i].lib_id == LIB_ID)
+                    && all(body)
+            },
+            Stmt::If { test, then, otherwise } => {
+                let test = &self.actions[*test];
+                test.execution_type == execution_type::FUNCTION
+                    && self.action_convertible(test)
+                    && all(then)
+                    && otherwise.as_ref().map_or(true, |(i, body)| self.actions[*i].lib_id == LIB_ID && all(body))
+            },
+            Stmt::Repeat { repeat, body } => self.actions[*repeat].lib_id == LIB_ID && all(body),
+        }
+    }
+
+    fn action_convertible(&self, action: &CodeAction) -> bool {
+        if action.lib_id != LIB_ID || target(action).is_err() {
+            return false
+        }
+        match action.action_kind {
+            kind::NORMAL => {
+                action.execution_type == execution_type::FUNCTION
+                    && (!action.is_relative || RELATIVE.iter().any(|(name, _)| name.as_bytes() == &*action.fn_name.0))
+            },
+            kind::VARIABLE => true,
+            // var, exit and return would act differently alongside other code
+            kind::CODE => {
+                !action.is_relative
+                    && !Lexer::new(&action.param_strings[0].0)
+                        .any(|t| matches!(t, Token::Keyword(Keyword::Var | Keyword::Exit | Keyword::Return)))
+            },
+            kind::EXIT => self.allow_exit,
+            _ => false,
+        }
+    }
+}
+
+/// What to put in a `with` for an action, `Ok(None)` if it runs on `self`,
+/// or `Err(())` if it's something `with` can't express.
+fn target(action: &CodeAction) -> Result<Option<String>, ()> {
+    match action.applies_to {
+        _ if !action.applies_to_something => Ok(None),
+        SELF => Ok(None),
+        OTHER => Ok(Some("other".into())),
+        id if id >= 0 => Ok(Some(id.to_string())),
+        _ => Err(()),
+    }
+}
+
+/// A parameter written as GML, either a string literal or an expression.
+fn argument(action: &CodeAction, index: usize) -> Vec<u8> {
+    let param = &action.param_strings[index].0;
+    match action.param_types[index] {
+        1 | 2 => string_literal(param),
+        _ => expression(param),
+    }
+}
+
+fn expression(param: &[u8]) -> Vec<u8> {
+    if param.iter().all(u8::is_ascii_whitespace) { b"0".to_vec() } else { param.to_vec() }
+}
+
+/// Wraps an expression in brackets unless it's a single token.
+fn group(expr: &[u8]) -> Vec<u8> {
+    if Lexer::new(expr).nth(1).is_none() { expr.to_vec() } else { [b"(", expr, b")"].concat() }
+}
+
+/// GML has no escapes, so a string containing both kinds of quote is joined from pieces.
+fn string_literal(s: &[u8]) -> Vec<u8> {
+    if !s.contains(&b'"') {
+        [b"\"", s, b"\""].concat()
+    } else if !s.contains(&b'\'') {
+        [b"'", s, b"'"].concat()
+    } else {
+        let pieces = s.split(|b| *b == b'"').map(|p| [b"\"", p, b"\""].concat()).collect::<Vec<_>>();
+        pieces.join(&b" + '\"' + "[..])
+    }
+}
+
+/// A call to an action's function, with "relative" worked into the arguments.
+fn call(action: &CodeAction) -> Vec<u8> {
+    let count = action.param_count.min(PARAM_COUNT);
+    let mut args = (0..count).map(|i| argument(action, i)).collect::<Vec<_>>();
+    let mut name = action.fn_name.0.to_vec();
+    if action.is_relative {
+        match RELATIVE.iter().find(|(n, _)| n.as_bytes() == &*name).map(|(_, r)| r) {
+            Some(Relative::Add(offsets)) => {
+                let raw = args.clone();
+                for &(index, base) in offsets.iter().filter(|(index, _)| *index < count) {
+                    let mut base = base.to_string();
+                    for (j, arg) in raw.iter().enumerate() {
+                        base = base.replace(&format!("{{{}}}", j), &String::from_utf8_lossy(arg));
+                    }
+                    args[index] = [base.as_bytes(), b" + ", &group(&raw[index])].concat();
+                }
+            },
+            Some(Relative::Call(other)) => name = other.as_bytes().to_vec(),
+            None => (),
+        }
+    }
+    [name, b"(".to_vec(), args.join(&b", "[..]), b")".to_vec()].concat()
+}
+
+/// GML being written out, one line at a time.
+#[derive(Default)]
+struct Gml {
+    lines: Vec<Vec<u8>>,
+    depth: usize,
+    uses_result: bool,
+}
+
+impl Gml {
+    fn line(&mut self, text: &[u8]) {
+        let mut line = if text.is_empty() { Vec::new() } else { b"    ".repeat(self.depth) };
+        line.extend_from_slice(text);
+        self.lines.push(line);
+    }
+
+    fn open(&mut self, text: &[u8]) {
+        self.line(&[text, b" {"].concat());
+        self.depth += 1;
+    }
+
+    fn close(&mut self) {
+        self.depth -= 1;
+        self.line(b"}");
+    }
+
+    /// Writes a statement for an action, wrapped in `with` if it applies to another instance.
+    fn targeted(&mut self, action: &CodeAction, stmt: &[u8]) {
+        match target(action).ok().flatten() {
+            Some(target) => self.line(&[format!("with ({}) ", target).as_bytes(), stmt].concat()),
+            None => self.line(stmt),
+        }
+    }
+
+    fn body(&mut self, actions: &[CodeAction], stmts: &[Stmt]) {
+        for stmt in stmts {
+            self.stmt(actions, stmt);
+        }
+    }
+
+    fn stmt(&mut self, actions: &[CodeAction], stmt: &Stmt) {
+        match stmt {
+            Stmt::Comment(i) => {
+                let text = actions[*i].param_strings[0].0.iter().map(|b| if *b < b' ' { b' ' } else { *b });
+                self.line(&b"// ".iter().copied().chain(text).collect::<Vec<_>>());
+            },
+            Stmt::Action(i) => self.action(&actions[*i]),
+            Stmt::Block { body, .. } => self.body(actions, body),
+            Stmt::If { test, then, otherwise } => {
+                let test = &actions[*test];
+                let not = if test.invert_condition { "!" } else { "" };
+                let condition = match target(test).ok().flatten() {
+                    Some(target) => {
+                        self.uses_result = true;
+                        self.line(format!("{} = 0;", RESULT).as_bytes());
+                        self.line(&[format!("with ({}) {} = ", target, RESULT).as_bytes(), &call(test), b";"].concat());
+                        format!("{}{}", not, RESULT).into_bytes()
+                    },
+                    None => [not.as_bytes(), &call(test)].concat(),
+                };
+                self.open(&[b"if (", &condition[..], b")"].concat());
+                self.body(actions, then);
+                if let Some((_, otherwise)) = otherwise {
+                    self.depth -= 1;
+                    self.open(b"} else");
+                    self.body(actions, otherwise);
+                }
+                self.close();
+            },
+            Stmt::Repeat { repeat, body } => {
+                let count = expression(&actions[*repeat].param_strings[0].0);
+                self.open(&[b"repeat (", &count[..], b")"].concat());
+                self.body(actions, body);
+                self.close();
+            },
+        }
+    }
+
+    fn action(&mut self, action: &CodeAction) {
+        match action.action_kind {
+            kind::VARIABLE => {
+                let op: &[u8] = if action.is_relative { b" += " } else { b" = " };
+                let value = expression(&action.param_strings[1].0);
+                self.targeted(action, &[&action.param_strings[0].0[..], op, &value, b";"].concat());
+            },
+            kind::CODE => {
+                let code = &action.param_strings[0].0;
+                let code = code.strip_suffix(b"\r\n").or_else(|| code.strip_suffix(b"\n")).unwrap_or(code);
+                let target = target(action).ok().flatten();
+                if let Some(target) = &target {
+                    self.open(format!("with ({})", target).as_bytes());
+                }
+                for line in code.split(|b| *b == b'\n') {
+                    self.line(line.strip_suffix(b"\r").unwrap_or(line));
+                }
+                if target.is_some() {
+                    self.close();
+                }
+            },
+            kind::EXIT => self.line(b"exit;"),
+            _ => self.targeted(action, &[&call(action)[..], b";"].concat()),
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut lines = self.lines;
+        if self.uses_result {
+            lines.insert(0, format!("var {};", RESULT).into_bytes());
+        }
+        lines.join(&b"\r\n"[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(name: &str, args: &[&str]) -> CodeAction {
+        let mut action = code_action(PascalString::default());
+        action.id = 1;
+        action.action_kind = kind::NORMAL;
+        action.execution_type = execution_type::FUNCTION;
+        action.fn_name = name.into();
+        action.param_count = args.len();
+        action.param_types = [0; PARAM_COUNT];
+        for (param, arg) in action.param_strings.iter_mut().zip(args) {
+            *param = (*arg).into();
+        }
+        action
+    }
+
+    fn question(name: &str, args: &[&str]) -> CodeAction {
+        CodeAction { is_condition: true, ..function(name, args) }
+    }
+
+    fn relative(action: CodeAction) -> CodeAction {
+        CodeAction { is_relative: true, ..action }
+    }
+
+    fn applies_to(applies_to: i32, action: CodeAction) -> CodeAction {
+        CodeAction { applies_to, ..action }
+    }
+
+    fn not(action: CodeAction) -> CodeAction {
+        CodeAction { invert_condition: true, ..action }
+    }
+
+    fn variable(name: &str, value: &str) -> CodeAction {
+        CodeAction { action_kind: kind::VARIABLE, execution_type: execution_type::NONE, ..function("", &[name, value]) }
+    }
+
+    fn marker(action_kind: u32, args: &[&str]) -> CodeAction {
+        CodeAction { action_kind, execution_type: execution_type::NONE, ..function("", args) }
+    }
+
+    fn comment(text: &str) -> CodeAction {
+        CodeAction { id: COMMENT_ID, ..marker(kind::NORMAL, &[text]) }
+    }
+
+    fn custom(name: &str) -> CodeAction {
+        CodeAction { lib_id: 2, ..function(name, &[]) }
+    }
+
+    /// The code of each action that's been converted, and the function name or kind of the rest.
+    fn describe(actions: &[CodeAction]) -> Vec<String> {
+        actions
+            .iter()
+            .map(|a| match (a.id, a.action_kind) {
+                (603, kind::CODE) => String::from_utf8(a.param_strings[0].0.to_vec()).unwrap(),
+                _ if a.fn_name.0.is_empty() => format!("<kind {}>", a.action_kind),
+                _ => format!("<{}>", a.fn_name),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn relative_arguments() {
+        let mut actions = vec![
+            relative(function("action_set_hspeed", &["4"])),
+            relative(function("action_set_alarm", &["30", "1"])),
+            relative(function("action_move_to", &["a + b", "y2"])),
+            relative(function("action_set_motion", &["90", "4"])),
+            relative(variable("hp", "1")),
+            function("action_set_score", &[" "]),
+        ];
+        assert_eq!(convert(&mut actions), 6);
+        assert_eq!(describe(&actions), [concat!(
+            "action_set_hspeed(hspeed + 4);\r\n",
+            "action_set_alarm(alarm[1] + 30, 1);\r\n",
+            "action_move_to(x + (a + b), y + y2);\r\n",
+            "motion_add(90, 4);\r\n",
+            "hp += 1;\r\n",
+            "action_set_score(0);",
+        )]);
+
+        // without a way to express "relative", the action stays as it is
+        let mut actions = vec![relative(function("action_sound", &["snd", "0"]))];
+        assert_eq!(convert(&mut actions), 0);
+        assert_eq!(describe(&actions), ["<action_sound>"]);
+    }
+
+    #[test]
+    fn targets_and_not() {
+        let mut actions = vec![
+            not(applies_to(OTHER, question("action_if_variable", &["hp", "0", "1"]))),
+            applies_to(3, variable("hp", "3")),
+            not(question("action_if_dice", &["2"])),
+            applies_to(OTHER, function("action_kill_object", &[])),
+        ];
+        assert_eq!(convert(&mut actions), 4);
+        assert_eq!(describe(&actions), [concat!(
+            "var __dnd;\r\n",
+            "__dnd = 0;\r\n",
+            "with (other) __dnd = action_if_variable(hp, 0, 1);\r\n",
+            "if (!__dnd) {\r\n",
+            "    with (3) hp = 3;\r\n",
+            "}\r\n",
+            "if (!action_if_dice(2)) {\r\n",
+            "    with (other) action_kill_object();\r\n",
+            "}",
+        )]);
+
+        // `all` can't be put in a with that returns a single result
+        let mut actions = vec![applies_to(-3, question("action_if_dice", &["2"])), variable("x", "0")];
+        assert_eq!(convert(&mut actions), 1);
+        assert_eq!(describe(&actions), ["<action_if_dice>", "x = 0;"]);
+    }
+
+    #[test]
+    fn nesting() {
+        let mut actions = vec![
+            question("action_if_dice", &["2"]),
+            marker(kind::BEGIN_GROUP, &[]),
+            marker(kind::REPEAT, &["3"]),
+            function("action_sound", &["snd", "0"]),
+            marker(kind::END_GROUP, &[]),
+            comment("otherwise"),
+            marker(kind::ELSE, &[]),
+            relative(variable("lives", "1")),
+            comment("done"),
+        ];
+        assert_eq!(convert(&mut actions), 9);
+        assert_eq!(describe(&actions), [concat!(
+            "if (action_if_dice(2)) {\r\n",
+            "    repeat (3) {\r\n",
+            "        action_sound(snd, 0);\r\n",
+            "    }\r\n",
+            "    // otherwise\r\n",
+            "} else {\r\n",
+            "    lives += 1;\r\n",
+            "}\r\n",
+            "// done",
+        )]);
+    }
+
+    #[test]
+    fn exit() {
+        let mut actions = vec![function("action_kill_object", &[]), marker(kind::EXIT, &[])];
+        assert_eq!(convert(&mut actions), 2);
+        assert_eq!(describe(&actions), ["action_kill_object();\r\nexit;"]);
+
+        // exit in a code action that doesn't cover the whole list would only leave that action
+        let mut actions = vec![function("action_kill_object", &[]), marker(kind::EXIT, &[]), custom("ext_fn")];
+        assert_eq!(convert(&mut actions), 1);
+        assert_eq!(describe(&actions), ["action_kill_object();", "<kind 4>", "<ext_fn>"]);
+
+        // and code using exit can't be merged with anything
+        let mut actions = vec![code_action("exit;".into()), function("action_kill_object", &[])];
+        assert_eq!(convert(&mut actions), 1);
+        assert_eq!(describe(&actions), ["exit;", "action_kill_object();"]);
+    }
+
+    #[test]
+    fn custom_library() {
+        let mut actions = vec![
+            CodeAction { is_condition: true, ..custom("ext_if") },
+            function("action_kill_object", &[]),
+            marker(kind::ELSE, &[]),
+            variable("x", "1"),
+            custom("ext_fn"),
+            comment("only comments"),
+        ];
+        actions[4].param_strings[0] = "kept as is".into();
+        assert_eq!(convert(&mut actions), 2);
+        assert_eq!(describe(&actions), [
+            "<ext_if>",
+            "action_kill_object();",
+            "<kind 3>",
+            "x = 1;",
+            "<ext_fn>",
+            "<kind 0>",
+        ]);
+        assert_eq!((actions[4].lib_id, &*actions[4].param_strings[0].0), (2, &b"kept as is"[..]));
+        assert_eq!(actions[5].id, COMMENT_ID);
+
+        let mut actions = vec![custom("ext_fn"), comment("note")];
+        assert_eq!(convert(&mut actions), 0);
+    }
+}
@@ -3,7 +3,9 @@ use byteorder::{WriteBytesExt, LE};
 use flate2::{write::ZlibEncoder, Compression};
 use gm8exe::{
     asset::{self, included_file::ExportSetting, PascalString, WritePascalString},
+    rsrc::VersionInfo,
     settings::{GameHelpDialog, Settings},
+    tree::{self, NodeKind},
     GameAssets, GameVersion,
 };
 use rayon::prelude::*;
@@ -48,6 +50,7 @@ pub fn write_settings<W>(
     writer: &mut W,
     settings: &Settings,
     ico_file: Option<Vec<u8>>,
+    version_info: Option<&VersionInfo>,
     version: GameVersion,
 ) -> io::Result<()>
 where
@@ -155,15 +158,17 @@ where
     write_timestamp(&mut enc)?; // timestamp
     enc.write_pas_string(&"".into())?; // information
 
-    // TODO: extract all this stuff from .rsrc in gm8x
-    enc.write_u32::<LE>(1)?; // major version
-    enc.write_u32::<LE>(0)?; // minor version
-    enc.write_u32::<LE>(0)?; // release version
-    enc.write_u32::<LE>(0)?; // build version
-    enc.write_pas_string(&"".into())?; // company
-    enc.write_pas_string(&"".into())?; // product
-    enc.write_pas_string(&"".into())?; // copyright info
-    enc.write_pas_string(&"".into())?; // description
+    // GameMaker's defaults if the exe didn't have any version info
+    let default_info = VersionInfo { major: 1, ..Default::default() };
+    let info = version_info.unwrap_or(&default_info);
+    enc.write_u32::<LE>(info.major)?;
+    enc.write_u32::<LE>(info.minor)?;
+    enc.write_u32::<LE>(info.release)?;
+    enc.write_u32::<LE>(info.build)?;
+    enc.write_pas_string(&info.company)?;
+    enc.write_pas_string(&info.product)?;
+    enc.write_pas_string(&info.copyright)?;
+    enc.write_pas_string(&info.description)?;
     write_timestamp(&mut enc)?; // timestamp
 
     enc.finish(writer)?;
@@ -624,22 +629,23 @@ where
         writer.write_u32::<LE>(false as u32)?; // locked in editor
     }
 
-    // All these settings are 0/false by default when creating a new room in the IDE.
-    // Commented with Zach's names for the variables, I haven't verified what they do
-    writer.write_u32::<LE>(false as u32)?; // remember room editor info
-    writer.write_u32::<LE>(0)?; // editor width
-    writer.write_u32::<LE>(0)?; // editor height
-    writer.write_u32::<LE>(false as u32)?; // show grid
-    writer.write_u32::<LE>(false as u32)?; // show objects
-    writer.write_u32::<LE>(false as u32)?; // show tiles
-    writer.write_u32::<LE>(false as u32)?; // show backgrounds
-    writer.write_u32::<LE>(false as u32)?; // show foregrounds
-    writer.write_u32::<LE>(false as u32)?; // show views
-    writer.write_u32::<LE>(false as u32)?; // delete underlying objects
-    writer.write_u32::<LE>(false as u32)?; // delete underlying tiles
-    writer.write_u32::<LE>(0)?; // tab
-    writer.write_u32::<LE>(0)?; // x position scroll
-    writer.write_u32::<LE>(0)?; // y position scroll
+    // All these settings are 0/false by default when creating a new room in the IDE,
+    // which is what rooms from an exe get
+    let editor = room.editor_settings.unwrap_or_default();
+    writer.write_u32::<LE>(editor.remember.into())?;
+    writer.write_u32::<LE>(editor.width)?;
+    writer.write_u32::<LE>(editor.height)?;
+    writer.write_u32::<LE>(editor.show_grid.into())?;
+    writer.write_u32::<LE>(editor.show_objects.into())?;
+    writer.write_u32::<LE>(editor.show_tiles.into())?;
+    writer.write_u32::<LE>(editor.show_backgrounds.into())?;
+    writer.write_u32::<LE>(editor.show_foregrounds.into())?;
+    writer.write_u32::<LE>(editor.show_views.into())?;
+    writer.write_u32::<LE>(editor.delete_underlying_objects.into())?;
+    writer.write_u32::<LE>(editor.delete_underlying_tiles.into())?;
+    writer.write_u32::<LE>(editor.tab)?;
+    writer.write_u32::<LE>(editor.scroll_x)?;
+    writer.write_u32::<LE>(editor.scroll_y)?;
 
     Ok(())
 }
@@ -812,6 +818,47 @@ where
         iter.into_iter().enumerate().filter_map(|(i, opt)| opt.as_ref().map(|x| (i, x)))
     }
 
+    /// Finds the name of the asset a resource node refers to.
+    fn asset_name(assets: &GameAssets, group: u32, index: usize) -> Option<&PascalString> {
+        fn get<T>(list: &[Option<Box<T>>], index: usize) -> Option<&T> {
+            list.get(index)?.as_deref()
+        }
+        match group {
+            1 => get(&assets.objects, index).map(|x| &x.name),
+            2 => get(&assets.sprites, index).map(|x| &x.name),
+            3 => get(&assets.sounds, index).map(|x| &x.name),
+            4 => get(&assets.rooms, index).map(|x| &x.name),
+            6 => get(&assets.backgrounds, index).map(|x| &x.name),
+            7 => get(&assets.scripts, index).map(|x| &x.name),
+            8 => get(&assets.paths, index).map(|x| &x.name),
+            9 => get(&assets.fonts, index).map(|x| &x.name),
+            12 => get(&assets.timelines, index).map(|x| &x.name),
+            _ => None,
+        }
+    }
+
+    /// Writes a node kept from a project file. Resources get the asset's current name, in case it was renamed.
+    fn write_rt_node<W>(writer: &mut W, assets: &GameAssets, node: &tree::Node) -> io::Result<()>
+    where
+        W: io::Write,
+    {
+        let name = match node.kind {
+            NodeKind::Resource => asset_name(assets, node.group, node.index as usize).unwrap_or(&node.name),
+            NodeKind::Root | NodeKind::Group => &node.name,
+        };
+        writer.write_u32::<LE>(node.kind as u32)?;
+        writer.write_u32::<LE>(node.group)?;
+        writer.write_u32::<LE>(node.index)?;
+        writer.write_pas_string(name)?;
+        writer.write_u32::<LE>(node.children.len() as u32)?;
+        node.children.iter().try_for_each(|child| write_rt_node(writer, assets, child))
+    }
+
+    // Projects keep their own tree, folders and all
+    if !assets.resource_tree.is_empty() {
+        return assets.resource_tree.iter().try_for_each(|node| write_rt_node(writer, assets, node))
+    }
+
     write_rt_heading(writer, "Sprites", 2, count_existing(&assets.sprites))?;
     for (i, sprite) in enumerate_existing(&assets.sprites) {
         write_rt_asset(writer, &sprite.name, 2, i as u32)?;
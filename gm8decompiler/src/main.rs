@@ -1,4 +1,4 @@
-use gm8exe::GameVersion;
+use gm8exe::{GameAssets, GameVersion};
 use std::{
     env, fs,
     path::{Path, PathBuf},
@@ -7,8 +7,10 @@ use std::{
 
 pub mod collision;
 pub mod deobfuscate;
+pub mod dnd;
 pub mod gmk;
 pub mod mappings;
+pub mod project;
 pub mod zlib;
 
 static INFO_STRING: &str = concat!(
@@ -22,6 +24,20 @@ static INFO_STRING: &str = concat!(
     env!("GIT_HASH"),
 );
 
+/// What to write a game as.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Output {
+    /// A gmk or gm81 file to open in GameMaker.
+    Gmk,
+    /// A project folder, see `project`.
+    Project,
+    /// Images, sounds and other files, see `gm8exe::extract`.
+    Media,
+    /// A summary of what was read from the game, see `gm8exe::inspect`.
+    #[cfg(feature = "inspect")]
+    Json { payloads: bool },
+}
+
 // Know to "press any key" but only if double-clicked in WinExplorer or whatever.
 #[cfg(windows)]
 fn is_cmd(argv_0: &str) -> bool {
@@ -63,8 +79,15 @@ fn main() {
         .optflag("v", "verbose", "enable verbose logging for decompilation")
         .optopt("d", "deobfuscate", "set deobfuscation mode auto/on/off (default=auto)", "")
         .optflag("p", "preserve", "preserve broken events (instead of trying to fix them)")
+        .optflag("g", "gml", "convert built-in drag-and-drop actions to GML code")
         .optflag("s", "singlethread", "decompile gamedata synchronously (lower RAM usage)")
+        .optflag("r", "recover", "skip assets that can't be read instead of stopping")
+        .optflag("f", "folder", "write a project folder instead of a gmk file")
+        .optopt("x", "extract", "extract sprites, sounds and other media into a folder instead", "DIR")
         .optopt("o", "output", "specify output filename", "FILE");
+    #[cfg(feature = "inspect")]
+    opts.optopt("j", "json", "write what was found in the game as JSON instead", "FILE")
+        .optflag("", "payloads", "include image, sound and file contents in the JSON");
 
     // parse command line arguments
     let matches = match opts.parse(&args[1..]) {
@@ -93,15 +116,28 @@ fn main() {
         println!(
             "Usage: {} FILENAME [options]
 
+FILENAME can also be a project folder written with -f, which gets turned back into a gmk file.
+
 Options:
     -h, --help                print this help message
     -l, --lazy                disable various data integrity checks
     -v, --verbose             enable verbose logging for decompilation
     -d, --deobfuscate <mode>  set deobfuscation mode auto/on/off (defaults to auto)
     -p, --preserve            preserve broken events (instead of trying to fix them)
+    -g, --gml                 convert built-in drag-and-drop actions to GML code
     -s, --singlethread        decompile gamedata synchronously (lower RAM usage)
-    -o, --output <file>       specify output filename",
-            process_path
+    -r, --recover             skip assets that can't be read instead of stopping
+    -f, --folder              write a project folder instead of a gmk file
+    -x, --extract <dir>       extract sprites, sounds and other media into a folder instead
+{}    -o, --output <file>       specify output filename",
+            process_path,
+            if cfg!(feature = "inspect") {
+                "    -j, --json <file>         write what was found in the game as JSON instead
+        --payloads            include image, sound and file contents in the JSON
+"
+            } else {
+                ""
+            },
         );
         if should_pause {
             pause(true);
@@ -125,6 +161,7 @@ Options:
     let input = &matches.free[0];
     let lazy = matches.opt_present("l");
     let singlethread = matches.opt_present("s");
+    let recover = matches.opt_present("r");
     let verbose = matches.opt_present("v");
     let deobfuscate = match matches.opt_str("d").as_deref() {
         Some("on") => deobfuscate::Mode::On,
@@ -137,6 +174,27 @@ Options:
     };
     let out_path = matches.opt_str("o");
     let preserve = matches.opt_present("p");
+    let gml = matches.opt_present("g");
+    let extract_dir = matches.opt_str("x");
+    #[cfg(feature = "inspect")]
+    let json_path = matches.opt_str("j");
+    #[cfg(not(feature = "inspect"))]
+    let json_path: Option<String> = None;
+    let output = match (matches.opt_present("f"), &extract_dir, &json_path) {
+        (false, None, None) => Output::Gmk,
+        (true, None, None) => Output::Project,
+        (false, Some(_), None) if out_path.is_none() => Output::Media,
+        #[cfg(feature = "inspect")]
+        (false, None, Some(_)) if out_path.is_none() => Output::Json { payloads: matches.opt_present("payloads") },
+        _ => {
+            eprintln!("Only one of -o, -f, -x and -j can be used at a time");
+            if should_pause {
+                pause(true);
+            }
+            process::exit(1);
+        },
+    };
+    let out_path = extract_dir.or(json_path).or(out_path);
     // no_pause extracted before help
 
     // print flags for confirmation
@@ -155,49 +213,97 @@ Options:
     if singlethread {
         println!("Single-threaded mode ON: process will not start new threads (slow)");
     }
+    if recover {
+        println!("Recovery mode ON: assets that can't be read will be skipped");
+    }
     if let Some(path) = &out_path {
         println!("Specified output path: {}", path);
     }
     if preserve {
         println!("Preserve mode ON: broken events will be preserved and will not be fixed");
     }
+    if gml {
+        println!("GML mode ON: built-in drag-and-drop actions will be converted to code");
+    }
+    match output {
+        Output::Project => println!("Folder mode ON: will write a project folder instead of a gmk file"),
+        Output::Media => println!("Extract mode ON: will extract media instead of writing a gmk file"),
+        #[cfg(feature = "inspect")]
+        Output::Json { .. } => println!("JSON mode ON: will write a JSON summary instead of a gmk file"),
+        Output::Gmk => (),
+    }
 
     // resolve input path
     let input_path = Path::new(input);
-    if !input_path.is_file() {
+    if input_path.is_dir() {
+        // a project folder from -f, to turn back into a gmk
+        if let Err(e) = rebuild(input_path, out_path, !singlethread) {
+            eprintln!("Error rebuilding project:\n{}", e);
+            process::exit(1);
+        }
+    } else if input_path.is_file() {
+        // allow decompile to handle the rest of main
+        let result = decompile(
+            input_path,
+            out_path,
+            !lazy,
+            !singlethread,
+            recover,
+            verbose,
+            deobfuscate,
+            !preserve,
+            gml,
+            output,
+        );
+        if let Err(e) = result {
+            eprintln!("Error parsing gamedata:\n{}", e);
+            process::exit(1);
+        }
+    } else {
         eprintln!("Input file '{}' does not exist.", input);
         process::exit(1);
     }
 
-    // allow decompile to handle the rest of main
-    if let Err(e) = decompile(input_path, out_path, !lazy, !singlethread, verbose, deobfuscate, !preserve) {
-        eprintln!("Error parsing gamedata:\n{}", e);
-        process::exit(1);
-    }
-
     if should_pause {
         pause(false);
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn decompile(
     in_path: &Path,
     out_path: Option<String>,
     strict: bool,
     multithread: bool,
+    recover: bool,
     verbose: bool,
     deobf_mode: deobfuscate::Mode,
     fix_events: bool,
+    convert_dnd: bool,
+    output: Output,
 ) -> Result<(), String> {
     // slurp in file contents
     let file = fs::read(&in_path).map_err(|e| format!("Failed to read '{}': {}", in_path.display(), e))?;
 
     // parse (entire) gamedata
     let logger = if verbose { Some(|msg: &str| println!("{}", msg)) } else { None };
-    let mut assets = gm8exe::reader::from_exe(file, logger, strict, multithread) // huge call
+    let mut assets = gm8exe::reader::from_exe(file, logger, strict, multithread, recover) // huge call
         .map_err(|e| format!("Reader error: {}", e))?;
 
     println!("Successfully parsed game!");
+    for warning in &assets.warnings {
+        println!("***WARNING*** Skipped an asset that couldn't be read: {}", warning);
+    }
+
+    // the summary is of the game as it was read, so it's written before anything below changes it
+    #[cfg(feature = "inspect")]
+    if let (Output::Json { payloads }, Some(path)) = (output, &out_path) {
+        println!("Writing JSON...");
+        fs::write(path, gm8exe::inspect::to_json(&assets, payloads))
+            .map_err(|e| format!("Failed to write '{}': {}", path, e))?;
+        println!("Successfully written JSON to '{}'", path);
+        return Ok(())
+    }
 
     //Do we want to deobfuscate, yes or no?
     let deobfuscate = match deobf_mode {
@@ -252,6 +358,7 @@ fn decompile(
         GameVersion::GameMaker8_1 => "gm81",
     };
     let out_path = match out_path {
+        Some(p) if output != Output::Gmk => PathBuf::from(p),
         Some(p) => {
             let path = PathBuf::from(p);
             match (assets.version, path.extension().and_then(|oss| oss.to_str())) {
@@ -279,7 +386,7 @@ fn decompile(
         },
         None => {
             let mut path = PathBuf::from(in_path);
-            path.set_extension(out_expected_ext);
+            path.set_extension(if output == Output::Gmk { out_expected_ext } else { "" });
             path
         },
     };
@@ -288,7 +395,54 @@ fn decompile(
         deobfuscate::process(&mut assets);
     }
 
-    let mut gmk = fs::File::create(&out_path)
+    if convert_dnd {
+        let count = dnd::process(&mut assets);
+        println!("Converted {} drag-and-drop actions to code", count);
+    }
+
+    match output {
+        Output::Gmk => write_gmk(assets, &out_path, multithread),
+        Output::Project => {
+            println!("Writing project folder...");
+            project::export(&assets, &out_path)?;
+            println!("Successfully written project folder to '{}'", out_path.display());
+            Ok(())
+        },
+        Output::Media => {
+            println!("Extracting media...");
+            gm8exe::extract::extract_to_dir(&assets, &out_path)
+                .map_err(|e| format!("Failed to extract to '{}': {}", out_path.display(), e))?;
+            println!("Successfully extracted media to '{}'", out_path.display());
+            Ok(())
+        },
+        #[cfg(feature = "inspect")]
+        Output::Json { .. } => unreachable!("JSON is written straight after reading"),
+    }
+}
+
+fn rebuild(in_path: &Path, out_path: Option<String>, multithread: bool) -> Result<(), String> {
+    println!("Reading project folder...");
+    let assets = project::import(in_path)?;
+    let out_path = match out_path {
+        Some(p) => PathBuf::from(p),
+        None => {
+            let mut path = PathBuf::from(in_path);
+            path.set_extension(match assets.version {
+                GameVersion::GameMaker8_0 => "gmk",
+                GameVersion::GameMaker8_1 => "gm81",
+            });
+            path
+        },
+    };
+    write_gmk(assets, &out_path, multithread)
+}
+
+fn write_gmk(mut assets: GameAssets, out_path: &Path, multithread: bool) -> Result<(), String> {
+    let out_expected_ext = match assets.version {
+        GameVersion::GameMaker8_0 => "gmk",
+        GameVersion::GameMaker8_1 => "gm81",
+    };
+    let mut gmk = fs::File::create(out_path)
         .map_err(|e| format!("Failed to create output file '{}': {}", out_path.display(), e))?;
 
     println!("Writing {} header...", out_expected_ext);
@@ -297,7 +451,7 @@ fn decompile(
 
     println!("Writing {} settings...", out_expected_ext);
     let ico_file = assets.ico_file_raw.take();
-    gmk::write_settings(&mut gmk, &assets.settings, ico_file, assets.version)
+    gmk::write_settings(&mut gmk, &assets.settings, ico_file, assets.version_info.as_ref(), assets.version)
         .map_err(|e| format!("Failed to write settings block: {}", e))?;
 
     println!("Writing {} triggers...", assets.triggers.len());
@@ -381,3 +535,406 @@ fn decompile(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gm8exe::{
+        asset::{
+            extension, included_file::ExportSetting, path, room, sound, sprite, trigger, Background, Constant,
+            Extension, Font, IncludedFile, Object, PascalString, Path as GmPath, Room, Script, Sound, Sprite, Timeline,
+            Trigger,
+        },
+        rsrc::VersionInfo,
+        settings::{GameHelpDialog, Settings},
+        tree::{self, NodeKind},
+    };
+
+    pub fn text(s: &PascalString) -> &str {
+        std::str::from_utf8(&s.0).unwrap()
+    }
+
+    /// A small game with one of everything the project formats carry.
+    pub fn game(version: GameVersion) -> GameAssets {
+        let frame = sprite::Frame { width: 2, height: 2, data: vec![255; 16].into_boxed_slice() };
+        let collider = sprite::CollisionMap {
+            width: 2,
+            height: 2,
+            bbox_left: 0,
+            bbox_right: 1,
+            bbox_top: 0,
+            bbox_bottom: 1,
+            data: vec![true; 4].into_boxed_slice(),
+        };
+        let mut events: Vec<Vec<_>> = (0..12).map(|_| Vec::new()).collect();
+        events[0].push((0, vec![dnd::code_action("hp = 3;".into())]));
+        GameAssets {
+            triggers: vec![Some(Box::new(Trigger {
+                name: "trg".into(),
+                condition: "return hp <= 0;".into(),
+                moment: trigger::TriggerKind::EndStep,
+                constant_name: "ev_dead".into(),
+            }))],
+            constants: vec![Constant { name: "MAX_HP".into(), expression: "3".into() }],
+            extensions: vec![Extension {
+                name: "ext".into(),
+                folder_name: "ext".into(),
+                files: vec![extension::File {
+                    name: "ext.gml".into(),
+                    kind: extension::FileKind::GmlScript,
+                    initializer: "".into(),
+                    finalizer: "".into(),
+                    functions: Vec::new(),
+                    consts: Vec::new(),
+                    contents: b"#define ext_init\nreturn 1;"[..].into(),
+                }],
+            }],
+            sprites: vec![Some(Box::new(Sprite {
+                name: "spr".into(),
+                origin_x: 1,
+                origin_y: 1,
+                frames: vec![frame],
+                colliders: vec![collider],
+                per_frame_colliders: false,
+            }))],
+            sounds: vec![Some(Box::new(Sound {
+                name: "snd".into(),
+                source: "C:\\snd.wav".into(),
+                extension: ".wav".into(),
+                data: Some(vec![1, 2, 3].into_boxed_slice()),
+                kind: sound::SoundKind::BackgroundMusic,
+                volume: 0.5,
+                pan: -0.25,
+                preload: true,
+                fx: sound::SoundFX { chorus: false, echo: true, flanger: false, gargle: false, reverb: true },
+            }))],
+            backgrounds: vec![
+                None,
+                Some(Box::new(Background {
+                    name: "bg".into(),
+                    width: 1,
+                    height: 1,
+                    data: Some(vec![0, 0, 255, 255].into_boxed_slice()),
+                })),
+            ],
+            paths: vec![Some(Box::new(GmPath {
+                name: "pth".into(),
+                connection: path::ConnectionKind::SmoothCurve,
+                precision: 5,
+                closed: true,
+                points: vec![
+                    path::Point { x: 0.0, y: 0.0, speed: 100.0 },
+                    path::Point { x: 32.5, y: -8.0, speed: 50.0 },
+                ],
+            }))],
+            scripts: vec![Some(Box::new(Script { name: "scr".into(), source: "return argument0 * 2;".into() }))],
+            fonts: vec![Some(Box::new(Font {
+                name: "fnt".into(),
+                sys_name: "Arial".into(),
+                size: 12,
+                bold: true,
+                italic: false,
+                range_start: 32,
+                range_end: 127,
+                charset: 204,
+                aa_level: 3,
+                dmap: Box::new([1; 0x600]),
+                map_width: 1,
+                map_height: 1,
+                pixel_map: vec![255].into_boxed_slice(),
+            }))],
+            timelines: vec![Some(Box::new(Timeline {
+                name: "tml".into(),
+                moments: vec![(30, vec![dnd::code_action("instance_destroy();".into())])],
+            }))],
+            objects: vec![Some(Box::new(Object {
+                name: "obj".into(),
+                sprite_index: 0,
+                solid: true,
+                visible: true,
+                depth: -10,
+                persistent: false,
+                parent_index: -1,
+                mask_index: -1,
+                events,
+            }))],
+            rooms: vec![Some(Box::new(Room {
+                name: "rm".into(),
+                caption: "Room".into(),
+                width: 640,
+                height: 480,
+                speed: 60,
+                persistent: false,
+                bg_colour: 0x808080.into(),
+                clear_screen: true,
+                clear_region: true,
+                creation_code: "score = 0;".into(),
+                backgrounds: Vec::new(),
+                views_enabled: false,
+                views: Vec::new(),
+                instances: vec![room::Instance {
+                    x: 16,
+                    y: 32,
+                    object: 0,
+                    id: 100001,
+                    creation_code: "hp = 1;".into(),
+                    xscale: 2.0,
+                    yscale: 0.5,
+                    blend: 255,
+                    angle: match version {
+                        GameVersion::GameMaker8_0 => 0.0,
+                        GameVersion::GameMaker8_1 => 90.0,
+                    },
+                }],
+                tiles: vec![room::Tile {
+                    x: 0,
+                    y: 0,
+                    source_bg: 1,
+                    tile_x: 0,
+                    tile_y: 0,
+                    width: 1,
+                    height: 1,
+                    depth: 1000000,
+                    id: 10000001,
+                    xscale: 3.0,
+                    yscale: 3.0,
+                    blend: u32::MAX,
+                }],
+                uses_810_features: true,
+                uses_811_features: matches!(version, GameVersion::GameMaker8_1),
+                editor_settings: None,
+            }))],
+            included_files: vec![IncludedFile {
+                file_name: "data.txt".into(),
+                source_path: "C:\\data.txt".into(),
+                data_exists: true,
+                source_length: 3,
+                stored_in_gmk: true,
+                embedded_data: Some(vec![1, 2, 3].into_boxed_slice()),
+                export_settings: ExportSetting::CustomFolder("saves".into()),
+                overwrite_file: false,
+                free_memory: true,
+                remove_at_end: true,
+            }],
+            version,
+            detection: None,
+            warnings: Vec::new(),
+            dx_dll: Vec::new(),
+            ico_file_raw: None,
+            version_info: Some(VersionInfo {
+                major: 1,
+                minor: 2,
+                release: 3,
+                build: 4,
+                company: "OpenGMK".into(),
+                product: "Test".into(),
+                copyright: "".into(),
+                description: "A test game".into(),
+            }),
+            help_dialog: GameHelpDialog {
+                bg_colour: 0xFFFFFF.into(),
+                new_window: false,
+                caption: "Game Information".into(),
+                left: -1,
+                top: -1,
+                width: 600,
+                height: 400,
+                border: true,
+                resizable: true,
+                window_on_top: false,
+                freeze_game: true,
+                info: "Press F1".into(),
+            },
+            last_instance_id: 100001,
+            last_tile_id: 10000001,
+            library_init_strings: vec!["__init();".into()],
+            room_order: vec![0],
+            resource_tree: Vec::new(),
+            settings: Settings {
+                fullscreen: false,
+                scaling: -1,
+                interpolate_pixels: false,
+                clear_colour: 0,
+                allow_resize: true,
+                window_on_top: false,
+                dont_draw_border: false,
+                dont_show_buttons: false,
+                display_cursor: true,
+                freeze_on_lose_focus: false,
+                disable_screensaver: true,
+                force_cpu_render: true,
+                set_resolution: false,
+                colour_depth: 0,
+                resolution: 0,
+                frequency: 0,
+                vsync: false,
+                esc_close_game: true,
+                treat_close_as_esc: true,
+                f1_help_menu: true,
+                f4_fullscreen_toggle: true,
+                f5_save_f6_load: true,
+                f9_screenshot: true,
+                priority: 0,
+                custom_load_image: None,
+                transparent: false,
+                translucency: 255,
+                loading_bar: 1,
+                backdata: None,
+                frontdata: None,
+                scale_progress_bar: true,
+                show_error_messages: true,
+                log_errors: false,
+                always_abort: false,
+                zero_uninitialized_vars: true,
+                error_on_uninitialized_args: false,
+                swap_creation_events: false,
+            },
+            game_id: 123456,
+            guid: [1, 2, 3, 4],
+        }
+    }
+
+    /// Checks the parts of `game` which every project format keeps.
+    pub fn assert_same_game(got: &GameAssets, version: GameVersion) {
+        assert!(matches!(
+            (got.version, version),
+            (GameVersion::GameMaker8_0, GameVersion::GameMaker8_0)
+                | (GameVersion::GameMaker8_1, GameVersion::GameMaker8_1)
+        ));
+        assert_eq!((got.game_id, got.guid), (123456, [1, 2, 3, 4]));
+        assert!(got.settings.allow_resize && got.settings.zero_uninitialized_vars);
+        assert_eq!(text(&got.version_info.as_ref().unwrap().description), "A test game");
+
+        let trigger = got.triggers[0].as_ref().unwrap();
+        assert_eq!((text(&trigger.condition), text(&trigger.constant_name)), ("return hp <= 0;", "ev_dead"));
+        assert!(trigger.moment == trigger::TriggerKind::EndStep);
+        assert_eq!(text(&got.constants[0].expression), "3");
+
+        let sprite = got.sprites[0].as_ref().unwrap();
+        assert_eq!((sprite.origin_x, &*sprite.frames[0].data), (1, &[255; 16][..]));
+        assert_eq!((sprite.colliders[0].bbox_right, sprite.colliders[0].bbox_bottom), (1, 1));
+
+        let sound = got.sounds[0].as_ref().unwrap();
+        assert_eq!((text(&sound.extension), sound.data.as_deref()), (".wav", Some(&[1, 2, 3][..])));
+        assert_eq!((sound.volume, sound.pan, sound.preload), (0.5, -0.25, true));
+        assert!(
+            sound.kind == sound::SoundKind::BackgroundMusic && sound.fx.echo && sound.fx.reverb && !sound.fx.chorus
+        );
+
+        assert!(got.backgrounds[0].is_none());
+        assert_eq!(got.backgrounds[1].as_ref().unwrap().data.as_deref(), Some(&[0, 0, 255, 255][..]));
+
+        let path = got.paths[0].as_ref().unwrap();
+        assert!(path.connection == path::ConnectionKind::SmoothCurve && path.closed);
+        assert_eq!((path.precision, path.points[1].x, path.points[1].y, path.points[1].speed), (5, 32.5, -8.0, 50.0));
+
+        assert_eq!(text(&got.scripts[0].as_ref().unwrap().source), "return argument0 * 2;");
+
+        let font = got.fonts[0].as_ref().unwrap();
+        assert_eq!(
+            (text(&font.sys_name), font.size, font.bold, font.range_start, font.range_end),
+            ("Arial", 12, true, 32, 127)
+        );
+        if let GameVersion::GameMaker8_1 = version {
+            assert_eq!((font.charset, font.aa_level), (204, 3));
+        }
+
+        let timeline = got.timelines[0].as_ref().unwrap();
+        assert_eq!(
+            (timeline.moments[0].0, text(&timeline.moments[0].1[0].param_strings[0])),
+            (30, "instance_destroy();")
+        );
+
+        let object = got.objects[0].as_ref().unwrap();
+        assert_eq!((object.depth, object.solid, object.events.len()), (-10, true, 12));
+        assert_eq!(text(&object.events[0][0].1[0].param_strings[0]), "hp = 3;");
+
+        let room = got.rooms[0].as_ref().unwrap();
+        assert_eq!((text(&room.caption), room.speed, text(&room.creation_code)), ("Room", 60, "score = 0;"));
+        let instance = &room.instances[0];
+        assert_eq!((instance.x, instance.id, text(&instance.creation_code)), (16, 100001, "hp = 1;"));
+        assert_eq!((instance.xscale, instance.yscale, instance.blend), (2.0, 0.5, 255));
+        assert_eq!(instance.angle, if room.uses_811_features { 90.0 } else { 0.0 });
+        assert_eq!((room.tiles[0].id, room.tiles[0].xscale, room.tiles[0].blend), (10000001, 3.0, u32::MAX));
+
+        let file = &got.included_files[0];
+        assert_eq!((text(&file.file_name), file.embedded_data.as_deref()), ("data.txt", Some(&[1, 2, 3][..])));
+        assert!(matches!(&file.export_settings, ExportSetting::CustomFolder(f) if text(f) == "saves"));
+
+        assert_eq!(text(&got.extensions[0].name), "ext");
+        assert_eq!(text(&got.help_dialog.info), "Press F1");
+        assert_eq!((got.last_instance_id, got.last_tile_id), (100001, 10000001));
+        assert_eq!(text(&got.library_init_strings[0]), "__init();");
+        assert_eq!(got.room_order, [0]);
+    }
+
+    #[test]
+    fn gmk_round_trip() {
+        let dir = env::temp_dir().join(format!("gm8decompiler-gmk-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for version in [GameVersion::GameMaker8_0, GameVersion::GameMaker8_1] {
+            let out_path = dir.join("game.gmk");
+            write_gmk(game(version), &out_path, false).unwrap();
+            let gmk = fs::read(&out_path).unwrap();
+            let assets = gm8exe::reader::from_gmk(gmk, None::<fn(&str)>, true, false).unwrap();
+            assert_same_game(&assets, version);
+
+            // project files only reference these
+            let font = assets.fonts[0].as_ref().unwrap();
+            assert!(font.pixel_map.is_empty() && font.map_width == 0);
+            assert!(assets.extensions[0].files.is_empty());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn gmk_keeps_resource_tree_and_editor_settings() {
+        fn node(kind: NodeKind, group: u32, index: u32, name: &str, children: Vec<tree::Node>) -> tree::Node {
+            tree::Node { kind, group, index, name: name.into(), children }
+        }
+        fn shape(node: &tree::Node) -> String {
+            let children = node.children.iter().map(shape).collect::<Vec<_>>().join(",");
+            format!("{}:{}:{}:{}[{}]", node.kind as u32, node.group, node.index, text(&node.name), children)
+        }
+
+        let mut game = game(GameVersion::GameMaker8_0);
+        let editor = room::EditorSettings {
+            remember: true,
+            width: 800,
+            show_grid: true,
+            tab: 2,
+            scroll_y: 64,
+            ..Default::default()
+        };
+        game.rooms[0].as_mut().unwrap().editor_settings = Some(editor);
+        let roots = [(2, "Sprites"), (3, "Sounds"), (6, "Backgrounds"), (8, "Paths"), (7, "Scripts"), (9, "Fonts")]
+            .iter()
+            .chain(&[(12, "Time Lines"), (1, "Objects"), (4, "Rooms")]);
+        game.resource_tree = roots.map(|&(group, name)| node(NodeKind::Root, group, 0, name, Vec::new())).collect();
+        // a sprite two folders deep, under the name it had before it was renamed
+        let attacks = node(NodeKind::Group, 2, 0, "attacks", vec![node(NodeKind::Resource, 2, 0, "old", Vec::new())]);
+        game.resource_tree[0].children.push(node(NodeKind::Group, 2, 0, "player", vec![attacks]));
+        game.resource_tree[0].children.push(node(NodeKind::Group, 2, 0, "empty", Vec::new()));
+        game.resource_tree.push(node(NodeKind::Resource, 10, 0, "Game Information", Vec::new()));
+        game.resource_tree.push(node(NodeKind::Resource, 11, 0, "Global Game Settings", Vec::new()));
+        game.resource_tree.push(node(NodeKind::Resource, 13, 0, "Extension Packages", Vec::new()));
+
+        let dir = env::temp_dir().join(format!("gm8decompiler-tree-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let out_path = dir.join("game.gmk");
+        write_gmk(game, &out_path, false).unwrap();
+        let gmk = fs::read(&out_path).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        let assets = gm8exe::reader::from_gmk(gmk, None::<fn(&str)>, true, false).unwrap();
+
+        let tree = assets.resource_tree.iter().map(shape).collect::<Vec<_>>();
+        assert_eq!(tree.len(), 12);
+        assert_eq!(tree[0], "1:2:0:Sprites[2:2:0:player[2:2:0:attacks[3:2:0:spr[]]],2:2:0:empty[]]");
+        assert_eq!(tree[1], "1:3:0:Sounds[]");
+        assert_eq!(tree[11], "3:13:0:Extension Packages[]");
+
+        let got = assets.rooms[0].as_ref().unwrap().editor_settings.unwrap();
+        assert!(got.remember && got.show_grid && !got.show_objects);
+        assert_eq!((got.width, got.height, got.tab, got.scroll_x, got.scroll_y), (800, 0, 2, 0, 64));
+    }
+}
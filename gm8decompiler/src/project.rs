@@ -0,0 +1,1290 @@
+//! Project folders: a decompiled game written out as a tree of `.gml` code, PNG images and small text files,
+//! so it can be kept in version control and changes reviewed as diffs. `import` reads one back for writing a GMK.
+//!
+//! Assets are listed by id and name in `game.toml`. Each one's files are named after it when the name is usable as
+//! a file name, or `name#id` otherwise, and the importer works the file names out again from the same list.
+
+mod text;
+
+use crate::dnd::code_action;
+use gm8exe::{
+    asset::{
+        self,
+        code_action::PARAM_COUNT,
+        extension::Extension,
+        included_file::ExportSetting,
+        path::{ConnectionKind, Point},
+        room,
+        sound::SoundFX,
+        sprite::{CollisionMap, Frame},
+        CodeAction, PascalString, SoundKind, TriggerKind,
+    },
+    extract::{self, file_stems as stems, sanitize},
+    rsrc::VersionInfo,
+    settings::{GameHelpDialog, Settings},
+    AssetList, GameAssets, GameVersion,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+use text::{Document, Table, Writer};
+
+/// Files and folders `export` creates, which are cleared out first when exporting over an older export.
+const MANAGED: &[&str] = &[
+    "game.toml",
+    "help.rtf",
+    "icon.ico",
+    "constants.toml",
+    "triggers.toml",
+    "included_files.toml",
+    "loading",
+    "library_init",
+    "included_files",
+    "sprites",
+    "sounds",
+    "backgrounds",
+    "paths",
+    "scripts",
+    "fonts",
+    "timelines",
+    "objects",
+    "rooms",
+];
+
+const EVENT_NAMES: [&str; 12] = [
+    "create",
+    "destroy",
+    "alarm",
+    "step",
+    "collision",
+    "keyboard",
+    "mouse",
+    "other",
+    "draw",
+    "keypress",
+    "keyrelease",
+    "trigger",
+];
+
+fn write(path: &Path, data: &[u8]) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create '{}': {}", parent.display(), e))?;
+    }
+    fs::write(path, data).map_err(|e| format!("Failed to write '{}': {}", path.display(), e))
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("Failed to read '{}': {}", path.display(), e))
+}
+
+fn read_toml(path: &Path) -> Result<Document, String> {
+    Document::parse(&read(path)?).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+fn names<T>(list: &AssetList<T>, name: fn(&T) -> &PascalString) -> Vec<Option<&[u8]>> {
+    list.iter().map(|x| x.as_deref().map(|x| name(x).0.as_ref())).collect()
+}
+
+fn asset_name<T>(list: &AssetList<T>, id: i32, name: fn(&T) -> &PascalString) -> &[u8] {
+    usize::try_from(id)
+        .ok()
+        .and_then(|id| list.get(id))
+        .and_then(|x| x.as_deref())
+        .map_or(&[][..], |x| name(x).0.as_ref())
+}
+
+fn object_name(assets: &GameAssets, id: i32) -> &[u8] {
+    asset_name(&assets.objects, id, |x| &x.name)
+}
+
+fn sprite_name(assets: &GameAssets, id: i32) -> &[u8] {
+    asset_name(&assets.sprites, id, |x| &x.name)
+}
+
+fn background_name(assets: &GameAssets, id: i32) -> &[u8] {
+    asset_name(&assets.backgrounds, id, |x| &x.name)
+}
+
+fn write_index(w: &mut Writer, kind: &str, names: &[Option<&[u8]>]) {
+    w.table(kind).int("count", names.len() as i64);
+    for (id, name) in names.iter().enumerate() {
+        if let Some(name) = name {
+            w.str(&id.to_string(), name);
+        }
+    }
+}
+
+fn read_index(doc: &Document, kind: &str) -> Result<Vec<Option<Vec<u8>>>, String> {
+    let table = doc.table(kind)?;
+    let count: usize = table.int("count")?;
+    (0..count)
+        .map(|id| {
+            let key = id.to_string();
+            if table.has(&key) { table.bytes(&key).map(|x| Some(x.to_vec())) } else { Ok(None) }
+        })
+        .collect()
+}
+
+fn export_list<T>(
+    dir: &Path,
+    list: &AssetList<T>,
+    name: fn(&T) -> &PascalString,
+    export: impl Fn(&Path, &str, &T) -> Result<(), String>,
+) -> Result<(), String> {
+    for (asset, stem) in list.iter().zip(stems(&names(list, name))) {
+        if let (Some(asset), Some(stem)) = (asset, stem) {
+            export(dir, &stem, asset)?;
+        }
+    }
+    Ok(())
+}
+
+fn import_list<T>(
+    dir: &Path,
+    names: Vec<Option<Vec<u8>>>,
+    import: impl Fn(&Path, &str, PascalString) -> Result<T, String>,
+) -> Result<AssetList<T>, String> {
+    let stems = stems(&names.iter().map(|x| x.as_deref()).collect::<Vec<_>>());
+    names
+        .into_iter()
+        .zip(stems)
+        .map(|(name, stem)| match (name, stem) {
+            (Some(name), Some(stem)) => import(dir, &stem, PascalString(name.into())).map(|x| Some(Box::new(x))),
+            _ => Ok(None),
+        })
+        .collect()
+}
+
+/// Picks a file extension for raw image data that was stored as-is, which is usually a bitmap.
+fn data_file(dir: &Path, stem: &str, data: &[u8]) -> PathBuf {
+    dir.join(format!("{}.{}", stem, if data.starts_with(b"BM") { "bmp" } else { "bin" }))
+}
+
+fn find_data_file(dir: &Path, stem: &str) -> Result<Option<Box<[u8]>>, String> {
+    for ext in ["bmp", "bin"] {
+        let path = dir.join(format!("{}.{}", stem, ext));
+        if path.is_file() {
+            return read(&path).map(|x| Some(x.into_boxed_slice()))
+        }
+    }
+    Ok(None)
+}
+
+fn write_png(path: &Path, width: u32, height: u32, colour: png::ColorType, data: &[u8]) -> Result<(), String> {
+    let png = extract::encode_png(width, height, colour, data)
+        .map_err(|e| format!("Failed to encode '{}': {}", path.display(), e))?;
+    write(path, &png)
+}
+
+/// Reads a PNG of any colour type as 8-bit RGBA.
+fn read_png(path: &Path) -> Result<(u32, u32, Vec<u8>), String> {
+    let error = |e: png::DecodingError| format!("Failed to decode '{}': {}", path.display(), e);
+    let mut decoder = png::Decoder::new(fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?);
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let (info, mut reader) = decoder.read_info().map_err(error)?;
+    let mut buf = vec![0; info.buffer_size()];
+    reader.next_frame(&mut buf).map_err(error)?;
+    let rgba = match info.color_type {
+        png::ColorType::RGBA => buf,
+        png::ColorType::RGB => buf.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buf.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|p| [*p, *p, *p, 255]).collect(),
+        png::ColorType::Indexed => return Err(format!("Failed to decode '{}': unexpanded palette", path.display())),
+    };
+    Ok((info.width, info.height, rgba))
+}
+
+/// Swaps the red and blue channels, going between the BGRA the game stores and the RGBA in a PNG.
+fn swap_red_blue(data: &mut [u8]) {
+    for pixel in data.chunks_exact_mut(4) {
+        pixel.swap(0, 2);
+    }
+}
+
+/// An action made by typing code into an event with the Execute Code action, which can be saved as plain GML.
+fn action_code(actions: &[CodeAction]) -> Option<&PascalString> {
+    let template = code_action(PascalString::default());
+    match actions {
+        [a] if a.id == template.id
+            && a.applies_to == template.applies_to
+            && a.is_condition == template.is_condition
+            && a.invert_condition == template.invert_condition
+            && a.is_relative == template.is_relative
+            && a.lib_id == template.lib_id
+            && a.action_kind == template.action_kind
+            && a.execution_type == template.execution_type
+            && a.can_be_relative == template.can_be_relative
+            && a.applies_to_something == template.applies_to_something
+            && a.fn_name.0.is_empty()
+            && a.fn_code.0.is_empty()
+            && a.param_count == template.param_count
+            && a.param_types == template.param_types
+            && a.param_strings[1..].iter().all(|s| s.0.is_empty()) =>
+        {
+            Some(&a.param_strings[0])
+        },
+        _ => None,
+    }
+}
+
+/// Writes an action list as `stem.gml` if it's a single block of code, or `stem.toml` otherwise.
+fn export_actions(dir: &Path, stem: &str, actions: &[CodeAction], assets: &GameAssets) -> Result<(), String> {
+    if let Some(code) = action_code(actions) {
+        return write(&dir.join(format!("{}.gml", stem)), &code.0)
+    }
+    let mut w = Writer::new();
+    for action in actions {
+        w.array_table("action")
+            .int("library", action.lib_id)
+            .int("id", action.id)
+            .int("kind", action.action_kind)
+            .int("execution", action.execution_type)
+            .int("applies_to", action.applies_to)
+            .note(object_name(assets, action.applies_to))
+            .bool("applies_to_something", action.applies_to_something)
+            .bool("relative", action.is_relative)
+            .int("can_be_relative", action.can_be_relative)
+            .bool("condition", action.is_condition)
+            .bool("invert", action.invert_condition)
+            .str("function", &action.fn_name.0)
+            .str("code", &action.fn_code.0)
+            .int("param_count", action.param_count as i64)
+            .ints("param_types", action.param_types.iter().map(|x| i64::from(*x)));
+        for (i, param) in action.param_strings.iter().enumerate() {
+            if !param.0.is_empty() {
+                w.str(&format!("param_{}", i), &param.0);
+            }
+        }
+    }
+    write(&dir.join(format!("{}.toml", stem)), &w.finish())
+}
+
+fn import_action(t: &Table) -> Result<CodeAction, String> {
+    let param_types = t.ints::<u32>("param_types")?;
+    let mut action = CodeAction {
+        id: t.int("id")?,
+        applies_to: t.int("applies_to")?,
+        is_condition: t.bool("condition")?,
+        invert_condition: t.bool("invert")?,
+        is_relative: t.bool("relative")?,
+        lib_id: t.int("library")?,
+        action_kind: t.int("kind")?,
+        execution_type: t.int("execution")?,
+        can_be_relative: t.int("can_be_relative")?,
+        applies_to_something: t.bool("applies_to_something")?,
+        fn_name: t.str("function")?,
+        fn_code: t.str("code")?,
+        param_count: t.int("param_count")?,
+        param_types: param_types.try_into().map_err(|_| format!("expected {} param_types", PARAM_COUNT))?,
+        param_strings: Default::default(),
+    };
+    for (i, param) in action.param_strings.iter_mut().enumerate() {
+        let key = format!("param_{}", i);
+        if t.has(&key) {
+            *param = t.str(&key)?;
+        }
+    }
+    Ok(action)
+}
+
+/// Reads the action lists in a folder, named `<prefix><number>.gml` or `.toml`, sorted by number.
+fn import_actions(dir: &Path, prefix: &str) -> Result<Vec<(u32, Vec<CodeAction>)>, String> {
+    let mut lists = Vec::new();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(lists),
+    };
+    for entry in entries {
+        let path = entry.map_err(|e| format!("Failed to read '{}': {}", dir.display(), e))?.path();
+        let (stem, ext) = match (path.file_stem().and_then(|x| x.to_str()), path.extension().and_then(|x| x.to_str())) {
+            (Some(stem), Some(ext)) if ext == "gml" || ext == "toml" => (stem, ext),
+            _ => continue,
+        };
+        let number = match stem.strip_prefix(prefix).and_then(|x| x.parse::<u32>().ok()) {
+            Some(number) => number,
+            None => continue,
+        };
+        let actions = if ext == "gml" {
+            vec![code_action(PascalString(read(&path)?.into()))]
+        } else {
+            let doc = read_toml(&path)?;
+            doc.tables("action")
+                .map(import_action)
+                .collect::<Result<_, _>>()
+                .map_err(|e| format!("{}: {}", path.display(), e))?
+        };
+        if lists.iter().any(|(n, _)| *n == number) {
+            return Err(format!("'{}' has both a .gml and a .toml file", dir.join(stem).display()))
+        }
+        lists.push((number, actions));
+    }
+    lists.sort_by_key(|(number, _)| *number);
+    Ok(lists)
+}
+
+/// Whether a folder holds an earlier export, going by its `game.toml`.
+fn is_export(dir: &Path) -> bool {
+    read_toml(&dir.join("game.toml")).is_ok_and(|doc| {
+        let root = doc.root();
+        matches!(root.int::<u32>("version"), Ok(800 | 810)) && root.has("game_id") && doc.table("help").is_ok()
+    })
+}
+
+/// Writes a game to a project folder, replacing the output of an earlier export if there is one.
+/// Anything else in the folder is left alone, and a `game.toml` which `export` didn't write is an error.
+pub fn export(assets: &GameAssets, dir: &Path) -> Result<(), String> {
+    if dir.join("game.toml").is_file() {
+        if !is_export(dir) {
+            return Err(format!("'{}' has a game.toml which isn't from an export, not replacing it", dir.display()))
+        }
+        for name in MANAGED {
+            let path = dir.join(name);
+            let result = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+            if let Err(e) = result.or_else(|e| if path.exists() { Err(e) } else { Ok(()) }) {
+                return Err(format!("Failed to remove old '{}': {}", path.display(), e))
+            }
+        }
+    } else if dir.is_file() {
+        return Err(format!("'{}' is a file, not a folder", dir.display()))
+    }
+
+    let mut w = Writer::new();
+    w.int("version", match assets.version {
+        GameVersion::GameMaker8_0 => 800,
+        GameVersion::GameMaker8_1 => 810,
+    })
+    .int("game_id", assets.game_id)
+    .ints("guid", assets.guid.iter().map(|x| i64::from(*x)))
+    .int("last_instance_id", assets.last_instance_id)
+    .int("last_tile_id", assets.last_tile_id)
+    .int("library_init_strings", assets.library_init_strings.len() as i64)
+    .ints("room_order", assets.room_order.iter().map(|x| i64::from(*x)));
+    export_settings(&mut w, &assets.settings);
+    let help = &assets.help_dialog;
+    w.table("help")
+        .int("bg_colour", u32::from(help.bg_colour))
+        .bool("new_window", help.new_window)
+        .str("caption", &help.caption.0)
+        .int("left", help.left)
+        .int("top", help.top)
+        .int("width", help.width)
+        .int("height", help.height)
+        .bool("border", help.border)
+        .bool("resizable", help.resizable)
+        .bool("window_on_top", help.window_on_top)
+        .bool("freeze_game", help.freeze_game);
+    if let Some(info) = &assets.version_info {
+        w.table("version_info")
+            .int("major", info.major)
+            .int("minor", info.minor)
+            .int("release", info.release)
+            .int("build", info.build)
+            .str("company", &info.company.0)
+            .str("product", &info.product.0)
+            .str("copyright", &info.copyright.0)
+            .str("description", &info.description.0);
+    }
+    for extension in &assets.extensions {
+        w.array_table("extension").str("name", &extension.name.0);
+    }
+    write_index(&mut w, "sprites", &names(&assets.sprites, |x| &x.name));
+    write_index(&mut w, "sounds", &names(&assets.sounds, |x| &x.name));
+    write_index(&mut w, "backgrounds", &names(&assets.backgrounds, |x| &x.name));
+    write_index(&mut w, "paths", &names(&assets.paths, |x| &x.name));
+    write_index(&mut w, "scripts", &names(&assets.scripts, |x| &x.name));
+    write_index(&mut w, "fonts", &names(&assets.fonts, |x| &x.name));
+    write_index(&mut w, "timelines", &names(&assets.timelines, |x| &x.name));
+    write_index(&mut w, "objects", &names(&assets.objects, |x| &x.name));
+    write_index(&mut w, "rooms", &names(&assets.rooms, |x| &x.name));
+    write(&dir.join("game.toml"), &w.finish())?;
+
+    write(&dir.join("help.rtf"), &help.info.0)?;
+    if let Some(ico) = &assets.ico_file_raw {
+        write(&dir.join("icon.ico"), ico)?;
+    }
+    let loading = dir.join("loading");
+    let settings = &assets.settings;
+    for (stem, data) in
+        [("image", &settings.custom_load_image), ("back", &settings.backdata), ("front", &settings.frontdata)]
+    {
+        if let Some(data) = data {
+            write(&data_file(&loading, stem, data), data)?;
+        }
+    }
+    for (i, code) in assets.library_init_strings.iter().enumerate() {
+        write(&dir.join("library_init").join(format!("{}.gml", i)), &code.0)?;
+    }
+
+    let mut w = Writer::new();
+    for constant in &assets.constants {
+        w.array_table("constant").str("name", &constant.name.0).str("value", &constant.expression.0);
+    }
+    write(&dir.join("constants.toml"), &w.finish())?;
+
+    let mut w = Writer::new();
+    w.int("count", assets.triggers.len() as i64);
+    for (id, trigger) in assets.triggers.iter().enumerate() {
+        if let Some(trigger) = trigger {
+            w.array_table("trigger")
+                .int("id", id as i64)
+                .str("name", &trigger.name.0)
+                .str("condition", &trigger.condition.0)
+                .int("moment", trigger.moment as u32)
+                .str("constant_name", &trigger.constant_name.0);
+        }
+    }
+    write(&dir.join("triggers.toml"), &w.finish())?;
+
+    let mut w = Writer::new();
+    for (i, file) in assets.included_files.iter().enumerate() {
+        let (export, folder) = match &file.export_settings {
+            ExportSetting::NoExport => (0, &[][..]),
+            ExportSetting::TempFolder => (1, &[][..]),
+            ExportSetting::GameFolder => (2, &[][..]),
+            ExportSetting::CustomFolder(folder) => (3, folder.0.as_ref()),
+        };
+        w.array_table("file")
+            .str("file_name", &file.file_name.0)
+            .str("source_path", &file.source_path.0)
+            .bool("data_exists", file.data_exists)
+            .int("source_length", file.source_length as i64)
+            .bool("stored_in_gmk", file.stored_in_gmk)
+            .int("export", export)
+            .str("export_folder", folder)
+            .bool("overwrite_file", file.overwrite_file)
+            .bool("free_memory", file.free_memory)
+            .bool("remove_at_end", file.remove_at_end);
+        if let Some(data) = &file.embedded_data {
+            let data_name = format!("{}_{}", i, sanitize(&file.file_name.0, b".-"));
+            w.str("data", data_name.as_bytes());
+            write(&dir.join("included_files").join(data_name), data)?;
+        }
+    }
+    write(&dir.join("included_files.toml"), &w.finish())?;
+
+    export_list(&dir.join("scripts"), &assets.scripts, |x| &x.name, |dir, stem, script| {
+        write(&dir.join(format!("{}.gml", stem)), &script.source.0)
+    })?;
+    export_list(&dir.join("sprites"), &assets.sprites, |x| &x.name, export_sprite)?;
+    export_list(&dir.join("sounds"), &assets.sounds, |x| &x.name, export_sound)?;
+    export_list(&dir.join("backgrounds"), &assets.backgrounds, |x| &x.name, export_background)?;
+    export_list(&dir.join("paths"), &assets.paths, |x| &x.name, export_path)?;
+    export_list(&dir.join("fonts"), &assets.fonts, |x| &x.name, export_font)?;
+    export_list(&dir.join("timelines"), &assets.timelines, |x| &x.name, |dir, stem, timeline| {
+        let dir = dir.join(stem);
+        for (moment, actions) in &timeline.moments {
+            export_actions(&dir, &moment.to_string(), actions, assets)?;
+        }
+        Ok(())
+    })?;
+    export_list(&dir.join("objects"), &assets.objects, |x| &x.name, |dir, stem, object| {
+        export_object(&dir.join(stem), object, assets)
+    })?;
+    export_list(&dir.join("rooms"), &assets.rooms, |x| &x.name, |dir, stem, room| {
+        export_room(&dir.join(stem), room, assets)
+    })?;
+    Ok(())
+}
+
+/// Reads a project folder back into a game.
+pub fn import(dir: &Path) -> Result<GameAssets, String> {
+    let game = read_toml(&dir.join("game.toml"))?;
+    let root = game.root();
+    let version = match root.int::<u32>("version")? {
+        800 => GameVersion::GameMaker8_0,
+        810 => GameVersion::GameMaker8_1,
+        v => return Err(format!("Unknown version {} in game.toml", v)),
+    };
+    let guid = root.ints::<u32>("guid")?.try_into().map_err(|_| "guid in game.toml should have 4 numbers")?;
+    let help = game.table("help")?;
+    let help_dialog = GameHelpDialog {
+        bg_colour: help.int::<u32>("bg_colour")?.into(),
+        new_window: help.bool("new_window")?,
+        caption: help.str("caption")?,
+        left: help.int("left")?,
+        top: help.int("top")?,
+        width: help.int("width")?,
+        height: help.int("height")?,
+        border: help.bool("border")?,
+        resizable: help.bool("resizable")?,
+        window_on_top: help.bool("window_on_top")?,
+        freeze_game: help.bool("freeze_game")?,
+        info: PascalString(read(&dir.join("help.rtf"))?.into()),
+    };
+    let version_info = game
+        .tables("version_info")
+        .next()
+        .map(|info| -> Result<_, String> {
+            Ok(VersionInfo {
+                major: info.int("major")?,
+                minor: info.int("minor")?,
+                release: info.int("release")?,
+                build: info.int("build")?,
+                company: info.str("company")?,
+                product: info.str("product")?,
+                copyright: info.str("copyright")?,
+                description: info.str("description")?,
+            })
+        })
+        .transpose()?;
+    let loading = dir.join("loading");
+    let settings = import_settings(
+        game.table("settings")?,
+        find_data_file(&loading, "image")?,
+        find_data_file(&loading, "back")?,
+        find_data_file(&loading, "front")?,
+    )?;
+    let ico_path = dir.join("icon.ico");
+    let ico_file_raw = if ico_path.is_file() { Some(read(&ico_path)?) } else { None };
+    let library_init_strings = (0..root.int::<usize>("library_init_strings")?)
+        .map(|i| read(&dir.join("library_init").join(format!("{}.gml", i))).map(|x| PascalString(x.into())))
+        .collect::<Result<_, _>>()?;
+    let extensions = game
+        .tables("extension")
+        .map(|t| Ok(Extension { name: t.str("name")?, folder_name: PascalString::default(), files: Vec::new() }))
+        .collect::<Result<_, String>>()?;
+
+    let constants_path = dir.join("constants.toml");
+    let constants = read_toml(&constants_path)?
+        .tables("constant")
+        .map(|t| Ok(asset::Constant { name: t.str("name")?, expression: t.str("value")? }))
+        .collect::<Result<_, String>>()
+        .map_err(|e| format!("{}: {}", constants_path.display(), e))?;
+
+    let triggers_path = dir.join("triggers.toml");
+    let triggers_doc = read_toml(&triggers_path)?;
+    let mut triggers: AssetList<asset::Trigger> = Vec::new();
+    triggers.resize_with(triggers_doc.root().int("count")?, || None);
+    for t in triggers_doc.tables("trigger") {
+        let trigger = asset::Trigger {
+            name: t.str("name")?,
+            condition: t.str("condition")?,
+            moment: match t.int("moment")? {
+                0 => TriggerKind::Step,
+                1 => TriggerKind::BeginStep,
+                2 => TriggerKind::EndStep,
+                _ => return Err(format!("{}: unknown trigger moment", triggers_path.display())),
+            },
+            constant_name: t.str("constant_name")?,
+        };
+        let id: usize = t.int("id")?;
+        *triggers.get_mut(id).ok_or_else(|| format!("{}: trigger {} is past count", triggers_path.display(), id))? =
+            Some(Box::new(trigger));
+    }
+
+    let included_path = dir.join("included_files.toml");
+    let included_files = read_toml(&included_path)?
+        .tables("file")
+        .map(|t| {
+            Ok(asset::IncludedFile {
+                file_name: t.str("file_name")?,
+                source_path: t.str("source_path")?,
+                data_exists: t.bool("data_exists")?,
+                source_length: t.int("source_length")?,
+                stored_in_gmk: t.bool("stored_in_gmk")?,
+                embedded_data: if t.has("data") {
+                    let name = String::from_utf8_lossy(t.bytes("data")?).into_owned();
+                    Some(read(&dir.join("included_files").join(name))?.into_boxed_slice())
+                } else {
+                    None
+                },
+                export_settings: match t.int("export")? {
+                    0 => ExportSetting::NoExport,
+                    1 => ExportSetting::TempFolder,
+                    2 => ExportSetting::GameFolder,
+                    _ => ExportSetting::CustomFolder(t.str("export_folder")?),
+                },
+                overwrite_file: t.bool("overwrite_file")?,
+                free_memory: t.bool("free_memory")?,
+                remove_at_end: t.bool("remove_at_end")?,
+            })
+        })
+        .collect::<Result<_, String>>()
+        .map_err(|e| format!("{}: {}", included_path.display(), e))?;
+
+    Ok(GameAssets {
+        triggers,
+        constants,
+        extensions,
+        sprites: import_list(&dir.join("sprites"), read_index(&game, "sprites")?, import_sprite)?,
+        sounds: import_list(&dir.join("sounds"), read_index(&game, "sounds")?, import_sound)?,
+        backgrounds: import_list(&dir.join("backgrounds"), read_index(&game, "backgrounds")?, import_background)?,
+        paths: import_list(&dir.join("paths"), read_index(&game, "paths")?, import_path)?,
+        scripts: import_list(&dir.join("scripts"), read_index(&game, "scripts")?, |dir, stem, name| {
+            Ok(asset::Script { name, source: PascalString(read(&dir.join(format!("{}.gml", stem)))?.into()) })
+        })?,
+        fonts: import_list(&dir.join("fonts"), read_index(&game, "fonts")?, import_font)?,
+        timelines: import_list(&dir.join("timelines"), read_index(&game, "timelines")?, |dir, stem, name| {
+            Ok(asset::Timeline { name, moments: import_actions(&dir.join(stem), "")? })
+        })?,
+        objects: import_list(&dir.join("objects"), read_index(&game, "objects")?, |dir, stem, name| {
+            import_object(&dir.join(stem), name)
+        })?,
+        rooms: import_list(&dir.join("rooms"), read_index(&game, "rooms")?, |dir, stem, name| {
+            import_room(&dir.join(stem), name)
+        })?,
+        included_files,
+        version,
+        dx_dll: Vec::new(),
+        ico_file_raw,
+        version_info,
+        detection: None,
+        warnings: Vec::new(),
+        help_dialog,
+        last_instance_id: root.int("last_instance_id")?,
+        last_tile_id: root.int("last_tile_id")?,
+        library_init_strings,
+        room_order: root.ints("room_order")?,
+        resource_tree: Vec::new(),
+        settings,
+        game_id: root.int("game_id")?,
+        guid,
+    })
+}
+
+fn export_settings(w: &mut Writer, s: &Settings) {
+    w.table("settings")
+        .bool("fullscreen", s.fullscreen)
+        .int("scaling", s.scaling)
+        .bool("interpolate_pixels", s.interpolate_pixels)
+        .int("clear_colour", s.clear_colour)
+        .bool("allow_resize", s.allow_resize)
+        .bool("window_on_top", s.window_on_top)
+        .bool("dont_draw_border", s.dont_draw_border)
+        .bool("dont_show_buttons", s.dont_show_buttons)
+        .bool("display_cursor", s.display_cursor)
+        .bool("freeze_on_lose_focus", s.freeze_on_lose_focus)
+        .bool("disable_screensaver", s.disable_screensaver)
+        .bool("force_cpu_render", s.force_cpu_render)
+        .bool("set_resolution", s.set_resolution)
+        .int("colour_depth", s.colour_depth)
+        .int("resolution", s.resolution)
+        .int("frequency", s.frequency)
+        .bool("vsync", s.vsync)
+        .bool("esc_close_game", s.esc_close_game)
+        .bool("treat_close_as_esc", s.treat_close_as_esc)
+        .bool("f1_help_menu", s.f1_help_menu)
+        .bool("f4_fullscreen_toggle", s.f4_fullscreen_toggle)
+        .bool("f5_save_f6_load", s.f5_save_f6_load)
+        .bool("f9_screenshot", s.f9_screenshot)
+        .int("priority", s.priority)
+        .bool("transparent", s.transparent)
+        .int("translucency", s.translucency)
+        .int("loading_bar", s.loading_bar)
+        .bool("scale_progress_bar", s.scale_progress_bar)
+        .bool("show_error_messages", s.show_error_messages)
+        .bool("log_errors", s.log_errors)
+        .bool("always_abort", s.always_abort)
+        .bool("zero_uninitialized_vars", s.zero_uninitialized_vars)
+        .bool("error_on_uninitialized_args", s.error_on_uninitialized_args)
+        .bool("swap_creation_events", s.swap_creation_events);
+}
+
+fn import_settings(
+    t: &Table,
+    custom_load_image: Option<Box<[u8]>>,
+    backdata: Option<Box<[u8]>>,
+    frontdata: Option<Box<[u8]>>,
+) -> Result<Settings, String> {
+    Ok(Settings {
+        fullscreen: t.bool("fullscreen")?,
+        scaling: t.int("scaling")?,
+        interpolate_pixels: t.bool("interpolate_pixels")?,
+        clear_colour: t.int("clear_colour")?,
+        allow_resize: t.bool("allow_resize")?,
+        window_on_top: t.bool("window_on_top")?,
+        dont_draw_border: t.bool("dont_draw_border")?,
+        dont_show_buttons: t.bool("dont_show_buttons")?,
+        display_cursor: t.bool("display_cursor")?,
+        freeze_on_lose_focus: t.bool("freeze_on_lose_focus")?,
+        disable_screensaver: t.bool("disable_screensaver")?,
+        force_cpu_render: t.bool("force_cpu_render")?,
+        set_resolution: t.bool("set_resolution")?,
+        colour_depth: t.int("colour_depth")?,
+        resolution: t.int("resolution")?,
+        frequency: t.int("frequency")?,
+        vsync: t.bool("vsync")?,
+        esc_close_game: t.bool("esc_close_game")?,
+        treat_close_as_esc: t.bool("treat_close_as_esc")?,
+        f1_help_menu: t.bool("f1_help_menu")?,
+        f4_fullscreen_toggle: t.bool("f4_fullscreen_toggle")?,
+        f5_save_f6_load: t.bool("f5_save_f6_load")?,
+        f9_screenshot: t.bool("f9_screenshot")?,
+        priority: t.int("priority")?,
+        custom_load_image,
+        transparent: t.bool("transparent")?,
+        translucency: t.int("translucency")?,
+        loading_bar: t.int("loading_bar")?,
+        backdata,
+        frontdata,
+        scale_progress_bar: t.bool("scale_progress_bar")?,
+        show_error_messages: t.bool("show_error_messages")?,
+        log_errors: t.bool("log_errors")?,
+        always_abort: t.bool("always_abort")?,
+        zero_uninitialized_vars: t.bool("zero_uninitialized_vars")?,
+        error_on_uninitialized_args: t.bool("error_on_uninitialized_args")?,
+        swap_creation_events: t.bool("swap_creation_events")?,
+    })
+}
+
+fn export_sprite(dir: &Path, stem: &str, sprite: &asset::Sprite) -> Result<(), String> {
+    let (width, height) = sprite.frames.first().map_or((0, 0), |f| (f.width, f.height));
+    let strip = sprite.frames.iter().all(|f| f.width == width && f.height == height);
+    let mut w = Writer::new();
+    w.int("origin_x", sprite.origin_x)
+        .int("origin_y", sprite.origin_y)
+        .int("frames", sprite.frames.len() as i64)
+        .bool("strip", strip)
+        .bool("per_frame_colliders", sprite.per_frame_colliders);
+    if strip {
+        // frames side by side in one image, left to right
+        w.int("width", width).int("height", height);
+        if width * height != 0 {
+            let row = width as usize * 4;
+            let mut data = Vec::with_capacity(row * height as usize * sprite.frames.len());
+            for y in 0..height as usize {
+                for frame in &sprite.frames {
+                    data.extend_from_slice(&frame.data[y * row..(y + 1) * row]);
+                }
+            }
+            swap_red_blue(&mut data);
+            let path = dir.join(format!("{}.png", stem));
+            write_png(&path, width * sprite.frames.len() as u32, height, png::ColorType::RGBA, &data)?;
+        }
+    } else {
+        for (i, frame) in sprite.frames.iter().enumerate() {
+            w.array_table("frame").int("width", frame.width).int("height", frame.height);
+            if frame.width * frame.height != 0 {
+                let mut data = frame.data.to_vec();
+                swap_red_blue(&mut data);
+                let path = dir.join(format!("{}.{}.png", stem, i));
+                write_png(&path, frame.width, frame.height, png::ColorType::RGBA, &data)?;
+            }
+        }
+    }
+    for (i, map) in sprite.colliders.iter().enumerate() {
+        w.array_table("collider")
+            .int("width", map.width)
+            .int("height", map.height)
+            .int("bbox_left", map.bbox_left)
+            .int("bbox_right", map.bbox_right)
+            .int("bbox_top", map.bbox_top)
+            .int("bbox_bottom", map.bbox_bottom);
+        if map.width * map.height != 0 {
+            let data = map.data.iter().map(|x| if *x { 255 } else { 0 }).collect::<Vec<u8>>();
+            let path = dir.join(format!("{}.mask{}.png", stem, i));
+            write_png(&path, map.width, map.height, png::ColorType::Grayscale, &data)?;
+        }
+    }
+    write(&dir.join(format!("{}.toml", stem)), &w.finish())
+}
+
+fn import_sprite(dir: &Path, stem: &str, name: PascalString) -> Result<asset::Sprite, String> {
+    let doc = read_toml(&dir.join(format!("{}.toml", stem)))?;
+    let t = doc.root();
+    let frame_count: usize = t.int("frames")?;
+    let frames = if t.bool("strip")? {
+        let (width, height) = (t.int::<u32>("width")?, t.int::<u32>("height")?);
+        if width * height == 0 {
+            (0..frame_count).map(|_| Frame { width, height, data: Box::new([]) }).collect()
+        } else {
+            let (_, _, mut data) =
+                read_sized_png(&dir.join(format!("{}.png", stem)), width * frame_count as u32, height)?;
+            swap_red_blue(&mut data);
+            let row = width as usize * 4;
+            (0..frame_count)
+                .map(|i| Frame {
+                    width,
+                    height,
+                    data: data.chunks_exact(row).skip(i).step_by(frame_count).flatten().copied().collect(),
+                })
+                .collect()
+        }
+    } else {
+        doc.tables("frame")
+            .enumerate()
+            .map(|(i, f)| {
+                let (width, height) = (f.int::<u32>("width")?, f.int::<u32>("height")?);
+                if width * height == 0 {
+                    return Ok(Frame { width, height, data: Box::new([]) })
+                }
+                let (_, _, mut data) = read_sized_png(&dir.join(format!("{}.{}.png", stem, i)), width, height)?;
+                swap_red_blue(&mut data);
+                Ok(Frame { width, height, data: data.into_boxed_slice() })
+            })
+            .collect::<Result<_, String>>()?
+    };
+    let colliders = doc
+        .tables("collider")
+        .enumerate()
+        .map(|(i, c)| {
+            let (width, height) = (c.int::<u32>("width")?, c.int::<u32>("height")?);
+            let data = if width * height == 0 {
+                Box::new([]) as Box<[bool]>
+            } else {
+                let (_, _, data) = read_sized_png(&dir.join(format!("{}.mask{}.png", stem, i)), width, height)?;
+                data.chunks_exact(4).map(|p| p[0] >= 128).collect()
+            };
+            Ok(CollisionMap {
+                width,
+                height,
+                bbox_left: c.int("bbox_left")?,
+                bbox_right: c.int("bbox_right")?,
+                bbox_top: c.int("bbox_top")?,
+                bbox_bottom: c.int("bbox_bottom")?,
+                data,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(asset::Sprite {
+        name,
+        origin_x: t.int("origin_x")?,
+        origin_y: t.int("origin_y")?,
+        frames,
+        colliders,
+        per_frame_colliders: t.bool("per_frame_colliders")?,
+    })
+}
+
+fn read_sized_png(path: &Path, width: u32, height: u32) -> Result<(u32, u32, Vec<u8>), String> {
+    let image = read_png(path)?;
+    if (image.0, image.1) != (width, height) {
+        return Err(format!("'{}' should be {}x{}", path.display(), width, height))
+    }
+    Ok(image)
+}
+
+fn export_background(dir: &Path, stem: &str, background: &asset::Background) -> Result<(), String> {
+    let mut w = Writer::new();
+    w.int("width", background.width).int("height", background.height).bool("has_data", background.data.is_some());
+    if let Some(data) = &background.data {
+        if background.width * background.height != 0 {
+            let mut data = data.to_vec();
+            swap_red_blue(&mut data);
+            let path = dir.join(format!("{}.png", stem));
+            write_png(&path, background.width, background.height, png::ColorType::RGBA, &data)?;
+        }
+    }
+    write(&dir.join(format!("{}.toml", stem)), &w.finish())
+}
+
+fn import_background(dir: &Path, stem: &str, name: PascalString) -> Result<asset::Background, String> {
+    let t = read_toml(&dir.join(format!("{}.toml", stem)))?;
+    let t = t.root();
+    let (width, height) = (t.int::<u32>("width")?, t.int::<u32>("height")?);
+    let data = if !t.bool("has_data")? {
+        None
+    } else if width * height == 0 {
+        Some(Box::new([]) as Box<[u8]>)
+    } else {
+        let (_, _, mut data) = read_sized_png(&dir.join(format!("{}.png", stem)), width, height)?;
+        swap_red_blue(&mut data);
+        Some(data.into_boxed_slice())
+    };
+    Ok(asset::Background { name, width, height, data })
+}
+
+fn export_sound(dir: &Path, stem: &str, sound: &asset::Sound) -> Result<(), String> {
+    let mut w = Writer::new();
+    w.str("source", &sound.source.0)
+        .str("extension", &sound.extension.0)
+        .int("kind", sound.kind as u32)
+        .float("volume", sound.volume)
+        .float("pan", sound.pan)
+        .bool("preload", sound.preload)
+        .bool("chorus", sound.fx.chorus)
+        .bool("echo", sound.fx.echo)
+        .bool("flanger", sound.fx.flanger)
+        .bool("gargle", sound.fx.gargle)
+        .bool("reverb", sound.fx.reverb);
+    if let Some(data) = &sound.data {
+        let ext = &sound.extension.0;
+        let ext = match ext.strip_prefix(b".") {
+            Some(e) if !e.is_empty() && e.len() <= 8 && e.iter().all(u8::is_ascii_alphanumeric) => {
+                String::from_utf8_lossy(ext).to_ascii_lowercase()
+            },
+            _ => ".bin".into(),
+        };
+        let file_name = format!("{}{}", stem, ext);
+        w.str("data", file_name.as_bytes());
+        write(&dir.join(file_name), data)?;
+    }
+    write(&dir.join(format!("{}.toml", stem)), &w.finish())
+}
+
+fn import_sound(dir: &Path, stem: &str, name: PascalString) -> Result<asset::Sound, String> {
+    let t = read_toml(&dir.join(format!("{}.toml", stem)))?;
+    let t = t.root();
+    Ok(asset::Sound {
+        name,
+        source: t.str("source")?,
+        extension: t.str("extension")?,
+        data: if t.has("data") {
+            Some(read(&dir.join(String::from_utf8_lossy(t.bytes("data")?).as_ref()))?.into_boxed_slice())
+        } else {
+            None
+        },
+        kind: match t.int("kind")? {
+            0 => SoundKind::Normal,
+            1 => SoundKind::BackgroundMusic,
+            2 => SoundKind::ThreeDimensional,
+            3 => SoundKind::Multimedia,
+            _ => return Err(format!("unknown sound kind in '{}'", dir.join(stem).display())),
+        },
+        volume: t.float("volume")?,
+        pan: t.float("pan")?,
+        preload: t.bool("preload")?,
+        fx: SoundFX {
+            chorus: t.bool("chorus")?,
+            echo: t.bool("echo")?,
+            flanger: t.bool("flanger")?,
+            gargle: t.bool("gargle")?,
+            reverb: t.bool("reverb")?,
+        },
+    })
+}
+
+fn export_path(dir: &Path, stem: &str, path: &asset::Path) -> Result<(), String> {
+    let mut w = Writer::new();
+    w.int("connection", path.connection as u32).int("precision", path.precision).bool("closed", path.closed);
+    for point in &path.points {
+        w.array_table("point").float("x", point.x).float("y", point.y).float("speed", point.speed);
+    }
+    write(&dir.join(format!("{}.toml", stem)), &w.finish())
+}
+
+fn import_path(dir: &Path, stem: &str, name: PascalString) -> Result<asset::Path, String> {
+    let doc = read_toml(&dir.join(format!("{}.toml", stem)))?;
+    let t = doc.root();
+    Ok(asset::Path {
+        name,
+        connection: if t.int::<u32>("connection")? == 0 {
+            ConnectionKind::StraightLine
+        } else {
+            ConnectionKind::SmoothCurve
+        },
+        precision: t.int("precision")?,
+        closed: t.bool("closed")?,
+        points: doc
+            .tables("point")
+            .map(|p| Ok(Point { x: p.float("x")?, y: p.float("y")?, speed: p.float("speed")? }))
+            .collect::<Result<_, String>>()?,
+    })
+}
+
+fn export_font(dir: &Path, stem: &str, font: &asset::Font) -> Result<(), String> {
+    let mut w = Writer::new();
+    w.str("sys_name", &font.sys_name.0)
+        .int("size", font.size)
+        .bool("bold", font.bold)
+        .bool("italic", font.italic)
+        .int("range_start", font.range_start)
+        .int("range_end", font.range_end)
+        .int("charset", font.charset)
+        .int("aa_level", font.aa_level);
+    write(&dir.join(format!("{}.toml", stem)), &w.finish())
+}
+
+fn import_font(dir: &Path, stem: &str, name: PascalString) -> Result<asset::Font, String> {
+    let t = read_toml(&dir.join(format!("{}.toml", stem)))?;
+    let t = t.root();
+    // the glyphs are rendered again from the system font when the GMK is built, so they aren't kept
+    Ok(asset::Font {
+        name,
+        sys_name: t.str("sys_name")?,
+        size: t.int("size")?,
+        bold: t.bool("bold")?,
+        italic: t.bool("italic")?,
+        range_start: t.int("range_start")?,
+        range_end: t.int("range_end")?,
+        charset: t.int("charset")?,
+        aa_level: t.int("aa_level")?,
+        dmap: Box::new([0; 0x600]),
+        map_width: 0,
+        map_height: 0,
+        pixel_map: Box::new([]),
+    })
+}
+
+fn event_name(kind: usize) -> String {
+    EVENT_NAMES.get(kind).map_or_else(|| format!("event{}", kind), |x| (*x).into())
+}
+
+fn export_object(dir: &Path, object: &asset::Object, assets: &GameAssets) -> Result<(), String> {
+    let mut w = Writer::new();
+    w.int("sprite", object.sprite_index)
+        .note(sprite_name(assets, object.sprite_index))
+        .int("mask", object.mask_index)
+        .note(sprite_name(assets, object.mask_index))
+        .int("parent", object.parent_index)
+        .note(object_name(assets, object.parent_index))
+        .bool("solid", object.solid)
+        .bool("visible", object.visible)
+        .int("depth", object.depth)
+        .bool("persistent", object.persistent)
+        .int("event_lists", object.events.len() as i64);
+    write(&dir.join("object.toml"), &w.finish())?;
+    for (kind, list) in object.events.iter().enumerate() {
+        for (sub, actions) in list {
+            export_actions(dir, &format!("{}_{}", event_name(kind), sub), actions, assets)?;
+        }
+    }
+    Ok(())
+}
+
+fn import_object(dir: &Path, name: PascalString) -> Result<asset::Object, String> {
+    let t = read_toml(&dir.join("object.toml"))?;
+    let t = t.root();
+    let events = (0..t.int::<usize>("event_lists")?)
+        .map(|kind| import_actions(dir, &format!("{}_", event_name(kind))))
+        .collect::<Result<_, _>>()?;
+    Ok(asset::Object {
+        name,
+        sprite_index: t.int("sprite")?,
+        solid: t.bool("solid")?,
+        visible: t.bool("visible")?,
+        depth: t.int("depth")?,
+        persistent: t.bool("persistent")?,
+        parent_index: t.int("parent")?,
+        mask_index: t.int("mask")?,
+        events,
+    })
+}
+
+fn export_room(dir: &Path, room: &asset::Room, assets: &GameAssets) -> Result<(), String> {
+    let mut w = Writer::new();
+    w.str("caption", &room.caption.0)
+        .int("width", room.width)
+        .int("height", room.height)
+        .int("speed", room.speed)
+        .bool("persistent", room.persistent)
+        .int("bg_colour", u32::from(room.bg_colour))
+        .bool("clear_screen", room.clear_screen)
+        .bool("clear_region", room.clear_region)
+        .bool("views_enabled", room.views_enabled)
+        .bool("uses_810_features", room.uses_810_features)
+        .bool("uses_811_features", room.uses_811_features);
+    if !room.creation_code.0.is_empty() {
+        write(&dir.join("creation.gml"), &room.creation_code.0)?;
+    }
+    for bg in &room.backgrounds {
+        w.array_table("background")
+            .bool("visible_on_start", bg.visible_on_start)
+            .bool("is_foreground", bg.is_foreground)
+            .int("source_bg", bg.source_bg)
+            .note(background_name(assets, bg.source_bg))
+            .int("xoffset", bg.xoffset)
+            .int("yoffset", bg.yoffset)
+            .bool("tile_horz", bg.tile_horz)
+            .bool("tile_vert", bg.tile_vert)
+            .int("hspeed", bg.hspeed)
+            .int("vspeed", bg.vspeed)
+            .bool("stretch", bg.stretch);
+    }
+    for view in &room.views {
+        w.array_table("view")
+            .bool("visible", view.visible)
+            .int("source_x", view.source_x)
+            .int("source_y", view.source_y)
+            .int("source_w", view.source_w)
+            .int("source_h", view.source_h)
+            .int("port_x", view.port_x)
+            .int("port_y", view.port_y)
+            .int("port_w", view.port_w)
+            .int("port_h", view.port_h)
+            .int("hborder", view.following.hborder)
+            .int("vborder", view.following.vborder)
+            .int("hspeed", view.following.hspeed)
+            .int("vspeed", view.following.vspeed)
+            .int("target", view.following.target)
+            .note(object_name(assets, view.following.target));
+    }
+    let mut code_files = Vec::new();
+    for instance in &room.instances {
+        w.array_table("instance")
+            .int("id", instance.id)
+            .int("object", instance.object)
+            .note(object_name(assets, instance.object))
+            .int("x", instance.x)
+            .int("y", instance.y)
+            .float("xscale", instance.xscale)
+            .float("yscale", instance.yscale)
+            .int("blend", instance.blend)
+            .float("angle", instance.angle);
+        if !instance.creation_code.0.is_empty() {
+            // ids should be unique, but the file names have to be
+            let mut file_name = format!("instance_{}.gml", instance.id);
+            while code_files.contains(&file_name) {
+                file_name = format!("instance_{}_{}.gml", instance.id, code_files.len());
+            }
+            w.str("creation_code", file_name.as_bytes());
+            write(&dir.join(&file_name), &instance.creation_code.0)?;
+            code_files.push(file_name);
+        }
+    }
+    for tile in &room.tiles {
+        w.array_table("tile")
+            .int("id", tile.id)
+            .int("source_bg", tile.source_bg)
+            .note(background_name(assets, tile.source_bg))
+            .int("x", tile.x)
+            .int("y", tile.y)
+            .int("tile_x", tile.tile_x)
+            .int("tile_y", tile.tile_y)
+            .int("width", tile.width)
+            .int("height", tile.height)
+            .int("depth", tile.depth)
+            .float("xscale", tile.xscale)
+            .float("yscale", tile.yscale)
+            .int("blend", tile.blend);
+    }
+    write(&dir.join("room.toml"), &w.finish())
+}
+
+fn import_room(dir: &Path, name: PascalString) -> Result<asset::Room, String> {
+    let doc = read_toml(&dir.join("room.toml"))?;
+    let t = doc.root();
+    let creation_path = dir.join("creation.gml");
+    let creation_code = if creation_path.is_file() { read(&creation_path)? } else { Vec::new() };
+    Ok(asset::Room {
+        name,
+        caption: t.str("caption")?,
+        width: t.int("width")?,
+        height: t.int("height")?,
+        speed: t.int("speed")?,
+        persistent: t.bool("persistent")?,
+        bg_colour: t.int::<u32>("bg_colour")?.into(),
+        clear_screen: t.bool("clear_screen")?,
+        clear_region: t.bool("clear_region")?,
+        creation_code: PascalString(creation_code.into()),
+        backgrounds: doc
+            .tables("background")
+            .map(|b| {
+                Ok(room::Background {
+                    visible_on_start: b.bool("visible_on_start")?,
+                    is_foreground: b.bool("is_foreground")?,
+                    source_bg: b.int("source_bg")?,
+                    xoffset: b.int("xoffset")?,
+                    yoffset: b.int("yoffset")?,
+                    tile_horz: b.bool("tile_horz")?,
+                    tile_vert: b.bool("tile_vert")?,
+                    hspeed: b.int("hspeed")?,
+                    vspeed: b.int("vspeed")?,
+                    stretch: b.bool("stretch")?,
+                })
+            })
+            .collect::<Result<_, String>>()?,
+        views_enabled: t.bool("views_enabled")?,
+        views: doc
+            .tables("view")
+            .map(|v| {
+                Ok(room::View {
+                    visible: v.bool("visible")?,
+                    source_x: v.int("source_x")?,
+                    source_y: v.int("source_y")?,
+                    source_w: v.int("source_w")?,
+                    source_h: v.int("source_h")?,
+                    port_x: v.int("port_x")?,
+                    port_y: v.int("port_y")?,
+                    port_w: v.int("port_w")?,
+                    port_h: v.int("port_h")?,
+                    following: room::ViewFollowData {
+                        hborder: v.int("hborder")?,
+                        vborder: v.int("vborder")?,
+                        hspeed: v.int("hspeed")?,
+                        vspeed: v.int("vspeed")?,
+                        target: v.int("target")?,
+                    },
+                })
+            })
+            .collect::<Result<_, String>>()?,
+        instances: doc
+            .tables("instance")
+            .map(|i| {
+                let creation_code = if i.has("creation_code") {
+                    read(&dir.join(String::from_utf8_lossy(i.bytes("creation_code")?).as_ref()))?
+                } else {
+                    Vec::new()
+                };
+                Ok(room::Instance {
+                    x: i.int("x")?,
+                    y: i.int("y")?,
+                    object: i.int("object")?,
+                    id: i.int("id")?,
+                    creation_code: PascalString(creation_code.into()),
+                    xscale: i.float("xscale")?,
+                    yscale: i.float("yscale")?,
+                    blend: i.int("blend")?,
+                    angle: i.float("angle")?,
+                })
+            })
+            .collect::<Result<_, String>>()?,
+        tiles: doc
+            .tables("tile")
+            .map(|t| {
+                Ok(room::Tile {
+                    x: t.int("x")?,
+                    y: t.int("y")?,
+                    source_bg: t.int("source_bg")?,
+                    tile_x: t.int("tile_x")?,
+                    tile_y: t.int("tile_y")?,
+                    width: t.int("width")?,
+                    height: t.int("height")?,
+                    depth: t.int("depth")?,
+                    id: t.int("id")?,
+                    xscale: t.float("xscale")?,
+                    yscale: t.float("yscale")?,
+                    blend: t.int("blend")?,
+                })
+            })
+            .collect::<Result<_, String>>()?,
+        uses_810_features: t.bool("uses_810_features")?,
+        uses_811_features: t.bool("uses_811_features")?,
+        editor_settings: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{assert_same_game, game};
+    use std::{env, process};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("gm8decompiler-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn round_trip() {
+        let dir = temp_dir("project");
+        for version in [GameVersion::GameMaker8_0, GameVersion::GameMaker8_1] {
+            export(&game(version), &dir).unwrap();
+            let assets = import(&dir).unwrap();
+            assert_same_game(&assets, version);
+            // like project files, project folders only keep what a font is rendered from
+            assert!(assets.fonts[0].as_ref().unwrap().pixel_map.is_empty());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn export_replaces_only_its_own_files() {
+        let dir = temp_dir("replace");
+        export(&game(GameVersion::GameMaker8_1), &dir).unwrap();
+        fs::write(dir.join("scripts").join("stale.gml"), b"").unwrap();
+        fs::write(dir.join("notes.txt"), b"keep me").unwrap();
+        export(&game(GameVersion::GameMaker8_1), &dir).unwrap();
+        assert!(!dir.join("scripts").join("stale.gml").exists());
+        assert_eq!(fs::read(dir.join("notes.txt")).unwrap(), b"keep me");
+
+        // a game.toml from something else means the folder isn't an export, so nothing is deleted
+        fs::write(dir.join("game.toml"), b"[package]\nname = \"game\"\n").unwrap();
+        assert!(export(&game(GameVersion::GameMaker8_1), &dir).is_err());
+        assert!(dir.join("scripts").is_dir());
+        assert_eq!(fs::read(dir.join("game.toml")).unwrap(), b"[package]\nname = \"game\"\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
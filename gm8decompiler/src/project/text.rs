@@ -0,0 +1,374 @@
+//! A small subset of TOML for project files: `key = value` lines under `[table]` and `[[array]]` headers.
+//!
+//! Strings are kept as bytes, so text in the game's code page goes in and out unchanged,
+//! the same as it does in the `.gml` files next to it. That's also why this isn't the `toml` crate: TOML has to be
+//! UTF-8, and a `\u00E9` escape there means the character é rather than the byte 0xE9 it stands for here.
+
+use gm8exe::asset::PascalString;
+
+pub enum Value {
+    Str(Vec<u8>),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Ints(Vec<i64>),
+}
+
+#[derive(Default)]
+pub struct Writer(Vec<u8>);
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn header(&mut self, header: String) -> &mut Self {
+        if !self.0.is_empty() {
+            self.0.push(b'\n');
+        }
+        self.0.extend_from_slice(header.as_bytes());
+        self
+    }
+
+    pub fn table(&mut self, name: &str) -> &mut Self {
+        self.header(format!("[{}]\n", name))
+    }
+
+    pub fn array_table(&mut self, name: &str) -> &mut Self {
+        self.header(format!("[[{}]]\n", name))
+    }
+
+    fn key(&mut self, key: &str) {
+        self.0.extend_from_slice(key.as_bytes());
+        self.0.extend_from_slice(b" = ");
+    }
+
+    pub fn str(&mut self, key: &str, value: &[u8]) -> &mut Self {
+        self.key(key);
+        self.0.push(b'"');
+        for &byte in value {
+            match byte {
+                b'\\' => self.0.extend_from_slice(b"\\\\"),
+                b'"' => self.0.extend_from_slice(b"\\\""),
+                b'\n' => self.0.extend_from_slice(b"\\n"),
+                b'\r' => self.0.extend_from_slice(b"\\r"),
+                b'\t' => self.0.extend_from_slice(b"\\t"),
+                0..=0x1F | 0x7F => self.0.extend_from_slice(format!("\\u{:04X}", byte).as_bytes()),
+                _ => self.0.push(byte),
+            }
+        }
+        self.0.extend_from_slice(b"\"\n");
+        self
+    }
+
+    pub fn int(&mut self, key: &str, value: impl Into<i64>) -> &mut Self {
+        self.key(key);
+        self.0.extend_from_slice(format!("{}\n", value.into()).as_bytes());
+        self
+    }
+
+    pub fn float(&mut self, key: &str, value: f64) -> &mut Self {
+        self.key(key);
+        let text = match value {
+            x if x.is_nan() => "nan".into(),
+            x if x.is_infinite() => if x > 0.0 { "inf" } else { "-inf" }.into(),
+            x => format!("{:?}", x), // always has a '.' or an exponent, and reads back exactly
+        };
+        self.0.extend_from_slice(text.as_bytes());
+        self.0.push(b'\n');
+        self
+    }
+
+    pub fn bool(&mut self, key: &str, value: bool) -> &mut Self {
+        self.key(key);
+        self.0.extend_from_slice(if value { b"true\n" } else { b"false\n" });
+        self
+    }
+
+    pub fn ints(&mut self, key: &str, values: impl IntoIterator<Item = i64>) -> &mut Self {
+        self.key(key);
+        let values = values.into_iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        self.0.extend_from_slice(format!("[{}]\n", values.join(", ")).as_bytes());
+        self
+    }
+
+    /// Adds a comment to the end of the last line, such as the name of an asset an index refers to.
+    pub fn note(&mut self, note: &[u8]) -> &mut Self {
+        if !note.is_empty() && !note.iter().any(|b| b.is_ascii_control()) && self.0.last() == Some(&b'\n') {
+            self.0.pop();
+            self.0.extend_from_slice(b" # ");
+            self.0.extend_from_slice(note);
+            self.0.push(b'\n');
+        }
+        self
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+pub struct Table {
+    name: String,
+    entries: Vec<(String, Value)>,
+}
+
+impl Table {
+    fn get(&self, key: &str) -> Result<&Value, String> {
+        self.entries
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+            .ok_or_else(|| format!("missing key '{}' in [{}]", key, self.name))
+    }
+
+    fn mismatch(&self, key: &str, expected: &str) -> String {
+        format!("key '{}' in [{}] should be {}", key, self.name, expected)
+    }
+
+    pub fn has(&self, key: &str) -> bool {
+        self.entries.iter().any(|(k, _)| k == key)
+    }
+
+    pub fn bytes(&self, key: &str) -> Result<&[u8], String> {
+        match self.get(key)? {
+            Value::Str(s) => Ok(s),
+            _ => Err(self.mismatch(key, "a string")),
+        }
+    }
+
+    pub fn str(&self, key: &str) -> Result<PascalString, String> {
+        self.bytes(key).map(|s| PascalString(s.into()))
+    }
+
+    pub fn int<T: TryFrom<i64>>(&self, key: &str) -> Result<T, String> {
+        match self.get(key)? {
+            Value::Int(i) => T::try_from(*i).map_err(|_| self.mismatch(key, "in range")),
+            _ => Err(self.mismatch(key, "an integer")),
+        }
+    }
+
+    pub fn float(&self, key: &str) -> Result<f64, String> {
+        match self.get(key)? {
+            Value::Float(f) => Ok(*f),
+            Value::Int(i) => Ok(*i as f64),
+            _ => Err(self.mismatch(key, "a number")),
+        }
+    }
+
+    pub fn bool(&self, key: &str) -> Result<bool, String> {
+        match self.get(key)? {
+            Value::Bool(b) => Ok(*b),
+            _ => Err(self.mismatch(key, "true or false")),
+        }
+    }
+
+    pub fn ints<T: TryFrom<i64>>(&self, key: &str) -> Result<Vec<T>, String> {
+        match self.get(key)? {
+            Value::Ints(v) => v.iter().map(|i| T::try_from(*i).map_err(|_| self.mismatch(key, "in range"))).collect(),
+            _ => Err(self.mismatch(key, "a list of integers")),
+        }
+    }
+}
+
+pub struct Document {
+    tables: Vec<Table>,
+}
+
+impl Document {
+    /// The keys before any table header.
+    pub fn root(&self) -> &Table {
+        &self.tables[0]
+    }
+
+    pub fn table(&self, name: &str) -> Result<&Table, String> {
+        self.tables.iter().find(|t| t.name == name).ok_or_else(|| format!("missing table [{}]", name))
+    }
+
+    pub fn tables<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Table> + 'a {
+        self.tables.iter().filter(move |t| t.name == name)
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        let mut tables = vec![Table { name: String::new(), entries: Vec::new() }];
+        for (i, line) in data.split(|b| *b == b'\n').enumerate() {
+            let line = trim(line);
+            if line.is_empty() || line[0] == b'#' {
+                continue
+            }
+            let error = |msg: &str| format!("line {}: {}", i + 1, msg);
+            if line[0] == b'[' {
+                let name = match line.strip_prefix(b"[[") {
+                    Some(l) => l.strip_suffix(b"]]"),
+                    None => line[1..].strip_suffix(b"]"),
+                }
+                .filter(|name| !name.iter().any(|b| matches!(b, b'[' | b']')))
+                .ok_or_else(|| error("unclosed table header"))?;
+                let name = String::from_utf8(trim(name).to_vec()).map_err(|_| error("bad table name"))?;
+                tables.push(Table { name, entries: Vec::new() });
+                continue
+            }
+            let eq = line.iter().position(|b| *b == b'=').ok_or_else(|| error("expected key = value"))?;
+            let key = String::from_utf8(trim(&line[..eq]).to_vec()).map_err(|_| error("bad key"))?;
+            let value = parse_value(trim(&line[eq + 1..])).map_err(|e| error(&e))?;
+            tables.last_mut().unwrap().entries.push((key, value));
+        }
+        Ok(Self { tables })
+    }
+}
+
+fn trim(mut text: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = text {
+        if !first.is_ascii_whitespace() {
+            break
+        }
+        text = rest;
+    }
+    while let [rest @ .., last] = text {
+        if !last.is_ascii_whitespace() {
+            break
+        }
+        text = rest;
+    }
+    text
+}
+
+fn parse_value(text: &[u8]) -> Result<Value, String> {
+    if let Some(rest) = text.strip_prefix(b"\"") {
+        let mut out = Vec::new();
+        let mut iter = rest.iter();
+        loop {
+            match iter.next() {
+                Some(b'"') => break,
+                Some(b'\\') => match iter.next() {
+                    Some(b'\\') => out.push(b'\\'),
+                    Some(b'"') => out.push(b'"'),
+                    Some(b'n') => out.push(b'\n'),
+                    Some(b'r') => out.push(b'\r'),
+                    Some(b't') => out.push(b'\t'),
+                    Some(b'u') => {
+                        let hex = iter.by_ref().take(4).copied().collect::<Vec<u8>>();
+                        let code = std::str::from_utf8(&hex)
+                            .ok()
+                            .and_then(|h| u32::from_str_radix(h, 16).ok())
+                            .filter(|c| *c < 0x100)
+                            .ok_or("bad \\u escape")?;
+                        out.push(code as u8);
+                    },
+                    _ => return Err("bad escape".into()),
+                },
+                Some(byte) => out.push(*byte),
+                None => return Err("unterminated string".into()),
+            }
+        }
+        let rest = trim(iter.as_slice());
+        if !rest.is_empty() && rest[0] != b'#' {
+            return Err("unexpected text after string".into())
+        }
+        return Ok(Value::Str(out))
+    }
+
+    // anything else can have a comment after it
+    let text = match text.iter().position(|b| *b == b'#') {
+        Some(pos) => trim(&text[..pos]),
+        None => text,
+    };
+    let text = std::str::from_utf8(text).map_err(|_| "bad value")?;
+    match text {
+        "true" => return Ok(Value::Bool(true)),
+        "false" => return Ok(Value::Bool(false)),
+        _ => (),
+    }
+    if let Some(list) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        return list
+            .split(',')
+            .map(str::trim)
+            .filter(|x| !x.is_empty())
+            .map(|x| x.parse().map_err(|_| format!("bad integer '{}'", x)))
+            .collect::<Result<_, _>>()
+            .map(Value::Ints)
+    }
+    if let Ok(i) = text.parse() {
+        return Ok(Value::Int(i))
+    }
+    text.parse().map(Value::Float).map_err(|_| format!("bad value '{}'", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_one(line: &[u8]) -> Result<Value, String> {
+        let mut data = b"x = ".to_vec();
+        data.extend_from_slice(line);
+        Document::parse(&data).map(|mut doc| doc.tables.remove(0).entries.remove(0).1)
+    }
+
+    #[test]
+    fn strings() {
+        let raw = b"tab\t \"quoted\" \\ \x01\x7F \xE9\xFF\r\n";
+        let mut w = Writer::new();
+        w.str("s", raw).note(b"a note");
+        let data = w.finish();
+        assert_eq!(data, b"s = \"tab\\t \\\"quoted\\\" \\\\ \\u0001\\u007F \xE9\xFF\\r\\n\" # a note\n");
+        let doc = Document::parse(&data).unwrap();
+        assert_eq!(doc.root().bytes("s").unwrap(), raw);
+
+        assert!(matches!(parse_one(br#""\u00e9\u0000""#), Ok(Value::Str(s)) if s == b"\xE9\0"));
+        assert!(matches!(parse_one(br#""a # b" # c"#), Ok(Value::Str(s)) if s == b"a # b"));
+        assert_eq!(parse_one(br#""\u0100""#).err().unwrap(), "line 1: bad \\u escape");
+        assert_eq!(parse_one(br#""\u12""#).err().unwrap(), "line 1: bad \\u escape");
+        assert_eq!(parse_one(br#""\q""#).err().unwrap(), "line 1: bad escape");
+        assert_eq!(parse_one(br#""open"#).err().unwrap(), "line 1: unterminated string");
+        assert_eq!(parse_one(br#""a" "b""#).err().unwrap(), "line 1: unexpected text after string");
+        assert_eq!(parse_one(br#""a"b"#).err().unwrap(), "line 1: unexpected text after string");
+    }
+
+    #[test]
+    fn numbers() {
+        let mut w = Writer::new();
+        w.float("nan", f64::NAN)
+            .float("inf", f64::INFINITY)
+            .float("ninf", f64::NEG_INFINITY)
+            .float("whole", 3.0)
+            .float("tiny", 1e-300)
+            .int("int", -5)
+            .note(b"five")
+            .ints("ints", [1, -2, 3])
+            .note(b"\xE9")
+            .bool("yes", true)
+            .note(b"line\nbreak");
+        let data = w.finish();
+        assert!(data.ends_with(b"ints = [1, -2, 3] # \xE9\nyes = true\n"));
+        let doc = Document::parse(&data).unwrap();
+        let t = doc.root();
+        assert!(t.float("nan").unwrap().is_nan());
+        assert_eq!((t.float("inf").unwrap(), t.float("ninf").unwrap()), (f64::INFINITY, f64::NEG_INFINITY));
+        assert_eq!((t.float("whole").unwrap(), t.float("tiny").unwrap()), (3.0, 1e-300));
+        assert_eq!((t.int::<i32>("int").unwrap(), t.float("int").unwrap()), (-5, -5.0));
+        assert_eq!(t.ints::<i32>("ints").unwrap(), [1, -2, 3]);
+        assert!(t.bool("yes").unwrap());
+
+        assert_eq!(t.int::<u32>("int").err().unwrap(), "key 'int' in [] should be in range");
+        assert_eq!(t.int::<i32>("whole").err().unwrap(), "key 'whole' in [] should be an integer");
+        assert_eq!(t.bool("missing").err().unwrap(), "missing key 'missing' in []");
+        assert_eq!(parse_one(b"[1, x]").err().unwrap(), "line 1: bad integer 'x'");
+        assert_eq!(parse_one(b"yes").err().unwrap(), "line 1: bad value 'yes'");
+    }
+
+    #[test]
+    fn tables() {
+        let data = b"# comment\r\na = 1\r\n\r\n[one]\nb = 2\n[[many]]\nc = 3\n[[many]]\nc = 4\n";
+        let doc = Document::parse(data).unwrap();
+        assert_eq!(doc.root().int::<i32>("a").unwrap(), 1);
+        assert_eq!(doc.table("one").unwrap().int::<i32>("b").unwrap(), 2);
+        assert_eq!(doc.tables("many").map(|t| t.int::<i32>("c").unwrap()).collect::<Vec<_>>(), [3, 4]);
+        assert!(!doc.root().has("b"));
+        assert_eq!(doc.table("two").err().unwrap(), "missing table [two]");
+
+        assert_eq!(Document::parse(b"a = 1\n[open\n").err().unwrap(), "line 2: unclosed table header");
+        assert_eq!(Document::parse(b"[[open]\n").err().unwrap(), "line 1: unclosed table header");
+        assert_eq!(Document::parse(b"a = 1\njust words\n").err().unwrap(), "line 2: expected key = value");
+        assert_eq!(Document::parse(b"\xFF = 1\n").err().unwrap(), "line 1: bad key");
+    }
+}
@@ -1,10 +1,14 @@
-#[cfg(not(all(target_os = "windows", target_arch = "x86")))]
-compile_error!("this crate cannot be built for a target other than windows i686");
+#[cfg(not(all(any(target_os = "windows", target_os = "linux"), target_arch = "x86")))]
+compile_error!("this crate cannot be built for a target other than windows or linux i686");
 
 #[path = "../../gm8emulator/src/game/external/dll.rs"]
 mod dll;
+#[cfg(target_os = "windows")]
 #[path = "../../gm8emulator/src/game/external/win32.rs"]
-mod win32;
+mod native;
+#[cfg(target_os = "linux")]
+#[path = "../../gm8emulator/src/game/external/pe.rs"]
+mod native;
 #[path = "../../gm8emulator/src/handleman.rs"]
 #[allow(dead_code)]
 mod handleman;
@@ -14,8 +18,8 @@ use handleman::{HandleList, HandleManager};
 use std::{env, io::{self, Read, Write}};
 
 struct Manager {
-    externals: HandleList<win32::NativeExternal>,
-    manager: win32::NativeManager,
+    externals: HandleList<native::NativeExternal>,
+    manager: native::NativeManager,
 }
 
 impl Manager {
@@ -33,6 +37,7 @@ impl Manager {
     }
 }
 
+#[cfg(target_os = "windows")]
 fn pause() {
     extern "C" {
         fn _getch() -> std::os::raw::c_int;
@@ -41,10 +46,31 @@ fn pause() {
     let _ = unsafe { _getch() };
 }
 
+#[cfg(target_os = "linux")]
+fn pause() {}
+
+/// Takes stdout for the protocol, so that anything a DLL prints goes to stderr instead of corrupting it.
+#[cfg(target_os = "linux")]
+fn protocol_output() -> io::Result<std::fs::File> {
+    use std::os::unix::io::FromRawFd;
+    unsafe {
+        let fd = libc::dup(1);
+        if fd < 0 || libc::dup2(2, 1) < 0 {
+            return Err(io::Error::last_os_error())
+        }
+        Ok(std::fs::File::from_raw_fd(fd))
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn protocol_output() -> io::Result<io::Stdout> {
+    Ok(io::stdout())
+}
+
 fn main() -> io::Result<()> {
-    let mut manager = Manager { externals: HandleList::new(), manager: win32::NativeManager::new() };
+    let mut manager = Manager { externals: HandleList::new(), manager: native::NativeManager::new() };
     let mut stdin = io::stdin();
-    let mut stdout = io::stdout();
+    let mut stdout = protocol_output()?;
 
     match env::args().nth(1).and_then(|s| s.parse::<u16>().ok()) {
         None => {
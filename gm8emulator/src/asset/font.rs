@@ -9,6 +9,8 @@ use crate::{
 use encoding_rs::Encoding;
 use serde::{Deserialize, Serialize};
 
+mod truetype;
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Font {
     pub name: gml::String,
@@ -36,25 +38,70 @@ impl Font {
         if let Some(index) = index.checked_sub(self.first) { self.chars.get(index as usize).copied() } else { None }
     }
 
+    /// Frees the textures belonging to this font's characters, if it owns them.
+    pub fn delete_graphics(&self, renderer: &mut Renderer) {
+        if self.own_graphics {
+            for c in self.chars.iter() {
+                renderer.delete_sprite(c.atlas_ref);
+            }
+        }
+    }
+
     pub fn get_encoding(&self, default: &'static Encoding) -> &'static Encoding {
-        match self.charset {
-            0x00 => encoding_rs::WINDOWS_1252, // ANSI_CHARSET
-            0x80 => encoding_rs::SHIFT_JIS,    // SHIFTJIS_CHARSET
-            0x81 => encoding_rs::EUC_KR,       // HANGUL_CHARSET
-            0x82 => default,                   // JOHAB_CHARSET
-            0x86 => encoding_rs::GBK,          // GB2312_CHARSET
-            0x88 => encoding_rs::BIG5,         // CHINESEBIG5_CHARSET
-            0xA1 => encoding_rs::WINDOWS_1253, // GREEK_CHARSET
-            0xA2 => encoding_rs::WINDOWS_1254, // TURKISH_CHARSET
-            0xA3 => encoding_rs::WINDOWS_1258, // VIETNAMESE_CHARSET
-            0xB1 => encoding_rs::WINDOWS_1255, // HEBREW_CHARSET
-            0xB2 => encoding_rs::WINDOWS_1256, // ARABIC_CHARSET
-            0xBA => encoding_rs::WINDOWS_1257, // BALTIC_CHARSET
-            0xCC => encoding_rs::WINDOWS_1251, // RUSSIAN_CHARSET
-            0xDE => encoding_rs::WINDOWS_874,  // THAI_CHARSET
-            0xEE => encoding_rs::WINDOWS_1250, // EASTEUROPE_CHARSET
-            _ => default,
+        charset_encoding(self.charset).unwrap_or(default)
+    }
+
+    /// Rasterises characters `first..=last` of the installed TrueType font `sys_name`, like GM8 does with GDI.
+    /// If `installed` is false, or there's no such font, the bundled fallback font is used instead.
+    pub fn rasterise(
+        &mut self,
+        default_encoding: &'static Encoding,
+        installed: bool,
+        renderer: &mut Renderer,
+    ) -> Result<(), String> {
+        let font = installed
+            .then(|| truetype::find_system_font(&self.sys_name.decode(default_encoding), self.bold, self.italic))
+            .flatten()
+            .unwrap_or_else(truetype::fallback_font);
+        let encoding = self.get_encoding(default_encoding);
+        // GM8 asks GDI for a font `size` points tall at 96 DPI
+        let em_size = (self.size as f32 * 96.0 / 72.0).round();
+        let mut chars = Vec::with_capacity(usize::from(self.last.saturating_sub(self.first)) + 1);
+        let mut tallest_char_height = 0;
+        for byte in self.first..=self.last {
+            let bytes = [byte];
+            let (text, _, _) = encoding.decode(&bytes);
+            let glyph = font.rasterise(text.chars().next().unwrap_or('\0'), em_size);
+            tallest_char_height = tallest_char_height.max(glyph.height);
+            let data = glyph.data.iter().flat_map(|&alpha| [0xFF, 0xFF, 0xFF, alpha]).collect::<Vec<_>>();
+            let atlas_ref =
+                renderer.upload_sprite(data.into_boxed_slice(), glyph.width as _, glyph.height as _, 0, 0)?;
+            chars.push(Character { offset: glyph.offset, distance: glyph.distance, atlas_ref });
         }
+        self.chars = chars.into_boxed_slice();
+        self.tallest_char_height = tallest_char_height;
+        Ok(())
+    }
+}
+
+/// The code page for a Windows font charset, if it has one of its own.
+pub fn charset_encoding(charset: u32) -> Option<&'static Encoding> {
+    match charset {
+        0x00 => Some(encoding_rs::WINDOWS_1252), // ANSI_CHARSET
+        0x80 => Some(encoding_rs::SHIFT_JIS),    // SHIFTJIS_CHARSET
+        0x81 => Some(encoding_rs::EUC_KR),       // HANGUL_CHARSET
+        0x86 => Some(encoding_rs::GBK),          // GB2312_CHARSET
+        0x88 => Some(encoding_rs::BIG5),         // CHINESEBIG5_CHARSET
+        0xA1 => Some(encoding_rs::WINDOWS_1253), // GREEK_CHARSET
+        0xA2 => Some(encoding_rs::WINDOWS_1254), // TURKISH_CHARSET
+        0xA3 => Some(encoding_rs::WINDOWS_1258), // VIETNAMESE_CHARSET
+        0xB1 => Some(encoding_rs::WINDOWS_1255), // HEBREW_CHARSET
+        0xB2 => Some(encoding_rs::WINDOWS_1256), // ARABIC_CHARSET
+        0xBA => Some(encoding_rs::WINDOWS_1257), // BALTIC_CHARSET
+        0xCC => Some(encoding_rs::WINDOWS_1251), // RUSSIAN_CHARSET
+        0xDE => Some(encoding_rs::WINDOWS_874),  // THAI_CHARSET
+        0xEE => Some(encoding_rs::WINDOWS_1250), // EASTEUROPE_CHARSET
+        _ => None,                               // DEFAULT_CHARSET, SYMBOL_CHARSET, JOHAB_CHARSET etc.
     }
 }
 
@@ -0,0 +1,648 @@
+//! A minimal TrueType reader and rasteriser, used to make fonts at runtime with font_add.
+//! Only what's needed for GM8 fonts is supported: the `glyf` outline format, cmap formats 4 and 12,
+//! and the metrics GDI uses to lay out characters.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// A parsed TrueType font file.
+pub struct TrueType {
+    data: Vec<u8>,
+    units_per_em: u16,
+    long_loca: bool,
+    glyph_count: u16,
+    hmetric_count: u16,
+    ascent: i16,
+    descent: i16,
+    cmap: usize,
+    glyf: usize,
+    loca: usize,
+    hmtx: usize,
+}
+
+/// A rasterised glyph as an 8-bit coverage map, laid out like a GM8 font character.
+pub struct Glyph {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+    /// Horizontal distance from the pen position to the left edge of the image.
+    pub distance: i32,
+    /// How far to advance the pen after drawing.
+    pub offset: i32,
+}
+
+#[derive(Clone, Copy)]
+struct Point {
+    x: f32,
+    y: f32,
+    on_curve: bool,
+}
+
+fn read_u16(data: &[u8], pos: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(pos..pos + 2)?.try_into().ok()?))
+}
+
+fn read_i16(data: &[u8], pos: usize) -> Option<i16> {
+    read_u16(data, pos).map(|x| x as i16)
+}
+
+fn read_u32(data: &[u8], pos: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(pos..pos + 4)?.try_into().ok()?))
+}
+
+fn find_table(data: &[u8], tag: &[u8; 4]) -> Option<usize> {
+    let count = read_u16(data, 4)?;
+    (0..usize::from(count))
+        .map(|i| 12 + i * 16)
+        .find(|&entry| data.get(entry..entry + 4) == Some(tag))
+        .and_then(|entry| read_u32(data, entry + 8))
+        .map(|offset| offset as usize)
+        .filter(|&offset| offset < data.len())
+}
+
+impl TrueType {
+    pub fn parse(data: Vec<u8>) -> Option<Self> {
+        // TrueType outlines only, CFF-flavoured OpenType ("OTTO") isn't supported
+        if read_u32(&data, 0)? != 0x00010000 && data.get(0..4)? != b"true" {
+            return None
+        }
+        let head = find_table(&data, b"head")?;
+        let maxp = find_table(&data, b"maxp")?;
+        let hhea = find_table(&data, b"hhea")?;
+        let (ascent, descent) = match find_table(&data, b"OS/2") {
+            // GDI uses the Windows metrics for tmAscent and tmDescent
+            Some(os2) => (read_u16(&data, os2 + 74)? as i16, read_u16(&data, os2 + 76)? as i16),
+            None => (read_i16(&data, hhea + 4)?, -read_i16(&data, hhea + 6)?),
+        };
+        Some(Self {
+            units_per_em: read_u16(&data, head + 18)?.max(1),
+            long_loca: read_i16(&data, head + 50)? != 0,
+            glyph_count: read_u16(&data, maxp + 4)?,
+            hmetric_count: read_u16(&data, hhea + 34)?,
+            ascent,
+            descent,
+            cmap: find_table(&data, b"cmap")?,
+            glyf: find_table(&data, b"glyf")?,
+            loca: find_table(&data, b"loca")?,
+            hmtx: find_table(&data, b"hmtx")?,
+            data,
+        })
+    }
+
+    /// Returns the family name and whether the font is bold and italic, according to the `name` and `head` tables.
+    pub fn style(&self) -> Option<(String, bool, bool)> {
+        let head = find_table(&self.data, b"head")?;
+        let mac_style = read_u16(&self.data, head + 44)?;
+        let name = find_table(&self.data, b"name")?;
+        let count = read_u16(&self.data, name + 2)?;
+        let strings = name + usize::from(read_u16(&self.data, name + 4)?);
+        let mut family = None;
+        for i in 0..usize::from(count) {
+            let record = name + 6 + i * 12;
+            let platform = read_u16(&self.data, record)?;
+            let name_id = read_u16(&self.data, record + 6)?;
+            if name_id != 1 {
+                continue
+            }
+            let len = usize::from(read_u16(&self.data, record + 8)?);
+            let start = strings + usize::from(read_u16(&self.data, record + 10)?);
+            let bytes = self.data.get(start..start + len)?;
+            match platform {
+                // Windows and Unicode platform names are UTF-16BE
+                0 | 3 => {
+                    let chars = bytes.chunks_exact(2).map(|c| u16::from_be_bytes([c[0], c[1]])).collect::<Vec<_>>();
+                    family = Some(String::from_utf16_lossy(&chars));
+                    break
+                },
+                1 if family.is_none() => family = Some(bytes.iter().map(|&b| char::from(b)).collect()),
+                _ => (),
+            }
+        }
+        Some((family?, mac_style & 1 != 0, mac_style & 2 != 0))
+    }
+
+    fn scale(&self, size: f32) -> f32 {
+        size / f32::from(self.units_per_em)
+    }
+
+    /// Height in pixels of a line of text with the given em size, which is the height of every character image.
+    pub fn line_height(&self, size: f32) -> u32 {
+        let scale = self.scale(size);
+        ((f32::from(self.ascent) * scale).round() + (f32::from(self.descent) * scale).round()).max(1.0) as u32
+    }
+
+    pub fn glyph_index(&self, c: char) -> u16 {
+        self.lookup_cmap(u32::from(c)).unwrap_or(0)
+    }
+
+    fn lookup_cmap(&self, code: u32) -> Option<u16> {
+        let data = &self.data;
+        let count = read_u16(data, self.cmap + 2)?;
+        let mut subtables = (0..usize::from(count))
+            .filter_map(|i| {
+                let record = self.cmap + 4 + i * 8;
+                let platform = read_u16(data, record)?;
+                let encoding = read_u16(data, record + 2)?;
+                let offset = self.cmap + read_u32(data, record + 4)? as usize;
+                // Unicode subtables only, preferring the full-repertoire ones
+                match (platform, encoding) {
+                    (3, 10) | (0, 4) | (0, 6) => Some((0, offset)),
+                    (3, 1) | (0, _) => Some((1, offset)),
+                    (3, 0) => Some((2, offset)),
+                    _ => None,
+                }
+            })
+            .collect::<Vec<_>>();
+        subtables.sort_by_key(|(priority, _)| *priority);
+        subtables.into_iter().find_map(|(_, table)| match read_u16(data, table)? {
+            4 => self.lookup_cmap_4(table, code),
+            12 => self.lookup_cmap_12(table, code),
+            _ => None,
+        })
+    }
+
+    fn lookup_cmap_4(&self, table: usize, code: u32) -> Option<u16> {
+        let data = &self.data;
+        let code = u16::try_from(code).ok()?;
+        let segments = usize::from(read_u16(data, table + 6)? / 2);
+        let end_codes = table + 14;
+        let start_codes = end_codes + segments * 2 + 2;
+        let deltas = start_codes + segments * 2;
+        let range_offsets = deltas + segments * 2;
+        for i in 0..segments {
+            if read_u16(data, end_codes + i * 2)? < code {
+                continue
+            }
+            let start = read_u16(data, start_codes + i * 2)?;
+            if start > code {
+                return None
+            }
+            let delta = read_u16(data, deltas + i * 2)?;
+            let range_offset = read_u16(data, range_offsets + i * 2)?;
+            if range_offset == 0 {
+                return Some(code.wrapping_add(delta))
+            }
+            let pos = range_offsets + i * 2 + usize::from(range_offset) + usize::from(code - start) * 2;
+            return match read_u16(data, pos)? {
+                0 => None,
+                glyph => Some(glyph.wrapping_add(delta)),
+            }
+        }
+        None
+    }
+
+    fn lookup_cmap_12(&self, table: usize, code: u32) -> Option<u16> {
+        let groups = read_u32(&self.data, table + 12)? as usize;
+        (0..groups).find_map(|i| {
+            let group = table + 16 + i * 12;
+            let start = read_u32(&self.data, group)?;
+            let end = read_u32(&self.data, group + 4)?;
+            if (start..=end).contains(&code) {
+                u16::try_from(read_u32(&self.data, group + 8)? + (code - start)).ok()
+            } else {
+                None
+            }
+        })
+    }
+
+    fn advance_width(&self, glyph: u16) -> u16 {
+        let index = usize::from(glyph.min(self.hmetric_count.saturating_sub(1)));
+        read_u16(&self.data, self.hmtx + index * 4).unwrap_or(0)
+    }
+
+    fn glyph_range(&self, glyph: u16) -> Option<(usize, usize)> {
+        if glyph >= self.glyph_count {
+            return None
+        }
+        let glyph = usize::from(glyph);
+        let (start, end) = if self.long_loca {
+            (read_u32(&self.data, self.loca + glyph * 4)? as usize, read_u32(&self.data, self.loca + glyph * 4 + 4)? as usize)
+        } else {
+            (
+                usize::from(read_u16(&self.data, self.loca + glyph * 2)?) * 2,
+                usize::from(read_u16(&self.data, self.loca + glyph * 2 + 2)?) * 2,
+            )
+        };
+        if end > start { Some((self.glyf + start, self.glyf + end)) } else { None }
+    }
+
+    /// Reads a glyph's contours in font units, following composite glyphs up to a sensible depth.
+    fn outline(&self, glyph: u16, depth: u32, contours: &mut Vec<Vec<Point>>) -> Option<()> {
+        let (start, end) = match self.glyph_range(glyph) {
+            Some(range) => range,
+            None => return Some(()), // empty glyph, such as a space
+        };
+        let data = self.data.get(start..end)?;
+        let contour_count = read_i16(data, 0)?;
+        if contour_count >= 0 {
+            self.simple_outline(data, contour_count as usize, contours)
+        } else if depth < 8 {
+            self.composite_outline(data, depth, contours)
+        } else {
+            None
+        }
+    }
+
+    fn simple_outline(&self, data: &[u8], contour_count: usize, contours: &mut Vec<Vec<Point>>) -> Option<()> {
+        let end_points =
+            (0..contour_count).map(|i| read_u16(data, 10 + i * 2).map(usize::from)).collect::<Option<Vec<_>>>()?;
+        let point_count = end_points.last().map(|&x| x + 1).unwrap_or(0);
+        let instruction_len = usize::from(read_u16(data, 10 + contour_count * 2)?);
+        let mut pos = 12 + contour_count * 2 + instruction_len;
+
+        let mut flags = Vec::with_capacity(point_count);
+        while flags.len() < point_count {
+            let flag = *data.get(pos)?;
+            pos += 1;
+            flags.push(flag);
+            if flag & 8 != 0 {
+                let repeat = *data.get(pos)?;
+                pos += 1;
+                flags.extend(std::iter::repeat(flag).take(usize::from(repeat)));
+            }
+        }
+        flags.truncate(point_count);
+
+        let mut read_coords = |short_bit: u8, same_bit: u8| -> Option<Vec<i32>> {
+            let mut value = 0i32;
+            flags
+                .iter()
+                .map(|&flag| {
+                    if flag & short_bit != 0 {
+                        let delta = i32::from(*data.get(pos)?);
+                        pos += 1;
+                        value += if flag & same_bit != 0 { delta } else { -delta };
+                    } else if flag & same_bit == 0 {
+                        value += i32::from(read_i16(data, pos)?);
+                        pos += 2;
+                    }
+                    Some(value)
+                })
+                .collect()
+        };
+        let xs = read_coords(2, 16)?;
+        let ys = read_coords(4, 32)?;
+
+        let mut first = 0;
+        for end in end_points {
+            let points = (first..=end)
+                .map(|i| Some(Point { x: *xs.get(i)? as f32, y: *ys.get(i)? as f32, on_curve: flags.get(i)? & 1 != 0 }))
+                .collect::<Option<Vec<_>>>()?;
+            contours.push(points);
+            first = end + 1;
+        }
+        Some(())
+    }
+
+    fn composite_outline(&self, data: &[u8], depth: u32, contours: &mut Vec<Vec<Point>>) -> Option<()> {
+        const ARGS_ARE_WORDS: u16 = 0x1;
+        const ARGS_ARE_XY: u16 = 0x2;
+        const HAS_SCALE: u16 = 0x8;
+        const MORE_COMPONENTS: u16 = 0x20;
+        const HAS_XY_SCALE: u16 = 0x40;
+        const HAS_2X2: u16 = 0x80;
+
+        let f2dot14 = |pos: usize| read_i16(data, pos).map(|x| f32::from(x) / 16384.0);
+        let mut pos = 10;
+        loop {
+            let flags = read_u16(data, pos)?;
+            let glyph = read_u16(data, pos + 2)?;
+            pos += 4;
+            let (dx, dy) = if flags & ARGS_ARE_WORDS != 0 {
+                pos += 4;
+                (read_i16(data, pos - 4)?, read_i16(data, pos - 2)?)
+            } else {
+                pos += 2;
+                (i16::from(*data.get(pos - 2)? as i8), i16::from(*data.get(pos - 1)? as i8))
+            };
+            let (a, b, c, d) = if flags & HAS_SCALE != 0 {
+                pos += 2;
+                let s = f2dot14(pos - 2)?;
+                (s, 0.0, 0.0, s)
+            } else if flags & HAS_XY_SCALE != 0 {
+                pos += 4;
+                (f2dot14(pos - 4)?, 0.0, 0.0, f2dot14(pos - 2)?)
+            } else if flags & HAS_2X2 != 0 {
+                pos += 8;
+                (f2dot14(pos - 8)?, f2dot14(pos - 6)?, f2dot14(pos - 4)?, f2dot14(pos - 2)?)
+            } else {
+                (1.0, 0.0, 0.0, 1.0)
+            };
+            // point-matched components are rare in fonts GM games use, so they're placed without an offset
+            let (dx, dy) = if flags & ARGS_ARE_XY != 0 { (f32::from(dx), f32::from(dy)) } else { (0.0, 0.0) };
+
+            let mut component = Vec::new();
+            self.outline(glyph, depth + 1, &mut component)?;
+            contours.extend(component.into_iter().map(|contour| {
+                contour
+                    .into_iter()
+                    .map(|p| Point { x: p.x * a + p.y * c + dx, y: p.x * b + p.y * d + dy, on_curve: p.on_curve })
+                    .collect()
+            }));
+
+            if flags & MORE_COMPONENTS == 0 {
+                break Some(())
+            }
+        }
+    }
+
+    /// Rasterises a character at the given em size in pixels, into an image as tall as a line of text.
+    pub fn rasterise(&self, c: char, size: f32) -> Glyph {
+        let scale = self.scale(size);
+        let glyph = self.glyph_index(c);
+        let offset = (f32::from(self.advance_width(glyph)) * scale).round() as i32;
+        let height = self.line_height(size);
+        let baseline = (f32::from(self.ascent) * scale).round();
+
+        let mut contours = Vec::new();
+        if self.outline(glyph, 0, &mut contours).is_none() {
+            contours.clear();
+        }
+        let points = contours.iter().flatten();
+        let (min_x, max_x) = points.fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), p| (min.min(p.x), max.max(p.x)));
+        if !min_x.is_finite() {
+            // nothing to draw, but GM8 still gives every character an image
+            return Glyph { width: 1, height, data: vec![0; height as usize], distance: 0, offset }
+        }
+        let left = (min_x * scale).floor();
+        let width = ((max_x * scale).ceil() - left).max(1.0) as u32;
+
+        let mut raster = Raster::new(width as usize, height as usize);
+        let transform = |p: Point| (p.x * scale - left, baseline - p.y * scale);
+        for contour in &contours {
+            for (p0, p1) in flatten(contour) {
+                raster.line(transform(p0), transform(p1));
+            }
+        }
+        Glyph { width, height, data: raster.finish(), distance: left as i32, offset }
+    }
+}
+
+/// Turns a closed quadratic contour into line segments.
+fn flatten(contour: &[Point]) -> Vec<(Point, Point)> {
+    let mut lines = Vec::new();
+    if contour.len() < 2 {
+        return lines
+    }
+    let midpoint = |a: Point, b: Point| Point { x: (a.x + b.x) / 2.0, y: (a.y + b.y) / 2.0, on_curve: true };
+
+    // start on an on-curve point, or an implied one if there isn't any
+    let start_index = contour.iter().position(|p| p.on_curve);
+    let start = match start_index {
+        Some(i) => contour[i],
+        None => midpoint(contour[0], contour[1]),
+    };
+    let rotate = start_index.map(|i| i + 1).unwrap_or(1);
+
+    let mut current = start;
+    let mut control: Option<Point> = None;
+    for i in 0..contour.len() {
+        let p = contour[(rotate + i) % contour.len()];
+        match (control, p.on_curve) {
+            (None, true) => {
+                lines.push((current, p));
+                current = p;
+            },
+            (None, false) => control = Some(p),
+            (Some(c), true) => {
+                curve(current, c, p, &mut lines);
+                current = p;
+                control = None;
+            },
+            (Some(c), false) => {
+                let mid = midpoint(c, p);
+                curve(current, c, mid, &mut lines);
+                current = mid;
+                control = Some(p);
+            },
+        }
+    }
+    match control {
+        Some(c) => curve(current, c, start, &mut lines),
+        None => lines.push((current, start)),
+    }
+    lines
+}
+
+fn curve(p0: Point, c: Point, p1: Point, lines: &mut Vec<(Point, Point)>) {
+    // subdivide based on how far the control point is from the chord, in font units
+    let dev = ((p0.x - 2.0 * c.x + p1.x).powi(2) + (p0.y - 2.0 * c.y + p1.y).powi(2)).sqrt();
+    let steps = ((dev / 16.0).sqrt().ceil() as usize).clamp(1, 16);
+    let mut prev = p0;
+    for i in 1..=steps {
+        let t = i as f32 / steps as f32;
+        let mt = 1.0 - t;
+        let next = Point {
+            x: mt * mt * p0.x + 2.0 * mt * t * c.x + t * t * p1.x,
+            y: mt * mt * p0.y + 2.0 * mt * t * c.y + t * t * p1.y,
+            on_curve: true,
+        };
+        lines.push((prev, next));
+        prev = next;
+    }
+}
+
+/// Antialiased scanline coverage using signed area accumulation.
+struct Raster {
+    width: usize,
+    height: usize,
+    acc: Vec<f32>,
+}
+
+impl Raster {
+    fn new(width: usize, height: usize) -> Self {
+        // one spare cell per row for coverage spilling past the right edge
+        Self { width, height, acc: vec![0.0; (width + 2) * height] }
+    }
+
+    fn line(&mut self, (x0, y0): (f32, f32), (x1, y1): (f32, f32)) {
+        if (y0 - y1).abs() <= f32::EPSILON {
+            return
+        }
+        let (dir, (x0, y0), (x1, y1)) = if y0 < y1 { (1.0, (x0, y0), (x1, y1)) } else { (-1.0, (x1, y1), (x0, y0)) };
+        let dxdy = (x1 - x0) / (y1 - y0);
+        let stride = self.width + 2;
+        let clamp_x = |x: f32| x.clamp(0.0, self.width as f32);
+
+        let mut x = x0;
+        if y0 < 0.0 {
+            x -= y0 * dxdy;
+        }
+        let first_row = y0.max(0.0) as usize;
+        let last_row = (y1.ceil().max(0.0) as usize).min(self.height);
+        for row in first_row..last_row {
+            let dy = ((row + 1) as f32).min(y1) - (row as f32).max(y0);
+            let x_next = x + dxdy * dy;
+            let d = dy * dir;
+            let (xa, xb) = if x < x_next { (clamp_x(x), clamp_x(x_next)) } else { (clamp_x(x_next), clamp_x(x)) };
+            let line = &mut self.acc[row * stride..(row + 1) * stride];
+            let xa_floor = xa.floor();
+            let xa_i = xa_floor as usize;
+            let xb_ceil = xb.ceil();
+            let xb_i = xb_ceil as usize;
+            if xb_i <= xa_i + 1 {
+                // the segment stays within one pixel on this row
+                let mid = 0.5 * (xa + xb) - xa_floor;
+                line[xa_i] += d - d * mid;
+                line[xa_i + 1] += d * mid;
+            } else {
+                let s = (xb - xa).recip();
+                let xa_frac = xa - xa_floor;
+                let a0 = 0.5 * s * (1.0 - xa_frac) * (1.0 - xa_frac);
+                let xb_frac = xb - xb_ceil + 1.0;
+                let am = 0.5 * s * xb_frac * xb_frac;
+                line[xa_i] += d * a0;
+                if xb_i == xa_i + 2 {
+                    line[xa_i + 1] += d * (1.0 - a0 - am);
+                } else {
+                    let a1 = s * (1.5 - xa_frac);
+                    line[xa_i + 1] += d * (a1 - a0);
+                    for cell in &mut line[xa_i + 2..xb_i - 1] {
+                        *cell += d * s;
+                    }
+                    let a2 = a1 + (xb_i - xa_i - 3) as f32 * s;
+                    line[xb_i - 1] += d * (1.0 - a2 - am);
+                }
+                line[xb_i] += d * am;
+            }
+            x = x_next;
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let stride = self.width + 2;
+        let mut out = Vec::with_capacity(self.width * self.height);
+        for row in self.acc.chunks_exact(stride) {
+            let mut total = 0.0;
+            for cell in &row[..self.width] {
+                total += cell;
+                out.push((total.abs().min(1.0) * 255.0).round() as u8);
+            }
+        }
+        out
+    }
+}
+
+/// Directories which are searched for installed fonts.
+fn font_directories() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if cfg!(windows) {
+        let windir = std::env::var_os("WINDIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("C:\\Windows"));
+        dirs.push(windir.join("Fonts"));
+    } else {
+        if let Some(home) = std::env::var_os("HOME").map(PathBuf::from) {
+            dirs.push(home.join(".local/share/fonts"));
+            dirs.push(home.join(".fonts"));
+        }
+        dirs.push(PathBuf::from("/usr/local/share/fonts"));
+        dirs.push(PathBuf::from("/usr/share/fonts"));
+        dirs.push(PathBuf::from("/Library/Fonts"));
+        dirs.push(PathBuf::from("/System/Library/Fonts"));
+    }
+    dirs
+}
+
+fn collect_font_files(dir: &Path, depth: u32, out: &mut Vec<PathBuf>) {
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.is_dir() {
+                if depth < 4 {
+                    collect_font_files(&path, depth + 1, out);
+                }
+            } else if path.extension().and_then(|e| e.to_str()).map(|e| e.eq_ignore_ascii_case("ttf")).unwrap_or(false)
+            {
+                out.push(path);
+            }
+        }
+    }
+}
+
+// Metric-compatible replacements for the core Windows fonts, for systems that don't have them
+const SUBSTITUTES: [(&str, &[&str]); 5] = [
+    ("arial", &["arimo", "liberation sans", "dejavu sans"]),
+    ("times new roman", &["tinos", "liberation serif", "dejavu serif"]),
+    ("courier new", &["cousine", "liberation mono", "dejavu sans mono"]),
+    ("verdana", &["dejavu sans"]),
+    ("tahoma", &["dejavu sans"]),
+];
+
+/// An installed font file, with the style read from it when the font directories were scanned.
+struct FontFile {
+    path: PathBuf,
+    family: String,
+    bold: bool,
+    italic: bool,
+}
+
+thread_local! {
+    // Scanning means parsing every installed font, so it's only done the first time a font is looked up
+    static SYSTEM_FONTS: RefCell<Option<Rc<[FontFile]>>> = RefCell::new(None);
+    static LOADED_FONTS: RefCell<HashMap<PathBuf, Rc<TrueType>>> = RefCell::new(HashMap::new());
+    static FALLBACK_FONT: Rc<TrueType> = Rc::new(
+        // DejaVu Sans, under the Bitstream Vera license (see data/dejavusans-license.txt). https://dejavu-fonts.github.io/
+        TrueType::parse(include_bytes!("../../../data/dejavusans.ttf").to_vec()).expect("bundled font is valid"),
+    );
+}
+
+fn system_fonts() -> Rc<[FontFile]> {
+    SYSTEM_FONTS.with(|cache| {
+        cache
+            .borrow_mut()
+            .get_or_insert_with(|| {
+                let mut files = Vec::new();
+                for dir in font_directories() {
+                    collect_font_files(&dir, 0, &mut files);
+                }
+                files.sort();
+                files
+                    .into_iter()
+                    .filter_map(|path| {
+                        let font = TrueType::parse(std::fs::read(&path).ok()?)?;
+                        let (family, bold, italic) = font.style()?;
+                        Some(FontFile { path, family: family.to_lowercase(), bold, italic })
+                    })
+                    .collect()
+            })
+            .clone()
+    })
+}
+
+/// The font bundled with the emulator, used when a font isn't installed and whenever
+/// the result has to be the same on every machine, such as when recording or replaying.
+pub fn fallback_font() -> Rc<TrueType> {
+    FALLBACK_FONT.with(Rc::clone)
+}
+
+/// Finds an installed font by family name, preferring the requested style.
+/// Falls back to a metric-compatible substitute for common Windows fonts.
+pub fn find_system_font(name: &str, bold: bool, italic: bool) -> Option<Rc<TrueType>> {
+    let name = name.trim().to_lowercase();
+    let mut families = vec![name.clone()];
+    if let Some((_, subs)) = SUBSTITUTES.iter().find(|(font, _)| *font == name) {
+        families.extend(subs.iter().map(|s| s.to_string()));
+    }
+
+    let fonts = system_fonts();
+    let best = fonts
+        .iter()
+        .filter_map(|file| {
+            let family_rank = families.iter().position(|f| *f == file.family)?;
+            let style_misses = u32::from(file.bold != bold) + u32::from(file.italic != italic);
+            Some(((family_rank, style_misses), file))
+        })
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, file)| file)?;
+
+    LOADED_FONTS.with(|loaded| {
+        if let Some(font) = loaded.borrow().get(&best.path) {
+            return Some(font.clone())
+        }
+        let font = Rc::new(TrueType::parse(std::fs::read(&best.path).ok()?)?);
+        loaded.borrow_mut().insert(best.path.clone(), font.clone());
+        Some(font)
+    })
+}
@@ -129,6 +129,110 @@ impl Path {
         ((left + right) / Real::from(2), (top + bottom) / Real::from(2))
     }
 
+    /// Adds a point to the end of the Path
+    pub fn add_point(&mut self, point: Point) {
+        self.points.push(point);
+        self.update();
+    }
+
+    /// Inserts a point at index n, clamped to the start or end of the Path
+    pub fn insert_point(&mut self, n: i32, point: Point) {
+        let n = n.max(0).min(self.points.len() as i32) as usize;
+        self.points.insert(n, point);
+        self.update();
+    }
+
+    /// Replaces the point at index n, if there is one
+    pub fn change_point(&mut self, n: i32, point: Point) {
+        if n >= 0 {
+            if let Some(p) = self.points.get_mut(n as usize) {
+                *p = point;
+                self.update();
+            }
+        }
+    }
+
+    /// Removes the point at index n, if there is one
+    pub fn delete_point(&mut self, n: i32) {
+        if n >= 0 && (n as usize) < self.points.len() {
+            self.points.remove(n as usize);
+            self.update();
+        }
+    }
+
+    /// Removes all points from the Path
+    pub fn clear_points(&mut self) {
+        self.points.clear();
+        self.update();
+    }
+
+    /// Adds a copy of the given points to the end of the Path
+    pub fn append(&mut self, points: &[Point]) {
+        self.points.extend_from_slice(points);
+        self.update();
+    }
+
+    /// Reverses the order of the points
+    pub fn reverse(&mut self) {
+        self.points.reverse();
+        self.update();
+    }
+
+    /// Mirrors the Path horizontally around its center
+    pub fn mirror(&mut self) {
+        let (xcenter, _) = self.center();
+        for point in &mut self.points {
+            point.x = xcenter - (point.x - xcenter);
+        }
+        self.update();
+    }
+
+    /// Flips the Path vertically around its center
+    pub fn flip(&mut self) {
+        let (_, ycenter) = self.center();
+        for point in &mut self.points {
+            point.y = ycenter - (point.y - ycenter);
+        }
+        self.update();
+    }
+
+    /// Rotates the Path anticlockwise around its center by an angle in degrees
+    pub fn rotate(&mut self, angle: Real) {
+        let sin = -angle.to_radians().sin().into_inner();
+        let cos = angle.to_radians().cos().into_inner();
+        let (xcenter, ycenter) = self.center();
+        for point in &mut self.points {
+            crate::util::rotate_around(
+                point.x.as_mut_ref(),
+                point.y.as_mut_ref(),
+                xcenter.into(),
+                ycenter.into(),
+                sin,
+                cos,
+            );
+        }
+        self.update();
+    }
+
+    /// Scales the Path around its center
+    pub fn scale(&mut self, xscale: Real, yscale: Real) {
+        let (xcenter, ycenter) = self.center();
+        for point in &mut self.points {
+            point.x = xcenter + xscale * (point.x - xcenter);
+            point.y = ycenter + yscale * (point.y - ycenter);
+        }
+        self.update();
+    }
+
+    /// Moves every point of the Path by the given amount
+    pub fn shift(&mut self, xshift: Real, yshift: Real) {
+        for point in &mut self.points {
+            point.x += xshift;
+            point.y += yshift;
+        }
+        self.update();
+    }
+
     /// Returns a Point on the path at the given offset, where 0 is the beginning and 1 is the end
     pub fn get_point(&self, offset: Real) -> Point {
         match &*self.control_nodes {
@@ -178,3 +282,83 @@ impl Point {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(x: f64, y: f64) -> Point {
+        Point { x: x.into(), y: y.into(), speed: Real::from(100.0) }
+    }
+
+    fn path(points: &[(f64, f64)]) -> Path {
+        let mut path = Path {
+            name: "path".into(),
+            points: points.iter().map(|&(x, y)| point(x, y)).collect(),
+            control_nodes: Vec::new(),
+            length: Real::from(0.0),
+            curve: false,
+            closed: false,
+            precision: 4,
+            start: Point::default(),
+            end: Point::default(),
+        };
+        path.update();
+        path
+    }
+
+    fn coords(path: &Path) -> Vec<(f64, f64)> {
+        path.points.iter().map(|p| (p.x.round().into(), p.y.round().into())).collect()
+    }
+
+    #[test]
+    fn edit_points() {
+        let mut p = path(&[(0.0, 0.0)]);
+        p.add_point(point(10.0, 0.0));
+        assert_eq!(p.length, Real::from(10.0));
+        p.insert_point(-5, point(0.0, -10.0));
+        p.insert_point(99, point(10.0, 10.0));
+        p.insert_point(2, point(5.0, 0.0));
+        assert_eq!(coords(&p), [(0.0, -10.0), (0.0, 0.0), (5.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        assert_eq!(p.length, Real::from(30.0));
+
+        p.change_point(2, point(5.0, 5.0));
+        p.change_point(-1, point(99.0, 99.0));
+        p.change_point(5, point(99.0, 99.0));
+        assert_eq!(coords(&p)[2], (5.0, 5.0));
+
+        p.delete_point(2);
+        p.delete_point(-1);
+        p.delete_point(4);
+        assert_eq!(coords(&p), [(0.0, -10.0), (0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        assert_eq!((p.start.y, p.end.y), (Real::from(-10.0), Real::from(10.0)));
+
+        let other = path(&[(20.0, 10.0)]);
+        p.append(&other.points);
+        p.reverse();
+        assert_eq!(coords(&p)[..2], [(20.0, 10.0), (10.0, 10.0)]);
+        assert_eq!(p.length, Real::from(40.0));
+
+        p.clear_points();
+        assert!(p.points.is_empty() && p.control_nodes.is_empty());
+    }
+
+    #[test]
+    fn transform() {
+        let mut p = path(&[(0.0, 0.0), (10.0, 0.0), (10.0, 20.0)]);
+        p.mirror();
+        assert_eq!(coords(&p), [(10.0, 0.0), (0.0, 0.0), (0.0, 20.0)]);
+        p.flip();
+        assert_eq!(coords(&p), [(10.0, 20.0), (0.0, 20.0), (0.0, 0.0)]);
+        p.shift(5.0.into(), (-5.0).into());
+        assert_eq!(coords(&p), [(15.0, 15.0), (5.0, 15.0), (5.0, -5.0)]);
+        p.scale(2.0.into(), 0.5.into());
+        assert_eq!(coords(&p), [(20.0, 10.0), (0.0, 10.0), (0.0, 0.0)]);
+        assert_eq!((p.end.x, p.end.y), (p.points[2].x, p.points[2].y));
+
+        // Positive angles rotate anticlockwise on screen, where y points down
+        p.rotate(90.0.into());
+        assert_eq!(coords(&p), [(15.0, -5.0), (15.0, 15.0), (5.0, 15.0)]);
+        assert_eq!(p.length, Real::from(30.0));
+    }
+}
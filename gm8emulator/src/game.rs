@@ -1,5 +1,6 @@
 pub mod audio;
 pub mod background;
+pub mod codepage;
 pub mod draw;
 pub mod events;
 pub mod external;
@@ -35,7 +36,7 @@ use crate::{
     },
     game::gm_save::GMSave,
     game::replay::FrameRng,
-    gml::{self, ds, ev, file, rand::Random, runtime::Instruction, Compiler, Context},
+    gml::{self, ds, ev, file, network, rand::Random, runtime::Instruction, Compiler, Context},
     handleman::{HandleArray, HandleList},
     input::{self, Input},
     instance::{DummyFieldHolder, Instance, InstanceState},
@@ -76,6 +77,7 @@ pub struct Game {
     pub binary_files: HandleArray<file::BinaryHandle, 32>,
     pub rand: Random,
     pub input: Input,
+    pub gamepads: input::GamepadManager,
     pub assets: Assets,
     pub event_holders: [IndexMap<u32, Rc<RefCell<Vec<ID>>>>; 12],
     pub custom_draw_objects: HashSet<ID>,
@@ -173,6 +175,9 @@ pub struct Game {
     pub frame_limit_at: usize, // on which frame to start limiting FPS
 
     pub audio: audio::AudioManager,
+    pub cd: audio::cd::CdDrive,
+    pub mci: audio::mci::Mci,
+    pub multiplayer: network::Multiplayer,
 
     // winit windowing
     pub window: Window,
@@ -184,7 +189,9 @@ pub struct Game {
     pub window_offset_spoof: (i32, i32),
     pub window_is_logical_dpi: bool,
     pub window_sizeable: bool,
+    pub window_stayontop: bool,
     pub window_visible: bool,
+    pub virtual_display: platform::VirtualDisplay,
     pub close_requested: bool,
     // Scaling type
     pub scaling: Scaling,
@@ -284,7 +291,7 @@ impl Game {
         file_path: PathBuf,
         game_arguments: Vec<String>,
         temp_dir: Option<PathBuf>,
-        encoding: &'static Encoding,
+        encoding: Option<&'static Encoding>,
         frame_limiter: bool,
         frame_limit_at: usize,
         play_type: PlayType,
@@ -319,6 +326,8 @@ impl Game {
             }
         }
 
+        let encoding = encoding.unwrap_or_else(|| codepage::detect(&assets));
+
         // Destructure assets
         let gm8exe::GameAssets {
             game_id,
@@ -362,7 +371,10 @@ impl Game {
         let room1_speed = room1.speed;
         let room1_colour = room1.bg_colour.as_decimal().into();
         let room1_show_colour = room1.clear_screen;
-        let room1_caption = String::from_utf8_lossy(room1.caption.0.as_ref()).into_owned();
+        let room1_caption = match gm_version {
+            Version::GameMaker8_0 => encoding.decode_without_bom_handling(room1.caption.0.as_ref()).0.into_owned(),
+            Version::GameMaker8_1 => String::from_utf8_lossy(room1.caption.0.as_ref()).into_owned(),
+        };
 
         let _ = room1;
 
@@ -752,6 +764,12 @@ impl Game {
                                 data,
                                 sound_id as i32,
                                 b.volume,
+                                b.pan,
+                                (u32::from(b.fx.chorus) * audio::effects::CHORUS)
+                                    | (u32::from(b.fx.echo) * audio::effects::ECHO)
+                                    | (u32::from(b.fx.flanger) * audio::effects::FLANGER)
+                                    | (u32::from(b.fx.gargle) * audio::effects::GARGLE)
+                                    | (u32::from(b.fx.reverb) * audio::effects::REVERB),
                                 b.kind == SoundKind::ThreeDimensional,
                                 b.kind == SoundKind::Multimedia,
                             ) {
@@ -764,19 +782,31 @@ impl Game {
                                     FileType::None
                                 },
                             },
+                            b".mid" | b".midi" | b".rmi" => match audio.add_midi(&data, sound_id as i32) {
+                                Ok(x) => FileType::Midi(x),
+                                Err(e) => {
+                                    println!(
+                                        "WARNING: invalid midi data in sound '{}': {}",
+                                        String::from_utf8_lossy(b.name.0.as_ref()),
+                                        e
+                                    );
+                                    FileType::None
+                                },
+                            },
                             _ => FileType::None,
                         },
                         None => FileType::None,
                     };
-                    Box::new(Sound {
+                    Ok(Box::new(Sound {
                         name: b.name.into(),
                         handle,
                         gml_kind: f64::from(b.kind as u8).into(),
                         gml_preload: f64::from(u8::from(b.preload)).into(),
-                    })
+                    }))
                 })
+                .transpose()
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, String>>()?;
 
         let sprites = sprites
             .into_iter()
@@ -863,35 +893,39 @@ impl Game {
                         Version::GameMaker8_0 => 1, // DEFAULT_CHARSET
                         Version::GameMaker8_1 => b.charset,
                     };
-                    let chars = b
-                        .dmap
-                        .chunks_exact(6)
-                        .skip(b.range_start as usize)
-                        .take(((b.range_end - b.range_start) + 1) as usize)
-                        .map(|char_blob| {
-                            if tallest_char_height < char_blob[3] {
-                                tallest_char_height = char_blob[3];
-                            }
-                            let mut data: Vec<u8> = Vec::with_capacity((char_blob[2] * char_blob[3] * 4) as usize);
-                            for y in 0..char_blob[3] {
-                                for x in 0..char_blob[2] {
-                                    data.push(0xFF);
-                                    data.push(0xFF);
-                                    data.push(0xFF);
-                                    data.push(
-                                        b.pixel_map[((y + char_blob[1]) * b.map_width + x + char_blob[0]) as usize],
-                                    );
+                    // project files don't store glyphs, these get rasterised once the renderer is set up
+                    let chars = if b.pixel_map.is_empty() {
+                        Box::new([]) as Box<[Character]>
+                    } else {
+                        b.dmap
+                            .chunks_exact(6)
+                            .skip(b.range_start as usize)
+                            .take(((b.range_end - b.range_start) + 1) as usize)
+                            .map(|char_blob| {
+                                if tallest_char_height < char_blob[3] {
+                                    tallest_char_height = char_blob[3];
                                 }
-                            }
-                            Ok(Character {
-                                offset: char_blob[4] as _,
-                                distance: char_blob[5] as _,
-                                atlas_ref: atlases
-                                    .texture(char_blob[2] as _, char_blob[3] as _, 0, 0, data.into_boxed_slice())
-                                    .ok_or(())?,
+                                let mut data: Vec<u8> = Vec::with_capacity((char_blob[2] * char_blob[3] * 4) as usize);
+                                for y in 0..char_blob[3] {
+                                    for x in 0..char_blob[2] {
+                                        data.push(0xFF);
+                                        data.push(0xFF);
+                                        data.push(0xFF);
+                                        data.push(
+                                            b.pixel_map[((y + char_blob[1]) * b.map_width + x + char_blob[0]) as usize],
+                                        );
+                                    }
+                                }
+                                Ok(Character {
+                                    offset: char_blob[4] as _,
+                                    distance: char_blob[5] as _,
+                                    atlas_ref: atlases
+                                        .texture(char_blob[2] as _, char_blob[3] as _, 0, 0, data.into_boxed_slice())
+                                        .ok_or(())?,
+                                })
                             })
-                        })
-                        .collect::<Result<Box<_>, ()>>()?;
+                            .collect::<Result<Box<_>, ()>>()?
+                    };
                     Ok(Box::new(Font {
                         name: b.name.into(),
                         sys_name: b.sys_name.into(),
@@ -1191,6 +1225,13 @@ impl Game {
 
         renderer.push_atlases(atlases)?;
 
+        // Live joysticks are only used in normal play, recordings get their joystick input from the replay
+        let mut input = Input::new();
+        let gamepads = match play_type {
+            PlayType::Normal => input::GamepadManager::open(&mut input),
+            PlayType::Record | PlayType::Replay => input::GamepadManager::new(),
+        };
+
         let mut game = Self {
             compiler,
             text_files: HandleArray::new(),
@@ -1204,7 +1245,8 @@ impl Game {
             extension_finalizers,
             externals,
             surface_fix: false,
-            input: Input::new(),
+            input,
+            gamepads,
             assets: Assets { backgrounds, fonts, objects, paths, rooms, scripts, sprites, sounds, timelines, triggers },
             event_holders,
             custom_draw_objects,
@@ -1291,6 +1333,9 @@ impl Game {
             error_occurred: false,
             error_last: "".to_string().into(),
             audio,
+            cd: audio::cd::CdDrive::new(file_path2.join("cd")),
+            mci: Default::default(),
+            multiplayer: Default::default(),
             window,
             window_border,
             window_icons,
@@ -1310,11 +1355,17 @@ impl Game {
             window_is_logical_dpi: false,
             window_offset_spoof: (0, 0),
             window_sizeable: settings.allow_resize,
+            window_stayontop: false,
             window_visible: true,
+            virtual_display: Default::default(),
         };
 
         game.temp_directory = game.encode_str_maybe(temp_directory.to_str().unwrap()).unwrap().into_owned().into();
 
+        for font in game.assets.fonts.iter_mut().flatten().filter(|f| f.chars.is_empty()) {
+            font.rasterise(game.encoding, play_type == PlayType::Normal, &mut game.renderer)?;
+        }
+
         // Evaluate constants
         for extension in extensions {
             for file in extension.files {
@@ -1458,6 +1509,24 @@ impl Game {
         }
     }
 
+    /// Like `encode_str_maybe`, but with characters the code page doesn't have turned into `?`.
+    pub fn encode_str<'a>(&self, utf8: &'a str) -> Cow<'a, [u8]> {
+        match self.encode_str_maybe(utf8) {
+            Some(encoded) => encoded,
+            None => {
+                let mut encoded = Vec::with_capacity(utf8.len());
+                let mut buf = [0u8; 4];
+                for c in utf8.chars() {
+                    match self.encode_str_maybe(c.encode_utf8(&mut buf)) {
+                        Some(bytes) => encoded.extend_from_slice(&bytes),
+                        None => encoded.push(b'?'),
+                    }
+                }
+                Cow::from(encoded)
+            },
+        }
+    }
+
     pub fn encode_str_maybe<'a>(&self, utf8: &'a str) -> Option<Cow<'a, [u8]>> {
         match self.gm_version {
             Version::GameMaker8_0 => {
@@ -1779,6 +1848,10 @@ impl Game {
 
     /// Runs a frame loop and draws the screen. Exits immediately, without waiting for any FPS limitation.
     pub fn frame(&mut self) -> gml::Result<()> {
+        let now = self.spoofed_time_nanos.unwrap_or_else(gml::datetime::now_as_nanos);
+        self.cd.update(&mut self.audio, now);
+        self.mci.update(&mut self.audio, now);
+
         if self.esc_close_game && self.input.keyboard_lastkey() == input::Button::Escape as u8 {
             self.scene_change = Some(SceneChange::End);
             return Ok(())
@@ -1992,6 +2065,12 @@ impl Game {
                     match event {
                         Event::KeyboardDown(key) => self.input.button_press(input::ramen2vk(key), true),
                         Event::KeyboardUp(key) => self.input.button_release(input::ramen2vk(key), true),
+                        Event::Input(chr) => {
+                            let mut buf = [0; 4];
+                            for &byte in self.encode_str(chr.encode_utf8(&mut buf)).iter() {
+                                self.input.char_input(byte);
+                            }
+                        },
                         Event::MouseMove((x, y)) => {
                             if let (Ok(x), Ok(y)) = (i32::try_from(x), i32::try_from(y)) {
                                 self.input.mouse_move_to((x, y));
@@ -2006,6 +2085,7 @@ impl Game {
                         _ => (),
                     }
                 }
+                self.gamepads.poll(&mut self.input);
             },
             _ => (),
         }
@@ -2032,7 +2112,21 @@ impl Game {
             };
             match &external.call {
                 external::Call::Dummy(x) => Ok(x.clone()),
-                &external::Call::Emulated(func) => func.invoke(self, context, args),
+                &external::Call::Emulated(func) => {
+                    // emulated functions don't know how the game defined them, so convert to what it expects
+                    let return_type = external.signature.type_return;
+                    Ok(match (func.invoke(self, context, args)?, return_type) {
+                        (gml::Value::Real(x), dll::ValueType::Str) => x.to_string().into(),
+                        (gml::Value::Str(s), dll::ValueType::Real) => {
+                            String::from_utf8_lossy(s.as_ref()).trim().parse::<f64>().unwrap_or(0.0).into()
+                        },
+                        (value, _) => value,
+                    })
+                },
+                external::Call::Unemulated => Err(gml::Error::FunctionError(
+                    external.signature.symbol.clone(),
+                    format!("this function of {} isn't emulated", external.signature.dll),
+                )),
                 external::Call::Native(_) => {
                     let args = convert_args();
                     Ok(self.externals.call_native(id as _, &args).into())
@@ -2187,12 +2281,35 @@ impl Game {
         self.input.mouse_move_to((frame.mouse_x as i32, frame.mouse_y as i32));
         for ev in frame.inputs.iter() {
             match ev {
-                replay::Input::KeyPress(v) => self.input.button_press(*v as u8, true),
+                replay::Input::KeyPress(v) => {
+                    self.input.button_press(*v as u8, true);
+                    self.input.char_press(*v, self.encoding);
+                },
                 replay::Input::KeyRelease(v) => self.input.button_release(*v as u8, true),
                 replay::Input::MousePress(b) => self.input.mouse_press(*b as i8, true),
                 replay::Input::MouseRelease(b) => self.input.mouse_release(*b as i8, true),
                 replay::Input::MouseWheelUp => self.input.mouse_scroll_up(),
                 replay::Input::MouseWheelDown => self.input.mouse_scroll_down(),
+                replay::Input::JoystickPress(j, b) => {
+                    if let Some(joystick) = self.input.emulated_joystick_mut(usize::from(*j)) {
+                        joystick.set_button(*b, true);
+                    }
+                },
+                replay::Input::JoystickRelease(j, b) => {
+                    if let Some(joystick) = self.input.emulated_joystick_mut(usize::from(*j)) {
+                        joystick.set_button(*b, false);
+                    }
+                },
+                replay::Input::JoystickAxis(j, axis, value) => {
+                    if let Some(joystick) = self.input.emulated_joystick_mut(usize::from(*j)) {
+                        joystick.set_axis(*axis, *value);
+                    }
+                },
+                replay::Input::JoystickPov(j, pov) => {
+                    if let Some(joystick) = self.input.emulated_joystick_mut(usize::from(*j)) {
+                        joystick.set_pov(*pov);
+                    }
+                },
             }
         }
     }
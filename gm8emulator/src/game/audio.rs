@@ -1,12 +1,20 @@
+pub mod cd;
+pub mod effects;
+pub mod mci;
+mod midi;
 mod mixer;
 mod mp3;
+mod soundfont;
+pub mod spatial;
+mod synth;
 
 use serde::{Deserialize, Serialize};
 use std::{
     collections::HashMap,
+    path::Path,
     sync::{
-        atomic::{AtomicU32, Ordering},
-        Arc,
+        atomic::{AtomicBool, AtomicU32, Ordering},
+        Arc, Mutex,
     },
 };
 use udon::{
@@ -14,13 +22,16 @@ use udon::{
     rechanneler::Rechanneler,
     resampler::Resampler,
     session::{Api, Session},
-    source::{ChannelCount, SampleRate, Source},
+    source::{ChannelCount, Sample, SampleRate, Source},
     wav::WavPlayer,
 };
 
 use self::{
+    midi::{MidiPlayer, Sequence},
     mixer::{Mixer, MixerHandle},
     mp3::Mp3Player,
+    soundfont::SoundFont,
+    spatial::Spatial,
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -29,18 +40,43 @@ pub struct Mp3Handle {
     id: i32,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct MidiHandle {
+    sequence: Arc<Sequence>,
+    volume: Arc<AtomicU32>,
+    id: i32,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct WavHandle {
     player: WavPlayer,
     params: Arc<SoundParams>,
-    _use_3d: bool,
+    spatial: Option<Spatial>, // only for 3D sounds
     exclusive: bool,
     id: i32,
 }
 
+/// Parameters shared between a sound and every instance of it playing in the mixer.
+/// Floats are stored as their bits.
 #[derive(Serialize, Deserialize)]
 pub struct SoundParams {
-    pub volume: AtomicU32,
+    pub volume: AtomicU32, // 0 to 1, as given in GML
+    pub pan: AtomicU32,    // -1 to 1
+    pub spatial_volume: AtomicU32,
+    pub spatial_pan: AtomicU32,
+    pub fade_id: AtomicU32,
+    pub effects: Mutex<effects::Settings>,
+    pub effects_id: AtomicU32,
+    pub paused: AtomicBool,
+}
+
+/// A sound loaded by an emulated extension DLL rather than by the game itself.
+/// Each time one is played it gets its own params, so DLLs can control every instance separately.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum ExternalSound {
+    Wav(WavPlayer),
+    Mp3(Mp3Player),
+    Midi(Arc<Sequence>),
 }
 
 pub struct AudioManager {
@@ -51,6 +87,10 @@ pub struct AudioManager {
     global_volume: Arc<AtomicU32>,
     end_times: HashMap<i32, Option<u128>>,
     multimedia_end: Option<(i32, Option<u128>)>,
+    multimedia_is_midi: bool,
+    midi_tempo: Arc<AtomicU32>,
+    soundfont: Option<Arc<SoundFont>>,
+    next_external_id: i32,
 }
 
 impl AudioManager {
@@ -76,26 +116,50 @@ impl AudioManager {
             global_volume,
             end_times: HashMap::new(),
             multimedia_end: None,
+            multimedia_is_midi: false,
+            midi_tempo: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+            soundfont: None,
+            next_external_id: -1,
         }
     }
 
+    /// Loads a SoundFont to play MIDIs with. Without one, a set of basic built-in instruments is used.
+    pub fn load_soundfont(&mut self, path: &Path) -> Result<(), String> {
+        // no point loading it if nothing's going to be heard
+        if self.do_output {
+            let file = std::fs::read(path).map_err(|e| e.to_string())?;
+            self.soundfont = Some(Arc::new(SoundFont::new(&file).map_err(|e| e.to_string())?));
+        }
+        Ok(())
+    }
+
     pub fn add_mp3(&mut self, file: Box<[u8]>, sound_id: i32) -> Option<Mp3Handle> {
         Mp3Player::new(file).map(|player| Mp3Handle { player, id: sound_id }).ok()
     }
 
+    pub fn add_midi(&mut self, file: &[u8], sound_id: i32) -> Result<MidiHandle, midi::Error> {
+        Sequence::new(file).map(|sequence| MidiHandle {
+            sequence: Arc::new(sequence),
+            volume: Arc::new(AtomicU32::new(1.0f32.to_bits())),
+            id: sound_id,
+        })
+    }
+
     pub fn add_wav(
         &mut self,
         file: Box<[u8]>,
         sound_id: i32,
         volume: f64,
+        pan: f64,
+        effects: u32,
         use_3d: bool,
         exclusive: bool,
     ) -> Option<WavHandle> {
         WavPlayer::new(file)
             .map(|player| WavHandle {
                 player,
-                params: Arc::new(SoundParams { volume: AtomicU32::new(make_volume(volume).to_bits()) }),
-                _use_3d: use_3d,
+                params: Arc::new(SoundParams::new(volume, pan, effects)),
+                spatial: if use_3d { Some(Spatial::default()) } else { None },
                 exclusive,
                 id: sound_id,
             })
@@ -109,6 +173,7 @@ impl AudioManager {
             1, // mp3 length() already takes channels into account
         ) + start_time;
         self.multimedia_end = Some((handle.id, Some(end_time)));
+        self.multimedia_is_midi = false;
         if self.do_output {
             let _ = self.mixer_handle.add_exclusive(
                 Rechanneler::new(
@@ -128,6 +193,7 @@ impl AudioManager {
         ) + start_time;
         if handle.exclusive {
             self.multimedia_end = Some((handle.id, Some(end_time)));
+            self.multimedia_is_midi = false;
         } else if self.end_times.get(&handle.id) != Some(&None) {
             self.end_times.insert(handle.id, Some(end_time));
         }
@@ -154,8 +220,58 @@ impl AudioManager {
         }
     }
 
+    /// MIDIs play in the same slot as multimedia sounds, so only one can play at once.
+    pub fn play_midi(&mut self, handle: &MidiHandle, start_time: u128) {
+        let tempo = f64::from(f32::from_bits(self.midi_tempo.load(Ordering::Acquire)));
+        let end_time = (handle.sequence.length() as f64 * 1000.0 / tempo) as u128 + start_time;
+        self.multimedia_end = Some((handle.id, Some(end_time)));
+        self.multimedia_is_midi = true;
+        if self.do_output {
+            let _ = self.mixer_handle.add_exclusive(
+                Rechanneler::new(self.midi_player(handle), self.mixer_channel_count),
+                handle.id,
+            );
+        }
+    }
+
+    pub fn loop_midi(&mut self, handle: &MidiHandle) {
+        self.multimedia_end = Some((handle.id, None));
+        self.multimedia_is_midi = true;
+        if self.do_output {
+            let _ = self.mixer_handle.add_exclusive(
+                Cycle::new(Rechanneler::new(self.midi_player(handle), self.mixer_channel_count)),
+                handle.id,
+            );
+        }
+    }
+
+    fn midi_player(&self, handle: &MidiHandle) -> MidiPlayer {
+        MidiPlayer::new(
+            handle.sequence.clone(),
+            self.soundfont.clone(),
+            self.mixer_sample_rate,
+            handle.volume.clone(),
+            self.midi_tempo.clone(),
+        )
+    }
+
+    /// Changes the speed MIDIs play at. If one's playing now, its end time is moved to match.
+    pub fn set_midi_tempo(&mut self, tempo: f64, current_time: u128) {
+        let tempo = tempo.clamp(0.01, 100.0);
+        let old_tempo = f64::from(f32::from_bits(self.midi_tempo.swap((tempo as f32).to_bits(), Ordering::AcqRel)));
+        if self.multimedia_is_midi {
+            if let Some((_, Some(end_time))) = &mut self.multimedia_end {
+                if *end_time > current_time {
+                    let remaining = (*end_time - current_time) as f64 * old_tempo / f64::from(tempo as f32);
+                    *end_time = current_time + remaining as u128;
+                }
+            }
+        }
+    }
+
     pub fn loop_mp3(&mut self, handle: &Mp3Handle) {
         self.multimedia_end = Some((handle.id, None));
+        self.multimedia_is_midi = false;
         if self.do_output {
             let _ = self.mixer_handle.add_exclusive(
                 Cycle::new(Rechanneler::new(
@@ -170,6 +286,7 @@ impl AudioManager {
     pub fn loop_wav(&mut self, handle: &WavHandle) {
         if handle.exclusive {
             self.multimedia_end = Some((handle.id, None));
+            self.multimedia_is_midi = false;
         } else {
             self.end_times.insert(handle.id, None);
         }
@@ -214,6 +331,89 @@ impl AudioManager {
         }
     }
 
+    /// Fades a sound's volume to the given value over some number of milliseconds.
+    pub fn fade_wav(&self, handle: &WavHandle, vol: f64, ms: u32) {
+        let id = handle.params.fade_id.fetch_add(1, Ordering::AcqRel).wrapping_add(1);
+        if self.do_output && ms > 0 {
+            let frames = u64::from(ms) * u64::from(u32::from(self.mixer_sample_rate)) / 1000;
+            let _ = self.mixer_handle.fade(handle.params.clone(), vol.clamp(0.0, 1.0) as f32, frames, id);
+        } else {
+            handle.params.volume.store((vol.clamp(0.0, 1.0) as f32).to_bits(), Ordering::Release);
+        }
+    }
+
+    /// Decodes a sound file for an emulated DLL, going by its contents rather than its name.
+    pub fn add_external(&self, file: Box<[u8]>) -> Option<ExternalSound> {
+        if file.get(0..4) == Some(b"MThd") || file.get(8..12) == Some(b"RMID") {
+            Sequence::new(&file).ok().map(|sequence| ExternalSound::Midi(Arc::new(sequence)))
+        } else if file.get(0..4) == Some(b"RIFF") {
+            WavPlayer::new(file).ok().map(ExternalSound::Wav)
+        } else {
+            Mp3Player::new(file).ok().map(ExternalSound::Mp3)
+        }
+    }
+
+    /// Plays a sound for an emulated DLL, starting some number of nanoseconds in, and returns the ID to stop it with.
+    /// These IDs are negative so they never clash with the game's own sounds.
+    pub fn play_external(
+        &mut self,
+        sound: &ExternalSound,
+        params: Arc<SoundParams>,
+        looping: bool,
+        start: u128,
+    ) -> i32 {
+        let id = self.next_external_id;
+        self.next_external_id = self.next_external_id.checked_sub(1).unwrap_or(-1);
+        if self.do_output {
+            match sound {
+                ExternalSound::Wav(player) => {
+                    let resampler = Resampler::new(player.clone(), self.mixer_sample_rate);
+                    self.add_external_source(resampler, params, looping, start, id)
+                },
+                ExternalSound::Mp3(player) => {
+                    let resampler = Resampler::new(player.clone(), self.mixer_sample_rate);
+                    self.add_external_source(resampler, params, looping, start, id)
+                },
+                ExternalSound::Midi(sequence) => {
+                    let player = MidiPlayer::new(
+                        sequence.clone(),
+                        self.soundfont.clone(),
+                        self.mixer_sample_rate,
+                        Arc::new(AtomicU32::new(1.0f32.to_bits())),
+                        Arc::new(AtomicU32::new(1.0f32.to_bits())),
+                    );
+                    self.add_external_source(player, params, looping, start, id)
+                },
+            }
+        }
+        id
+    }
+
+    fn add_external_source(
+        &self,
+        source: impl Source + Send + 'static,
+        params: Arc<SoundParams>,
+        looping: bool,
+        start: u128,
+        id: i32,
+    ) {
+        let source = Rechanneler::new(source, self.mixer_channel_count);
+        let channels = u128::from(u16::from(self.mixer_channel_count));
+        let frames = start * u128::from(u32::from(self.mixer_sample_rate)) / 1_000_000_000;
+        let samples = (frames * channels) as usize;
+        let _ = if looping {
+            self.mixer_handle.add(Skip { source: Cycle::new(source), samples }, params, id)
+        } else {
+            self.mixer_handle.add(Skip { source, samples }, params, id)
+        };
+    }
+
+    pub fn stop_external(&self, id: i32) {
+        if self.do_output {
+            let _ = self.mixer_handle.stop(id);
+        }
+    }
+
     pub fn set_global_volume(&self, vol: f64) {
         self.global_volume.store(make_volume(vol).to_bits(), Ordering::Release)
     }
@@ -241,6 +441,8 @@ impl AudioManager {
             global_volume: self.global_volume.clone(),
             end_times: self.end_times.clone(),
             multimedia_end: self.multimedia_end,
+            multimedia_is_midi: self.multimedia_is_midi,
+            midi_tempo: self.midi_tempo.load(Ordering::Acquire),
         }
     }
 
@@ -248,12 +450,181 @@ impl AudioManager {
         self.global_volume = state.global_volume;
         self.end_times = state.end_times;
         self.multimedia_end = state.multimedia_end;
+        self.multimedia_is_midi = state.multimedia_is_midi;
+        self.midi_tempo.store(state.midi_tempo, Ordering::Release);
+    }
+}
+
+impl SoundParams {
+    pub fn new(volume: f64, pan: f64, effects: u32) -> Self {
+        Self {
+            volume: AtomicU32::new((volume as f32).to_bits()),
+            pan: AtomicU32::new((pan as f32).to_bits()),
+            spatial_volume: AtomicU32::new(1.0f32.to_bits()),
+            spatial_pan: AtomicU32::new(0.0f32.to_bits()),
+            fade_id: AtomicU32::new(0),
+            effects: Mutex::new(effects::Settings { enabled: effects, ..Default::default() }),
+            effects_id: AtomicU32::new(0),
+            paused: AtomicBool::new(false),
+        }
+    }
+}
+
+impl ExternalSound {
+    /// How long the sound takes to play once, in nanoseconds.
+    pub fn length(&self) -> u128 {
+        match self {
+            Self::Wav(player) => {
+                length_to_ns(player.length(), player.sample_rate().into(), player.channel_count().into())
+            },
+            Self::Mp3(player) => length_to_ns(player.length(), player.sample_rate().into(), 1),
+            Self::Midi(sequence) => u128::from(sequence.length()) * 1000,
+        }
+    }
+}
+
+/// One playthrough of an external sound in the mixer, keeping track of time so its position can be queried.
+pub struct ExternalPlayback {
+    params: Arc<SoundParams>,
+    pub mixer_id: i32,
+    pub looping: bool,
+    length: u128,
+    offset: u128,
+    started: u128,
+    pub paused_at: Option<u128>,
+}
+
+impl ExternalPlayback {
+    pub fn start(audio: &mut AudioManager, sound: &ExternalSound, looping: bool, paused: bool, now: u128) -> Self {
+        Self::start_at(audio, sound, looping, paused, now, 0)
+    }
+
+    /// Starts playing from the given position in nanoseconds.
+    pub fn start_at(
+        audio: &mut AudioManager,
+        sound: &ExternalSound,
+        looping: bool,
+        paused: bool,
+        now: u128,
+        position: u128,
+    ) -> Self {
+        let params = Arc::new(SoundParams::new(1.0, 0.0, 0));
+        params.paused.store(paused, Ordering::Release);
+        let mixer_id = audio.play_external(sound, params.clone(), looping, position);
+        Self {
+            params,
+            mixer_id,
+            looping,
+            length: sound.length(),
+            offset: position,
+            started: now,
+            paused_at: if paused { Some(now) } else { None },
+        }
+    }
+
+    fn elapsed(&self, now: u128) -> u128 {
+        self.offset + self.paused_at.unwrap_or(now).saturating_sub(self.started)
+    }
+
+    pub fn is_playing(&self, now: u128) -> bool {
+        self.looping || self.elapsed(now) < self.length
+    }
+
+    /// How far into the sound it is, in nanoseconds.
+    pub fn position(&self, now: u128) -> u128 {
+        match self.looping {
+            true if self.length > 0 => self.elapsed(now) % self.length,
+            _ => self.elapsed(now).min(self.length),
+        }
+    }
+
+    pub fn set_paused(&mut self, paused: bool, now: u128) {
+        match (self.paused_at, paused) {
+            (None, true) => self.paused_at = Some(now),
+            (Some(paused_at), false) => {
+                self.started += now.saturating_sub(paused_at);
+                self.paused_at = None;
+            },
+            _ => (),
+        }
+        self.params.paused.store(paused, Ordering::Release);
+    }
+
+    pub fn set_volume(&self, volume: f64) {
+        self.params.volume.store((volume.clamp(0.0, 1.0) as f32).to_bits(), Ordering::Release);
+    }
+
+    pub fn set_pan(&self, pan: f64) {
+        self.params.pan.store((pan.clamp(-1.0, 1.0) as f32).to_bits(), Ordering::Release);
+    }
+
+    pub fn pan(&self) -> f64 {
+        f32::from_bits(self.params.pan.load(Ordering::Acquire)).into()
+    }
+}
+
+/// Skips the start of a source, for sounds which begin partway through.
+struct Skip<S> {
+    source: S,
+    samples: usize,
+}
+
+impl<S: Source> Source for Skip<S> {
+    fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+        while self.samples > 0 && !buffer.is_empty() {
+            let len = self.samples.min(buffer.len());
+            match self.source.write_samples(&mut buffer[..len]) {
+                0 => self.samples = 0,
+                written => self.samples -= written,
+            }
+        }
+        self.source.write_samples(buffer)
+    }
+
+    fn channel_count(&self) -> ChannelCount {
+        self.source.channel_count()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.source.sample_rate()
+    }
+
+    fn reset(&mut self) {
+        self.source.reset()
+    }
+}
+
+impl MidiHandle {
+    pub fn set_volume(&self, vol: f64) {
+        self.volume.store((vol.clamp(0.0, 1.0) as f32).to_bits(), Ordering::Release);
     }
 }
 
 impl WavHandle {
     pub fn set_volume(&self, vol: f64) {
-        self.params.volume.store(make_volume(vol).to_bits(), Ordering::Release);
+        // this also cancels any fade in progress
+        self.params.fade_id.fetch_add(1, Ordering::AcqRel);
+        self.params.volume.store((vol.clamp(0.0, 1.0) as f32).to_bits(), Ordering::Release);
+    }
+
+    pub fn set_pan(&self, pan: f64) {
+        self.params.pan.store((pan.clamp(-1.0, 1.0) as f32).to_bits(), Ordering::Release);
+    }
+
+    /// Changes the sound's effect settings, which will be picked up by all instances of it currently playing.
+    pub fn update_effects(&self, f: impl FnOnce(&mut effects::Settings)) {
+        f(&mut self.params.effects.lock().unwrap());
+        self.params.effects_id.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Changes the sound's 3D properties. Does nothing if it isn't a 3D sound.
+    pub fn update_3d(&mut self, f: impl FnOnce(&mut Spatial)) {
+        if let Some(spatial) = &mut self.spatial {
+            f(spatial);
+            let (volume, pan) = spatial.output();
+            self.params.spatial_volume.store(volume.to_bits(), Ordering::Release);
+            self.params.spatial_pan.store(pan.to_bits(), Ordering::Release);
+        }
     }
 }
 
@@ -262,6 +633,8 @@ pub struct AudioState {
     global_volume: Arc<AtomicU32>,
     end_times: HashMap<i32, Option<u128>>,
     multimedia_end: Option<(i32, Option<u128>)>,
+    multimedia_is_midi: bool,
+    midi_tempo: u32,
 }
 
 fn length_to_ns(sample_count: usize, sample_rate: u32, channels: u16) -> u128 {
@@ -0,0 +1,260 @@
+use super::{AudioManager, ExternalPlayback, ExternalSound};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// A virtual CD drive for the cd_* functions. Its tracks are the audio files in a folder, in order of file name.
+pub struct CdDrive {
+    folder: PathBuf,
+    tracks: Option<Vec<ExternalSound>>,
+    state: CdState,
+    playback: Option<(usize, ExternalPlayback)>,
+}
+
+/// The part of the drive that goes in savestates. Positions and times are in nanoseconds.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct CdState {
+    door_open: bool,
+    playing: Option<PlayState>,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+struct PlayState {
+    last: usize,           // index of the track to stop after
+    position: u128,        // position on the disc when playback was last (re)started
+    since: u128,           // time it was (re)started at
+    paused: Option<u128>,  // time it was paused at
+}
+
+/// Where each track starts on the disc, followed by where the disc ends.
+fn track_offsets(lengths: impl Iterator<Item = u128>) -> Vec<u128> {
+    let mut offsets = vec![0];
+    for length in lengths {
+        offsets.push(offsets.last().unwrap() + length);
+    }
+    offsets
+}
+
+/// Finds the track (indexed from 0) at a position on the disc, and the position within it.
+fn locate(offsets: &[u128], position: u128) -> Option<(usize, u128)> {
+    offsets.windows(2).position(|w| position < w[1]).map(|i| (i, position - offsets[i]))
+}
+
+impl PlayState {
+    fn position(&self, now: u128) -> u128 {
+        self.position + self.paused.unwrap_or(now).saturating_sub(self.since)
+    }
+}
+
+impl CdDrive {
+    pub fn new(folder: PathBuf) -> Self {
+        Self { folder, tracks: None, state: Default::default(), playback: None }
+    }
+
+    pub fn set_folder(&mut self, folder: PathBuf) {
+        self.folder = folder;
+        self.tracks = None;
+    }
+
+    /// Rescans the folder, as if the disc was changed.
+    pub fn init(&mut self, audio: &mut AudioManager) {
+        self.stop(audio);
+        self.tracks = None;
+    }
+
+    fn tracks(&mut self, audio: &AudioManager) -> &[ExternalSound] {
+        let folder = &self.folder;
+        self.tracks.get_or_insert_with(|| {
+            let mut paths = fs::read_dir(folder)
+                .map(|dir| dir.filter_map(|e| e.ok()).map(|e| e.path()).filter(|p| p.is_file()).collect::<Vec<_>>())
+                .unwrap_or_default();
+            paths.sort();
+            paths
+                .into_iter()
+                .filter_map(|path| fs::read(path).ok())
+                .filter_map(|file| audio.add_external(file.into_boxed_slice()))
+                .collect()
+        })
+    }
+
+    fn offsets(&mut self, audio: &AudioManager) -> Vec<u128> {
+        track_offsets(self.tracks(audio).iter().map(ExternalSound::length))
+    }
+
+    pub fn present(&mut self, audio: &AudioManager) -> bool {
+        !self.state.door_open && !self.tracks(audio).is_empty()
+    }
+
+    pub fn number(&mut self, audio: &AudioManager) -> usize {
+        if self.state.door_open { 0 } else { self.tracks(audio).len() }
+    }
+
+    pub fn playing(&self) -> bool {
+        self.state.playing.map_or(false, |p| p.paused.is_none())
+    }
+
+    pub fn paused(&self) -> bool {
+        self.state.playing.map_or(false, |p| p.paused.is_some())
+    }
+
+    /// Total length of the disc.
+    pub fn length(&mut self, audio: &AudioManager) -> u128 {
+        self.tracks(audio).iter().map(|t| t.length()).sum()
+    }
+
+    /// Length of a track, numbered from 1.
+    pub fn track_length(&mut self, audio: &AudioManager, track: i32) -> u128 {
+        usize::try_from(track - 1).ok().and_then(|i| self.tracks(audio).get(i)).map_or(0, |t| t.length())
+    }
+
+    pub fn position(&self, now: u128) -> u128 {
+        self.state.playing.map_or(0, |p| p.position(now))
+    }
+
+    /// The current track, numbered from 1, and the position within it.
+    pub fn track(&mut self, audio: &AudioManager, now: u128) -> (usize, u128) {
+        let position = self.position(now);
+        locate(&self.offsets(audio), position).map_or((1, 0), |(i, position)| (i + 1, position))
+    }
+
+    /// Plays from the start of the first track to the end of the last, numbered from 1.
+    pub fn play(&mut self, audio: &mut AudioManager, first: i32, last: i32, now: u128) {
+        self.stop(audio);
+        let count = self.number(audio);
+        if count == 0 {
+            return
+        }
+        let first = (first.max(1) as usize - 1).min(count - 1);
+        let last = (last.max(1) as usize - 1).clamp(first, count - 1);
+        let position = self.offsets(audio)[first];
+        self.state.playing = Some(PlayState { last, position, since: now, paused: None });
+        self.update(audio, now);
+    }
+
+    pub fn stop(&mut self, audio: &mut AudioManager) {
+        self.state.playing = None;
+        self.stop_track(audio);
+    }
+
+    fn stop_track(&mut self, audio: &mut AudioManager) {
+        if let Some((_, playback)) = self.playback.take() {
+            audio.stop_external(playback.mixer_id);
+        }
+    }
+
+    pub fn pause(&mut self, now: u128) {
+        if let Some(state) = self.state.playing.as_mut().filter(|s| s.paused.is_none()) {
+            state.paused = Some(now);
+            if let Some((_, playback)) = &mut self.playback {
+                playback.set_paused(true, now);
+            }
+        }
+    }
+
+    pub fn resume(&mut self, audio: &mut AudioManager, now: u128) {
+        if let Some(state) = self.state.playing.as_mut() {
+            if let Some(paused) = state.paused.take() {
+                state.since += now.saturating_sub(paused);
+                if let Some((_, playback)) = &mut self.playback {
+                    playback.set_paused(false, now);
+                }
+                self.update(audio, now);
+            }
+        }
+    }
+
+    /// Moves to a position on the disc. This doesn't start playback if the drive is stopped.
+    pub fn set_position(&mut self, audio: &mut AudioManager, position: u128, now: u128) {
+        if let Some(state) = self.state.playing.as_mut() {
+            state.position = position;
+            state.since = now;
+            if state.paused.is_some() {
+                state.paused = Some(now);
+            }
+            self.stop_track(audio);
+            self.update(audio, now);
+        }
+    }
+
+    /// Moves to a position within the current track.
+    pub fn set_track_position(&mut self, audio: &mut AudioManager, position: u128, now: u128) {
+        let (track, _) = self.track(audio, now);
+        let start = self.offsets(audio)[track - 1];
+        self.set_position(audio, start + position, now);
+    }
+
+    pub fn open_door(&mut self, audio: &mut AudioManager) {
+        self.stop(audio);
+        self.state.door_open = true;
+    }
+
+    pub fn close_door(&mut self) {
+        self.state.door_open = false;
+    }
+
+    /// Keeps the mixer in step with the disc position, moving on to the next track or stopping as needed.
+    pub fn update(&mut self, audio: &mut AudioManager, now: u128) {
+        let state = match self.state.playing {
+            Some(state) if state.paused.is_none() => state,
+            _ => return,
+        };
+        let position = state.position(now);
+        let offsets = self.offsets(audio);
+        if offsets.get(state.last + 1).map_or(true, |&end| position >= end) {
+            self.stop(audio);
+            return
+        }
+        let (track, track_position) = locate(&offsets, position).unwrap_or((0, position));
+        match &self.playback {
+            Some((current, playback)) if *current == track && playback.is_playing(now) => (),
+            _ => {
+                self.stop_track(audio);
+                let sound = &self.tracks(audio)[track];
+                let playback = ExternalPlayback::start_at(audio, sound, false, false, now, track_position);
+                self.playback = Some((track, playback));
+            },
+        }
+    }
+
+    pub fn state(&self) -> CdState {
+        self.state.clone()
+    }
+
+    /// Restores the drive from a savestate. Playback picks up again on the next update.
+    pub fn set_state(&mut self, audio: &mut AudioManager, state: CdState) {
+        self.stop_track(audio);
+        self.state = state;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn track_positions() {
+        // the empty second track gets skipped over
+        let offsets = track_offsets([3, 0, 5, 2].into_iter());
+        assert_eq!(offsets, [0, 3, 3, 8, 10]);
+        let cases =
+            [(0, Some((0, 0))), (2, Some((0, 2))), (3, Some((2, 0))), (7, Some((2, 4))), (9, Some((3, 1))), (10, None)];
+        for (position, track) in cases {
+            assert_eq!(locate(&offsets, position), track, "{}", position);
+        }
+
+        let empty = track_offsets(std::iter::empty());
+        assert_eq!(empty, [0]);
+        assert_eq!(locate(&empty, 0), None);
+    }
+
+    #[test]
+    fn play_state_position() {
+        let mut state = PlayState { last: 0, position: 100, since: 1000, paused: None };
+        assert_eq!(state.position(1000), 100);
+        assert_eq!(state.position(1250), 350);
+        state.paused = Some(1100);
+        assert_eq!(state.position(5000), 200);
+        // a clock that went backwards, say from loading a savestate, doesn't underflow
+        state.paused = None;
+        assert_eq!(state.position(900), 100);
+    }
+}
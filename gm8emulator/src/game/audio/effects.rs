@@ -0,0 +1,447 @@
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
+use udon::source::Sample;
+
+// Effect flags, same values as the se_* constants in GML
+pub const CHORUS: u32 = 1 << 0;
+pub const ECHO: u32 = 1 << 1;
+pub const FLANGER: u32 = 1 << 2;
+pub const GARGLE: u32 = 1 << 3;
+pub const REVERB: u32 = 1 << 4;
+pub const COMPRESSOR: u32 = 1 << 5;
+pub const EQUALIZER: u32 = 1 << 6;
+
+/// The DirectX effect parameters for one sound, as set by the sound_effect_* functions.
+/// Defaults are the same as DirectX's.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Settings {
+    pub enabled: u32,
+    pub chorus: Modulation,
+    pub echo: Echo,
+    pub flanger: Modulation,
+    pub gargle: Gargle,
+    pub reverb: Reverb,
+    pub compressor: Compressor,
+    pub equalizer: Equalizer,
+}
+
+/// Parameters for chorus and flanger, which are both a delay line with an oscillating length.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Modulation {
+    pub wet_dry: f32,   // 0 to 100
+    pub depth: f32,     // 0 to 100
+    pub feedback: f32,  // -99 to 99
+    pub frequency: f32, // Hz
+    pub sine: bool,     // triangle wave otherwise
+    pub delay: f32,     // ms
+    pub phase: u32,     // 0 to 4, for -180, -90, 0, 90 and 180 degrees between left and right
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Echo {
+    pub wet_dry: f32,     // 0 to 100
+    pub feedback: f32,    // 0 to 100
+    pub left_delay: f32,  // ms
+    pub right_delay: f32, // ms
+    pub pan_delay: bool,  // whether echoes swap between left and right
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Gargle {
+    pub rate: f32, // Hz
+    pub square: bool,
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Reverb {
+    pub gain: f32,     // dB
+    pub mix: f32,      // dB
+    pub time: f32,     // ms
+    pub hf_ratio: f32, // 0.001 to 0.999
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Compressor {
+    pub gain: f32,      // dB
+    pub attack: f32,    // ms
+    pub release: f32,   // ms
+    pub threshold: f32, // dB
+    pub ratio: f32,
+    pub delay: f32, // ms
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Equalizer {
+    pub center: f32,    // Hz
+    pub bandwidth: f32, // semitones
+    pub gain: f32,      // dB
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            enabled: 0,
+            chorus: Modulation {
+                wet_dry: 50.0,
+                depth: 10.0,
+                feedback: 25.0,
+                frequency: 1.1,
+                sine: true,
+                delay: 16.0,
+                phase: 3,
+            },
+            echo: Echo { wet_dry: 50.0, feedback: 50.0, left_delay: 500.0, right_delay: 500.0, pan_delay: false },
+            flanger: Modulation {
+                wet_dry: 50.0,
+                depth: 100.0,
+                feedback: -50.0,
+                frequency: 0.25,
+                sine: true,
+                delay: 2.0,
+                phase: 2,
+            },
+            gargle: Gargle { rate: 20.0, square: false },
+            reverb: Reverb { gain: 0.0, mix: 0.0, time: 1000.0, hf_ratio: 0.001 },
+            compressor: Compressor {
+                gain: 0.0,
+                attack: 10.0,
+                release: 200.0,
+                threshold: -20.0,
+                ratio: 3.0,
+                delay: 4.0,
+            },
+            equalizer: Equalizer { center: 8000.0, bandwidth: 12.0, gain: 0.0 },
+        }
+    }
+}
+
+/// The state of all the effects on one playing sound.
+/// Effects are applied in the order of their flags, same as GM8 passes them to DirectSound.
+pub struct Chain {
+    sample_rate: f32,
+    channels: usize,
+    settings: Settings,
+    chorus: ModulatedDelay,
+    echo: EchoState,
+    flanger: ModulatedDelay,
+    gargle_phase: f32,
+    reverb: ReverbState,
+    compressor: CompressorState,
+    equalizer: Vec<Biquad>,
+}
+
+impl Chain {
+    pub fn new(sample_rate: u32, channels: u16) -> Self {
+        let (sample_rate, channels) = (sample_rate as f32, usize::from(channels.max(1)));
+        let mut chain = Self {
+            sample_rate,
+            channels,
+            settings: Settings::default(),
+            chorus: ModulatedDelay::new(channels, ms_to_samples(sample_rate, 40.0)),
+            echo: EchoState::new(channels, ms_to_samples(sample_rate, 2000.0)),
+            flanger: ModulatedDelay::new(channels, ms_to_samples(sample_rate, 10.0)),
+            gargle_phase: 0.0,
+            reverb: ReverbState::new(channels, sample_rate),
+            compressor: CompressorState::new(channels, ms_to_samples(sample_rate, 4.0)),
+            equalizer: vec![Biquad::default(); channels],
+        };
+        chain.set_settings(Settings::default());
+        chain
+    }
+
+    /// Replaces the effect parameters, keeping the effects' current state.
+    pub fn set_settings(&mut self, settings: Settings) {
+        self.settings = settings;
+        let eq = settings.equalizer;
+        let coefficients = Biquad::peaking(self.sample_rate, eq.center, eq.bandwidth, eq.gain);
+        for filter in &mut self.equalizer {
+            filter.set_coefficients(coefficients);
+        }
+        self.reverb.set_time(self.sample_rate, settings.reverb.time, settings.reverb.hf_ratio);
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.settings.enabled != 0
+    }
+
+    /// Applies all enabled effects to a buffer of interleaved samples.
+    pub fn process(&mut self, buffer: &mut [Sample]) {
+        let enabled = self.settings.enabled;
+        let (rate, channels) = (self.sample_rate, self.channels);
+        if enabled & CHORUS != 0 {
+            self.chorus.process(buffer, channels, rate, &self.settings.chorus);
+        }
+        if enabled & ECHO != 0 {
+            self.echo.process(buffer, channels, rate, &self.settings.echo);
+        }
+        if enabled & FLANGER != 0 {
+            self.flanger.process(buffer, channels, rate, &self.settings.flanger);
+        }
+        if enabled & GARGLE != 0 {
+            let gargle = self.settings.gargle;
+            for frame in buffer.chunks_mut(channels) {
+                let gain = if gargle.square {
+                    if self.gargle_phase < 0.5 { 1.0 } else { 0.0 }
+                } else {
+                    1.0 - (2.0 * self.gargle_phase - 1.0).abs()
+                };
+                frame.iter_mut().for_each(|s| *s *= gain);
+                self.gargle_phase = (self.gargle_phase + gargle.rate / rate).fract();
+            }
+        }
+        if enabled & REVERB != 0 {
+            self.reverb.process(buffer, channels, &self.settings.reverb);
+        }
+        if enabled & COMPRESSOR != 0 {
+            self.compressor.process(buffer, channels, rate, &self.settings.compressor);
+        }
+        if enabled & EQUALIZER != 0 {
+            for frame in buffer.chunks_mut(channels) {
+                for (sample, filter) in frame.iter_mut().zip(self.equalizer.iter_mut()) {
+                    *sample = filter.process(*sample);
+                }
+            }
+        }
+    }
+}
+
+fn ms_to_samples(sample_rate: f32, ms: f32) -> usize {
+    (sample_rate * ms / 1000.0).ceil().max(1.0) as usize
+}
+
+fn db_to_gain(db: f32) -> f32 {
+    10.0f32.powf(db / 20.0)
+}
+
+/// A fixed-length ring buffer which can be read at any delay up to its length.
+#[derive(Clone)]
+struct DelayLine {
+    buffer: Vec<Sample>,
+    pos: usize,
+}
+
+impl DelayLine {
+    fn new(len: usize) -> Self {
+        Self { buffer: vec![0.0; len + 2], pos: 0 }
+    }
+
+    /// Reads the sample from `delay` samples ago, interpolating between samples.
+    fn read(&self, delay: f32) -> Sample {
+        let delay = delay.clamp(1.0, (self.buffer.len() - 2) as f32);
+        let whole = delay.floor() as usize;
+        let frac = delay - whole as f32;
+        let len = self.buffer.len();
+        let a = self.buffer[(self.pos + len - whole) % len];
+        let b = self.buffer[(self.pos + len - whole - 1) % len];
+        a + (b - a) * frac
+    }
+
+    fn write(&mut self, sample: Sample) {
+        self.pos = (self.pos + 1) % self.buffer.len();
+        self.buffer[self.pos] = sample;
+    }
+}
+
+struct ModulatedDelay {
+    lines: Vec<DelayLine>,
+    phase: f32,
+}
+
+impl ModulatedDelay {
+    fn new(channels: usize, max_delay: usize) -> Self {
+        Self { lines: vec![DelayLine::new(max_delay); channels], phase: 0.0 }
+    }
+
+    fn process(&mut self, buffer: &mut [Sample], channels: usize, rate: f32, settings: &Modulation) {
+        let wet = (settings.wet_dry / 100.0).clamp(0.0, 1.0);
+        let feedback = (settings.feedback / 100.0).clamp(-0.99, 0.99);
+        let depth = (settings.depth / 100.0).clamp(0.0, 1.0);
+        let delay = rate * settings.delay.max(0.0) / 1000.0;
+        let phase_offset = (settings.phase.min(4) as f32 - 2.0) / 4.0;
+        for frame in buffer.chunks_mut(channels) {
+            for (channel, (sample, line)) in frame.iter_mut().zip(self.lines.iter_mut()).enumerate() {
+                // odd channels are on the right, so they get the phase difference
+                let phase = if channel % 2 == 1 { (self.phase + phase_offset).rem_euclid(1.0) } else { self.phase };
+                let lfo = if settings.sine {
+                    (phase * 2.0 * PI).sin()
+                } else {
+                    4.0 * (phase - (phase + 0.5).floor()).abs() - 1.0
+                };
+                let delayed = line.read(delay * (1.0 + lfo * depth));
+                line.write(*sample + delayed * feedback);
+                *sample = *sample * (1.0 - wet) + delayed * wet;
+            }
+            self.phase = (self.phase + settings.frequency.max(0.0) / rate).fract();
+        }
+    }
+}
+
+struct EchoState {
+    lines: Vec<DelayLine>,
+}
+
+impl EchoState {
+    fn new(channels: usize, max_delay: usize) -> Self {
+        Self { lines: vec![DelayLine::new(max_delay); channels] }
+    }
+
+    fn process(&mut self, buffer: &mut [Sample], channels: usize, rate: f32, settings: &Echo) {
+        let wet = (settings.wet_dry / 100.0).clamp(0.0, 1.0);
+        let feedback = (settings.feedback / 100.0).clamp(0.0, 1.0);
+        let delays = [rate * settings.left_delay / 1000.0, rate * settings.right_delay / 1000.0];
+        let mut delayed = vec![0.0; channels];
+        for frame in buffer.chunks_mut(channels) {
+            for (channel, line) in self.lines.iter().enumerate() {
+                delayed[channel] = line.read(delays[channel % 2]);
+            }
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                // with pan delay on, each echo feeds into the opposite side
+                let partner = if settings.pan_delay && channels > 1 { channel ^ 1 } else { channel };
+                let fed_back = delayed.get(partner).copied().unwrap_or(delayed[channel]);
+                self.lines[channel].write(*sample + fed_back * feedback);
+                *sample = *sample * (1.0 - wet) + delayed[channel] * wet;
+            }
+        }
+    }
+}
+
+/// A comb-and-allpass reverb in the style of Schroeder's, approximating the Waves reverb DirectX uses.
+struct ReverbState {
+    combs: Vec<[Comb; 4]>,
+    allpasses: Vec<[DelayLine; 2]>,
+}
+
+struct Comb {
+    line: DelayLine,
+    length: usize,
+    feedback: f32,
+    damping: f32,
+    filtered: Sample,
+}
+
+// Delay lengths in ms, mutually prime-ish so the echoes don't line up
+const COMB_LENGTHS: [f32; 4] = [29.7, 37.1, 41.1, 43.7];
+const ALLPASS_LENGTHS: [f32; 2] = [5.0, 1.7];
+const ALLPASS_FEEDBACK: f32 = 0.5;
+// How much later the combs on odd channels are, for some stereo spread
+const STEREO_SPREAD: f32 = 0.5;
+
+impl ReverbState {
+    fn new(channels: usize, rate: f32) -> Self {
+        let spread = |channel: usize, ms: f32| ms + if channel % 2 == 1 { STEREO_SPREAD } else { 0.0 };
+        Self {
+            combs: (0..channels)
+                .map(|c| {
+                    COMB_LENGTHS.map(|ms| {
+                        let length = ms_to_samples(rate, spread(c, ms));
+                        Comb { line: DelayLine::new(length), length, feedback: 0.0, damping: 0.0, filtered: 0.0 }
+                    })
+                })
+                .collect(),
+            allpasses: (0..channels)
+                .map(|c| ALLPASS_LENGTHS.map(|ms| DelayLine::new(ms_to_samples(rate, spread(c, ms)))))
+                .collect(),
+        }
+    }
+
+    fn set_time(&mut self, rate: f32, time: f32, hf_ratio: f32) {
+        let time = time.max(0.001) / 1000.0;
+        let hf_ratio = hf_ratio.clamp(0.001, 0.999);
+        for comb in self.combs.iter_mut().flatten() {
+            // the feedback which makes this comb decay by 60dB in the reverb time
+            let delay = comb.length as f32 / rate;
+            comb.feedback = 10.0f32.powf(-3.0 * delay / time);
+            // high frequencies should die out faster by the given ratio
+            let hf_feedback = 10.0f32.powf(-3.0 * delay / (time * hf_ratio));
+            comb.damping = (1.0 - hf_feedback / comb.feedback).clamp(0.0, 0.95);
+        }
+    }
+
+    fn process(&mut self, buffer: &mut [Sample], channels: usize, settings: &Reverb) {
+        let in_gain = db_to_gain(settings.gain.min(0.0));
+        let mix = db_to_gain(settings.mix.min(0.0)) / COMB_LENGTHS.len() as f32;
+        for frame in buffer.chunks_mut(channels) {
+            for ((sample, combs), allpasses) in frame.iter_mut().zip(&mut self.combs).zip(&mut self.allpasses) {
+                let input = *sample * in_gain;
+                let mut wet = 0.0;
+                for comb in combs.iter_mut() {
+                    let delayed = comb.line.read(comb.length as f32);
+                    comb.filtered = delayed + (comb.filtered - delayed) * comb.damping;
+                    comb.line.write(input + comb.filtered * comb.feedback);
+                    wet += delayed;
+                }
+                for allpass in allpasses.iter_mut() {
+                    let delayed = allpass.read((allpass.buffer.len() - 2) as f32);
+                    allpass.write(wet + delayed * ALLPASS_FEEDBACK);
+                    wet = delayed - wet * ALLPASS_FEEDBACK;
+                }
+                *sample = input + wet * mix;
+            }
+        }
+    }
+}
+
+struct CompressorState {
+    lines: Vec<DelayLine>,
+    envelope: f32,
+}
+
+impl CompressorState {
+    fn new(channels: usize, max_delay: usize) -> Self {
+        Self { lines: vec![DelayLine::new(max_delay); channels], envelope: 0.0 }
+    }
+
+    fn process(&mut self, buffer: &mut [Sample], channels: usize, rate: f32, settings: &Compressor) {
+        let coefficient = |ms: f32| (-1000.0 / (ms.max(0.01) * rate)).exp();
+        let (attack, release) = (coefficient(settings.attack), coefficient(settings.release));
+        let slope = 1.0 - 1.0 / settings.ratio.max(1.0);
+        let output_gain = db_to_gain(settings.gain);
+        let delay = rate * settings.delay.max(0.0) / 1000.0;
+        for frame in buffer.chunks_mut(channels) {
+            // the envelope follows the loudest channel, so all channels get the same gain and the image stays put
+            let peak = frame.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+            let coefficient = if peak > self.envelope { attack } else { release };
+            self.envelope = peak + (self.envelope - peak) * coefficient;
+            let level = 20.0 * self.envelope.max(1e-6).log10();
+            let reduction = (level - settings.threshold).max(0.0) * slope;
+            let gain = db_to_gain(-reduction) * output_gain;
+            // the audio is delayed relative to the envelope, so it can react before a peak arrives
+            for (sample, line) in frame.iter_mut().zip(self.lines.iter_mut()) {
+                line.write(*sample);
+                let delayed = if delay >= 1.0 { line.read(delay) } else { *sample };
+                *sample = delayed * gain;
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Biquad {
+    coefficients: [f32; 5],
+    state: [f32; 4],
+}
+
+impl Biquad {
+    /// RBJ peaking EQ coefficients, normalised so a0 = 1.
+    fn peaking(rate: f32, center: f32, bandwidth: f32, gain: f32) -> [f32; 5] {
+        let a = 10.0f32.powf(gain / 40.0);
+        let w0 = 2.0 * PI * center.clamp(1.0, rate * 0.49) / rate;
+        let (sin, cos) = w0.sin_cos();
+        let octaves = bandwidth.max(0.01) / 12.0;
+        let alpha = sin * ((2.0f32.ln() / 2.0) * octaves * w0 / sin).sinh();
+        let a0 = 1.0 + alpha / a;
+        [(1.0 + alpha * a) / a0, (-2.0 * cos) / a0, (1.0 - alpha * a) / a0, (-2.0 * cos) / a0, (1.0 - alpha / a) / a0]
+    }
+
+    fn set_coefficients(&mut self, coefficients: [f32; 5]) {
+        self.coefficients = coefficients;
+    }
+
+    fn process(&mut self, x: Sample) -> Sample {
+        let [b0, b1, b2, a1, a2] = self.coefficients;
+        let [x1, x2, y1, y2] = self.state;
+        let y = b0 * x + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+        self.state = [x, x1, y, y1];
+        y
+    }
+}
@@ -0,0 +1,332 @@
+use super::{AudioManager, ExternalPlayback, ExternalSound};
+use crate::gml::file;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, fs};
+
+/// Interprets the common MCI command strings games send through MCI_command, playing through the mixer.
+/// Devices are keyed by their lowercase alias, and times are in milliseconds as with MCI's default time format.
+#[derive(Default)]
+pub struct Mci {
+    devices: HashMap<String, Device>,
+}
+
+struct Device {
+    path: String,
+    sound: ExternalSound,
+    playback: Option<ExternalPlayback>,
+    position: u128, // where playback stops at and restarts from, in nanoseconds
+    end: Option<u128>,
+    volume: f64,
+}
+
+/// The open devices, for savestates. Sounds get loaded again from their files.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct MciState {
+    devices: Vec<DeviceState>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct DeviceState {
+    alias: String,
+    path: String,
+    position: u128,
+    end: Option<u128>,
+    volume: f64,
+    playing: Option<(bool, bool)>, // looping, paused
+}
+
+const MS: u128 = 1_000_000;
+
+/// Splits a command into words, keeping quoted paths in one piece.
+fn tokenize(command: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = command.trim();
+    while !rest.is_empty() {
+        let (token, tail) = match rest.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => rest.split_once(char::is_whitespace).unwrap_or((rest, "")),
+        };
+        tokens.push(token);
+        rest = tail.trim_start();
+    }
+    tokens
+}
+
+/// Splits a command into its lowercase verb, its device and the rest of its arguments. The `wait` and `notify` flags
+/// can go anywhere after the device, but they're dropped since every command finishes straight away.
+fn parse(command: &str) -> Option<(String, &str, Vec<&str>)> {
+    let tokens = tokenize(command);
+    let (verb, device, args) = match tokens.as_slice() {
+        [verb, device, args @ ..] => (verb.to_ascii_lowercase(), *device, args),
+        _ => return None,
+    };
+    let is_flag = |a: &&str| a.eq_ignore_ascii_case("wait") || a.eq_ignore_ascii_case("notify");
+    Some((verb, device, args.iter().copied().filter(|a| !is_flag(a)).collect()))
+}
+
+/// Finds the value following a keyword, such as the alias in `open file alias music`.
+fn option<'a>(args: &[&'a str], keyword: &str) -> Option<&'a str> {
+    args.iter().position(|a| a.eq_ignore_ascii_case(keyword)).and_then(|i| args.get(i + 1)).copied()
+}
+
+fn flag(args: &[&str], keyword: &str) -> bool {
+    args.iter().any(|a| a.eq_ignore_ascii_case(keyword))
+}
+
+fn time(args: &[&str], keyword: &str) -> Option<u128> {
+    option(args, keyword).and_then(|t| t.parse::<u128>().ok()).map(|t| t * MS)
+}
+
+fn load(audio: &AudioManager, path: &str) -> Option<ExternalSound> {
+    fs::read(file::to_path(path).as_ref()).ok().and_then(|f| audio.add_external(f.into_boxed_slice()))
+}
+
+impl Device {
+    fn is_playing(&self, now: u128) -> bool {
+        self.playback.as_ref().map_or(false, |p| p.paused_at.is_none() && p.is_playing(now))
+    }
+
+    fn position(&self, now: u128) -> u128 {
+        match &self.playback {
+            Some(p) if p.is_playing(now) => p.position(now),
+            Some(_) => self.end.unwrap_or_else(|| self.sound.length()),
+            None => self.position,
+        }
+    }
+
+    /// Answers `status <device> <item>`.
+    fn status(&self, item: &str, now: u128) -> String {
+        match item.to_ascii_lowercase().as_str() {
+            "mode" => match &self.playback {
+                Some(p) if p.paused_at.is_some() => "paused".into(),
+                _ if self.is_playing(now) => "playing".into(),
+                _ => "stopped".into(),
+            },
+            "length" => (self.sound.length() / MS).to_string(),
+            "position" => (self.position(now) / MS).to_string(),
+            "ready" => "true".into(),
+            "volume" => ((self.volume * 1000.0).round() as i32).to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn stop(&mut self, audio: &mut AudioManager, now: u128) {
+        self.position = self.position(now);
+        if let Some(playback) = self.playback.take() {
+            audio.stop_external(playback.mixer_id);
+        }
+    }
+}
+
+impl Mci {
+    /// Runs a command, returning what MCI would write to its return string.
+    pub fn command(&mut self, audio: &mut AudioManager, command: &str, now: u128) -> String {
+        self.update(audio, now);
+        let (verb, file, args) = match parse(command) {
+            Some(parsed) => parsed,
+            None => return String::new(),
+        };
+        let name = file.to_ascii_lowercase();
+        if verb == "open" {
+            let alias = option(&args, "alias").map_or(name, |a| a.to_ascii_lowercase());
+            if let Some(sound) = load(audio, file) {
+                if let Some(mut old) = self.devices.remove(&alias) {
+                    old.stop(audio, now);
+                }
+                let device = Device { path: file.into(), sound, playback: None, position: 0, end: None, volume: 1.0 };
+                self.devices.insert(alias, device);
+            }
+            return String::new()
+        }
+        if verb == "close" && name == "all" {
+            for (_, mut device) in self.devices.drain() {
+                device.stop(audio, now);
+            }
+            return String::new()
+        }
+        let device = match self.devices.get_mut(&name) {
+            Some(device) => device,
+            None => return String::new(),
+        };
+        match verb.as_str() {
+            "play" => {
+                let from = time(&args, "from").unwrap_or_else(|| {
+                    let position = device.position(now);
+                    if position >= device.sound.length() { 0 } else { position }
+                });
+                device.stop(audio, now);
+                device.end = time(&args, "to");
+                let looping = flag(&args, "repeat");
+                let playback = ExternalPlayback::start_at(audio, &device.sound, looping, false, now, from);
+                playback.set_volume(device.volume);
+                device.playback = Some(playback);
+            },
+            "stop" => device.stop(audio, now),
+            "pause" => {
+                if let Some(playback) = &mut device.playback {
+                    playback.set_paused(true, now);
+                }
+            },
+            "resume" => {
+                if let Some(playback) = &mut device.playback {
+                    playback.set_paused(false, now);
+                }
+            },
+            "seek" => {
+                device.stop(audio, now);
+                device.position = match args.as_slice() {
+                    [to, position] if to.eq_ignore_ascii_case("to") => {
+                        if position.eq_ignore_ascii_case("start") {
+                            0
+                        } else if position.eq_ignore_ascii_case("end") {
+                            device.sound.length()
+                        } else {
+                            position.parse::<u128>().map_or(0, |t| t * MS).min(device.sound.length())
+                        }
+                    },
+                    _ => device.position,
+                };
+            },
+            "close" => {
+                if let Some(mut device) = self.devices.remove(&name) {
+                    device.stop(audio, now);
+                }
+            },
+            "status" => return args.first().map_or_else(String::new, |item| device.status(item, now)),
+            "setaudio" => {
+                let volume = match option(&args, "volume") {
+                    Some(to) if to.eq_ignore_ascii_case("to") => option(&args, "to"),
+                    volume => volume,
+                };
+                if let Some(volume) = volume.and_then(|v| v.parse::<f64>().ok()) {
+                    let volume = f64::clamp(volume, 0.0, 1000.0) / 1000.0;
+                    device.volume = volume;
+                    if let Some(playback) = &device.playback {
+                        playback.set_volume(volume);
+                    }
+                }
+            },
+            _ => (),
+        }
+        String::new()
+    }
+
+    /// Stops devices which have reached the position given to `play ... to`.
+    pub fn update(&mut self, audio: &mut AudioManager, now: u128) {
+        for device in self.devices.values_mut() {
+            if let (Some(playback), Some(end)) = (&device.playback, device.end) {
+                if !playback.looping && playback.position(now) >= end {
+                    device.stop(audio, now);
+                    device.position = end;
+                }
+            }
+        }
+    }
+
+    pub fn state(&self, now: u128) -> MciState {
+        let devices = self
+            .devices
+            .iter()
+            .map(|(alias, device)| DeviceState {
+                alias: alias.clone(),
+                path: device.path.clone(),
+                position: device.position(now),
+                end: device.end,
+                volume: device.volume,
+                playing: device
+                    .playback
+                    .as_ref()
+                    .filter(|p| p.is_playing(now))
+                    .map(|p| (p.looping, p.paused_at.is_some())),
+            })
+            .collect();
+        MciState { devices }
+    }
+
+    /// Restores the open devices from a savestate, reopening their files and picking up where they were.
+    pub fn set_state(&mut self, audio: &mut AudioManager, state: MciState, now: u128) {
+        for (_, mut device) in self.devices.drain() {
+            device.stop(audio, now);
+        }
+        for saved in state.devices {
+            if let Some(sound) = load(audio, &saved.path) {
+                let playback = saved.playing.map(|(looping, paused)| {
+                    let playback = ExternalPlayback::start_at(audio, &sound, looping, paused, now, saved.position);
+                    playback.set_volume(saved.volume);
+                    playback
+                });
+                let device = Device {
+                    path: saved.path,
+                    sound,
+                    playback,
+                    position: saved.position,
+                    end: saved.end,
+                    volume: saved.volume,
+                };
+                self.devices.insert(saved.alias, device);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{super::midi::Sequence, *};
+    use std::sync::Arc;
+
+    #[test]
+    fn tokenize_commands() {
+        let cases: &[(&str, &[&str])] = &[
+            ("", &[]),
+            ("  play   music  ", &["play", "music"]),
+            (r#"open "C:\My Music\song.mp3" type mpegvideo alias music"#, &[
+                "open",
+                r"C:\My Music\song.mp3",
+                "type",
+                "mpegvideo",
+                "alias",
+                "music",
+            ]),
+            (r#"open "" alias x"#, &["open", "", "alias", "x"]),
+            (r#"open "unterminated path"#, &["open", "unterminated path"]),
+        ];
+        for (command, tokens) in cases {
+            assert_eq!(tokenize(command), *tokens, "{}", command);
+        }
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse("close"), None);
+        let cases: &[(&str, &str, &str, &[&str])] = &[
+            ("PLAY Music from 0 to 1000", "play", "Music", &["from", "0", "to", "1000"]),
+            ("play music from 0 wait", "play", "music", &["from", "0"]),
+            ("seek music WAIT to start", "seek", "music", &["to", "start"]),
+            ("status music notify length", "status", "music", &["length"]),
+            (r#"open "a b.mid" alias x wait"#, "open", "a b.mid", &["alias", "x"]),
+        ];
+        for &(command, verb, device, args) in cases {
+            assert_eq!(parse(command), Some((verb.to_string(), device, args.to_vec())), "{}", command);
+        }
+    }
+
+    #[test]
+    fn status() {
+        // a 2.5 second midi file, as it's the one kind of sound that doesn't need decoding
+        let file = b"MThd\0\0\0\x06\0\0\0\x01\0\x60MTrk\0\0\0\x06\x83\x60\xFF\x2F\0\0";
+        let sound = ExternalSound::Midi(Arc::new(Sequence::new(&file[..]).unwrap()));
+        let device =
+            Device { path: "song.mid".into(), sound, playback: None, position: 1500 * MS, end: None, volume: 0.25 };
+        let cases = [
+            ("length", "2500"),
+            ("position", "1500"),
+            ("mode", "stopped"),
+            ("Volume", "250"),
+            ("ready", "true"),
+            ("time format", ""),
+        ];
+        for (item, answer) in cases {
+            assert_eq!(device.status(item, 0), answer, "{}", item);
+        }
+    }
+}
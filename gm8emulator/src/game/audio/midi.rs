@@ -0,0 +1,399 @@
+use super::{make_volume, soundfont::SoundFont, synth::Synth};
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+use udon::source::{ChannelCount, Sample, SampleRate, Source};
+
+/// A MIDI file flattened into a single list of timed events, with all tempo changes already applied.
+#[derive(Serialize, Deserialize)]
+pub struct Sequence {
+    events: Vec<Event>,
+    length: u64, // microseconds
+}
+
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Event {
+    pub time: u64, // microseconds
+    pub message: Message,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Message {
+    NoteOff { channel: u8, key: u8 },
+    NoteOn { channel: u8, key: u8, velocity: u8 },
+    ControlChange { channel: u8, controller: u8, value: u8 },
+    ProgramChange { channel: u8, program: u8 },
+    PitchBend { channel: u8, value: i16 },
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidHeader,
+    UnexpectedEof,
+}
+
+impl std::error::Error for Error {}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidHeader => write!(f, "invalid MIDI header"),
+            Self::UnexpectedEof => write!(f, "unexpected end of MIDI data"),
+        }
+    }
+}
+
+// Default tempo is 120bpm
+const DEFAULT_TEMPO: u64 = 500000;
+
+impl Sequence {
+    /// Parses a standard MIDI file, or an RMID file containing one.
+    pub fn new(file: &[u8]) -> Result<Self, Error> {
+        let file = unwrap_rmid(file).unwrap_or(file);
+        let mut reader = Reader { data: file, pos: 0 };
+        if reader.bytes(4)? != b"MThd" {
+            return Err(Error::InvalidHeader)
+        }
+        let header_len = reader.u32()? as usize;
+        let header = Reader { data: reader.bytes(header_len)?, pos: 0 };
+        let (track_count, division) = match header.data {
+            [_, _, t1, t2, d1, d2, ..] => (u16::from_be_bytes([*t1, *t2]), u16::from_be_bytes([*d1, *d2])),
+            _ => return Err(Error::InvalidHeader),
+        };
+
+        // Read every track into (tick, message) pairs, keeping tempo changes separately
+        let mut timed = Vec::new();
+        let mut tempos = vec![(0u64, DEFAULT_TEMPO)];
+        let mut end_tick = 0;
+        let mut found_tracks = 0;
+        while found_tracks < track_count && reader.pos + 8 <= reader.data.len() {
+            let id = reader.bytes(4)?;
+            let len = reader.u32()? as usize;
+            // some files lie about the last track's length
+            let len = len.min(reader.data.len() - reader.pos);
+            let data = reader.bytes(len)?;
+            if id != b"MTrk" {
+                continue
+            }
+            found_tracks += 1;
+            let mut track = Reader { data, pos: 0 };
+            let mut tick = 0u64;
+            let mut running_status = 0u8;
+            while track.pos < track.data.len() {
+                tick += u64::from(track.varlen()?);
+                let mut status = track.u8()?;
+                if status < 0x80 {
+                    // running status, so that byte was actually data
+                    track.pos -= 1;
+                    status = running_status;
+                }
+                match status {
+                    0xFF => {
+                        let kind = track.u8()?;
+                        let len = track.varlen()? as usize;
+                        let data = track.bytes(len)?;
+                        match (kind, data) {
+                            (0x51, &[a, b, c]) => tempos.push((tick, u64::from(u32::from_be_bytes([0, a, b, c])))),
+                            (0x2F, _) => break,
+                            _ => (),
+                        }
+                    },
+                    0xF0 | 0xF7 => {
+                        let len = track.varlen()? as usize;
+                        track.bytes(len)?;
+                    },
+                    0x80..=0xEF => {
+                        running_status = status;
+                        let channel = status & 0xF;
+                        let message = match status & 0xF0 {
+                            0x80 => {
+                                let (key, _) = (track.u8()?, track.u8()?);
+                                Some(Message::NoteOff { channel, key })
+                            },
+                            0x90 => match (track.u8()?, track.u8()?) {
+                                (key, 0) => Some(Message::NoteOff { channel, key }),
+                                (key, velocity) => Some(Message::NoteOn { channel, key, velocity }),
+                            },
+                            0xB0 => {
+                                let (controller, value) = (track.u8()?, track.u8()?);
+                                Some(Message::ControlChange { channel, controller, value })
+                            },
+                            0xC0 => Some(Message::ProgramChange { channel, program: track.u8()? }),
+                            0xE0 => {
+                                let (lsb, msb) = (track.u8()?, track.u8()?);
+                                let value = ((i16::from(msb & 0x7F) << 7) | i16::from(lsb & 0x7F)) - 0x2000;
+                                Some(Message::PitchBend { channel, value })
+                            },
+                            0xD0 => {
+                                track.u8()?;
+                                None
+                            },
+                            _ => {
+                                // key pressure
+                                track.bytes(2)?;
+                                None
+                            },
+                        };
+                        if let Some(message) = message {
+                            timed.push((tick, message));
+                        }
+                    },
+                    _ => (),
+                }
+            }
+            end_tick = end_tick.max(tick);
+        }
+
+        // Merge the tracks, keeping the order of events that happen on the same tick
+        timed.sort_by_key(|(tick, _)| *tick);
+        tempos.sort_by_key(|(tick, _)| *tick);
+
+        let to_micros = |tick: u64| -> u64 {
+            if division & 0x8000 != 0 {
+                // SMPTE timing, in frames per second and ticks per frame
+                let fps = u64::from(((division >> 8) as i8).unsigned_abs()).max(1);
+                let ticks_per_frame = u64::from(division & 0xFF).max(1);
+                tick * 1000000 / (fps * ticks_per_frame)
+            } else {
+                let ticks_per_beat = u64::from(division).max(1);
+                let mut micros = 0;
+                let (mut last_tick, mut tempo) = (0, DEFAULT_TEMPO);
+                for &(change_tick, new_tempo) in tempos.iter().take_while(|(t, _)| *t < tick) {
+                    micros += (change_tick - last_tick) * tempo / ticks_per_beat;
+                    last_tick = change_tick;
+                    tempo = new_tempo;
+                }
+                micros + (tick - last_tick) * tempo / ticks_per_beat
+            }
+        };
+
+        let events = timed.into_iter().map(|(tick, message)| Event { time: to_micros(tick), message }).collect();
+        Ok(Self { events, length: to_micros(end_tick) })
+    }
+
+    /// The length of the sequence in microseconds.
+    pub fn length(&self) -> u64 {
+        self.length
+    }
+}
+
+fn unwrap_rmid(file: &[u8]) -> Option<&[u8]> {
+    if file.get(0..4)? != b"RIFF" || file.get(8..12)? != b"RMID" {
+        return None
+    }
+    let mut pos = 12;
+    while pos + 8 <= file.len() {
+        let len = u32::from_le_bytes(file[pos + 4..pos + 8].try_into().ok()?) as usize;
+        if &file[pos..pos + 4] == b"data" {
+            return file.get(pos + 8..(pos + 8 + len).min(file.len()))
+        }
+        pos += 8 + len + (len & 1);
+    }
+    None
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        let bytes = self.data.get(self.pos..self.pos + len).ok_or(Error::UnexpectedEof)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn varlen(&mut self) -> Result<u32, Error> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = (value << 7) | u32::from(byte & 0x7F);
+            if byte & 0x80 == 0 {
+                break
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Plays a Sequence through the synthesizer, in stereo at the mixer's sample rate.
+pub struct MidiPlayer {
+    sequence: Arc<Sequence>,
+    synth: Synth,
+    sample_rate: SampleRate,
+    volume: Arc<AtomicU32>,
+    tempo: Arc<AtomicU32>,
+    next_event: usize,
+    time: f64, // microseconds into the sequence
+}
+
+impl MidiPlayer {
+    pub fn new(
+        sequence: Arc<Sequence>,
+        soundfont: Option<Arc<SoundFont>>,
+        sample_rate: SampleRate,
+        volume: Arc<AtomicU32>,
+        tempo: Arc<AtomicU32>,
+    ) -> Self {
+        let synth = Synth::new(soundfont, u32::from(sample_rate));
+        Self { sequence, synth, sample_rate, volume, tempo, next_event: 0, time: 0.0 }
+    }
+}
+
+impl Source for MidiPlayer {
+    fn channel_count(&self) -> ChannelCount {
+        ChannelCount::new(2).unwrap()
+    }
+
+    fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+
+    fn write_samples(&mut self, buffer: &mut [Sample]) -> usize {
+        let tempo = f64::from(f32::from_bits(self.tempo.load(Ordering::Acquire)));
+        let micros_per_frame = 1000000.0 * tempo / f64::from(u32::from(self.sample_rate));
+        let length = self.sequence.length as f64;
+        let mut written = 0;
+        for frame in buffer.chunks_exact_mut(2) {
+            if self.time >= length {
+                break
+            }
+            while let Some(event) = self.sequence.events.get(self.next_event) {
+                if event.time as f64 > self.time {
+                    break
+                }
+                self.synth.handle(event.message);
+                self.next_event += 1;
+            }
+            self.synth.render_frame(frame);
+            self.time += micros_per_frame;
+            written += 2;
+        }
+        let volume = make_volume(f64::from(f32::from_bits(self.volume.load(Ordering::Acquire))));
+        buffer[..written].iter_mut().for_each(|s| *s *= volume);
+        written
+    }
+
+    fn reset(&mut self) {
+        self.synth.reset();
+        self.next_event = 0;
+        self.time = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a standard MIDI file with 96 ticks per beat, unless `division` says otherwise.
+    fn smf(format: u16, division: u16, tracks: &[&[u8]]) -> Vec<u8> {
+        let mut file = b"MThd\0\0\0\x06".to_vec();
+        for x in [format, tracks.len() as u16, division] {
+            file.extend_from_slice(&x.to_be_bytes());
+        }
+        for track in tracks {
+            file.extend_from_slice(b"MTrk");
+            file.extend_from_slice(&(track.len() as u32).to_be_bytes());
+            file.extend_from_slice(track);
+        }
+        file
+    }
+
+    fn events(sequence: &Sequence) -> Vec<(u64, Message)> {
+        sequence.events.iter().map(|e| (e.time, e.message)).collect()
+    }
+
+    const END: [u8; 4] = [0, 0xFF, 0x2F, 0];
+
+    #[test]
+    fn format_0() {
+        let track = [&[0, 0x90, 60, 100, 0x60, 0x90, 60, 0, 0, 0xC1, 5][..], &END].concat();
+        let sequence = Sequence::new(&smf(0, 96, &[&track])).unwrap();
+        assert_eq!(events(&sequence), [
+            (0, Message::NoteOn { channel: 0, key: 60, velocity: 100 }),
+            (500000, Message::NoteOff { channel: 0, key: 60 }),
+            (500000, Message::ProgramChange { channel: 1, program: 5 }),
+        ]);
+        assert_eq!(sequence.length(), 500000);
+    }
+
+    #[test]
+    fn format_1_and_tempo() {
+        // the tempo track halves the beat length after one beat
+        let tempo = [&[0, 0xFF, 0x51, 3, 0x07, 0xA1, 0x20, 0x60, 0xFF, 0x51, 3, 0x03, 0xD0, 0x90][..], &END].concat();
+        let notes = [
+            &[0x60, 0x91, 64, 90, 0x60, 0x81, 64, 0][..],
+            // a two-beat delta, which needs two bytes
+            &[0x81, 0x40, 0xB1, 7, 100],
+            &END,
+        ]
+        .concat();
+        let sequence = Sequence::new(&smf(1, 96, &[&tempo, &notes])).unwrap();
+        assert_eq!(events(&sequence), [
+            (500000, Message::NoteOn { channel: 1, key: 64, velocity: 90 }),
+            (750000, Message::NoteOff { channel: 1, key: 64 }),
+            (1250000, Message::ControlChange { channel: 1, controller: 7, value: 100 }),
+        ]);
+        assert_eq!(sequence.length(), 1250000);
+    }
+
+    #[test]
+    fn running_status() {
+        let track = [
+            &[0, 0x92, 60, 100][..],
+            &[0x30, 62, 80],
+            // meta and sysex events don't change the running status
+            &[0, 0xFF, 0x01, 2, b'h', b'i', 0, 0xF0, 1, 0xF7],
+            &[0x30, 60, 0],
+            &[0, 0xE2, 0, 0x40, 0, 0x7F, 0x7F],
+            &END,
+        ]
+        .concat();
+        let sequence = Sequence::new(&smf(0, 96, &[&track])).unwrap();
+        assert_eq!(events(&sequence), [
+            (0, Message::NoteOn { channel: 2, key: 60, velocity: 100 }),
+            (250000, Message::NoteOn { channel: 2, key: 62, velocity: 80 }),
+            (500000, Message::NoteOff { channel: 2, key: 60 }),
+            (500000, Message::PitchBend { channel: 2, value: 0 }),
+            (500000, Message::PitchBend { channel: 2, value: 0x1FFF }),
+        ]);
+    }
+
+    #[test]
+    fn smpte_and_rmid() {
+        // 25 frames per second of 40 ticks each, so a tick is a millisecond
+        let track = [&[0x87, 0x68, 0x90, 60, 100][..], &END].concat();
+        let file = smf(0, 0xE728, &[&track]);
+        assert_eq!(events(&Sequence::new(&file).unwrap()), [(1000000, Message::NoteOn {
+            channel: 0,
+            key: 60,
+            velocity: 100
+        })]);
+
+        let mut rmid = b"RIFF\0\0\0\0RMIDdata".to_vec();
+        rmid.extend_from_slice(&(file.len() as u32).to_le_bytes());
+        rmid.extend_from_slice(&file);
+        assert_eq!(Sequence::new(&rmid).unwrap().length(), 1000000);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(Sequence::new(b"MTh"), Err(Error::UnexpectedEof)));
+        assert!(matches!(Sequence::new(b"RIFF\0\0\0\0WAVE"), Err(Error::InvalidHeader)));
+        assert!(matches!(Sequence::new(b"MThd\0\0\0\x02\0\0"), Err(Error::InvalidHeader)));
+        let track = [0, 0x90, 60];
+        assert!(matches!(Sequence::new(&smf(0, 96, &[&track])), Err(Error::UnexpectedEof)));
+    }
+}
@@ -1,4 +1,4 @@
-use super::SoundParams;
+use super::{effects, make_volume, SoundParams};
 use std::sync::{
     atomic::{AtomicU32, Ordering},
     mpsc::{self, Receiver, Sender},
@@ -12,16 +12,37 @@ const INIT_CAPACITY: usize = 16;
 pub struct Mixer {
     channels: ChannelCount,
     sample_rate: SampleRate,
-    sources: Vec<(Box<dyn Source + Send + 'static>, Arc<SoundParams>, i32)>,
+    sources: Vec<Playing>,
     exclusive_source: Option<(Box<dyn Source + Send + 'static>, i32)>,
+    fades: Vec<Fade>,
     global_volume: Arc<AtomicU32>,
     input_buffer: Vec<Sample>,
     receiver: Receiver<Command>,
 }
 
+/// A sound being mixed, along with the state of its effects.
+struct Playing {
+    source: Box<dyn Source + Send + 'static>,
+    params: Arc<SoundParams>,
+    id: i32,
+    // Only created once effects get enabled, as the delay lines take a fair bit of memory
+    effects: Option<Box<effects::Chain>>,
+    effects_id: u32,
+}
+
+/// A volume ramp started by sound_fade.
+struct Fade {
+    params: Arc<SoundParams>,
+    target: f32,
+    step: f32,
+    // Matches the sound's fade_id until something else changes its volume
+    id: u32,
+}
+
 enum Command {
     Add { source: Box<dyn Source + Send + 'static>, params: Arc<SoundParams>, id: i32 },
     AddExclusive { source: Box<dyn Source + Send + 'static>, id: i32 },
+    Fade { params: Arc<SoundParams>, target: f32, frames: u64, id: u32 },
     Stop(i32),
     StopAll,
 }
@@ -47,6 +68,7 @@ impl Mixer {
                 sample_rate,
                 sources: Vec::with_capacity(INIT_CAPACITY),
                 exclusive_source: None,
+                fades: Vec::new(),
                 global_volume,
                 input_buffer: Vec::new(),
                 receiver,
@@ -61,10 +83,20 @@ impl Source for Mixer {
         // Check for new incoming commands
         while let Ok(cmd) = self.receiver.try_recv() {
             match cmd {
-                Command::Add { source, params, id } => self.sources.push((source, params, id)),
+                Command::Add { source, params, id } => {
+                    // effects_id starts out different so the effects get checked on the first buffer
+                    let effects_id = params.effects_id.load(Ordering::Acquire).wrapping_sub(1);
+                    self.sources.push(Playing { source, params, id, effects: None, effects_id })
+                },
                 Command::AddExclusive { source, id } => self.exclusive_source = Some((source, id)),
+                Command::Fade { params, target, frames, id } => {
+                    let current = f32::from_bits(params.volume.load(Ordering::Acquire));
+                    let step = (target - current).abs() / frames.max(1) as f32;
+                    self.fades.retain(|fade| !Arc::ptr_eq(&fade.params, &params));
+                    self.fades.push(Fade { params, target, step, id });
+                },
                 Command::Stop(id) => {
-                    self.sources.retain(|(_, _, x)| *x != id);
+                    self.sources.retain(|playing| playing.id != id);
                     if let Some((_, x)) = &self.exclusive_source {
                         if *x == id {
                             self.exclusive_source = None;
@@ -88,16 +120,63 @@ impl Source for Mixer {
             buffer.iter_mut().for_each(|x| *x = 0.0);
         }
 
+        // Move any fading volumes along by the length of this buffer
+        let channel_count = usize::from(u16::from(self.channels));
+        let frames = (buffer.len() / channel_count) as f32;
+        self.fades.retain(|fade| {
+            if fade.params.fade_id.load(Ordering::Acquire) != fade.id {
+                return false
+            }
+            let current = f32::from_bits(fade.params.volume.load(Ordering::Acquire));
+            let distance = fade.target - current;
+            let new = if distance.abs() <= fade.step * frames {
+                fade.target
+            } else {
+                current + fade.step * frames * distance.signum()
+            };
+            fade.params.volume.store(new.to_bits(), Ordering::Release);
+            new != fade.target
+        });
+
         let input_buffer = &mut self.input_buffer;
         input_buffer.resize_with(buffer.len(), Default::default);
         let global_volume = f32::from_bits(self.global_volume.load(Ordering::Acquire));
+        let sample_rate = u32::from(self.sample_rate);
 
-        RetainMut::retain_mut(&mut self.sources, |(source, params, _)| {
-            let volume = f32::from_bits(params.volume.load(Ordering::Acquire));
-            let count = source.write_samples(input_buffer);
+        RetainMut::retain_mut(&mut self.sources, |playing| {
+            let params = &playing.params;
+            if params.paused.load(Ordering::Acquire) {
+                return true
+            }
+            let volume = make_volume(f32::from_bits(params.volume.load(Ordering::Acquire)).into())
+                * f32::from_bits(params.spatial_volume.load(Ordering::Acquire));
+            let pan = (f32::from_bits(params.pan.load(Ordering::Acquire))
+                + f32::from_bits(params.spatial_pan.load(Ordering::Acquire)))
+            .clamp(-1.0, 1.0);
+            // Panning attenuates the opposite side on the same scale as volume
+            let channel_volumes = [make_volume((1.0 - pan.max(0.0)).into()), make_volume((1.0 + pan.min(0.0)).into())];
+            let count = playing.source.write_samples(input_buffer);
+
+            let effects_id = params.effects_id.load(Ordering::Acquire);
+            if effects_id != playing.effects_id {
+                playing.effects_id = effects_id;
+                let settings = *params.effects.lock().unwrap();
+                if settings.enabled != 0 || playing.effects.is_some() {
+                    playing
+                        .effects
+                        .get_or_insert_with(|| Box::new(effects::Chain::new(sample_rate, channel_count as u16)))
+                        .set_settings(settings);
+                }
+            }
+            if let Some(chain) = playing.effects.as_mut().filter(|chain| chain.is_active()) {
+                chain.process(&mut input_buffer[..count]);
+            }
 
-            for (in_sample, out_sample) in input_buffer.iter().take(count).copied().zip(buffer.iter_mut()) {
-                *out_sample += in_sample * volume * global_volume;
+            for (i, (in_sample, out_sample)) in
+                input_buffer.iter().take(count).copied().zip(buffer.iter_mut()).enumerate()
+            {
+                let channel_volume = if channel_count >= 2 { channel_volumes[(i % channel_count) % 2] } else { 1.0 };
+                *out_sample += in_sample * volume * channel_volume * global_volume;
             }
 
             count == input_buffer.len()
@@ -132,6 +211,12 @@ impl MixerHandle {
         self.0.send(command).map_err(|_| Error::SendError)
     }
 
+    /// Ramps a sound's volume to the target over the given number of sample frames.
+    /// The fade is cancelled if the sound's fade_id changes from `id` before it finishes.
+    pub fn fade(&self, params: Arc<SoundParams>, target: f32, frames: u64, id: u32) -> Result<(), Error> {
+        self.0.send(Command::Fade { params, target, frames, id }).map_err(|_| Error::SendError)
+    }
+
     /// Stops all sounds with a certain ID
     pub fn stop(&self, id: i32) -> Result<(), Error> {
         self.0.send(Command::Stop(id)).map_err(|_| Error::SendError)
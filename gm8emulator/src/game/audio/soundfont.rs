@@ -0,0 +1,299 @@
+use std::collections::HashMap;
+
+/// A parsed SoundFont 2 file, with presets flattened into regions so notes can be looked up quickly.
+pub struct SoundFont {
+    samples: Vec<f32>,
+    headers: Vec<SampleHeader>,
+    presets: HashMap<(u16, u16), Vec<Region>>,
+}
+
+pub struct SampleHeader {
+    pub start: u32,
+    pub end: u32,
+    pub loop_start: u32,
+    pub loop_end: u32,
+    pub sample_rate: u32,
+    pub original_pitch: u8,
+    pub pitch_correction: i8,
+}
+
+/// One preset zone combined with one of its instrument's zones.
+/// Generators are already resolved: instrument defaults and global zones applied, and preset values added on.
+pub struct Region {
+    pub key_range: (u8, u8),
+    pub vel_range: (u8, u8),
+    pub gens: [i16; GEN_COUNT],
+}
+
+#[derive(Debug)]
+pub enum Error {
+    InvalidFormat,
+    MissingChunk(&'static str),
+    NoSamples,
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidFormat => write!(f, "not a valid SoundFont 2 file"),
+            Self::MissingChunk(id) => write!(f, "missing '{}' chunk", id),
+            Self::NoSamples => write!(f, "'smpl' chunk has no samples"),
+        }
+    }
+}
+
+// Generator indices we use
+pub const START_OFFSET: usize = 0;
+pub const END_OFFSET: usize = 1;
+pub const LOOP_START_OFFSET: usize = 2;
+pub const LOOP_END_OFFSET: usize = 3;
+pub const START_COARSE_OFFSET: usize = 4;
+pub const END_COARSE_OFFSET: usize = 12;
+pub const PAN: usize = 17;
+pub const DELAY_VOL_ENV: usize = 33;
+pub const ATTACK_VOL_ENV: usize = 34;
+pub const HOLD_VOL_ENV: usize = 35;
+pub const DECAY_VOL_ENV: usize = 36;
+pub const SUSTAIN_VOL_ENV: usize = 37;
+pub const RELEASE_VOL_ENV: usize = 38;
+const INSTRUMENT: usize = 41;
+const KEY_RANGE: usize = 43;
+const VEL_RANGE: usize = 44;
+pub const LOOP_START_COARSE_OFFSET: usize = 45;
+pub const INITIAL_ATTENUATION: usize = 48;
+pub const LOOP_END_COARSE_OFFSET: usize = 50;
+pub const COARSE_TUNE: usize = 51;
+pub const FINE_TUNE: usize = 52;
+pub const SAMPLE_ID: usize = 53;
+pub const SAMPLE_MODES: usize = 54;
+pub const SCALE_TUNING: usize = 56;
+pub const EXCLUSIVE_CLASS: usize = 57;
+pub const OVERRIDING_ROOT_KEY: usize = 58;
+pub const GEN_COUNT: usize = 61;
+
+/// The instrument-level defaults from the SF2 spec, for the generators that aren't 0 by default.
+fn default_gens() -> [i16; GEN_COUNT] {
+    let mut gens = [0; GEN_COUNT];
+    gens[8] = 13500; // initialFilterFc
+    for timecents in [21, 23, 25, 26, 27, 28, 30, DELAY_VOL_ENV, ATTACK_VOL_ENV, HOLD_VOL_ENV, DECAY_VOL_ENV] {
+        gens[timecents] = -12000;
+    }
+    gens[RELEASE_VOL_ENV] = -12000;
+    gens[46] = -1; // keynum
+    gens[47] = -1; // velocity
+    gens[SCALE_TUNING] = 100;
+    gens[OVERRIDING_ROOT_KEY] = -1;
+    gens
+}
+
+struct Zone {
+    gens: Vec<(usize, i16)>,
+    key_range: (u8, u8),
+    vel_range: (u8, u8),
+}
+
+impl SoundFont {
+    pub fn new(file: &[u8]) -> Result<Self, Error> {
+        if file.get(0..4) != Some(b"RIFF") || file.get(8..12) != Some(b"sfbk") {
+            return Err(Error::InvalidFormat)
+        }
+        let lists = chunks(&file[12..]);
+        let find_list = |kind: &[u8]| {
+            lists.iter().find(|(id, data)| id == b"LIST" && data.get(0..4) == Some(kind)).map(|(_, data)| &data[4..])
+        };
+        let sdta = chunks(find_list(b"sdta").ok_or(Error::MissingChunk("sdta"))?);
+        let pdta = chunks(find_list(b"pdta").ok_or(Error::MissingChunk("pdta"))?);
+        let smpl = find(&sdta, "smpl")?;
+        let samples: Vec<f32> =
+            smpl.chunks_exact(2).map(|b| f32::from(i16::from_le_bytes([b[0], b[1]])) / 32768.0).collect();
+        // every sample position is clamped into this, so it can't be empty
+        if samples.is_empty() {
+            return Err(Error::NoSamples)
+        }
+
+        let headers = find(&pdta, "shdr")?
+            .chunks_exact(46)
+            .map(|r| SampleHeader {
+                start: u32_at(r, 20),
+                end: u32_at(r, 24),
+                loop_start: u32_at(r, 28),
+                loop_end: u32_at(r, 32),
+                sample_rate: u32_at(r, 36).max(1),
+                original_pitch: r[40],
+                pitch_correction: r[41] as i8,
+            })
+            .collect::<Vec<_>>();
+
+        let instrument_zones = read_zones(find(&pdta, "inst")?, find(&pdta, "ibag")?, find(&pdta, "igen")?, 22, 20);
+        let preset_zones = read_zones(find(&pdta, "phdr")?, find(&pdta, "pbag")?, find(&pdta, "pgen")?, 38, 24);
+        let preset_ids = find(&pdta, "phdr")?
+            .chunks_exact(38)
+            .map(|r| (u16_at(r, 22), u16_at(r, 20)))
+            .collect::<Vec<_>>();
+
+        let mut presets = HashMap::new();
+        for (zones, &(bank, program)) in preset_zones.iter().zip(preset_ids.iter()) {
+            let mut regions = Vec::new();
+            for preset_zone in zones.1.iter() {
+                let instrument = match preset_zone.gens.iter().find(|(g, _)| *g == INSTRUMENT) {
+                    Some(&(_, i)) => i as usize,
+                    None => continue,
+                };
+                let instrument_zones = match instrument_zones.get(instrument) {
+                    Some(zones) => zones,
+                    None => continue,
+                };
+                for instrument_zone in instrument_zones.1.iter() {
+                    if !instrument_zone.gens.iter().any(|(g, _)| *g == SAMPLE_ID) {
+                        continue
+                    }
+                    let key_range = intersect(preset_zone.key_range, instrument_zone.key_range);
+                    let vel_range = intersect(preset_zone.vel_range, instrument_zone.vel_range);
+                    if key_range.0 > key_range.1 || vel_range.0 > vel_range.1 {
+                        continue
+                    }
+                    let mut gens = default_gens();
+                    for &(g, value) in instrument_zones.0.iter().chain(instrument_zone.gens.iter()) {
+                        gens[g] = value;
+                    }
+                    // preset generators are offsets on top of the instrument's
+                    let mut offsets = [0i16; GEN_COUNT];
+                    for &(g, value) in zones.0.iter().chain(preset_zone.gens.iter()) {
+                        offsets[g] = value;
+                    }
+                    for (g, offset) in offsets.iter().copied().enumerate() {
+                        // (except for the ones which aren't allowed at preset level)
+                        if !matches!(g, INSTRUMENT | SAMPLE_ID | SAMPLE_MODES | EXCLUSIVE_CLASS | OVERRIDING_ROOT_KEY)
+                            && !(START_OFFSET..=LOOP_END_OFFSET).contains(&g)
+                            && !matches!(g, START_COARSE_OFFSET | END_COARSE_OFFSET | 46 | 47)
+                            && !matches!(g, LOOP_START_COARSE_OFFSET | LOOP_END_COARSE_OFFSET)
+                        {
+                            gens[g] = gens[g].saturating_add(offset);
+                        }
+                    }
+                    if headers.get(gens[SAMPLE_ID] as u16 as usize).is_some() {
+                        regions.push(Region { key_range, vel_range, gens });
+                    }
+                }
+            }
+            presets.entry((bank, program)).or_insert(regions);
+        }
+
+        Ok(Self { samples, headers, presets })
+    }
+
+    /// Finds all regions that should sound for a note, falling back to similar presets if this one doesn't exist.
+    pub fn regions(&self, bank: u16, program: u16, key: u8, velocity: u8) -> impl Iterator<Item = &Region> {
+        let regions = self
+            .presets
+            .get(&(bank, program))
+            .or_else(|| if bank == 128 { self.presets.get(&(128, 0)) } else { self.presets.get(&(0, program)) })
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        regions.iter().filter(move |r| {
+            (r.key_range.0..=r.key_range.1).contains(&key) && (r.vel_range.0..=r.vel_range.1).contains(&velocity)
+        })
+    }
+
+    pub fn header(&self, index: i16) -> &SampleHeader {
+        &self.headers[index as u16 as usize]
+    }
+
+    pub fn samples(&self) -> &[f32] {
+        &self.samples
+    }
+}
+
+fn find<'a>(list: &[([u8; 4], &'a [u8])], id: &'static str) -> Result<&'a [u8], Error> {
+    list.iter().find(|(x, _)| x == id.as_bytes()).map(|(_, data)| *data).ok_or(Error::MissingChunk(id))
+}
+
+/// Splits a chunk list into (id, data) pairs.
+fn chunks(mut data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = Vec::new();
+    while data.len() >= 8 {
+        let id = [data[0], data[1], data[2], data[3]];
+        let len = (u32_at(data, 4) as usize).min(data.len() - 8);
+        chunks.push((id, &data[8..8 + len]));
+        data = &data[(8 + len + (len & 1)).min(data.len())..];
+    }
+    chunks
+}
+
+/// Reads the zones for each preset or instrument. The first part of each pair is the global zone's generators.
+/// Header records are `header_size` bytes long with the bag index at `bag_offset`.
+/// As usual, the last header and bag records are terminators.
+fn read_zones(
+    headers: &[u8],
+    bags: &[u8],
+    gens: &[u8],
+    header_size: usize,
+    bag_offset: usize,
+) -> Vec<(Vec<(usize, i16)>, Vec<Zone>)> {
+    let bag_starts = headers.chunks_exact(header_size).map(|r| u16_at(r, bag_offset) as usize).collect::<Vec<_>>();
+    let gen_starts = bags.chunks_exact(4).map(|r| u16_at(r, 0) as usize).collect::<Vec<_>>();
+    let gens = gens.chunks_exact(4).map(|r| (u16_at(r, 0) as usize, i16::from_le_bytes([r[2], r[3]]))).collect::<Vec<_>>();
+
+    bag_starts
+        .windows(2)
+        .map(|bag_range| {
+            let mut global = Vec::new();
+            let mut zones = Vec::new();
+            for bag in bag_range[0]..bag_range[1] {
+                let (start, end) = match (gen_starts.get(bag), gen_starts.get(bag + 1)) {
+                    (Some(&start), Some(&end)) if start <= end && end <= gens.len() => (start, end),
+                    _ => continue,
+                };
+                let mut zone = Zone { gens: Vec::new(), key_range: (0, 127), vel_range: (0, 127) };
+                for &(g, value) in &gens[start..end] {
+                    let [lo, hi] = value.to_le_bytes();
+                    match g {
+                        KEY_RANGE => zone.key_range = (lo, hi),
+                        VEL_RANGE => zone.vel_range = (lo, hi),
+                        g if g < GEN_COUNT => zone.gens.push((g, value)),
+                        _ => (),
+                    }
+                }
+                // a first zone without an instrument or sample is the global zone
+                let terminal = zone.gens.iter().any(|(g, _)| *g == INSTRUMENT || *g == SAMPLE_ID);
+                if !terminal && bag == bag_range[0] {
+                    global = zone.gens;
+                } else {
+                    zones.push(zone);
+                }
+            }
+            (global, zones)
+        })
+        .collect()
+}
+
+fn intersect(a: (u8, u8), b: (u8, u8)) -> (u8, u8) {
+    (a.0.max(b.0), a.1.min(b.1))
+}
+
+fn u16_at(data: &[u8], pos: usize) -> u16 {
+    u16::from_le_bytes([data[pos], data[pos + 1]])
+}
+
+fn u32_at(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+        [id, &(data.len() as u32).to_le_bytes(), data].concat()
+    }
+
+    #[test]
+    fn rejects_empty_samples() {
+        let sdta = chunk(b"LIST", &[&b"sdta"[..], &chunk(b"smpl", &[])].concat());
+        let pdta = chunk(b"LIST", b"pdta");
+        let file = chunk(b"RIFF", &[&b"sfbk"[..], &sdta, &pdta].concat());
+        assert!(matches!(SoundFont::new(&file), Err(Error::NoSamples)));
+        assert!(matches!(SoundFont::new(&file[..12]), Err(Error::MissingChunk("sdta"))));
+        assert!(matches!(SoundFont::new(b"RIFF\0\0\0\0WAVE"), Err(Error::InvalidFormat)));
+    }
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+/// The DirectSound3D properties of a 3D sound, as set by the sound_3d_* functions.
+/// GM8 never moves the listener, so it's always at the origin facing +z with +y up.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct Spatial {
+    pub position: [f64; 3],
+    pub velocity: [f64; 3],
+    pub min_distance: f64,
+    pub max_distance: f64,
+    pub cone_orientation: [f64; 3],
+    pub cone_inside: f64,  // degrees
+    pub cone_outside: f64, // degrees
+    pub outside_volume: f64, // hundredths of a decibel, -10000 to 0
+}
+
+impl Default for Spatial {
+    fn default() -> Self {
+        Self {
+            position: [0.0; 3],
+            velocity: [0.0; 3],
+            min_distance: 1.0,
+            max_distance: 1000000000.0,
+            cone_orientation: [0.0, 0.0, 1.0],
+            cone_inside: 360.0,
+            cone_outside: 360.0,
+            outside_volume: 0.0,
+        }
+    }
+}
+
+impl Spatial {
+    /// Calculates the gain and the pan (-1 to 1) this sound would have as heard from the listener.
+    /// Velocity is stored but not applied, as the emulator has no pitch control to do doppler with.
+    pub fn output(&self) -> (f32, f32) {
+        let [x, y, z] = self.position;
+        let distance = (x * x + y * y + z * z).sqrt();
+        let min_distance = self.min_distance.max(f64::EPSILON);
+
+        // inverse distance rolloff, which stops getting quieter at the max distance
+        let mut gain = min_distance / distance.clamp(min_distance, self.max_distance.max(min_distance));
+
+        // sound cones, checked against the direction from the sound to the listener
+        if self.cone_outside < 360.0 && distance > 0.0 {
+            let [ox, oy, oz] = self.cone_orientation;
+            let length = (ox * ox + oy * oy + oz * oz).sqrt();
+            if length > 0.0 {
+                let cos = -(x * ox + y * oy + z * oz) / (distance * length);
+                let angle = cos.clamp(-1.0, 1.0).acos().to_degrees() * 2.0;
+                let (inside, outside) = (self.cone_inside.min(self.cone_outside), self.cone_outside);
+                let outside_db = self.outside_volume.clamp(-10000.0, 0.0) / 100.0;
+                let db = if angle <= inside {
+                    0.0
+                } else if angle >= outside {
+                    outside_db
+                } else {
+                    outside_db * (angle - inside) / (outside - inside)
+                };
+                gain *= 10.0f64.powf(db / 20.0);
+            }
+        }
+
+        let pan = if distance > 0.0 { x / distance } else { 0.0 };
+        (gain as f32, pan as f32)
+    }
+}
@@ -0,0 +1,544 @@
+use super::{
+    midi::Message,
+    soundfont::{self, SoundFont},
+};
+use std::{f64::consts::TAU, sync::Arc};
+use udon::source::Sample;
+
+const MAX_VOICES: usize = 64;
+const PERCUSSION_CHANNEL: u8 = 9;
+const PERCUSSION_BANK: u16 = 128;
+// Envelope level at which a voice is considered silent
+const SILENCE: f32 = 0.00001;
+// The built-in instruments are raw waveforms, so they need to be a good bit quieter than sampled ones
+const FALLBACK_GAIN: f32 = 0.25;
+
+/// A General MIDI synthesizer which plays SoundFont presets, or simple built-in waveforms if there's no SoundFont.
+pub struct Synth {
+    soundfont: Option<Arc<SoundFont>>,
+    sample_rate: f32,
+    channels: [Channel; 16],
+    voices: Vec<Voice>,
+    noise: u32,
+    note_count: u64,
+}
+
+#[derive(Clone, Copy)]
+struct Channel {
+    bank: u16,
+    program: u8,
+    volume: u8,
+    expression: u8,
+    pan: u8,
+    sustain: bool,
+    pitch_bend: i16,
+    bend_range: u8, // semitones
+    bend_ratio: f64,
+    rpn: (u8, u8),
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self {
+            bank: 0,
+            program: 0,
+            volume: 100,
+            expression: 127,
+            pan: 64,
+            sustain: false,
+            pitch_bend: 0,
+            bend_range: 2,
+            bend_ratio: 1.0,
+            rpn: (127, 127),
+        }
+    }
+}
+
+impl Channel {
+    fn gain(&self) -> f32 {
+        // squared, which is close to the concave curve the SF2 default modulators use
+        let volume = f32::from(self.volume) / 127.0;
+        let expression = f32::from(self.expression) / 127.0;
+        volume * volume * expression * expression
+    }
+
+    fn update_bend(&mut self) {
+        let cents = f64::from(self.pitch_bend) / 8192.0 * f64::from(self.bend_range) * 100.0;
+        self.bend_ratio = 2.0f64.powf(cents / 1200.0);
+    }
+}
+
+struct Voice {
+    channel: u8,
+    key: u8,
+    generator: Generator,
+    gain: f32,
+    pan: f32,
+    envelope: Envelope,
+    state: VoiceState,
+    exclusive_class: i16,
+    started: u64,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum VoiceState {
+    Playing,
+    Sustained, // note off received while the sustain pedal was down
+    Released,
+    Finished,
+}
+
+enum Generator {
+    Sample { pos: f64, step: f64, end: usize, loop_start: usize, loop_end: usize, mode: LoopMode },
+    Oscillator { phase: f64, step: f64, wave: Wave },
+    Noise { filtered: f32, cutoff: f32 },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LoopMode {
+    None,
+    Continuous,
+    UntilRelease,
+}
+
+#[derive(Clone, Copy)]
+enum Wave {
+    Sine,
+    Triangle,
+    Square,
+    Saw,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    Delay,
+    Attack,
+    Hold,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// A DAHDSR volume envelope. Times are in samples, attack is linear and everything else is linear in decibels.
+struct Envelope {
+    stage: Stage,
+    level: f32,
+    timer: f32,
+    delay: f32,
+    attack: f32,
+    hold: f32,
+    decay_factor: f32,
+    sustain: f32,
+    release_factor: f32,
+}
+
+impl Envelope {
+    /// `decay` and `release` are the times it would take to fall by 100dB, as in SF2.
+    fn new(rate: f32, delay: f32, attack: f32, hold: f32, decay: f32, sustain: f32, release: f32) -> Self {
+        let factor = |seconds: f32| 10.0f32.powf(-5.0 / (seconds * rate).max(1.0));
+        Self {
+            stage: Stage::Delay,
+            level: 0.0,
+            timer: 0.0,
+            delay: delay * rate,
+            attack: attack * rate,
+            hold: hold * rate,
+            decay_factor: factor(decay),
+            sustain: sustain.clamp(0.0, 1.0),
+            release_factor: factor(release),
+        }
+    }
+
+    fn next(&mut self) -> f32 {
+        self.timer += 1.0;
+        match self.stage {
+            Stage::Delay => {
+                if self.timer >= self.delay {
+                    self.stage = Stage::Attack;
+                    self.timer = 0.0;
+                }
+            },
+            Stage::Attack => {
+                self.level = (self.timer / self.attack.max(1.0)).min(1.0);
+                if self.level >= 1.0 {
+                    self.stage = Stage::Hold;
+                    self.timer = 0.0;
+                }
+            },
+            Stage::Hold => {
+                if self.timer >= self.hold {
+                    self.stage = Stage::Decay;
+                }
+            },
+            Stage::Decay => {
+                self.level *= self.decay_factor;
+                if self.level <= self.sustain {
+                    self.level = self.sustain;
+                    self.stage = Stage::Sustain;
+                }
+            },
+            Stage::Sustain => (),
+            Stage::Release => self.level *= self.release_factor,
+        }
+        self.level
+    }
+
+    fn release(&mut self) {
+        self.stage = Stage::Release;
+    }
+
+    /// Whether the envelope has faded out and won't come back.
+    fn is_silent(&self) -> bool {
+        self.level < SILENCE && matches!(self.stage, Stage::Decay | Stage::Sustain | Stage::Release)
+    }
+}
+
+fn timecents_to_seconds(timecents: i16) -> f32 {
+    2.0f32.powf(f32::from(timecents) / 1200.0)
+}
+
+fn centibels_to_gain(centibels: i16) -> f32 {
+    10.0f32.powf(-f32::from(centibels.max(0)) / 200.0)
+}
+
+impl Synth {
+    pub fn new(soundfont: Option<Arc<SoundFont>>, sample_rate: u32) -> Self {
+        Self {
+            soundfont,
+            sample_rate: sample_rate as f32,
+            channels: Default::default(),
+            voices: Vec::with_capacity(MAX_VOICES),
+            noise: 0x12345678,
+            note_count: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.channels = Default::default();
+        self.voices.clear();
+    }
+
+    pub fn handle(&mut self, message: Message) {
+        match message {
+            Message::NoteOn { channel, key, velocity } => self.note_on(channel, key, velocity),
+            Message::NoteOff { channel, key } => {
+                let sustain = self.channels[usize::from(channel)].sustain;
+                for voice in self.voices.iter_mut() {
+                    if voice.channel == channel && voice.key == key && voice.state == VoiceState::Playing {
+                        if sustain {
+                            voice.state = VoiceState::Sustained;
+                        } else {
+                            voice.release();
+                        }
+                    }
+                }
+            },
+            Message::ControlChange { channel, controller, value } => {
+                let state = &mut self.channels[usize::from(channel)];
+                match controller {
+                    0 => state.bank = u16::from(value),
+                    6 if state.rpn == (0, 0) => {
+                        state.bend_range = value;
+                        state.update_bend();
+                    },
+                    7 => state.volume = value,
+                    10 => state.pan = value,
+                    11 => state.expression = value,
+                    64 => {
+                        state.sustain = value >= 64;
+                        if !state.sustain {
+                            self.voices
+                                .iter_mut()
+                                .filter(|v| v.channel == channel && v.state == VoiceState::Sustained)
+                                .for_each(Voice::release);
+                        }
+                    },
+                    100 => state.rpn.1 = value,
+                    101 => state.rpn.0 = value,
+                    // all sound off
+                    120 => self.voices.retain(|v| v.channel != channel),
+                    // reset all controllers
+                    121 => {
+                        *state = Channel { bank: state.bank, program: state.program, ..Default::default() };
+                    },
+                    // all notes off
+                    123 => {
+                        self.voices.iter_mut().filter(|v| v.channel == channel).for_each(Voice::release);
+                    },
+                    _ => (),
+                }
+            },
+            Message::ProgramChange { channel, program } => self.channels[usize::from(channel)].program = program,
+            Message::PitchBend { channel, value } => {
+                let state = &mut self.channels[usize::from(channel)];
+                state.pitch_bend = value;
+                state.update_bend();
+            },
+        }
+    }
+
+    fn note_on(&mut self, channel: u8, key: u8, velocity: u8) {
+        let state = self.channels[usize::from(channel)];
+        let (bank, program) = if channel == PERCUSSION_CHANNEL {
+            (PERCUSSION_BANK, u16::from(state.program))
+        } else {
+            (state.bank, u16::from(state.program))
+        };
+        let velocity_gain = (f32::from(velocity) / 127.0).powi(2);
+        let mut new_voices = Vec::new();
+
+        if let Some(soundfont) = &self.soundfont {
+            for region in soundfont.regions(bank, program, key, velocity) {
+                let gens = &region.gens;
+                let header = soundfont.header(gens[soundfont::SAMPLE_ID]);
+                let sample_count = soundfont.samples().len();
+                let offset = |base: u32, fine: usize, coarse: usize| {
+                    let pos = i64::from(base) + i64::from(gens[fine]) + i64::from(gens[coarse]) * 32768;
+                    pos.clamp(0, sample_count as i64 - 1) as usize
+                };
+                let start = offset(header.start, soundfont::START_OFFSET, soundfont::START_COARSE_OFFSET);
+                let end = offset(header.end, soundfont::END_OFFSET, soundfont::END_COARSE_OFFSET);
+                let loop_start =
+                    offset(header.loop_start, soundfont::LOOP_START_OFFSET, soundfont::LOOP_START_COARSE_OFFSET);
+                let loop_end = offset(header.loop_end, soundfont::LOOP_END_OFFSET, soundfont::LOOP_END_COARSE_OFFSET);
+                let mode = match gens[soundfont::SAMPLE_MODES] & 3 {
+                    _ if loop_end <= loop_start => LoopMode::None,
+                    1 => LoopMode::Continuous,
+                    3 => LoopMode::UntilRelease,
+                    _ => LoopMode::None,
+                };
+
+                let root_key = match gens[soundfont::OVERRIDING_ROOT_KEY] {
+                    root @ 0..=127 => root,
+                    _ => i16::from(header.original_pitch),
+                };
+                let cents = f64::from(i16::from(key) - root_key) * f64::from(gens[soundfont::SCALE_TUNING])
+                    + f64::from(gens[soundfont::COARSE_TUNE]) * 100.0
+                    + f64::from(gens[soundfont::FINE_TUNE])
+                    + f64::from(header.pitch_correction);
+                let step = 2.0f64.powf(cents / 1200.0) * f64::from(header.sample_rate) / f64::from(self.sample_rate);
+
+                let envelope = Envelope::new(
+                    self.sample_rate,
+                    timecents_to_seconds(gens[soundfont::DELAY_VOL_ENV]),
+                    timecents_to_seconds(gens[soundfont::ATTACK_VOL_ENV]),
+                    timecents_to_seconds(gens[soundfont::HOLD_VOL_ENV]),
+                    timecents_to_seconds(gens[soundfont::DECAY_VOL_ENV]),
+                    centibels_to_gain(gens[soundfont::SUSTAIN_VOL_ENV]),
+                    timecents_to_seconds(gens[soundfont::RELEASE_VOL_ENV]),
+                );
+                new_voices.push(Voice {
+                    channel,
+                    key,
+                    generator: Generator::Sample { pos: start as f64, step, end, loop_start, loop_end, mode },
+                    gain: centibels_to_gain(gens[soundfont::INITIAL_ATTENUATION]) * velocity_gain,
+                    pan: (f32::from(gens[soundfont::PAN]) / 500.0).clamp(-1.0, 1.0),
+                    envelope,
+                    state: VoiceState::Playing,
+                    exclusive_class: gens[soundfont::EXCLUSIVE_CLASS],
+                    started: self.note_count,
+                });
+            }
+        } else {
+            new_voices.push(self.fallback_voice(channel, key, state.program));
+            new_voices[0].gain *= velocity_gain;
+        }
+
+        // starting a note in an exclusive class (like an open hi-hat) cuts off the others in that class
+        for class in new_voices.iter().map(|v| v.exclusive_class).filter(|&c| c != 0) {
+            self.voices.retain(|v| v.channel != channel || v.exclusive_class != class);
+        }
+        for voice in new_voices {
+            if self.voices.len() >= MAX_VOICES {
+                // steal the oldest voice, preferring ones that have already been released
+                let oldest = self
+                    .voices
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, v)| (v.state == VoiceState::Playing, v.started))
+                    .map(|(i, _)| i);
+                if let Some(i) = oldest {
+                    self.voices.swap_remove(i);
+                }
+            }
+            self.voices.push(voice);
+        }
+        self.note_count += 1;
+    }
+
+    /// Makes a voice for the built-in instruments, which pick a waveform and envelope from the GM instrument family.
+    fn fallback_voice(&mut self, channel: u8, key: u8, program: u8) -> Voice {
+        let rate = self.sample_rate;
+        let frequency = 440.0 * 2.0f64.powf((f64::from(key) - 69.0) / 12.0);
+        let (generator, envelope) = if channel == PERCUSSION_CHANNEL {
+            match key {
+                // bass drums are a low thump
+                35 | 36 => (
+                    Generator::Oscillator { phase: 0.0, step: 55.0 / f64::from(rate), wave: Wave::Sine },
+                    Envelope::new(rate, 0.0, 0.001, 0.0, 0.6, 0.0, 0.1),
+                ),
+                // cymbals ring for longer and are brighter
+                49 | 51 | 52 | 55 | 57 | 59 => (
+                    Generator::Noise { filtered: 0.0, cutoff: 0.9 },
+                    Envelope::new(rate, 0.0, 0.001, 0.0, 2.0, 0.0, 0.3),
+                ),
+                _ => (
+                    Generator::Noise { filtered: 0.0, cutoff: (f32::from(key) / 127.0).clamp(0.1, 0.9) },
+                    Envelope::new(rate, 0.0, 0.001, 0.0, 0.5, 0.0, 0.1),
+                ),
+            }
+        } else {
+            // (wave, attack, decay, sustain, release) for each family of 8 instruments
+            let (wave, attack, decay, sustain, release) = match program / 8 {
+                0 | 1 => (Wave::Triangle, 0.002, 3.0, 0.0, 0.3),
+                2 => (Wave::Square, 0.01, 1.0, 0.8, 0.05),
+                3 => (Wave::Saw, 0.002, 2.0, 0.0, 0.2),
+                4 => (Wave::Triangle, 0.005, 4.0, 0.0, 0.1),
+                5 | 6 => (Wave::Saw, 0.08, 1.0, 0.7, 0.3),
+                7 | 8 => (Wave::Square, 0.03, 1.0, 0.7, 0.1),
+                9 => (Wave::Sine, 0.03, 1.0, 0.8, 0.1),
+                10 => (Wave::Square, 0.005, 1.0, 0.8, 0.1),
+                11 => (Wave::Triangle, 0.3, 2.0, 0.6, 0.8),
+                _ => (Wave::Sine, 0.005, 2.0, 0.3, 0.3),
+            };
+            (
+                Generator::Oscillator { phase: 0.0, step: frequency / f64::from(rate), wave },
+                Envelope::new(rate, 0.0, attack, 0.0, decay, sustain, release),
+            )
+        };
+        Voice {
+            channel,
+            key,
+            generator,
+            gain: FALLBACK_GAIN,
+            pan: 0.0,
+            envelope,
+            state: VoiceState::Playing,
+            exclusive_class: 0,
+            started: self.note_count,
+        }
+    }
+
+    /// Renders a single stereo frame.
+    pub fn render_frame(&mut self, frame: &mut [Sample]) {
+        let (mut left, mut right) = (0.0, 0.0);
+        let samples = self.soundfont.as_ref().map(|sf| sf.samples()).unwrap_or(&[]);
+        for voice in self.voices.iter_mut() {
+            let channel = &self.channels[usize::from(voice.channel)];
+            let sample = match &mut voice.generator {
+                Generator::Sample { pos, step, end, loop_start, loop_end, mode } => {
+                    let looping = *mode == LoopMode::Continuous
+                        || (*mode == LoopMode::UntilRelease && voice.state != VoiceState::Released);
+                    let index = *pos as usize;
+                    if !looping && index + 1 >= *end {
+                        voice.state = VoiceState::Finished;
+                        continue
+                    }
+                    let frac = (*pos - index as f64) as f32;
+                    let next = if looping && index + 1 >= *loop_end { *loop_start } else { index + 1 };
+                    let (a, b) = (samples[index], samples[next]);
+                    *pos += *step * channel.bend_ratio;
+                    if looping && *pos >= *loop_end as f64 {
+                        *pos -= (*loop_end - *loop_start) as f64;
+                    }
+                    a + (b - a) * frac
+                },
+                Generator::Oscillator { phase, step, wave } => {
+                    let p = *phase;
+                    *phase = (*phase + *step * channel.bend_ratio).fract();
+                    (match wave {
+                        Wave::Sine => (p * TAU).sin(),
+                        Wave::Triangle => 1.0 - 4.0 * (p - 0.5).abs(),
+                        Wave::Square => {
+                            if p < 0.5 {
+                                1.0
+                            } else {
+                                -1.0
+                            }
+                        },
+                        Wave::Saw => 2.0 * p - 1.0,
+                    }) as f32
+                },
+                Generator::Noise { filtered, cutoff } => {
+                    // xorshift, then a one-pole low pass so lower drums sound lower
+                    self.noise ^= self.noise << 13;
+                    self.noise ^= self.noise >> 17;
+                    self.noise ^= self.noise << 5;
+                    let white = (self.noise as f32 / u32::MAX as f32) * 2.0 - 1.0;
+                    *filtered += (white - *filtered) * *cutoff;
+                    *filtered
+                },
+            };
+            let level = voice.envelope.next();
+            if voice.envelope.is_silent() {
+                voice.state = VoiceState::Finished;
+                continue
+            }
+            let pan = (voice.pan + (f32::from(channel.pan) - 64.0) / 64.0).clamp(-1.0, 1.0);
+            let angle = (pan + 1.0) * std::f32::consts::FRAC_PI_4;
+            let out = sample * level * voice.gain * channel.gain();
+            left += out * angle.cos();
+            right += out * angle.sin();
+        }
+        self.voices.retain(|v| v.state != VoiceState::Finished);
+        frame[0] = left;
+        frame[1] = right;
+    }
+}
+
+impl Voice {
+    fn release(&mut self) {
+        if self.state != VoiceState::Finished {
+            self.state = VoiceState::Released;
+            self.envelope.release();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders some frames, returning the loudest sample.
+    fn render(synth: &mut Synth, frames: usize) -> f32 {
+        let mut frame = [0.0; 2];
+        (0..frames).fold(0.0, |peak: f32, _| {
+            synth.render_frame(&mut frame);
+            peak.max(frame[0].abs()).max(frame[1].abs())
+        })
+    }
+
+    #[test]
+    fn fallback_notes() {
+        let mut synth = Synth::new(None, 1000);
+        assert_eq!(render(&mut synth, 10), 0.0);
+        synth.handle(Message::NoteOn { channel: 0, key: 69, velocity: 127 });
+        assert!(render(&mut synth, 100) > 0.01);
+        synth.handle(Message::NoteOff { channel: 0, key: 69 });
+        assert_eq!(synth.voices[0].state, VoiceState::Released);
+        render(&mut synth, 1000);
+        assert!(synth.voices.is_empty());
+        assert_eq!(render(&mut synth, 10), 0.0);
+    }
+
+    #[test]
+    fn sustain_and_controllers() {
+        let mut synth = Synth::new(None, 1000);
+        synth.handle(Message::ControlChange { channel: 3, controller: 64, value: 127 });
+        synth.handle(Message::NoteOn { channel: 3, key: 60, velocity: 100 });
+        synth.handle(Message::NoteOff { channel: 3, key: 60 });
+        assert_eq!(synth.voices[0].state, VoiceState::Sustained);
+        synth.handle(Message::ControlChange { channel: 3, controller: 64, value: 0 });
+        assert_eq!(synth.voices[0].state, VoiceState::Released);
+        synth.handle(Message::NoteOn { channel: 3, key: 62, velocity: 100 });
+        synth.handle(Message::ControlChange { channel: 3, controller: 120, value: 0 });
+        assert!(synth.voices.is_empty());
+
+        // voices past the limit steal the oldest
+        for key in 0..=MAX_VOICES as u8 {
+            synth.handle(Message::NoteOn { channel: 0, key, velocity: 100 });
+        }
+        assert_eq!(synth.voices.len(), MAX_VOICES);
+        assert!(synth.voices.iter().all(|v| v.key != 0));
+    }
+}
@@ -0,0 +1,192 @@
+use crate::asset::font::charset_encoding;
+use encoding_rs::Encoding;
+use gm8exe::{asset::PascalString, GameAssets};
+use std::collections::HashMap;
+
+/// Looks up an encoding by its Windows code page number (such as 932 or 1251) or by any WHATWG label.
+pub fn from_label(label: &str) -> Option<&'static Encoding> {
+    match label.trim().parse::<u32>() {
+        Ok(874) => Some(encoding_rs::WINDOWS_874),
+        Ok(932) => Some(encoding_rs::SHIFT_JIS),
+        Ok(936) => Some(encoding_rs::GBK),
+        Ok(949) => Some(encoding_rs::EUC_KR),
+        Ok(950) => Some(encoding_rs::BIG5),
+        Ok(1250) => Some(encoding_rs::WINDOWS_1250),
+        Ok(1251) => Some(encoding_rs::WINDOWS_1251),
+        Ok(1252) => Some(encoding_rs::WINDOWS_1252),
+        Ok(1253) => Some(encoding_rs::WINDOWS_1253),
+        Ok(1254) => Some(encoding_rs::WINDOWS_1254),
+        Ok(1255) => Some(encoding_rs::WINDOWS_1255),
+        Ok(1256) => Some(encoding_rs::WINDOWS_1256),
+        Ok(1257) => Some(encoding_rs::WINDOWS_1257),
+        Ok(1258) => Some(encoding_rs::WINDOWS_1258),
+        Ok(65001) => Some(encoding_rs::UTF_8),
+        Ok(_) => None,
+        Err(_) => Encoding::for_label(label.trim().as_bytes()),
+    }
+}
+
+/// Guesses which code page a GameMaker 8.0 game was made with, since the file doesn't say.
+/// Fonts made for a specific charset settle it; otherwise it's guessed from the game's strings.
+pub fn detect(assets: &GameAssets) -> &'static Encoding {
+    detect_from_charsets(assets.fonts.iter().flatten().map(|font| font.charset))
+        .unwrap_or_else(|| detect_from_text(&strings(assets)))
+}
+
+/// The encoding most of the fonts' charsets call for, if any of them are specific about it.
+fn detect_from_charsets(charsets: impl IntoIterator<Item = u32>) -> Option<&'static Encoding> {
+    let mut votes: HashMap<&'static str, (&'static Encoding, usize)> = HashMap::new();
+    for charset in charsets {
+        // ANSI_CHARSET is what fonts get by default, so it doesn't say much
+        if let Some(encoding) = charset_encoding(charset).filter(|_| charset != 0) {
+            votes.entry(encoding.name()).or_insert((encoding, 0)).1 += 1;
+        }
+    }
+    votes.into_values().max_by_key(|(e, count)| (*count, e.name())).map(|(encoding, _)| encoding)
+}
+
+fn strings(assets: &GameAssets) -> Vec<&[u8]> {
+    let mut strings: Vec<&PascalString> = Vec::new();
+    strings.extend(assets.scripts.iter().flatten().map(|s| &s.source));
+    for room in assets.rooms.iter().flatten() {
+        strings.push(&room.caption);
+        strings.push(&room.creation_code);
+        strings.extend(room.instances.iter().map(|i| &i.creation_code));
+    }
+    let actions = assets
+        .objects
+        .iter()
+        .flatten()
+        .flat_map(|o| o.events.iter().flatten().flat_map(|(_, actions)| actions))
+        .chain(assets.timelines.iter().flatten().flat_map(|t| t.moments.iter().flat_map(|(_, actions)| actions)));
+    for action in actions {
+        strings.extend(action.param_strings.iter());
+    }
+    strings.into_iter().map(|s| s.0.as_ref()).filter(|s| !s.is_ascii()).collect()
+}
+
+/// The share of non-ASCII characters that fall in the given ranges when the strings are decoded,
+/// and whether any of them fell in the `required` ranges. Bytes which don't decode count against it.
+fn score(
+    strings: &[&[u8]],
+    encoding: &'static Encoding,
+    ranges: &[(char, char)],
+    required: &[(char, char)],
+) -> (f64, bool) {
+    let in_ranges = |c: char, ranges: &[(char, char)]| ranges.iter().any(|&(lo, hi)| (lo..=hi).contains(&c));
+    let (mut matching, mut total, mut found) = (0usize, 0usize, false);
+    for string in strings {
+        let (decoded, _) = encoding.decode_without_bom_handling(string);
+        for c in decoded.chars().filter(|c| !c.is_ascii()) {
+            total += 1;
+            if in_ranges(c, ranges) {
+                matching += 1;
+            }
+            found |= in_ranges(c, required);
+        }
+    }
+    (if total == 0 { 0.0 } else { matching as f64 / total as f64 }, found)
+}
+
+const KANA: &[(char, char)] = &[('\u{3040}', '\u{30FF}')];
+const CJK: &[(char, char)] = &[('\u{3000}', '\u{303F}'), ('\u{4E00}', '\u{9FFF}'), ('\u{FF01}', '\u{FF5E}')];
+const JAPANESE: &[(char, char)] =
+    &[('\u{3000}', '\u{30FF}'), ('\u{4E00}', '\u{9FFF}'), ('\u{FF01}', '\u{FF5E}'), ('\u{25A0}', '\u{25FF}')];
+const HANGUL: &[(char, char)] = &[('\u{AC00}', '\u{D7A3}'), ('\u{3131}', '\u{318E}'), ('\u{3000}', '\u{303F}')];
+
+fn detect_from_text(strings: &[&[u8]]) -> &'static Encoding {
+    if strings.is_empty() {
+        return encoding_rs::WINDOWS_1252
+    }
+
+    // In windows-1251 almost every high byte in Russian text is a letter (0xC0 to 0xFF, plus Ё and ё), and
+    // they come in whole words, where Western text has the odd accented letter among Latin ones.
+    // Double-byte encodings spread their bytes across 0xA1 to 0xFE, so they don't pass the first test.
+    let (mut high, mut cyrillic, mut in_words) = (0usize, 0usize, 0usize);
+    for string in strings {
+        for (i, &b) in string.iter().enumerate().filter(|(_, &b)| b >= 0x80) {
+            high += 1;
+            if b >= 0xC0 || b == 0xA8 || b == 0xB8 {
+                cyrillic += 1;
+            }
+            let neighbours = [i.checked_sub(1).map(|j| string[j]), string.get(i + 1).copied()];
+            if neighbours.iter().flatten().any(|&n| n >= 0x80) {
+                in_words += 1;
+            }
+        }
+    }
+    if cyrillic * 10 >= high * 9 && in_words * 2 > high {
+        return encoding_rs::WINDOWS_1251
+    }
+
+    // Single-byte Western text often decodes without error as a double-byte encoding, but not into sensible
+    // characters. Halfwidth katakana are left out of the Japanese ranges for that reason.
+    const THRESHOLD: f64 = 0.9;
+    let (share, has_kana) = score(strings, encoding_rs::SHIFT_JIS, JAPANESE, KANA);
+    if share >= THRESHOLD && has_kana {
+        return encoding_rs::SHIFT_JIS
+    }
+    if score(strings, encoding_rs::EUC_KR, HANGUL, &[]).0 >= THRESHOLD {
+        return encoding_rs::EUC_KR
+    }
+    if score(strings, encoding_rs::GBK, CJK, &[]).0 >= THRESHOLD {
+        return encoding_rs::GBK
+    }
+    encoding_rs::WINDOWS_1252
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(encoding: &'static Encoding, text: &str) -> Vec<u8> {
+        let (bytes, _, unmappable) = encoding.encode(text);
+        assert!(!unmappable);
+        bytes.into_owned()
+    }
+
+    fn detect_strings(encoding: &'static Encoding, texts: &[&str]) -> &'static Encoding {
+        let strings = texts.iter().map(|text| encode(encoding, text)).collect::<Vec<_>>();
+        detect_from_text(&strings.iter().map(Vec::as_slice).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(from_label("1251"), Some(encoding_rs::WINDOWS_1251));
+        assert_eq!(from_label(" 932\n"), Some(encoding_rs::SHIFT_JIS));
+        assert_eq!(from_label("1252"), Some(encoding_rs::WINDOWS_1252));
+        assert_eq!(from_label("Shift_JIS"), Some(encoding_rs::SHIFT_JIS));
+        assert_eq!(from_label("cp1251"), Some(encoding_rs::WINDOWS_1251));
+        assert_eq!(from_label("latin1"), Some(encoding_rs::WINDOWS_1252));
+        assert_eq!(from_label("437"), None);
+        assert_eq!(from_label("klingon"), None);
+    }
+
+    #[test]
+    fn charsets() {
+        assert_eq!(detect_from_charsets([0, 0xCC, 0]), Some(encoding_rs::WINDOWS_1251));
+        assert_eq!(detect_from_charsets([0x80, 0xCC, 0x80]), Some(encoding_rs::SHIFT_JIS));
+        // default and unknown charsets don't vote, so the text gets the say
+        assert_eq!(detect_from_charsets([0, 0, 1, 2]), None);
+        assert_eq!(detect_from_charsets([]), None);
+    }
+
+    #[test]
+    fn cyrillic() {
+        let texts = ["Привет, мир!", "show_message(\"Игра окончена\");", "Ёлка"];
+        assert_eq!(detect_strings(encoding_rs::WINDOWS_1251, &texts), encoding_rs::WINDOWS_1251);
+    }
+
+    #[test]
+    fn shift_jis() {
+        let texts = ["こんにちは世界", "draw_text(0, 0, \"ゲームオーバー\");", "スコア：１００"];
+        assert_eq!(detect_strings(encoding_rs::SHIFT_JIS, &texts), encoding_rs::SHIFT_JIS);
+    }
+
+    #[test]
+    fn western() {
+        let texts = ["Café", "show_message(\"Überraschung! Ça marche.\");", "© 2008", "naïve résumé"];
+        assert_eq!(detect_strings(encoding_rs::WINDOWS_1252, &texts), encoding_rs::WINDOWS_1252);
+        assert_eq!(detect_from_text(&[]), encoding_rs::WINDOWS_1252);
+    }
+}
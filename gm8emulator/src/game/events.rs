@@ -2,7 +2,7 @@ use crate::{
     asset::trigger::TriggerTime,
     game::{Game, GetAsset},
     gml,
-    input::MouseButton,
+    input::{self, MouseButton},
     instance::Instance,
     types::ID,
 };
@@ -417,6 +417,30 @@ impl Game {
             self.run_object_event(gml::ev::MOUSE, 61, None)?;
         }
 
+        self.run_joystick_events()
+    }
+
+    /// Runs joystick direction and button events, which are mouse sub-events in GM8.
+    /// Like keyboard events, they run every frame for as long as the direction or button is held.
+    pub fn run_joystick_events(&mut self) -> gml::Result<()> {
+        // Sub-event offsets for joystick 1 and 2: left, right, up, down are +0 to +3, buttons 1-8 are +5 to +12
+        for (id, base) in [(0, 16), (1, 31)] {
+            let (direction, buttons) = match self.input.joystick(id) {
+                Some(joystick) => (joystick.direction() - input::Button::Keypad1 as u8, joystick.buttons()),
+                None => continue,
+            };
+            let (column, row) = (direction % 3, direction / 3);
+            for (sub, held) in [column == 0, column == 2, row == 2, row == 0].iter().enumerate() {
+                if *held {
+                    self.run_object_event(gml::ev::MOUSE, base + sub as u32, None)?;
+                }
+            }
+            for button in 0..8 {
+                if buttons & (1 << button) != 0 {
+                    self.run_object_event(gml::ev::MOUSE, base + 5 + button, None)?;
+                }
+            }
+        }
         Ok(())
     }
 
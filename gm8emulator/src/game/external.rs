@@ -1,5 +1,7 @@
 pub mod dll;
 mod dummy;
+pub mod emulated;
+pub mod pe;
 pub mod win32;
 mod wow64;
 
@@ -7,14 +9,16 @@ use crate::{gml, gml::Function, types::ID};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-#[cfg(not(all(target_os = "windows", target_arch = "x86")))]
+#[cfg(not(all(any(target_os = "windows", target_os = "linux"), target_arch = "x86")))]
 use dummy as native;
+#[cfg(all(target_os = "linux", target_arch = "x86"))]
+use pe as native;
 #[cfg(all(target_os = "windows", target_arch = "x86"))]
 use win32 as native;
 
-#[cfg(all(target_os = "windows", target_arch = "x86"))]
+#[cfg(all(any(target_os = "windows", target_os = "linux"), target_arch = "x86"))]
 use dummy as ipc;
-#[cfg(not(all(target_os = "windows", target_arch = "x86")))]
+#[cfg(not(all(any(target_os = "windows", target_os = "linux"), target_arch = "x86")))]
 use wow64 as ipc;
 
 pub use native::NativeExternal;
@@ -22,6 +26,8 @@ pub use native::NativeExternal;
 pub enum Call {
     Dummy(gml::Value),
     Emulated(Function),
+    // a function from an emulated DLL that hasn't been emulated, which is an error to call
+    Unemulated,
     Native(NativeExternal),
     Ipc(ipc::IpcExternal),
 }
@@ -34,6 +40,7 @@ pub struct External {
 pub struct ExternalManager {
     externals: Vec<Option<External>>,
     dummy_audio: bool,
+    pub emulated: emulated::EmulatedState,
 
     native_manager: native::NativeManager,
     ipc_manager: ipc::IpcManager,
@@ -49,16 +56,24 @@ impl ExternalManager {
         Self {
             externals: Vec::new(),
             dummy_audio,
+            emulated: Default::default(),
             native_manager: native::NativeManager::new(),
             ipc_manager: ipc::IpcManager::new(),
         }
     }
 
     fn make_call(&mut self, signature: &dll::ExternalSignature) -> Result<Call, String> {
+        // Emulated DLLs come first so they act the same whether or not anything is being dummied
+        match emulated::lookup(&signature.dll, &signature.symbol) {
+            Some(Some(function)) => return Ok(Call::Emulated(function)),
+            // Games tend to define everything a DLL has up front, so only fail if they actually call it
+            Some(None) => return Ok(Call::Unemulated),
+            None => (),
+        }
         if let Some(dummy) = self.should_dummy(&signature) {
             return Ok(Call::Dummy(dummy))
         }
-        if cfg!(all(target_os = "windows", target_arch = "x86")) {
+        if cfg!(all(any(target_os = "windows", target_os = "linux"), target_arch = "x86")) {
             Ok(Call::Native(self.native_manager.define(&signature)?))
         } else {
             Ok(Call::Ipc(self.ipc_manager.define(&signature)?))
@@ -129,19 +144,7 @@ impl ExternalManager {
 
         let mut dummy = None;
         if self.dummy_audio {
-            if dll.eq_ignore_ascii_case("gmfmodsimple.dll") {
-                if sym == "FMODSoundAdd" {
-                    dummy = Some(gml::Value::Real(1.into()));
-                } else {
-                    dummy = Some(gml::Value::Real(0.into()));
-                }
-            } else if dll.eq_ignore_ascii_case("ssound.dll") || dll.eq_ignore_ascii_case("supersound.dll") {
-                if sym == "SS_Init" {
-                    dummy = Some(gml::Value::Str("Yes".into()));
-                } else {
-                    dummy = Some(gml::Value::Real(0.into()));
-                }
-            } else if dll.eq_ignore_ascii_case("sgaudio.dll") || dll.eq_ignore_ascii_case("sxms-3.dll") {
+            if dll.eq_ignore_ascii_case("sgaudio.dll") || dll.eq_ignore_ascii_case("sxms-3.dll") {
                 dummy = Some(gml::Value::Real(0.into()));
             } else if dll.eq_ignore_ascii_case("caster.dll") {
                 if sym == "caster_error_message" || sym == "caster_version" {
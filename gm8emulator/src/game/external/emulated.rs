@@ -0,0 +1,91 @@
+mod dll39;
+mod fmod;
+mod ini;
+mod strings;
+mod supersound;
+
+use crate::{
+    game::{audio::AudioManager, Game, PlayType},
+    gml::{datetime, Function, Value},
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+// Rust versions of extension DLLs that lots of games ship with, so those games work on any platform.
+// Each DLL is a module with a `lookup` function mapping its exported symbols to emulated functions.
+
+#[derive(Default)]
+pub struct EmulatedState {
+    pub fmod: fmod::State,
+    pub supersound: supersound::State,
+    pub dll39: dll39::State,
+}
+
+/// The part of every emulated DLL's state that goes in savestates.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedState {
+    fmod: fmod::SavedState,
+    supersound: supersound::SavedState,
+    dll39: dll39::SavedState,
+}
+
+impl EmulatedState {
+    pub fn save(&self, now: u128) -> SavedState {
+        SavedState { fmod: self.fmod.save(now), supersound: self.supersound.save(now), dll39: self.dll39.save() }
+    }
+
+    /// Restores the state from a savestate, picking up any sounds that were playing where they left off.
+    pub fn load(&mut self, audio: &mut AudioManager, saved: SavedState, now: u128) {
+        self.fmod.load(audio, saved.fmod, now);
+        self.supersound.load(audio, saved.supersound, now);
+        self.dll39.load(saved.dll39);
+    }
+}
+
+/// Finds the emulated version of a DLL function.
+/// Returns None if the DLL isn't one we emulate, and Some(None) if it is but that function isn't emulated.
+/// Only a few functions of system DLLs are emulated, so for those anything else is None and goes to the real DLL.
+pub fn lookup(dll: &str, symbol: &str) -> Option<Option<Function>> {
+    let dll = Path::new(dll).file_name().and_then(|oss| oss.to_str()).unwrap_or(dll).to_ascii_lowercase();
+    match dll.as_str() {
+        "gmfmodsimple.dll" => Some(fmod::lookup(symbol)),
+        "ssound.dll" | "supersound.dll" => Some(supersound::lookup(symbol)),
+        "39dll.dll" => Some(dll39::lookup(symbol)),
+        "kernel32.dll" | "kernel32" => ini::lookup(symbol).or_else(|| strings::lookup(symbol)).map(Some),
+        "user32.dll" | "user32" => strings::lookup(symbol).map(Some),
+        _ => None,
+    }
+}
+
+/// Whether the game may use the network. Network traffic isn't recorded, so it's only allowed in normal play.
+fn online(game: &Game) -> bool {
+    game.play_type == PlayType::Normal
+}
+
+/// Gets an argument as a real. DLLs which take strings for everything get those parsed as numbers.
+fn real(args: &[Value], index: usize) -> f64 {
+    match args.get(index) {
+        Some(Value::Real(x)) => (*x).into(),
+        Some(Value::Str(s)) => String::from_utf8_lossy(s.as_ref()).trim().parse().unwrap_or(0.0),
+        None => 0.0,
+    }
+}
+
+fn int(args: &[Value], index: usize) -> i32 {
+    real(args, index).round() as i32
+}
+
+fn bytes(args: &[Value], index: usize) -> &[u8] {
+    match args.get(index) {
+        Some(Value::Str(s)) => s.as_ref(),
+        _ => &[],
+    }
+}
+
+fn string(args: &[Value], index: usize) -> String {
+    String::from_utf8_lossy(bytes(args, index)).into_owned()
+}
+
+fn now(game: &Game) -> u128 {
+    game.spoofed_time_nanos.unwrap_or_else(datetime::now_as_nanos)
+}
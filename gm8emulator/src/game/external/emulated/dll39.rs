@@ -0,0 +1,728 @@
+use super::{bytes, int, online, real, string};
+use crate::{
+    game::Game,
+    gml::{self, network, Function, Value},
+    handleman::{HandleList, HandleManager},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Read, Seek, SeekFrom, Write},
+    net::{self, Ipv4Addr, Shutdown, TcpListener, TcpStream, ToSocketAddrs, UdpSocket},
+    time::Duration,
+};
+
+// 39dll, the networking DLL nearly every online GM8 game uses.
+// Sockets and files are numbered from 1 as 0 means failure, but buffer 0 is the default buffer and always exists.
+// Network traffic can't go in replays or savestates, so while recording or replaying no sockets can be opened.
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct State {
+    buffers: HandleList<Buffer>,
+    sockets: HandleList<Socket>,
+    files: HandleList<fs::File>,
+    last_in: Option<net::SocketAddr>,
+}
+
+/// The part of the state that goes in savestates. Like the game's own files, open files aren't included.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedState {
+    buffers: HandleList<Buffer>,
+    last_in: Option<net::SocketAddr>,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+struct Buffer {
+    data: Vec<u8>,
+    read_pos: usize,
+}
+
+struct Socket {
+    kind: SocketKind,
+    format: Format,
+    blocking: bool,
+    incoming: Vec<u8>,
+    closed: bool,
+    last_error: i32,
+}
+
+enum SocketKind {
+    Tcp(TcpStream),
+    Listener(TcpListener),
+    Udp(UdpSocket),
+}
+
+/// How messages are split up in a TCP stream.
+enum Format {
+    // each message has its length before it as a u16
+    Binary,
+    // messages end with a separator string
+    Text(Vec<u8>),
+    // no splitting, receive whatever's arrived
+    Raw,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        let mut buffers = HandleList::new();
+        buffers.put(Buffer::default());
+        Self { buffers, sockets: HandleList::new(), files: HandleList::new(), last_in: None }
+    }
+}
+
+impl State {
+    pub fn save(&self) -> SavedState {
+        SavedState { buffers: self.buffers.clone(), last_in: self.last_in }
+    }
+
+    pub fn load(&mut self, saved: SavedState) {
+        self.buffers = saved.buffers;
+        self.last_in = saved.last_in;
+    }
+}
+
+impl Buffer {
+    fn read(&mut self, len: usize) -> &[u8] {
+        let start = self.read_pos.min(self.data.len());
+        let end = (start + len).min(self.data.len());
+        self.read_pos = end;
+        &self.data[start..end]
+    }
+
+    fn read_array<const N: usize>(&mut self) -> [u8; N] {
+        let mut array = [0; N];
+        let data = self.read(N);
+        array[..data.len()].copy_from_slice(data);
+        array
+    }
+}
+
+impl Socket {
+    fn new(kind: SocketKind, blocking: bool) -> Self {
+        let socket =
+            Self { kind, format: Format::Binary, blocking, incoming: Vec::new(), closed: false, last_error: 0 };
+        socket.set_blocking(blocking);
+        socket
+    }
+
+    fn set_blocking(&self, blocking: bool) {
+        let _ = match &self.kind {
+            SocketKind::Tcp(stream) => stream.set_nonblocking(!blocking),
+            SocketKind::Listener(listener) => listener.set_nonblocking(!blocking),
+            SocketKind::Udp(socket) => socket.set_nonblocking(!blocking),
+        };
+    }
+
+    /// Reads whatever's arrived on a TCP socket. If `wait` is set, blocks until at least something comes in.
+    fn fill(&mut self, wait: bool) {
+        let stream = match &mut self.kind {
+            SocketKind::Tcp(stream) => stream,
+            _ => return,
+        };
+        let mut chunk = [0u8; 4096];
+        if wait {
+            let _ = stream.set_nonblocking(false);
+        }
+        loop {
+            match stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.closed = true;
+                    break
+                },
+                Ok(n) => {
+                    self.incoming.extend_from_slice(&chunk[..n]);
+                    if wait {
+                        break
+                    }
+                    let _ = stream.set_nonblocking(true);
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.last_error = e.raw_os_error().unwrap_or(-1);
+                    self.closed = true;
+                    break
+                },
+            }
+        }
+        let _ = stream.set_nonblocking(!self.blocking);
+    }
+
+    /// Finds the length of the next complete message in `incoming`, and how many bytes to drop around it.
+    /// Returns (header length, message length, trailer length).
+    fn next_message(&self, len: usize) -> Option<(usize, usize, usize)> {
+        if len > 0 {
+            return if self.incoming.len() >= len { Some((0, len, 0)) } else { None }
+        }
+        match &self.format {
+            Format::Binary => {
+                let size = u16::from_le_bytes([*self.incoming.first()?, *self.incoming.get(1)?]) as usize;
+                if self.incoming.len() >= 2 + size { Some((2, size, 0)) } else { None }
+            },
+            Format::Text(sep) if !sep.is_empty() => {
+                let pos = self.incoming.windows(sep.len()).position(|w| w == sep.as_slice())?;
+                Some((0, pos, sep.len()))
+            },
+            _ => {
+                if self.incoming.is_empty() { None } else { Some((0, self.incoming.len(), 0)) }
+            },
+        }
+    }
+}
+
+pub fn lookup(symbol: &str) -> Option<Function> {
+    Some(match symbol {
+        "dllInit" | "sockstart" | "sockexit" => Function::Pure(|_| Ok(gml::TRUE.into())),
+        "tcpconnect" => Function::Engine(tcpconnect),
+        "tcplisten" => Function::Engine(tcplisten),
+        "tcpaccept" => Function::Engine(tcpaccept),
+        "tcpip" => Function::Constant(tcpip),
+        "tcpconnected" => Function::Engine(tcpconnected),
+        "udpconnect" => Function::Engine(udpconnect),
+        "sendmessage" => Function::Engine(sendmessage),
+        "receivemessage" => Function::Engine(|game, args| receivemessage(game, args, false)),
+        "peekmessage" => Function::Engine(|game, args| receivemessage(game, args, true)),
+        "setformat" => Function::Engine(setformat),
+        "setsync" => Function::Engine(setsync),
+        "setnagle" => Function::Engine(setnagle),
+        "closesock" | "closesocket" => Function::Engine(closesocket),
+        "socklasterror" => Function::Constant(socklasterror),
+        "getsocketid" => Function::Constant(|_, args| Ok(int(args, 0).into())),
+        "myhost" => Function::Constant(myhost),
+        "hostip" => Function::Constant(hostip),
+        "compareip" => Function::Pure(compareip),
+        "lastinIP" => Function::Constant(last_in_ip),
+        "lastinPort" => Function::Constant(last_in_port),
+        "netconnected" => Function::Constant(|game, _| Ok((online(game) && network::get_local_ip().is_ok()).into())),
+        "iptouint" => Function::Pure(iptouint),
+        "uinttoip" => Function::Pure(uinttoip),
+        "getmacaddress" => Function::Pure(|_| Ok("00-00-00-00-00-00".into())),
+
+        "createbuffer" => Function::Engine(createbuffer),
+        "freebuffer" => Function::Engine(freebuffer),
+        "bufferexists" => Function::Constant(bufferexists),
+        "clearbuffer" => Function::Engine(clearbuffer),
+        "buffsize" => Function::Engine(|game, args| with_buffer(game, args, 0, |b| b.data.len() as f64)),
+        "bytesleft" => Function::Engine(bytesleft),
+        "getpos" => Function::Engine(getpos),
+        "setpos" => Function::Engine(setpos),
+        "copybuffer" => Function::Engine(copybuffer),
+        "copybuffer2" => Function::Engine(copybuffer2),
+        "writebyte" => Function::Engine(|game, args| write(game, args, |x| [x as i64 as u8])),
+        "writeshort" => Function::Engine(|game, args| write(game, args, |x| (x as i64 as i16).to_le_bytes())),
+        "writeushort" => Function::Engine(|game, args| write(game, args, |x| (x as i64 as u16).to_le_bytes())),
+        "writeint" => Function::Engine(|game, args| write(game, args, |x| (x as i64 as i32).to_le_bytes())),
+        "writeuint" => Function::Engine(|game, args| write(game, args, |x| (x as i64 as u32).to_le_bytes())),
+        "writefloat" => Function::Engine(|game, args| write(game, args, |x| (x as f32).to_le_bytes())),
+        "writedouble" => Function::Engine(|game, args| write(game, args, f64::to_le_bytes)),
+        "writechars" => Function::Engine(|game, args| write_bytes(game, args, false)),
+        "writestring" => Function::Engine(|game, args| write_bytes(game, args, true)),
+        "readbyte" => Function::Engine(|game, args| read(game, args, |b| f64::from(b.read_array::<1>()[0]))),
+        "readshort" => Function::Engine(|game, args| read(game, args, |b| i16::from_le_bytes(b.read_array()).into())),
+        "readushort" => Function::Engine(|game, args| read(game, args, |b| u16::from_le_bytes(b.read_array()).into())),
+        "readint" => Function::Engine(|game, args| read(game, args, |b| i32::from_le_bytes(b.read_array()).into())),
+        "readuint" => Function::Engine(|game, args| read(game, args, |b| u32::from_le_bytes(b.read_array()).into())),
+        "readfloat" => Function::Engine(|game, args| read(game, args, |b| f32::from_le_bytes(b.read_array()).into())),
+        "readdouble" => Function::Engine(|game, args| read(game, args, |b| f64::from_le_bytes(b.read_array()))),
+        "readchars" => Function::Engine(readchars),
+        "readstring" => Function::Engine(readstring),
+        "adler32" => Function::Engine(|game, args| with_buffer(game, args, 0, |b| f64::from(adler32(&b.data)))),
+        "md5buffer" => Function::Engine(|game, args| with_buffer(game, args, 0, |b| hex::encode(md5(&b.data)))),
+        "md5string" => Function::Pure(|args| Ok(hex::encode(md5(bytes(args, 0))).into())),
+        "bufferencrypt" => Function::Engine(bufferencrypt),
+
+        "fileopen" => Function::Engine(fileopen),
+        "fileclose" => Function::Engine(fileclose),
+        "filewrite" => Function::Engine(filewrite),
+        "fileread" => Function::Engine(fileread),
+        "filepos" => Function::Engine(|game, args| with_file(game, args, |f| f.stream_position().map(|p| p as f64))),
+        "filesetpos" => Function::Engine(filesetpos),
+        "filesize" => Function::Engine(|game, args| with_file(game, args, |f| f.metadata().map(|m| m.len() as f64))),
+        _ => return None,
+    })
+}
+
+fn add_socket(game: &mut Game, socket: Socket) -> Value {
+    (game.externals.emulated.dll39.sockets.put(socket) + 1).into()
+}
+
+fn socket<'a>(game: &'a mut Game, args: &[Value], index: usize) -> Option<&'a mut Socket> {
+    game.externals.emulated.dll39.sockets.get_mut(int(args, index) - 1)
+}
+
+fn tcpconnect(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let (host, port, mode) = (string(args, 0), int(args, 1) as u16, int(args, 2));
+    if !online(game) {
+        return Ok(0.into())
+    }
+    let stream = (host.as_str(), port)
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addrs| addrs.find_map(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).ok()));
+    match stream {
+        Some(stream) => Ok(add_socket(game, Socket::new(SocketKind::Tcp(stream), mode == 0))),
+        None => Ok(0.into()),
+    }
+}
+
+fn tcplisten(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let (port, mode) = (int(args, 0) as u16, int(args, 2));
+    if !online(game) {
+        return Ok(0.into())
+    }
+    match TcpListener::bind((Ipv4Addr::UNSPECIFIED, port)) {
+        Ok(listener) => Ok(add_socket(game, Socket::new(SocketKind::Listener(listener), mode == 0))),
+        Err(_) => Ok(0.into()),
+    }
+}
+
+fn tcpaccept(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let mode = int(args, 1);
+    let stream = match socket(game, args, 0).map(|s| &s.kind) {
+        Some(SocketKind::Listener(listener)) => listener.accept().ok().map(|(stream, _)| stream),
+        _ => None,
+    };
+    match stream {
+        Some(stream) => Ok(add_socket(game, Socket::new(SocketKind::Tcp(stream), mode == 0))),
+        None => Ok(0.into()),
+    }
+}
+
+fn tcpip(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    match game.externals.emulated.dll39.sockets.get(int(args, 0) - 1).map(|s| &s.kind) {
+        Some(SocketKind::Tcp(stream)) => Ok(stream.peer_addr().map_or(String::new(), |a| a.ip().to_string()).into()),
+        _ => Ok("".into()),
+    }
+}
+
+fn tcpconnected(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    match socket(game, args, 0) {
+        Some(socket @ Socket { kind: SocketKind::Tcp(_), .. }) => {
+            socket.fill(false);
+            Ok((!socket.closed).into())
+        },
+        _ => Ok(gml::FALSE.into()),
+    }
+}
+
+fn udpconnect(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let (port, mode) = (int(args, 0) as u16, int(args, 1));
+    if !online(game) {
+        return Ok(0.into())
+    }
+    match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, port)) {
+        Ok(socket) => Ok(add_socket(game, Socket::new(SocketKind::Udp(socket), mode == 0))),
+        Err(_) => Ok(0.into()),
+    }
+}
+
+fn sendmessage(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let (host, port, buffer) = (string(args, 1), int(args, 2) as u16, int(args, 3));
+    let state = &mut game.externals.emulated.dll39;
+    let data = match state.buffers.get(buffer) {
+        Some(buffer) => buffer.data.clone(),
+        None => return Ok((-1).into()),
+    };
+    let socket = match state.sockets.get_mut(int(args, 0) - 1) {
+        Some(socket) => socket,
+        None => return Ok((-1).into()),
+    };
+    let result = match &mut socket.kind {
+        SocketKind::Tcp(stream) => {
+            let mut message = Vec::with_capacity(data.len() + 2);
+            match &socket.format {
+                Format::Binary => {
+                    message.extend_from_slice(&(data.len() as u16).to_le_bytes());
+                    message.extend_from_slice(&data);
+                },
+                Format::Text(sep) => {
+                    message.extend_from_slice(&data);
+                    message.extend_from_slice(sep);
+                },
+                Format::Raw => message.extend_from_slice(&data),
+            }
+            // write_all doesn't cope with non-blocking sockets, so always send in blocking mode
+            let _ = stream.set_nonblocking(false);
+            let result = stream.write_all(&message).map(|_| message.len());
+            let _ = stream.set_nonblocking(!socket.blocking);
+            result
+        },
+        SocketKind::Udp(udp) => udp.send_to(&data, (host.as_str(), port)),
+        SocketKind::Listener(_) => return Ok((-1).into()),
+    };
+    match result {
+        Ok(sent) => Ok(sent.into()),
+        Err(e) => {
+            socket.last_error = e.raw_os_error().unwrap_or(-1);
+            Ok((-1).into())
+        },
+    }
+}
+
+/// Moves the next message into a buffer. Returns its size, 0 if the connection closed or -1 if nothing's there yet.
+fn receivemessage(game: &mut Game, args: &[Value], peek: bool) -> gml::Result<Value> {
+    let (len, buffer) = (real(args, 1).max(0.0) as usize, int(args, 2));
+    let state = &mut game.externals.emulated.dll39;
+    let socket = match state.sockets.get_mut(int(args, 0) - 1) {
+        Some(socket) => socket,
+        None => return Ok((-1).into()),
+    };
+    let message = match &socket.kind {
+        SocketKind::Tcp(_) => {
+            socket.fill(false);
+            while socket.blocking && !socket.closed && socket.next_message(len).is_none() {
+                socket.fill(true);
+            }
+            match socket.next_message(len) {
+                Some((header, size, trailer)) => {
+                    let message = socket.incoming[header..header + size].to_vec();
+                    if !peek {
+                        socket.incoming.drain(..header + size + trailer);
+                    }
+                    message
+                },
+                None if socket.closed => return Ok(0.into()),
+                None => return Ok((-1).into()),
+            }
+        },
+        SocketKind::Udp(udp) => {
+            let mut datagram = vec![0; 65536];
+            let result = if peek { udp.peek_from(&mut datagram) } else { udp.recv_from(&mut datagram) };
+            match result {
+                Ok((size, from)) => {
+                    state.last_in = Some(from);
+                    datagram.truncate(size);
+                    datagram
+                },
+                Err(_) => return Ok((-1).into()),
+            }
+        },
+        SocketKind::Listener(_) => return Ok((-1).into()),
+    };
+    match state.buffers.get_mut(buffer) {
+        Some(buffer) => {
+            let size = message.len();
+            *buffer = Buffer { data: message, read_pos: 0 };
+            Ok(size.into())
+        },
+        None => Ok((-1).into()),
+    }
+}
+
+fn setformat(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let (mode, separator) = (int(args, 1), bytes(args, 2).to_vec());
+    if let Some(socket) = socket(game, args, 0) {
+        socket.format = match mode {
+            0 => Format::Binary,
+            1 => Format::Text(separator),
+            _ => Format::Raw,
+        };
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn setsync(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let blocking = int(args, 1) == 0;
+    if let Some(socket) = socket(game, args, 0) {
+        socket.blocking = blocking;
+        socket.set_blocking(blocking);
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn setnagle(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let nagle = real(args, 1) >= 0.5;
+    if let Some(Socket { kind: SocketKind::Tcp(stream), .. }) = socket(game, args, 0) {
+        let _ = stream.set_nodelay(!nagle);
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn closesocket(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let id = int(args, 0) - 1;
+    if let Some(Socket { kind: SocketKind::Tcp(stream), .. }) = game.externals.emulated.dll39.sockets.get(id) {
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+    Ok(game.externals.emulated.dll39.sockets.delete(id).into())
+}
+
+fn socklasterror(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.dll39.sockets.get(int(args, 0) - 1).map_or(0, |s| s.last_error).into())
+}
+
+fn myhost(game: &Game, _args: &[Value]) -> gml::Result<Value> {
+    let ip = if online(game) { network::get_local_ip().ok() } else { None };
+    Ok(ip.unwrap_or(Ipv4Addr::LOCALHOST.into()).to_string().into())
+}
+
+fn hostip(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    if !online(game) {
+        return Ok("".into())
+    }
+    let ip = (string(args, 0).as_str(), 0).to_socket_addrs().ok().and_then(|mut addrs| addrs.next());
+    Ok(ip.map_or(String::new(), |addr| addr.ip().to_string()).into())
+}
+
+/// Compares an IP to a mask, where a * in the mask matches any part.
+fn compareip(args: &[Value]) -> gml::Result<Value> {
+    let (ip, mask) = (string(args, 0), string(args, 1));
+    let (ip, mask) = (ip.split('.').collect::<Vec<_>>(), mask.split('.').collect::<Vec<_>>());
+    let matches = ip.len() == mask.len() && ip.iter().zip(mask.iter()).all(|(a, b)| *b == "*" || a == b);
+    Ok(matches.into())
+}
+
+fn last_in_ip(game: &Game, _args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.dll39.last_in.map_or(String::new(), |addr| addr.ip().to_string()).into())
+}
+
+fn last_in_port(game: &Game, _args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.dll39.last_in.map_or(0, |addr| u32::from(addr.port())).into())
+}
+
+fn iptouint(args: &[Value]) -> gml::Result<Value> {
+    // in network byte order, same as inet_addr
+    let ip = string(args, 0).parse::<Ipv4Addr>().map_or(0, |ip| u32::from_le_bytes(ip.octets()));
+    Ok(ip.into())
+}
+
+fn uinttoip(args: &[Value]) -> gml::Result<Value> {
+    Ok(Ipv4Addr::from((real(args, 0) as i64 as u32).to_le_bytes()).to_string().into())
+}
+
+fn createbuffer(game: &mut Game, _args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.dll39.buffers.put(Buffer::default()).into())
+}
+
+fn bufferexists(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.dll39.buffers.get(int(args, 0)).is_some().into())
+}
+
+fn clearbuffer(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    with_buffer(game, args, 0, |b| {
+        *b = Buffer::default();
+        gml::TRUE
+    })
+}
+
+fn freebuffer(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    // the default buffer can't be freed
+    let id = int(args, 0);
+    Ok((id != 0 && game.externals.emulated.dll39.buffers.delete(id)).into())
+}
+
+/// Runs something on the buffer given by the argument at `index`. Returns 0 if it doesn't exist.
+fn with_buffer<T: Into<Value>>(
+    game: &mut Game,
+    args: &[Value],
+    index: usize,
+    f: impl FnOnce(&mut Buffer) -> T,
+) -> gml::Result<Value> {
+    Ok(game.externals.emulated.dll39.buffers.get_mut(int(args, index)).map_or(0.into(), |b| f(b).into()))
+}
+
+fn bytesleft(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    with_buffer(game, args, 0, |b| b.data.len().saturating_sub(b.read_pos))
+}
+
+fn getpos(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let which = int(args, 0);
+    with_buffer(game, args, 1, |b| if which == 0 { b.data.len() } else { b.read_pos })
+}
+
+fn setpos(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let pos = real(args, 0).max(0.0) as usize;
+    with_buffer(game, args, 1, |b| {
+        b.read_pos = pos.min(b.data.len());
+        gml::TRUE
+    })
+}
+
+fn copybuffer(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let buffers = &mut game.externals.emulated.dll39.buffers;
+    let data = buffers.get(int(args, 1)).map(|b| b.data.clone()).unwrap_or_default();
+    with_buffer(game, args, 0, |b| {
+        b.data.extend_from_slice(&data);
+        b.data.len()
+    })
+}
+
+fn copybuffer2(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let (start, len) = (real(args, 1).max(0.0) as usize, real(args, 2).max(0.0) as usize);
+    let buffers = &mut game.externals.emulated.dll39.buffers;
+    let data = buffers
+        .get(int(args, 3))
+        .map(|b| b.data.get(start.min(b.data.len())..(start + len).min(b.data.len())).unwrap_or(&[]).to_vec())
+        .unwrap_or_default();
+    with_buffer(game, args, 0, |b| {
+        b.data.extend_from_slice(&data);
+        b.data.len()
+    })
+}
+
+fn write<const N: usize>(game: &mut Game, args: &[Value], f: fn(f64) -> [u8; N]) -> gml::Result<Value> {
+    let bytes = f(real(args, 0));
+    with_buffer(game, args, 1, |b| {
+        b.data.extend_from_slice(&bytes);
+        b.data.len()
+    })
+}
+
+fn write_bytes(game: &mut Game, args: &[Value], terminate: bool) -> gml::Result<Value> {
+    let data = bytes(args, 0).to_vec();
+    with_buffer(game, args, 1, |b| {
+        b.data.extend_from_slice(&data);
+        if terminate {
+            b.data.push(0);
+        }
+        b.data.len()
+    })
+}
+
+fn read(game: &mut Game, args: &[Value], f: fn(&mut Buffer) -> f64) -> gml::Result<Value> {
+    with_buffer(game, args, 0, f)
+}
+
+fn readchars(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let len = real(args, 0).max(0.0) as usize;
+    with_buffer(game, args, 1, |b| b.read(len).to_vec())
+}
+
+fn readstring(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    with_buffer(game, args, 0, |b| {
+        let rest = &b.data[b.read_pos.min(b.data.len())..];
+        let len = rest.iter().position(|&c| c == 0).unwrap_or(rest.len());
+        let string = rest[..len].to_vec();
+        b.read_pos += (len + 1).min(rest.len());
+        string
+    })
+}
+
+fn bufferencrypt(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let key = bytes(args, 0).to_vec();
+    with_buffer(game, args, 1, |b| {
+        rc4(&key, &mut b.data);
+        gml::TRUE
+    })
+}
+
+fn fileopen(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let (path, mode) = (string(args, 0), int(args, 1));
+    let file = match mode {
+        0 => fs::File::open(path),
+        1 => fs::File::create(path),
+        _ => fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path),
+    };
+    match file {
+        Ok(file) => Ok((game.externals.emulated.dll39.files.put(file) + 1).into()),
+        Err(_) => Ok(0.into()),
+    }
+}
+
+fn with_file(game: &mut Game, args: &[Value], f: impl FnOnce(&mut fs::File) -> io::Result<f64>) -> gml::Result<Value> {
+    let file = game.externals.emulated.dll39.files.get_mut(int(args, 0) - 1);
+    Ok(file.map_or(-1.0, |file| f(file).unwrap_or(-1.0)).into())
+}
+
+fn fileclose(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.dll39.files.delete(int(args, 0) - 1).into())
+}
+
+fn filesetpos(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let pos = real(args, 1).max(0.0) as u64;
+    with_file(game, args, |f| f.seek(SeekFrom::Start(pos)).map(|p| p as f64))
+}
+
+fn filewrite(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let state = &mut game.externals.emulated.dll39;
+    let data = state.buffers.get(int(args, 1)).map(|b| b.data.clone()).unwrap_or_default();
+    with_file(game, args, |f| f.write_all(&data).map(|_| data.len() as f64))
+}
+
+fn fileread(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let len = real(args, 1).max(0.0) as u64;
+    let mut data = Vec::new();
+    let result = with_file(game, args, |f| f.take(len).read_to_end(&mut data).map(|n| n as f64))?;
+    with_buffer(game, args, 2, |b| {
+        *b = Buffer { data, read_pos: 0 };
+        gml::TRUE
+    })?;
+    Ok(result)
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + u32::from(byte)) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+fn rc4(key: &[u8], data: &mut [u8]) {
+    if key.is_empty() {
+        return
+    }
+    let mut s = [0u8; 256];
+    for (i, x) in s.iter_mut().enumerate() {
+        *x = i as u8;
+    }
+    let mut j = 0u8;
+    for i in 0..256 {
+        j = j.wrapping_add(s[i]).wrapping_add(key[i % key.len()]);
+        s.swap(i, j as usize);
+    }
+    let (mut i, mut j) = (0u8, 0u8);
+    for byte in data {
+        i = i.wrapping_add(1);
+        j = j.wrapping_add(s[i as usize]);
+        s.swap(i as usize, j as usize);
+        *byte ^= s[s[i as usize].wrapping_add(s[j as usize]) as usize];
+    }
+}
+
+fn md5(data: &[u8]) -> [u8; 16] {
+    const SHIFTS: [u32; 16] = [7, 12, 17, 22, 5, 9, 14, 20, 4, 11, 16, 23, 6, 10, 15, 21];
+    let mut k = [0u32; 64];
+    for (i, x) in k.iter_mut().enumerate() {
+        *x = ((i as f64 + 1.0).sin().abs() * 4294967296.0) as u32;
+    }
+    let mut state = [0x67452301u32, 0xefcdab89, 0x98badcfe, 0x10325476];
+
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    for chunk in message.chunks_exact(64) {
+        let mut m = [0u32; 16];
+        for (x, word) in m.iter_mut().zip(chunk.chunks_exact(4)) {
+            *x = u32::from_le_bytes(word.try_into().unwrap());
+        }
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in 0..64 {
+            let (f, g) = match i / 16 {
+                0 => ((b & c) | (!b & d), i),
+                1 => ((d & b) | (!d & c), (5 * i + 1) % 16),
+                2 => (b ^ c ^ d, (3 * i + 5) % 16),
+                _ => (c ^ (b | !d), (7 * i) % 16),
+            };
+            let sum = a.wrapping_add(f).wrapping_add(k[i]).wrapping_add(m[g]);
+            a = d;
+            d = c;
+            c = b;
+            b = b.wrapping_add(sum.rotate_left(SHIFTS[(i / 16) * 4 + i % 4]));
+        }
+        for (s, x) in state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(x);
+        }
+    }
+
+    let mut digest = [0; 16];
+    for (i, s) in state.iter().enumerate() {
+        digest[i * 4..i * 4 + 4].copy_from_slice(&s.to_le_bytes());
+    }
+    digest
+}
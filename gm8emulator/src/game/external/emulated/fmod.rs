@@ -0,0 +1,346 @@
+use super::{int, now, real, string};
+use crate::{
+    game::{
+        audio::{AudioManager, ExternalPlayback as Playback, ExternalSound},
+        Game,
+    },
+    gml::{self, Function, Value},
+    handleman::{HandleList, HandleManager},
+};
+use serde::{Deserialize, Serialize};
+
+// GMFMODSimple, the most common FMOD wrapper. Handles start at 1 since it uses 0 to mean failure.
+
+pub struct State {
+    sounds: HandleList<Sound>,
+    instances: HandleList<Instance>,
+    group_volumes: Vec<f64>,
+    master_volume: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Sound {
+    sound: ExternalSound,
+    max_volume: f64,
+    group: usize,
+}
+
+struct Instance {
+    sound: i32,
+    playback: Playback,
+    volume: f64,
+}
+
+/// The part of the state that goes in savestates, with each instance's playback reduced to where it was.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedState {
+    sounds: HandleList<Sound>,
+    instances: HandleList<SavedInstance>,
+    group_volumes: Vec<f64>,
+    master_volume: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SavedInstance {
+    sound: i32,
+    volume: f64,
+    pan: f64,
+    looping: bool,
+    paused: bool,
+    position: u128,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self { sounds: HandleList::new(), instances: HandleList::new(), group_volumes: vec![1.0], master_volume: 1.0 }
+    }
+}
+
+impl State {
+    fn sound(&self, args: &[Value]) -> Option<&Sound> {
+        self.sounds.get(int(args, 0) - 1)
+    }
+
+    fn instance(&self, args: &[Value]) -> Option<&Instance> {
+        self.instances.get(int(args, 0) - 1)
+    }
+
+    fn instance_mut(&mut self, args: &[Value]) -> Option<&mut Instance> {
+        self.instances.get_mut(int(args, 0) - 1)
+    }
+
+    fn group_volume(&self, group: usize) -> f64 {
+        self.group_volumes.get(group).copied().unwrap_or(1.0)
+    }
+
+    /// Sends an instance's volume to the mixer, after applying its sound's, group's and the master volume.
+    fn apply_volume(&self, instance: &Instance) {
+        let (max_volume, group) = self.sounds.get(instance.sound).map_or((1.0, 0), |s| (s.max_volume, s.group));
+        instance.playback.set_volume(instance.volume * max_volume * self.group_volume(group) * self.master_volume);
+    }
+
+    fn apply_volumes(&self) {
+        for id in self.instances.ids() {
+            self.apply_volume(self.instances.get(id).unwrap());
+        }
+    }
+
+    /// The instances of every sound matching the filter.
+    fn instances_of(&self, f: impl Fn(i32, &Sound) -> bool) -> Vec<i32> {
+        self.instances
+            .ids()
+            .filter(|&id| {
+                let sound = self.instances.get(id).unwrap().sound;
+                self.sounds.get(sound).map_or(false, |s| f(sound, s))
+            })
+            .collect()
+    }
+
+    pub fn save(&self, now: u128) -> SavedState {
+        SavedState {
+            sounds: self.sounds.clone(),
+            instances: self.instances.map(|instance| SavedInstance {
+                sound: instance.sound,
+                volume: instance.volume,
+                pan: instance.playback.pan(),
+                looping: instance.playback.looping,
+                paused: instance.playback.paused_at.is_some(),
+                position: instance.playback.position(now),
+            }),
+            group_volumes: self.group_volumes.clone(),
+            master_volume: self.master_volume,
+        }
+    }
+
+    /// Stops everything that's playing and restarts the instances from a savestate where they were.
+    pub fn load(&mut self, audio: &mut AudioManager, saved: SavedState, now: u128) {
+        self.stop(audio, self.instances.ids().collect::<Vec<_>>());
+        let SavedState { sounds, instances, group_volumes, master_volume } = saved;
+        self.sounds = sounds;
+        self.group_volumes = group_volumes;
+        self.master_volume = master_volume;
+        self.instances = instances.map(|saved| {
+            // instances are always stopped when their sound is freed
+            let sound = &self.sounds.get(saved.sound).unwrap().sound;
+            let playback = Playback::start_at(audio, sound, saved.looping, saved.paused, now, saved.position);
+            playback.set_pan(saved.pan);
+            Instance { sound: saved.sound, playback, volume: saved.volume }
+        });
+        self.apply_volumes();
+    }
+
+    fn stop(&mut self, audio: &AudioManager, ids: impl IntoIterator<Item = i32>) {
+        for id in ids {
+            if let Some(instance) = self.instances.get(id) {
+                audio.stop_external(instance.playback.mixer_id);
+            }
+            self.instances.delete(id);
+        }
+    }
+}
+
+pub fn lookup(symbol: &str) -> Option<Function> {
+    Some(match symbol {
+        "FMODinit" => Function::Engine(init),
+        "FMODfree" => Function::Engine(init),
+        "FMODUpdate" => Function::Pure(|_| Ok(gml::TRUE.into())),
+        "FMODSoundAdd" => Function::Engine(sound_add),
+        "FMODSoundFree" => Function::Engine(sound_free),
+        "FMODSoundPlay" => Function::Engine(|game, args| play(game, args, false)),
+        "FMODSoundLoop" => Function::Engine(|game, args| play(game, args, true)),
+        "FMODSoundSetGroup" => Function::Engine(sound_set_group),
+        "FMODSoundSetMaxVolume" => Function::Engine(sound_set_max_volume),
+        "FMODSoundGetMaxVolume" => Function::Constant(sound_get_max_volume),
+        "FMODSoundGetLength" => Function::Constant(sound_get_length),
+        "FMODInstanceStop" => Function::Engine(instance_stop),
+        "FMODInstanceSetPaused" => Function::Engine(instance_set_paused),
+        "FMODInstanceGetPaused" => Function::Constant(instance_get_paused),
+        "FMODInstanceIsPlaying" => Function::Volatile(instance_is_playing),
+        "FMODInstanceSetVolume" => Function::Engine(instance_set_volume),
+        "FMODInstanceGetVolume" => Function::Constant(instance_get_volume),
+        "FMODInstanceSetPan" => Function::Engine(instance_set_pan),
+        "FMODInstanceGetPan" => Function::Constant(instance_get_pan),
+        "FMODInstanceGetSound" => Function::Constant(instance_get_sound),
+        "FMODMasterSetVolume" => Function::Engine(master_set_volume),
+        "FMODMasterGetVolume" => Function::Constant(master_get_volume),
+        "FMODGroupSetVolume" => Function::Engine(group_set_volume),
+        "FMODGroupGetVolume" => Function::Constant(group_get_volume),
+        "FMODGroupStop" => Function::Engine(group_stop),
+        "FMODGroupSetPaused" => Function::Engine(group_set_paused),
+        "FMODAllStop" => Function::Engine(all_stop),
+        "FMODGetLastError" => Function::Pure(|_| Ok(0.into())),
+        "FMODErrorStr" => Function::Pure(|_| Ok("No errors.".into())),
+        _ => return None,
+    })
+}
+
+/// Used for both FMODinit and FMODfree, as either way we just throw away everything.
+fn init(game: &mut Game, _args: &[Value]) -> gml::Result<Value> {
+    all_stop(game, &[])?;
+    game.externals.emulated.fmod = State::default();
+    Ok(gml::TRUE.into())
+}
+
+fn sound_add(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    // 3D and streaming flags don't matter here
+    let sound = match std::fs::read(string(args, 0)).ok().and_then(|f| game.audio.add_external(f.into_boxed_slice())) {
+        Some(sound) => sound,
+        None => return Ok(0.into()),
+    };
+    let handle = game.externals.emulated.fmod.sounds.put(Sound { sound, max_volume: 1.0, group: 0 });
+    Ok((handle + 1).into())
+}
+
+fn sound_free(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let sound = int(args, 0) - 1;
+    let state = &mut game.externals.emulated.fmod;
+    let instances = state.instances_of(|id, _| id == sound);
+    state.stop(&game.audio, instances);
+    Ok(state.sounds.delete(sound).into())
+}
+
+fn play(game: &mut Game, args: &[Value], looping: bool) -> gml::Result<Value> {
+    let now = now(game);
+    let state = &mut game.externals.emulated.fmod;
+    let sound = int(args, 0) - 1;
+    let playback = match state.sounds.get(sound) {
+        Some(s) => Playback::start(&mut game.audio, &s.sound, looping, real(args, 1) >= 0.5, now),
+        None => return Ok(0.into()),
+    };
+    let instance = Instance { sound, playback, volume: 1.0 };
+    state.apply_volume(&instance);
+    Ok((state.instances.put(instance) + 1).into())
+}
+
+fn sound_set_group(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let group = int(args, 1).max(0) as usize;
+    let state = &mut game.externals.emulated.fmod;
+    if let Some(sound) = state.sounds.get_mut(int(args, 0) - 1) {
+        sound.group = group;
+    }
+    state.apply_volumes();
+    Ok(gml::TRUE.into())
+}
+
+fn sound_set_max_volume(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let state = &mut game.externals.emulated.fmod;
+    if let Some(sound) = state.sounds.get_mut(int(args, 0) - 1) {
+        sound.max_volume = real(args, 1).clamp(0.0, 1.0);
+    }
+    state.apply_volumes();
+    Ok(gml::TRUE.into())
+}
+
+fn sound_get_max_volume(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.fmod.sound(args).map_or(0.0, |s| s.max_volume).into())
+}
+
+fn sound_get_length(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    // in milliseconds
+    let length = game.externals.emulated.fmod.sound(args).map_or(0, |s| s.sound.length() / 1_000_000);
+    Ok((length as f64).into())
+}
+
+fn instance_stop(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let id = int(args, 0) - 1;
+    game.externals.emulated.fmod.stop(&game.audio, Some(id));
+    Ok(gml::TRUE.into())
+}
+
+fn instance_set_paused(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let now = now(game);
+    if let Some(instance) = game.externals.emulated.fmod.instance_mut(args) {
+        instance.playback.set_paused(real(args, 1) >= 0.5, now);
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn instance_get_paused(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.fmod.instance(args).map_or(false, |i| i.playback.paused_at.is_some()).into())
+}
+
+fn instance_is_playing(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    let now = now(game);
+    Ok(game.externals.emulated.fmod.instance(args).map_or(false, |i| i.playback.is_playing(now)).into())
+}
+
+fn instance_set_volume(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let state = &mut game.externals.emulated.fmod;
+    if let Some(instance) = state.instance_mut(args) {
+        instance.volume = real(args, 1).clamp(0.0, 1.0);
+    }
+    if let Some(instance) = state.instance(args) {
+        state.apply_volume(instance);
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn instance_get_volume(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.fmod.instance(args).map_or(0.0, |i| i.volume).into())
+}
+
+fn instance_set_pan(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    if let Some(instance) = game.externals.emulated.fmod.instance(args) {
+        instance.playback.set_pan(real(args, 1));
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn instance_get_pan(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.fmod.instance(args).map_or(0.0, |i| i.playback.pan()).into())
+}
+
+fn instance_get_sound(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.fmod.instance(args).map_or(0, |i| i.sound + 1).into())
+}
+
+fn master_set_volume(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let state = &mut game.externals.emulated.fmod;
+    state.master_volume = real(args, 0).clamp(0.0, 1.0);
+    state.apply_volumes();
+    Ok(gml::TRUE.into())
+}
+
+fn master_get_volume(game: &Game, _args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.fmod.master_volume.into())
+}
+
+fn group_set_volume(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let group = int(args, 0).max(0) as usize;
+    let state = &mut game.externals.emulated.fmod;
+    if state.group_volumes.len() <= group {
+        state.group_volumes.resize(group + 1, 1.0);
+    }
+    state.group_volumes[group] = real(args, 1).clamp(0.0, 1.0);
+    state.apply_volumes();
+    Ok(gml::TRUE.into())
+}
+
+fn group_get_volume(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.fmod.group_volume(int(args, 0).max(0) as usize).into())
+}
+
+fn group_stop(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let group = int(args, 0).max(0) as usize;
+    let state = &mut game.externals.emulated.fmod;
+    let instances = state.instances_of(|_, s| s.group == group);
+    state.stop(&game.audio, instances);
+    Ok(gml::TRUE.into())
+}
+
+fn group_set_paused(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let (group, paused) = (int(args, 0).max(0) as usize, real(args, 1) >= 0.5);
+    let now = now(game);
+    let state = &mut game.externals.emulated.fmod;
+    for id in state.instances_of(|_, s| s.group == group) {
+        state.instances.get_mut(id).unwrap().playback.set_paused(paused, now);
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn all_stop(game: &mut Game, _args: &[Value]) -> gml::Result<Value> {
+    let state = &mut game.externals.emulated.fmod;
+    let instances = state.instances.ids().collect::<Vec<_>>();
+    state.stop(&game.audio, instances);
+    Ok(gml::TRUE.into())
+}
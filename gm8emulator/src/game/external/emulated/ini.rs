@@ -0,0 +1,58 @@
+use super::{bytes, int};
+use crate::{
+    game::Game,
+    gml::{self, file, Function, Value},
+};
+
+// The private profile functions from kernel32.dll, which games define to use more than one ini file at a time.
+// Files are read and written on every call, the same as Windows does.
+
+pub fn lookup(symbol: &str) -> Option<Function> {
+    Some(match symbol {
+        "GetPrivateProfileIntA" => Function::Volatile(get_private_profile_int),
+        "WritePrivateProfileStringA" => Function::Engine(write_private_profile_string),
+        _ => return None,
+    })
+}
+
+fn load(game: &Game, path: &str) -> Option<::ini::Ini> {
+    let data = std::fs::read(file::to_path(path).as_ref()).ok()?;
+    ::ini::Ini::load_from_str(&game.decode_str(&data)).ok()
+}
+
+/// Reads a number from (section, key, default, file). Like Windows, this takes the digits at the start of the value.
+fn get_private_profile_int(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    let (section, key, path) = (game.decode_str(bytes(args, 0)), game.decode_str(bytes(args, 1)), bytes(args, 3));
+    let ini = match load(game, &game.decode_str(path)) {
+        Some(ini) => ini,
+        None => return Ok(int(args, 2).into()),
+    };
+    match ini.section(Some(section.as_ref())).and_then(|s| s.get(key.as_ref())) {
+        Some(value) => {
+            let value = value.trim();
+            let (sign, digits) = match value.strip_prefix('-') {
+                Some(rest) => (-1, rest),
+                None => (1, value),
+            };
+            let number = digits.chars().take_while(char::is_ascii_digit).fold(0i32, |n, c| {
+                n.wrapping_mul(10).wrapping_add(c.to_digit(10).unwrap() as i32)
+            });
+            Ok((sign * number).into())
+        },
+        None => Ok(int(args, 2).into()),
+    }
+}
+
+/// Writes (section, key, value, file), creating the file if it doesn't exist.
+fn write_private_profile_string(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let path = game.decode_str(bytes(args, 3)).into_owned();
+    let section = game.decode_str(bytes(args, 0)).into_owned();
+    let key = game.decode_str(bytes(args, 1)).into_owned();
+    let value = game.decode_str(bytes(args, 2)).into_owned();
+    let mut ini = load(game, &path).unwrap_or_else(::ini::Ini::new);
+    ini.with_section(Some(section.as_str())).set(key.as_str(), value.as_str());
+    let mut contents = Vec::new();
+    ini.write_to(&mut contents).expect("writing an ini to memory can't fail");
+    let contents = game.encode_str(&String::from_utf8_lossy(&contents)).into_owned();
+    Ok(std::fs::write(file::to_path(&path).as_ref(), contents).is_ok().into())
+}
@@ -0,0 +1,30 @@
+use super::bytes;
+use crate::gml::{self, Function, Value};
+use std::cmp::Ordering;
+
+// The string functions from kernel32.dll and user32.dll that games define to get around GML's.
+// These work on the game's bytes as they are, treating them as ASCII like the "A" versions do.
+
+pub fn lookup(symbol: &str) -> Option<Function> {
+    Some(match symbol {
+        "CharUpperA" => Function::Pure(|args| Ok(bytes(args, 0).to_ascii_uppercase().into())),
+        "CharLowerA" => Function::Pure(|args| Ok(bytes(args, 0).to_ascii_lowercase().into())),
+        "lstrlenA" => Function::Pure(|args| Ok(bytes(args, 0).len().into())),
+        "lstrcmpA" => Function::Pure(|args| Ok(compare(bytes(args, 0).cmp(bytes(args, 1))))),
+        "lstrcmpiA" => Function::Pure(lstrcmpi),
+        _ => return None,
+    })
+}
+
+fn compare(ordering: Ordering) -> Value {
+    match ordering {
+        Ordering::Less => (-1).into(),
+        Ordering::Equal => 0.into(),
+        Ordering::Greater => 1.into(),
+    }
+}
+
+fn lstrcmpi(args: &[Value]) -> gml::Result<Value> {
+    let (a, b) = (bytes(args, 0).to_ascii_lowercase(), bytes(args, 1).to_ascii_lowercase());
+    Ok(compare(a.cmp(&b)))
+}
@@ -0,0 +1,244 @@
+use super::{int, now, real, string};
+use crate::{
+    game::{
+        audio::{AudioManager, ExternalPlayback as Playback, ExternalSound},
+        Game,
+    },
+    gml::{self, Function, Value},
+    handleman::{HandleList, HandleManager},
+};
+use serde::{Deserialize, Serialize};
+
+// SuperSound, which plays each loaded sound as a single channel rather than having separate instances.
+// It passes its handles around as strings, which works out fine since emulated return values get converted.
+// Volume goes from 0 to 10000 and pan from -10000 to 10000.
+
+pub struct State {
+    sounds: HandleList<Sound>,
+}
+
+struct Sound {
+    sound: ExternalSound,
+    playback: Option<Playback>,
+    volume: f64,
+    pan: f64,
+    frequency: f64,
+}
+
+/// The part of the state that goes in savestates, with each sound's playback reduced to where it was.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedState {
+    sounds: HandleList<SavedSound>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SavedSound {
+    sound: ExternalSound,
+    playing: Option<SavedPlayback>,
+    volume: f64,
+    pan: f64,
+    frequency: f64,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct SavedPlayback {
+    looping: bool,
+    paused: bool,
+    position: u128,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self { sounds: HandleList::new() }
+    }
+}
+
+impl State {
+    pub fn save(&self, now: u128) -> SavedState {
+        SavedState {
+            sounds: self.sounds.map(|sound| SavedSound {
+                sound: sound.sound.clone(),
+                playing: sound.playback.as_ref().map(|playback| SavedPlayback {
+                    looping: playback.looping,
+                    paused: playback.paused_at.is_some(),
+                    position: playback.position(now),
+                }),
+                volume: sound.volume,
+                pan: sound.pan,
+                frequency: sound.frequency,
+            }),
+        }
+    }
+
+    /// Stops everything that's playing and restarts the sounds from a savestate where they were.
+    pub fn load(&mut self, audio: &mut AudioManager, saved: SavedState, now: u128) {
+        for id in self.sounds.ids().collect::<Vec<_>>() {
+            if let Some(playback) = self.sounds.get_mut(id).and_then(|s| s.playback.take()) {
+                audio.stop_external(playback.mixer_id);
+            }
+        }
+        self.sounds = saved.sounds.map(|saved| {
+            let playback = saved.playing.as_ref().map(|p| {
+                let playback = Playback::start_at(audio, &saved.sound, p.looping, p.paused, now, p.position);
+                playback.set_volume(saved.volume / 10000.0);
+                playback.set_pan(saved.pan / 10000.0);
+                playback
+            });
+            Sound {
+                sound: saved.sound.clone(),
+                playback,
+                volume: saved.volume,
+                pan: saved.pan,
+                frequency: saved.frequency,
+            }
+        });
+    }
+
+    fn sound(&self, args: &[Value]) -> Option<&Sound> {
+        self.sounds.get(int(args, 0) - 1)
+    }
+
+    fn sound_mut(&mut self, args: &[Value]) -> Option<&mut Sound> {
+        self.sounds.get_mut(int(args, 0) - 1)
+    }
+}
+
+pub fn lookup(symbol: &str) -> Option<Function> {
+    Some(match symbol {
+        "SS_Init" => Function::Engine(init),
+        "SS_Unload" => Function::Engine(unload),
+        "SS_LoadSound" => Function::Engine(load_sound),
+        "SS_FreeSound" => Function::Engine(free_sound),
+        "SS_IsHandleValid" => Function::Constant(is_handle_valid),
+        "SS_PlaySound" => Function::Engine(|game, args| play(game, args, false)),
+        "SS_LoopSound" => Function::Engine(|game, args| play(game, args, true)),
+        "SS_StopSound" => Function::Engine(stop_sound),
+        "SS_PauseSound" => Function::Engine(|game, args| set_paused(game, args, true)),
+        "SS_ResumeSound" => Function::Engine(|game, args| set_paused(game, args, false)),
+        "SS_IsSoundPlaying" => Function::Volatile(is_sound_playing),
+        "SS_IsSoundLooping" => Function::Volatile(is_sound_looping),
+        "SS_IsSoundPaused" => Function::Constant(is_sound_paused),
+        "SS_SetSoundVol" => Function::Engine(set_sound_vol),
+        "SS_GetSoundVol" => Function::Constant(|game, args| sound_property(game, args, |s| s.volume)),
+        "SS_SetSoundPan" => Function::Engine(set_sound_pan),
+        "SS_GetSoundPan" => Function::Constant(|game, args| sound_property(game, args, |s| s.pan)),
+        // There's no pitch control in the mixer, so frequency is only remembered
+        "SS_SetSoundFreq" => Function::Engine(set_sound_freq),
+        "SS_GetSoundFreq" => Function::Constant(|game, args| sound_property(game, args, |s| s.frequency)),
+        "SS_GetSoundLength" => Function::Constant(get_sound_length),
+        _ => return None,
+    })
+}
+
+fn init(game: &mut Game, _args: &[Value]) -> gml::Result<Value> {
+    unload(game, &[])?;
+    Ok("Yes".into())
+}
+
+fn unload(game: &mut Game, _args: &[Value]) -> gml::Result<Value> {
+    let state = &mut game.externals.emulated.supersound;
+    for id in state.sounds.ids().collect::<Vec<_>>() {
+        let _ = free_sound(game, &[(id + 1).into()]);
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn load_sound(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    let sound = match std::fs::read(string(args, 0)).ok().and_then(|f| game.audio.add_external(f.into_boxed_slice())) {
+        Some(sound) => sound,
+        None => return Ok(0.into()),
+    };
+    let sound = Sound { sound, playback: None, volume: 10000.0, pan: 0.0, frequency: 0.0 };
+    Ok((game.externals.emulated.supersound.sounds.put(sound) + 1).into())
+}
+
+fn free_sound(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    stop_sound(game, args)?;
+    Ok(game.externals.emulated.supersound.sounds.delete(int(args, 0) - 1).into())
+}
+
+fn is_handle_valid(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    Ok(game.externals.emulated.supersound.sound(args).is_some().into())
+}
+
+fn play(game: &mut Game, args: &[Value], looping: bool) -> gml::Result<Value> {
+    stop_sound(game, args)?;
+    let now = now(game);
+    if let Some(sound) = game.externals.emulated.supersound.sound_mut(args) {
+        let playback = Playback::start(&mut game.audio, &sound.sound, looping, false, now);
+        playback.set_volume(sound.volume / 10000.0);
+        playback.set_pan(sound.pan / 10000.0);
+        sound.playback = Some(playback);
+        Ok(gml::TRUE.into())
+    } else {
+        Ok(gml::FALSE.into())
+    }
+}
+
+fn stop_sound(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    if let Some(playback) = game.externals.emulated.supersound.sound_mut(args).and_then(|s| s.playback.take()) {
+        game.audio.stop_external(playback.mixer_id);
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn set_paused(game: &mut Game, args: &[Value], paused: bool) -> gml::Result<Value> {
+    let now = now(game);
+    if let Some(playback) = game.externals.emulated.supersound.sound_mut(args).and_then(|s| s.playback.as_mut()) {
+        playback.set_paused(paused, now);
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn is_sound_playing(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    let now = now(game);
+    let playback = game.externals.emulated.supersound.sound(args).and_then(|s| s.playback.as_ref());
+    Ok(playback.map_or(false, |p| p.paused_at.is_none() && p.is_playing(now)).into())
+}
+
+fn is_sound_looping(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    let playback = game.externals.emulated.supersound.sound(args).and_then(|s| s.playback.as_ref());
+    Ok(playback.map_or(false, |p| p.looping).into())
+}
+
+fn is_sound_paused(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    let playback = game.externals.emulated.supersound.sound(args).and_then(|s| s.playback.as_ref());
+    Ok(playback.map_or(false, |p| p.paused_at.is_some()).into())
+}
+
+fn set_sound_vol(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    if let Some(sound) = game.externals.emulated.supersound.sound_mut(args) {
+        sound.volume = real(args, 1).clamp(0.0, 10000.0);
+        if let Some(playback) = &sound.playback {
+            playback.set_volume(sound.volume / 10000.0);
+        }
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn set_sound_pan(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    if let Some(sound) = game.externals.emulated.supersound.sound_mut(args) {
+        sound.pan = real(args, 1).clamp(-10000.0, 10000.0);
+        if let Some(playback) = &sound.playback {
+            playback.set_pan(sound.pan / 10000.0);
+        }
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn set_sound_freq(game: &mut Game, args: &[Value]) -> gml::Result<Value> {
+    if let Some(sound) = game.externals.emulated.supersound.sound_mut(args) {
+        sound.frequency = real(args, 1);
+    }
+    Ok(gml::TRUE.into())
+}
+
+fn sound_property(game: &Game, args: &[Value], f: fn(&Sound) -> f64) -> gml::Result<Value> {
+    Ok(game.externals.emulated.supersound.sound(args).map_or(0.0, f).into())
+}
+
+fn get_sound_length(game: &Game, args: &[Value]) -> gml::Result<Value> {
+    // in milliseconds
+    let length = game.externals.emulated.supersound.sound(args).map_or(0, |s| s.sound.length() / 1_000_000);
+    Ok((length as f64).into())
+}
@@ -0,0 +1,2066 @@
+#![cfg(all(target_os = "linux", target_arch = "x86"))]
+
+use super::dll;
+use std::{
+    arch::{asm, global_asm},
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    env,
+    ffi::{c_void, CStr, CString},
+    fmt, fs, io,
+    ops::Range,
+    os::raw::c_char,
+    path::{Path, PathBuf},
+};
+
+// A loader for 32-bit Windows DLLs on Linux. DLLs get mapped into memory by hand, and their imports are pointed at
+// Rust stand-ins for the small part of kernel32, user32 and msvcrt which extensions tend to use.
+// Anything else they import is reported by name when loading, since calling into nothing would just crash.
+// Everything happens on one thread, which is also why critical sections and such can be no-ops.
+
+const DLL_PROCESS_DETACH: u32 = 0;
+const DLL_PROCESS_ATTACH: u32 = 1;
+
+const IMAGE_FILE_MACHINE_I386: u16 = 0x14C;
+const IMAGE_NT_OPTIONAL_HDR32_MAGIC: u16 = 0x10B;
+const IMAGE_DIRECTORY_ENTRY_EXPORT: usize = 0;
+const IMAGE_DIRECTORY_ENTRY_IMPORT: usize = 1;
+const IMAGE_DIRECTORY_ENTRY_BASERELOC: usize = 5;
+const IMAGE_REL_BASED_ABSOLUTE: u16 = 0;
+const IMAGE_REL_BASED_HIGHLOW: u16 = 3;
+
+const PROCESS_HEAP: u32 = 1;
+const TLS_SLOTS: u32 = 64;
+
+// offsets into the thread environment block
+const TEB_LAST_ERROR: usize = 0x34;
+const TEB_TLS_SLOTS: usize = 0xE10;
+
+pub struct NativeManager;
+
+pub struct NativeExternal {
+    module: usize,
+    function: usize,
+    type_return: dll::ValueType,
+}
+
+impl NativeManager {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn define(&self, signature: &dll::ExternalSignature) -> Result<NativeExternal, String> {
+        let module = load_library(&signature.dll, None)?;
+        match get_proc_address(module, &Import::Name(&signature.symbol)) {
+            Some(function) => Ok(NativeExternal { module, function, type_return: signature.type_return }),
+            None => {
+                free_library(module);
+                Err(format!("failed to find '{}' in '{}'", signature.symbol, signature.dll))
+            },
+        }
+    }
+
+    pub fn call(&mut self, external: &NativeExternal, args: &[dll::Value]) -> dll::Value {
+        let mut words = Vec::with_capacity(args.len() * 2);
+        for arg in args {
+            match arg {
+                dll::Value::Real(x) => {
+                    let bits = x.to_bits();
+                    words.push(bits as u32);
+                    words.push((bits >> 32) as u32);
+                },
+                dll::Value::Str(s) => words.push(s.as_ptr() as u32),
+            }
+        }
+        unsafe {
+            match external.type_return {
+                dll::ValueType::Real => dll::Value::Real(call_real(external.function, &words)),
+                dll::ValueType::Str => dll::Value::Str({
+                    let char_ptr = call_int(external.function, &words) as *const c_char;
+                    if !char_ptr.is_null() {
+                        dll::PascalString::new(CStr::from_ptr(char_ptr).to_bytes())
+                    } else {
+                        dll::PascalString::empty()
+                    }
+                }),
+            }
+        }
+    }
+}
+
+impl Drop for NativeExternal {
+    fn drop(&mut self) {
+        free_library(self.module);
+    }
+}
+
+// calling into dlls
+
+// Arguments are pushed as 32-bit words and the stack pointer is put back afterwards,
+// so this works for both stdcall and cdecl, whether or not the callee cleaned up.
+unsafe fn call_int(function: usize, words: &[u32]) -> u32 {
+    let result: usize;
+    asm!(
+        "mov edi, esp",
+        "2:",
+        "test ecx, ecx",
+        "jz 3f",
+        "push dword ptr [edx + 4*ecx - 4]",
+        "dec ecx",
+        "jmp 2b",
+        "3:",
+        "call eax",
+        "mov esp, edi",
+        inout("eax") function => result,
+        inout("ecx") words.len() => _,
+        inout("edx") words.as_ptr() as usize => _,
+        out("edi") _,
+        clobber_abi("C"),
+    );
+    result as u32
+}
+
+// Same as above, but for functions returning a double in st(0).
+unsafe fn call_real(function: usize, words: &[u32]) -> f64 {
+    let low: usize;
+    let high: usize;
+    asm!(
+        "mov edi, esp",
+        "2:",
+        "test ecx, ecx",
+        "jz 3f",
+        "push dword ptr [edx + 4*ecx - 4]",
+        "dec ecx",
+        "jmp 2b",
+        "3:",
+        "call eax",
+        "mov esp, edi",
+        "sub esp, 8",
+        "fstp qword ptr [esp]",
+        "pop eax",
+        "pop edx",
+        inout("eax") function => low,
+        inout("ecx") words.len() => _,
+        inout("edx") words.as_ptr() as usize => high,
+        out("edi") _,
+        clobber_abi("C"),
+    );
+    f64::from_bits((high as u64) << 32 | low as u64)
+}
+
+// calling from dlls
+
+// Msvc code only keeps the stack 4-byte aligned, but the stand-ins below and the C library they use are built
+// assuming it's 16-byte aligned on entry, which SSE code relies on. So each import is pointed at a stub which pushes
+// the stand-in's address and jumps here, to call it on an aligned copy of the first 32 argument words.
+// Afterwards, how far the stand-in moved the stack pointer says how many bytes of arguments to pop when returning,
+// which covers both stdcall and cdecl.
+global_asm!(
+    ".globl opengmk_realign",
+    "opengmk_realign:",
+    "push ebp",
+    "mov ebp, esp",
+    "push esi",
+    "push edi",
+    "sub esp, 128",
+    "and esp, -16",
+    "mov edi, esp",
+    "lea esi, [ebp + 12]",
+    "mov ecx, 32",
+    "cld",
+    "rep movsd",
+    "mov esi, esp",
+    "call dword ptr [ebp + 4]",
+    // ecx is the only register left which isn't callee-saved or holding the result
+    "mov ecx, esp",
+    "sub ecx, esi",
+    "mov edi, dword ptr [ebp + 8]",
+    "mov dword ptr [ebp + ecx + 8], edi",
+    "lea ecx, [ebp + ecx + 8]",
+    "mov edi, dword ptr [ebp - 8]",
+    "mov esi, dword ptr [ebp - 4]",
+    "mov ebp, dword ptr [ebp]",
+    "mov esp, ecx",
+    "ret",
+);
+
+extern "C" {
+    fn opengmk_realign();
+}
+
+const STUB_SIZE: usize = 16;
+
+/// Returns the stub which calls a stand-in function through `opengmk_realign`, making one if needed.
+fn realign(function: *const ()) -> Option<usize> {
+    STATE.with(|s| {
+        let mut state = s.borrow_mut();
+        if let Some(&stub) = state.stubs.get(&(function as usize)) {
+            return Some(stub)
+        }
+        if state.stub_space.is_empty() {
+            let page = unsafe {
+                libc::mmap(
+                    std::ptr::null_mut(),
+                    0x1000,
+                    libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+                    libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                    -1,
+                    0,
+                )
+            };
+            if page == libc::MAP_FAILED {
+                return None
+            }
+            state.stub_space = page as usize..page as usize + 0x1000;
+        }
+        let stub = state.stub_space.start;
+        state.stub_space.start += STUB_SIZE;
+
+        // push function; jmp opengmk_realign
+        let jump = (opengmk_realign as *const () as usize).wrapping_sub(stub + 10) as u32;
+        let code = [&[0x68][..], &(function as u32).to_le_bytes(), &[0xE9], &jump.to_le_bytes()].concat();
+        unsafe { std::ptr::copy_nonoverlapping(code.as_ptr(), stub as *mut u8, code.len()) };
+        state.stubs.insert(function as usize, stub);
+        Some(stub)
+    })
+}
+
+// thread environment block, which msvc code reaches through fs
+
+thread_local! {
+    static TEB: Cell<usize> = Cell::new(0);
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+#[repr(C)]
+struct UserDesc {
+    entry_number: u32,
+    base_addr: u32,
+    limit: u32,
+    flags: u32,
+}
+
+fn init_thread() -> Result<(), String> {
+    if TEB.with(Cell::get) != 0 {
+        return Ok(())
+    }
+    unsafe {
+        let teb = Box::leak(vec![0u32; 0x400].into_boxed_slice()).as_mut_ptr();
+        let peb = Box::leak(vec![0u32; 0x100].into_boxed_slice()).as_mut_ptr();
+        let (stack_base, stack_limit) = {
+            let mut attr = std::mem::zeroed();
+            let (mut address, mut size) = (std::ptr::null_mut(), 0);
+            libc::pthread_getattr_np(libc::pthread_self(), &mut attr);
+            libc::pthread_attr_getstack(&attr, &mut address, &mut size);
+            libc::pthread_attr_destroy(&mut attr);
+            (address as u32 + size as u32, address as u32)
+        };
+        teb.write(u32::MAX); // no SEH handlers
+        teb.add(1).write(stack_base);
+        teb.add(2).write(stack_limit);
+        teb.add(0x18 / 4).write(teb as u32);
+        teb.add(0x20 / 4).write(libc::getpid() as u32);
+        teb.add(0x24 / 4).write(libc::syscall(libc::SYS_gettid) as u32);
+        teb.add(0x30 / 4).write(peb as u32);
+        peb.add(0x18 / 4).write(PROCESS_HEAP);
+
+        // a 4KiB 32-bit data segment, with the kernel picking a free GDT entry
+        let mut desc = UserDesc { entry_number: u32::MAX, base_addr: teb as u32, limit: 0xFFF, flags: 0x41 };
+        if libc::syscall(libc::SYS_set_thread_area, &mut desc as *mut UserDesc) != 0 {
+            return Err(format!("couldn't set up a thread environment block: {}", io::Error::last_os_error()))
+        }
+        let selector = (desc.entry_number << 3 | 3) as u16;
+        asm!("mov fs, {0:x}", in(reg) selector, options(nostack, preserves_flags));
+        TEB.with(|t| t.set(teb as usize));
+    }
+    Ok(())
+}
+
+unsafe fn teb_field(offset: usize) -> *mut u32 {
+    (TEB.with(Cell::get) + offset) as *mut u32
+}
+
+fn set_last_error(code: u32) {
+    unsafe { teb_field(TEB_LAST_ERROR).write(code) }
+}
+
+// module loading
+
+#[derive(Default)]
+struct State {
+    modules: Vec<Module>,
+    allocations: HashMap<usize, usize>,
+    next_tls_slot: u32,
+    stubs: HashMap<usize, usize>,
+    stub_space: Range<usize>,
+}
+
+struct Module {
+    path: PathBuf,
+    base: usize,
+    size: usize,
+    entry: Option<usize>,
+    refs: usize,
+    attached: bool,
+    exports: Exports,
+    dependencies: Vec<usize>,
+}
+
+#[derive(Default)]
+struct Exports {
+    ordinal_base: u32,
+    functions: Vec<Option<Export>>,
+    names: HashMap<String, usize>,
+}
+
+#[derive(Clone)]
+enum Export {
+    Address(usize),
+    Forward(String),
+}
+
+enum Import<'a> {
+    Name(&'a str),
+    Ordinal(u16),
+}
+
+impl fmt::Display for Import<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "{}", name),
+            Self::Ordinal(ordinal) => write!(f, "#{}", ordinal),
+        }
+    }
+}
+
+impl Exports {
+    fn find(&self, import: &Import) -> Option<Export> {
+        let index = match import {
+            Import::Name(name) => *self.names.get(*name)?,
+            Import::Ordinal(ordinal) => u32::from(*ordinal).checked_sub(self.ordinal_base)? as usize,
+        };
+        self.functions.get(index).cloned().flatten()
+    }
+}
+
+fn le_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    bytes.get(offset..offset + 2).map(|b| u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn le_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    bytes.get(offset..offset + 4).map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+unsafe fn read<T: Copy>(address: usize) -> T {
+    (address as *const T).read_unaligned()
+}
+
+unsafe fn c_string(address: usize) -> String {
+    String::from_utf8_lossy(CStr::from_ptr(address as *const c_char).to_bytes()).into_owned()
+}
+
+fn file_name(path: &str) -> &str {
+    path.rsplit(|c| c == '/' || c == '\\').next().unwrap_or(path)
+}
+
+/// Finds a file, ignoring case in its name as Windows would.
+fn find_file(path: &Path) -> Option<PathBuf> {
+    if path.is_file() {
+        return Some(path.into())
+    }
+    let dir = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."));
+    let name = path.file_name()?.to_str()?;
+    fs::read_dir(dir)
+        .ok()?
+        .filter_map(Result::ok)
+        .map(|entry| entry.path())
+        .find(|p| p.file_name().and_then(|n| n.to_str()).map_or(false, |n| n.eq_ignore_ascii_case(name)))
+}
+
+fn find_dll(name: &str, search_dir: Option<&Path>) -> Option<PathBuf> {
+    let mut name = name.replace('\\', "/");
+    if !file_name(&name).contains('.') {
+        name.push_str(".dll");
+    }
+    let path = Path::new(&name);
+    if path.components().count() > 1 {
+        return find_file(path)
+    }
+    search_dir.and_then(|dir| find_file(&dir.join(path))).or_else(|| find_file(path))
+}
+
+// system dlls have no image, so their handles are just some unique addresses
+#[derive(Clone, Copy)]
+enum System {
+    Kernel32,
+    User32,
+    Msvcrt,
+}
+
+static SYSTEM_MODULES: [u8; 4] = [0; 4];
+const MAIN_MODULE: usize = 3;
+
+fn system_dll(name: &str) -> Option<System> {
+    let name = file_name(name).to_ascii_lowercase();
+    let name = name.strip_suffix(".dll").unwrap_or(&name);
+    match name {
+        "kernel32" | "kernelbase" => Some(System::Kernel32),
+        "user32" => Some(System::User32),
+        "msvcrt" | "ucrtbase" => Some(System::Msvcrt),
+        _ if name.starts_with("msvcr") || name.starts_with("vcruntime") || name.starts_with("api-ms-win-crt-") => {
+            Some(System::Msvcrt)
+        },
+        _ => None,
+    }
+}
+
+fn system_handle(index: usize) -> usize {
+    &SYSTEM_MODULES[index] as *const u8 as usize
+}
+
+fn handle_system(handle: usize) -> Option<System> {
+    [System::Kernel32, System::User32, System::Msvcrt].iter().copied().find(|&s| system_handle(s as usize) == handle)
+}
+
+fn module_handle(name: &str) -> Option<usize> {
+    if let Some(system) = system_dll(name) {
+        return Some(system_handle(system as usize))
+    }
+    let mut name = file_name(name).to_string();
+    if !name.contains('.') {
+        name.push_str(".dll");
+    }
+    STATE.with(|s| {
+        let state = s.borrow();
+        state
+            .modules
+            .iter()
+            .find(|m| m.path.file_name().and_then(|n| n.to_str()).map_or(false, |n| n.eq_ignore_ascii_case(&name)))
+            .map(|m| m.base)
+    })
+}
+
+fn module_path(handle: usize) -> Option<PathBuf> {
+    if handle == 0 || handle == system_handle(MAIN_MODULE) {
+        return env::current_exe().ok()
+    }
+    STATE.with(|s| s.borrow().modules.iter().find(|m| m.base == handle).map(|m| m.path.clone()))
+}
+
+fn load_library(name: &str, search_dir: Option<&Path>) -> Result<usize, String> {
+    if let Some(system) = system_dll(name) {
+        return Ok(system_handle(system as usize))
+    }
+    let path = find_dll(name, search_dir).ok_or_else(|| format!("couldn't find dll '{}'", name))?;
+    let path = path.canonicalize().unwrap_or(path);
+    let loaded = STATE.with(|s| {
+        let mut state = s.borrow_mut();
+        state.modules.iter_mut().find(|m| m.path == path).map(|m| {
+            m.refs += 1;
+            m.base
+        })
+    });
+    if let Some(base) = loaded {
+        return Ok(base)
+    }
+
+    init_thread()?;
+    let (module, imports) = unsafe { map_image(path)? };
+    let (base, entry, dir) = (module.base, module.entry, module.path.parent().map(Path::to_path_buf));
+    STATE.with(|s| s.borrow_mut().modules.push(module));
+
+    // the borrow mustn't be held while running dll code, as it may well call LoadLibrary itself
+    let result = unsafe { link(base, imports, dir.as_deref(), name) }.and_then(|dependencies| {
+        STATE.with(|s| {
+            let mut state = s.borrow_mut();
+            let module = state.modules.iter_mut().find(|m| m.base == base).unwrap();
+            module.dependencies = dependencies;
+        });
+        match entry {
+            Some(entry) if unsafe { call_int(entry, &[base as u32, DLL_PROCESS_ATTACH, 0]) } == 0 => {
+                Err(format!("'{}' failed to initialise", name))
+            },
+            _ => Ok(()),
+        }
+    });
+    match result {
+        Ok(()) => {
+            STATE.with(|s| s.borrow_mut().modules.iter_mut().find(|m| m.base == base).unwrap().attached = true);
+            Ok(base)
+        },
+        Err(e) => {
+            free_library(base);
+            Err(e)
+        },
+    }
+}
+
+fn free_library(handle: usize) {
+    let module = STATE.with(|s| {
+        let mut state = s.borrow_mut();
+        let index = state.modules.iter().position(|m| m.base == handle)?;
+        state.modules[index].refs -= 1;
+        if state.modules[index].refs == 0 { Some(state.modules.remove(index)) } else { None }
+    });
+    if let Some(module) = module {
+        unsafe {
+            if let (Some(entry), true) = (module.entry, module.attached) {
+                call_int(entry, &[module.base as u32, DLL_PROCESS_DETACH, 0]);
+            }
+            libc::munmap(module.base as *mut c_void, module.size);
+        }
+        for dependency in module.dependencies {
+            free_library(dependency);
+        }
+    }
+}
+
+fn get_proc_address(handle: usize, import: &Import) -> Option<usize> {
+    if let Some(system) = handle_system(handle) {
+        return match import {
+            Import::Name(name) => match system {
+                System::Kernel32 => kernel32::lookup(name).and_then(realign),
+                System::User32 => user32::lookup(name).and_then(realign),
+                System::Msvcrt => match msvcrt::variable(name) {
+                    Some(variable) => Some(variable as usize),
+                    None => msvcrt::lookup(name).and_then(realign),
+                },
+            },
+            Import::Ordinal(_) => None,
+        }
+    }
+    let export = STATE.with(|s| s.borrow().modules.iter().find(|m| m.base == handle)?.exports.find(import))?;
+    match export {
+        Export::Address(address) => Some(address),
+        Export::Forward(target) => {
+            // these look like "NTDLL.RtlUnwind" or "MYDLL.#12"
+            let (dll, name) = target.split_once('.')?;
+            let handle = load_library(dll, None).ok()?;
+            match name.strip_prefix('#') {
+                Some(ordinal) => get_proc_address(handle, &Import::Ordinal(ordinal.parse().ok()?)),
+                None => get_proc_address(handle, &Import::Name(name)),
+            }
+        },
+    }
+}
+
+/// Maps a DLL's sections into memory and relocates them, returning it along with its import directory.
+unsafe fn map_image(path: PathBuf) -> Result<(Module, (u32, u32)), String> {
+    let name = path.display();
+    let data = fs::read(&path).map_err(|e| format!("couldn't read '{}': {}", name, e))?;
+    let invalid = || format!("'{}' isn't a valid dll", name);
+
+    if data.get(..2) != Some(&b"MZ"[..]) {
+        return Err(invalid())
+    }
+    let pe = le_u32(&data, 0x3C).ok_or_else(invalid)? as usize;
+    if data.get(pe..pe + 4) != Some(&b"PE\0\0"[..]) {
+        return Err(invalid())
+    }
+    let opt = pe + 24;
+    if le_u16(&data, pe + 4) != Some(IMAGE_FILE_MACHINE_I386)
+        || le_u16(&data, opt) != Some(IMAGE_NT_OPTIONAL_HDR32_MAGIC)
+    {
+        return Err(format!("'{}' isn't a 32-bit x86 dll", name))
+    }
+    let field = |offset: usize| le_u32(&data, opt + offset).ok_or_else(invalid);
+    let section_count = le_u16(&data, pe + 6).ok_or_else(invalid)? as usize;
+    let sections = opt + le_u16(&data, pe + 20).ok_or_else(invalid)? as usize;
+    let entry = field(16)?;
+    let image_base = field(28)?;
+    let image_size = field(56)? as usize;
+    let headers_size = field(60)? as usize;
+    let directory_count = field(92)? as usize;
+    let directory = |index: usize| match index < directory_count {
+        true => (field(96 + index * 8).unwrap_or(0), field(100 + index * 8).unwrap_or(0)),
+        false => (0, 0),
+    };
+
+    // everything is left writable and executable, which saves emulating VirtualProtect
+    let memory = libc::mmap(
+        image_base as *mut c_void,
+        image_size,
+        libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+        libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+        -1,
+        0,
+    );
+    if memory == libc::MAP_FAILED {
+        return Err(format!("couldn't map '{}': {}", name, io::Error::last_os_error()))
+    }
+    let base = memory as usize;
+    let image = std::slice::from_raw_parts_mut(memory as *mut u8, image_size);
+
+    let copied = (|| {
+        let header_len = headers_size.min(data.len()).min(image_size);
+        image[..header_len].copy_from_slice(&data[..header_len]);
+        for section in (0..section_count).map(|i| sections + i * 40) {
+            let virtual_size = le_u32(&data, section + 8)? as usize;
+            let address = le_u32(&data, section + 12)? as usize;
+            let raw_size = le_u32(&data, section + 16)? as usize;
+            let raw_pointer = le_u32(&data, section + 20)? as usize;
+            let len = if virtual_size == 0 { raw_size } else { raw_size.min(virtual_size) };
+            image.get_mut(address..address + len)?.copy_from_slice(data.get(raw_pointer..raw_pointer + len)?);
+        }
+        Some(())
+    })()
+    .ok_or_else(invalid);
+    let result = copied.and_then(|()| {
+        relocate(image, base.wrapping_sub(image_base as usize) as u32, directory(IMAGE_DIRECTORY_ENTRY_BASERELOC))
+    });
+    if let Err(e) = result {
+        libc::munmap(memory, image_size);
+        return Err(format!("'{}': {}", name, e))
+    }
+
+    let module = Module {
+        exports: read_exports(base, directory(IMAGE_DIRECTORY_ENTRY_EXPORT)),
+        path: path.clone(),
+        base,
+        size: image_size,
+        entry: if entry != 0 { Some(base + entry as usize) } else { None },
+        refs: 1,
+        attached: false,
+        dependencies: Vec::new(),
+    };
+    Ok((module, directory(IMAGE_DIRECTORY_ENTRY_IMPORT)))
+}
+
+fn relocate(image: &mut [u8], delta: u32, (rva, size): (u32, u32)) -> Result<(), String> {
+    if delta == 0 {
+        return Ok(())
+    }
+    if rva == 0 {
+        return Err("couldn't be loaded at its preferred address, and has no relocations".into())
+    }
+    let truncated = || String::from("relocations are truncated");
+    let (mut block, end) = (rva as usize, (rva + size) as usize);
+    while block + 8 <= end {
+        let page = le_u32(image, block).ok_or_else(truncated)? as usize;
+        let block_size = le_u32(image, block + 4).ok_or_else(truncated)? as usize;
+        if block_size < 8 {
+            break
+        }
+        for entry in (block + 8..block + block_size).step_by(2) {
+            let entry = le_u16(image, entry).ok_or_else(truncated)?;
+            match entry >> 12 {
+                IMAGE_REL_BASED_ABSOLUTE => (),
+                IMAGE_REL_BASED_HIGHLOW => {
+                    let at = page + usize::from(entry & 0xFFF);
+                    let value = le_u32(image, at).ok_or_else(truncated)?.wrapping_add(delta);
+                    image[at..at + 4].copy_from_slice(&value.to_le_bytes());
+                },
+                kind => return Err(format!("unsupported relocation type {}", kind)),
+            }
+        }
+        block += block_size;
+    }
+    Ok(())
+}
+
+unsafe fn read_exports(base: usize, (rva, size): (u32, u32)) -> Exports {
+    if rva == 0 {
+        return Exports::default()
+    }
+    let dir = base + rva as usize;
+    let ordinal_base = read::<u32>(dir + 16);
+    let function_count = read::<u32>(dir + 20) as usize;
+    let name_count = read::<u32>(dir + 24) as usize;
+    let (functions, names, ordinals) = (
+        base + read::<u32>(dir + 28) as usize,
+        base + read::<u32>(dir + 32) as usize,
+        base + read::<u32>(dir + 36) as usize,
+    );
+    Exports {
+        ordinal_base,
+        functions: (0..function_count)
+            .map(|i| match read::<u32>(functions + i * 4) {
+                0 => None,
+                f if f >= rva && f < rva + size => Some(Export::Forward(c_string(base + f as usize))),
+                f => Some(Export::Address(base + f as usize)),
+            })
+            .collect(),
+        names: (0..name_count)
+            .map(|i| (c_string(base + read::<u32>(names + i * 4) as usize), usize::from(read::<u16>(ordinals + i * 2))))
+            .collect(),
+    }
+}
+
+/// Resolves a freshly mapped DLL's imports, returning the handles of the DLLs it depends on.
+unsafe fn link(base: usize, (rva, _): (u32, u32), search_dir: Option<&Path>, name: &str) -> Result<Vec<usize>, String> {
+    if rva == 0 {
+        return Ok(Vec::new())
+    }
+    let mut dependencies = Vec::new();
+    let mut missing = Vec::new();
+    let mut descriptor = base + rva as usize;
+    loop {
+        let (lookup, dll_name, thunks) =
+            (read::<u32>(descriptor), read::<u32>(descriptor + 12), read::<u32>(descriptor + 16));
+        if dll_name == 0 || thunks == 0 {
+            break
+        }
+        let dll = c_string(base + dll_name as usize);
+        let handle = match load_library(&dll, search_dir) {
+            Ok(handle) => handle,
+            Err(e) => {
+                dependencies.into_iter().for_each(free_library);
+                return Err(format!("couldn't load '{}' for '{}': {}", dll, name, e))
+            },
+        };
+        dependencies.push(handle);
+        let lookup = base + if lookup != 0 { lookup } else { thunks } as usize;
+        for i in 0.. {
+            let entry = read::<u32>(lookup + i * 4);
+            if entry == 0 {
+                break
+            }
+            let symbol = match entry & 0x8000_0000 {
+                0 => c_string(base + entry as usize + 2),
+                _ => String::new(),
+            };
+            let import = match entry & 0x8000_0000 {
+                0 => Import::Name(&symbol),
+                _ => Import::Ordinal(entry as u16),
+            };
+            match get_proc_address(handle, &import) {
+                Some(address) => ((base + thunks as usize + i * 4) as *mut u32).write(address as u32),
+                None => missing.push(format!("{}!{}", dll, import)),
+            }
+        }
+        descriptor += 20;
+    }
+    if missing.is_empty() {
+        Ok(dependencies)
+    } else {
+        dependencies.into_iter().for_each(free_library);
+        Err(format!("'{}' imports functions which aren't supported: {}", name, missing.join(", ")))
+    }
+}
+
+/// Translates a Windows path for opening a file, matching its name regardless of case.
+fn host_path(path: &[u8]) -> Option<CString> {
+    let path = PathBuf::from(String::from_utf8_lossy(path).replace('\\', "/"));
+    let path = find_file(&path).unwrap_or(path);
+    CString::new(path.to_str()?).ok()
+}
+
+unsafe fn copy_c_string(text: &[u8], buf: *mut u8, size: u32) -> u32 {
+    if size == 0 {
+        return 0
+    }
+    let len = text.len().min(size as usize - 1);
+    std::ptr::copy_nonoverlapping(text.as_ptr(), buf, len);
+    buf.add(len).write(0);
+    len as u32
+}
+
+#[allow(non_snake_case)]
+mod kernel32 {
+    use super::*;
+    use std::{
+        sync::atomic::{AtomicI32, Ordering},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    type BOOL = i32;
+
+    const ERROR_FILE_NOT_FOUND: u32 = 2;
+    const ERROR_INVALID_HANDLE: u32 = 6;
+    const ERROR_NOT_ENOUGH_MEMORY: u32 = 8;
+    const ERROR_INSUFFICIENT_BUFFER: u32 = 122;
+    const ERROR_MOD_NOT_FOUND: u32 = 126;
+    const ERROR_PROC_NOT_FOUND: u32 = 127;
+
+    const HEAP_ZERO_MEMORY: u32 = 0x08;
+    const GMEM_ZEROINIT: u32 = 0x40;
+    const MEM_RESERVE: u32 = 0x2000;
+    const MEM_RELEASE: u32 = 0x8000;
+    const PAGE_EXECUTE_READWRITE: u32 = 0x40;
+
+    const GENERIC_READ: u32 = 0x8000_0000;
+    const GENERIC_WRITE: u32 = 0x4000_0000;
+    const CREATE_NEW: u32 = 1;
+    const CREATE_ALWAYS: u32 = 2;
+    const OPEN_ALWAYS: u32 = 4;
+    const TRUNCATE_EXISTING: u32 = 5;
+    const INVALID_HANDLE_VALUE: u32 = u32::MAX;
+    const INVALID_FILE_ATTRIBUTES: u32 = u32::MAX;
+    const FILE_ATTRIBUTE_DIRECTORY: u32 = 0x10;
+    const FILE_ATTRIBUTE_NORMAL: u32 = 0x80;
+
+    // file handles are file descriptors offset by this, so they can't be mistaken for anything else
+    const FILE_HANDLE_BASE: u32 = 0x1000;
+
+    pub fn lookup(name: &str) -> Option<*const ()> {
+        Some(match name {
+            "GetModuleHandleA" => GetModuleHandleA as *const (),
+            "GetModuleHandleW" => GetModuleHandleW as *const (),
+            "GetModuleFileNameA" => GetModuleFileNameA as *const (),
+            "LoadLibraryA" => LoadLibraryA as *const (),
+            "LoadLibraryW" => LoadLibraryW as *const (),
+            "LoadLibraryExA" => LoadLibraryExA as *const (),
+            "FreeLibrary" => FreeLibrary as *const (),
+            "GetProcAddress" => GetProcAddress as *const (),
+            "DisableThreadLibraryCalls" => DisableThreadLibraryCalls as *const (),
+            "GetLastError" => GetLastError as *const (),
+            "SetLastError" => SetLastError as *const (),
+            "GetProcessHeap" => GetProcessHeap as *const (),
+            "HeapCreate" => HeapCreate as *const (),
+            "HeapDestroy" => HeapDestroy as *const (),
+            "HeapAlloc" => HeapAlloc as *const (),
+            "HeapReAlloc" => HeapReAlloc as *const (),
+            "HeapFree" => HeapFree as *const (),
+            "HeapSize" => HeapSize as *const (),
+            "GlobalAlloc" | "LocalAlloc" => GlobalAlloc as *const (),
+            "GlobalFree" | "LocalFree" => GlobalFree as *const (),
+            "VirtualAlloc" => VirtualAlloc as *const (),
+            "VirtualFree" => VirtualFree as *const (),
+            "VirtualProtect" => VirtualProtect as *const (),
+            "FlushInstructionCache" => FlushInstructionCache as *const (),
+            "GetTickCount" => GetTickCount as *const (),
+            "QueryPerformanceCounter" => QueryPerformanceCounter as *const (),
+            "QueryPerformanceFrequency" => QueryPerformanceFrequency as *const (),
+            "GetSystemTimeAsFileTime" => GetSystemTimeAsFileTime as *const (),
+            "Sleep" => Sleep as *const (),
+            "InitializeCriticalSection" | "EnterCriticalSection" | "LeaveCriticalSection" | "DeleteCriticalSection" => {
+                CriticalSection as *const ()
+            },
+            "InitializeCriticalSectionAndSpinCount" => InitializeCriticalSectionAndSpinCount as *const (),
+            "TryEnterCriticalSection" => TryEnterCriticalSection as *const (),
+            "TlsAlloc" => TlsAlloc as *const (),
+            "FlsAlloc" => FlsAlloc as *const (),
+            "TlsFree" | "FlsFree" => TlsFree as *const (),
+            "TlsGetValue" | "FlsGetValue" => TlsGetValue as *const (),
+            "TlsSetValue" | "FlsSetValue" => TlsSetValue as *const (),
+            "GetCurrentProcess" => GetCurrentProcess as *const (),
+            "GetCurrentProcessId" => GetCurrentProcessId as *const (),
+            "GetCurrentThread" => GetCurrentThread as *const (),
+            "GetCurrentThreadId" => GetCurrentThreadId as *const (),
+            "InterlockedIncrement" => InterlockedIncrement as *const (),
+            "InterlockedDecrement" => InterlockedDecrement as *const (),
+            "InterlockedExchange" => InterlockedExchange as *const (),
+            "InterlockedExchangeAdd" => InterlockedExchangeAdd as *const (),
+            "InterlockedCompareExchange" => InterlockedCompareExchange as *const (),
+            "IsDebuggerPresent" => IsDebuggerPresent as *const (),
+            "IsProcessorFeaturePresent" => IsProcessorFeaturePresent as *const (),
+            "SetUnhandledExceptionFilter" | "UnhandledExceptionFilter" => SetUnhandledExceptionFilter as *const (),
+            "EncodePointer" | "DecodePointer" => EncodePointer as *const (),
+            "GetVersion" => GetVersion as *const (),
+            "GetVersionExA" => GetVersionExA as *const (),
+            "GetSystemInfo" => GetSystemInfo as *const (),
+            "GetStartupInfoA" => GetStartupInfoA as *const (),
+            "GetCommandLineA" => GetCommandLineA as *const (),
+            "GetACP" => GetACP as *const (),
+            "GetOEMCP" => GetOEMCP as *const (),
+            "MultiByteToWideChar" => MultiByteToWideChar as *const (),
+            "WideCharToMultiByte" => WideCharToMultiByte as *const (),
+            "lstrlenA" => lstrlenA as *const (),
+            "lstrcpyA" => lstrcpyA as *const (),
+            "lstrcatA" => lstrcatA as *const (),
+            "lstrcmpA" => lstrcmpA as *const (),
+            "lstrcmpiA" => lstrcmpiA as *const (),
+            "OutputDebugStringA" => OutputDebugStringA as *const (),
+            "GetStdHandle" => GetStdHandle as *const (),
+            "CreateFileA" => CreateFileA as *const (),
+            "ReadFile" => ReadFile as *const (),
+            "WriteFile" => WriteFile as *const (),
+            "SetFilePointer" => SetFilePointer as *const (),
+            "GetFileSize" => GetFileSize as *const (),
+            "CloseHandle" => CloseHandle as *const (),
+            "DeleteFileA" => DeleteFileA as *const (),
+            "GetFileAttributesA" => GetFileAttributesA as *const (),
+            "CreateDirectoryA" => CreateDirectoryA as *const (),
+            "ExitProcess" => ExitProcess as *const (),
+            "TerminateProcess" => TerminateProcess as *const (),
+            "RaiseException" => RaiseException as *const (),
+            _ => return None,
+        })
+    }
+
+    unsafe fn bytes<'a>(s: *const c_char) -> &'a [u8] {
+        CStr::from_ptr(s).to_bytes()
+    }
+
+    unsafe fn wide_string(mut s: *const u16) -> String {
+        let mut units = Vec::new();
+        while *s != 0 {
+            units.push(*s);
+            s = s.add(1);
+        }
+        String::from_utf16_lossy(&units)
+    }
+
+    // modules
+
+    unsafe extern "stdcall" fn GetModuleHandleA(name: *const c_char) -> usize {
+        if name.is_null() {
+            return system_handle(MAIN_MODULE)
+        }
+        module_handle(&String::from_utf8_lossy(bytes(name))).unwrap_or_else(|| {
+            set_last_error(ERROR_MOD_NOT_FOUND);
+            0
+        })
+    }
+
+    unsafe extern "stdcall" fn GetModuleHandleW(name: *const u16) -> usize {
+        if name.is_null() {
+            return system_handle(MAIN_MODULE)
+        }
+        module_handle(&wide_string(name)).unwrap_or_else(|| {
+            set_last_error(ERROR_MOD_NOT_FOUND);
+            0
+        })
+    }
+
+    unsafe extern "stdcall" fn GetModuleFileNameA(module: usize, buf: *mut u8, size: u32) -> u32 {
+        match module_path(module) {
+            Some(path) => copy_c_string(path.to_string_lossy().as_bytes(), buf, size),
+            None => {
+                set_last_error(ERROR_MOD_NOT_FOUND);
+                0
+            },
+        }
+    }
+
+    fn load(name: &str) -> usize {
+        load_library(name, None).unwrap_or_else(|e| {
+            eprintln!("LoadLibrary failed: {}", e);
+            set_last_error(ERROR_MOD_NOT_FOUND);
+            0
+        })
+    }
+
+    unsafe extern "stdcall" fn LoadLibraryA(name: *const c_char) -> usize {
+        load(&String::from_utf8_lossy(bytes(name)))
+    }
+
+    unsafe extern "stdcall" fn LoadLibraryW(name: *const u16) -> usize {
+        load(&wide_string(name))
+    }
+
+    unsafe extern "stdcall" fn LoadLibraryExA(name: *const c_char, _file: usize, _flags: u32) -> usize {
+        LoadLibraryA(name)
+    }
+
+    extern "stdcall" fn FreeLibrary(module: usize) -> BOOL {
+        free_library(module);
+        1
+    }
+
+    unsafe extern "stdcall" fn GetProcAddress(module: usize, name: usize) -> usize {
+        let symbol;
+        let import = if name < 0x10000 {
+            Import::Ordinal(name as u16)
+        } else {
+            symbol = c_string(name);
+            Import::Name(&symbol)
+        };
+        get_proc_address(module, &import).unwrap_or_else(|| {
+            set_last_error(ERROR_PROC_NOT_FOUND);
+            0
+        })
+    }
+
+    extern "stdcall" fn DisableThreadLibraryCalls(_module: usize) -> BOOL {
+        1
+    }
+
+    unsafe extern "stdcall" fn GetLastError() -> u32 {
+        teb_field(TEB_LAST_ERROR).read()
+    }
+
+    extern "stdcall" fn SetLastError(code: u32) {
+        set_last_error(code)
+    }
+
+    // memory
+
+    extern "stdcall" fn GetProcessHeap() -> u32 {
+        PROCESS_HEAP
+    }
+
+    extern "stdcall" fn HeapCreate(_options: u32, _initial: usize, _maximum: usize) -> u32 {
+        PROCESS_HEAP
+    }
+
+    extern "stdcall" fn HeapDestroy(_heap: u32) -> BOOL {
+        1
+    }
+
+    unsafe extern "stdcall" fn HeapAlloc(_heap: u32, flags: u32, size: usize) -> *mut c_void {
+        if flags & HEAP_ZERO_MEMORY != 0 { libc::calloc(1, size.max(1)) } else { libc::malloc(size.max(1)) }
+    }
+
+    unsafe extern "stdcall" fn HeapReAlloc(_heap: u32, _flags: u32, ptr: *mut c_void, size: usize) -> *mut c_void {
+        libc::realloc(ptr, size.max(1))
+    }
+
+    unsafe extern "stdcall" fn HeapFree(_heap: u32, _flags: u32, ptr: *mut c_void) -> BOOL {
+        libc::free(ptr);
+        1
+    }
+
+    unsafe extern "stdcall" fn HeapSize(_heap: u32, _flags: u32, ptr: *mut c_void) -> usize {
+        libc::malloc_usable_size(ptr)
+    }
+
+    unsafe extern "stdcall" fn GlobalAlloc(flags: u32, size: usize) -> *mut c_void {
+        HeapAlloc(PROCESS_HEAP, if flags & GMEM_ZEROINIT != 0 { HEAP_ZERO_MEMORY } else { 0 }, size)
+    }
+
+    unsafe extern "stdcall" fn GlobalFree(ptr: *mut c_void) -> usize {
+        libc::free(ptr);
+        0
+    }
+
+    unsafe extern "stdcall" fn VirtualAlloc(address: usize, size: usize, kind: u32, _protect: u32) -> usize {
+        if address != 0 && kind & MEM_RESERVE == 0 {
+            // committing reserved memory, which was all mapped in the first place
+            return address
+        }
+        let memory = libc::mmap(
+            address as *mut c_void,
+            size,
+            libc::PROT_READ | libc::PROT_WRITE | libc::PROT_EXEC,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if memory == libc::MAP_FAILED {
+            set_last_error(ERROR_NOT_ENOUGH_MEMORY);
+            return 0
+        }
+        STATE.with(|s| s.borrow_mut().allocations.insert(memory as usize, size));
+        memory as usize
+    }
+
+    unsafe extern "stdcall" fn VirtualFree(address: usize, _size: usize, kind: u32) -> BOOL {
+        if kind & MEM_RELEASE != 0 {
+            if let Some(size) = STATE.with(|s| s.borrow_mut().allocations.remove(&address)) {
+                libc::munmap(address as *mut c_void, size);
+            }
+        }
+        1
+    }
+
+    unsafe extern "stdcall" fn VirtualProtect(_address: usize, _size: usize, _protect: u32, old: *mut u32) -> BOOL {
+        if !old.is_null() {
+            old.write(PAGE_EXECUTE_READWRITE);
+        }
+        1
+    }
+
+    extern "stdcall" fn FlushInstructionCache(_process: usize, _address: usize, _size: usize) -> BOOL {
+        1
+    }
+
+    // time
+
+    fn monotonic_nanos() -> u64 {
+        let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut time) };
+        time.tv_sec as u64 * 1_000_000_000 + time.tv_nsec as u64
+    }
+
+    extern "stdcall" fn GetTickCount() -> u32 {
+        (monotonic_nanos() / 1_000_000) as u32
+    }
+
+    unsafe extern "stdcall" fn QueryPerformanceCounter(count: *mut u64) -> BOOL {
+        count.write_unaligned(monotonic_nanos());
+        1
+    }
+
+    unsafe extern "stdcall" fn QueryPerformanceFrequency(frequency: *mut u64) -> BOOL {
+        frequency.write_unaligned(1_000_000_000);
+        1
+    }
+
+    unsafe extern "stdcall" fn GetSystemTimeAsFileTime(time: *mut u64) {
+        // 100ns intervals since 1601
+        let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        time.write_unaligned(since_epoch.as_nanos() as u64 / 100 + 116_444_736_000_000_000);
+    }
+
+    extern "stdcall" fn Sleep(ms: u32) {
+        std::thread::sleep(std::time::Duration::from_millis(ms.into()));
+    }
+
+    // threads
+
+    extern "stdcall" fn CriticalSection(_section: usize) {}
+
+    extern "stdcall" fn InitializeCriticalSectionAndSpinCount(_section: usize, _spin_count: u32) -> BOOL {
+        1
+    }
+
+    extern "stdcall" fn TryEnterCriticalSection(_section: usize) -> BOOL {
+        1
+    }
+
+    extern "stdcall" fn TlsAlloc() -> u32 {
+        STATE.with(|s| {
+            let mut state = s.borrow_mut();
+            if state.next_tls_slot < TLS_SLOTS {
+                state.next_tls_slot += 1;
+                state.next_tls_slot - 1
+            } else {
+                u32::MAX
+            }
+        })
+    }
+
+    extern "stdcall" fn FlsAlloc(_callback: usize) -> u32 {
+        TlsAlloc()
+    }
+
+    extern "stdcall" fn TlsFree(_index: u32) -> BOOL {
+        1
+    }
+
+    unsafe extern "stdcall" fn TlsGetValue(index: u32) -> u32 {
+        if index < TLS_SLOTS {
+            set_last_error(0);
+            teb_field(TEB_TLS_SLOTS + index as usize * 4).read()
+        } else {
+            0
+        }
+    }
+
+    unsafe extern "stdcall" fn TlsSetValue(index: u32, value: u32) -> BOOL {
+        if index < TLS_SLOTS {
+            teb_field(TEB_TLS_SLOTS + index as usize * 4).write(value);
+            1
+        } else {
+            0
+        }
+    }
+
+    extern "stdcall" fn GetCurrentProcess() -> u32 {
+        u32::MAX
+    }
+
+    extern "stdcall" fn GetCurrentProcessId() -> u32 {
+        unsafe { libc::getpid() as u32 }
+    }
+
+    extern "stdcall" fn GetCurrentThread() -> u32 {
+        u32::MAX - 1
+    }
+
+    extern "stdcall" fn GetCurrentThreadId() -> u32 {
+        unsafe { libc::syscall(libc::SYS_gettid) as u32 }
+    }
+
+    unsafe fn atomic<'a>(target: *mut i32) -> &'a AtomicI32 {
+        &*(target as *const AtomicI32)
+    }
+
+    unsafe extern "stdcall" fn InterlockedIncrement(target: *mut i32) -> i32 {
+        atomic(target).fetch_add(1, Ordering::SeqCst).wrapping_add(1)
+    }
+
+    unsafe extern "stdcall" fn InterlockedDecrement(target: *mut i32) -> i32 {
+        atomic(target).fetch_sub(1, Ordering::SeqCst).wrapping_sub(1)
+    }
+
+    unsafe extern "stdcall" fn InterlockedExchange(target: *mut i32, value: i32) -> i32 {
+        atomic(target).swap(value, Ordering::SeqCst)
+    }
+
+    unsafe extern "stdcall" fn InterlockedExchangeAdd(target: *mut i32, value: i32) -> i32 {
+        atomic(target).fetch_add(value, Ordering::SeqCst)
+    }
+
+    unsafe extern "stdcall" fn InterlockedCompareExchange(target: *mut i32, value: i32, comparand: i32) -> i32 {
+        match atomic(target).compare_exchange(comparand, value, Ordering::SeqCst, Ordering::SeqCst) {
+            Ok(old) | Err(old) => old,
+        }
+    }
+
+    // system information
+
+    extern "stdcall" fn IsDebuggerPresent() -> BOOL {
+        0
+    }
+
+    extern "stdcall" fn IsProcessorFeaturePresent(_feature: u32) -> BOOL {
+        0
+    }
+
+    extern "stdcall" fn SetUnhandledExceptionFilter(_filter: usize) -> usize {
+        0
+    }
+
+    extern "stdcall" fn EncodePointer(ptr: usize) -> usize {
+        ptr
+    }
+
+    extern "stdcall" fn GetVersion() -> u32 {
+        // windows xp
+        0x0A28_0105
+    }
+
+    unsafe extern "stdcall" fn GetVersionExA(info: *mut u32) -> BOOL {
+        info.add(1).write(5);
+        info.add(2).write(1);
+        info.add(3).write(2600);
+        info.add(4).write(2); // VER_PLATFORM_WIN32_NT
+        1
+    }
+
+    unsafe extern "stdcall" fn GetSystemInfo(info: *mut u32) {
+        std::ptr::write_bytes(info, 0, 9);
+        info.add(1).write(0x1000); // page size
+        info.add(2).write(0x1_0000);
+        info.add(3).write(0x7FFE_FFFF);
+        info.add(4).write(1);
+        info.add(5).write(1); // processor count
+        info.add(6).write(586);
+        info.add(7).write(0x1_0000); // allocation granularity
+    }
+
+    unsafe extern "stdcall" fn GetStartupInfoA(info: *mut u32) {
+        std::ptr::write_bytes(info, 0, 17);
+        info.write(68);
+    }
+
+    extern "stdcall" fn GetCommandLineA() -> *const u8 {
+        b"game.exe\0".as_ptr()
+    }
+
+    extern "stdcall" fn GetACP() -> u32 {
+        1252
+    }
+
+    extern "stdcall" fn GetOEMCP() -> u32 {
+        437
+    }
+
+    // strings, which are treated as latin-1 for conversions
+
+    unsafe extern "stdcall" fn MultiByteToWideChar(
+        _code_page: u32,
+        _flags: u32,
+        src: *const u8,
+        src_len: i32,
+        dst: *mut u16,
+        dst_len: i32,
+    ) -> i32 {
+        let src = match src_len {
+            len if len < 0 => CStr::from_ptr(src.cast()).to_bytes_with_nul(),
+            len => std::slice::from_raw_parts(src, len as usize),
+        };
+        if dst_len == 0 {
+            return src.len() as i32
+        }
+        if (dst_len as usize) < src.len() {
+            set_last_error(ERROR_INSUFFICIENT_BUFFER);
+            return 0
+        }
+        for (i, &byte) in src.iter().enumerate() {
+            dst.add(i).write(byte.into());
+        }
+        src.len() as i32
+    }
+
+    unsafe extern "stdcall" fn WideCharToMultiByte(
+        _code_page: u32,
+        _flags: u32,
+        src: *const u16,
+        src_len: i32,
+        dst: *mut u8,
+        dst_len: i32,
+        _default_char: *const c_char,
+        _used_default: *mut BOOL,
+    ) -> i32 {
+        let len = match src_len {
+            len if len < 0 => (0..).find(|&i| *src.add(i) == 0).unwrap() + 1,
+            len => len as usize,
+        };
+        if dst_len == 0 {
+            return len as i32
+        }
+        if (dst_len as usize) < len {
+            set_last_error(ERROR_INSUFFICIENT_BUFFER);
+            return 0
+        }
+        for i in 0..len {
+            let unit = *src.add(i);
+            dst.add(i).write(if unit <= 0xFF { unit as u8 } else { b'?' });
+        }
+        len as i32
+    }
+
+    unsafe extern "stdcall" fn lstrlenA(s: *const c_char) -> i32 {
+        if s.is_null() { 0 } else { libc::strlen(s) as i32 }
+    }
+
+    unsafe extern "stdcall" fn lstrcpyA(dst: *mut c_char, src: *const c_char) -> *mut c_char {
+        libc::strcpy(dst, src)
+    }
+
+    unsafe extern "stdcall" fn lstrcatA(dst: *mut c_char, src: *const c_char) -> *mut c_char {
+        libc::strcat(dst, src)
+    }
+
+    unsafe extern "stdcall" fn lstrcmpA(a: *const c_char, b: *const c_char) -> i32 {
+        libc::strcmp(a, b).signum()
+    }
+
+    unsafe extern "stdcall" fn lstrcmpiA(a: *const c_char, b: *const c_char) -> i32 {
+        libc::strcasecmp(a, b).signum()
+    }
+
+    unsafe extern "stdcall" fn OutputDebugStringA(s: *const c_char) {
+        eprint!("{}", String::from_utf8_lossy(bytes(s)));
+    }
+
+    // files
+
+    fn fd(handle: u32) -> Option<i32> {
+        handle.checked_sub(FILE_HANDLE_BASE).map(|fd| fd as i32)
+    }
+
+    extern "stdcall" fn GetStdHandle(kind: u32) -> u32 {
+        match kind as i32 {
+            -10 => FILE_HANDLE_BASE,
+            -11 => FILE_HANDLE_BASE + 1,
+            -12 => FILE_HANDLE_BASE + 2,
+            _ => INVALID_HANDLE_VALUE,
+        }
+    }
+
+    unsafe extern "stdcall" fn CreateFileA(
+        name: *const c_char,
+        access: u32,
+        _share_mode: u32,
+        _security: usize,
+        disposition: u32,
+        _flags: u32,
+        _template: usize,
+    ) -> u32 {
+        let path = match host_path(bytes(name)) {
+            Some(path) => path,
+            None => {
+                set_last_error(ERROR_FILE_NOT_FOUND);
+                return INVALID_HANDLE_VALUE
+            },
+        };
+        let mut flags = match (access & GENERIC_READ != 0, access & GENERIC_WRITE != 0) {
+            (true, true) => libc::O_RDWR,
+            (false, true) => libc::O_WRONLY,
+            _ => libc::O_RDONLY,
+        };
+        flags |= match disposition {
+            CREATE_NEW => libc::O_CREAT | libc::O_EXCL,
+            CREATE_ALWAYS => libc::O_CREAT | libc::O_TRUNC,
+            OPEN_ALWAYS => libc::O_CREAT,
+            TRUNCATE_EXISTING => libc::O_TRUNC,
+            _ => 0,
+        };
+        match libc::open(path.as_ptr(), flags, 0o644) {
+            fd if fd < 0 => {
+                set_last_error(ERROR_FILE_NOT_FOUND);
+                INVALID_HANDLE_VALUE
+            },
+            fd => fd as u32 + FILE_HANDLE_BASE,
+        }
+    }
+
+    unsafe extern "stdcall" fn ReadFile(handle: u32, buf: *mut c_void, len: u32, read: *mut u32, _ov: usize) -> BOOL {
+        let count = fd(handle).map_or(-1, |fd| libc::read(fd, buf, len as usize));
+        if !read.is_null() {
+            read.write(count.max(0) as u32);
+        }
+        (count >= 0).into()
+    }
+
+    unsafe extern "stdcall" fn WriteFile(
+        handle: u32,
+        buf: *const c_void,
+        len: u32,
+        written: *mut u32,
+        _ov: usize,
+    ) -> BOOL {
+        let count = fd(handle).map_or(-1, |fd| libc::write(fd, buf, len as usize));
+        if !written.is_null() {
+            written.write(count.max(0) as u32);
+        }
+        (count >= 0).into()
+    }
+
+    unsafe extern "stdcall" fn SetFilePointer(handle: u32, distance: i32, high: *mut i32, method: u32) -> u32 {
+        let offset = match high.is_null() {
+            true => i64::from(distance),
+            false => i64::from(*high) << 32 | i64::from(distance as u32),
+        };
+        let whence = match method {
+            1 => libc::SEEK_CUR,
+            2 => libc::SEEK_END,
+            _ => libc::SEEK_SET,
+        };
+        match fd(handle).map_or(-1, |fd| libc::lseek64(fd, offset, whence)) {
+            position if position < 0 => {
+                set_last_error(ERROR_INVALID_HANDLE);
+                u32::MAX
+            },
+            position => {
+                if !high.is_null() {
+                    high.write((position >> 32) as i32);
+                }
+                position as u32
+            },
+        }
+    }
+
+    unsafe extern "stdcall" fn GetFileSize(handle: u32, high: *mut u32) -> u32 {
+        let mut stat = std::mem::zeroed::<libc::stat64>();
+        if fd(handle).map_or(-1, |fd| libc::fstat64(fd, &mut stat)) != 0 {
+            set_last_error(ERROR_INVALID_HANDLE);
+            return u32::MAX
+        }
+        if !high.is_null() {
+            high.write((stat.st_size >> 32) as u32);
+        }
+        stat.st_size as u32
+    }
+
+    unsafe extern "stdcall" fn CloseHandle(handle: u32) -> BOOL {
+        match fd(handle) {
+            Some(fd) if fd > 2 => (libc::close(fd) == 0).into(),
+            _ => 1,
+        }
+    }
+
+    unsafe extern "stdcall" fn DeleteFileA(name: *const c_char) -> BOOL {
+        host_path(bytes(name)).map_or(false, |path| libc::unlink(path.as_ptr()) == 0).into()
+    }
+
+    unsafe extern "stdcall" fn GetFileAttributesA(name: *const c_char) -> u32 {
+        match host_path(bytes(name)).and_then(|path| fs::metadata(path.to_str().ok()?).ok()) {
+            Some(metadata) if metadata.is_dir() => FILE_ATTRIBUTE_DIRECTORY,
+            Some(_) => FILE_ATTRIBUTE_NORMAL,
+            None => {
+                set_last_error(ERROR_FILE_NOT_FOUND);
+                INVALID_FILE_ATTRIBUTES
+            },
+        }
+    }
+
+    unsafe extern "stdcall" fn CreateDirectoryA(name: *const c_char, _security: usize) -> BOOL {
+        host_path(bytes(name)).map_or(false, |path| libc::mkdir(path.as_ptr(), 0o755) == 0).into()
+    }
+
+    // process
+
+    extern "stdcall" fn ExitProcess(code: u32) {
+        std::process::exit(code as i32)
+    }
+
+    extern "stdcall" fn TerminateProcess(_process: u32, code: u32) -> BOOL {
+        std::process::exit(code as i32)
+    }
+
+    extern "stdcall" fn RaiseException(code: u32, _flags: u32, _arg_count: u32, _args: usize) {
+        // there's nothing to dispatch it to, so this is as far as it goes
+        eprintln!("a dll raised exception {:#010X}, which can't be handled", code);
+        std::process::abort()
+    }
+}
+
+#[allow(non_snake_case)]
+mod user32 {
+    use super::*;
+
+    pub fn lookup(name: &str) -> Option<*const ()> {
+        Some(match name {
+            "MessageBoxA" => MessageBoxA as *const (),
+            "GetActiveWindow" | "GetForegroundWindow" | "GetDesktopWindow" | "GetFocus" => GetActiveWindow as *const (),
+            "GetSystemMetrics" | "GetAsyncKeyState" | "GetKeyState" => GetSystemMetrics as *const (),
+            "wsprintfA" => msvcrt::lookup("sprintf")?,
+            _ => return None,
+        })
+    }
+
+    unsafe extern "stdcall" fn MessageBoxA(
+        _window: usize,
+        text: *const c_char,
+        caption: *const c_char,
+        _kind: u32,
+    ) -> i32 {
+        let text = if text.is_null() { String::new() } else { c_string(text as usize) };
+        let caption = if caption.is_null() { String::new() } else { c_string(caption as usize) };
+        eprintln!("[{}] {}", caption, text);
+        1 // IDOK
+    }
+
+    extern "stdcall" fn GetActiveWindow() -> usize {
+        0
+    }
+
+    extern "stdcall" fn GetSystemMetrics(_index: i32) -> i32 {
+        0
+    }
+}
+
+#[allow(non_snake_case)]
+mod msvcrt {
+    use super::*;
+
+    // These are passed straight through to glibc, as cdecl is the same on both sides for what they take.
+    // Only their addresses are needed, so the signatures don't matter.
+    extern "C" {
+        fn malloc();
+        fn calloc();
+        fn realloc();
+        fn free();
+        fn memcpy();
+        fn memmove();
+        fn memset();
+        fn memcmp();
+        fn memchr();
+        fn strlen();
+        fn strcpy();
+        fn strncpy();
+        fn strcat();
+        fn strncat();
+        fn strcmp();
+        fn strncmp();
+        fn strcasecmp();
+        fn strncasecmp();
+        fn strchr();
+        fn strrchr();
+        fn strstr();
+        fn strtok();
+        fn strdup();
+        fn sprintf();
+        fn snprintf();
+        fn vsprintf();
+        fn vsnprintf();
+        fn sscanf();
+        fn printf();
+        fn puts();
+        fn fopen();
+        fn fclose();
+        fn fread();
+        fn fwrite();
+        fn fseek();
+        fn ftell();
+        fn fflush();
+        fn fgets();
+        fn fputs();
+        fn fgetc();
+        fn fputc();
+        fn feof();
+        fn fprintf();
+        fn remove();
+        fn rename();
+        fn strtol();
+        fn strtoul();
+        fn strtod();
+        fn atoi();
+        fn atol();
+        fn atof();
+        fn toupper();
+        fn tolower();
+        fn isalpha();
+        fn isdigit();
+        fn isalnum();
+        fn isspace();
+        fn isupper();
+        fn islower();
+        fn abs();
+        fn labs();
+        fn rand();
+        fn srand();
+        fn qsort();
+        fn getenv();
+        fn time();
+        fn exit();
+        fn abort();
+        fn sin();
+        fn cos();
+        fn tan();
+        fn asin();
+        fn acos();
+        fn atan();
+        fn atan2();
+        fn sinh();
+        fn cosh();
+        fn tanh();
+        fn sqrt();
+        fn pow();
+        fn exp();
+        fn log();
+        fn log10();
+        fn floor();
+        fn ceil();
+        fn fmod();
+        fn fabs();
+        fn __errno_location();
+        fn opengmk_ftol();
+    }
+
+    // msvc's float to integer conversion, which takes its argument in st(0) and returns in edx:eax
+    global_asm!(
+        ".globl opengmk_ftol",
+        "opengmk_ftol:",
+        "sub esp, 12",
+        "fnstcw word ptr [esp + 8]",
+        "mov ax, word ptr [esp + 8]",
+        "or ax, 0x0C00",
+        "mov word ptr [esp + 10], ax",
+        "fldcw word ptr [esp + 10]",
+        "fistp qword ptr [esp]",
+        "fldcw word ptr [esp + 8]",
+        "mov eax, dword ptr [esp]",
+        "mov edx, dword ptr [esp + 4]",
+        "add esp, 12",
+        "ret",
+    );
+
+    static ADJUST_FDIV: i32 = 0;
+
+    pub fn variable(name: &str) -> Option<*const ()> {
+        match name {
+            "_adjust_fdiv" => Some(&ADJUST_FDIV as *const i32 as *const ()),
+            _ => None,
+        }
+    }
+
+    pub fn lookup(name: &str) -> Option<*const ()> {
+        Some(match name {
+            "malloc" | "_malloc_crt" | "??2@YAPAXI@Z" | "??_U@YAPAXI@Z" => malloc as *const (),
+            "calloc" | "_calloc_crt" => calloc as *const (),
+            "realloc" | "_realloc_crt" => realloc as *const (),
+            "free" | "??3@YAXPAX@Z" | "??_V@YAXPAX@Z" => free as *const (),
+            "memcpy" => memcpy as *const (),
+            "memmove" => memmove as *const (),
+            "memset" => memset as *const (),
+            "memcmp" => memcmp as *const (),
+            "memchr" => memchr as *const (),
+            "strlen" => strlen as *const (),
+            "strcpy" => strcpy as *const (),
+            "strncpy" => strncpy as *const (),
+            "strcat" => strcat as *const (),
+            "strncat" => strncat as *const (),
+            "strcmp" => strcmp as *const (),
+            "strncmp" => strncmp as *const (),
+            "_stricmp" | "_strcmpi" | "stricmp" => strcasecmp as *const (),
+            "_strnicmp" | "strnicmp" => strncasecmp as *const (),
+            "strchr" => strchr as *const (),
+            "strrchr" => strrchr as *const (),
+            "strstr" => strstr as *const (),
+            "strtok" => strtok as *const (),
+            "_strdup" | "strdup" => strdup as *const (),
+            "sprintf" => sprintf as *const (),
+            "_snprintf" | "snprintf" => snprintf as *const (),
+            "vsprintf" => vsprintf as *const (),
+            "_vsnprintf" | "vsnprintf" => vsnprintf as *const (),
+            "sscanf" => sscanf as *const (),
+            "printf" => printf as *const (),
+            "puts" => puts as *const (),
+            "fopen" => fopen as *const (),
+            "fclose" => fclose as *const (),
+            "fread" => fread as *const (),
+            "fwrite" => fwrite as *const (),
+            "fseek" => fseek as *const (),
+            "ftell" => ftell as *const (),
+            "fflush" => fflush as *const (),
+            "fgets" => fgets as *const (),
+            "fputs" => fputs as *const (),
+            "fgetc" | "getc" => fgetc as *const (),
+            "fputc" | "putc" => fputc as *const (),
+            "feof" => feof as *const (),
+            "fprintf" => fprintf as *const (),
+            "remove" => remove as *const (),
+            "rename" => rename as *const (),
+            "strtol" => strtol as *const (),
+            "strtoul" => strtoul as *const (),
+            "strtod" => strtod as *const (),
+            "atoi" => atoi as *const (),
+            "atol" => atol as *const (),
+            "atof" => atof as *const (),
+            "toupper" => toupper as *const (),
+            "tolower" => tolower as *const (),
+            "isalpha" => isalpha as *const (),
+            "isdigit" => isdigit as *const (),
+            "isalnum" => isalnum as *const (),
+            "isspace" => isspace as *const (),
+            "isupper" => isupper as *const (),
+            "islower" => islower as *const (),
+            "abs" => abs as *const (),
+            "labs" => labs as *const (),
+            "rand" => rand as *const (),
+            "srand" => srand as *const (),
+            "qsort" => qsort as *const (),
+            "getenv" => getenv as *const (),
+            "time" | "_time32" => time as *const (),
+            "exit" | "_exit" => exit as *const (),
+            "abort" => abort as *const (),
+            "sin" => sin as *const (),
+            "cos" => cos as *const (),
+            "tan" => tan as *const (),
+            "asin" => asin as *const (),
+            "acos" => acos as *const (),
+            "atan" => atan as *const (),
+            "atan2" => atan2 as *const (),
+            "sinh" => sinh as *const (),
+            "cosh" => cosh as *const (),
+            "tanh" => tanh as *const (),
+            "sqrt" => sqrt as *const (),
+            "pow" => pow as *const (),
+            "exp" => exp as *const (),
+            "log" => log as *const (),
+            "log10" => log10 as *const (),
+            "floor" => floor as *const (),
+            "ceil" => ceil as *const (),
+            "fmod" => fmod as *const (),
+            "fabs" => fabs as *const (),
+            "_errno" => __errno_location as *const (),
+            "_ftol" | "_ftol2" | "_ftol2_sse" => opengmk_ftol as *const (),
+            "_itoa" | "_ltoa" => _itoa as *const (),
+            "_initterm" => _initterm as *const (),
+            "_initterm_e" => _initterm_e as *const (),
+            "_onexit" | "__dllonexit" => _onexit as *const (),
+            "atexit" => atexit as *const (),
+            "_lock" | "_unlock" => _lock as *const (),
+            "_amsg_exit" => _amsg_exit as *const (),
+            "_purecall" => _purecall as *const (),
+            "_CxxThrowException" => _CxxThrowException as *const (),
+            "_except_handler3" | "_except_handler4_common" | "__CxxFrameHandler3" => _except_handler3 as *const (),
+            "_XcptFilter" | "__CppXcptFilter" => _XcptFilter as *const (),
+            _ => return None,
+        })
+    }
+
+    unsafe extern "C" fn _itoa(value: i32, buf: *mut u8, radix: i32) -> *mut u8 {
+        let radix = radix.clamp(2, 36) as u32;
+        let mut digits = Vec::new();
+        let mut n = if radix == 10 { value.unsigned_abs() } else { value as u32 };
+        loop {
+            digits.push(std::char::from_digit(n % radix, radix).unwrap() as u8);
+            n /= radix;
+            if n == 0 {
+                break
+            }
+        }
+        if radix == 10 && value < 0 {
+            digits.push(b'-');
+        }
+        digits.reverse();
+        digits.push(0);
+        std::ptr::copy_nonoverlapping(digits.as_ptr(), buf, digits.len());
+        buf
+    }
+
+    // the crt's static constructors
+    unsafe extern "C" fn _initterm(start: *const usize, end: *const usize) {
+        let mut function = start;
+        while function < end {
+            if *function != 0 {
+                call_int(*function, &[]);
+            }
+            function = function.add(1);
+        }
+    }
+
+    unsafe extern "C" fn _initterm_e(start: *const usize, end: *const usize) -> i32 {
+        let mut function = start;
+        while function < end {
+            if *function != 0 {
+                match call_int(*function, &[]) {
+                    0 => (),
+                    error => return error as i32,
+                }
+            }
+            function = function.add(1);
+        }
+        0
+    }
+
+    // exit handlers never run, since dlls are only unloaded when the emulator's done with them anyway
+    extern "C" fn _onexit(function: usize) -> usize {
+        function
+    }
+
+    extern "C" fn atexit(_function: usize) -> i32 {
+        0
+    }
+
+    extern "C" fn _lock(_lock: i32) {}
+
+    extern "C" fn _amsg_exit(code: i32) {
+        eprintln!("a dll's C runtime hit error {}", code);
+        std::process::exit(255)
+    }
+
+    extern "C" fn _purecall() {
+        eprintln!("a dll called a pure virtual function");
+        std::process::abort()
+    }
+
+    extern "C" fn _CxxThrowException(_object: usize, _info: usize) {
+        eprintln!("a dll threw a C++ exception, which can't be handled");
+        std::process::abort()
+    }
+
+    extern "C" fn _except_handler3() -> i32 {
+        1 // ExceptionContinueSearch
+    }
+
+    extern "C" fn _XcptFilter() -> i32 {
+        0 // EXCEPTION_CONTINUE_SEARCH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE_BASE: u32 = 0x10000000;
+
+    fn put(image: &mut [u8], offset: usize, bytes: &[u8]) {
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn put_u32(image: &mut [u8], offset: usize, value: u32) {
+        put(image, offset, &value.to_le_bytes());
+    }
+
+    /// Builds a small DLL with one section, which exports `get_data` (returns 0x12345678 through an absolute
+    /// address), `string_length` (calls the import on its argument) and `forwarded` (forwarded to lstrlenA).
+    fn fixture(import: &str, relocations: bool) -> Vec<u8> {
+        let mut image = vec![0u8; 0x2000];
+
+        // DOS header, PE signature and file header
+        put(&mut image, 0, b"MZ");
+        put_u32(&mut image, 0x3C, 0x40);
+        put(&mut image, 0x40, b"PE\0\0");
+        put(&mut image, 0x44, &IMAGE_FILE_MACHINE_I386.to_le_bytes());
+        put(&mut image, 0x46, &1u16.to_le_bytes()); // sections
+        put(&mut image, 0x54, &0xE0u16.to_le_bytes()); // optional header size
+        put(&mut image, 0x56, &0x2102u16.to_le_bytes()); // executable, 32-bit, dll
+
+        // optional header, with no entry point
+        let opt = 0x58;
+        put(&mut image, opt, &IMAGE_NT_OPTIONAL_HDR32_MAGIC.to_le_bytes());
+        put_u32(&mut image, opt + 28, IMAGE_BASE);
+        put_u32(&mut image, opt + 32, 0x1000); // section alignment
+        put_u32(&mut image, opt + 36, 0x1000); // file alignment
+        put_u32(&mut image, opt + 56, 0x2000); // image size
+        put_u32(&mut image, opt + 60, 0x1000); // headers size
+        put(&mut image, opt + 68, &2u16.to_le_bytes()); // GUI subsystem
+        put_u32(&mut image, opt + 92, 16);
+        let directory = |index: usize| opt + 96 + index * 8;
+        put_u32(&mut image, directory(IMAGE_DIRECTORY_ENTRY_EXPORT), 0x1300);
+        put_u32(&mut image, directory(IMAGE_DIRECTORY_ENTRY_EXPORT) + 4, 0x100);
+        put_u32(&mut image, directory(IMAGE_DIRECTORY_ENTRY_IMPORT), 0x1200);
+        put_u32(&mut image, directory(IMAGE_DIRECTORY_ENTRY_IMPORT) + 4, 40);
+        if relocations {
+            put_u32(&mut image, directory(IMAGE_DIRECTORY_ENTRY_BASERELOC), 0x1400);
+            put_u32(&mut image, directory(IMAGE_DIRECTORY_ENTRY_BASERELOC) + 4, 12);
+        }
+
+        // the one section, with its file offset the same as its address
+        let section = opt + 0xE0;
+        put(&mut image, section, b".text\0\0\0");
+        put_u32(&mut image, section + 8, 0x1000);
+        put_u32(&mut image, section + 12, 0x1000);
+        put_u32(&mut image, section + 16, 0x1000);
+        put_u32(&mut image, section + 20, 0x1000);
+        put_u32(&mut image, section + 36, 0xE0000020); // code, readable, writable, executable
+
+        // get_data: mov eax, [data]; ret
+        put(&mut image, 0x1000, &[0xA1]);
+        put_u32(&mut image, 0x1001, IMAGE_BASE + 0x1100);
+        put(&mut image, 0x1005, &[0xC3]);
+        // string_length: push dword [esp + 4]; call [iat]; ret
+        put(&mut image, 0x1010, &[0xFF, 0x74, 0x24, 0x04, 0xFF, 0x15]);
+        put_u32(&mut image, 0x1016, IMAGE_BASE + 0x12A0);
+        put(&mut image, 0x101A, &[0xC3]);
+        put_u32(&mut image, 0x1100, 0x12345678);
+
+        // imports: one descriptor then a null one, with the lookup table, address table and names after
+        put_u32(&mut image, 0x1200, 0x1280);
+        put_u32(&mut image, 0x120C, 0x12C0);
+        put_u32(&mut image, 0x1210, 0x12A0);
+        put_u32(&mut image, 0x1280, 0x12E0);
+        put_u32(&mut image, 0x12A0, 0x12E0);
+        put(&mut image, 0x12C0, b"kernel32.dll\0");
+        put(&mut image, 0x12E2, import.as_bytes());
+
+        // exports, with names sorted and the forwarder string inside the export directory
+        put_u32(&mut image, 0x130C, 0x1380);
+        put_u32(&mut image, 0x1310, 1); // ordinal base
+        put_u32(&mut image, 0x1314, 3);
+        put_u32(&mut image, 0x1318, 3);
+        put_u32(&mut image, 0x131C, 0x1340);
+        put_u32(&mut image, 0x1320, 0x1350);
+        put_u32(&mut image, 0x1324, 0x1360);
+        for (i, function) in [0x1000, 0x1010, 0x13A0].into_iter().enumerate() {
+            put_u32(&mut image, 0x1340 + i * 4, function);
+        }
+        for (i, (name, ordinal)) in [(0x13C0, 2u16), (0x13D0, 0), (0x13E0, 1)].into_iter().enumerate() {
+            put_u32(&mut image, 0x1350 + i * 4, name);
+            put(&mut image, 0x1360 + i * 2, &ordinal.to_le_bytes());
+        }
+        put(&mut image, 0x1380, b"fixture.dll\0");
+        put(&mut image, 0x13A0, b"KERNEL32.lstrlenA\0");
+        put(&mut image, 0x13C0, b"forwarded\0");
+        put(&mut image, 0x13D0, b"get_data\0");
+        put(&mut image, 0x13E0, b"string_length\0");
+
+        // one block of relocations for the two absolute addresses
+        put_u32(&mut image, 0x1400, 0x1000);
+        put_u32(&mut image, 0x1404, 12);
+        put(&mut image, 0x1408, &(IMAGE_REL_BASED_HIGHLOW << 12 | 0x001).to_le_bytes());
+        put(&mut image, 0x140A, &(IMAGE_REL_BASED_HIGHLOW << 12 | 0x016).to_le_bytes());
+
+        image
+    }
+
+    /// Writes a fixture to its own directory, so tests running at the same time don't share modules.
+    fn write_fixture(test: &str, name: &str, data: &[u8]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("gm8emulator-pe-{}-{}", std::process::id(), test));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn export(module: usize, name: &str) -> usize {
+        get_proc_address(module, &Import::Name(name)).unwrap()
+    }
+
+    #[test]
+    fn relocations() {
+        let data = fixture("lstrlenA", true);
+        let first = load_library(write_fixture("relocations", "first.dll", &data).to_str().unwrap(), None).unwrap();
+        let second = load_library(write_fixture("relocations", "second.dll", &data).to_str().unwrap(), None).unwrap();
+        // both can't be at the preferred address, so at least one of them was relocated
+        assert_ne!(first, second);
+        for module in [first, second] {
+            // the absolute address in get_data points into wherever the module ended up
+            assert_eq!(unsafe { read::<u32>(module + 0x1001) }, module as u32 + 0x1100);
+            assert_eq!(unsafe { call_int(export(module, "get_data"), &[]) }, 0x12345678);
+        }
+        free_library(first);
+        free_library(second);
+    }
+
+    #[repr(align(16))]
+    struct Aligned(u32);
+
+    #[inline(never)]
+    fn stack_aligned() -> bool {
+        let local = Aligned(0);
+        &local as *const Aligned as usize % 16 == 0
+    }
+
+    extern "stdcall" fn subtract(a: u32, b: u32) -> u32 {
+        if stack_aligned() { a.wrapping_sub(b) } else { u32::MAX }
+    }
+
+    extern "C" fn add(a: u32, b: u32, c: u32) -> u32 {
+        if stack_aligned() { a + b + c } else { u32::MAX }
+    }
+
+    /// Calls a function with the stack pointer `offset` bytes below a 16-byte boundary before the arguments are
+    /// pushed, returning the result and how many bytes of arguments the function popped.
+    unsafe fn call_misaligned(function: usize, words: &[u32], offset: usize) -> (u32, usize) {
+        let (result, after, saved): (usize, usize, usize);
+        asm!(
+            "mov edi, esp",
+            "and esp, -16",
+            "sub esp, {offset}",
+            "2:",
+            "test ecx, ecx",
+            "jz 3f",
+            "push dword ptr [edx + 4*ecx - 4]",
+            "dec ecx",
+            "jmp 2b",
+            "3:",
+            "call eax",
+            "mov ecx, esp",
+            "mov esp, edi",
+            offset = in(reg) offset,
+            inout("eax") function => result,
+            inout("ecx") words.len() => after,
+            inout("edx") words.as_ptr() as usize => _,
+            out("edi") saved,
+            clobber_abi("C"),
+        );
+        (result as u32, after - ((saved & !15) - offset - words.len() * 4))
+    }
+
+    #[test]
+    fn realigned_calls() {
+        let subtract = realign(subtract as *const ()).unwrap();
+        let add = realign(add as *const ()).unwrap();
+        // stubs get reused
+        assert_eq!(realign(self::subtract as *const ()), Some(subtract));
+        for offset in [0, 4, 8, 12] {
+            assert_eq!(unsafe { call_misaligned(subtract, &[10, 3], offset) }, (7, 8));
+            assert_eq!(unsafe { call_misaligned(add, &[1, 2, 3], offset) }, (6, 0));
+        }
+    }
+
+    #[test]
+    fn missing_relocations() {
+        let data = fixture("lstrlenA", false);
+        let first = load_library(write_fixture("norelocs", "first.dll", &data).to_str().unwrap(), None);
+        let second = load_library(write_fixture("norelocs", "second.dll", &data).to_str().unwrap(), None);
+        // other tests may be using the preferred address, but if the first copy got it then the second can't
+        match first {
+            Ok(first) => {
+                assert_eq!(first, IMAGE_BASE as usize);
+                assert!(second.unwrap_err().contains("has no relocations"));
+                free_library(first);
+            },
+            Err(e) => {
+                assert!(e.contains("has no relocations"), "{}", e);
+                second.into_iter().for_each(free_library);
+            },
+        }
+    }
+
+    #[test]
+    fn imports() {
+        let path = write_fixture("imports", "fixture.dll", &fixture("lstrlenA", true));
+        let module = load_library(path.to_str().unwrap(), None).unwrap();
+        let text = CString::new("hello").unwrap();
+        assert_eq!(unsafe { call_int(export(module, "string_length"), &[text.as_ptr() as u32]) }, 5);
+        free_library(module);
+    }
+
+    #[test]
+    fn unsupported_imports() {
+        let path = write_fixture("unsupported", "fixture.dll", &fixture("NotARealFunction", true));
+        let error = load_library(path.to_str().unwrap(), None).unwrap_err();
+        assert!(error.contains("kernel32.dll!NotARealFunction"), "{}", error);
+        // nothing is left loaded after a failure
+        assert!(module_handle("fixture.dll").is_none());
+    }
+
+    #[test]
+    fn exports() {
+        let path = write_fixture("exports", "fixture.dll", &fixture("lstrlenA", true));
+        let module = load_library(path.to_str().unwrap(), None).unwrap();
+        // loading it again only adds a reference, and the name is found without the extension or case
+        assert_eq!(load_library(path.to_str().unwrap(), None).unwrap(), module);
+        assert_eq!(module_handle("FIXTURE"), Some(module));
+        assert_eq!(export(module, "get_data"), module + 0x1000);
+        assert_eq!(get_proc_address(module, &Import::Ordinal(2)), Some(module + 0x1010));
+        let kernel32 = system_handle(System::Kernel32 as usize);
+        assert_eq!(export(module, "forwarded"), get_proc_address(kernel32, &Import::Name("lstrlenA")).unwrap());
+        assert!(get_proc_address(module, &Import::Name("missing")).is_none());
+        assert!(get_proc_address(module, &Import::Ordinal(4)).is_none());
+        free_library(module);
+        assert_eq!(module_handle("fixture.dll"), Some(module));
+        free_library(module);
+        assert!(module_handle("fixture.dll").is_none());
+    }
+}
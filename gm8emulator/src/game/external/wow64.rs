@@ -1,4 +1,4 @@
-#![cfg(not(all(target_os = "windows", target_arch = "x86")))]
+#![cfg(not(all(any(target_os = "windows", target_os = "linux"), target_arch = "x86")))]
 
 use super::dll;
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
@@ -11,6 +11,8 @@ use std::{
 };
 
 const PROCESS_DEFAULT_NAME: &str = "gm8emulator-wow64.exe";
+// the server built for i686 linux, which loads dlls itself rather than needing wine
+const PROCESS_LINUX_NAME: &str = "gm8emulator-wow64";
 const PROCESS_ENV_OVERRIDE: &str = "OPENGMK_WOW64_BINARY";
 
 pub struct IpcExternal(i32);
@@ -57,13 +59,20 @@ impl ChildProcess {
             Some(name) => name,
             None => PROCESS_DEFAULT_NAME.into(),
         });
+        if cfg!(target_os = "linux") && env::var_os(PROCESS_ENV_OVERRIDE).is_none() {
+            let linux_path = process_path.with_file_name(PROCESS_LINUX_NAME);
+            if linux_path.exists() {
+                process_path = linux_path;
+            }
+        }
         if !process_path.exists() {
             return Err(format!(
                 "{} was not found, please extract it into your gm8emulator directory",
                 PROCESS_DEFAULT_NAME
             ))
         }
-        let mut child = if cfg!(windows) {
+        let is_exe = process_path.extension().map_or(false, |ext| ext.eq_ignore_ascii_case("exe"));
+        let mut child = if cfg!(windows) || !is_exe {
             process::Command::new(process_path)
                 .arg(dll::PROTOCOL_VERSION.to_string())
                 .stdin(process::Stdio::piped())
@@ -4,6 +4,7 @@ use crate::{
     math::Real,
 };
 use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
 
 #[derive(Clone, Copy, Serialize, Deserialize)]
 pub struct PotentialStepSettings {
@@ -59,9 +60,139 @@ impl MpGrid {
         self.mpgrid[x][y]
     }
 
-    // This will panic on OOB, so make sure you check bounds before calling
-    pub fn set(&mut self, x: usize, y: usize, val: i32) {
-        self.mpgrid[x][y] = val;
+    /// Sets a cell, doing nothing if it's outside the grid.
+    pub fn set_cell(&mut self, x: i32, y: i32, val: i32) {
+        if let Some(cell) = self.mpgrid.get_mut(x as usize).and_then(|col| col.get_mut(y as usize)) {
+            *cell = val;
+        }
+    }
+
+    /// Sets every cell.
+    pub fn set_all(&mut self, val: i32) {
+        self.mpgrid.iter_mut().flatten().for_each(|cell| *cell = val);
+    }
+
+    /// Sets every cell overlapping a rectangle, given by two opposite corners in room coordinates.
+    pub fn set_rectangle(&mut self, x1: i32, y1: i32, x2: i32, y2: i32, val: i32) {
+        if let Some((xs, ys)) = self.cells_in(x1, y1, x2, y2) {
+            for x in xs {
+                self.mpgrid[x][ys.clone()].iter_mut().for_each(|cell| *cell = val);
+            }
+        }
+    }
+
+    /// Marks the cells overlapping an instance's bounding box as forbidden, if `collides` says the instance is
+    /// really in them. It's given each cell's left, top, right and bottom in room coordinates, inclusive.
+    pub fn add_instance(&mut self, bbox: (i32, i32, i32, i32), mut collides: impl FnMut(i32, i32, i32, i32) -> bool) {
+        let (left, top, right, bottom) = bbox;
+        if let Some((xs, ys)) = self.cells_in(left, top, right, bottom) {
+            for x in xs {
+                for y in ys.clone() {
+                    let x1 = self.left + x as i32 * self.cellwidth;
+                    let y1 = self.top + y as i32 * self.cellheight;
+                    if collides(x1, y1, x1 + self.cellwidth - 1, y1 + self.cellheight - 1) {
+                        self.mpgrid[x][y] = -1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Gets the columns and rows of the cells overlapping a rectangle in room coordinates, if any are.
+    fn cells_in(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> Option<(RangeInclusive<usize>, RangeInclusive<usize>)> {
+        fn span(a: i32, b: i32, start: i32, size: i32, count: usize) -> Option<RangeInclusive<usize>> {
+            if size <= 0 || count == 0 {
+                return None
+            }
+            let first = (a.min(b) - start).div_euclid(size).max(0);
+            let last = (a.max(b) - start).div_euclid(size).min(count as i32 - 1);
+            if first <= last { Some(first as usize..=last as usize) } else { None }
+        }
+        Some((
+            span(x1, x2, self.left, self.cellwidth, self.hcells)?,
+            span(y1, y2, self.top, self.cellheight, self.vcells)?,
+        ))
+    }
+
+    /// Gets the cell containing a room position, if it's inside the grid.
+    pub fn cell_at(&self, x: Real, y: Real) -> Option<(usize, usize)> {
+        let cx = ((x - self.left.into()) / self.cellwidth.into()).floor();
+        let cy = ((y - self.top.into()) / self.cellheight.into()).floor();
+        if cx < 0.into() || cy < 0.into() {
+            return None
+        }
+        let (cx, cy) = (cx.to_i32() as usize, cy.to_i32() as usize);
+        if cx < self.hcells && cy < self.vcells { Some((cx, cy)) } else { None }
+    }
+
+    /// Gets the room position of the centre of a cell.
+    pub fn cell_centre(&self, x: usize, y: usize) -> (Real, Real) {
+        (
+            Real::from(self.left + x as i32 * self.cellwidth) + Real::from(self.cellwidth) / 2.into(),
+            Real::from(self.top + y as i32 * self.cellheight) + Real::from(self.cellheight) / 2.into(),
+        )
+    }
+
+    fn is_free(&self, x: isize, y: isize) -> bool {
+        x >= 0
+            && y >= 0
+            && (x as usize) < self.hcells
+            && (y as usize) < self.vcells
+            && self.mpgrid[x as usize][y as usize] >= 0
+    }
+
+    /// Lists the cells which can be moved into from the given one, in the order GM8 checks them.
+    /// Diagonal moves can't cut corners, so both cells next to the diagonal have to be free.
+    fn neighbours(&self, x: usize, y: usize, allow_diag: bool) -> impl Iterator<Item = (usize, usize)> + '_ {
+        const ORTHOGONAL: [(isize, isize); 4] = [(-1, 0), (1, 0), (0, -1), (0, 1)];
+        const DIAGONAL: [(isize, isize); 4] = [(-1, -1), (1, -1), (-1, 1), (1, 1)];
+        let (x, y) = (x as isize, y as isize);
+        let diagonals = if allow_diag { &DIAGONAL[..] } else { &[] };
+        ORTHOGONAL
+            .iter()
+            .filter(move |(dx, dy)| self.is_free(x + dx, y + dy))
+            .chain(diagonals.iter().filter(move |(dx, dy)| {
+                self.is_free(x + dx, y + dy) && self.is_free(x + dx, y) && self.is_free(x, y + dy)
+            }))
+            .map(move |(dx, dy)| ((x + dx) as usize, (y + dy) as usize))
+    }
+
+    /// Finds a shortest route of cells from start to goal, both inclusive.
+    /// This floods outwards from the start one step at a time, so every move (diagonal or not) costs the same,
+    /// and then walks back from the goal taking the first neighbour one step closer.
+    pub fn find_path(&self, start: (usize, usize), goal: (usize, usize), allow_diag: bool) -> Option<Vec<(usize, usize)>> {
+        if self.mpgrid[start.0][start.1] < 0 || self.mpgrid[goal.0][goal.1] < 0 {
+            return None
+        }
+        let mut distance = vec![vec![u32::MAX; self.vcells]; self.hcells];
+        let mut queue = std::collections::VecDeque::new();
+        distance[start.0][start.1] = 0;
+        queue.push_back(start);
+        while let Some((x, y)) = queue.pop_front() {
+            if (x, y) == goal {
+                break
+            }
+            let next = distance[x][y] + 1;
+            for (nx, ny) in self.neighbours(x, y, allow_diag) {
+                if distance[nx][ny] == u32::MAX {
+                    distance[nx][ny] = next;
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+        if distance[goal.0][goal.1] == u32::MAX {
+            return None
+        }
+
+        let mut cells = vec![goal];
+        let mut current = goal;
+        while current != start {
+            let wanted = distance[current.0][current.1] - 1;
+            current = self.neighbours(current.0, current.1, allow_diag).find(|&(x, y)| distance[x][y] == wanted)?;
+            cells.push(current);
+        }
+        cells.reverse();
+        Some(cells)
     }
 }
 
@@ -194,3 +325,110 @@ pub fn make_path(inst: &Instance, path: &mut Path, func: impl Fn(&Instance) -> P
     inst.bbox_is_stale.set(true);
     result == PathGenResult::Done
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Makes a grid of 16x16 cells at (10, 20) from rows of text, where '#' is a forbidden cell.
+    fn grid(rows: &[&str]) -> MpGrid {
+        let mut grid = MpGrid::new(10, 20, rows[0].len(), rows.len(), 16, 16);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                if c == '#' {
+                    grid.set_cell(x as i32, y as i32, -1);
+                }
+            }
+        }
+        grid
+    }
+
+    fn rows(grid: &MpGrid) -> Vec<String> {
+        (0..grid.vcells)
+            .map(|y| (0..grid.hcells).map(|x| if grid.get(x, y) < 0 { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn blocked_start_or_goal() {
+        let grid = grid(&["#..", "...", "..#"]);
+        assert_eq!(grid.find_path((0, 0), (1, 1), true), None);
+        assert_eq!(grid.find_path((1, 1), (2, 2), true), None);
+        assert_eq!(grid.find_path((1, 1), (1, 1), false), Some(vec![(1, 1)]));
+    }
+
+    #[test]
+    fn unreachable_goal() {
+        let grid = self::grid(&["..#..", "..#..", "..#.."]);
+        assert_eq!(grid.find_path((0, 0), (4, 2), true), None);
+        // diagonal moves can't squeeze between two forbidden cells
+        let grid = self::grid(&[".#", "#."]);
+        assert_eq!(grid.find_path((0, 0), (1, 1), true), None);
+    }
+
+    #[test]
+    fn diagonal_moves() {
+        let grid = self::grid(&["...", "...", "..."]);
+        assert_eq!(grid.find_path((0, 0), (2, 2), true), Some(vec![(0, 0), (1, 1), (2, 2)]));
+        assert_eq!(grid.find_path((0, 0), (2, 2), false), Some(vec![(0, 0), (0, 1), (0, 2), (1, 2), (2, 2)]));
+        // going around a corner has to take the orthogonal step first
+        let grid = self::grid(&["...", ".#.", "..."]);
+        assert_eq!(grid.find_path((0, 0), (2, 2), true), Some(vec![(0, 0), (0, 1), (0, 2), (1, 2), (2, 2)]));
+    }
+
+    #[test]
+    fn edge_cells() {
+        let grid = self::grid(&["...", "##.", "..."]);
+        // (1, 2) to (2, 1) would cut the corner of (1, 1), so the path goes right round the edge
+        let path = vec![(0, 2), (1, 2), (2, 2), (2, 1), (2, 0), (1, 0), (0, 0)];
+        assert_eq!(grid.find_path((0, 2), (0, 0), true), Some(path));
+        assert_eq!(grid.cell_at(10.into(), 20.into()), Some((0, 0)));
+        assert_eq!(grid.cell_at(57.9.into(), 67.9.into()), Some((2, 2)));
+        assert_eq!(grid.cell_at(58.into(), 20.into()), None);
+        assert_eq!(grid.cell_at(10.into(), 68.into()), None);
+        assert_eq!(grid.cell_at(9.9.into(), 20.into()), None);
+        let (x, y) = grid.cell_centre(2, 0);
+        assert_eq!((x.into_inner(), y.into_inner()), (50.0, 28.0));
+    }
+
+    #[test]
+    fn cells_and_rectangles() {
+        let mut grid = self::grid(&["....", "....", "...."]);
+        grid.set_cell(-1, 0, -1);
+        grid.set_cell(4, 0, -1);
+        grid.set_cell(0, 3, -1);
+        assert_eq!(rows(&grid), ["....", "....", "...."]);
+        grid.set_rectangle(42, 36, 26, 51, -1);
+        assert_eq!(rows(&grid), ["....", ".##.", "...."]);
+        grid.set_rectangle(-100, -100, 10, 20, -1);
+        grid.set_rectangle(200, 20, 300, 100, -1);
+        grid.set_rectangle(0, 20, 9, 100, -1);
+        assert_eq!(rows(&grid), ["#...", ".##.", "...."]);
+        grid.set_rectangle(42, 0, 1000, 1000, 0);
+        assert_eq!(rows(&grid), ["#...", ".#..", "...."]);
+        grid.set_all(0);
+        assert_eq!(rows(&grid), ["....", "....", "...."]);
+
+        let mut empty = MpGrid::new(0, 0, 0, 0, 16, 16);
+        empty.set_rectangle(0, 0, 100, 100, -1);
+        let mut flat = MpGrid::new(0, 0, 2, 2, 0, 16);
+        flat.set_rectangle(0, 0, 100, 100, -1);
+        assert_eq!(rows(&flat), ["..", ".."]);
+    }
+
+    #[test]
+    fn add_instance() {
+        let mut grid = self::grid(&["....", "....", "...."]);
+        let mut checked = Vec::new();
+        // a bounding box over the middle two columns, where only the right half is solid
+        grid.add_instance((30, 30, 55, 40), |x1, y1, x2, y2| {
+            checked.push((x1, y1, x2, y2));
+            x2 >= 43
+        });
+        assert_eq!(checked, [(26, 20, 41, 35), (26, 36, 41, 51), (42, 20, 57, 35), (42, 36, 57, 51)]);
+        assert_eq!(rows(&grid), ["..#.", "..#.", "...."]);
+        // only the part inside the grid is checked
+        grid.add_instance((-50, 60, 12, 100), |_, _, _, _| true);
+        assert_eq!(rows(&grid), ["..#.", "..#.", "#..."]);
+    }
+}
@@ -1,4 +1,58 @@
+mod linux;
 mod windows;
 
+use serde::{Deserialize, Serialize};
+
+#[cfg(target_os = "linux")]
+pub use linux::{
+    display_colour_depth, display_frequency, display_height, display_mouse_position, display_width, has_display,
+    reset_display, set_display, set_display_mouse_position, set_stay_on_top, set_window_mouse_position,
+    set_window_position, test_display,
+};
 #[cfg(windows)]
-pub use windows::{disk_free, disk_size, display_colour_depth, display_frequency, display_height, display_width};
+pub use windows::{
+    disk_free, disk_size, display_colour_depth, display_frequency, display_height, display_mouse_position,
+    display_width, has_display, reset_display, set_display, set_display_mouse_position, set_stay_on_top,
+    set_window_mouse_position, set_window_position, test_display,
+};
+
+/// The display as the game sees it in record and replay modes, which is the same on every machine.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct VirtualDisplay {
+    pub width: u32,
+    pub height: u32,
+    pub colour_depth: u32,
+    pub frequency: u32,
+}
+
+impl Default for VirtualDisplay {
+    fn default() -> Self {
+        Self { width: 1280, height: 720, colour_depth: 32, frequency: 60 }
+    }
+}
+
+impl VirtualDisplay {
+    /// Changes the display mode like display_set_all, where -1 leaves a setting as it is.
+    pub fn set(&mut self, width: i32, height: i32, frequency: i32, colour_depth: i32) -> bool {
+        if !Self::test(width, height, frequency, colour_depth) {
+            return false
+        }
+        if width > 0 && height > 0 {
+            self.width = width as u32;
+            self.height = height as u32;
+        }
+        if frequency > 0 {
+            self.frequency = frequency as u32;
+        }
+        if colour_depth > 0 {
+            self.colour_depth = colour_depth as u32;
+        }
+        true
+    }
+
+    pub fn test(width: i32, height: i32, frequency: i32, colour_depth: i32) -> bool {
+        ((width > 0 && height > 0) || (width == -1 && height == -1))
+            && (frequency > 0 || frequency == -1)
+            && [-1, 16, 32].contains(&colour_depth)
+    }
+}
@@ -0,0 +1,357 @@
+#![cfg(target_os = "linux")]
+#![allow(non_snake_case, non_upper_case_globals)]
+
+use ramen::window::Window;
+use std::{
+    cell::Cell,
+    os::raw::{c_char, c_int, c_long, c_short, c_uint, c_ulong, c_ushort, c_void},
+    ptr,
+    sync::Once,
+};
+
+type Display = c_void;
+type XWindow = c_ulong;
+type Atom = c_ulong;
+type Bool = c_int;
+type Rotation = c_ushort;
+type SizeID = c_ushort;
+type XRRScreenConfiguration = c_void;
+
+#[repr(C)]
+struct XRRScreenSize {
+    width: c_int,
+    height: c_int,
+    mwidth: c_int,
+    mheight: c_int,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct XClientMessageEvent {
+    type_: c_int,
+    serial: c_ulong,
+    send_event: Bool,
+    display: *mut Display,
+    window: XWindow,
+    message_type: Atom,
+    format: c_int,
+    data: [c_long; 5],
+}
+
+#[repr(C)]
+union XEvent {
+    client_message: XClientMessageEvent,
+    pad: [c_long; 24],
+}
+
+const ClientMessage: c_int = 33;
+const SubstructureNotifyMask: c_long = 1 << 19;
+const SubstructureRedirectMask: c_long = 1 << 20;
+const CurrentTime: c_ulong = 0;
+const _NET_WM_STATE_REMOVE: c_long = 0;
+const _NET_WM_STATE_ADD: c_long = 1;
+
+// Xlib and XRandR are loaded at runtime rather than linked, so that a system without them (or without an X server
+// at all, such as Wayland with no XWayland) can still start. The display functions then return nothing, and the
+// kernel falls back to the virtual display.
+macro_rules! library {
+    ($name:ident [$($soname:literal),+] { $(fn $func:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;)* }) => {
+        struct $name {
+            $($func: unsafe extern "C" fn($($arg: $ty),*) $(-> $ret)?,)*
+        }
+
+        impl $name {
+            unsafe fn load() -> Option<Self> {
+                let handle = [$(concat!($soname, "\0")),+]
+                    .iter()
+                    .map(|name| libc::dlopen(name.as_ptr().cast(), libc::RTLD_LAZY | libc::RTLD_LOCAL))
+                    .find(|handle| !handle.is_null())?;
+                Some(Self {
+                    $($func: {
+                        let symbol = libc::dlsym(handle, concat!(stringify!($func), "\0").as_ptr().cast());
+                        if symbol.is_null() {
+                            libc::dlclose(handle);
+                            return None
+                        }
+                        std::mem::transmute::<*mut c_void, unsafe extern "C" fn($($ty),*) $(-> $ret)?>(symbol)
+                    },)*
+                })
+            }
+        }
+    };
+}
+
+library!(Xlib ["libX11.so.6", "libX11.so"] {
+    fn XOpenDisplay(display_name: *const c_char) -> *mut Display;
+    fn XDefaultScreen(display: *mut Display) -> c_int;
+    fn XDefaultRootWindow(display: *mut Display) -> XWindow;
+    fn XDisplayWidth(display: *mut Display, screen: c_int) -> c_int;
+    fn XDisplayHeight(display: *mut Display, screen: c_int) -> c_int;
+    fn XDefaultDepth(display: *mut Display, screen: c_int) -> c_int;
+    fn XQueryPointer(
+        display: *mut Display,
+        w: XWindow,
+        root_return: *mut XWindow,
+        child_return: *mut XWindow,
+        root_x_return: *mut c_int,
+        root_y_return: *mut c_int,
+        win_x_return: *mut c_int,
+        win_y_return: *mut c_int,
+        mask_return: *mut c_uint,
+    ) -> Bool;
+    fn XWarpPointer(
+        display: *mut Display,
+        src_w: XWindow,
+        dest_w: XWindow,
+        src_x: c_int,
+        src_y: c_int,
+        src_width: c_uint,
+        src_height: c_uint,
+        dest_x: c_int,
+        dest_y: c_int,
+    ) -> c_int;
+    fn XMoveWindow(display: *mut Display, w: XWindow, x: c_int, y: c_int) -> c_int;
+    fn XInternAtom(display: *mut Display, atom_name: *const c_char, only_if_exists: Bool) -> Atom;
+    fn XSendEvent(display: *mut Display, w: XWindow, propagate: Bool, event_mask: c_long, event: *mut XEvent) -> c_int;
+    fn XFlush(display: *mut Display) -> c_int;
+});
+
+library!(Xrandr ["libXrandr.so.2", "libXrandr.so"] {
+    fn XRRGetScreenInfo(display: *mut Display, window: XWindow) -> *mut XRRScreenConfiguration;
+    fn XRRFreeScreenConfigInfo(config: *mut XRRScreenConfiguration);
+    fn XRRConfigSizes(config: *mut XRRScreenConfiguration, nsizes: *mut c_int) -> *mut XRRScreenSize;
+    fn XRRConfigRates(config: *mut XRRScreenConfiguration, size_index: c_int, nrates: *mut c_int) -> *mut c_short;
+    fn XRRConfigCurrentConfiguration(config: *mut XRRScreenConfiguration, rotation: *mut Rotation) -> SizeID;
+    fn XRRConfigCurrentRate(config: *mut XRRScreenConfiguration) -> c_short;
+    fn XRRSetScreenConfigAndRate(
+        display: *mut Display,
+        config: *mut XRRScreenConfiguration,
+        draw: XWindow,
+        size_index: c_int,
+        rotation: Rotation,
+        rate: c_short,
+        timestamp: c_ulong,
+    ) -> c_int;
+});
+
+struct XConnection {
+    xlib: Xlib,
+    // without this, the display can be queried but its mode can't be changed
+    xrandr: Option<Xrandr>,
+    display: *mut Display,
+    screen: c_int,
+    root: XWindow,
+}
+
+// Our own connection to the X server, separate from the window's, so that queries work the same whether or not
+// the window is the one being asked about.
+static CONNECTION_INIT: Once = Once::new();
+static mut CONNECTION: Option<&XConnection> = None;
+
+thread_local! {
+    // The mode the screen was in before the game first changed it, so it can be put back.
+    static ORIGINAL_MODE: Cell<Option<(c_int, Rotation, c_short)>> = Cell::new(None);
+}
+
+fn connection() -> Option<&'static XConnection> {
+    unsafe {
+        CONNECTION_INIT.call_once(|| {
+            CONNECTION = (|| {
+                let xlib = Xlib::load()?;
+                let display = (xlib.XOpenDisplay)(ptr::null());
+                if display.is_null() {
+                    return None
+                }
+                let (screen, root) = ((xlib.XDefaultScreen)(display), (xlib.XDefaultRootWindow)(display));
+                Some(&*Box::leak(Box::new(XConnection { xlib, xrandr: Xrandr::load(), display, screen, root })))
+            })()
+        });
+        CONNECTION
+    }
+}
+
+/// Whether there's an X display to query and control, rather than only the virtual one.
+pub fn has_display() -> bool {
+    connection().is_some()
+}
+
+pub fn display_width() -> Option<u32> {
+    connection().map(|conn| unsafe { (conn.xlib.XDisplayWidth)(conn.display, conn.screen) as u32 })
+}
+
+pub fn display_height() -> Option<u32> {
+    connection().map(|conn| unsafe { (conn.xlib.XDisplayHeight)(conn.display, conn.screen) as u32 })
+}
+
+pub fn display_frequency() -> Option<u32> {
+    with_screen_config(|_, xrandr, config| unsafe { (xrandr.XRRConfigCurrentRate)(config) as u32 })
+}
+
+pub fn display_colour_depth() -> Option<u32> {
+    // a 24-bit X visual is what Windows calls 32-bit colour, the fourth byte just going unused
+    connection().map(|conn| match unsafe { (conn.xlib.XDefaultDepth)(conn.display, conn.screen) } {
+        24 => 32,
+        depth => depth as u32,
+    })
+}
+
+fn with_screen_config<T>(f: impl FnOnce(&XConnection, &Xrandr, *mut XRRScreenConfiguration) -> T) -> Option<T> {
+    let conn = connection()?;
+    let xrandr = conn.xrandr.as_ref()?;
+    unsafe {
+        let config = (xrandr.XRRGetScreenInfo)(conn.display, conn.root);
+        if config.is_null() {
+            return None
+        }
+        let result = f(conn, xrandr, config);
+        (xrandr.XRRFreeScreenConfigInfo)(config);
+        Some(result)
+    }
+}
+
+/// Finds the screen size and refresh rate for a mode, with zero meaning the current value of each setting.
+unsafe fn find_mode(
+    xrandr: &Xrandr,
+    config: *mut XRRScreenConfiguration,
+    width: u32,
+    height: u32,
+    frequency: u32,
+) -> Option<(c_int, c_short)> {
+    let mut rotation = 0;
+    let current = (xrandr.XRRConfigCurrentConfiguration)(config, &mut rotation);
+    let mut size_count = 0;
+    let sizes = (xrandr.XRRConfigSizes)(config, &mut size_count);
+    if sizes.is_null() {
+        return None
+    }
+    let sizes = std::slice::from_raw_parts(sizes, size_count as usize);
+    let size_index = if width == 0 || height == 0 {
+        c_int::from(current)
+    } else {
+        sizes.iter().position(|s| s.width as u32 == width && s.height as u32 == height)? as c_int
+    };
+    let rate = if frequency == 0 {
+        (xrandr.XRRConfigCurrentRate)(config)
+    } else {
+        let mut rate_count = 0;
+        let rates = (xrandr.XRRConfigRates)(config, size_index, &mut rate_count);
+        if rates.is_null() {
+            return None
+        }
+        *std::slice::from_raw_parts(rates, rate_count as usize).iter().find(|&&r| r as u32 == frequency)?
+    };
+    Some((size_index, rate))
+}
+
+/// Checks whether the display supports a mode. X can't change colour depth, so that has to match already.
+pub fn test_display(width: u32, height: u32, frequency: u32, colour_depth: u32) -> bool {
+    if colour_depth != 0 && Some(colour_depth) != display_colour_depth() {
+        return false
+    }
+    with_screen_config(|_, xrandr, config| unsafe { find_mode(xrandr, config, width, height, frequency).is_some() })
+        .unwrap_or(false)
+}
+
+pub fn set_display(width: u32, height: u32, frequency: u32, colour_depth: u32) -> bool {
+    if colour_depth != 0 && Some(colour_depth) != display_colour_depth() {
+        return false
+    }
+    with_screen_config(|conn, xrandr, config| unsafe {
+        let (size_index, rate) = match find_mode(xrandr, config, width, height, frequency) {
+            Some(mode) => mode,
+            None => return false,
+        };
+        let mut rotation = 0;
+        let current = (xrandr.XRRConfigCurrentConfiguration)(config, &mut rotation);
+        if ORIGINAL_MODE.with(|mode| mode.get().is_none()) {
+            let mode = (c_int::from(current), rotation, (xrandr.XRRConfigCurrentRate)(config));
+            ORIGINAL_MODE.with(|original| original.set(Some(mode)));
+        }
+        let set = xrandr.XRRSetScreenConfigAndRate;
+        set(conn.display, config, conn.root, size_index, rotation, rate, CurrentTime) == 0
+    })
+    .unwrap_or(false)
+}
+
+pub fn reset_display() {
+    if let Some((size_index, rotation, rate)) = ORIGINAL_MODE.with(|mode| mode.take()) {
+        with_screen_config(|conn, xrandr, config| unsafe {
+            let set = xrandr.XRRSetScreenConfigAndRate;
+            set(conn.display, config, conn.root, size_index, rotation, rate, CurrentTime);
+        });
+    }
+}
+
+pub fn display_mouse_position() -> Option<(i32, i32)> {
+    let conn = connection()?;
+    let (mut root, mut child) = (0, 0);
+    let (mut root_x, mut root_y, mut win_x, mut win_y) = (0, 0, 0, 0);
+    let mut mask = 0;
+    let response = unsafe {
+        (conn.xlib.XQueryPointer)(
+            conn.display,
+            conn.root,
+            &mut root,
+            &mut child,
+            &mut root_x,
+            &mut root_y,
+            &mut win_x,
+            &mut win_y,
+            &mut mask,
+        )
+    };
+    (response != 0).then(|| (root_x, root_y))
+}
+
+pub fn set_display_mouse_position(x: i32, y: i32) {
+    if let Some(conn) = connection() {
+        unsafe {
+            (conn.xlib.XWarpPointer)(conn.display, 0, conn.root, 0, 0, 0, 0, x, y);
+            (conn.xlib.XFlush)(conn.display);
+        }
+    }
+}
+
+pub fn set_window_mouse_position(window: &Window, x: i32, y: i32) {
+    if let Some(conn) = connection() {
+        unsafe {
+            (conn.xlib.XWarpPointer)(conn.display, 0, window.xid().into(), 0, 0, 0, 0, x, y);
+            (conn.xlib.XFlush)(conn.display);
+        }
+    }
+}
+
+pub fn set_window_position(window: &Window, x: i32, y: i32) {
+    if let Some(conn) = connection() {
+        unsafe {
+            (conn.xlib.XMoveWindow)(conn.display, window.xid().into(), x, y);
+            (conn.xlib.XFlush)(conn.display);
+        }
+    }
+}
+
+/// Asks the window manager to keep the window above others, as there's no way to do it directly.
+pub fn set_stay_on_top(window: &Window, stay_on_top: bool) {
+    if let Some(conn) = connection() {
+        unsafe {
+            let state = (conn.xlib.XInternAtom)(conn.display, b"_NET_WM_STATE\0".as_ptr().cast(), 0);
+            let above = (conn.xlib.XInternAtom)(conn.display, b"_NET_WM_STATE_ABOVE\0".as_ptr().cast(), 0);
+            let action = if stay_on_top { _NET_WM_STATE_ADD } else { _NET_WM_STATE_REMOVE };
+            let mut event = XEvent {
+                client_message: XClientMessageEvent {
+                    type_: ClientMessage,
+                    serial: 0,
+                    send_event: 1,
+                    display: conn.display,
+                    window: window.xid().into(),
+                    message_type: state,
+                    format: 32,
+                    data: [action, above as c_long, 0, 1, 0],
+                },
+            };
+            let mask = SubstructureRedirectMask | SubstructureNotifyMask;
+            (conn.xlib.XSendEvent)(conn.display, conn.root, 0, mask, &mut event);
+            (conn.xlib.XFlush)(conn.display);
+        }
+    }
+}
@@ -1,5 +1,6 @@
 #![cfg(windows)]
 
+use ramen::window::Window;
 use std::{ffi::OsStr, mem, os::windows::ffi::OsStrExt, ptr};
 
 #[allow(non_snake_case)]
@@ -34,11 +35,37 @@ struct DEVMODEW {
     dmPanningHeight: u32,
 }
 
+#[repr(C)]
+struct POINT {
+    x: i32,
+    y: i32,
+}
+
+type HWND = ramen::platform::win32::HWND;
+
 const ENUM_CURRENT_SETTINGS: u32 = u32::MAX;
+const CDS_TEST: u32 = 0x2;
+const CDS_FULLSCREEN: u32 = 0x4;
+const DISP_CHANGE_SUCCESSFUL: i32 = 0;
+const DM_BITSPERPEL: u32 = 0x40000;
+const DM_PELSWIDTH: u32 = 0x80000;
+const DM_PELSHEIGHT: u32 = 0x100000;
+const DM_DISPLAYFREQUENCY: u32 = 0x400000;
+const HWND_TOPMOST: isize = -1;
+const HWND_NOTOPMOST: isize = -2;
+const SWP_NOSIZE: u32 = 0x1;
+const SWP_NOMOVE: u32 = 0x2;
+const SWP_NOZORDER: u32 = 0x4;
+const SWP_NOACTIVATE: u32 = 0x10;
 
 #[link(name = "user32")]
 extern "system" {
     fn EnumDisplaySettingsW(lpszDeviceName: *const u16, iModeNum: u32, lpDevMode: *mut DEVMODEW) -> i32;
+    fn ChangeDisplaySettingsW(lpDevMode: *mut DEVMODEW, dwFlags: u32) -> i32;
+    fn GetCursorPos(lpPoint: *mut POINT) -> i32;
+    fn SetCursorPos(x: i32, y: i32) -> i32;
+    fn ClientToScreen(hWnd: HWND, lpPoint: *mut POINT) -> i32;
+    fn SetWindowPos(hWnd: HWND, hWndInsertAfter: HWND, x: i32, y: i32, cx: i32, cy: i32, uFlags: u32) -> i32;
     fn GetDiskFreeSpaceExW(
         lpDirectoryName: *const u16,
         lpFreeBytesAvailableToCaller: *mut u64,
@@ -55,6 +82,11 @@ fn get_display_settings() -> Option<DEVMODEW> {
     }
 }
 
+/// Whether there's a real display to query and control, which there always is on Windows.
+pub fn has_display() -> bool {
+    true
+}
+
 pub fn display_width() -> Option<u32> {
     get_display_settings().map(|dm| dm.dmPelsWidth)
 }
@@ -71,6 +103,74 @@ pub fn display_colour_depth() -> Option<u32> {
     get_display_settings().map(|dm| dm.dmBitsPerPel)
 }
 
+/// Changes the display mode, or only checks if it's possible. Zero means the current value for each setting.
+fn change_display_settings(width: u32, height: u32, frequency: u32, colour_depth: u32, flags: u32) -> bool {
+    let mut device = match get_display_settings() {
+        Some(device) => device,
+        None => return false,
+    };
+    device.dmFields = DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYFREQUENCY | DM_BITSPERPEL;
+    if width != 0 && height != 0 {
+        device.dmPelsWidth = width;
+        device.dmPelsHeight = height;
+    }
+    if frequency != 0 {
+        device.dmDisplayFrequency = frequency;
+    }
+    if colour_depth != 0 {
+        device.dmBitsPerPel = colour_depth;
+    }
+    unsafe { ChangeDisplaySettingsW(&mut device, flags) == DISP_CHANGE_SUCCESSFUL }
+}
+
+pub fn set_display(width: u32, height: u32, frequency: u32, colour_depth: u32) -> bool {
+    change_display_settings(width, height, frequency, colour_depth, CDS_FULLSCREEN)
+}
+
+pub fn test_display(width: u32, height: u32, frequency: u32, colour_depth: u32) -> bool {
+    change_display_settings(width, height, frequency, colour_depth, CDS_TEST)
+}
+
+pub fn reset_display() {
+    unsafe {
+        ChangeDisplaySettingsW(ptr::null_mut(), 0);
+    }
+}
+
+pub fn display_mouse_position() -> Option<(i32, i32)> {
+    let mut point = POINT { x: 0, y: 0 };
+    let response = unsafe { GetCursorPos(&mut point) };
+    (response != 0).then(|| (point.x, point.y))
+}
+
+pub fn set_display_mouse_position(x: i32, y: i32) {
+    unsafe {
+        SetCursorPos(x, y);
+    }
+}
+
+pub fn set_window_mouse_position(window: &Window, x: i32, y: i32) {
+    let mut point = POINT { x, y };
+    unsafe {
+        if ClientToScreen(window.hwnd(), &mut point) != 0 {
+            SetCursorPos(point.x, point.y);
+        }
+    }
+}
+
+pub fn set_window_position(window: &Window, x: i32, y: i32) {
+    unsafe {
+        SetWindowPos(window.hwnd(), ptr::null_mut(), x, y, 0, 0, SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE);
+    }
+}
+
+pub fn set_stay_on_top(window: &Window, stay_on_top: bool) {
+    let after = if stay_on_top { HWND_TOPMOST } else { HWND_NOTOPMOST };
+    unsafe {
+        SetWindowPos(window.hwnd(), after as HWND, 0, 0, 0, 0, SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE);
+    }
+}
+
 fn trim_drive(drive: Option<char>) -> Vec<u16> {
     match drive {
         Some(letter) => {
@@ -14,6 +14,7 @@ mod popup_dialog;
 
 use crate::{
     game::{
+        codepage,
         savestate::{self, SaveState},
         recording::{
             instance_report::InstanceReport,
@@ -28,16 +29,18 @@ use crate::{
     },
     render::{atlas::AtlasRef, PrimitiveType, RendererState},
     types::Colour,
-    imgui, input,
+    imgui,
+    input::{self, KeyboardLayout},
 };
 use ramen::{
     event::Event,
     input::Key,
 };
+use encoding_rs::Encoding;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::File,
-    path::PathBuf,
+    fs::{self, File},
+    path::{Path, PathBuf},
     time::Instant,
 };
 
@@ -377,30 +380,35 @@ impl KeyState {
     }
 
     pub fn push_key_inputs(&self, key: u8, inputs: &mut Vec<replay::Input>) {
+        self.push_inputs(replay::Input::KeyPress(key), replay::Input::KeyRelease(key), inputs)
+    }
+
+    /// Pushes the given press and release inputs in the order this state represents.
+    pub fn push_inputs(&self, press: replay::Input, release: replay::Input, inputs: &mut Vec<replay::Input>) {
         match self {
             Self::NeutralWillPress => {
-                inputs.push(replay::Input::KeyPress(key));
+                inputs.push(press.clone());
             },
             Self::NeutralWillDouble | Self::NeutralDoubleEveryFrame => {
-                inputs.push(replay::Input::KeyPress(key));
-                inputs.push(replay::Input::KeyRelease(key));
+                inputs.push(press.clone());
+                inputs.push(release.clone());
             },
             Self::NeutralWillTriple => {
-                inputs.push(replay::Input::KeyPress(key));
-                inputs.push(replay::Input::KeyRelease(key));
-                inputs.push(replay::Input::KeyPress(key));
+                inputs.push(press.clone());
+                inputs.push(release.clone());
+                inputs.push(press.clone());
             },
             Self::HeldWillRelease | Self::NeutralWillCactus => {
-                inputs.push(replay::Input::KeyRelease(key));
+                inputs.push(release.clone());
             },
             Self::HeldWillDouble | Self::HeldDoubleEveryFrame => {
-                inputs.push(replay::Input::KeyRelease(key));
-                inputs.push(replay::Input::KeyPress(key));
+                inputs.push(release.clone());
+                inputs.push(press.clone());
             },
             Self::HeldWillTriple => {
-                inputs.push(replay::Input::KeyRelease(key));
-                inputs.push(replay::Input::KeyPress(key));
-                inputs.push(replay::Input::KeyRelease(key));
+                inputs.push(release.clone());
+                inputs.push(press.clone());
+                inputs.push(release.clone());
             },
             Self::Neutral | Self::Held => (),
         }
@@ -440,7 +448,7 @@ pub struct ProjectConfig {
     config_path: PathBuf,
     is_read_only: bool,
     current_frame: usize,
-    set_mouse_using_textbox: bool
+    set_mouse_using_textbox: bool,
 }
 
 impl ProjectConfig {
@@ -479,6 +487,35 @@ impl ProjectConfig {
         config
     }
 
+    /// Reads the code page a project was recorded with, so it can be used again when it's next opened.
+    /// It's kept in its own file rather than in project.cfg, so that older projects' configs still load.
+    /// Projects without one were recorded before the code page could be chosen, which means with Shift-JIS.
+    pub fn encoding(project_path: &Path) -> &'static Encoding {
+        fs::read_to_string(project_path.join("codepage.txt"))
+            .ok()
+            .and_then(|label| codepage::from_label(&label))
+            .unwrap_or(encoding_rs::SHIFT_JIS)
+    }
+
+    /// Reads the keyboard layout a project was recorded with, kept apart for the same reason as the code page.
+    pub fn keyboard_layout(project_path: &Path) -> Option<KeyboardLayout> {
+        KeyboardLayout::from_label(&fs::read_to_string(project_path.join("keyboard.txt")).ok()?)
+    }
+
+    /// Saves a code page chosen for the project. If that failed it will return a description of the error.
+    pub fn save_encoding(project_path: &Path, encoding: &'static Encoding) -> Option<String> {
+        fs::write(project_path.join("codepage.txt"), encoding.name())
+            .err()
+            .map(|e| format!("Code page was not saved to disk because of an error: {}", e))
+    }
+
+    /// Saves a keyboard layout chosen for the project. If that failed it will return a description of the error.
+    pub fn save_keyboard_layout(project_path: &Path, layout: KeyboardLayout) -> Option<String> {
+        fs::write(project_path.join("keyboard.txt"), layout.label())
+            .err()
+            .map(|e| format!("Keyboard layout was not saved to disk because of an error: {}", e))
+    }
+
     /// Saves the configuration file. If that failed it will return a description of the error, otherwise None
     pub fn save(&self) -> Option<String> {
         File::create(&self.config_path)
@@ -500,7 +537,12 @@ impl Game {
         };
         let mut config = ProjectConfig::from_file_or_default(&config_path);
 
-        let mut replay = Replay::new(self.spoofed_time_nanos.unwrap_or(0), self.rand.seed());
+        let mut replay = Replay::new(
+            self.spoofed_time_nanos.unwrap_or(0),
+            self.rand.seed(),
+            self.encoding,
+            self.input.keyboard_layout(),
+        );
 
         let mut context = imgui::Context::new();
         context.make_current();
@@ -1,6 +1,6 @@
 use crate::{
     imgui,
-    input::Button,
+    input::{Button, JoystickAxis, JOYSTICK_BUTTON_COUNT},
     game::{
         replay::{
             Input,
@@ -29,6 +29,122 @@ enum MouseSelection {
     Right,
     Fixed,
 }
+/// A column of button states in the input table
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+enum InputColumn {
+    Key(u8),
+    JoystickButton(u8, u8),
+}
+
+impl InputColumn {
+    /// Gets the column an input belongs to, and whether it's a press
+    fn from_input(input: &Input) -> Option<(Self, bool)> {
+        match input {
+            Input::KeyPress(key) => Some((Self::Key(*key), true)),
+            Input::KeyRelease(key) => Some((Self::Key(*key), false)),
+            Input::JoystickPress(joy, button) => Some((Self::JoystickButton(*joy, *button), true)),
+            Input::JoystickRelease(joy, button) => Some((Self::JoystickButton(*joy, *button), false)),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            Self::Key(key) => Button::try_from_u8(*key).map(|b| b.to_string()).unwrap_or_else(|| format!("{}", key)),
+            Self::JoystickButton(joy, button) => format!("J{}B{}", joy + 1, button + 1),
+        }
+    }
+
+    fn push_inputs(&self, state: &KeyState, inputs: &mut Vec<Input>) {
+        match *self {
+            Self::Key(key) => state.push_key_inputs(key, inputs),
+            Self::JoystickButton(joy, button) => {
+                state.push_inputs(Input::JoystickPress(joy, button), Input::JoystickRelease(joy, button), inputs)
+            },
+        }
+    }
+}
+
+/// Stick directions which can be set from the joystick columns, as (label, x, y)
+const JOYSTICK_DIRECTIONS: [(&str, i16, i16); 9] = [
+    ("C", 0, 0),
+    ("U", 0, -i16::MAX),
+    ("UR", i16::MAX, -i16::MAX),
+    ("R", i16::MAX, 0),
+    ("DR", i16::MAX, i16::MAX),
+    ("D", 0, i16::MAX),
+    ("DL", -i16::MAX, i16::MAX),
+    ("L", -i16::MAX, 0),
+    ("UL", -i16::MAX, -i16::MAX),
+];
+
+/// Joystick state edited as a direction from JOYSTICK_DIRECTIONS, one column of each per joystick
+#[derive(Copy, Clone)]
+enum StickColumn {
+    /// A pair of axes moved together like a stick, as (horizontal, vertical)
+    Axes(JoystickAxis, JoystickAxis),
+    /// The point-of-view hat, where each direction after "C" is another 45 degrees clockwise from up
+    Pov,
+}
+
+const STICK_COLUMNS: [(&str, StickColumn); 4] = [
+    ("XY", StickColumn::Axes(JoystickAxis::X, JoystickAxis::Y)),
+    ("ZR", StickColumn::Axes(JoystickAxis::Z, JoystickAxis::R)),
+    ("UV", StickColumn::Axes(JoystickAxis::U, JoystickAxis::V)),
+    ("POV", StickColumn::Pov),
+];
+
+impl StickColumn {
+    /// Gets the index into JOYSTICK_DIRECTIONS of the movement on a frame, if there is any
+    fn direction_on_frame(self, inputs: &[Input], joy: u8) -> Option<usize> {
+        match self {
+            Self::Axes(h, v) => {
+                let (mut x, mut y) = (None, None);
+                for input in inputs {
+                    match input {
+                        Input::JoystickAxis(j, axis, value) if *j == joy && *axis == h => x = Some(*value),
+                        Input::JoystickAxis(j, axis, value) if *j == joy && *axis == v => y = Some(*value),
+                        _ => (),
+                    }
+                }
+                if x.is_none() && y.is_none() {
+                    return None
+                }
+                let (x, y) = (x.unwrap_or(0).signum(), y.unwrap_or(0).signum());
+                JOYSTICK_DIRECTIONS.iter().position(|(_, dx, dy)| dx.signum() == x && dy.signum() == y)
+            },
+            Self::Pov => inputs.iter().rev().find_map(|input| match input {
+                Input::JoystickPov(j, None) if *j == joy => Some(0),
+                Input::JoystickPov(j, Some(angle)) if *j == joy => Some((usize::from(*angle) + 2250) / 4500 % 8 + 1),
+                _ => None,
+            }),
+        }
+    }
+
+    /// Replaces the movement on a frame with the given direction, or removes it if None
+    fn set_direction_on_frame(self, inputs: &mut Vec<Input>, joy: u8, direction: Option<usize>) {
+        match self {
+            Self::Axes(h, v) => {
+                inputs.retain(|input| {
+                    !matches!(input, Input::JoystickAxis(j, axis, _) if *j == joy && (*axis == h || *axis == v))
+                });
+                if let Some((_, x, y)) = direction.and_then(|d| JOYSTICK_DIRECTIONS.get(d)) {
+                    inputs.push(Input::JoystickAxis(joy, h, *x));
+                    inputs.push(Input::JoystickAxis(joy, v, *y));
+                }
+            },
+            Self::Pov => {
+                inputs.retain(|input| !matches!(input, Input::JoystickPov(j, _) if *j == joy));
+                match direction {
+                    Some(0) => inputs.push(Input::JoystickPov(joy, None)),
+                    Some(d) => inputs.push(Input::JoystickPov(joy, Some((d as u16 - 1) * 4500))),
+                    None => (),
+                }
+            },
+        }
+    }
+}
+
 #[derive(PartialEq, Eq, Copy, Clone)]
 enum TableColor {
     DISABLED, CURRENT, SELECTED, DEFAULT, NONE
@@ -36,8 +152,10 @@ enum TableColor {
 pub struct InputEditWindow {
     is_open: bool,
     updated: bool,
-    keys: Vec<u8>,
+    keys: Vec<InputColumn>,
     states: Vec<Vec<KeyState>>,
+    show_joysticks: bool,
+    show_all_buttons: bool,
     last_frame: usize,
     scroll_y: f32,
     hovered_text: Option<&'static str>,
@@ -65,6 +183,7 @@ pub struct InputEditWindow {
 const INPUT_TABLE_WIDTH: f32 = 50.0;
 const INPUT_TABLE_RNG_WIDTH: f32 = INPUT_TABLE_WIDTH * 1.5;
 const INPUT_TABLE_MOUSE_WIDTH: f32 = INPUT_TABLE_WIDTH * 1.5;
+const JOYSTICK_COLUMN_COUNT: usize = 2;
 const INPUT_TABLE_HEIGHT: f32 = 20.0;
 const INPUT_TABLE_YPOS: f32 = 44.0;
 const TABLE_PADDING: f32 = 2.0;
@@ -134,7 +253,7 @@ impl Window for InputEditWindow {
 
         if info.frame.begin_table(
             "Input",
-            self.keys.len() as i32 + 3, // + Frame counter, RNG Seed and Mouse columns
+            self.keys.len() as i32 + 3 + self.joystick_column_count() as i32, // + Frame counter, RNG Seed, Mouse and stick columns
             (cimgui_sys::ImGuiTableFlags__ImGuiTableFlags_RowBg
                 | cimgui_sys::ImGuiTableFlags__ImGuiTableFlags_Reorderable
                 | cimgui_sys::ImGuiTableFlags__ImGuiTableFlags_Borders
@@ -146,12 +265,14 @@ impl Window for InputEditWindow {
         ) {
             info.frame.table_setup_column("Frame", cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_NoReorder as _, 0.0);
             for key in self.keys.iter() {
-                if let Some(button) = Button::try_from_u8(*key) {
-                    info.frame.table_setup_column(&format!("{}", button), cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_WidthFixed as _, INPUT_TABLE_WIDTH);
-                }
+                info.frame.table_setup_column(&key.name(), cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_WidthFixed as _, INPUT_TABLE_WIDTH);
             }
             info.frame.table_setup_column("RNG", (cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_NoReorder |  cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_WidthFixed) as _, INPUT_TABLE_RNG_WIDTH);
             info.frame.table_setup_column("Mouse", (cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_NoReorder |  cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_WidthFixed) as _, INPUT_TABLE_MOUSE_WIDTH);
+            for column in 0..self.joystick_column_count() {
+                let (joy, (name, _)) = (column / STICK_COLUMNS.len(), STICK_COLUMNS[column % STICK_COLUMNS.len()]);
+                info.frame.table_setup_column(&format!("J{} {}", joy + 1, name), (cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_NoReorder |  cimgui_sys::ImGuiTableColumnFlags__ImGuiTableColumnFlags_WidthFixed) as _, INPUT_TABLE_WIDTH);
+            }
             info.frame.table_setup_scroll_freeze(0, 1); // freeze header row
             info.frame.table_headers_row();
 
@@ -208,6 +329,19 @@ impl Window for InputEditWindow {
         info.frame.same_line(0.0, -1.0);
         info.frame.checkbox("##mouse", &mut self.single_frame_mouse);
 
+        unsafe {
+            cimgui_sys::igSetCursorPos(imgui::Vec2(8.0, 2.0).into());
+        }
+        if info.frame.checkbox("Joysticks", &mut self.show_joysticks) {
+            self.update_keys(info.replay);
+        }
+        if self.show_joysticks {
+            info.frame.same_line(0.0, -1.0);
+            if info.frame.checkbox("All buttons", &mut self.show_all_buttons) {
+                self.update_keys(info.replay);
+            }
+        }
+
         unsafe { cimgui_sys::igPopStyleVar(1); } // ImGuiStyleVar__ImGuiStyleVar_WindowPadding
         info.frame.end();
     }
@@ -272,6 +406,8 @@ impl InputEditWindow {
             updated: false,
             keys: Vec::new(),
             states: Vec::new(),
+            show_joysticks: false,
+            show_all_buttons: false,
             last_frame: 0,
             scroll_y: 0.0,
             hovered_text: None,
@@ -296,6 +432,10 @@ impl InputEditWindow {
         }
     }
 
+    fn joystick_column_count(&self) -> usize {
+        if self.show_joysticks { JOYSTICK_COLUMN_COUNT * STICK_COLUMNS.len() } else { 0 }
+    }
+
     fn update_mouse_position_for_frame(&mut self, frame: usize, end_frame: Option<usize>, x: i32, y: i32, replay: &mut Replay) {
         // If we want to set a new mouse position and aren't setting the mouse position anymore, update the frames accordingly
         if let Some(replay_frame) = replay.get_frame_mut(frame) {
@@ -525,16 +665,18 @@ impl InputEditWindow {
         self.is_selecting = MouseSelection::None;
 
         self.keys.clear();
+        if self.show_joysticks {
+            // Always show the buttons which have joystick events so they can be edited before they're first used
+            let buttons = if self.show_all_buttons { JOYSTICK_BUTTON_COUNT as u8 } else { 8 };
+            for joy in 0..JOYSTICK_COLUMN_COUNT as u8 {
+                self.keys.extend((0..buttons).map(|button| InputColumn::JoystickButton(joy, button)));
+            }
+        }
         for i in 0..replay.frame_count() {
             if let Some(frame) = replay.get_frame(i) {
-                for input in &frame.inputs {
-                    match input {
-                        Input::KeyPress(btn) | Input::KeyRelease(btn) => {
-                            if !self.keys.contains(btn) {
-                                self.keys.push(*btn);
-                            }
-                        },
-                        _ => {},
+                for (column, _) in frame.inputs.iter().filter_map(InputColumn::from_input) {
+                    if !self.keys.contains(&column) {
+                        self.keys.push(column);
                     }
                 }
             }
@@ -547,14 +689,9 @@ impl InputEditWindow {
         
         for i in 0..replay.frame_count() {
             if let Some(frame) = replay.get_frame(i) {
-                for input in &frame.inputs {
-                    match input {
-                        Input::KeyPress(current_key) | Input::KeyRelease(current_key)  => {
-                            if let Some(index) = self.keys.iter().position(|k| k == current_key) {
-                                self.update_keystate(i, index, matches!(input, Input::KeyPress(_)));
-                            }
-                        },
-                        _ => {},
+                for (column, pressed) in frame.inputs.iter().filter_map(InputColumn::from_input) {
+                    if let Some(index) = self.keys.iter().position(|k| *k == column) {
+                        self.update_keystate(i, index, pressed);
                     }
                 }
 
@@ -743,6 +880,32 @@ impl InputEditWindow {
                 }
             }
 
+            // Stick and POV columns, clicking cycles through directions and middle clicking removes the movement
+            for column in 0..self.joystick_column_count() {
+                let current_frame = replay.get_frame_mut(i).unwrap();
+                let (joy, (_, stick)) = (column / STICK_COLUMNS.len(), STICK_COLUMNS[column % STICK_COLUMNS.len()]);
+                frame.table_set_column_index(self.keys.len() as i32 + 3 + column as i32);
+                let direction = stick.direction_on_frame(&current_frame.inputs, joy as u8);
+                let text = direction.map(|d| JOYSTICK_DIRECTIONS[d].0).unwrap_or("-");
+                frame.button(&format!("{}##stick{}_{}", text, column, i), imgui::Vec2(INPUT_TABLE_WIDTH, INPUT_TABLE_HEIGHT), None);
+                let hovered = frame.item_hovered();
+                if hovered {
+                    any_button_hovered = true;
+                }
+                if i >= config.current_frame && hovered {
+                    let count = JOYSTICK_DIRECTIONS.len();
+                    if frame.left_clicked() {
+                        let new = direction.map(|d| (d + 1) % count).unwrap_or(0);
+                        stick.set_direction_on_frame(&mut current_frame.inputs, joy as u8, Some(new));
+                    } else if frame.right_clicked() {
+                        let new = direction.map(|d| (d + count - 1) % count).unwrap_or(count - 1);
+                        stick.set_direction_on_frame(&mut current_frame.inputs, joy as u8, Some(new));
+                    } else if frame.middle_clicked() {
+                        stick.set_direction_on_frame(&mut current_frame.inputs, joy as u8, None);
+                    }
+                }
+            }
+
             // If we aren't hovering any of the key buttons, check if we are hovering the current row
             if frame.right_clicked() && !any_button_hovered && frame.window_hovered() && i >= config.current_frame {
                 let row_pos = frame.get_item_rect_min(); // Get last item position to figure out whether or not we are hovering the current row
@@ -847,13 +1010,13 @@ impl InputEditWindow {
     fn update_replay(&mut self, frame_index: usize, key_index: usize, replay: &mut Replay, target_state: KeyState) {
         if let Some(replay_frame) = replay.get_frame_mut(frame_index) {
             let mut new_inputs: Vec<Input> = replay_frame.inputs.iter().filter(|input|
-                match input {
-                    Input::KeyPress(key) | Input::KeyRelease(key) => *key != self.keys[key_index],
-                    _ => true,
+                match InputColumn::from_input(input) {
+                    Some((column, _)) => column != self.keys[key_index],
+                    None => true,
                 }
             ).cloned().collect();
 
-            target_state.push_key_inputs(self.keys[key_index], &mut new_inputs);
+            self.keys[key_index].push_inputs(&target_state, &mut new_inputs);
             replay_frame.inputs = new_inputs;
 
             self.states[frame_index][key_index] = target_state;
@@ -1,5 +1,10 @@
-use crate::gml::Value;
+use crate::{
+    game::codepage,
+    gml::Value,
+    input::{JoystickAxis, KeyboardLayout},
+};
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use encoding_rs::Encoding;
 use lzzzz::lz4;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -18,6 +23,11 @@ pub struct Replay {
     // RNG seed to use at the beginning of this replay.
     pub start_seed: i32,
 
+    // Name of the code page the game ran with, and the layout key presses were typed with.
+    // Both decide what text the game gets from the recorded keys, so they have to match on playback.
+    pub encoding: String,
+    pub keyboard_layout: KeyboardLayout,
+
     // Special list of stored events used during startup (before frame 0)
     pub startup_events: Vec<Event>,
 
@@ -80,8 +90,36 @@ pub enum Input {
     MouseRelease(i8),
     MouseWheelUp,
     MouseWheelDown,
+    JoystickPress(u8, u8),   // joystick index (0 or 1), button index (0-31)
+    JoystickRelease(u8, u8), // joystick index (0 or 1), button index (0-31)
+    JoystickAxis(u8, JoystickAxis, i16),
+    JoystickPov(u8, Option<u16>), // hundredths of a degree, None if centred
 }
 
+// The layout of version 1 files, which were all recorded with Shift-JIS and a US keyboard
+#[derive(Deserialize)]
+struct ReplayV1 {
+    start_time: u128,
+    start_seed: i32,
+    startup_events: Vec<Event>,
+    frames: Vec<Frame>,
+}
+
+impl From<ReplayV1> for Replay {
+    fn from(old: ReplayV1) -> Self {
+        Self {
+            start_time: old.start_time,
+            start_seed: old.start_seed,
+            encoding: encoding_rs::SHIFT_JIS.name().into(),
+            keyboard_layout: KeyboardLayout::Us,
+            startup_events: old.startup_events,
+            frames: old.frames,
+        }
+    }
+}
+
+const FORMAT_VERSION: u32 = 2;
+
 #[derive(Debug)]
 pub enum ReadError {
     IOErr(io::Error),
@@ -98,8 +136,25 @@ pub enum WriteError {
 }
 
 impl Replay {
-    pub fn new(start_time: u128, start_seed: i32) -> Self {
-        Self { start_time, start_seed, startup_events: Vec::new(), frames: Vec::new() }
+    pub fn new(
+        start_time: u128,
+        start_seed: i32,
+        encoding: &'static Encoding,
+        keyboard_layout: KeyboardLayout,
+    ) -> Self {
+        Self {
+            start_time,
+            start_seed,
+            encoding: encoding.name().into(),
+            keyboard_layout,
+            startup_events: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    // The code page this replay was recorded with, if it's one the emulator knows
+    pub fn encoding(&self) -> Option<&'static Encoding> {
+        codepage::from_label(&self.encoding)
     }
 
     // Loads a Replay from a gmtas-format file (doesn't check the file extension)
@@ -109,7 +164,7 @@ impl Replay {
         let mut file = File::open(path).map_err(ReadError::IOErr)?;
 
         match file.read_u32::<LE>() {
-            Ok(1) => {
+            Ok(version @ (1 | FORMAT_VERSION)) => {
                 let init_size = file.metadata().map(|m| m.len() as usize + 1).unwrap_or(0);
                 lz4_buf.reserve(init_size);
                 match file.read_to_end(&mut lz4_buf) {
@@ -120,8 +175,11 @@ impl Replay {
                             match lz4::decompress(block, bin_buf.as_mut_slice()) {
                                 Ok(len) => {
                                     unsafe { bin_buf.set_len(len) };
-                                    bincode::deserialize::<'_, Self>(bin_buf.as_slice())
-                                        .map_err(ReadError::DeserializeErr)
+                                    match version {
+                                        1 => bincode::deserialize::<'_, ReplayV1>(&bin_buf).map(Self::from),
+                                        _ => bincode::deserialize::<'_, Self>(&bin_buf),
+                                    }
+                                    .map_err(ReadError::DeserializeErr)
                                 },
                                 Err(err) => Err(ReadError::DecompressErr(err)),
                             }
@@ -145,7 +203,7 @@ impl Replay {
             Ok(()) => match lz4::compress_to_vec(bin_buf.as_slice(), lz4_buf.as_mut(), lz4::ACC_LEVEL_DEFAULT) {
                 Ok(_length) => {
                     match OpenOptions::new().create(true).write(true).truncate(true).open(path).and_then(|mut f| {
-                        f.write_u32::<LE>(1).and_then(|_| {
+                        f.write_u32::<LE>(FORMAT_VERSION).and_then(|_| {
                             f.write_u64::<LE>(bin_buf.len() as u64).and_then(|_| f.write_all(lz4_buf.as_slice()))
                         })
                     }) {
@@ -253,3 +311,62 @@ impl Replay {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("gm8emulator-replay-{}-{}.gmtas", std::process::id(), name))
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = temp_path("round-trip");
+        let mut replay = Replay::new(1234, 5678, encoding_rs::WINDOWS_1251, KeyboardLayout::German);
+        replay.new_frame().inputs.push(Input::KeyPress(b'A'));
+        replay.to_file(&path).unwrap();
+        let loaded = Replay::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, replay);
+        assert_eq!(loaded.encoding(), Some(encoding_rs::WINDOWS_1251));
+        assert_eq!(loaded.keyboard_layout, KeyboardLayout::German);
+    }
+
+    #[test]
+    fn version_1() {
+        #[derive(Serialize)]
+        struct Old {
+            start_time: u128,
+            start_seed: i32,
+            startup_events: Vec<Event>,
+            frames: Vec<Frame>,
+        }
+        let frames = vec![Frame {
+            mouse_x: 3,
+            mouse_y: 4,
+            inputs: vec![Input::KeyPress(b'A')],
+            events: Vec::new(),
+            new_seed: Some(FrameRng::Override(9)),
+            new_time: None,
+        }];
+        let old = Old { start_time: 1234, start_seed: 5678, startup_events: Vec::new(), frames: frames.clone() };
+        let data = bincode::serialize(&old).unwrap();
+        let mut compressed = Vec::new();
+        lz4::compress_to_vec(&data, &mut compressed, lz4::ACC_LEVEL_DEFAULT).unwrap();
+        let path = temp_path("version-1");
+        let mut file = File::create(&path).unwrap();
+        file.write_u32::<LE>(1).unwrap();
+        file.write_u64::<LE>(data.len() as u64).unwrap();
+        file.write_all(&compressed).unwrap();
+        drop(file);
+
+        let loaded = Replay::from_file(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((loaded.start_time, loaded.start_seed), (1234, 5678));
+        assert_eq!(loaded.frames, frames);
+        assert_eq!(loaded.encoding(), Some(encoding_rs::SHIFT_JIS));
+        assert_eq!(loaded.keyboard_layout, KeyboardLayout::Us);
+    }
+}
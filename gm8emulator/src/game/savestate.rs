@@ -1,10 +1,11 @@
 use crate::{
     game::{
-        audio::AudioState, draw, external, includedfile::IncludedFile, model::Model, particle,
-        pathfinding::PotentialStepSettings, surface::Surface, transition::UserTransition, Assets, Game, Replay,
-        RoomState, Version,
+        audio::{cd::CdState, mci::MciState, AudioState},
+        codepage, draw, external, includedfile::IncludedFile, model::Model, particle,
+        pathfinding::PotentialStepSettings, platform::VirtualDisplay, surface::Surface, transition::UserTransition,
+        Assets, Game, Replay, RoomState, Version,
     },
-    gml::{self, ds, rand::Random, Compiler},
+    gml::{self, datetime, ds, rand::Random, Compiler},
     handleman::HandleList,
     input::Input,
     instance::DummyFieldHolder,
@@ -114,8 +115,15 @@ pub struct SaveState {
     unscaled_height: u32,
     window_width: u32,
     window_height: u32,
+    window_offset_spoof: (i32, i32),
+    window_stayontop: bool,
+    virtual_display: VirtualDisplay,
+    encoding: String,
 
     audio_state: AudioState,
+    cd_state: CdState,
+    mci_state: MciState,
+    emulated_state: external::emulated::SavedState,
 
     pub replay: Replay,
     screenshot: Box<[u8]>,
@@ -125,6 +133,7 @@ pub struct SaveState {
 impl SaveState {
     /// Creates a new SaveState from the given components.
     pub fn from(game: &Game, replay: Replay, renderer_state: RendererState, clean_state: bool) -> Self {
+        let now = game.spoofed_time_nanos.unwrap_or_else(datetime::now_as_nanos);
         let (window_width, window_height) = game.renderer.stored_size();
         let screenshot = game.renderer.stored_pixels();
         let zbuffer = game.renderer.stored_zbuffer();
@@ -200,7 +209,14 @@ impl SaveState {
             unscaled_height: game.unscaled_height,
             window_width,
             window_height,
+            window_offset_spoof: game.window_offset_spoof,
+            window_stayontop: game.window_stayontop,
+            virtual_display: game.virtual_display,
+            encoding: game.encoding.name().into(),
             audio_state: game.audio.state(),
+            cd_state: game.cd.state(),
+            mci_state: game.mci.state(now),
+            emulated_state: game.externals.emulated.save(now),
             replay,
             screenshot,
             zbuffer,
@@ -287,9 +303,19 @@ impl SaveState {
         game.gm_version = self.gm_version;
         game.spoofed_time_nanos = self.spoofed_time_nanos;
         game.audio.set_state(self.audio_state);
+        game.cd.set_state(&mut game.audio, self.cd_state);
+        let now = game.spoofed_time_nanos.unwrap_or_else(datetime::now_as_nanos);
+        game.mci.set_state(&mut game.audio, self.mci_state, now);
+        game.externals.emulated.load(&mut game.audio, self.emulated_state, now);
         game.scaling = self.scaling;
         game.unscaled_width = self.unscaled_width;
         game.unscaled_height = self.unscaled_height;
+        game.window_offset_spoof = self.window_offset_spoof;
+        game.window_stayontop = self.window_stayontop;
+        game.virtual_display = self.virtual_display;
+        if let Some(encoding) = codepage::from_label(&self.encoding) {
+            game.encoding = encoding;
+        }
         (self.replay, self.renderer_state)
     }
 
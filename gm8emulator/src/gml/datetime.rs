@@ -44,6 +44,79 @@ fn i32_to_month(m: i32) -> Option<time::Month> {
     })
 }
 
+fn days_in_month(year: i32, month: time::Month) -> u8 {
+    use time::Month::*;
+    match month {
+        February if time::util::is_leap_year(year) => 29,
+        February => 28,
+        April | June | September | November => 30,
+        _ => 31,
+    }
+}
+
+/// Short date format used by DateToStr, in Delphi's format syntax (en-US locale).
+pub const SHORT_DATE_FORMAT: &str = "m/d/yyyy";
+
+/// Long time format used by TimeToStr, in Delphi's format syntax (en-US locale).
+pub const LONG_TIME_FORMAT: &str = "h:nn:ss AMPM";
+
+// Delphi's TDateTime constants used by the DateUtils span and compare functions
+const APPROX_DAYS_PER_YEAR: f64 = 365.25;
+const APPROX_DAYS_PER_MONTH: f64 = 30.4375;
+const ONE_MILLISECOND: f64 = 1.0 / 86400000.0;
+
+/// Delphi's SpanOfNowAndThen. This is a plain subtraction, so it's off by the time part for dates before 1899.
+pub fn span(a: Real, b: Real) -> Real {
+    if a < b { b - a } else { a - b }
+}
+
+pub fn year_span(a: Real, b: Real) -> Real {
+    span(a, b) / APPROX_DAYS_PER_YEAR.into()
+}
+
+pub fn month_span(a: Real, b: Real) -> Real {
+    span(a, b) / APPROX_DAYS_PER_MONTH.into()
+}
+
+/// Delphi's CompareDateTime, which treats values less than a millisecond apart as equal.
+pub fn compare_datetime(a: Real, b: Real) -> i32 {
+    if (a - b).abs() < ONE_MILLISECOND.into() {
+        0
+    } else if a < b {
+        -1
+    } else {
+        1
+    }
+}
+
+pub fn compare_date(a: Real, b: Real) -> i32 {
+    if a.trunc() == b.trunc() {
+        0
+    } else if a < b {
+        -1
+    } else {
+        1
+    }
+}
+
+pub fn compare_time(a: Real, b: Real) -> i32 {
+    compare_datetime(a.fract(), b.fract())
+}
+
+/// Delphi's IncMonth: moves the date part by whole months, clamping the day to the new month,
+/// and copies the time part over unchanged. Returns None if the year leaves the range 1-9999.
+pub fn inc_month(datetime: Real, months: i32) -> Option<Real> {
+    let dt = DateTime::from(datetime);
+    let month_index = i64::from(dt.year()) * 12 + i64::from(dt.month()) - 1 + i64::from(months);
+    let year = i32::try_from(month_index.div_euclid(12)).ok().filter(|y| (1..=9999).contains(y))?;
+    let month = i32_to_month(month_index.rem_euclid(12) as i32 + 1)?;
+    let day = dt.0.day().min(days_in_month(year, month));
+    let date = Real::from(DateTime(time::Date::from_calendar_date(year, month, day).ok()?.midnight()));
+    // ReplaceTime keeps the sign convention of negative datetimes
+    let time = datetime.fract().abs();
+    Some(if date >= 0.into() { date + time } else { date - time })
+}
+
 pub struct DateTime(PrimitiveDateTime);
 
 impl DateTime {
@@ -134,6 +207,117 @@ impl DateTime {
     pub fn weekday(&self) -> u32 {
         self.0.weekday().number_from_sunday().into()
     }
+
+    pub fn days_in_month(&self) -> u32 {
+        days_in_month(self.year(), self.0.month()).into()
+    }
+
+    pub fn days_in_year(&self) -> u32 {
+        time::util::days_in_year(self.year()).into()
+    }
+
+    pub fn is_leap_year(&self) -> bool {
+        time::util::is_leap_year(self.year())
+    }
+
+    /// Formats the datetime like Delphi's FormatDateTime, supporting the date and time specifiers GM8 uses.
+    pub fn format(&self, format: &str) -> String {
+        const DAY_NAMES: [&str; 7] = ["Sunday", "Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday"];
+        let month_name = |m: u32| i32_to_month(m as i32).map(|m| m.to_string()).unwrap_or_default();
+        let hour12 = match self.hour() % 12 {
+            0 => 12,
+            h => h,
+        };
+
+        let chars = format.chars().collect::<Vec<_>>();
+        let mut out = String::new();
+        let mut i = 0;
+        let mut last_was_hour = false;
+        // AM/PM specifiers anywhere in the format switch all hours to 12-hour time
+        let lower = format.to_ascii_lowercase();
+        let use_12_hour = lower.contains("ampm") || lower.contains("am/pm") || lower.contains("a/p");
+        while i < chars.len() {
+            let c = chars[i];
+            let run = chars[i..].iter().take_while(|x| x.eq_ignore_ascii_case(&c)).count();
+            let rest = chars[i..].iter().collect::<String>().to_ascii_lowercase();
+            let hour = if use_12_hour { hour12 } else { self.hour() };
+            match c.to_ascii_lowercase() {
+                '"' | '\'' => {
+                    let end = chars[i + 1..].iter().position(|x| *x == c).map(|p| i + 1 + p).unwrap_or(chars.len());
+                    out.extend(&chars[i + 1..end]);
+                    i = end + 1;
+                    continue
+                },
+                'y' => {
+                    if run <= 2 {
+                        out += &format!("{:02}", self.year() % 100);
+                    } else {
+                        out += &format!("{:04}", self.year());
+                    }
+                },
+                'm' if last_was_hour => {
+                    out += &if run == 1 { self.minute().to_string() } else { format!("{:02}", self.minute()) };
+                },
+                'm' => {
+                    out += &match run {
+                        1 => self.month().to_string(),
+                        2 => format!("{:02}", self.month()),
+                        3 => month_name(self.month())[..3].to_string(),
+                        _ => month_name(self.month()),
+                    };
+                },
+                'd' => {
+                    out += &match run {
+                        1 => self.day().to_string(),
+                        2 => format!("{:02}", self.day()),
+                        3 => DAY_NAMES[self.weekday() as usize - 1][..3].to_string(),
+                        4 => DAY_NAMES[self.weekday() as usize - 1].to_string(),
+                        5 => self.format(SHORT_DATE_FORMAT),
+                        _ => self.format("dddd, mmmm d, yyyy"),
+                    };
+                },
+                'c' => {
+                    // date followed by time, with the time left out at midnight
+                    out += &self.format(SHORT_DATE_FORMAT);
+                    if self.0.time() != time::Time::MIDNIGHT {
+                        out.push(' ');
+                        out += &self.format(LONG_TIME_FORMAT);
+                    }
+                    i += 1;
+                    continue
+                },
+                'h' => out += &if run == 1 { hour.to_string() } else { format!("{:02}", hour) },
+                'n' => out += &if run == 1 { self.minute().to_string() } else { format!("{:02}", self.minute()) },
+                's' => out += &if run == 1 { self.second().to_string() } else { format!("{:02}", self.second()) },
+                't' if run >= 2 => out += &self.format(LONG_TIME_FORMAT),
+                't' => out += &self.format("h:nn AMPM"),
+                'a' if rest.starts_with("ampm") => {
+                    out += if self.hour() < 12 { "AM" } else { "PM" };
+                    i += 4;
+                    continue
+                },
+                'a' if rest.starts_with("am/pm") => {
+                    let s = &chars[i..i + 5];
+                    out.extend(if self.hour() < 12 { &s[..2] } else { &s[3..] });
+                    i += 5;
+                    continue
+                },
+                'a' if rest.starts_with("a/p") => {
+                    out.push(if self.hour() < 12 { chars[i] } else { chars[i + 2] });
+                    i += 3;
+                    continue
+                },
+                _ => {
+                    out.push(c);
+                    i += 1;
+                    continue
+                },
+            }
+            last_was_hour = c.eq_ignore_ascii_case(&'h');
+            i += run;
+        }
+        out
+    }
 }
 
 impl From<DateTime> for Real {
@@ -160,3 +344,69 @@ impl From<Real> for DateTime {
         Self(epoch() + days + if dt > 0.into() { ms } else { -ms })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ymd(datetime: Real) -> (i32, u32, u32) {
+        let dt = DateTime::from(datetime);
+        (dt.year(), dt.month(), dt.day())
+    }
+
+    fn date(y: i32, m: i32, d: i32) -> Real {
+        DateTime::from_ymd(y, m, d).unwrap().into()
+    }
+
+    #[test]
+    fn inc_month_clamps_to_month_end() {
+        assert_eq!(ymd(inc_month(date(2021, 1, 31), 1).unwrap()), (2021, 2, 28));
+        assert_eq!(ymd(inc_month(date(2020, 1, 31), 1).unwrap()), (2020, 2, 29));
+        assert_eq!(ymd(inc_month(date(2020, 3, 31), -1).unwrap()), (2020, 2, 29));
+        assert_eq!(ymd(inc_month(date(2020, 5, 31), 1).unwrap()), (2020, 6, 30));
+        assert_eq!(ymd(inc_month(date(2020, 2, 29), 12).unwrap()), (2021, 2, 28));
+        assert_eq!(ymd(inc_month(date(2020, 12, 15), 1).unwrap()), (2021, 1, 15));
+        assert_eq!(ymd(inc_month(date(2021, 1, 15), -13).unwrap()), (2019, 12, 15));
+        assert!(inc_month(date(9999, 12, 1), 1).is_none());
+        assert!(inc_month(date(1, 1, 1), -1).is_none());
+    }
+
+    #[test]
+    fn inc_month_keeps_time() {
+        let noon = DateTime::from_ymdhms(2021, 1, 31, 12, 0, 0).unwrap();
+        let dt = DateTime::from(inc_month(noon.into(), 1).unwrap());
+        assert_eq!((dt.year(), dt.month(), dt.day(), dt.hour()), (2021, 2, 28, 12));
+    }
+
+    #[test]
+    fn leap_years() {
+        let feb = |y| DateTime::from_ymd(y, 2, 1).unwrap();
+        assert_eq!(feb(2000).days_in_month(), 29);
+        assert_eq!(feb(1900).days_in_month(), 28);
+        assert_eq!(feb(2024).days_in_month(), 29);
+        assert_eq!(feb(2023).days_in_month(), 28);
+        assert_eq!(feb(2024).days_in_year(), 366);
+        assert!(!feb(2100).is_leap_year());
+        assert!(DateTime::from_ymd(2023, 2, 29).is_none());
+        assert_eq!(DateTime::from_ymd(2024, 12, 31).unwrap().day_of_year(), 366);
+    }
+
+    #[test]
+    fn format() {
+        let dt = DateTime::from_ymdhms(2024, 2, 9, 14, 5, 7).unwrap();
+        assert_eq!(dt.format("yyyy-mm-dd hh:nn:ss"), "2024-02-09 14:05:07");
+        assert_eq!(dt.format("yy/m/d h:n:s"), "24/2/9 14:5:7");
+        assert_eq!(dt.format("ddd dddd mmm mmmm"), "Fri Friday Feb February");
+        // m straight after an hour is minutes
+        assert_eq!(dt.format("h:m"), "14:5");
+        assert_eq!(dt.format("h:nn AMPM"), "2:05 PM");
+        assert_eq!(dt.format("hh am/pm a/p"), "02 pm p");
+        assert_eq!(dt.format("'yyyy' \"d\" d"), "yyyy d 9");
+        assert_eq!(dt.format("ddddd"), "2/9/2024");
+        assert_eq!(dt.format("dddddd"), "Friday, February 9, 2024");
+        assert_eq!(dt.format("c"), "2/9/2024 2:05:07 PM");
+        assert_eq!(dt.format("t tt"), "2:05 PM 2:05:07 PM");
+        assert_eq!(dt.date().format("c"), "2/9/2024");
+        assert_eq!(DateTime::from_ymdhms(2024, 2, 9, 0, 30, 0).unwrap().format("h AMPM"), "12 AM");
+    }
+}
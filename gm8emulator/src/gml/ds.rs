@@ -1,6 +1,9 @@
-use crate::{gml::Value, math::Real};
+use crate::{
+    gml::{rand::Random, Value},
+    math::Real,
+};
 use serde::{Deserialize, Serialize};
-use std::{cmp::Ordering, collections};
+use std::{cmp::Ordering, collections, io::Read};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -39,6 +42,30 @@ impl From<Error> for String {
     }
 }
 
+/// Writes a queue as ds_queue_write does, in hex: a 201 header, the length and then each value.
+pub fn write_queue(queue: &Queue) -> String {
+    let mut output = "C9000000".to_string();
+    output.push_str(&hex::encode_upper((queue.len() as u32).to_le_bytes()));
+    output.extend(queue.iter().map(|v| hex::encode_upper(v.as_bytes())));
+    output
+}
+
+/// Reads a queue written by ds_queue_write, after it's been decoded from hex.
+pub fn read_queue(mut reader: &[u8]) -> Option<Queue> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf).ok()?;
+    if u32::from_le_bytes(buf) != 0xc9 {
+        return None
+    }
+    reader.read_exact(&mut buf).ok()?;
+    let size = u32::from_le_bytes(buf) as usize;
+    let mut queue = Queue::with_capacity(size);
+    for _ in 0..size {
+        queue.push_back(Value::from_reader(&mut reader)?);
+    }
+    Some(queue)
+}
+
 impl Map {
     // Returns the index associated with the given key, or None if there is none.
     pub fn get_index(&self, key: &Value, precision: Real) -> Option<usize> {
@@ -239,6 +266,55 @@ impl Grid {
         })
     }
 
+    /// Copies out a region's cells with their positions relative to its top-left corner.
+    /// It's copied so that the ds_grid_*_grid_region functions can apply it to the same grid it came from.
+    pub fn copy_region(&self, x1: i32, y1: i32, x2: i32, y2: i32) -> Vec<((i32, i32), Value)> {
+        let (left, top) = (x1.min(x2), y1.min(y2));
+        self.region_positioned(x1, y1, x2, y2)
+            .map(|((x, y), val)| ((x as i32 - left, y as i32 - top), val.clone()))
+            .collect()
+    }
+
+    /// Combines copied cells into this grid with their corner at the given position, leaving out any past the edge.
+    pub fn apply_region(
+        &mut self,
+        xpos: i32,
+        ypos: i32,
+        cells: Vec<((i32, i32), Value)>,
+        op: impl Fn(&mut Value, Value),
+    ) {
+        for ((x, y), val) in cells {
+            if let Some(cell) = self.get_mut(xpos + x, ypos + y) {
+                op(cell, val);
+            }
+        }
+    }
+
+    /// Shuffles the cells the way ds_grid_shuffle does, with a random swap for each cell after the first.
+    pub fn shuffle(&mut self, rand: &mut Random) {
+        let count = self.width() * self.height();
+        for _ in 1..count {
+            let id1 = rand.next_int(count as u32 - 1);
+            let id2 = rand.next_int(count as u32 - 1);
+            self.swap(id1 as usize, id2 as usize);
+        }
+    }
+
+    /// Swaps two cells, numbered column by column as in `all`
+    pub fn swap(&mut self, i: usize, j: usize) {
+        let height = self.height;
+        if height == 0 {
+            return
+        }
+        let (a, b) = ((i / height, i % height), (j / height, j % height));
+        if a.0 == b.0 {
+            self.grid[a.0].swap(a.1, b.1);
+        } else {
+            let tmp = std::mem::take(&mut self.grid[a.0][a.1]);
+            self.grid[a.0][a.1] = std::mem::replace(&mut self.grid[b.0][b.1], tmp);
+        }
+    }
+
     /// Goes through each column
     pub fn all(&self) -> impl Iterator<Item = &Value> {
         self.grid.iter().flatten()
@@ -258,6 +334,27 @@ impl Grid {
     }
 }
 
+/// The mean of the real values among some grid cells, as ds_grid_get_mean gives it, or 0 if there aren't any.
+pub fn grid_mean<'a>(cells: impl Iterator<Item = &'a Value>) -> Real {
+    let (sum, count) =
+        cells.filter_map(Value::as_real).fold((Real::from(0.0), 0), |(sum, count), x| (sum + x, count + 1));
+    if count == 0 { Real::from(0.0) } else { sum / Real::from(count) }
+}
+
+/// Adds to a grid cell the way ds_grid_add does, replacing it if the types don't match.
+pub fn grid_add(cell: &mut Value, val: Value) {
+    if cell.add_assign(val.clone()).is_err() {
+        *cell = val;
+    }
+}
+
+/// Multiplies a grid cell the way ds_grid_multiply does, leaving it alone unless both are reals.
+pub fn grid_multiply(cell: &mut Value, val: Value) {
+    if let (Value::Real(cell), Value::Real(fac)) = (cell, val) {
+        *cell *= fac;
+    }
+}
+
 pub fn eq(v1: &Value, v2: &Value, precision: Real) -> bool {
     match (v1, v2) {
         (Value::Real(x), Value::Real(y)) => (*x - *y).abs() <= precision,
@@ -280,3 +377,109 @@ pub fn cmp(v1: &Value, v2: &Value, precision: Real) -> Ordering {
         (Value::Str(_), Value::Real(_)) => Ordering::Greater,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(width: usize, height: usize) -> Grid {
+        let mut grid = Grid::new(width, height);
+        for (i, cell) in grid.all_mut().enumerate() {
+            *cell = Value::from(i as i32);
+        }
+        grid
+    }
+
+    fn reals<'a>(values: impl Iterator<Item = &'a Value>) -> Vec<i32> {
+        values.map(|v| v.as_real().unwrap().round().to_i32()).collect()
+    }
+
+    #[test]
+    fn queue_round_trip() {
+        let queue: Queue =
+            vec![Value::from(1), Value::from(-2.5), Value::Str("hello".to_string().into()), Value::from(0)].into();
+        let written = write_queue(&queue);
+        assert!(written.starts_with("C900000004000000"));
+        let read = read_queue(&hex::decode(&written).unwrap()).unwrap();
+        assert_eq!(read, queue);
+
+        assert!(read_queue(&hex::decode(write_queue(&Queue::new())).unwrap()).unwrap().is_empty());
+        // the header has to match, and the values have to all be there
+        assert!(read_queue(&hex::decode("C800000000000000").unwrap()).is_none());
+        assert!(read_queue(&hex::decode(&written[..written.len() - 2]).unwrap()).is_none());
+    }
+
+    #[test]
+    fn regions() {
+        // cells are numbered down each column in turn, so (x, y) holds x * 3 + y
+        let mut grid = grid(4, 3);
+        assert_eq!(reals(grid.region(1, 0, 2, 1)), [3, 4, 6, 7]);
+        // corners can be given either way round, and the region is clipped to the grid
+        assert_eq!(reals(grid.region(2, 1, 1, 0)), [3, 4, 6, 7]);
+        assert_eq!(reals(grid.region(-5, 2, 0, 9)), [2]);
+        assert_eq!(reals(grid.region(4, 0, 9, 9)), [] as [i32; 0]);
+
+        grid.region_mut(0, 0, 1, 0).for_each(|cell| grid_add(cell, Value::from(10)));
+        grid.region_mut(0, 1, 0, 2).for_each(|cell| grid_multiply(cell, Value::from(2)));
+        assert_eq!(reals(grid.region(0, 0, 1, 2)), [10, 2, 4, 13, 4, 5]);
+
+        // adding a string to a real replaces it, and multiplying by one does nothing
+        grid_add(grid.get_mut(0, 0).unwrap(), Value::Str("a".to_string().into()));
+        grid_multiply(grid.get_mut(0, 0).unwrap(), Value::from(2));
+        assert!(matches!(grid.get(0, 0), Some(Value::Str(_))));
+        assert_eq!(grid_mean(grid.region(0, 0, 0, 2)), Real::from(3));
+        assert_eq!(grid_mean(grid.region(0, 0, 0, 0)), Real::from(0));
+        assert_eq!(grid_mean(grid.region(3, 2, 3, 2)), Real::from(11));
+    }
+
+    #[test]
+    fn grid_regions() {
+        let mut grid = grid(4, 3);
+        // copying a region onto an overlapping part of the same grid uses the cells from before the copy
+        let cells = grid.copy_region(1, 1, 0, 0);
+        grid.apply_region(1, 1, cells, |cell, val| *cell = val);
+        assert_eq!(reals(grid.region(0, 0, 3, 2)), [0, 1, 2, 3, 0, 1, 6, 3, 4, 9, 10, 11]);
+
+        // and anything past the edge is left out
+        let cells = grid.copy_region(0, 0, 3, 2);
+        grid.apply_region(2, 1, cells, grid_add);
+        assert_eq!(reals(grid.region(2, 0, 3, 2)), [6, 3, 5, 9, 13, 11]);
+    }
+
+    #[test]
+    fn disks() {
+        let mut grid = grid(5, 5);
+        assert_eq!(reals(grid.disk(Real::from(2), Real::from(2), Real::from(1))), [7, 11, 12, 13, 17]);
+        assert_eq!(reals(grid.disk(Real::from(0), Real::from(0), Real::from(1.5))), [0, 1, 5, 6]);
+        assert_eq!(reals(grid.disk(Real::from(2.5), Real::from(2), Real::from(0.4))), [] as [i32; 0]);
+        grid.disk_mut(Real::from(4), Real::from(4), Real::from(1)).for_each(|cell| *cell = Value::from(-1));
+        assert_eq!(reals(grid.region(3, 3, 4, 4)), [18, -1, -1, -1]);
+        assert_eq!(grid_mean(grid.disk(Real::from(2), Real::from(2), Real::from(1))), Real::from(12));
+    }
+
+    #[test]
+    fn shuffle() {
+        let shuffled = |seed| {
+            let mut grid = grid(4, 3);
+            let mut rand = Random::with_seed(seed);
+            grid.shuffle(&mut rand);
+            (reals(grid.all()), rand.seed())
+        };
+        let (cells, seed) = shuffled(1234);
+        // the same seed gives the same order, it's still the same cells, and it takes two numbers per swap
+        assert_eq!(shuffled(1234), (cells.clone(), seed));
+        assert_ne!(cells, (0..12).collect::<Vec<_>>());
+        let mut sorted = cells;
+        sorted.sort_unstable();
+        assert_eq!(sorted, (0..12).collect::<Vec<_>>());
+        let mut rand = Random::with_seed(1234);
+        (0..22).for_each(|_| rand.cycle());
+        assert_eq!(seed, rand.seed());
+
+        // a grid with one cell or none doesn't use any
+        let mut rand = Random::with_seed(1234);
+        Grid::new(1, 1).shuffle(&mut rand);
+        Grid::new(0, 3).shuffle(&mut rand);
+        assert_eq!(rand.seed(), 1234);
+    }
+}
@@ -1,3 +1,5 @@
+use byteorder::{ReadBytesExt, LE};
+use flate2::read::ZlibDecoder;
 use image::{codecs::gif::GifDecoder, AnimationDecoder, ImageError, ImageFormat, Pixel, RgbaImage};
 use std::{
     fs::{File, OpenOptions},
@@ -34,6 +36,7 @@ pub enum Error {
     CantWrite,
     IOError(io::Error),
     ImageError(ImageError),
+    InvalidFormat,
 }
 
 impl From<io::Error> for Error {
@@ -57,6 +60,7 @@ impl std::fmt::Display for Error {
             Self::CantWrite => write!(f, "file is not open for writing"),
             Self::IOError(err) => write!(f, "io error: {}", err),
             Self::ImageError(err) => write!(f, "image error: {}", err),
+            Self::InvalidFormat => write!(f, "invalid file format"),
         }
     }
 }
@@ -454,3 +458,88 @@ pub fn save_image<P: AsRef<Path>>(path: P, image: RgbaImage) -> Result<()> {
     }
     Ok(())
 }
+
+/// The contents of a .gmspr file, as saved by the GM8 sprite editor.
+pub struct SpriteFile {
+    pub origin_x: i32,
+    pub origin_y: i32,
+    pub frames: Vec<RgbaImage>,
+    pub shape: u32,
+    pub alpha_tolerance: u32,
+    pub sepmasks: bool,
+    pub bbox_mode: u32,
+    pub bbox_left: i32,
+    pub bbox_right: i32,
+    pub bbox_bottom: i32,
+    pub bbox_top: i32,
+}
+
+const GM_FILE_MAGIC: u32 = 1234321;
+
+/// Opens a GM8 resource file (.gmspr, .gmbck) and returns a reader for its contents.
+/// The body after the header is zlib-compressed in files from GM8, but older versions stored it as-is.
+fn open_resource_file(path: &str) -> Result<Box<dyn Read>> {
+    let mut file = BufReader::new(File::open(path)?);
+    if file.read_u32::<LE>()? != GM_FILE_MAGIC || file.read_u32::<LE>()? < 800 {
+        return Err(Error::InvalidFormat)
+    }
+    let mut body = Vec::new();
+    file.read_to_end(&mut body)?;
+    if body.first() == Some(&0x78) { Ok(Box::new(ZlibDecoder::new(io::Cursor::new(body)))) } else { Ok(Box::new(io::Cursor::new(body))) }
+}
+
+/// Reads a versioned image block: version, width, height and BGRA pixeldata if the image isn't empty.
+fn read_resource_image(mut reader: impl Read) -> Result<RgbaImage> {
+    let _version = reader.read_u32::<LE>()?;
+    let width = reader.read_u32::<LE>()?;
+    let height = reader.read_u32::<LE>()?;
+    if width == 0 || height == 0 {
+        return Ok(RgbaImage::new(1, 1))
+    }
+    let len = reader.read_u32::<LE>()? as usize;
+    if len != width as usize * height as usize * 4 {
+        return Err(Error::InvalidFormat)
+    }
+    let mut data = vec![0; len];
+    reader.read_exact(&mut data)?;
+    for px in data.chunks_exact_mut(4) {
+        px.swap(0, 2);
+    }
+    RgbaImage::from_vec(width, height, data).ok_or(Error::InvalidFormat)
+}
+
+pub fn load_sprite_file(path: &str) -> Result<SpriteFile> {
+    let mut reader = open_resource_file(path)?;
+    let _version = reader.read_u32::<LE>()?;
+    let origin_x = reader.read_i32::<LE>()?;
+    let origin_y = reader.read_i32::<LE>()?;
+    let frame_count = reader.read_u32::<LE>()?;
+    let frames = (0..frame_count).map(|_| read_resource_image(&mut reader)).collect::<Result<Vec<_>>>()?;
+    if frames.is_empty() {
+        return Err(Error::InvalidFormat)
+    }
+    Ok(SpriteFile {
+        origin_x,
+        origin_y,
+        frames,
+        shape: reader.read_u32::<LE>()?,
+        alpha_tolerance: reader.read_u32::<LE>()?,
+        sepmasks: reader.read_u32::<LE>()? != 0,
+        bbox_mode: reader.read_u32::<LE>()?,
+        bbox_left: reader.read_i32::<LE>()?,
+        bbox_right: reader.read_i32::<LE>()?,
+        bbox_bottom: reader.read_i32::<LE>()?,
+        bbox_top: reader.read_i32::<LE>()?,
+    })
+}
+
+/// Loads the image from a .gmbck file. The tileset settings are skipped as they're not accessible at runtime.
+pub fn load_background_file(path: &str) -> Result<RgbaImage> {
+    let mut reader = open_resource_file(path)?;
+    let _version = reader.read_u32::<LE>()?;
+    // use as tileset, tile width and height, offsets and separation
+    for _ in 0..7 {
+        reader.read_u32::<LE>()?;
+    }
+    read_resource_image(reader)
+}
@@ -5,7 +5,7 @@
 use crate::{
     action, asset,
     game::{
-        draw, external, gm_save::GMSave, model, particle, pathfinding, platform, replay, surface::Surface,
+        audio, draw, external, gm_save::GMSave, model, particle, pathfinding, platform, replay, surface::Surface,
         transition::UserTransition, view::View, Game, GetAsset, PlayType, SceneChange, Version,
     },
     gml::{
@@ -16,7 +16,7 @@ use crate::{
         network, Context, Value,
     },
     handleman::HandleManager,
-    input::MouseButton,
+    input::{self, MouseButton},
     instance::{Field, Instance, InstanceState},
     math::Real,
     render::{BlendType, Fog, Light, Renderer, Scaling},
@@ -94,93 +94,161 @@ fn rgb_to_hsv(colour: i32) -> (i32, i32, i32) {
     (h, s, v)
 }
 
+/// An installed font with no glyphs yet, as given to font_add and font_replace.
+fn system_font(
+    name: gml::String,
+    sys_name: gml::String,
+    size: i32,
+    bold: bool,
+    italic: bool,
+    first: i32,
+    last: i32,
+) -> asset::Font {
+    let first = first.clamp(0, 255) as u8;
+    let last = (last.clamp(0, 255) as u8).max(first);
+    asset::Font {
+        name,
+        sys_name,
+        charset: 1, // DEFAULT_CHARSET
+        size: size.max(1) as u32,
+        bold,
+        italic,
+        first,
+        last,
+        tallest_char_height: 0,
+        chars: Box::new([]),
+        own_graphics: true,
+    }
+}
+
 impl Game {
     pub fn display_get_width(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        #[cfg(target_os = "windows")]
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
         if self.play_type == PlayType::Normal {
-            return platform::display_width().map(Value::from).ok_or_else(|| {
-                gml::Error::FunctionError("display_get_width".into(), "getting display width failed".into())
-            });
+            if let Some(width) = platform::display_width() {
+                return Ok(width.into())
+            }
         }
-        Ok(1280.into())
+        Ok(self.virtual_display.width.into())
     }
 
     pub fn display_get_height(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        #[cfg(target_os = "windows")]
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
         if self.play_type == PlayType::Normal {
-            return platform::display_height().map(Value::from).ok_or_else(|| {
-                gml::Error::FunctionError("display_get_height".into(), "getting display height failed".into())
-            });
+            if let Some(height) = platform::display_height() {
+                return Ok(height.into())
+            }
         }
-        Ok(720.into())
+        Ok(self.virtual_display.height.into())
     }
 
     pub fn display_get_colordepth(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        #[cfg(target_os = "windows")]
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
         if self.play_type == PlayType::Normal {
-            return platform::display_colour_depth().map(Value::from).ok_or_else(|| {
-                gml::Error::FunctionError("display_get_colordepth".into(), "getting display colour depth failed".into())
-            });
+            if let Some(colour_depth) = platform::display_colour_depth() {
+                return Ok(colour_depth.into())
+            }
         }
-        Ok(32.into())
+        Ok(self.virtual_display.colour_depth.into())
     }
 
     pub fn display_get_frequency(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        #[cfg(target_os = "windows")]
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
         if self.play_type == PlayType::Normal {
-            return platform::display_frequency().map(Value::from).ok_or_else(|| {
-                gml::Error::FunctionError("display_get_frequency".into(), "getting display frequency failed".into())
-            });
+            if let Some(frequency) = platform::display_frequency() {
+                return Ok(frequency.into())
+            }
         }
-        Ok(60.into())
+        Ok(self.virtual_display.frequency.into())
     }
 
-    pub fn display_set_size(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function display_set_size")
+    /// Changes the display mode like display_set_all, where -1 leaves a setting as it is.
+    fn set_display(&mut self, width: i32, height: i32, frequency: i32, colour_depth: i32) -> bool {
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if self.play_type == PlayType::Normal && platform::has_display() {
+            let [width, height, frequency, colour_depth] =
+                [width, height, frequency, colour_depth].map(|x| x.max(0) as u32);
+            return platform::set_display(width, height, frequency, colour_depth)
+        }
+        self.virtual_display.set(width, height, frequency, colour_depth)
     }
 
-    pub fn display_set_colordepth(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function display_set_colordepth")
+    pub fn display_set_size(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (width, height) = expect_args!(args, [int, int])?;
+        Ok(self.set_display(width, height, -1, -1).into())
     }
 
-    pub fn display_set_frequency(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function display_set_frequency")
+    pub fn display_set_colordepth(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let colour_depth = expect_args!(args, [int])?;
+        Ok(self.set_display(-1, -1, -1, colour_depth).into())
     }
 
-    pub fn display_set_all(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function display_set_all")
+    pub fn display_set_frequency(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let frequency = expect_args!(args, [int])?;
+        Ok(self.set_display(-1, -1, frequency, -1).into())
     }
 
-    pub fn display_test_all(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function display_test_all")
+    pub fn display_set_all(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (width, height, frequency, colour_depth) = expect_args!(args, [int, int, int, int])?;
+        Ok(self.set_display(width, height, frequency, colour_depth).into())
     }
 
-    pub fn display_reset(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        unimplemented!("Called unimplemented kernel function display_reset")
+    pub fn display_test_all(&self, args: &[Value]) -> gml::Result<Value> {
+        let (width, height, frequency, colour_depth) = expect_args!(args, [int, int, int, int])?;
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if self.play_type == PlayType::Normal && platform::has_display() {
+            let [width, height, frequency, colour_depth] =
+                [width, height, frequency, colour_depth].map(|x| x.max(0) as u32);
+            return Ok(platform::test_display(width, height, frequency, colour_depth).into())
+        }
+        Ok(platform::VirtualDisplay::test(width, height, frequency, colour_depth).into())
     }
 
-    pub fn display_mouse_get_x(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function display_mouse_get_x")
+    pub fn display_reset(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if self.play_type == PlayType::Normal {
+            platform::reset_display();
+        }
+        self.virtual_display = Default::default();
+        Ok(Default::default())
     }
 
-    pub fn display_mouse_get_y(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function display_mouse_get_y")
+    /// The mouse position on the display. The virtual display has the window at its spoofed position.
+    fn display_mouse_position(&self) -> (i32, i32) {
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if self.play_type == PlayType::Normal {
+            if let Some(position) = platform::display_mouse_position() {
+                return position
+            }
+        }
+        let (window_x, window_y) = self.window_offset_spoof;
+        (window_x + self.input.mouse_x(), window_y + self.input.mouse_y())
     }
 
-    pub fn display_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function display_mouse_set")
+    pub fn display_mouse_get_x(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.display_mouse_position().0.into())
+    }
+
+    pub fn display_mouse_get_y(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.display_mouse_position().1.into())
+    }
+
+    pub fn display_mouse_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y) = expect_args!(args, [int, int])?;
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if self.play_type == PlayType::Normal {
+            platform::set_display_mouse_position(x, y);
+        }
+        let (window_x, window_y) = self.window_offset_spoof;
+        self.input.mouse_move_to((x - window_x, y - window_y));
+        Ok(Default::default())
     }
 
     pub fn window_set_visible(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -241,14 +309,21 @@ impl Game {
         Ok(self.window_icons.into())
     }
 
-    pub fn window_set_stayontop(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function window_set_stayontop")
+    pub fn window_set_stayontop(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let stay_on_top = expect_args!(args, [bool])?;
+        if stay_on_top != self.window_stayontop {
+            self.window_stayontop = stay_on_top;
+            #[cfg(any(target_os = "windows", target_os = "linux"))]
+            if self.play_type == PlayType::Normal {
+                platform::set_stay_on_top(&self.window, stay_on_top);
+            }
+        }
+        Ok(Default::default())
     }
 
-    pub fn window_get_stayontop(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_get_stayontop")
+    pub fn window_get_stayontop(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.window_stayontop.into())
     }
 
     pub fn window_set_sizeable(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -268,18 +343,19 @@ impl Game {
     }
 
     pub fn window_set_caption(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let caption = expect_args!(args, [string])?;
+        let caption = expect_args!(args, [bytes])?;
+        let caption = self.decode_str(caption.as_ref()).into_owned();
         if self.play_type == PlayType::Record {
-            self.window.set_title(caption.as_ref());
+            self.window.set_title(&caption);
         }
-        self.window_caption = caption.into_owned();
+        self.window_caption = caption;
         Ok(Default::default())
     }
 
     // NB: This function is constant because caption gets updated on every frame.
     pub fn window_get_caption(&self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        Ok(self.window_caption.clone().into())
+        Ok(self.encode_str(&self.window_caption).as_ref().into())
     }
 
     pub fn window_set_cursor(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -335,9 +411,31 @@ impl Game {
         Ok(u32::from(self.background_colour).into())
     }
 
+    fn move_window(&mut self, x: i32, y: i32) {
+        self.window_offset_spoof = (x, y);
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if self.play_type == PlayType::Normal {
+            platform::set_window_position(&self.window, x, y);
+        }
+    }
+
+    /// Centres the window on the display, as the game sees it.
+    fn center_window(&mut self) {
+        let mut display_size = (self.virtual_display.width, self.virtual_display.height);
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if self.play_type == PlayType::Normal {
+            if let (Some(width), Some(height)) = (platform::display_width(), platform::display_height()) {
+                display_size = (width, height);
+            }
+        }
+        let (display_width, display_height) = display_size;
+        let (width, height) = self.window_inner_size;
+        self.move_window((display_width as i32 - width as i32) / 2, (display_height as i32 - height as i32) / 2);
+    }
+
     pub fn window_set_position(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (x, y) = expect_args!(args, [int, int])?;
-        self.window_offset_spoof = (x, y);
+        self.move_window(x, y);
         Ok(Default::default())
     }
 
@@ -350,19 +448,31 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn window_set_rectangle(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function window_set_rectangle")
+    pub fn window_set_rectangle(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y, width, height) = expect_args!(args, [int, int, int, int])?;
+        self.window_set_size(&[width.into(), height.into()])?;
+        self.move_window(x, y);
+        Ok(Default::default())
     }
 
-    pub fn window_center(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // TODO: make this do something!
+    pub fn window_center(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.center_window();
         Ok(Default::default())
     }
 
-    pub fn window_default(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_default")
+    pub fn window_default(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        let (width, height) = match self.scaling {
+            Scaling::Fixed(n) => {
+                ((f64::from(self.unscaled_width) * n) as u32, (f64::from(self.unscaled_height) * n) as u32)
+            },
+            _ => (self.unscaled_width, self.unscaled_height),
+        };
+        self.window_inner_size = (width, height);
+        self.window.set_size((width as _, height as _));
+        self.center_window();
+        Ok(Default::default())
     }
 
     pub fn window_get_x(&self, args: &[Value]) -> gml::Result<Value> {
@@ -446,39 +556,68 @@ impl Game {
         Ok(self.input.mouse_y().into())
     }
 
-    pub fn window_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function window_mouse_set")
+    /// Moves the mouse to a point in the window, both for real and in the game's input state.
+    fn move_mouse(&mut self, x: i32, y: i32) {
+        #[cfg(any(target_os = "windows", target_os = "linux"))]
+        if self.play_type == PlayType::Normal {
+            platform::set_window_mouse_position(&self.window, x, y);
+        }
+        self.input.mouse_move_to((x, y));
     }
 
-    pub fn window_view_mouse_get_x(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function window_view_mouse_get_x")
+    pub fn window_mouse_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y) = expect_args!(args, [int, int])?;
+        self.move_mouse(x, y);
+        Ok(Default::default())
     }
 
-    pub fn window_view_mouse_get_y(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function window_view_mouse_get_y")
+    pub fn window_view_mouse_get_x(&self, args: &[Value]) -> gml::Result<Value> {
+        let view_id = expect_args!(args, [int])?;
+        let (x, y) = (self.input.mouse_x(), self.input.mouse_y());
+        match self.room.views.get(view_id as usize) {
+            Some(view) => Ok(view.transform_point(x, y).0.into()),
+            None => Ok(x.into()),
+        }
     }
 
-    pub fn window_view_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function window_view_mouse_set")
+    pub fn window_view_mouse_get_y(&self, args: &[Value]) -> gml::Result<Value> {
+        let view_id = expect_args!(args, [int])?;
+        let (x, y) = (self.input.mouse_x(), self.input.mouse_y());
+        match self.room.views.get(view_id as usize) {
+            Some(view) => Ok(view.transform_point(x, y).1.into()),
+            None => Ok(y.into()),
+        }
     }
 
-    pub fn window_views_mouse_get_x(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_views_mouse_get_x")
+    pub fn window_view_mouse_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (view_id, x, y) = expect_args!(args, [int, int, int])?;
+        let (x, y) = match self.room.views.get(view_id as usize) {
+            Some(view) => view.untransform_point(x, y),
+            None => (x, y),
+        };
+        self.move_mouse(x, y);
+        Ok(Default::default())
     }
 
-    pub fn window_views_mouse_get_y(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function window_views_mouse_get_y")
+    pub fn window_views_mouse_get_x(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.get_mouse_in_room().0.into())
     }
 
-    pub fn window_views_mouse_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function window_views_mouse_set")
+    pub fn window_views_mouse_get_y(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.get_mouse_in_room().1.into())
+    }
+
+    pub fn window_views_mouse_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y) = expect_args!(args, [int, int])?;
+        let view = if self.room.views_enabled { self.room.views.iter().find(|view| view.visible) } else { None };
+        let (x, y) = match view {
+            Some(view) => view.untransform_point(x, y),
+            None => (x, y),
+        };
+        self.move_mouse(x, y);
+        Ok(Default::default())
     }
 
     pub fn set_synchronization(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -519,7 +658,8 @@ impl Game {
     }
 
     pub fn screen_save(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let fname = expect_args!(args, [string])?;
+        let fname = expect_args!(args, [bytes])?;
+        let fname = self.decode_str(fname.as_ref());
         self.renderer.flush_queue();
         let (width, height) = (self.unscaled_width, self.unscaled_height);
         let rgba = self.renderer.get_pixels(0, 0, width as _, height as _);
@@ -532,7 +672,8 @@ impl Game {
     }
 
     pub fn screen_save_part(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (fname, x, y, w, h) = expect_args!(args, [string, int, int, int, int])?;
+        let (fname, x, y, w, h) = expect_args!(args, [bytes, int, int, int, int])?;
+        let fname = self.decode_str(fname.as_ref());
         let x = x.max(0);
         let y = y.max(0);
         let w = w.min(self.unscaled_width as i32 - x);
@@ -2063,9 +2204,17 @@ impl Game {
         Ok(self.last_tile_id.into())
     }
 
-    pub fn tile_find(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function tile_find")
+    pub fn tile_find(&self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y, foreground) = expect_args!(args, [real, real, bool])?;
+        let use_scaling = self.gm_version == Version::GameMaker8_1; // 8.1 bugfix
+        let mut iter_tile = self.room.tile_list.iter_by_drawing();
+        while let Some(handle) = iter_tile.next(&self.room.tile_list) {
+            let tile = self.room.tile_list.get(handle);
+            if tile.is_at(x, y, foreground, use_scaling) {
+                return Ok(tile.id.get().into())
+            }
+        }
+        Ok((-1).into())
     }
 
     pub fn tile_exists(&self, args: &[Value]) -> gml::Result<Value> {
@@ -2083,9 +2232,11 @@ impl Game {
         }
     }
 
-    pub fn tile_delete_at(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function tile_delete_at")
+    pub fn tile_delete_at(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (x, y, foreground) = expect_args!(args, [real, real, bool])?;
+        let use_scaling = self.gm_version == Version::GameMaker8_1; // 8.1 bugfix
+        self.room.tile_list.remove_with(|tile| tile.is_at(x, y, foreground, use_scaling));
+        Ok(Default::default())
     }
 
     pub fn tile_layer_hide(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -2131,18 +2282,15 @@ impl Game {
         Ok(Default::default())
     }
 
+    /// Whether a point is on a tile, for the functions which find tiles by position.
+    /// Before 8.1 these ignored the tile's size, so nothing was ever found.
     pub fn tile_layer_find(&self, args: &[Value]) -> gml::Result<Value> {
         let (depth, x, y) = expect_args!(args, [real, real, real])?;
         let use_scaling = self.gm_version == Version::GameMaker8_1; // 8.1 bugfix
         let mut iter_tile = self.room.tile_list.iter_by_drawing();
         while let Some(handle) = iter_tile.next(&self.room.tile_list) {
             let tile = self.room.tile_list.get(handle);
-            if tile.depth.get() == depth
-                && x >= tile.x.get()
-                && x < tile.x.get() + if use_scaling { tile.xscale.get() } else { 0.into() } * tile.width.get().into()
-                && y >= tile.y.get()
-                && y < tile.y.get() + if use_scaling { tile.yscale.get() } else { 0.into() } * tile.height.get().into()
-            {
+            if tile.depth.get() == depth && tile.contains(x, y, use_scaling) {
                 return Ok(tile.id.get().into())
             }
         }
@@ -2152,13 +2300,9 @@ impl Game {
     pub fn tile_layer_delete_at(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (depth, x, y) = expect_args!(args, [real, real, real])?;
         let use_scaling = self.gm_version == Version::GameMaker8_1; // 8.1 bugfix
-        self.room.tile_list.remove_with(|tile| {
-            tile.depth.get() == depth
-                && x >= tile.x.get()
-                && x < tile.x.get() + if use_scaling { tile.xscale.get() } else { 0.into() } * tile.width.get().into()
-                && y >= tile.y.get()
-                && y < tile.y.get() + if use_scaling { tile.yscale.get() } else { 0.into() } * tile.height.get().into()
-        });
+        self.room
+            .tile_list
+            .remove_with(|tile| tile.depth.get() == depth && tile.contains(x, y, use_scaling));
         Ok(Default::default())
     }
 
@@ -2423,7 +2567,8 @@ impl Game {
     }
 
     pub fn surface_save(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (surf_id, fname) = expect_args!(args, [int, string])?;
+        let (surf_id, fname) = expect_args!(args, [int, bytes])?;
+        let fname = self.decode_str(fname.as_ref());
         if Some(surf_id) == self.surface_target {
             self.renderer.flush_queue();
         }
@@ -2441,7 +2586,8 @@ impl Game {
     }
 
     pub fn surface_save_part(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (surf_id, fname, x, y, w, h) = expect_args!(args, [int, string, int, int, int, int])?;
+        let (surf_id, fname, x, y, w, h) = expect_args!(args, [int, bytes, int, int, int, int])?;
+        let fname = self.decode_str(fname.as_ref());
         if Some(surf_id) == self.surface_target {
             self.renderer.flush_queue();
         }
@@ -4824,7 +4970,7 @@ impl Game {
                     self.check_collision_solid(context.this).is_some()
                 }
             };
-            pathfinding::make_path(inst, &mut path, |inst| {
+            let found = pathfinding::make_path(inst, &mut path, |inst| {
                 let (old_x, old_y) = (inst.x.get(), inst.y.get());
                 if pathfinding::linear_step(xg, yg, step_size, inst, coll) {
                     pathfinding::PathGenResult::Done
@@ -4835,7 +4981,7 @@ impl Game {
                 }
             });
             self.assets.paths[path_id as usize] = Some(path);
-            Ok(Default::default())
+            Ok(found.into())
         } else {
             Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id))
         }
@@ -4851,9 +4997,33 @@ impl Game {
         .into())
     }
 
-    pub fn mp_linear_path_object(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function mp_linear_path_object")
+    pub fn mp_linear_path_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, obj) = expect_args!(args, [int, real, real, real, int])?;
+        // see mp_linear_path for why the path is taken out
+        if let Some(mut path) =
+            usize::try_from(path_id).ok().and_then(|id| self.assets.paths.get_mut(id)).and_then(Option::take)
+        {
+            let inst = self.room.instance_list.get(context.this);
+            let coll = || match obj {
+                gml::SELF => false,
+                gml::OTHER => self.check_collision(context.this, context.other),
+                obj => self.find_instance_with(obj, |handle| self.check_collision(context.this, handle)).is_some(),
+            };
+            let found = pathfinding::make_path(inst, &mut path, |inst| {
+                let (old_x, old_y) = (inst.x.get(), inst.y.get());
+                if pathfinding::linear_step(xg, yg, step_size, inst, coll) {
+                    pathfinding::PathGenResult::Done
+                } else if inst.x.get() == old_x && inst.y.get() == old_y {
+                    pathfinding::PathGenResult::Failed
+                } else {
+                    pathfinding::PathGenResult::NotDone
+                }
+            });
+            self.assets.paths[path_id as usize] = Some(path);
+            Ok(found.into())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id))
+        }
     }
 
     pub fn mp_potential_settings(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -4886,9 +5056,15 @@ impl Game {
         .into())
     }
 
-    pub fn mp_potential_path(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function mp_potential_path")
+    pub fn mp_potential_path(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, factor, checkall) = expect_args!(args, [int, real, real, real, real, bool])?;
+        self.potential_path(context, path_id, xg, yg, step_size, factor, |game: &Self| {
+            if checkall {
+                game.check_collision_any(context.this).is_some()
+            } else {
+                game.check_collision_solid(context.this).is_some()
+            }
+        })
     }
 
     pub fn mp_potential_step_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -4908,9 +5084,55 @@ impl Game {
         .into())
     }
 
-    pub fn mp_potential_path_object(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function mp_potential_path_object")
+    pub fn mp_potential_path_object(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, xg, yg, step_size, factor, obj) = expect_args!(args, [int, real, real, real, real, int])?;
+        self.potential_path(context, path_id, xg, yg, step_size, factor, |game: &Self| match obj {
+            gml::SELF => false,
+            gml::OTHER => game.check_collision(context.this, context.other),
+            obj => game.find_instance_with(obj, |handle| game.check_collision(context.this, handle)).is_some(),
+        })
+    }
+
+    /// Shared implementation of mp_potential_path and mp_potential_path_object.
+    /// The search gives up once the steps taken add up to more than `factor` times the straight distance to the goal.
+    fn potential_path(
+        &mut self,
+        context: &Context,
+        path_id: i32,
+        xg: Real,
+        yg: Real,
+        step_size: Real,
+        factor: Real,
+        coll: impl Fn(&Self) -> bool,
+    ) -> gml::Result<Value> {
+        // see mp_linear_path for why the path is taken out
+        if let Some(mut path) =
+            usize::try_from(path_id).ok().and_then(|id| self.assets.paths.get_mut(id)).and_then(Option::take)
+        {
+            let inst = self.room.instance_list.get(context.this);
+            let distance = (xg - inst.x.get()).into_inner().hypot((yg - inst.y.get()).into_inner());
+            let max_steps =
+                if step_size > 0.into() { (factor * Real::from(distance) / step_size).ceil() } else { 0.into() };
+            let steps = std::cell::Cell::new(Real::from(0));
+            let found = pathfinding::make_path(inst, &mut path, |inst| {
+                let (old_x, old_y) = (inst.x.get(), inst.y.get());
+                pathfinding::potential_step(xg, yg, step_size, &self.potential_step_settings, inst, || coll(self));
+                steps.set(steps.get() + 1.into());
+                if inst.x.get() == xg && inst.y.get() == yg {
+                    pathfinding::PathGenResult::Done
+                } else if steps.get() > max_steps
+                    || (!self.potential_step_settings.rotate_on_spot && inst.x.get() == old_x && inst.y.get() == old_y)
+                {
+                    pathfinding::PathGenResult::Failed
+                } else {
+                    pathfinding::PathGenResult::NotDone
+                }
+            });
+            self.assets.paths[path_id as usize] = Some(path);
+            Ok(found.into())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id))
+        }
     }
 
     pub fn mp_grid_create(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -4943,11 +5165,7 @@ impl Game {
         let id = expect_args!(args, [int])?;
         match self.mpgrids.get_mut(id) {
             Some(mpgrid) => {
-                for x in 0..mpgrid.hcells {
-                    for y in 0..mpgrid.vcells {
-                        mpgrid.set(x, y, 0);
-                    }
-                }
+                mpgrid.set_all(0);
                 Ok(Default::default())
             },
             None => Err(gml::Error::FunctionError(
@@ -4961,9 +5179,7 @@ impl Game {
         let (id, x, y) = expect_args!(args, [int, int, int])?;
         match self.mpgrids.get_mut(id) {
             Some(mpgrid) => {
-                if x >= 0 && y >= 0 {
-                    mpgrid.set(x as usize, y as usize, 0);
-                }
+                mpgrid.set_cell(x, y, 0);
                 Ok(Default::default())
             },
             None => Err(gml::Error::FunctionError(
@@ -4977,24 +5193,11 @@ impl Game {
         let (id, left, top, right, bottom) = expect_args!(args, [int, int, int, int, int])?;
         match self.mpgrids.get_mut(id) {
             Some(mpgrid) => {
-                let (left, right) = if right < left { (right, left) } else { (left, right) };
-                let (top, bottom) = if bottom < top { (bottom, top) } else { (top, bottom) };
-
-                let gl = (((left - mpgrid.left) / mpgrid.cellwidth).max(0) as usize).min(mpgrid.hcells - 1);
-                let gt = (((top - mpgrid.top) / mpgrid.cellheight).max(0) as usize).min(mpgrid.vcells - 1);
-                let gr = (((right - mpgrid.left) / mpgrid.cellwidth).max(0) as usize).min(mpgrid.hcells - 1);
-                let gb = (((bottom - mpgrid.top) / mpgrid.cellheight).max(0) as usize).min(mpgrid.vcells - 1);
-
-                for x in gl..=gr {
-                    for y in gt..=gb {
-                        mpgrid.set(x, y, 0);
-                    }
-                }
-
+                mpgrid.set_rectangle(left, top, right, bottom, 0);
                 Ok(Default::default())
             },
             None => Err(gml::Error::FunctionError(
-                "mp_grid_add_rectangle".into(),
+                "mp_grid_clear_rectangle".into(),
                 pathfinding::Error::NonexistentStructure(id).into(),
             )),
         }
@@ -5004,13 +5207,11 @@ impl Game {
         let (id, x, y) = expect_args!(args, [int, int, int])?;
         match self.mpgrids.get_mut(id) {
             Some(mpgrid) => {
-                if x >= 0 && y >= 0 {
-                    mpgrid.set(x as usize, y as usize, -1);
-                }
+                mpgrid.set_cell(x, y, -1);
                 Ok(Default::default())
             },
             None => Err(gml::Error::FunctionError(
-                "mp_grid_clear_cell".into(),
+                "mp_grid_add_cell".into(),
                 pathfinding::Error::NonexistentStructure(id).into(),
             )),
         }
@@ -5020,20 +5221,7 @@ impl Game {
         let (id, left, top, right, bottom) = expect_args!(args, [int, int, int, int, int])?;
         match self.mpgrids.get_mut(id) {
             Some(mpgrid) => {
-                let (left, right) = if right < left { (right, left) } else { (left, right) };
-                let (top, bottom) = if bottom < top { (bottom, top) } else { (top, bottom) };
-
-                let gl = (((left - mpgrid.left) / mpgrid.cellwidth).max(0) as usize).min(mpgrid.hcells - 1);
-                let gt = (((top - mpgrid.top) / mpgrid.cellheight).max(0) as usize).min(mpgrid.vcells - 1);
-                let gr = (((right - mpgrid.left) / mpgrid.cellwidth).max(0) as usize).min(mpgrid.hcells - 1);
-                let gb = (((bottom - mpgrid.top) / mpgrid.cellheight).max(0) as usize).min(mpgrid.vcells - 1);
-
-                for x in gl..=gr {
-                    for y in gt..=gb {
-                        mpgrid.set(x, y, -1);
-                    }
-                }
-
+                mpgrid.set_rectangle(left, top, right, bottom, -1);
                 Ok(Default::default())
             },
             None => Err(gml::Error::FunctionError(
@@ -5043,14 +5231,86 @@ impl Game {
         }
     }
 
-    pub fn mp_grid_add_instances(&mut self, _context: &mut Context, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mp_grid_add_instances")
+    pub fn mp_grid_add_instances(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
+        let (id, obj, precise) = expect_args!(args, [int, int, bool])?;
+        let mut mpgrid = match self.mpgrids.get(id) {
+            Some(mpgrid) => mpgrid.clone(),
+            None => {
+                return Err(gml::Error::FunctionError(
+                    "mp_grid_add_instances".into(),
+                    pathfinding::Error::NonexistentStructure(id).into(),
+                ))
+            },
+        };
+        if mpgrid.hcells == 0 || mpgrid.vcells == 0 {
+            return Ok(Default::default())
+        }
+        let handles = std::cell::RefCell::new(Vec::new());
+        match obj {
+            gml::SELF => handles.borrow_mut().push(context.this),
+            gml::OTHER => handles.borrow_mut().push(context.other),
+            obj => {
+                self.find_instance_with(obj, |handle| {
+                    handles.borrow_mut().push(handle);
+                    false
+                });
+            },
+        }
+        let grid_right = mpgrid.left + mpgrid.hcells as i32 * mpgrid.cellwidth - 1;
+        let grid_bottom = mpgrid.top + mpgrid.vcells as i32 * mpgrid.cellheight - 1;
+        for handle in handles.into_inner() {
+            // this also brings the instance's bbox up to date
+            if !self.check_collision_rectangle(handle, mpgrid.left, mpgrid.top, grid_right, grid_bottom, false) {
+                continue
+            }
+            // only check the cells overlapping the bounding box
+            let inst = self.room.instance_list.get(handle);
+            let bbox = (inst.bbox_left.get(), inst.bbox_top.get(), inst.bbox_right.get(), inst.bbox_bottom.get());
+            mpgrid.add_instance(bbox, |x1, y1, x2, y2| self.check_collision_rectangle(handle, x1, y1, x2, y2, precise));
+        }
+        if let Some(grid) = self.mpgrids.get_mut(id) {
+            *grid = mpgrid;
+        }
+        Ok(Default::default())
     }
 
-    pub fn mp_grid_path(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function mp_grid_path")
+    pub fn mp_grid_path(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, path_id, xstart, ystart, xgoal, ygoal, allowdiag) =
+            expect_args!(args, [int, int, real, real, real, real, bool])?;
+        let mpgrid = match self.mpgrids.get(id) {
+            Some(mpgrid) => mpgrid,
+            None => {
+                return Err(gml::Error::FunctionError(
+                    "mp_grid_path".into(),
+                    pathfinding::Error::NonexistentStructure(id).into(),
+                ))
+            },
+        };
+        let path = match self.assets.paths.get_asset_mut(path_id) {
+            Some(path) => path,
+            None => return Err(gml::Error::NonexistentAsset(asset::Type::Path, path_id)),
+        };
+        let cells = match (mpgrid.cell_at(xstart, ystart), mpgrid.cell_at(xgoal, ygoal)) {
+            (Some(start), Some(goal)) => mpgrid.find_path(start, goal, allowdiag),
+            _ => None,
+        };
+        match cells {
+            Some(cells) => {
+                // the path goes from the exact start position through the centre of every cell between
+                path.curve = false;
+                path.closed = false;
+                path.points.clear();
+                path.points.push(asset::path::Point { x: xstart, y: ystart, speed: 100.into() });
+                for &(x, y) in cells.iter().skip(1).take(cells.len().saturating_sub(2)) {
+                    let (x, y) = mpgrid.cell_centre(x, y);
+                    path.points.push(asset::path::Point { x, y, speed: 100.into() });
+                }
+                path.points.push(asset::path::Point { x: xgoal, y: ygoal, speed: 100.into() });
+                path.update();
+                Ok(true.into())
+            },
+            None => Ok(false.into()),
+        }
     }
 
     pub fn mp_grid_draw(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -5711,13 +5971,15 @@ impl Game {
     }
 
     pub fn game_load(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let fname = expect_args!(args, [string])?;
+        let fname = expect_args!(args, [bytes])?;
+        let fname = self.decode_str(fname.as_ref());
         self.scene_change = Some(SceneChange::Load(fname.into_owned().into()));
         Ok(Default::default())
     }
 
     pub fn game_save(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let fname = expect_args!(args, [string])?;
+        let fname = expect_args!(args, [bytes])?;
+        let fname = self.decode_str(fname.as_ref());
         let save = GMSave::from_game(self);
         let mut file = std::fs::File::create(file::to_path(&fname).as_ref())
             .map(std::io::BufWriter::new)
@@ -5836,7 +6098,8 @@ impl Game {
     }
 
     pub fn file_bin_open(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (filename, mode) = expect_args!(args, [string, int])?;
+        let (filename, mode) = expect_args!(args, [bytes, int])?;
+        let filename = self.decode_str(filename.as_ref());
         let mode = match mode {
             0 => file::AccessMode::Read,
             1 => file::AccessMode::Write,
@@ -5917,7 +6180,8 @@ impl Game {
     }
 
     pub fn file_text_open_read(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let filename = expect_args!(args, [string])?;
+        let filename = expect_args!(args, [bytes])?;
+        let filename = self.decode_str(filename.as_ref());
         use std::error::Error as _; // for .source() trait method
 
         match self.text_files.add_from(|| Ok(file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Read)?)) {
@@ -5934,7 +6198,8 @@ impl Game {
     }
 
     pub fn file_text_open_write(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let filename = expect_args!(args, [string])?;
+        let filename = expect_args!(args, [bytes])?;
+        let filename = self.decode_str(filename.as_ref());
         match self.text_files.add_from(|| Ok(file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Write)?)) {
             Ok(i) => Ok((i + 1).into()),
             Err(e) => Err(gml::Error::FunctionError("file_text_open_write".into(), e.to_string())),
@@ -5942,7 +6207,8 @@ impl Game {
     }
 
     pub fn file_text_open_append(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let filename = expect_args!(args, [string])?;
+        let filename = expect_args!(args, [bytes])?;
+        let filename = self.decode_str(filename.as_ref());
         match self.text_files.add_from(|| Ok(file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Special)?)) {
             Ok(i) => Ok((i + 1).into()),
             Err(e) => Err(gml::Error::FunctionError("file_text_open_append".into(), e.to_string())),
@@ -6040,7 +6306,8 @@ impl Game {
     }
 
     pub fn file_open_read(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let filename = expect_args!(args, [string])?;
+        let filename = expect_args!(args, [bytes])?;
+        let filename = self.decode_str(filename.as_ref());
         match file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Read) {
             Ok(f) => {
                 self.open_file.replace(f);
@@ -6056,7 +6323,8 @@ impl Game {
     }
 
     pub fn file_open_write(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let filename = expect_args!(args, [string])?;
+        let filename = expect_args!(args, [bytes])?;
+        let filename = self.decode_str(filename.as_ref());
         match file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Write) {
             Ok(f) => {
                 self.open_file.replace(f);
@@ -6070,7 +6338,8 @@ impl Game {
     }
 
     pub fn file_open_append(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let filename = expect_args!(args, [string])?;
+        let filename = expect_args!(args, [bytes])?;
+        let filename = self.decode_str(filename.as_ref());
         match file::TextHandle::open(file::to_path(&filename).as_ref(), file::AccessMode::Special) {
             Ok(f) => {
                 self.open_file.replace(f);
@@ -6166,7 +6435,8 @@ impl Game {
     }
 
     pub fn file_delete(&self, args: &[Value]) -> gml::Result<Value> {
-        let filename = expect_args!(args, [string])?;
+        let filename = expect_args!(args, [bytes])?;
+        let filename = self.decode_str(filename.as_ref());
         match file::delete(file::to_path(&filename).as_ref()) {
             Ok(()) => Ok(Default::default()),
             Err(e) => Err(gml::Error::FunctionError("file_delete".into(), e.to_string())),
@@ -6174,7 +6444,9 @@ impl Game {
     }
 
     pub fn file_rename(&self, args: &[Value]) -> gml::Result<Value> {
-        let (from, to) = expect_args!(args, [string, string])?;
+        let (from, to) = expect_args!(args, [bytes, bytes])?;
+        let from = self.decode_str(from.as_ref());
+        let to = self.decode_str(to.as_ref());
         if file::rename(file::to_path(&from).as_ref(), file::to_path(&to).as_ref()).is_err() {
             // Fail silently
             eprintln!("Warning (file_rename): could not rename {} to {}", from, to);
@@ -6183,7 +6455,9 @@ impl Game {
     }
 
     pub fn file_copy(&self, args: &[Value]) -> gml::Result<Value> {
-        let (from, to) = expect_args!(args, [string, string])?;
+        let (from, to) = expect_args!(args, [bytes, bytes])?;
+        let from = self.decode_str(from.as_ref());
+        let to = self.decode_str(to.as_ref());
         if file::copy(file::to_path(&from).as_ref(), file::to_path(&to).as_ref()).is_err() {
             // Fail silently
             eprintln!("Warning (file_copy): could not copy {} to {}", from, to);
@@ -6199,7 +6473,8 @@ impl Game {
     }
 
     pub fn directory_create(&self, args: &[Value]) -> gml::Result<Value> {
-        let path = expect_args!(args, [string])?;
+        let path = expect_args!(args, [bytes])?;
+        let path = self.decode_str(path.as_ref());
         match file::dir_create(file::to_path(&path).as_ref()) {
             Ok(()) => Ok(Default::default()),
             Err(e) => Err(gml::Error::FunctionError("directory_create".into(), e.to_string())),
@@ -6207,7 +6482,8 @@ impl Game {
     }
 
     pub fn file_find_first(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (path, attribs) = expect_args!(args, [string, int])?;
+        let (path, attribs) = expect_args!(args, [bytes, int])?;
+        let path = self.decode_str(path.as_ref());
         if path.ends_with("/") || path.ends_with("\\") {
             // match nothing
             self.file_finder = None;
@@ -6346,7 +6622,8 @@ impl Game {
     }
 
     pub fn export_include_file_location(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (name, path) = expect_args!(args, [bytes, string])?;
+        let (name, path) = expect_args!(args, [bytes, bytes])?;
+        let path = self.decode_str(path.as_ref());
         if let Some(file) = self.included_files.iter_mut().filter(|i| name.eq_ignore_ascii_case(i.name.as_ref())).next()
         {
             let path_ref: &str = path.as_ref();
@@ -6374,7 +6651,9 @@ impl Game {
     }
 
     pub fn execute_program(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (prog, prog_args, wait) = expect_args!(args, [string, string, bool])?;
+        let (prog, prog_args, wait) = expect_args!(args, [bytes, bytes, bool])?;
+        let prog = self.decode_str(prog.as_ref());
+        let prog_args = self.decode_str(prog_args.as_ref());
         // Rust doesn't let you execute a program with just a string, so unescape it manually
         let mut command_array = Vec::new();
         let mut buf = Some(String::new());
@@ -6522,7 +6801,11 @@ impl Game {
         let name = expect_args!(args, [bytes])?;
         let name_str = self.decode_str(name.as_ref());
         if file::file_exists(&file::to_path(&name_str)) {
-            match ini::Ini::load_from_file(name_str.as_ref()) {
+            let ini = match std::fs::read(file::to_path(&name_str).as_ref()) {
+                Ok(bytes) => ini::Ini::load_from_str(&self.decode_str(&bytes)).map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+            match ini {
                 Ok(ini) => {
                     self.open_ini = Some((ini, name));
                     Ok(Default::default())
@@ -6538,26 +6821,30 @@ impl Game {
     pub fn ini_close(&mut self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
         match self.open_ini.as_ref() {
-            Some((ini, path)) => match ini.write_to_file(self.decode_str(path.as_ref()).as_ref()) {
-                Ok(()) => {
-                    self.open_ini = None;
-                    Ok(Default::default())
-                },
-                Err(e) => Err(gml::Error::FunctionError("ini_close".into(), format!("{}", e))),
+            Some((ini, path)) => {
+                let mut contents = Vec::new();
+                ini.write_to(&mut contents).expect("writing an ini to memory can't fail");
+                let contents = self.encode_str(&String::from_utf8_lossy(&contents)).into_owned();
+                match std::fs::write(file::to_path(&self.decode_str(path.as_ref())).as_ref(), contents) {
+                    Ok(()) => {
+                        self.open_ini = None;
+                        Ok(Default::default())
+                    },
+                    Err(e) => Err(gml::Error::FunctionError("ini_close".into(), format!("{}", e))),
+                }
             },
             None => Ok(Default::default()),
         }
     }
 
     pub fn ini_read_string(&self, args: &[Value]) -> gml::Result<Value> {
-        let (section, key, default) = expect_args!(args, [string, string, string])?;
+        let (section, key, default) = expect_args!(args, [bytes, bytes, bytes])?;
+        let (section, key) = (self.decode_str(section.as_ref()), self.decode_str(key.as_ref()));
         match self.open_ini.as_ref() {
-            Some((ini, _)) => Ok(ini
-                .section(Some(section.as_ref()))
-                .and_then(|s| s.get(key))
-                .unwrap_or(default.as_ref())
-                .to_string()
-                .into()),
+            Some((ini, _)) => match ini.section(Some(section.as_ref())).and_then(|s| s.get(key.as_ref())) {
+                Some(val) => Ok(self.encode_str(val).as_ref().into()),
+                None => Ok(default.into()),
+            },
             None => Err(gml::Error::FunctionError(
                 "ini_read_string".into(),
                 "Trying to read from undefined INI file".to_string(),
@@ -6566,9 +6853,10 @@ impl Game {
     }
 
     pub fn ini_read_real(&self, args: &[Value]) -> gml::Result<Value> {
-        let (section, key, default) = expect_args!(args, [string, string, real])?;
+        let (section, key, default) = expect_args!(args, [bytes, bytes, real])?;
+        let (section, key) = (self.decode_str(section.as_ref()), self.decode_str(key.as_ref()));
         match self.open_ini.as_ref() {
-            Some((ini, _)) => match ini.section(Some(section.as_ref())).and_then(|s| s.get(key)) {
+            Some((ini, _)) => match ini.section(Some(section.as_ref())).and_then(|s| s.get(key.as_ref())) {
                 Some(val) => match val.parse::<f64>() {
                     Ok(x) => Ok(x.into()),
                     Err(_) => Ok(Default::default()),
@@ -6583,10 +6871,13 @@ impl Game {
     }
 
     pub fn ini_write_string(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (section, key, val) = expect_args!(args, [string, string, string])?;
+        let (section, key, val) = expect_args!(args, [bytes, bytes, bytes])?;
+        let section = self.decode_str(section.as_ref()).into_owned();
+        let key = self.decode_str(key.as_ref()).into_owned();
+        let val = self.decode_str(val.as_ref()).into_owned();
         match self.open_ini.as_mut() {
             Some((ini, _)) => {
-                ini.with_section(Some(section.as_ref())).set(key.as_ref(), val.as_ref());
+                ini.with_section(Some(section.as_str())).set(key.as_str(), val.as_str());
                 Ok(Default::default())
             },
             None => Err(gml::Error::FunctionError(
@@ -6597,10 +6888,12 @@ impl Game {
     }
 
     pub fn ini_write_real(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (section, key, val) = expect_args!(args, [string, string, real])?;
+        let (section, key, val) = expect_args!(args, [bytes, bytes, real])?;
+        let section = self.decode_str(section.as_ref()).into_owned();
+        let key = self.decode_str(key.as_ref()).into_owned();
         match self.open_ini.as_mut() {
             Some((ini, _)) => {
-                ini.with_section(Some(section.as_ref())).set(key.as_ref(), val.to_string());
+                ini.with_section(Some(section.as_str())).set(key.as_str(), val.to_string());
                 Ok(Default::default())
             },
             None => Err(gml::Error::FunctionError(
@@ -6611,10 +6904,11 @@ impl Game {
     }
 
     pub fn ini_key_exists(&self, args: &[Value]) -> gml::Result<Value> {
-        let (section, key) = expect_args!(args, [string, string])?;
+        let (section, key) = expect_args!(args, [bytes, bytes])?;
+        let (section, key) = (self.decode_str(section.as_ref()), self.decode_str(key.as_ref()));
         match self.open_ini.as_ref() {
             Some((ini, _)) => {
-                Ok(ini.section(Some(section.as_ref())).map(|s| s.contains_key(key)).unwrap_or(false).into())
+                Ok(ini.section(Some(section.as_ref())).map(|s| s.contains_key(key.as_ref())).unwrap_or(false).into())
             },
             None => Err(gml::Error::FunctionError(
                 "ini_key_exists".into(),
@@ -6624,7 +6918,8 @@ impl Game {
     }
 
     pub fn ini_section_exists(&self, args: &[Value]) -> gml::Result<Value> {
-        let section = expect_args!(args, [string])?;
+        let section = expect_args!(args, [bytes])?;
+        let section = self.decode_str(section.as_ref());
         match self.open_ini.as_ref() {
             Some((ini, _)) => Ok(ini.section(Some(section.as_ref())).is_some().into()),
             None => Err(gml::Error::FunctionError(
@@ -6635,10 +6930,12 @@ impl Game {
     }
 
     pub fn ini_key_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (section, key) = expect_args!(args, [string, string])?;
+        let (section, key) = expect_args!(args, [bytes, bytes])?;
+        let section = self.decode_str(section.as_ref()).into_owned();
+        let key = self.decode_str(key.as_ref()).into_owned();
         match self.open_ini.as_mut() {
             Some((ini, _)) => {
-                ini.delete_from(Some(section.as_ref()), key.as_ref());
+                ini.delete_from(Some(section.as_str()), key.as_str());
                 Ok(Default::default())
             },
             None => Err(gml::Error::FunctionError(
@@ -6649,10 +6946,11 @@ impl Game {
     }
 
     pub fn ini_section_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let section = expect_args!(args, [string])?;
+        let section = expect_args!(args, [bytes])?;
+        let section = self.decode_str(section.as_ref()).into_owned();
         match self.open_ini.as_mut() {
             Some((ini, _)) => {
-                ini.delete(Some(section.as_ref()));
+                ini.delete(Some(section.as_str()));
                 Ok(Default::default())
             },
             None => Err(gml::Error::FunctionError(
@@ -6810,8 +7108,8 @@ impl Game {
     }
 
     pub fn show_error(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (text, _abort) = expect_args!(args, [string, bool])?;
-        Err(gml::Error::FunctionError("show_error".into(), text.into()))
+        let (text, _abort) = expect_args!(args, [bytes, bool])?;
+        Err(gml::Error::FunctionError("show_error".into(), self.decode_str(text.as_ref()).into_owned()))
     }
 
     pub fn show_info(&mut self, _args: &[Value]) -> gml::Result<Value> {
@@ -7142,102 +7440,85 @@ impl Game {
         Ok(self.input.mouse_wheel_down().into())
     }
 
-    pub fn joystick_exists(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_exists")
-        // TODO
-        Ok(gml::FALSE.into())
+    /// Gets a joystick by its GML id (1 or 2)
+    fn get_joystick(&self, id: i32) -> Option<&input::Joystick> {
+        usize::try_from(id - 1).ok().and_then(|id| self.input.joystick(id))
     }
 
-    pub fn joystick_direction(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_direction")
-        // TODO
-        Ok(101.into())
+    pub fn joystick_exists(&self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.get_joystick(id).is_some().into())
     }
 
-    pub fn joystick_name(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_name")
-        // TODO
-        Ok("".into())
+    pub fn joystick_direction(&self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.get_joystick(id).map(|j| j.direction()).unwrap_or(input::Button::Keypad5 as u8).into())
     }
 
-    pub fn joystick_axes(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_axes")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.get_joystick(id).map(|j| j.name.as_str()).unwrap_or("").into())
     }
 
-    pub fn joystick_buttons(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_buttons")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_axes(&self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.get_joystick(id).map(|j| j.axis_count).unwrap_or(0).into())
     }
 
-    pub fn joystick_has_pov(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_has_pov")
-        // TODO
-        Ok(gml::FALSE.into())
+    pub fn joystick_buttons(&self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.get_joystick(id).map(|j| j.button_count).unwrap_or(0).into())
     }
 
-    pub fn joystick_check_button(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        //unimplemented!("Called unimplemented kernel function joystick_check_button")
-        // TODO
-        Ok(gml::FALSE.into())
+    pub fn joystick_has_pov(&self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.get_joystick(id).map(|j| j.has_pov).unwrap_or(false).into())
     }
 
-    pub fn joystick_xpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_xpos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_check_button(&self, args: &[Value]) -> gml::Result<Value> {
+        let (id, button) = expect_args!(args, [int, int])?;
+        let pressed = match (self.get_joystick(id), u8::try_from(button - 1)) {
+            (Some(joystick), Ok(button)) => joystick.button(button),
+            _ => false,
+        };
+        Ok(pressed.into())
     }
 
-    pub fn joystick_ypos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_ypos")
-        // TODO
-        Ok(0.into())
+    fn joystick_axis_pos(&self, args: &[Value], axis: input::JoystickAxis) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        Ok(self.get_joystick(id).map(|j| j.axis_pos(axis)).unwrap_or(0.0).into())
     }
 
-    pub fn joystick_zpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_zpos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_xpos(&self, args: &[Value]) -> gml::Result<Value> {
+        self.joystick_axis_pos(args, input::JoystickAxis::X)
     }
 
-    pub fn joystick_rpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_rpos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_ypos(&self, args: &[Value]) -> gml::Result<Value> {
+        self.joystick_axis_pos(args, input::JoystickAxis::Y)
     }
 
-    pub fn joystick_upos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_upos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_zpos(&self, args: &[Value]) -> gml::Result<Value> {
+        self.joystick_axis_pos(args, input::JoystickAxis::Z)
     }
 
-    pub fn joystick_vpos(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_vpos")
-        // TODO
-        Ok(0.into())
+    pub fn joystick_rpos(&self, args: &[Value]) -> gml::Result<Value> {
+        self.joystick_axis_pos(args, input::JoystickAxis::R)
     }
 
-    pub fn joystick_pov(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        //unimplemented!("Called unimplemented kernel function joystick_pov")
-        // TODO
-        Ok((-1).into())
+    pub fn joystick_upos(&self, args: &[Value]) -> gml::Result<Value> {
+        self.joystick_axis_pos(args, input::JoystickAxis::U)
+    }
+
+    pub fn joystick_vpos(&self, args: &[Value]) -> gml::Result<Value> {
+        self.joystick_axis_pos(args, input::JoystickAxis::V)
+    }
+
+    pub fn joystick_pov(&self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        match self.get_joystick(id).and_then(|j| j.pov()) {
+            Some(pov) => Ok((f64::from(pov) / 100.0).into()),
+            None => Ok((-1).into()),
+        }
     }
 
     pub fn keyboard_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -7261,8 +7542,8 @@ impl Game {
     pub fn io_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
         self.process_window_events();
-        // TODO: clear keyboard_string
         self.input.keyboard_clear_all();
+        self.input.set_keyboard_string(b"");
         self.input.mouse_clear_all();
         Ok(Default::default())
     }
@@ -7290,149 +7571,200 @@ impl Game {
         unimplemented!("Called unimplemented kernel function mouse_wait")
     }
 
-    pub fn mplay_init_ipx(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_init_ipx")
+    pub fn mplay_init_ipx(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        // Only TCP/IP is supported
+        Ok(gml::FALSE.into())
     }
 
-    pub fn mplay_init_tcpip(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_init_tcpip")
+    pub fn mplay_init_tcpip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let address = expect_args!(args, [string])?;
+        if self.play_type != PlayType::Normal {
+            // Network traffic isn't part of replays or savestates, so act like there's no network to connect to.
+            // Every other mplay function does nothing without a connection, so they don't need checking.
+            return Ok(gml::FALSE.into())
+        }
+        Ok(self.multiplayer.init_tcpip(&address).into())
     }
 
-    pub fn mplay_init_modem(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function mplay_init_modem")
+    pub fn mplay_init_modem(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [any, any])?;
+        Ok(gml::FALSE.into())
     }
 
-    pub fn mplay_init_serial(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function mplay_init_serial")
+    pub fn mplay_init_serial(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [any, any, any, any, any])?;
+        Ok(gml::FALSE.into())
     }
 
-    pub fn mplay_connect_status(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_connect_status")
+    pub fn mplay_connect_status(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.multiplayer.connect_status().into())
     }
 
-    pub fn mplay_end(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_end")
+    pub fn mplay_end(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.multiplayer.end();
+        Ok(Default::default())
     }
 
-    pub fn mplay_session_mode(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_session_mode")
+    pub fn mplay_session_mode(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let migrate = expect_args!(args, [bool])?;
+        self.multiplayer.set_host_migration(migrate);
+        Ok(Default::default())
     }
 
-    pub fn mplay_session_create(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mplay_session_create")
+    pub fn mplay_session_create(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (session_name, max_players, player_name) = expect_args!(args, [bytes, int, bytes])?;
+        let max_players = max_players.max(0) as u32;
+        Ok(self.multiplayer.create_session(session_name.as_ref(), max_players, player_name.as_ref()).into())
     }
 
-    pub fn mplay_session_find(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_session_find")
+    pub fn mplay_session_find(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.multiplayer.find_sessions().into())
     }
 
-    pub fn mplay_session_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_session_name")
+    pub fn mplay_session_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        Ok(self.multiplayer.session_name(index as usize).map_or(Default::default(), |name| name.into()))
     }
 
-    pub fn mplay_session_join(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function mplay_session_join")
+    pub fn mplay_session_join(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (index, player_name) = expect_args!(args, [int, bytes])?;
+        if index < 0 {
+            return Ok(gml::FALSE.into())
+        }
+        Ok(self.multiplayer.join_session(index as usize, player_name.as_ref()).into())
     }
 
-    pub fn mplay_session_status(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_session_status")
+    pub fn mplay_session_status(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.multiplayer.session_status().into())
     }
 
-    pub fn mplay_session_end(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_session_end")
+    pub fn mplay_session_end(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.multiplayer.end_session();
+        Ok(Default::default())
     }
 
-    pub fn mplay_player_find(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_player_find")
+    pub fn mplay_player_find(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.multiplayer.player_count().into())
     }
 
-    pub fn mplay_player_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_player_name")
+    pub fn mplay_player_name(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        let player = usize::try_from(index).ok().and_then(|i| self.multiplayer.player(i));
+        Ok(player.map_or(Default::default(), |(_, name)| name.into()))
     }
 
-    pub fn mplay_player_id(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_player_id")
+    pub fn mplay_player_id(&self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        let player = usize::try_from(index).ok().and_then(|i| self.multiplayer.player(i));
+        Ok(player.map_or(0.into(), |(id, _)| id.into()))
     }
 
-    pub fn mplay_data_write(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function mplay_data_write")
+    pub fn mplay_data_write(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (index, value) = expect_args!(args, [int, any])?;
+        if (0..10000).contains(&index) {
+            self.multiplayer.write_data(index as u32, Self::to_mp_value(value));
+        }
+        Ok(Default::default())
     }
 
-    pub fn mplay_data_read(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_data_read")
+    pub fn mplay_data_read(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let index = expect_args!(args, [int])?;
+        if !(0..10000).contains(&index) {
+            return Ok(Default::default())
+        }
+        Ok(self.multiplayer.read_data(index as u32).map_or(Default::default(), Self::from_mp_value))
     }
 
-    pub fn mplay_data_mode(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_data_mode")
+    pub fn mplay_data_mode(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let guaranteed = expect_args!(args, [bool])?;
+        self.multiplayer.set_guaranteed_data(guaranteed);
+        Ok(Default::default())
     }
 
-    pub fn mplay_message_send(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mplay_message_send")
+    pub fn mplay_message_send(&self, args: &[Value]) -> gml::Result<Value> {
+        // All messages go over TCP, so there's no difference between guaranteed and unguaranteed ones
+        self.mplay_message_send_guaranteed(args)
     }
 
-    pub fn mplay_message_send_guaranteed(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function mplay_message_send_guaranteed")
+    pub fn mplay_message_send_guaranteed(&self, args: &[Value]) -> gml::Result<Value> {
+        let (player, id, value) = expect_args!(args, [any, real, any])?;
+        let player = Self::mp_player(player);
+        Ok(self.multiplayer.send_message(&player, id.into(), Self::to_mp_value(value)).into())
     }
 
-    pub fn mplay_message_receive(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_message_receive")
+    pub fn mplay_message_receive(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        Ok(self.multiplayer.receive_message(&Self::mp_player(player)).into())
     }
 
-    pub fn mplay_message_id(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_id")
+    pub fn mplay_message_id(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.multiplayer.current_message.as_ref().map_or(0.into(), |m| Real::from(m.id).into()))
     }
 
-    pub fn mplay_message_value(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_value")
+    pub fn mplay_message_value(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.multiplayer.current_message.as_ref().map_or(Default::default(), |m| Self::from_mp_value(&m.value)))
     }
 
-    pub fn mplay_message_player(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_player")
+    pub fn mplay_message_player(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.multiplayer.current_message.as_ref().map_or(0.into(), |m| m.from.into()))
     }
 
-    pub fn mplay_message_name(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function mplay_message_name")
+    pub fn mplay_message_name(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.multiplayer.current_message.as_ref().map_or(Default::default(), |m| m.from_name.as_slice().into()))
     }
 
-    pub fn mplay_message_count(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_message_count")
+    pub fn mplay_message_count(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        Ok(self.multiplayer.message_count(&Self::mp_player(player)).into())
     }
 
-    pub fn mplay_message_clear(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function mplay_message_clear")
+    pub fn mplay_message_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let player = expect_args!(args, [any])?;
+        self.multiplayer.clear_messages(&Self::mp_player(player));
+        Ok(Default::default())
+    }
+
+    /// Player arguments to mplay functions are 0 for all players, a player id or a player name.
+    fn mp_player(player: Value) -> network::PlayerSelector {
+        match player {
+            Value::Real(x) if x.round().to_i32() == 0 => network::PlayerSelector::All,
+            Value::Real(x) => network::PlayerSelector::Id(x.round().to_u32()),
+            Value::Str(name) => network::PlayerSelector::Name(name.as_ref().to_vec()),
+        }
+    }
+
+    fn to_mp_value(value: Value) -> network::MpValue {
+        match value {
+            Value::Real(x) => network::MpValue::Real(x.into()),
+            Value::Str(s) => network::MpValue::Str(s.as_ref().to_vec()),
+        }
+    }
+
+    fn from_mp_value(value: &network::MpValue) -> Value {
+        match value {
+            network::MpValue::Real(x) => Real::from(*x).into(),
+            network::MpValue::Str(s) => s.as_slice().into(),
+        }
     }
 
     pub fn mplay_ipaddress(&mut self, args: &[Value]) -> gml::Result<Value> {
         expect_args!(args, [])?;
-        Ok(network::get_local_ip().unwrap_or(std::net::Ipv4Addr::LOCALHOST.into()).to_string().into())
+        let ip = match self.play_type {
+            PlayType::Normal => network::get_local_ip().unwrap_or(std::net::Ipv4Addr::LOCALHOST.into()),
+            PlayType::Record | PlayType::Replay => std::net::Ipv4Addr::LOCALHOST.into(),
+        };
+        Ok(ip.to_string().into())
     }
 
     pub fn event_inherited(&mut self, context: &mut Context, args: &[Value]) -> gml::Result<Value> {
@@ -7915,14 +8247,14 @@ impl Game {
         Ok((((0..24).contains(&h) && (0..60).contains(&m) && (0..60).contains(&s)) || (h, m, s) == (24, 0, 0)).into())
     }
 
-    pub fn date_inc_year(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_inc_year")
+    pub fn date_inc_year(args: &[Value]) -> gml::Result<Value> {
+        let (datetime, amount) = expect_args!(args, [real, int])?;
+        Ok(datetime::inc_month(datetime, amount.saturating_mul(12)).unwrap_or(0.into()).into())
     }
 
-    pub fn date_inc_month(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_inc_month")
+    pub fn date_inc_month(args: &[Value]) -> gml::Result<Value> {
+        let (datetime, amount) = expect_args!(args, [real, int])?;
+        Ok(datetime::inc_month(datetime, amount).unwrap_or(0.into()).into())
     }
 
     pub fn date_inc_week(args: &[Value]) -> gml::Result<Value> {
@@ -8014,99 +8346,100 @@ impl Game {
         Ok(DateTime::from(datetime).second_of_year().into())
     }
 
-    pub fn date_year_span(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_year_span")
+    pub fn date_year_span(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok(datetime::year_span(date1, date2).into())
     }
 
-    pub fn date_month_span(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_month_span")
+    pub fn date_month_span(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok(datetime::month_span(date1, date2).into())
     }
 
-    pub fn date_week_span(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_week_span")
+    pub fn date_week_span(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok((datetime::span(date1, date2) / 7.into()).into())
     }
 
-    pub fn date_day_span(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_day_span")
+    pub fn date_day_span(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok(datetime::span(date1, date2).into())
     }
 
-    pub fn date_hour_span(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_hour_span")
+    pub fn date_hour_span(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok((datetime::span(date1, date2) * 24.into()).into())
     }
 
-    pub fn date_minute_span(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_minute_span")
+    pub fn date_minute_span(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok((datetime::span(date1, date2) * 1440.into()).into())
     }
 
-    pub fn date_second_span(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_second_span")
+    pub fn date_second_span(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok((datetime::span(date1, date2) * 86400.into()).into())
     }
 
-    pub fn date_compare_datetime(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_compare_datetime")
+    pub fn date_compare_datetime(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok(datetime::compare_datetime(date1, date2).into())
     }
 
-    pub fn date_compare_date(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_compare_date")
+    pub fn date_compare_date(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok(datetime::compare_date(date1, date2).into())
     }
 
-    pub fn date_compare_time(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function date_compare_time")
+    pub fn date_compare_time(args: &[Value]) -> gml::Result<Value> {
+        let (date1, date2) = expect_args!(args, [real, real])?;
+        Ok(datetime::compare_time(date1, date2).into())
     }
 
-    pub fn date_date_of(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_date_of")
+    pub fn date_date_of(args: &[Value]) -> gml::Result<Value> {
+        let datetime = expect_args!(args, [real])?;
+        Ok(datetime.trunc().into())
     }
 
-    pub fn date_time_of(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_time_of")
+    pub fn date_time_of(args: &[Value]) -> gml::Result<Value> {
+        let datetime = expect_args!(args, [real])?;
+        Ok(datetime.fract().into())
     }
 
-    pub fn date_datetime_string(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_datetime_string")
+    pub fn date_datetime_string(&self, args: &[Value]) -> gml::Result<Value> {
+        let datetime = expect_args!(args, [real])?;
+        Ok(DateTime::from(datetime).format("c").into())
     }
 
-    pub fn date_date_string(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_date_string")
+    pub fn date_date_string(&self, args: &[Value]) -> gml::Result<Value> {
+        let datetime = expect_args!(args, [real])?;
+        Ok(DateTime::from(datetime).format(datetime::SHORT_DATE_FORMAT).into())
     }
 
-    pub fn date_time_string(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_time_string")
+    pub fn date_time_string(&self, args: &[Value]) -> gml::Result<Value> {
+        let datetime = expect_args!(args, [real])?;
+        Ok(DateTime::from(datetime).format(datetime::LONG_TIME_FORMAT).into())
     }
 
-    pub fn date_days_in_month(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_days_in_month")
+    pub fn date_days_in_month(args: &[Value]) -> gml::Result<Value> {
+        let datetime = expect_args!(args, [real])?;
+        Ok(DateTime::from(datetime).days_in_month().into())
     }
 
-    pub fn date_days_in_year(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_days_in_year")
+    pub fn date_days_in_year(args: &[Value]) -> gml::Result<Value> {
+        let datetime = expect_args!(args, [real])?;
+        Ok(DateTime::from(datetime).days_in_year().into())
     }
 
-    pub fn date_leap_year(_args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_leap_year")
+    pub fn date_leap_year(args: &[Value]) -> gml::Result<Value> {
+        let datetime = expect_args!(args, [real])?;
+        Ok(DateTime::from(datetime).is_leap_year().into())
     }
 
-    pub fn date_is_today(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function date_is_today")
+    pub fn date_is_today(&self, args: &[Value]) -> gml::Result<Value> {
+        let datetime = expect_args!(args, [real])?;
+        let today = Real::from(DateTime::now_or_nanos(self.spoofed_time_nanos).date());
+        Ok((datetime >= today && datetime < today + 1.into()).into())
     }
 
     pub fn sprite_exists(&self, args: &[Value]) -> gml::Result<Value> {
@@ -8467,7 +8800,8 @@ impl Game {
 
     pub fn sprite_add(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (fname, imgnumb, removeback, smooth, origin_x, origin_y) =
-            expect_args!(args, [string, int, bool, bool, int, int])?;
+            expect_args!(args, [bytes, int, bool, bool, int, int])?;
+        let fname = self.decode_str(fname.as_ref());
         let imgnumb = imgnumb.max(1) as usize;
         let mut images = match file::load_animation(file::to_path(&fname).as_ref(), imgnumb) {
             Ok(frames) => frames,
@@ -8517,7 +8851,8 @@ impl Game {
 
     pub fn sprite_replace(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (sprite_id, fname, imgnumb, removeback, smooth, origin_x, origin_y) =
-            expect_args!(args, [int, string, int, bool, bool, int, int])?;
+            expect_args!(args, [int, bytes, int, bool, bool, int, int])?;
+        let fname = self.decode_str(fname.as_ref());
         if let Some(sprite) = self.assets.sprites.get_asset_mut(sprite_id) {
             for frame in &sprite.frames {
                 self.renderer.delete_sprite(frame.atlas_ref);
@@ -8570,14 +8905,101 @@ impl Game {
         }
     }
 
-    pub fn sprite_add_sprite(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function sprite_add_sprite")
+    pub fn sprite_add_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [bytes])?;
+        let fname = self.decode_str(fname.as_ref());
+        let sprite_file = match file::load_sprite_file(file::to_path(&fname).as_ref()) {
+            Ok(sprite_file) => sprite_file,
+            Err(e) => {
+                eprintln!("Warning: sprite_add_sprite on {} failed: {}", fname, e);
+                return Ok((-1).into())
+            },
+        };
+        let sprite_id = self.assets.sprites.len();
+        self.assets.sprites.push(None);
+        self.load_sprite_file(sprite_id, format!("__newsprite{}", sprite_id).into(), sprite_file, "sprite_add_sprite")?;
+        Ok(sprite_id.into())
     }
 
-    pub fn sprite_replace_sprite(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sprite_replace_sprite")
+    pub fn sprite_replace_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sprite_id, fname) = expect_args!(args, [int, bytes])?;
+        let fname = self.decode_str(fname.as_ref());
+        if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
+            let sprite_file = match file::load_sprite_file(file::to_path(&fname).as_ref()) {
+                Ok(sprite_file) => sprite_file,
+                Err(e) => {
+                    eprintln!("Warning: sprite_replace_sprite on {} failed: {}", fname, e);
+                    return Ok((-1).into())
+                },
+            };
+            for frame in &sprite.frames {
+                self.renderer.delete_sprite(frame.atlas_ref);
+            }
+            let name = sprite.name.clone();
+            self.load_sprite_file(sprite_id as usize, name, sprite_file, "sprite_replace_sprite")?;
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError(
+                "sprite_replace_sprite".into(),
+                "Trying to replace non-existing sprite.".into(),
+            ))
+        }
+    }
+
+    /// Uploads the frames from a .gmspr file into the given sprite slot and applies its collision settings.
+    fn load_sprite_file(
+        &mut self,
+        sprite_id: usize,
+        name: gml::String,
+        sprite_file: file::SpriteFile,
+        function: &str,
+    ) -> gml::Result<()> {
+        let file::SpriteFile { origin_x, origin_y, mut frames, .. } = sprite_file;
+        // every frame takes the size of the first one, like when adding frames to an existing sprite
+        let (width, height) = frames[0].dimensions();
+        for image in frames.iter_mut() {
+            asset::sprite::scale(image, width, height);
+        }
+        let colliders = asset::sprite::make_colliders_precise(&frames, 0, false);
+        let renderer = &mut self.renderer;
+        let frames = frames
+            .drain(..)
+            .map(|i| {
+                Ok(asset::sprite::Frame {
+                    width,
+                    height,
+                    atlas_ref: renderer
+                        .upload_sprite(i.into_raw().into_boxed_slice(), width as _, height as _, origin_x, origin_y)
+                        .map_err(|e| gml::Error::FunctionError(function.into(), e))?,
+                })
+            })
+            .collect::<gml::Result<_>>()?;
+        self.assets.sprites[sprite_id] = Some(Box::new(asset::Sprite {
+            name,
+            frames,
+            bbox_left: colliders[0].bbox_left,
+            bbox_right: colliders[0].bbox_right,
+            bbox_top: colliders[0].bbox_top,
+            bbox_bottom: colliders[0].bbox_bottom,
+            colliders,
+            width,
+            height,
+            origin_x,
+            origin_y,
+            per_frame_colliders: sprite_file.sepmasks,
+        }));
+        self.sprite_collision_mask(&[
+            sprite_id.into(),
+            sprite_file.sepmasks.into(),
+            sprite_file.bbox_mode.into(),
+            sprite_file.bbox_left.into(),
+            sprite_file.bbox_top.into(),
+            sprite_file.bbox_right.into(),
+            sprite_file.bbox_bottom.into(),
+            sprite_file.shape.into(),
+            sprite_file.alpha_tolerance.into(),
+        ])?;
+        Ok(())
     }
 
     pub fn sprite_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8593,9 +9015,18 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn sprite_duplicate(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function sprite_duplicate")
+    pub fn sprite_duplicate(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let sprite_id = expect_args!(args, [int])?;
+        if self.assets.sprites.get_asset(sprite_id).is_none() {
+            return Err(gml::Error::NonexistentAsset(asset::Type::Sprite, sprite_id))
+        }
+        let new_id = self.assets.sprites.len();
+        self.assets.sprites.push(None);
+        self.sprite_assign(&[new_id.into(), sprite_id.into()])?;
+        if let Some(sprite) = self.assets.sprites[new_id].as_mut() {
+            sprite.name = format!("__newsprite{}", new_id).into();
+        }
+        Ok(new_id.into())
     }
 
     pub fn sprite_assign(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8644,13 +9075,50 @@ impl Game {
         }
     }
 
-    pub fn sprite_merge(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sprite_merge")
+    pub fn sprite_merge(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (dst_id, src_id) = expect_args!(args, [int, int])?;
+        let src = self.assets.sprites.get_asset(src_id).ok_or(gml::Error::NonexistentAsset(asset::Type::Sprite, src_id))?;
+        let dst = self.assets.sprites.get_asset(dst_id).ok_or(gml::Error::NonexistentAsset(asset::Type::Sprite, dst_id))?;
+        // download frames from gpu, stretching the new ones to the size of the destination sprite
+        let mut images = Vec::with_capacity(dst.frames.len() + src.frames.len());
+        for f in dst.frames.iter() {
+            images.push(RgbaImage::from_vec(f.width, f.height, self.renderer.dump_sprite(f.atlas_ref).into_vec()).unwrap());
+        }
+        let merge_start = images.len();
+        for f in src.frames.iter() {
+            let mut image =
+                RgbaImage::from_vec(f.width, f.height, self.renderer.dump_sprite(f.atlas_ref).into_vec()).unwrap();
+            asset::sprite::scale(&mut image, dst.width, dst.height);
+            images.push(image);
+        }
+        let sprite = self.assets.sprites.get_asset_mut(dst_id).unwrap();
+        sprite.colliders = asset::sprite::make_colliders_precise(&images, 0, sprite.per_frame_colliders);
+        sprite.bbox_left = sprite.colliders.iter().map(|c| c.bbox_left).min().unwrap();
+        sprite.bbox_top = sprite.colliders.iter().map(|c| c.bbox_top).min().unwrap();
+        sprite.bbox_right = sprite.colliders.iter().map(|c| c.bbox_right).max().unwrap();
+        sprite.bbox_bottom = sprite.colliders.iter().map(|c| c.bbox_bottom).max().unwrap();
+        for image in images.drain(merge_start..) {
+            sprite.frames.push(asset::sprite::Frame {
+                width: sprite.width,
+                height: sprite.height,
+                atlas_ref: self
+                    .renderer
+                    .upload_sprite(
+                        image.into_raw().into_boxed_slice(),
+                        sprite.width as _,
+                        sprite.height as _,
+                        sprite.origin_x,
+                        sprite.origin_y,
+                    )
+                    .map_err(|e| gml::Error::FunctionError("sprite_merge".into(), e))?,
+            });
+        }
+        Ok(Default::default())
     }
 
     pub fn sprite_save(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (sprite_id, subimg, fname) = expect_args!(args, [int, int, string])?;
+        let (sprite_id, subimg, fname) = expect_args!(args, [int, int, bytes])?;
+        let fname = self.decode_str(fname.as_ref());
         if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
             let image_index = subimg % sprite.frames.len() as i32;
             if let Some(frame) = sprite.get_frame(image_index) {
@@ -8667,9 +9135,23 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn sprite_save_strip(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sprite_save_strip")
+    pub fn sprite_save_strip(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sprite_id, fname) = expect_args!(args, [int, bytes])?;
+        let fname = self.decode_str(fname.as_ref());
+        if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
+            // lay out every frame side by side
+            let mut strip = RgbaImage::new(sprite.width * sprite.frames.len() as u32, sprite.height);
+            for (i, frame) in sprite.frames.iter().enumerate() {
+                let image =
+                    RgbaImage::from_vec(frame.width, frame.height, self.renderer.dump_sprite(frame.atlas_ref).into())
+                        .unwrap();
+                image::imageops::replace(&mut strip, &image, i as u32 * sprite.width, 0);
+            }
+            if let Err(e) = file::save_image(file::to_path(&fname).as_ref(), strip) {
+                return Err(gml::Error::FunctionError("sprite_save_strip".into(), e.to_string()))
+            }
+        }
+        Ok(Default::default())
     }
 
     pub fn sprite_collision_mask(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -8885,13 +9367,48 @@ impl Game {
         Ok(background_id.into())
     }
 
-    pub fn background_create_gradient(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function background_create_gradient")
+    pub fn background_create_gradient(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (w, h, col1, col2, kind) = expect_args!(args, [int, int, int, int, int])?;
+        let (width, height) = (w.max(1) as u32, h.max(1) as u32);
+        // position across the image from 0 at the start (or edges) to 1 at the end (or centre)
+        let along = |pos: u32, size: u32| if size > 1 { f64::from(pos) / f64::from(size - 1) } else { 0.0 };
+        let from_centre = |pos: u32, size: u32| (along(pos, size) * 2.0 - 1.0).abs();
+        let lerp_channel = |shift: u32, ratio: f64| {
+            let (c1, c2) = (f64::from((col1 >> shift) & 0xff), f64::from((col2 >> shift) & 0xff));
+            (c1 + (c2 - c1) * ratio).round() as u8
+        };
+        let mut data = Vec::with_capacity((width * height * 4) as usize);
+        for y in 0..height {
+            for x in 0..width {
+                let ratio = match kind {
+                    0 => along(x, width),
+                    1 => along(y, height),
+                    2 => 1.0 - from_centre(x, width).max(from_centre(y, height)),
+                    3 => 1.0 - from_centre(x, width).hypot(from_centre(y, height)).min(1.0),
+                    4 => 1.0 - from_centre(x, width),
+                    5 => 1.0 - from_centre(y, height),
+                    _ => 0.0,
+                };
+                data.extend_from_slice(&[lerp_channel(0, ratio), lerp_channel(8, ratio), lerp_channel(16, ratio), 255]);
+            }
+        }
+        let atlas_ref = self
+            .renderer
+            .upload_sprite(data.into_boxed_slice(), width as _, height as _, 0, 0)
+            .map_err(|e| gml::Error::FunctionError("background_create_gradient".into(), e))?;
+        let background_id = self.assets.backgrounds.len();
+        self.assets.backgrounds.push(Some(Box::new(asset::Background {
+            name: format!("__newbackground{}", background_id).into(),
+            width,
+            height,
+            atlas_ref: Some(atlas_ref),
+        })));
+        Ok(background_id.into())
     }
 
     pub fn background_add(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (fname, removeback, smooth) = expect_args!(args, [string, bool, bool])?;
+        let (fname, removeback, smooth) = expect_args!(args, [bytes, bool, bool])?;
+        let fname = self.decode_str(fname.as_ref());
         let mut image = match file::load_image(file::to_path(&fname).as_ref()) {
             Ok(im) => im,
             Err(e) => {
@@ -8917,7 +9434,8 @@ impl Game {
     }
 
     pub fn background_replace(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (background_id, fname, removeback, smooth) = expect_args!(args, [int, string, bool, bool])?;
+        let (background_id, fname, removeback, smooth) = expect_args!(args, [int, bytes, bool, bool])?;
+        let fname = self.decode_str(fname.as_ref());
         if let Some(background) = self.assets.backgrounds.get_asset_mut(background_id) {
             if let Some(atlas_ref) = background.atlas_ref {
                 self.renderer.delete_sprite(atlas_ref);
@@ -8948,14 +9466,60 @@ impl Game {
         }
     }
 
-    pub fn background_add_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function background_add_background")
+    pub fn background_add_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let fname = expect_args!(args, [bytes])?;
+        let fname = self.decode_str(fname.as_ref());
+        let image = match file::load_background_file(file::to_path(&fname).as_ref()) {
+            Ok(im) => im,
+            Err(e) => {
+                eprintln!("Warning: background_add_background on {} failed: {}", fname, e);
+                return Ok((-1).into())
+            },
+        };
+        let (width, height) = image.dimensions();
+        let atlas_ref = self
+            .renderer
+            .upload_sprite(image.into_raw().into_boxed_slice(), width as _, height as _, 0, 0)
+            .map_err(|e| gml::Error::FunctionError("background_add_background".into(), e))?;
+        let background_id = self.assets.backgrounds.len();
+        self.assets.backgrounds.push(Some(Box::new(asset::Background {
+            name: format!("__newbackground{}", background_id).into(),
+            width,
+            height,
+            atlas_ref: Some(atlas_ref),
+        })));
+        Ok(background_id.into())
     }
 
-    pub fn background_replace_background(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function background_replace_background")
+    pub fn background_replace_background(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (background_id, fname) = expect_args!(args, [int, bytes])?;
+        let fname = self.decode_str(fname.as_ref());
+        if let Some(background) = self.assets.backgrounds.get_asset_mut(background_id) {
+            let image = match file::load_background_file(file::to_path(&fname).as_ref()) {
+                Ok(im) => im,
+                Err(e) => {
+                    eprintln!("Warning: background_replace_background on {} failed: {}", fname, e);
+                    return Ok((-1).into())
+                },
+            };
+            if let Some(atlas_ref) = background.atlas_ref {
+                self.renderer.delete_sprite(atlas_ref);
+            }
+            let (width, height) = image.dimensions();
+            let atlas_ref = self
+                .renderer
+                .upload_sprite(image.into_raw().into_boxed_slice(), width as _, height as _, 0, 0)
+                .map_err(|e| gml::Error::FunctionError("background_replace_background".into(), e))?;
+            background.atlas_ref = Some(atlas_ref);
+            background.width = width;
+            background.height = height;
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError(
+                "background_replace_background".into(),
+                "Trying to replace non-existing background.".into(),
+            ))
+        }
     }
 
     pub fn background_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9033,7 +9597,8 @@ impl Game {
     }
 
     pub fn background_save(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (background_id, fname) = expect_args!(args, [int, string])?;
+        let (background_id, fname) = expect_args!(args, [int, bytes])?;
+        let fname = self.decode_str(fname.as_ref());
         if let Some(background) = self.assets.backgrounds.get_asset(background_id) {
             if let Some(atlas_ref) = background.atlas_ref {
                 // get RGBA
@@ -9085,7 +9650,8 @@ impl Game {
     }
 
     pub fn sound_add(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (fname, kind, preload) = expect_args!(args, [string, int, bool])?;
+        let (fname, kind, preload) = expect_args!(args, [bytes, int, bool])?;
+        let fname = self.decode_str(fname.as_ref());
         let path_buf = std::path::PathBuf::from(fname.as_ref());
         let data = match std::fs::read(&path_buf) {
             Ok(b) => b.into_boxed_slice(),
@@ -9097,10 +9663,14 @@ impl Game {
                 Some(x) => asset::sound::FileType::Mp3(x),
                 None => return Ok((-1).into()),
             },
-            Some("wav") => match self.audio.add_wav(data, sound_id as i32, 1.0, kind == 2, kind >= 3) {
+            Some("wav") => match self.audio.add_wav(data, sound_id as i32, 1.0, 0.0, 0, kind == 2, kind >= 3) {
                 Some(x) => asset::sound::FileType::Wav(x),
                 None => return Ok((-1).into()),
             },
+            Some("mid" | "midi" | "rmi") => match self.audio.add_midi(&data, sound_id as i32) {
+                Ok(x) => asset::sound::FileType::Midi(x),
+                Err(_) => return Ok((-1).into()),
+            },
             _ => return Ok((-1).into()),
         };
         self.assets.sounds.push(Some(Box::new(asset::Sound {
@@ -9113,7 +9683,8 @@ impl Game {
     }
 
     pub fn sound_replace(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (sound_id, fname, kind, preload) = expect_args!(args, [int, string, int, bool])?;
+        let (sound_id, fname, kind, preload) = expect_args!(args, [int, bytes, int, bool])?;
+        let fname = self.decode_str(fname.as_ref());
         if let Some(sound) = self.assets.sounds.get_asset_mut(sound_id) {
             self.audio.stop_sound(sound_id);
             sound.gml_kind = kind.into();
@@ -9130,10 +9701,14 @@ impl Game {
                         Some(x) => asset::sound::FileType::Mp3(x),
                         None => return Ok(0.into()),
                     },
-                    Some("wav") => match self.audio.add_wav(data, sound_id as i32, 1.0, kind == 2, kind >= 3) {
+                    Some("wav") => match self.audio.add_wav(data, sound_id as i32, 1.0, 0.0, 0, kind == 2, kind >= 3) {
                         Some(x) => asset::sound::FileType::Wav(x),
                         None => return Ok(0.into()),
                     },
+                    Some("mid" | "midi" | "rmi") => match self.audio.add_midi(&data, sound_id as i32) {
+                        Ok(x) => asset::sound::FileType::Midi(x),
+                        Err(_) => return Ok(0.into()),
+                    },
                     _ => return Ok(0.into()),
                 };
                 Ok(1.into())
@@ -9199,14 +9774,38 @@ impl Game {
         Ok(self.assets.fonts.get_asset(id).map(|x| x.last.into()).unwrap_or((-1).into()))
     }
 
-    pub fn font_add(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function font_add")
+    pub fn font_add(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sys_name, size, bold, italic, first, last) = expect_args!(args, [bytes, int, bool, bool, int, int])?;
+        let font_id = self.assets.fonts.len();
+        let name = format!("__newfont{}", font_id).into();
+        let font = self.rasterise_font("font_add", system_font(name, sys_name, size, bold, italic, first, last))?;
+        self.assets.fonts.push(Some(Box::new(font)));
+        Ok(font_id.into())
     }
 
-    pub fn font_replace(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function font_replace")
+    pub fn font_replace(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (font_id, sys_name, size, bold, italic, first, last) =
+            expect_args!(args, [int, bytes, int, bool, bool, int, int])?;
+        if let Some(old_font) = self.assets.fonts.get_asset(font_id) {
+            // the old font is kept as it is if the new one can't be made
+            let name = old_font.name.clone();
+            let font = system_font(name, sys_name, size, bold, italic, first, last);
+            let font = self.rasterise_font("font_replace", font)?;
+            if let Some(old_font) = self.assets.fonts[font_id as usize].replace(Box::new(font)) {
+                old_font.delete_graphics(&mut self.renderer);
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Font, font_id))
+        }
+    }
+
+    /// Rasterises an installed font for font_add and font_replace.
+    /// Installed fonts aren't used while recording or replaying, so that the result is the same on every machine.
+    fn rasterise_font(&mut self, function: &str, mut font: asset::Font) -> gml::Result<asset::Font> {
+        font.rasterise(self.encoding, self.play_type == PlayType::Normal, &mut self.renderer)
+            .map_err(|e| gml::Error::FunctionError(function.into(), e))?;
+        Ok(font)
     }
 
     pub fn font_add_sprite(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9239,12 +9838,7 @@ impl Game {
         let (font_id, sprite_id, first, prop, sep) = expect_args!(args, [int, int, int, bool, int])?;
         if let Some(font) = self.assets.fonts.get_asset_mut(font_id) {
             if let Some(sprite) = self.assets.sprites.get_asset(sprite_id) {
-                if font.own_graphics {
-                    // font_add isn't in yet but atm for ttfs all characters are on the same texture
-                    if let Some(c) = font.get_char(font.first) {
-                        self.renderer.delete_sprite(c.atlas_ref);
-                    }
-                }
+                font.delete_graphics(&mut self.renderer);
                 let chars = asset::font::create_chars_from_sprite(sprite, prop, sep, &self.renderer);
                 font.sys_name = "".into();
                 font.size = 12;
@@ -9266,12 +9860,7 @@ impl Game {
     pub fn font_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
         let font_id = expect_args!(args, [int])?;
         if let Some(font) = self.assets.fonts.get_asset(font_id) {
-            if font.own_graphics {
-                // font_add isn't in yet but atm for ttfs all characters are on the same texture
-                if let Some(c) = font.get_char(font.first) {
-                    self.renderer.delete_sprite(c.atlas_ref);
-                }
-            }
+            font.delete_graphics(&mut self.renderer);
         } else {
             return Err(gml::Error::FunctionError("font_delete".into(), "Trying to delete non-existing font".into()))
         }
@@ -9506,9 +10095,14 @@ impl Game {
         }
     }
 
-    pub fn path_append(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function path_append")
+    pub fn path_append(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, src_id) = expect_args!(args, [int, int])?;
+        if let Some(points) = self.assets.paths.get_asset(src_id).map(|p| p.points.clone()) {
+            if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
+                path.append(&points);
+            }
+        }
+        Ok(Default::default())
     }
 
     pub fn path_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9524,47 +10118,47 @@ impl Game {
     pub fn path_add_point(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (path_id, x, y, speed) = expect_args!(args, [int, real, real, real])?;
         if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
-            path.points.push(asset::path::Point { x, y, speed });
-            path.update();
+            path.add_point(asset::path::Point { x, y, speed });
         }
         Ok(Default::default())
     }
 
-    pub fn path_insert_point(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function path_insert_point")
+    pub fn path_insert_point(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, n, x, y, speed) = expect_args!(args, [int, int, real, real, real])?;
+        if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
+            path.insert_point(n, asset::path::Point { x, y, speed });
+        }
+        Ok(Default::default())
     }
 
     pub fn path_change_point(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (path_id, n, x, y, speed) = expect_args!(args, [int, int, real, real, real])?;
-        if n >= 0 {
-            if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
-                if let Some(point) = path.points.get_mut(n as usize) {
-                    point.x = x;
-                    point.y = y;
-                    point.speed = speed;
-                    path.update();
-                }
-            }
+        if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
+            path.change_point(n, asset::path::Point { x, y, speed });
         }
         Ok(Default::default())
     }
 
-    pub fn path_delete_point(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function path_delete_point")
+    pub fn path_delete_point(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (path_id, n) = expect_args!(args, [int, int])?;
+        if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
+            path.delete_point(n);
+        }
+        Ok(Default::default())
     }
 
-    pub fn path_clear_points(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function path_clear_points")
+    pub fn path_clear_points(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let path_id = expect_args!(args, [int])?;
+        if let Some(path) = self.assets.paths.get_asset_mut(path_id) {
+            path.clear_points();
+        }
+        Ok(Default::default())
     }
 
     pub fn path_reverse(&mut self, args: &[Value]) -> gml::Result<Value> {
         let id = expect_args!(args, [int])?;
         if let Some(path) = self.assets.paths.get_asset_mut(id) {
-            path.points.reverse();
-            path.update();
+            path.reverse();
         }
         Ok(Default::default())
     }
@@ -9572,11 +10166,7 @@ impl Game {
     pub fn path_mirror(&mut self, args: &[Value]) -> gml::Result<Value> {
         let id = expect_args!(args, [int])?;
         if let Some(path) = self.assets.paths.get_asset_mut(id) {
-            let (xcenter, _) = path.center();
-            for path in &mut path.points {
-                path.x = xcenter - (path.x - xcenter);
-            }
-            path.update();
+            path.mirror();
         }
         Ok(Default::default())
     }
@@ -9584,32 +10174,15 @@ impl Game {
     pub fn path_flip(&mut self, args: &[Value]) -> gml::Result<Value> {
         let id = expect_args!(args, [int])?;
         if let Some(path) = self.assets.paths.get_asset_mut(id) {
-            let (_, ycenter) = path.center();
-            for path in &mut path.points {
-                path.y = ycenter - (path.y - ycenter);
-            }
-            path.update();
+            path.flip();
         }
         Ok(Default::default())
     }
 
     pub fn path_rotate(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (id, angle) = expect_args!(args, [int, real])?;
-        let sin = -angle.to_radians().sin().into_inner();
-        let cos = angle.to_radians().cos().into_inner();
         if let Some(path) = self.assets.paths.get_asset_mut(id) {
-            let (xcenter, ycenter) = path.center();
-            for point in &mut path.points {
-                crate::util::rotate_around(
-                    point.x.as_mut_ref(),
-                    point.y.as_mut_ref(),
-                    xcenter.into(),
-                    ycenter.into(),
-                    sin,
-                    cos,
-                );
-            }
-            path.update();
+            path.rotate(angle);
         }
         Ok(Default::default())
     }
@@ -9617,12 +10190,7 @@ impl Game {
     pub fn path_scale(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (id, xscale, yscale) = expect_args!(args, [int, real, real])?;
         if let Some(path) = self.assets.paths.get_asset_mut(id) {
-            let (xcenter, ycenter) = path.center();
-            for path in &mut path.points {
-                path.x = xcenter + xscale * (path.x - xcenter);
-                path.y = ycenter + yscale * (path.y - ycenter);
-            }
-            path.update();
+            path.scale(xscale, yscale);
         }
         Ok(Default::default())
     }
@@ -9630,11 +10198,7 @@ impl Game {
     pub fn path_shift(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (id, xshift, yshift) = expect_args!(args, [int, real, real])?;
         if let Some(path) = self.assets.paths.get_asset_mut(id) {
-            for path in &mut path.points {
-                path.x += xshift;
-                path.y += yshift;
-            }
-            path.update();
+            path.shift(xshift, yshift);
         }
         Ok(Default::default())
     }
@@ -9899,9 +10463,26 @@ impl Game {
         Ok(id.into())
     }
 
-    pub fn object_delete(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function object_delete")
+    pub fn object_delete(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let object_id = expect_args!(args, [int])?;
+        if let Some(object) = self.assets.objects.get_asset(object_id) {
+            // Forget the object in its family, though its children keep their parent_index like in GM8
+            for &parent_id in object.parents.borrow().iter().filter(|&&id| id != object_id) {
+                if let Some(parent) = self.assets.objects.get_asset(parent_id) {
+                    parent.children.borrow_mut().remove(&object_id);
+                }
+            }
+            for &child_id in object.children.borrow().iter().filter(|&&id| id != object_id) {
+                if let Some(child) = self.assets.objects.get_asset(child_id) {
+                    child.parents.borrow_mut().remove(&object_id);
+                }
+            }
+        } else {
+            return Err(gml::Error::FunctionError("object_delete".into(), "Trying to delete non-existing object".into()))
+        }
+        self.assets.objects[object_id as usize] = None;
+        self.refresh_event_holders();
+        Ok(Default::default())
     }
 
     pub fn object_event_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -9976,9 +10557,16 @@ impl Game {
         Ok(Default::default())
     }
 
-    pub fn room_set_code(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function room_set_code")
+    pub fn room_set_code(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (room_id, code) = expect_args!(args, [int, bytes])?;
+        if let Some(room) = self.assets.rooms.get_asset_mut(room_id) {
+            // like in the loader, a compile error only surfaces when the room is entered
+            room.creation_code = self
+                .compiler
+                .compile(code.as_ref())
+                .map_err(|e| format!("Compiler error in room {} creation code: {}", room.name, e));
+        }
+        Ok(Default::default())
     }
 
     pub fn room_set_background_color(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -10185,19 +10773,60 @@ impl Game {
         }
     }
 
-    pub fn room_tile_add(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 9
-        unimplemented!("Called unimplemented kernel function room_tile_add")
+    pub fn room_tile_add(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (room_id, background_index, tile_x, tile_y, width, height, x, y, depth) =
+            expect_args!(args, [int, int, int, int, int, int, real, real, real])?;
+        self.room_tile_add_ext(&[
+            room_id.into(),
+            background_index.into(),
+            tile_x.into(),
+            tile_y.into(),
+            width.into(),
+            height.into(),
+            x.into(),
+            y.into(),
+            depth.into(),
+            1.into(),
+            1.into(),
+            1.into(),
+        ])
     }
 
-    pub fn room_tile_add_ext(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 12
-        unimplemented!("Called unimplemented kernel function room_tile_add_ext")
+    pub fn room_tile_add_ext(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (room_id, background_index, tile_x, tile_y, width, height, x, y, depth, xscale, yscale, alpha) =
+            expect_args!(args, [int, int, int, int, int, int, real, real, real, real, real, real])?;
+        if let Some(room) = self.assets.rooms.get_asset_mut(room_id) {
+            self.last_tile_id += 1;
+            room.tiles.push(Tile {
+                x: x.into(),
+                y: y.into(),
+                background_index: background_index.into(),
+                tile_x: tile_x.into(),
+                tile_y: tile_y.into(),
+                width: width.into(),
+                height: height.into(),
+                depth: depth.into(),
+                id: self.last_tile_id.into(),
+                alpha: alpha.into(),
+                blend: 0xffffff.into(),
+                xscale: xscale.into(),
+                yscale: yscale.into(),
+                visible: true.into(),
+            });
+            Ok(self.last_tile_id.into())
+        } else {
+            Ok((-1).into())
+        }
     }
 
-    pub fn room_tile_clear(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function room_tile_clear")
+    pub fn room_tile_clear(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let room_id = expect_args!(args, [int])?;
+        if let Some(room) = self.assets.rooms.get_asset_mut(room_id) {
+            room.tiles.clear();
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Room, room_id))
+        }
     }
 
     pub fn part_type_create(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -11279,14 +11908,30 @@ impl Game {
         }
     }
 
-    pub fn ds_queue_write(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function ds_queue_write")
+    pub fn ds_queue_write(&self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        match self.queues.get(id) {
+            Some(queue) => Ok(ds::write_queue(queue).into()),
+            None => Err(gml::Error::FunctionError("ds_queue_write".into(), ds::Error::NonexistentStructure(id).into())),
+        }
     }
 
-    pub fn ds_queue_read(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function ds_queue_read")
+    pub fn ds_queue_read(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, hex_data) = expect_args!(args, [int, string])?;
+        match self.queues.get_mut(id) {
+            Some(old_queue) => {
+                match hex::decode(hex_data.as_ref()) {
+                    Ok(data) => {
+                        if let Some(queue) = ds::read_queue(data.as_slice()) {
+                            *old_queue = queue;
+                        }
+                    },
+                    Err(e) => eprintln!("Warning (ds_queue_read): {}", e),
+                }
+                Ok(Default::default())
+            },
+            None => Err(gml::Error::FunctionError("ds_queue_read".into(), ds::Error::NonexistentStructure(id).into())),
+        }
     }
 
     pub fn ds_list_create(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -12092,9 +12737,7 @@ impl Game {
         let (id, x, y, val) = expect_args!(args, [int, int, int, any])?;
         if let Some(grid) = self.grids.get_mut(id) {
             if let Some(cell) = grid.get_mut(x, y) {
-                if cell.add_assign(val.clone()).is_err() {
-                    *cell = val;
-                }
+                ds::grid_add(cell, val);
             }
             Ok(Default::default())
         } else {
@@ -12105,9 +12748,8 @@ impl Game {
     pub fn ds_grid_multiply(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (id, x, y, val) = expect_args!(args, [int, int, int, any])?;
         if let Some(grid) = self.grids.get_mut(id) {
-            match (grid.get_mut(x, y), val) {
-                (Some(Value::Real(cell)), Value::Real(fac)) => *cell *= fac,
-                _ => (),
+            if let Some(cell) = grid.get_mut(x, y) {
+                ds::grid_multiply(cell, val);
             }
             Ok(Default::default())
         } else {
@@ -12127,14 +12769,28 @@ impl Game {
         }
     }
 
-    pub fn ds_grid_add_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function ds_grid_add_region")
+    pub fn ds_grid_add_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, x1, y1, x2, y2, val) = expect_args!(args, [int, int, int, int, int, any])?;
+        if let Some(grid) = self.grids.get_mut(id) {
+            for (_, cell) in grid.region_positioned_mut(x1, y1, x2, y2) {
+                ds::grid_add(cell, val.clone());
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_add_region".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_multiply_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function ds_grid_multiply_region")
+    pub fn ds_grid_multiply_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, x1, y1, x2, y2, val) = expect_args!(args, [int, int, int, int, int, any])?;
+        if let Some(grid) = self.grids.get_mut(id) {
+            for (_, cell) in grid.region_positioned_mut(x1, y1, x2, y2) {
+                ds::grid_multiply(cell, val.clone());
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_multiply_region".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
     pub fn ds_grid_set_disk(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -12149,29 +12805,64 @@ impl Game {
         }
     }
 
-    pub fn ds_grid_add_disk(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function ds_grid_add_disk")
+    pub fn ds_grid_add_disk(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r, val) = expect_args!(args, [int, real, real, real, any])?;
+        if let Some(grid) = self.grids.get_mut(id) {
+            for cell in grid.disk_mut(xm, ym, r) {
+                ds::grid_add(cell, val.clone());
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_add_disk".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_multiply_disk(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function ds_grid_multiply_disk")
+    pub fn ds_grid_multiply_disk(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r, val) = expect_args!(args, [int, real, real, real, any])?;
+        if let Some(grid) = self.grids.get_mut(id) {
+            for cell in grid.disk_mut(xm, ym, r) {
+                ds::grid_multiply(cell, val.clone());
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_multiply_disk".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_set_grid_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function ds_grid_set_grid_region")
+    /// Applies an operation to a region of one grid from the same-sized region of another, which may be the same grid.
+    fn ds_grid_grid_region(
+        &mut self,
+        function: &str,
+        args: &[Value],
+        op: impl Fn(&mut Value, Value),
+    ) -> gml::Result<Value> {
+        let (id, src_id, x1, y1, x2, y2, xpos, ypos) =
+            expect_args!(args, [int, int, int, int, int, int, int, int])?;
+        let source = match self.grids.get(src_id) {
+            Some(grid) => grid.copy_region(x1, y1, x2, y2),
+            None => {
+                return Err(gml::Error::FunctionError(function.into(), ds::Error::NonexistentStructure(src_id).into()))
+            },
+        };
+        match self.grids.get_mut(id) {
+            Some(grid) => {
+                grid.apply_region(xpos, ypos, source, op);
+                Ok(Default::default())
+            },
+            None => Err(gml::Error::FunctionError(function.into(), ds::Error::NonexistentStructure(id).into())),
+        }
     }
 
-    pub fn ds_grid_add_grid_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function ds_grid_add_grid_region")
+    pub fn ds_grid_set_grid_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.ds_grid_grid_region("ds_grid_set_grid_region", args, |cell, val| *cell = val)
     }
 
-    pub fn ds_grid_multiply_grid_region(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function ds_grid_multiply_grid_region")
+    pub fn ds_grid_add_grid_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.ds_grid_grid_region("ds_grid_add_grid_region", args, ds::grid_add)
+    }
+
+    pub fn ds_grid_multiply_grid_region(&mut self, args: &[Value]) -> gml::Result<Value> {
+        self.ds_grid_grid_region("ds_grid_multiply_grid_region", args, ds::grid_multiply)
     }
 
     pub fn ds_grid_get(&self, args: &[Value]) -> gml::Result<Value> {
@@ -12223,40 +12914,56 @@ impl Game {
     pub fn ds_grid_get_mean(&self, args: &[Value]) -> gml::Result<Value> {
         let (id, x1, y1, x2, y2) = expect_args!(args, [int, int, int, int, int])?;
         if let Some(grid) = self.grids.get(id) {
-            let mut count = 0;
-            Ok(grid
-                .region(x1, y1, x2, y2)
-                .filter_map(Value::as_real)
-                .reduce(|acc, val| {
-                    count += 1;
-                    acc + val
-                })
-                .map(|x| x / Real::from(count))
-                .unwrap_or_default()
-                .into())
+            Ok(ds::grid_mean(grid.region(x1, y1, x2, y2)).into())
         } else {
-            Err(gml::Error::FunctionError("ds_grid_get_max".into(), ds::Error::NonexistentStructure(id).into()))
+            Err(gml::Error::FunctionError("ds_grid_get_mean".into(), ds::Error::NonexistentStructure(id).into()))
         }
     }
 
-    pub fn ds_grid_get_disk_sum(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function ds_grid_get_disk_sum")
+    pub fn ds_grid_get_disk_sum(&self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r) = expect_args!(args, [int, real, real, real])?;
+        if let Some(grid) = self.grids.get(id) {
+            Ok(grid.disk(xm, ym, r).filter_map(Value::as_real).sum::<Real>().into())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_get_disk_sum".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_get_disk_max(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function ds_grid_get_disk_max")
+    pub fn ds_grid_get_disk_max(&self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r) = expect_args!(args, [int, real, real, real])?;
+        if let Some(grid) = self.grids.get(id) {
+            // weird fold needed due to NaN nonsense
+            Ok(grid
+                .disk(xm, ym, r)
+                .filter_map(Value::as_real)
+                .fold(Real::from(-100000000), |acc, val| if val >= acc { val } else { acc })
+                .into())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_get_disk_max".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_get_disk_min(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function ds_grid_get_disk_min")
+    pub fn ds_grid_get_disk_min(&self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r) = expect_args!(args, [int, real, real, real])?;
+        if let Some(grid) = self.grids.get(id) {
+            // weird fold needed due to NaN nonsense
+            Ok(grid
+                .disk(xm, ym, r)
+                .filter_map(Value::as_real)
+                .fold(Real::from(100000000), |acc, val| if val <= acc { val } else { acc })
+                .into())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_get_disk_min".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
-    pub fn ds_grid_get_disk_mean(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function ds_grid_get_disk_mean")
+    pub fn ds_grid_get_disk_mean(&self, args: &[Value]) -> gml::Result<Value> {
+        let (id, xm, ym, r) = expect_args!(args, [int, real, real, real])?;
+        if let Some(grid) = self.grids.get(id) {
+            Ok(ds::grid_mean(grid.disk(xm, ym, r)).into())
+        } else {
+            Err(gml::Error::FunctionError("ds_grid_get_disk_mean".into(), ds::Error::NonexistentStructure(id).into()))
+        }
     }
 
     pub fn ds_grid_value_exists(&self, args: &[Value]) -> gml::Result<Value> {
@@ -12336,9 +13043,17 @@ impl Game {
         }
     }
 
-    pub fn ds_grid_shuffle(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function ds_grid_shuffle")
+    pub fn ds_grid_shuffle(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let id = expect_args!(args, [int])?;
+        match self.grids.get_mut(id) {
+            Some(grid) => {
+                grid.shuffle(&mut self.rand);
+                Ok(Default::default())
+            },
+            None => {
+                Err(gml::Error::FunctionError("ds_grid_shuffle".into(), ds::Error::NonexistentStructure(id).into()))
+            },
+        }
     }
 
     pub fn ds_grid_write(&self, args: &[Value]) -> gml::Result<Value> {
@@ -12398,6 +13113,7 @@ impl Game {
             match &sound.handle {
                 FileType::Mp3(handle) => self.audio.play_mp3(handle, nanos),
                 FileType::Wav(handle) => self.audio.play_wav(handle, nanos),
+                FileType::Midi(handle) => self.audio.play_midi(handle, nanos),
                 FileType::None => (),
             }
             Ok(Default::default())
@@ -12413,6 +13129,7 @@ impl Game {
             match &sound.handle {
                 FileType::Mp3(handle) => self.audio.loop_mp3(handle),
                 FileType::Wav(handle) => self.audio.loop_wav(handle),
+                FileType::Midi(handle) => self.audio.loop_midi(handle),
                 FileType::None => (),
             }
             Ok(Default::default())
@@ -12441,10 +13158,10 @@ impl Game {
     pub fn sound_volume(&mut self, args: &[Value]) -> gml::Result<Value> {
         let (sound_id, volume) = expect_args!(args, [int, real])?;
         if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
-            // Deliberately written in a way that will produce an error when Kind::Midi is added
             use asset::sound::FileType;
             match &sound.handle {
                 FileType::Wav(handle) => handle.set_volume(volume.into()),
+                FileType::Midi(handle) => handle.set_volume(volume.into()),
                 FileType::Mp3(_) => (),
                 FileType::None => (),
             }
@@ -12454,19 +13171,27 @@ impl Game {
         }
     }
 
-    pub fn sound_fade(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function sound_fade")
+    pub fn sound_fade(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, volume, time) = expect_args!(args, [int, real, int])?;
+        if let Some(sound) = self.assets.sounds.get_asset(sound_id) {
+            if let asset::sound::FileType::Wav(handle) = &sound.handle {
+                self.audio.fade_wav(handle, volume.into(), time.max(0) as u32);
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sound, sound_id))
+        }
     }
 
-    pub fn sound_pan(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sound_pan")
+    pub fn sound_pan(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, pan) = expect_args!(args, [int, real])?;
+        self.update_wav(sound_id, |handle| handle.set_pan(pan.into()))
     }
 
-    pub fn sound_background_tempo(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        // Does nothing unless the sound is a midi, which we don't support yet
+    pub fn sound_background_tempo(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let factor = expect_args!(args, [real])?;
+        let nanos = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        self.audio.set_midi_tempo(factor.into(), nanos);
         Ok(Default::default())
     }
 
@@ -12481,159 +13206,253 @@ impl Game {
         unimplemented!("Called unimplemented kernel function sound_set_search_directory")
     }
 
-    pub fn sound_effect_set(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function sound_effect_set")
-    }
-
-    pub fn sound_effect_chorus(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function sound_effect_chorus")
+    pub fn sound_effect_set(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, effects) = expect_args!(args, [int, int])?;
+        self.update_wav(sound_id, |handle| handle.update_effects(|fx| fx.enabled = effects as u32))
     }
 
-    pub fn sound_effect_compressor(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function sound_effect_compressor")
+    pub fn sound_effect_chorus(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, wet_dry, depth, feedback, frequency, wave, delay, phase) =
+            expect_args!(args, [int, real, real, real, real, int, real, int])?;
+        let chorus = audio::effects::Modulation {
+            wet_dry: wet_dry.into_inner() as f32,
+            depth: depth.into_inner() as f32,
+            feedback: feedback.into_inner() as f32,
+            frequency: frequency.into_inner() as f32,
+            sine: wave != 0,
+            delay: delay.into_inner() as f32,
+            phase: phase.clamp(0, 4) as u32,
+        };
+        self.update_wav(sound_id, |handle| handle.update_effects(|fx| fx.chorus = chorus))
+    }
+
+    pub fn sound_effect_compressor(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, gain, attack, release, threshold, ratio, delay) =
+            expect_args!(args, [int, real, real, real, real, real, real])?;
+        let compressor = audio::effects::Compressor {
+            gain: gain.into_inner() as f32,
+            attack: attack.into_inner() as f32,
+            release: release.into_inner() as f32,
+            threshold: threshold.into_inner() as f32,
+            ratio: ratio.into_inner() as f32,
+            delay: delay.into_inner() as f32,
+        };
+        self.update_wav(sound_id, |handle| handle.update_effects(|fx| fx.compressor = compressor))
     }
 
-    pub fn sound_effect_echo(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 6
-        unimplemented!("Called unimplemented kernel function sound_effect_echo")
+    pub fn sound_effect_echo(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, wet_dry, feedback, left_delay, right_delay, pan_delay) =
+            expect_args!(args, [int, real, real, real, real, bool])?;
+        let echo = audio::effects::Echo {
+            wet_dry: wet_dry.into_inner() as f32,
+            feedback: feedback.into_inner() as f32,
+            left_delay: left_delay.into_inner() as f32,
+            right_delay: right_delay.into_inner() as f32,
+            pan_delay,
+        };
+        self.update_wav(sound_id, |handle| handle.update_effects(|fx| fx.echo = echo))
+    }
+
+    pub fn sound_effect_flanger(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, wet_dry, depth, feedback, frequency, wave, delay, phase) =
+            expect_args!(args, [int, real, real, real, real, int, real, int])?;
+        let flanger = audio::effects::Modulation {
+            wet_dry: wet_dry.into_inner() as f32,
+            depth: depth.into_inner() as f32,
+            feedback: feedback.into_inner() as f32,
+            frequency: frequency.into_inner() as f32,
+            sine: wave != 0,
+            delay: delay.into_inner() as f32,
+            phase: phase.clamp(0, 4) as u32,
+        };
+        self.update_wav(sound_id, |handle| handle.update_effects(|fx| fx.flanger = flanger))
     }
 
-    pub fn sound_effect_flanger(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 8
-        unimplemented!("Called unimplemented kernel function sound_effect_flanger")
+    pub fn sound_effect_gargle(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, rate, wave) = expect_args!(args, [int, real, int])?;
+        let gargle = audio::effects::Gargle { rate: rate.into_inner() as f32, square: wave != 0 };
+        self.update_wav(sound_id, |handle| handle.update_effects(|fx| fx.gargle = gargle))
     }
 
-    pub fn sound_effect_gargle(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function sound_effect_gargle")
+    pub fn sound_effect_equalizer(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, center, bandwidth, gain) = expect_args!(args, [int, real, real, real])?;
+        let equalizer = audio::effects::Equalizer {
+            center: center.into_inner() as f32,
+            bandwidth: bandwidth.into_inner() as f32,
+            gain: gain.into_inner() as f32,
+        };
+        self.update_wav(sound_id, |handle| handle.update_effects(|fx| fx.equalizer = equalizer))
     }
 
-    pub fn sound_effect_equalizer(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function sound_effect_equalizer")
+    pub fn sound_effect_reverb(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, gain, mix, time, ratio) = expect_args!(args, [int, real, real, real, real])?;
+        let reverb = audio::effects::Reverb {
+            gain: gain.into_inner() as f32,
+            mix: mix.into_inner() as f32,
+            time: time.into_inner() as f32,
+            hf_ratio: ratio.into_inner() as f32,
+        };
+        self.update_wav(sound_id, |handle| handle.update_effects(|fx| fx.reverb = reverb))
     }
 
-    pub fn sound_effect_reverb(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 5
-        unimplemented!("Called unimplemented kernel function sound_effect_reverb")
+    pub fn sound_3d_set_sound_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, x, y, z) = expect_args!(args, [int, real, real, real])?;
+        self.update_wav(sound_id, |handle| handle.update_3d(|s| s.position = [x.into(), y.into(), z.into()]))
     }
 
-    pub fn sound_3d_set_sound_position(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function sound_3d_set_sound_position")
+    pub fn sound_3d_set_sound_velocity(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, x, y, z) = expect_args!(args, [int, real, real, real])?;
+        self.update_wav(sound_id, |handle| handle.update_3d(|s| s.velocity = [x.into(), y.into(), z.into()]))
     }
 
-    pub fn sound_3d_set_sound_velocity(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 4
-        unimplemented!("Called unimplemented kernel function sound_3d_set_sound_velocity")
+    pub fn sound_3d_set_sound_distance(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, min_distance, max_distance) = expect_args!(args, [int, real, real])?;
+        self.update_wav(sound_id, |handle| {
+            handle.update_3d(|s| {
+                s.min_distance = min_distance.into();
+                s.max_distance = max_distance.into();
+            })
+        })
     }
 
-    pub fn sound_3d_set_sound_distance(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 3
-        unimplemented!("Called unimplemented kernel function sound_3d_set_sound_distance")
+    pub fn sound_3d_set_sound_cone(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (sound_id, x, y, z, angle_in, angle_out, volume_out) =
+            expect_args!(args, [int, real, real, real, real, real, real])?;
+        self.update_wav(sound_id, |handle| {
+            handle.update_3d(|s| {
+                s.cone_orientation = [x.into(), y.into(), z.into()];
+                s.cone_inside = angle_in.into();
+                s.cone_outside = angle_out.into();
+                s.outside_volume = volume_out.into();
+            })
+        })
     }
 
-    pub fn sound_3d_set_sound_cone(&mut self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 7
-        unimplemented!("Called unimplemented kernel function sound_3d_set_sound_cone")
+    /// Applies a change to a sound if it's a wav. Other kinds of sound can't have their pan, effects etc. changed.
+    fn update_wav(&mut self, sound_id: i32, f: impl FnOnce(&mut audio::WavHandle)) -> gml::Result<Value> {
+        if let Some(sound) = self.assets.sounds.get_asset_mut(sound_id) {
+            if let asset::sound::FileType::Wav(handle) = &mut sound.handle {
+                f(handle);
+            }
+            Ok(Default::default())
+        } else {
+            Err(gml::Error::NonexistentAsset(asset::Type::Sound, sound_id))
+        }
     }
 
-    pub fn cd_init(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_init")
+    pub fn cd_init(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.init(&mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_present(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_present")
+    pub fn cd_present(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.cd.present(&self.audio).into())
     }
 
-    pub fn cd_number(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_number")
+    pub fn cd_number(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok((self.cd.number(&self.audio) as f64).into())
     }
 
-    pub fn cd_playing(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_playing")
+    pub fn cd_playing(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.cd.playing().into())
     }
 
-    pub fn cd_paused(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_paused")
+    pub fn cd_paused(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(self.cd.paused().into())
     }
 
-    pub fn cd_track(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_track")
+    pub fn cd_track(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        let now = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        Ok((self.cd.track(&self.audio, now).0 as f64).into())
     }
 
-    pub fn cd_length(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_length")
+    pub fn cd_length(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        Ok(((self.cd.length(&self.audio) / 1_000_000) as f64).into())
     }
 
-    pub fn cd_track_length(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function cd_track_length")
+    pub fn cd_track_length(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let track = expect_args!(args, [int])?;
+        Ok(((self.cd.track_length(&self.audio, track) / 1_000_000) as f64).into())
     }
 
-    pub fn cd_position(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_position")
+    pub fn cd_position(&self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        let now = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        Ok(((self.cd.position(now) / 1_000_000) as f64).into())
     }
 
-    pub fn cd_track_position(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_track_position")
+    pub fn cd_track_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        let now = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        Ok(((self.cd.track(&self.audio, now).1 / 1_000_000) as f64).into())
     }
 
-    pub fn cd_play(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 2
-        unimplemented!("Called unimplemented kernel function cd_play")
+    pub fn cd_play(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let (first, last) = expect_args!(args, [int, int])?;
+        let now = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        self.cd.play(&mut self.audio, first, last, now);
+        Ok(Default::default())
     }
 
-    pub fn cd_stop(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_stop")
+    pub fn cd_stop(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.stop(&mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_pause(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_pause")
+    pub fn cd_pause(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        let now = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        self.cd.pause(now);
+        Ok(Default::default())
     }
 
-    pub fn cd_resume(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_resume")
+    pub fn cd_resume(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        let now = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        self.cd.resume(&mut self.audio, now);
+        Ok(Default::default())
     }
 
-    pub fn cd_set_position(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function cd_set_position")
+    pub fn cd_set_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let position = expect_args!(args, [int])?;
+        let now = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        self.cd.set_position(&mut self.audio, position.max(0) as u128 * 1_000_000, now);
+        Ok(Default::default())
     }
 
-    pub fn cd_set_track_position(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function cd_set_track_position")
+    pub fn cd_set_track_position(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let position = expect_args!(args, [int])?;
+        let now = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        self.cd.set_track_position(&mut self.audio, position.max(0) as u128 * 1_000_000, now);
+        Ok(Default::default())
     }
 
-    pub fn cd_open_door(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_open_door")
+    pub fn cd_open_door(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.open_door(&mut self.audio);
+        Ok(Default::default())
     }
 
-    pub fn cd_close_door(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 0
-        unimplemented!("Called unimplemented kernel function cd_close_door")
+    pub fn cd_close_door(&mut self, args: &[Value]) -> gml::Result<Value> {
+        expect_args!(args, [])?;
+        self.cd.close_door();
+        Ok(Default::default())
     }
 
-    pub fn mci_command(&self, _args: &[Value]) -> gml::Result<Value> {
-        // Expected arg count: 1
-        unimplemented!("Called unimplemented kernel function MCI_command")
+    pub fn mci_command(&mut self, args: &[Value]) -> gml::Result<Value> {
+        let command = expect_args!(args, [bytes])?;
+        let command = self.decode_str(command.as_ref()).into_owned();
+        let now = self.spoofed_time_nanos.unwrap_or_else(|| datetime::now_as_nanos());
+        let result = self.mci.command(&mut self.audio, &command, now);
+        Ok(result.into())
     }
 
     pub fn d3d_start(&mut self, args: &[Value]) -> gml::Result<Value> {
@@ -13398,7 +14217,8 @@ impl Game {
     }
 
     pub fn d3d_model_load(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (model_id, fname) = expect_args!(args, [int, string])?;
+        let (model_id, fname) = expect_args!(args, [int, bytes])?;
+        let fname = self.decode_str(fname.as_ref());
         fn load_model(fname: &str) -> Result<model::Model, Box<dyn std::error::Error>> {
             let mut file = std::io::BufReader::new(std::fs::File::open(file::to_path(&fname).as_ref())?);
             let version = file::read_real(&mut file)?;
@@ -13516,7 +14336,8 @@ impl Game {
     }
 
     pub fn d3d_model_save(&mut self, args: &[Value]) -> gml::Result<Value> {
-        let (model_id, fname) = expect_args!(args, [int, string])?;
+        let (model_id, fname) = expect_args!(args, [int, bytes])?;
+        let fname = self.decode_str(fname.as_ref());
         fn save_model(model: &model::Model, fname: &str) -> std::io::Result<()> {
             let mut file = std::io::BufWriter::new(std::fs::File::create(file::to_path(&fname).as_ref())?);
             writeln!(&mut file, "100\r\n{}\r", model.commands.len())?;
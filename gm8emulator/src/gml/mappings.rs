@@ -1173,11 +1173,11 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "action_partemit_destroy" => Function::Engine(Game::action_partemit_destroy),
     "action_partemit_burst" => Function::Engine(Game::action_partemit_burst),
     "action_partemit_stream" => Function::Engine(Game::action_partemit_stream),
-    "action_cd_play" => Function::Volatile(Game::cd_play),
-    "action_cd_stop" => Function::Volatile(Game::cd_stop),
-    "action_cd_pause" => Function::Volatile(Game::cd_pause),
-    "action_cd_resume" => Function::Volatile(Game::cd_resume),
-    "action_cd_present" => Function::Volatile(Game::cd_present),
+    "action_cd_play" => Function::Engine(Game::cd_play),
+    "action_cd_stop" => Function::Engine(Game::cd_stop),
+    "action_cd_pause" => Function::Engine(Game::cd_pause),
+    "action_cd_resume" => Function::Engine(Game::cd_resume),
+    "action_cd_present" => Function::Engine(Game::cd_present),
     "action_cd_playing" => Function::Volatile(Game::cd_playing),
     "action_set_cursor" => Function::Engine(Game::action_set_cursor),
     "action_webpage" => Function::Engine(Game::action_webpage),
@@ -1518,20 +1518,20 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "mouse_check_button_released" => Function::Constant(Game::mouse_check_button_released),
     "mouse_wheel_up" => Function::Constant(Game::mouse_wheel_up),
     "mouse_wheel_down" => Function::Constant(Game::mouse_wheel_down),
-    "joystick_exists" => Function::Engine(Game::joystick_exists),
-    "joystick_direction" => Function::Engine(Game::joystick_direction),
-    "joystick_name" => Function::Engine(Game::joystick_name),
-    "joystick_axes" => Function::Engine(Game::joystick_axes),
-    "joystick_buttons" => Function::Engine(Game::joystick_buttons),
-    "joystick_has_pov" => Function::Engine(Game::joystick_has_pov),
-    "joystick_check_button" => Function::Engine(Game::joystick_check_button),
-    "joystick_xpos" => Function::Engine(Game::joystick_xpos),
-    "joystick_ypos" => Function::Engine(Game::joystick_ypos),
-    "joystick_zpos" => Function::Engine(Game::joystick_zpos),
-    "joystick_rpos" => Function::Engine(Game::joystick_rpos),
-    "joystick_upos" => Function::Engine(Game::joystick_upos),
-    "joystick_vpos" => Function::Engine(Game::joystick_vpos),
-    "joystick_pov" => Function::Engine(Game::joystick_pov),
+    "joystick_exists" => Function::Constant(Game::joystick_exists),
+    "joystick_direction" => Function::Constant(Game::joystick_direction),
+    "joystick_name" => Function::Constant(Game::joystick_name),
+    "joystick_axes" => Function::Constant(Game::joystick_axes),
+    "joystick_buttons" => Function::Constant(Game::joystick_buttons),
+    "joystick_has_pov" => Function::Constant(Game::joystick_has_pov),
+    "joystick_check_button" => Function::Constant(Game::joystick_check_button),
+    "joystick_xpos" => Function::Constant(Game::joystick_xpos),
+    "joystick_ypos" => Function::Constant(Game::joystick_ypos),
+    "joystick_zpos" => Function::Constant(Game::joystick_zpos),
+    "joystick_rpos" => Function::Constant(Game::joystick_rpos),
+    "joystick_upos" => Function::Constant(Game::joystick_upos),
+    "joystick_vpos" => Function::Constant(Game::joystick_vpos),
+    "joystick_pov" => Function::Constant(Game::joystick_pov),
     "keyboard_clear" => Function::Engine(Game::keyboard_clear),
     "mouse_clear" => Function::Engine(Game::mouse_clear),
     "io_clear" => Function::Engine(Game::io_clear),
@@ -1549,7 +1549,7 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "mplay_session_find" => Function::Engine(Game::mplay_session_find),
     "mplay_session_name" => Function::Constant(Game::mplay_session_name),
     "mplay_session_join" => Function::Engine(Game::mplay_session_join),
-    "mplay_session_status" => Function::Constant(Game::mplay_session_status),
+    "mplay_session_status" => Function::Engine(Game::mplay_session_status),
     "mplay_session_end" => Function::Engine(Game::mplay_session_end),
     "mplay_player_find" => Function::Engine(Game::mplay_player_find),
     "mplay_player_name" => Function::Constant(Game::mplay_player_name),
@@ -1564,7 +1564,7 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "mplay_message_value" => Function::Constant(Game::mplay_message_value),
     "mplay_message_player" => Function::Constant(Game::mplay_message_player),
     "mplay_message_name" => Function::Constant(Game::mplay_message_name),
-    "mplay_message_count" => Function::Constant(Game::mplay_message_count),
+    "mplay_message_count" => Function::Engine(Game::mplay_message_count),
     "mplay_message_clear" => Function::Engine(Game::mplay_message_clear),
     "mplay_ipaddress" => Function::Engine(Game::mplay_ipaddress),
     "event_inherited" => Function::Runtime(Game::event_inherited),
@@ -2039,25 +2039,25 @@ pub const FUNCTIONS: phf::OrderedMap<&'static str, Function> = phf_ordered_map!
     "sound_3d_set_sound_velocity" => Function::Engine(Game::sound_3d_set_sound_velocity),
     "sound_3d_set_sound_distance" => Function::Engine(Game::sound_3d_set_sound_distance),
     "sound_3d_set_sound_cone" => Function::Engine(Game::sound_3d_set_sound_cone),
-    "cd_init" => Function::Volatile(Game::cd_init),
-    "cd_present" => Function::Volatile(Game::cd_present),
-    "cd_number" => Function::Volatile(Game::cd_number),
+    "cd_init" => Function::Engine(Game::cd_init),
+    "cd_present" => Function::Engine(Game::cd_present),
+    "cd_number" => Function::Engine(Game::cd_number),
     "cd_playing" => Function::Volatile(Game::cd_playing),
     "cd_paused" => Function::Volatile(Game::cd_paused),
-    "cd_track" => Function::Volatile(Game::cd_track),
-    "cd_length" => Function::Volatile(Game::cd_length),
-    "cd_track_length" => Function::Volatile(Game::cd_track_length),
+    "cd_track" => Function::Engine(Game::cd_track),
+    "cd_length" => Function::Engine(Game::cd_length),
+    "cd_track_length" => Function::Engine(Game::cd_track_length),
     "cd_position" => Function::Volatile(Game::cd_position),
-    "cd_track_position" => Function::Volatile(Game::cd_track_position),
-    "cd_play" => Function::Volatile(Game::cd_play),
-    "cd_stop" => Function::Volatile(Game::cd_stop),
-    "cd_pause" => Function::Volatile(Game::cd_pause),
-    "cd_resume" => Function::Volatile(Game::cd_resume),
-    "cd_set_position" => Function::Volatile(Game::cd_set_position),
-    "cd_set_track_position" => Function::Volatile(Game::cd_set_track_position),
-    "cd_open_door" => Function::Volatile(Game::cd_open_door),
-    "cd_close_door" => Function::Volatile(Game::cd_close_door),
-    "MCI_command" => Function::Volatile(Game::mci_command),
+    "cd_track_position" => Function::Engine(Game::cd_track_position),
+    "cd_play" => Function::Engine(Game::cd_play),
+    "cd_stop" => Function::Engine(Game::cd_stop),
+    "cd_pause" => Function::Engine(Game::cd_pause),
+    "cd_resume" => Function::Engine(Game::cd_resume),
+    "cd_set_position" => Function::Engine(Game::cd_set_position),
+    "cd_set_track_position" => Function::Engine(Game::cd_set_track_position),
+    "cd_open_door" => Function::Engine(Game::cd_open_door),
+    "cd_close_door" => Function::Engine(Game::cd_close_door),
+    "MCI_command" => Function::Engine(Game::mci_command),
     "d3d_start" => Function::Engine(Game::d3d_start),
     "d3d_end" => Function::Engine(Game::d3d_end),
     "d3d_set_perspective" => Function::Engine(Game::d3d_set_perspective),
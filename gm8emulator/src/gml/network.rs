@@ -1,4 +1,15 @@
-use std::{io, net};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{self, Read, Write},
+    net::{self, Shutdown, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
 
 pub fn get_local_ip() -> io::Result<net::IpAddr> {
     // For the meaning of 0.0.0.0, see 'INADDR_ANY'. Port 0 states that we don't expect any
@@ -42,3 +53,460 @@ pub fn get_local_ip() -> io::Result<net::IpAddr> {
     socket.connect(&broadcast[..])?;
     Ok(socket.local_addr()?.ip())
 }
+
+// DirectPlay's port. Sessions are hosted on the first free port from here, so several can run on one machine.
+const BASE_PORT: u16 = 47624;
+const PORT_COUNT: u16 = 8;
+const CONNECT_TIMEOUT: Duration = Duration::from_millis(250);
+const JOIN_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_PACKET_SIZE: usize = 16 * 1024 * 1024;
+
+/// A value in shared data or a message, which can be a real or a string just like in GML.
+#[derive(Clone, Serialize, Deserialize)]
+pub enum MpValue {
+    Real(f64),
+    Str(Vec<u8>),
+}
+
+#[derive(Serialize, Deserialize)]
+enum Packet {
+    // client to host
+    Query,
+    Join { name: Vec<u8> },
+    WriteData { index: u32, value: MpValue },
+    Send { to: u32, id: f64, value: MpValue },
+
+    // host to client
+    SessionInfo { name: Vec<u8> },
+    Welcome { id: u32, players: Vec<(u32, Vec<u8>)>, data: Vec<(u32, MpValue)> },
+    Rejected,
+    PlayerJoined { id: u32, name: Vec<u8> },
+    PlayerLeft { id: u32 },
+    Data { index: u32, value: MpValue },
+    Message { from: u32, id: f64, value: MpValue },
+}
+
+fn write_packet(mut stream: &TcpStream, packet: &Packet) -> io::Result<()> {
+    let data = bincode::serialize(packet).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let mut buf = Vec::with_capacity(data.len() + 4);
+    buf.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&data);
+    stream.write_all(&buf)
+}
+
+fn read_packet(mut stream: &TcpStream) -> io::Result<Packet> {
+    let mut len = [0u8; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as usize;
+    if len > MAX_PACKET_SIZE {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "packet too large"))
+    }
+    let mut data = vec![0; len];
+    stream.read_exact(&mut data)?;
+    bincode::deserialize(&data).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Which players a message function applies to.
+pub enum PlayerSelector {
+    All,
+    Id(u32),
+    Name(Vec<u8>),
+}
+
+pub struct ReceivedMessage {
+    pub from: u32,
+    pub from_name: Vec<u8>,
+    pub id: f64,
+    pub value: MpValue,
+}
+
+struct FoundSession {
+    name: Vec<u8>,
+    address: net::SocketAddr,
+}
+
+struct Session {
+    stream: TcpStream,
+    incoming: mpsc::Receiver<Packet>,
+    players: Vec<(u32, Vec<u8>)>,
+    host: Option<Arc<AtomicBool>>,
+}
+
+/// The emulator's version of GM8's DirectPlay multiplayer, TCP/IP only.
+/// Whoever creates a session runs a small host on a background thread, and every player (including the one who
+/// created it) connects to that. The host relays messages and keeps the shared data for newly joining players.
+#[derive(Default)]
+pub struct Multiplayer {
+    address: Option<String>,
+    sessions: Vec<FoundSession>,
+    session: Option<Session>,
+    data: HashMap<u32, MpValue>,
+    messages: VecDeque<ReceivedMessage>,
+    pub current_message: Option<ReceivedMessage>,
+}
+
+impl Multiplayer {
+    /// Starts using TCP/IP. The address is only needed for joining sessions, and may have a port on the end.
+    pub fn init_tcpip(&mut self, address: &str) -> bool {
+        self.end();
+        self.address = Some(address.trim().to_string());
+        true
+    }
+
+    /// 0 for no connection or 2 for TCP/IP, as other kinds of connection aren't supported.
+    pub fn connect_status(&self) -> u32 {
+        if self.address.is_some() { 2 } else { 0 }
+    }
+
+    pub fn end(&mut self) {
+        self.end_session();
+        self.address = None;
+        self.sessions.clear();
+    }
+
+    /// Sets whether the host role moves to another player when the host leaves.
+    /// The host here lives in the creating player's process, so sessions end when it leaves either way.
+    pub fn set_host_migration(&mut self, _migrate: bool) {}
+
+    /// Sets whether shared data is sent guaranteed. Everything goes over TCP, so it's always guaranteed anyway.
+    pub fn set_guaranteed_data(&mut self, _guaranteed: bool) {}
+
+    /// 0 for no session, 1 if we created it and 2 if we joined it.
+    pub fn session_status(&mut self) -> u32 {
+        self.poll();
+        match &self.session {
+            Some(Session { host: Some(_), .. }) => 1,
+            Some(_) => 2,
+            None => 0,
+        }
+    }
+
+    pub fn create_session(&mut self, name: &[u8], max_players: u32, player_name: &[u8]) -> bool {
+        if self.address.is_none() {
+            return false
+        }
+        self.end_session();
+        let listener = match (BASE_PORT..BASE_PORT + PORT_COUNT)
+            .find_map(|port| TcpListener::bind((net::Ipv4Addr::UNSPECIFIED, port)).ok())
+        {
+            Some(listener) => listener,
+            None => return false,
+        };
+        let port = match listener.local_addr() {
+            Ok(addr) => addr.port(),
+            Err(_) => return false,
+        };
+        let running = Arc::new(AtomicBool::new(true));
+        if host_session(listener, name.to_vec(), max_players, running.clone()).is_err() {
+            return false
+        }
+        if self.join_at((net::Ipv4Addr::LOCALHOST, port).into(), player_name) {
+            if let Some(session) = &mut self.session {
+                session.host = Some(running);
+            }
+            true
+        } else {
+            running.store(false, Ordering::Release);
+            false
+        }
+    }
+
+    /// Looks for sessions at the address given to init_tcpip, or on this machine if none was given.
+    pub fn find_sessions(&mut self) -> usize {
+        self.sessions.clear();
+        let address = match &self.address {
+            Some(address) => address.as_str(),
+            None => return 0,
+        };
+        let host = if address.is_empty() { "127.0.0.1" } else { address };
+        let candidates: Vec<net::SocketAddr> = match host.to_socket_addrs() {
+            // the address had its own port on it
+            Ok(addrs) => addrs.collect(),
+            Err(_) => (BASE_PORT..BASE_PORT + PORT_COUNT)
+                .filter_map(|port| (host, port).to_socket_addrs().ok())
+                .flatten()
+                .collect(),
+        };
+        for address in candidates {
+            if let Ok(stream) = TcpStream::connect_timeout(&address, CONNECT_TIMEOUT) {
+                let _ = stream.set_read_timeout(Some(JOIN_TIMEOUT));
+                if write_packet(&stream, &Packet::Query).is_ok() {
+                    if let Ok(Packet::SessionInfo { name }) = read_packet(&stream) {
+                        self.sessions.push(FoundSession { name, address });
+                    }
+                }
+            }
+        }
+        self.sessions.len()
+    }
+
+    pub fn session_name(&self, index: usize) -> Option<&[u8]> {
+        self.sessions.get(index).map(|s| s.name.as_slice())
+    }
+
+    pub fn join_session(&mut self, index: usize, player_name: &[u8]) -> bool {
+        self.end_session();
+        match self.sessions.get(index) {
+            Some(session) => self.join_at(session.address, player_name),
+            None => false,
+        }
+    }
+
+    fn join_at(&mut self, address: net::SocketAddr, player_name: &[u8]) -> bool {
+        let stream = match TcpStream::connect_timeout(&address, JOIN_TIMEOUT) {
+            Ok(stream) => stream,
+            Err(_) => return false,
+        };
+        let _ = stream.set_nodelay(true);
+        let _ = stream.set_read_timeout(Some(JOIN_TIMEOUT));
+        if write_packet(&stream, &Packet::Join { name: player_name.to_vec() }).is_err() {
+            return false
+        }
+        let (players, data) = match read_packet(&stream) {
+            Ok(Packet::Welcome { players, data, .. }) => (players, data),
+            _ => return false,
+        };
+        let reader = match stream.try_clone() {
+            Ok(reader) => reader,
+            Err(_) => return false,
+        };
+        let _ = reader.set_read_timeout(None);
+        let (sender, incoming) = mpsc::channel();
+        thread::spawn(move || {
+            while let Ok(packet) = read_packet(&reader) {
+                if sender.send(packet).is_err() {
+                    break
+                }
+            }
+        });
+        self.data = data.into_iter().collect();
+        self.messages.clear();
+        self.current_message = None;
+        self.session = Some(Session { stream, incoming, players, host: None });
+        true
+    }
+
+    pub fn end_session(&mut self) {
+        if let Some(session) = self.session.take() {
+            let _ = session.stream.shutdown(Shutdown::Both);
+            if let Some(running) = session.host {
+                running.store(false, Ordering::Release);
+            }
+        }
+        self.data.clear();
+        self.messages.clear();
+    }
+
+    /// Handles everything that's arrived from the host since last time.
+    fn poll(&mut self) {
+        let session = match &mut self.session {
+            Some(session) => session,
+            None => return,
+        };
+        loop {
+            match session.incoming.try_recv() {
+                Ok(Packet::PlayerJoined { id, name }) => session.players.push((id, name)),
+                Ok(Packet::PlayerLeft { id }) => session.players.retain(|(x, _)| *x != id),
+                Ok(Packet::Data { index, value }) => {
+                    self.data.insert(index, value);
+                },
+                Ok(Packet::Message { from, id, value }) => {
+                    let from_name = session.players.iter().find(|(x, _)| *x == from).map(|(_, n)| n.clone());
+                    self.messages.push_back(ReceivedMessage {
+                        from,
+                        from_name: from_name.unwrap_or_default(),
+                        id,
+                        value,
+                    });
+                },
+                Ok(_) => (),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    // the host's gone, so the session is over
+                    self.end_session();
+                    break
+                },
+            }
+        }
+    }
+
+    pub fn player_count(&mut self) -> usize {
+        self.poll();
+        self.session.as_ref().map_or(0, |s| s.players.len())
+    }
+
+    pub fn player(&self, index: usize) -> Option<(u32, &[u8])> {
+        self.session.as_ref()?.players.get(index).map(|(id, name)| (*id, name.as_slice()))
+    }
+
+    pub fn write_data(&mut self, index: u32, value: MpValue) {
+        self.poll();
+        if let Some(session) = &self.session {
+            let _ = write_packet(&session.stream, &Packet::WriteData { index, value: value.clone() });
+        }
+        self.data.insert(index, value);
+    }
+
+    pub fn read_data(&mut self, index: u32) -> Option<&MpValue> {
+        self.poll();
+        self.data.get(&index)
+    }
+
+    fn matches(&self, player: &PlayerSelector, id: u32) -> bool {
+        match player {
+            PlayerSelector::All => true,
+            PlayerSelector::Id(x) => *x == id,
+            PlayerSelector::Name(name) => self
+                .session
+                .as_ref()
+                .map_or(false, |s| s.players.iter().any(|(x, n)| *x == id && n == name)),
+        }
+    }
+
+    /// Sends a message to one player or everyone else. Returns false if there's no such player.
+    pub fn send_message(&self, player: &PlayerSelector, id: f64, value: MpValue) -> bool {
+        let session = match &self.session {
+            Some(session) => session,
+            None => return false,
+        };
+        let to = match player {
+            PlayerSelector::All => 0,
+            PlayerSelector::Id(id) => *id,
+            PlayerSelector::Name(name) => match session.players.iter().find(|(_, n)| n == name) {
+                Some((id, _)) => *id,
+                None => return false,
+            },
+        };
+        if to != 0 && !session.players.iter().any(|(x, _)| *x == to) {
+            return false
+        }
+        write_packet(&session.stream, &Packet::Send { to, id, value }).is_ok()
+    }
+
+    /// Takes the next message from the given player(s) and makes it the current message.
+    pub fn receive_message(&mut self, player: &PlayerSelector) -> bool {
+        self.poll();
+        match self.messages.iter().position(|m| self.matches(player, m.from)) {
+            Some(index) => {
+                self.current_message = self.messages.remove(index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    pub fn message_count(&mut self, player: &PlayerSelector) -> usize {
+        self.poll();
+        self.messages.iter().filter(|m| self.matches(player, m.from)).count()
+    }
+
+    pub fn clear_messages(&mut self, player: &PlayerSelector) {
+        self.poll();
+        let messages = std::mem::take(&mut self.messages);
+        self.messages = messages.into_iter().filter(|m| !self.matches(player, m.from)).collect();
+    }
+}
+
+impl Drop for Multiplayer {
+    fn drop(&mut self) {
+        self.end_session();
+    }
+}
+
+struct HostState {
+    name: Vec<u8>,
+    max_players: u32,
+    next_id: u32,
+    players: Vec<(u32, Vec<u8>, TcpStream)>,
+    data: HashMap<u32, MpValue>,
+}
+
+impl HostState {
+    fn broadcast(&self, except: u32, packet: &Packet) {
+        for (_, _, stream) in self.players.iter().filter(|(id, _, _)| *id != except) {
+            let _ = write_packet(stream, packet);
+        }
+    }
+}
+
+/// Starts hosting a session on the given listener, until `running` is set to false.
+fn host_session(listener: TcpListener, name: Vec<u8>, max_players: u32, running: Arc<AtomicBool>) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let state =
+        Arc::new(Mutex::new(HostState { name, max_players, next_id: 1, players: Vec::new(), data: HashMap::new() }));
+    thread::spawn(move || {
+        while running.load(Ordering::Acquire) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let state = state.clone();
+                    thread::spawn(move || host_client(stream, state));
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(20)),
+                Err(_) => break,
+            }
+        }
+        // disconnecting everyone tells them the session has ended
+        for (_, _, stream) in state.lock().unwrap().players.drain(..) {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    });
+    Ok(())
+}
+
+fn host_client(stream: TcpStream, state: Arc<Mutex<HostState>>) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_nodelay(true);
+    let id = match read_packet(&stream) {
+        Ok(Packet::Query) => {
+            let name = state.lock().unwrap().name.clone();
+            let _ = write_packet(&stream, &Packet::SessionInfo { name });
+            return
+        },
+        Ok(Packet::Join { name }) => {
+            let mut state = state.lock().unwrap();
+            if state.max_players != 0 && state.players.len() >= state.max_players as usize {
+                let _ = write_packet(&stream, &Packet::Rejected);
+                return
+            }
+            let writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(_) => return,
+            };
+            let id = state.next_id;
+            state.next_id += 1;
+            let mut players = state.players.iter().map(|(id, name, _)| (*id, name.clone())).collect::<Vec<_>>();
+            players.push((id, name.clone()));
+            let data = state.data.iter().map(|(k, v)| (*k, v.clone())).collect();
+            if write_packet(&stream, &Packet::Welcome { id, players, data }).is_err() {
+                return
+            }
+            state.broadcast(id, &Packet::PlayerJoined { id, name: name.clone() });
+            state.players.push((id, name, writer));
+            id
+        },
+        _ => return,
+    };
+
+    while let Ok(packet) = read_packet(&stream) {
+        let state = &mut *state.lock().unwrap();
+        match packet {
+            Packet::WriteData { index, value } => {
+                state.broadcast(id, &Packet::Data { index, value: value.clone() });
+                state.data.insert(index, value);
+            },
+            Packet::Send { to: 0, id: message_id, value } => {
+                state.broadcast(id, &Packet::Message { from: id, id: message_id, value });
+            },
+            Packet::Send { to, id: message_id, value } => {
+                if let Some((_, _, stream)) = state.players.iter().find(|(x, _, _)| *x == to) {
+                    let _ = write_packet(stream, &Packet::Message { from: id, id: message_id, value });
+                }
+            },
+            _ => (),
+        }
+    }
+
+    let mut state = state.lock().unwrap();
+    state.players.retain(|(x, _, _)| *x != id);
+    state.broadcast(id, &Packet::PlayerLeft { id });
+}
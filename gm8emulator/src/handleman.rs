@@ -56,6 +56,16 @@ impl<T> HandleList<T> {
     pub fn put(&mut self, handle: T) -> i32 {
         self.add(handle).unwrap()
     }
+
+    /// The indices of every slot that's in use.
+    pub fn ids(&self) -> impl Iterator<Item = i32> + '_ {
+        self.0.iter().enumerate().filter(|(_, slot)| slot.is_some()).map(|(i, _)| i as i32)
+    }
+
+    /// Converts every handle, keeping them at the same indices.
+    pub fn map<U>(&self, mut f: impl FnMut(&T) -> U) -> HandleList<U> {
+        HandleList(self.0.iter().map(|slot| slot.as_ref().map(&mut f)).collect())
+    }
 }
 
 impl<T, const LEN: usize> HandleArray<T, LEN> {
@@ -1,4 +1,10 @@
+#[cfg(not(target_os = "linux"))]
+mod dummy;
+#[cfg(target_os = "linux")]
+mod evdev;
+
 use crate::types::ArraySerde;
+use encoding_rs::Encoding;
 use serde::{Deserialize, Serialize};
 use std::{
     fmt::{
@@ -16,6 +22,17 @@ const MB_NONE: i8 = 0;
 const VK_NOKEY: u8 = 0; // TODO: dont redefine
 const VK_ANYKEY: u8 = 1; // TODO: dont redefine
 
+#[cfg(not(target_os = "linux"))]
+use dummy as gamepad;
+#[cfg(target_os = "linux")]
+use evdev as gamepad;
+
+pub use gamepad::GamepadManager;
+
+pub const JOYSTICK_COUNT: usize = 2;
+pub const JOYSTICK_AXIS_COUNT: usize = 6;
+pub const JOYSTICK_BUTTON_COUNT: usize = 32;
+
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 #[repr(u8)]
 pub enum Button {
@@ -676,6 +693,94 @@ const fn make_vk_fn_input_remap() -> [u8; KEY_MAX] {
 }
 const VK_FN_INPUT_REMAP: [u8; KEY_MAX] = make_vk_fn_input_remap();
 
+/// The keyboard layout that recorded key presses are typed with, since replays only record the keys themselves.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum KeyboardLayout {
+    Us,
+    Uk,
+    German,
+}
+
+impl Default for KeyboardLayout {
+    fn default() -> Self {
+        Self::Us
+    }
+}
+
+impl KeyboardLayout {
+    pub fn from_label(label: &str) -> Option<Self> {
+        match label.trim().to_ascii_lowercase().as_str() {
+            "us" | "en-us" => Some(Self::Us),
+            "uk" | "gb" | "en-gb" => Some(Self::Uk),
+            "de" | "de-de" => Some(Self::German),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Us => "us",
+            Self::Uk => "uk",
+            Self::German => "de",
+        }
+    }
+
+    /// The character a key types with this layout, as Windows would send it in WM_CHAR.
+    /// Dead keys, such as German's ^ and ´, don't type anything by themselves so they're left out.
+    fn char(self, vk: u8, shift: bool, capslock: bool, numlock: bool) -> Option<char> {
+        use KeyboardLayout::*;
+        let pick = |normal: char, shifted: char| Some(if shift { shifted } else { normal });
+        // caps lock only applies to letters, where it swaps the case shift gives
+        let letter = |normal: char, shifted: char| Some(if shift != capslock { shifted } else { normal });
+        let shifted_digits = match self {
+            Us => ")!@#$%^&*(",
+            Uk => ")!\"£$%^&*(",
+            German => "=!\"§$%&/()",
+        };
+        match (self, vk) {
+            (_, 0x08 | 0x09 | 0x0D | 0x1B | 0x20) => Some(char::from(vk)), // backspace, tab, return, escape, space
+            (_, b'0'..=b'9') => pick(char::from(vk), shifted_digits.chars().nth(usize::from(vk - b'0'))?),
+            (_, b'A'..=b'Z') => letter(char::from(vk.to_ascii_lowercase()), char::from(vk)),
+            (_, 0x60..=0x69) if numlock => Some(char::from(b'0' + (vk - 0x60))),
+            (_, 0x6A) => Some('*'),
+            (_, 0x6B) => Some('+'),
+            (_, 0x6D) => Some('-'),
+            (German, 0x6E) if numlock => Some(','),
+            (_, 0x6E) if numlock => Some('.'),
+            (_, 0x6F) => Some('/'),
+            (Us | Uk, 0xBA) => pick(';', ':'),
+            (Us | Uk, 0xBB) => pick('=', '+'),
+            (Us | Uk, 0xBC) => pick(',', '<'),
+            (Us | Uk, 0xBD) => pick('-', '_'),
+            (Us | Uk, 0xBE) => pick('.', '>'),
+            (Us | Uk, 0xBF) => pick('/', '?'),
+            (Us | Uk, 0xDB) => pick('[', '{'),
+            (Us | Uk, 0xDC) => pick('\\', '|'),
+            (Us | Uk, 0xDD) => pick(']', '}'),
+            (Us, 0xC0) => pick('`', '~'),
+            (Us, 0xDE) => pick('\'', '"'),
+            (Uk, 0xC0) => pick('\'', '@'),
+            (Uk, 0xDE) => pick('#', '~'),
+            (Uk, 0xDF) => pick('`', '¬'),
+            (Uk, 0xE2) => pick('\\', '|'),
+            (German, 0xBA) => letter('ü', 'Ü'),
+            (German, 0xBB) => pick('+', '*'),
+            (German, 0xBC) => pick(',', ';'),
+            (German, 0xBD) => pick('-', '_'),
+            (German, 0xBE) => pick('.', ':'),
+            (German, 0xBF) => pick('#', '\''),
+            (German, 0xC0) => letter('ö', 'Ö'),
+            (German, 0xDB) => pick('ß', '?'),
+            (German, 0xDE) => letter('ä', 'Ä'),
+            (German, 0xE2) => pick('<', '>'),
+            _ => None,
+        }
+    }
+}
+
+/// GM8 drops the start of keyboard_string once it gets longer than this.
+const KEYBOARD_STRING_MAX: usize = 1024;
+
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 #[repr(i8)]
 pub enum MouseButton {
@@ -710,6 +815,114 @@ fn mouse2button(code: i8) -> Option<Button> {
     }
 }
 
+/// Joystick axes in the order GameMaker exposes them (joystick_xpos, joystick_ypos, ...)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[repr(u8)]
+pub enum JoystickAxis {
+    X = 0,
+    Y = 1,
+    Z = 2,
+    R = 3,
+    U = 4,
+    V = 5,
+}
+
+impl JoystickAxis {
+    pub const ALL: [Self; JOYSTICK_AXIS_COUNT] = [Self::X, Self::Y, Self::Z, Self::R, Self::U, Self::V];
+}
+
+/// State of a single joystick. Axis positions are stored as signed 16-bit values so they can be
+/// recorded into replays exactly - `i16::MAX` is fully right/down and `-i16::MAX` is fully left/up.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Joystick {
+    pub name: String,
+    pub axis_count: u8,
+    pub button_count: u8,
+    pub has_pov: bool,
+    axes: [i16; JOYSTICK_AXIS_COUNT],
+    pov: Option<u16>, // hundredths of a degree clockwise from up, None if centred
+    buttons: u32,
+}
+
+impl Joystick {
+    pub fn new(name: String, axis_count: u8, button_count: u8, has_pov: bool) -> Self {
+        Self {
+            name,
+            axis_count: axis_count.min(JOYSTICK_AXIS_COUNT as u8),
+            button_count: button_count.min(JOYSTICK_BUTTON_COUNT as u8),
+            has_pov,
+            axes: [0; JOYSTICK_AXIS_COUNT],
+            pov: None,
+            buttons: 0,
+        }
+    }
+
+    /// The joystick used in record and replay mode, which has every input GameMaker can see.
+    pub fn emulated(id: usize) -> Self {
+        Self::new(
+            format!("OpenGMK Virtual Joystick {}", id + 1),
+            JOYSTICK_AXIS_COUNT as u8,
+            JOYSTICK_BUTTON_COUNT as u8,
+            true,
+        )
+    }
+
+    #[inline]
+    pub fn axis(&self, axis: JoystickAxis) -> i16 {
+        self.axes[axis as usize]
+    }
+
+    /// Axis position as GameMaker reports it, between -1 and 1
+    pub fn axis_pos(&self, axis: JoystickAxis) -> f64 {
+        (f64::from(self.axis(axis)) / f64::from(i16::MAX)).clamp(-1.0, 1.0)
+    }
+
+    #[inline]
+    pub fn set_axis(&mut self, axis: JoystickAxis, value: i16) {
+        self.axes[axis as usize] = value;
+    }
+
+    #[inline]
+    pub fn pov(&self) -> Option<u16> {
+        self.pov
+    }
+
+    #[inline]
+    pub fn set_pov(&mut self, pov: Option<u16>) {
+        self.pov = pov.map(|x| x % 36000);
+    }
+
+    /// Bitmask of held buttons, button 1 being the lowest bit
+    #[inline]
+    pub fn buttons(&self) -> u32 {
+        self.buttons
+    }
+
+    /// Checks a button, 0-indexed
+    pub fn button(&self, button: u8) -> bool {
+        usize::from(button) < JOYSTICK_BUTTON_COUNT && self.buttons & (1 << button) != 0
+    }
+
+    pub fn set_button(&mut self, button: u8, pressed: bool) {
+        if usize::from(button) < JOYSTICK_BUTTON_COUNT {
+            if pressed {
+                self.buttons |= 1 << button;
+            } else {
+                self.buttons &= !(1 << button);
+            }
+        }
+    }
+
+    /// The numpad key corresponding to the stick direction, like joystick_direction()
+    pub fn direction(&self) -> u8 {
+        let x = self.axis_pos(JoystickAxis::X);
+        let y = self.axis_pos(JoystickAxis::Y);
+        let column = if x < -0.5 { 0 } else if x > 0.5 { 2 } else { 1 };
+        let row = if y < -0.5 { 2 } else if y > 0.5 { 0 } else { 1 };
+        Button::Keypad1 as u8 + row * 3 + column
+    }
+}
+
 const fn gen_default_keymap() -> [u8; KEY_MAX] {
     let mut map = [0u8; KEY_MAX];
     let mut i = 0;
@@ -738,6 +951,13 @@ pub struct Input {
     mouse_previous: i8,
     mouse_position_previous: (i32, i32),
     numlock_state: bool, // spoofed!
+    capslock_state: bool, // also spoofed, and only used for typing recorded key presses
+    keyboard_layout: KeyboardLayout,
+    key_lastchar: u8,
+    keyboard_string: Vec<u8>, // in the game's code page
+
+    // joysticks, None if not connected
+    joysticks: [Option<Joystick>; JOYSTICK_COUNT],
 }
 
 impl Input {
@@ -755,6 +975,11 @@ impl Input {
             mouse_previous: 0,
             mouse_position_previous: (0, 0),
             numlock_state: false,
+            capslock_state: false,
+            keyboard_layout: KeyboardLayout::Us,
+            key_lastchar: 0,
+            keyboard_string: Vec::new(),
+            joysticks: [None, None],
         }
     }
 
@@ -905,7 +1130,7 @@ impl Input {
     pub fn keyboard_clear_all(&mut self) {
         self.key_current = 0;
         self.key_previous = 0;
-        // TODO: self.key_lastchar = 0;
+        self.key_lastchar = 0;
         self.button_state.iter_mut().for_each(|x| *x = false);
         self.button_state_press.iter_mut().for_each(|x| *x = false);
         self.button_state_release.iter_mut().for_each(|x| *x = false);
@@ -955,6 +1180,69 @@ impl Input {
         self.key_previous = vk;
     }
 
+    /// Handles a character being typed, as GM8 does for WM_CHAR.
+    pub fn char_input(&mut self, chr: u8) {
+        self.key_lastchar = chr;
+        if chr == 0x08 {
+            self.keyboard_string.pop();
+        } else if chr >= 0x20 {
+            self.keyboard_string.push(chr);
+            if self.keyboard_string.len() > KEYBOARD_STRING_MAX {
+                self.keyboard_string.remove(0);
+            }
+        }
+    }
+
+    /// Types the character for a key press, if it has one, taking the modifier keys and layout into account.
+    /// Characters which the game's code page doesn't have are dropped, as Windows would do.
+    pub fn char_press(&mut self, vk: u8, encoding: &'static Encoding) {
+        if vk == Button::CapsLock as u8 {
+            self.capslock_state = !self.capslock_state;
+        }
+        let held = |button: Button| self.keyboard_check_direct(button as u8);
+        if held(Button::Control) || held(Button::Alt) {
+            return
+        }
+        let shift = held(Button::Shift);
+        if let Some(chr) = self.keyboard_layout.char(vk, shift, self.capslock_state, self.numlock_state) {
+            let mut buf = [0; 4];
+            let (bytes, _, unmappable) = encoding.encode(chr.encode_utf8(&mut buf));
+            if !unmappable {
+                bytes.iter().for_each(|&byte| self.char_input(byte));
+            }
+        }
+    }
+
+    #[inline]
+    pub fn keyboard_layout(&self) -> KeyboardLayout {
+        self.keyboard_layout
+    }
+
+    #[inline]
+    pub fn set_keyboard_layout(&mut self, layout: KeyboardLayout) {
+        self.keyboard_layout = layout;
+    }
+
+    #[inline]
+    pub fn keyboard_lastchar(&self) -> u8 {
+        self.key_lastchar
+    }
+
+    #[inline]
+    pub fn set_keyboard_lastchar(&mut self, chr: u8) {
+        self.key_lastchar = chr;
+    }
+
+    #[inline]
+    pub fn keyboard_string(&self) -> &[u8] {
+        &self.keyboard_string
+    }
+
+    #[inline]
+    pub fn set_keyboard_string(&mut self, string: &[u8]) {
+        self.keyboard_string = string.to_vec();
+    }
+
     fn mouse_check_button_internal_indirect(&self, state: &[bool; KEY_MAX], mb: i8) -> bool {
         match mb {
             MB_ANY => {
@@ -1075,6 +1363,30 @@ impl Input {
         self.mouse_position_previous.1
     }
 
+    #[inline]
+    pub fn joystick(&self, id: usize) -> Option<&Joystick> {
+        self.joysticks.get(id).and_then(|x| x.as_ref())
+    }
+
+    #[inline]
+    pub fn joystick_mut(&mut self, id: usize) -> Option<&mut Joystick> {
+        self.joysticks.get_mut(id).and_then(|x| x.as_mut())
+    }
+
+    /// Connects or disconnects a joystick. Any previous state for that slot is discarded.
+    pub fn set_joystick(&mut self, id: usize, joystick: Option<Joystick>) {
+        if let Some(slot) = self.joysticks.get_mut(id) {
+            *slot = joystick;
+        }
+    }
+
+    /// Gets a joystick for recorded input, connecting an emulated one in its slot if there isn't one yet.
+    /// This means that replays without joystick input never see a joystick, as before joysticks existed.
+    pub fn emulated_joystick_mut(&mut self, id: usize) -> Option<&mut Joystick> {
+        let slot = self.joysticks.get_mut(id)?;
+        Some(slot.get_or_insert_with(|| Joystick::emulated(id)))
+    }
+
     /// Clears the button press and release buffers.
     /// Should be called after each frame.
     pub fn step(&mut self) {
@@ -1092,3 +1404,93 @@ impl Input {
         *self = Self::new();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn type_keys(input: &mut Input, keys: &[u8]) {
+        for &key in keys {
+            input.button_press(key, true);
+            input.char_press(key, encoding_rs::WINDOWS_1252);
+            input.button_release(key, true);
+        }
+    }
+
+    #[test]
+    fn shift_and_caps_lock() {
+        let mut input = Input::new();
+        type_keys(&mut input, b"A1\xBA");
+        input.button_press(Button::Shift as u8, true);
+        type_keys(&mut input, b"A1\xBA");
+        input.button_release(Button::Shift as u8, true);
+        assert_eq!(input.keyboard_string(), b"a1;A!:");
+
+        // caps lock only changes letters, and shift undoes it
+        input.set_keyboard_string(b"");
+        type_keys(&mut input, &[Button::CapsLock as u8, b'A', b'1']);
+        input.button_press(Button::Shift as u8, true);
+        type_keys(&mut input, b"A1");
+        input.button_release(Button::Shift as u8, true);
+        type_keys(&mut input, &[Button::CapsLock as u8, b'A']);
+        assert_eq!(input.keyboard_string(), b"A1a!a");
+        assert_eq!(input.keyboard_lastchar(), b'a');
+    }
+
+    #[test]
+    fn modifiers_and_numlock() {
+        let mut input = Input::new();
+        input.button_press(Button::Control as u8, true);
+        type_keys(&mut input, b"C");
+        input.button_release(Button::Control as u8, true);
+        type_keys(&mut input, &[0x60, 0x6E]);
+        input.keyboard_set_numlock(true);
+        type_keys(&mut input, &[0x61, 0x6E]);
+        assert_eq!(input.keyboard_string(), b"1.");
+    }
+
+    #[test]
+    fn backspace() {
+        let mut input = Input::new();
+        type_keys(&mut input, &[b'H', b'I', 0x08, b'O', 0x08, 0x08, 0x08]);
+        assert_eq!(input.keyboard_string(), b"");
+        assert_eq!(input.keyboard_lastchar(), 0x08);
+        type_keys(&mut input, &[b'O', b'K', 0x0D]);
+        assert_eq!(input.keyboard_string(), b"ok");
+    }
+
+    #[test]
+    fn length_limit() {
+        let mut input = Input::new();
+        let keys = (0..KEYBOARD_STRING_MAX + 3).map(|i| b'A' + (i % 26) as u8).collect::<Vec<_>>();
+        type_keys(&mut input, &keys);
+        assert_eq!(input.keyboard_string().len(), KEYBOARD_STRING_MAX);
+        assert_eq!(input.keyboard_string()[..3], *b"def");
+    }
+
+    #[test]
+    fn layouts() {
+        let mut input = Input::new();
+        input.set_keyboard_layout(KeyboardLayout::German);
+        type_keys(&mut input, &[0xDE, 0xC0, 0xDB, 0xDD, 0x33]);
+        input.button_press(Button::Shift as u8, true);
+        type_keys(&mut input, &[0xDE, 0x33, 0x37]);
+        input.button_release(Button::Shift as u8, true);
+        assert_eq!(input.keyboard_string(), b"\xE4\xF6\xDF3\xC4\xA7/");
+
+        // characters the code page doesn't have aren't typed
+        input.set_keyboard_string(b"");
+        input.char_press(0xDE, encoding_rs::SHIFT_JIS);
+        input.char_press(b'A', encoding_rs::SHIFT_JIS);
+        assert_eq!(input.keyboard_string(), b"a");
+
+        input.set_keyboard_layout(KeyboardLayout::Uk);
+        input.button_press(Button::Shift as u8, true);
+        type_keys(&mut input, &[b'3', 0xC0]);
+        assert_eq!(input.keyboard_string(), b"a\xA3@");
+
+        assert_eq!(KeyboardLayout::from_label(" DE "), Some(KeyboardLayout::German));
+        assert_eq!(KeyboardLayout::from_label("en-gb"), Some(KeyboardLayout::Uk));
+        assert_eq!(KeyboardLayout::from_label("dvorak"), None);
+    }
+}
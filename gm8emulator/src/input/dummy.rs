@@ -0,0 +1,17 @@
+//! Live joystick input isn't supported on this platform yet, so no joysticks are ever connected.
+
+use super::Input;
+
+pub struct GamepadManager;
+
+impl GamepadManager {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn open(_input: &mut Input) -> Self {
+        Self
+    }
+
+    pub fn poll(&mut self, _input: &mut Input) {}
+}
@@ -0,0 +1,232 @@
+//! Live joystick input on Linux, read from evdev devices in /dev/input.
+
+use super::{Input, Joystick, JoystickAxis, JOYSTICK_BUTTON_COUNT, JOYSTICK_COUNT};
+use std::{ffi::CString, fs, mem, os::raw::c_ulong};
+
+const EV_KEY: u16 = 0x01;
+const EV_ABS: u16 = 0x03;
+const KEY_MAX: usize = 0x2ff;
+const ABS_MAX: usize = 0x3f;
+
+const ABS_X: u16 = 0x00;
+const ABS_Y: u16 = 0x01;
+const ABS_Z: u16 = 0x02;
+const ABS_RX: u16 = 0x03;
+const ABS_RY: u16 = 0x04;
+const ABS_RZ: u16 = 0x05;
+const ABS_HAT0X: u16 = 0x10;
+const ABS_HAT0Y: u16 = 0x11;
+
+// Button codes which identify a device as a joystick or gamepad, in the order they get mapped to GM buttons
+const BTN_JOYSTICK_RANGES: [(u16, u16); 2] = [(0x120, 0x140), (0x2c0, 0x2e8)];
+
+// Evdev axis which feeds each of GameMaker's axes, in JoystickAxis order
+const AXIS_MAPPING: [u16; 6] = [ABS_X, ABS_Y, ABS_Z, ABS_RZ, ABS_RX, ABS_RY];
+
+const fn ioc_read(nr: c_ulong, size: c_ulong) -> c_ulong {
+    (2 << 30) | (size << 16) | ((b'E' as c_ulong) << 8) | nr
+}
+
+const fn eviocgname(len: usize) -> c_ulong {
+    ioc_read(0x06, len as c_ulong)
+}
+
+const fn eviocgbit(ev: u16, len: usize) -> c_ulong {
+    ioc_read(0x20 + ev as c_ulong, len as c_ulong)
+}
+
+const fn eviocgabs(abs: u16) -> c_ulong {
+    ioc_read(0x40 + abs as c_ulong, mem::size_of::<libc::input_absinfo>() as c_ulong)
+}
+
+#[derive(Clone, Copy)]
+struct AxisRange {
+    min: i32,
+    max: i32,
+}
+
+impl AxisRange {
+    fn normalize(&self, value: i32) -> i16 {
+        if self.max <= self.min {
+            return 0
+        }
+        let pos = (f64::from(value) - f64::from(self.min)) / (f64::from(self.max) - f64::from(self.min));
+        ((pos * 2.0 - 1.0).clamp(-1.0, 1.0) * f64::from(i16::MAX)).round() as i16
+    }
+}
+
+struct Device {
+    fd: libc::c_int,
+    slot: usize,
+    buttons: Vec<u16>,
+    axes: [Option<AxisRange>; 6],
+    hat: (i32, i32),
+}
+
+/// Keeps track of connected evdev joysticks and feeds their state into an `Input`.
+pub struct GamepadManager {
+    devices: Vec<Device>,
+}
+
+fn test_bit(bits: &[u8], bit: usize) -> bool {
+    bits.get(bit / 8).map(|x| x & (1 << (bit % 8)) != 0).unwrap_or(false)
+}
+
+impl Device {
+    unsafe fn open(path: &str, slot: usize) -> Option<(Self, Joystick)> {
+        let c_path = CString::new(path).ok()?;
+        let fd = libc::open(c_path.as_ptr(), libc::O_RDONLY | libc::O_NONBLOCK | libc::O_CLOEXEC);
+        if fd < 0 {
+            return None
+        }
+
+        let mut key_bits = [0u8; KEY_MAX / 8 + 1];
+        let mut abs_bits = [0u8; ABS_MAX / 8 + 1];
+        if libc::ioctl(fd, eviocgbit(EV_KEY, key_bits.len()), key_bits.as_mut_ptr()) < 0
+            || libc::ioctl(fd, eviocgbit(EV_ABS, abs_bits.len()), abs_bits.as_mut_ptr()) < 0
+        {
+            libc::close(fd);
+            return None
+        }
+
+        let buttons = BTN_JOYSTICK_RANGES
+            .iter()
+            .flat_map(|&(start, end)| start..end)
+            .filter(|&code| test_bit(&key_bits, code.into()))
+            .take(JOYSTICK_BUTTON_COUNT)
+            .collect::<Vec<_>>();
+        if buttons.is_empty() {
+            // keyboards, mice, touchpads etc.
+            libc::close(fd);
+            return None
+        }
+
+        let mut axes = [None; 6];
+        let mut initial = [0i16; 6];
+        for ((range, value), &code) in axes.iter_mut().zip(initial.iter_mut()).zip(AXIS_MAPPING.iter()) {
+            if test_bit(&abs_bits, code.into()) {
+                let mut info: libc::input_absinfo = mem::zeroed();
+                if libc::ioctl(fd, eviocgabs(code), &mut info) >= 0 {
+                    let axis_range = AxisRange { min: info.minimum, max: info.maximum };
+                    *value = axis_range.normalize(info.value);
+                    *range = Some(axis_range);
+                }
+            }
+        }
+        let has_pov = test_bit(&abs_bits, ABS_HAT0X.into()) && test_bit(&abs_bits, ABS_HAT0Y.into());
+
+        let mut name = [0u8; 256];
+        let name = if libc::ioctl(fd, eviocgname(name.len()), name.as_mut_ptr()) >= 0 {
+            let len = name.iter().position(|&x| x == 0).unwrap_or(name.len());
+            String::from_utf8_lossy(&name[..len]).into_owned()
+        } else {
+            String::new()
+        };
+
+        let mut joystick =
+            Joystick::new(name, axes.iter().filter(|x| x.is_some()).count() as u8, buttons.len() as u8, has_pov);
+        for (axis, value) in JoystickAxis::ALL.iter().zip(initial.iter()) {
+            joystick.set_axis(*axis, *value);
+        }
+
+        Some((Self { fd, slot, buttons, axes, hat: (0, 0) }, joystick))
+    }
+
+    fn hat_to_pov(&self) -> Option<u16> {
+        match self.hat {
+            (0, 0) => None,
+            (0, y) if y < 0 => Some(0),
+            (x, y) if x > 0 && y < 0 => Some(4500),
+            (x, 0) if x > 0 => Some(9000),
+            (x, y) if x > 0 && y > 0 => Some(13500),
+            (0, _) => Some(18000),
+            (x, y) if x < 0 && y > 0 => Some(22500),
+            (_, 0) => Some(27000),
+            _ => Some(31500),
+        }
+    }
+
+    fn poll(&mut self, input: &mut Input) {
+        let mut event: libc::input_event = unsafe { mem::zeroed() };
+        let size = mem::size_of::<libc::input_event>();
+        loop {
+            let read = unsafe { libc::read(self.fd, (&mut event as *mut libc::input_event).cast(), size) };
+            if read != size as isize {
+                break
+            }
+            let joystick = match input.joystick_mut(self.slot) {
+                Some(j) => j,
+                None => break,
+            };
+            match event.type_ {
+                EV_KEY => {
+                    if let Some(button) = self.buttons.iter().position(|&x| x == event.code) {
+                        joystick.set_button(button as u8, event.value != 0);
+                    }
+                },
+                EV_ABS => {
+                    if event.code == ABS_HAT0X || event.code == ABS_HAT0Y {
+                        if event.code == ABS_HAT0X {
+                            self.hat.0 = event.value;
+                        } else {
+                            self.hat.1 = event.value;
+                        }
+                        joystick.set_pov(self.hat_to_pov());
+                    } else if let Some(index) = AXIS_MAPPING.iter().position(|&x| x == event.code) {
+                        if let Some(range) = self.axes[index] {
+                            joystick.set_axis(JoystickAxis::ALL[index], range.normalize(event.value));
+                        }
+                    }
+                },
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Drop for Device {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+impl GamepadManager {
+    /// A manager with no devices, for when live input shouldn't be used.
+    pub fn new() -> Self {
+        Self { devices: Vec::new() }
+    }
+
+    /// Finds up to two joysticks and connects them to the given input state.
+    pub fn open(input: &mut Input) -> Self {
+        let mut paths = match fs::read_dir("/dev/input") {
+            Ok(dir) => dir
+                .filter_map(|e| e.ok())
+                .filter_map(|e| e.file_name().to_str().and_then(|n| n.strip_prefix("event")?.parse::<u32>().ok()))
+                .collect::<Vec<_>>(),
+            Err(_) => Vec::new(),
+        };
+        paths.sort_unstable();
+
+        let mut devices = Vec::new();
+        for n in paths {
+            if devices.len() == JOYSTICK_COUNT {
+                break
+            }
+            if let Some((device, joystick)) = unsafe { Device::open(&format!("/dev/input/event{}", n), devices.len()) }
+            {
+                input.set_joystick(device.slot, Some(joystick));
+                devices.push(device);
+            }
+        }
+        Self { devices }
+    }
+
+    /// Reads all pending events into the given input state.
+    pub fn poll(&mut self, input: &mut Input) {
+        for device in self.devices.iter_mut() {
+            device.poll(input);
+        }
+    }
+}
@@ -14,9 +14,12 @@ mod types;
 mod util;
 
 use game::{
+    codepage,
+    recording::ProjectConfig,
     savestate::{self, SaveState},
     Game, PlayType, Replay,
 };
+use input::KeyboardLayout;
 use std::{
     env, fs,
     path::{Path, PathBuf},
@@ -48,6 +51,7 @@ fn xmain() -> i32 {
     opts.optflag("h", "help", "prints this help message");
     opts.optflag("s", "strict", "enable various data integrity checks");
     opts.optflag("t", "singlethread", "parse gamedata synchronously");
+    opts.optflag("", "recover", "skip assets that can't be read instead of failing to load");
     opts.optflag("v", "verbose", "enables verbose logging");
     opts.optflag("r", "realtime", "disables clock spoofing");
     opts.optflagopt("l", "no-framelimit-until", "disables the frame-limiter until specified frame", "FRAME");
@@ -57,6 +61,10 @@ fn xmain() -> i32 {
     opts.optopt("x", "start-frame", "Start frame for the longer operation file when merging", "FRAME");
     opts.optopt("o", "output-file", "output savestate name in replay mode", "FILE.bin");
     opts.optmulti("a", "game-arg", "argument to pass to the game", "ARG");
+    opts.optopt("c", "cd-folder", "folder of audio files to use as the CD's tracks, instead of <game>/cd", "FOLDER");
+    opts.optopt("e", "encoding", "code page the game was made with, such as 932 or windows-1251 (detected if not given)", "CODEPAGE");
+    opts.optopt("k", "keyboard-layout", "layout recorded key presses are typed with: us, uk or de (us if not given)", "LAYOUT");
+    opts.optopt("m", "soundfont", "SoundFont to play MIDI music with, instead of the built-in instruments", "FILE.sf2");
     opts.optflagopt("p", "start-save", "Either loads the savestate specified after this parameter or starts at the first frame. If a .gmtas is specified by -f this will start the replay from this savestate instead", "savestate");
 
     let matches = match opts.parse(&args[1..]) {
@@ -81,6 +89,7 @@ fn xmain() -> i32 {
 
     let strict = matches.opt_present("s");
     let multithread = !matches.opt_present("t");
+    let recover = matches.opt_present("recover");
     let spoof_time = !matches.opt_present("r");
     let frame_limit_at = matches.opt_str("l").map(|frame| {
         match frame.parse::<usize>() 
@@ -230,17 +239,28 @@ fn xmain() -> i32 {
         println!("loading '{}'...", input);
     }
 
+    let logger = if verbose { Some(|s: &str| println!("{}", s)) } else { None };
+    let is_project = file_path
+        .extension()
+        .and_then(|x| x.to_str())
+        .map_or(false, |x| x.eq_ignore_ascii_case("gmk") || x.eq_ignore_ascii_case("gm81"));
     #[rustfmt::skip]
-    let assets = gm8exe::reader::from_exe(
-        &mut file,                              // mut exe: AsRef<[u8]>
-        if verbose {                            // logger: Option<Fn(&str)>
-            Some(|s: &str| println!("{}", s))
-        } else {
-            None
-        },
-        strict,                                 // strict: bool
-        multithread,                            // multithread: bool
-    );
+    let assets = if is_project {
+        gm8exe::reader::from_gmk(
+            &file,                              // gmk: AsRef<[u8]>
+            logger,                             // logger: Option<Fn(&str)>
+            strict,                             // strict: bool
+            multithread,                        // multithread: bool
+        )
+    } else {
+        gm8exe::reader::from_exe(
+            &mut file,                          // mut exe: AsRef<[u8]>
+            logger,                             // logger: Option<Fn(&str)>
+            strict,                             // strict: bool
+            multithread,                        // multithread: bool
+            recover,                            // recover: bool
+        )
+    };
     let assets = match assets {
         Ok(assets) => assets,
         Err(err) => {
@@ -248,6 +268,13 @@ fn xmain() -> i32 {
             return EXIT_FAILURE
         },
     };
+    for warning in &assets.warnings {
+        eprintln!("skipped unreadable asset - {}", warning);
+    }
+    // project files only name their extension packages, so their functions and constants won't exist
+    for extension in assets.extensions.iter().filter(|x| x.files.is_empty()) {
+        eprintln!("extension package '{}' has no files in a project file, so it won't be loaded", extension.name);
+    }
 
     let absolute_path = match file_path.canonicalize() {
         Ok(p) => p,
@@ -257,7 +284,54 @@ fn xmain() -> i32 {
         },
     };
 
-    let encoding = encoding_rs::SHIFT_JIS; // TODO: argument
+    // an explicit code page or keyboard layout wins over the one a project or replay was recorded with,
+    // and both over defaults
+    let encoding = match matches.opt_str("e") {
+        Some(label) => match codepage::from_label(&label) {
+            Some(encoding) => Some(encoding),
+            None => {
+                eprintln!("unknown code page {}", label);
+                return EXIT_FAILURE
+            },
+        },
+        None => project_path
+            .as_deref()
+            .map(ProjectConfig::encoding)
+            .or_else(|| replay.as_ref().and_then(Replay::encoding)),
+    };
+    let keyboard_layout = match matches.opt_str("k") {
+        Some(label) => match KeyboardLayout::from_label(&label) {
+            Some(layout) => layout,
+            None => {
+                eprintln!("unknown keyboard layout {}", label);
+                return EXIT_FAILURE
+            },
+        },
+        None => project_path
+            .as_deref()
+            .and_then(ProjectConfig::keyboard_layout)
+            .or_else(|| replay.as_ref().map(|replay| replay.keyboard_layout))
+            .unwrap_or_default(),
+    };
+    // and one given for a project is remembered for next time
+    if let Some(project_path) = project_path.as_deref() {
+        if let (Some(encoding), true) = (encoding, matches.opt_present("e")) {
+            if let Some(err) = ProjectConfig::save_encoding(project_path, encoding) {
+                eprintln!("{}", err);
+            }
+        }
+        if matches.opt_present("k") {
+            if let Some(err) = ProjectConfig::save_keyboard_layout(project_path, keyboard_layout) {
+                eprintln!("{}", err);
+            }
+        }
+    }
+
+    // resolved now because launching changes the working directory
+    let cd_folder = matches.opt_str("c").map(|path| match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path.into(),
+    });
 
     let play_type = if project_path.is_some() {
         PlayType::Record
@@ -276,6 +350,17 @@ fn xmain() -> i32 {
             },
         };
 
+    if let Some(path) = matches.opt_str("m") {
+        if let Err(e) = components.audio.load_soundfont(Path::new(&path)) {
+            eprintln!("WARNING: failed to load SoundFont '{}' ({}), using built-in instruments", path, e);
+        }
+    }
+
+    if let Some(folder) = cd_folder {
+        components.cd.set_folder(folder);
+    }
+    components.input.set_keyboard_layout(keyboard_layout);
+
     let time_now = gml::datetime::now_as_nanos();
 
     if let Err(err) = if let Some(path) = project_path {
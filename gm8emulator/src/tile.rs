@@ -47,3 +47,67 @@ pub struct Tile {
     /// Whether this tile will be drawn
     pub visible: Cell<bool>,
 }
+
+impl Tile {
+    /// Whether the point (x, y) is inside this tile. GM8.0 ignores the tile's scale here, GM8.1 doesn't.
+    pub fn contains(&self, x: Real, y: Real, use_scaling: bool) -> bool {
+        let (xscale, yscale) =
+            if use_scaling { (self.xscale.get(), self.yscale.get()) } else { (Real::from(1.0), Real::from(1.0)) };
+        x >= self.x.get()
+            && x < self.x.get() + xscale * self.width.get().into()
+            && y >= self.y.get()
+            && y < self.y.get() + yscale * self.height.get().into()
+    }
+
+    /// Whether this tile is in the foreground (negative depth) or background as requested and contains (x, y).
+    pub fn is_at(&self, x: Real, y: Real, foreground: bool, use_scaling: bool) -> bool {
+        (self.depth.get() < Real::from(0.0)) == foreground && self.contains(x, y, use_scaling)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile(x: f64, y: f64, depth: f64, xscale: f64) -> Tile {
+        Tile {
+            x: Real::from(x).into(),
+            y: Real::from(y).into(),
+            background_index: 0.into(),
+            tile_x: 0.into(),
+            tile_y: 0.into(),
+            width: 16.into(),
+            height: 8.into(),
+            depth: Real::from(depth).into(),
+            id: 10000001.into(),
+            alpha: Real::from(1.0).into(),
+            blend: 0xffffff.into(),
+            xscale: Real::from(xscale).into(),
+            yscale: Real::from(1.0).into(),
+            visible: true.into(),
+        }
+    }
+
+    #[test]
+    fn contains() {
+        let t = tile(10.0, 20.0, 0.0, 2.0);
+        assert!(t.contains(10.0.into(), 20.0.into(), false));
+        assert!(t.contains(25.5.into(), 27.5.into(), false));
+        assert!(!t.contains(26.0.into(), 20.0.into(), false));
+        assert!(!t.contains(10.0.into(), 28.0.into(), false));
+        assert!(!t.contains(9.5.into(), 20.0.into(), false));
+        assert!(t.contains(41.0.into(), 20.0.into(), true));
+        assert!(!t.contains(42.0.into(), 20.0.into(), true));
+    }
+
+    #[test]
+    fn is_at() {
+        let back = tile(0.0, 0.0, 1000.0, 1.0);
+        let front = tile(0.0, 0.0, -1.0, 1.0);
+        assert!(back.is_at(4.0.into(), 4.0.into(), false, false));
+        assert!(!back.is_at(4.0.into(), 4.0.into(), true, false));
+        assert!(front.is_at(4.0.into(), 4.0.into(), true, true));
+        assert!(!front.is_at(4.0.into(), 4.0.into(), false, true));
+        assert!(!front.is_at(20.0.into(), 4.0.into(), true, true));
+    }
+}
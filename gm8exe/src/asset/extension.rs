@@ -1,9 +1,10 @@
 use crate::{
-    asset::{assert_ver, Error, PascalString, ReadPascalString},
+    asset::{assert_ver, Error, PascalString, ReadPascalString, WritePascalString},
     reader::inflate,
+    writer::deflate,
 };
-use byteorder::{ReadBytesExt, LE};
-use std::io::{self, Read, Seek, SeekFrom};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use std::io::{self, Read, Seek, SeekFrom, Write};
 
 pub const VERSION: u32 = 700;
 
@@ -194,35 +195,7 @@ impl Extension {
 
         // Don't do decryption if there are no contents
         if contents_len != 0 {
-            // decryption setup
-            let mut char_table = [0u8; 0x200];
-            let mut seed1: i32 = seed1_raw as _;
-            let mut seed2: i32 = (seed1 % 0xFA) + 6;
-            seed1 /= 0xFA;
-            if seed1 < 0 {
-                seed1 += 100;
-            }
-            if seed2 < 0 {
-                seed2 += 100;
-            }
-            for (i, val) in char_table.iter_mut().enumerate() {
-                *val = (i % 256) as u8; // 0-255 repeating (twice)
-            }
-
-            // calculating char table - pass 1: pseudorandom byteswap
-            for i in 1..0x2711 {
-                let idx: usize = ((((i * seed2 as u32) + seed1 as u32) % 0xFE) + 1) as _;
-                let b1 = char_table[idx];
-                let b2 = char_table[idx + 1];
-                char_table[idx] = b2;
-                char_table[idx + 1] = b1;
-            }
-
-            // .. pass 2: use low half to scramble top half
-            for i in 0..0x100 {
-                let lo: u8 = char_table[i + 1];
-                char_table[lo as usize + 0x100] = (i as u8).wrapping_add(1);
-            }
+            let char_table = char_table(seed1_raw);
 
             // decrypt data chunk
             for byte in &mut reader.get_mut()[data_pos + 1..data_pos + contents_len] {
@@ -251,4 +224,105 @@ impl Extension {
 
         Ok(Extension { name, folder_name, files })
     }
+
+    /// Writes the extension as `read` expects it, encrypting the contents of its files.
+    pub fn write(&self, writer: &mut impl io::Write) -> io::Result<()> {
+        writer.write_u32::<LE>(VERSION)?;
+        writer.write_pas_string(&self.name)?;
+        writer.write_pas_string(&self.folder_name)?;
+
+        writer.write_u32::<LE>(self.files.len() as u32)?;
+        for file in &self.files {
+            writer.write_u32::<LE>(VERSION)?;
+            writer.write_pas_string(&file.name)?;
+            writer.write_u32::<LE>(file.kind as u32)?;
+            writer.write_pas_string(&file.initializer)?;
+            writer.write_pas_string(&file.finalizer)?;
+
+            writer.write_u32::<LE>(file.functions.len() as u32)?;
+            for function in &file.functions {
+                writer.write_u32::<LE>(VERSION)?;
+                writer.write_pas_string(&function.name)?;
+                writer.write_pas_string(&function.external_name)?;
+                writer.write_u32::<LE>(function.convention as u32)?;
+                writer.write_u32::<LE>(function.id)?;
+                writer.write_i32::<LE>(function.arg_count)?;
+                for kind in &function.arg_types {
+                    writer.write_u32::<LE>(*kind as u32)?;
+                }
+                writer.write_u32::<LE>(function.return_type as u32)?;
+            }
+
+            writer.write_u32::<LE>(file.consts.len() as u32)?;
+            for constant in &file.consts {
+                writer.write_u32::<LE>(VERSION)?;
+                writer.write_pas_string(&constant.name)?;
+                writer.write_pas_string(&constant.value)?;
+            }
+        }
+
+        let mut contents = Vec::new();
+        for file in self.files.iter().filter(|f| f.kind != FileKind::ActionLibrary) {
+            let data = deflate(&file.contents)?;
+            contents.write_u32::<LE>(data.len() as u32)?;
+            contents.write_all(&data)?;
+        }
+
+        // Some seeds give a table that doesn't map back onto every byte, so look for one that does
+        let (seed1_raw, table) = (1..)
+            .find_map(|seed| {
+                let mut table = [0u8; 0x100];
+                let mut seen = [false; 0x100];
+                for (i, byte) in char_table(seed)[0x100..].iter().enumerate() {
+                    table[*byte as usize] = i as u8;
+                    seen[*byte as usize] = true;
+                }
+                seen.iter().all(|x| *x).then(|| (seed, table))
+            })
+            .unwrap();
+
+        // The first byte isn't encrypted
+        for byte in contents.iter_mut().skip(1) {
+            *byte = table[*byte as usize];
+        }
+
+        writer.write_u32::<LE>(contents.len() as u32 + 4)?;
+        writer.write_u32::<LE>(seed1_raw)?;
+        writer.write_all(&contents)?;
+        Ok(())
+    }
+}
+
+/// Builds the substitution table for extension contents. Bytes are decrypted with the top half.
+fn char_table(seed1_raw: u32) -> [u8; 0x200] {
+    let mut char_table = [0u8; 0x200];
+    let mut seed1: i32 = seed1_raw as _;
+    let mut seed2: i32 = (seed1 % 0xFA) + 6;
+    seed1 /= 0xFA;
+    if seed1 < 0 {
+        seed1 += 100;
+    }
+    if seed2 < 0 {
+        seed2 += 100;
+    }
+    for (i, val) in char_table.iter_mut().enumerate() {
+        *val = (i % 256) as u8; // 0-255 repeating (twice)
+    }
+
+    // calculating char table - pass 1: pseudorandom byteswap
+    for i in 1..0x2711 {
+        let idx: usize = ((((i * seed2 as u32) + seed1 as u32) % 0xFE) + 1) as _;
+        let b1 = char_table[idx];
+        let b2 = char_table[idx + 1];
+        char_table[idx] = b2;
+        char_table[idx + 1] = b1;
+    }
+
+    // .. pass 2: use low half to scramble top half
+    for i in 0..0x100 {
+        let lo: u8 = char_table[i + 1];
+        char_table[lo as usize + 0x100] = (i as u8).wrapping_add(1);
+    }
+
+    char_table
 }
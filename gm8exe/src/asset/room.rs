@@ -53,6 +53,9 @@ pub struct Room {
 
     pub uses_810_features: bool,
     pub uses_811_features: bool,
+
+    /// How the room editor was left, which only project files have.
+    pub editor_settings: Option<EditorSettings>,
 }
 
 pub struct Background {
@@ -120,6 +123,25 @@ pub struct ViewFollowData {
     pub target: ID,
 }
 
+#[derive(Copy, Clone, Default)]
+pub struct EditorSettings {
+    /// Whether the IDE should restore the rest of these when opening the room.
+    pub remember: bool,
+    pub width: u32,
+    pub height: u32,
+    pub show_grid: bool,
+    pub show_objects: bool,
+    pub show_tiles: bool,
+    pub show_backgrounds: bool,
+    pub show_foregrounds: bool,
+    pub show_views: bool,
+    pub delete_underlying_objects: bool,
+    pub delete_underlying_tiles: bool,
+    pub tab: u32,
+    pub scroll_x: u32,
+    pub scroll_y: u32,
+}
+
 impl Asset for Room {
     fn deserialize_exe(mut reader: impl Read, version: GameVersion, strict: bool) -> Result<Self, Error> {
         let name = reader.read_pas_string()?;
@@ -242,12 +264,17 @@ impl Asset for Room {
             tiles,
             uses_810_features,
             uses_811_features,
+            editor_settings: None,
         })
     }
 
     fn serialize_exe(&self, mut writer: impl io::Write, version: GameVersion) -> io::Result<()> {
         writer.write_pas_string(&self.name)?;
-        writer.write_u32::<LE>(VERSION)?;
+        writer.write_u32::<LE>(match (self.uses_810_features, self.uses_811_features) {
+            (_, true) => 811,
+            (true, false) => 810,
+            (false, false) => VERSION,
+        })?;
         writer.write_pas_string(&self.caption)?;
         writer.write_u32::<LE>(self.width)?;
         writer.write_u32::<LE>(self.height)?;
@@ -299,6 +326,14 @@ impl Asset for Room {
             writer.write_i32::<LE>(instance.object)?;
             writer.write_i32::<LE>(instance.id)?;
             writer.write_pas_string(&instance.creation_code)?;
+            if self.uses_810_features || self.uses_811_features {
+                writer.write_f64::<LE>(instance.xscale)?;
+                writer.write_f64::<LE>(instance.yscale)?;
+                writer.write_u32::<LE>(instance.blend)?;
+            }
+            if self.uses_811_features {
+                writer.write_f64::<LE>(instance.angle)?;
+            }
         }
         writer.write_u32::<LE>(self.tiles.len() as u32)?;
         for tile in &self.tiles {
@@ -311,6 +346,11 @@ impl Asset for Room {
             writer.write_u32::<LE>(tile.height)?;
             writer.write_i32::<LE>(tile.depth)?;
             writer.write_i32::<LE>(tile.id)?;
+            if self.uses_810_features || self.uses_811_features {
+                writer.write_f64::<LE>(tile.xscale)?;
+                writer.write_f64::<LE>(tile.yscale)?;
+                writer.write_u32::<LE>(tile.blend)?;
+            }
         }
         Ok(())
     }
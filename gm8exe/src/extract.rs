@@ -0,0 +1,235 @@
+//! Extraction of a game's media into plain files: PNG images, sounds, font atlases, included files and extensions.
+//!
+//! `extract` hands each file to a callback so tools can index or hash them without touching the disk,
+//! and `extract_to_dir` writes them out under a folder.
+
+use crate::{AssetList, GameAssets};
+use std::{collections::HashMap, fs, io, path::Path};
+
+/// Replaces anything but letters, digits, underscores and the given extra characters, keeping at most 64.
+pub fn sanitize(name: &[u8], extra: &[u8]) -> String {
+    name.iter()
+        .take(64)
+        .map(|b| if b.is_ascii_alphanumeric() || *b == b'_' || extra.contains(b) { char::from(*b) } else { '_' })
+        .collect()
+}
+
+/// Whether a name can be used as a file name as it is, on any system.
+fn is_plain(name: &[u8]) -> bool {
+    const RESERVED: &[&str] = &["con", "prn", "aux", "nul", "com", "lpt"];
+    let lower = name.to_ascii_lowercase();
+    let reserved = RESERVED.iter().any(|r| {
+        lower.starts_with(r.as_bytes()) && (lower.len() == 3 || (lower.len() == 4 && lower[3].is_ascii_digit()))
+    });
+    !name.is_empty() && name.len() <= 64 && !reserved && name.iter().all(|b| b.is_ascii_alphanumeric() || *b == b'_')
+}
+
+/// Works out a file name (without extension) for each asset in a list of names indexed by id.
+///
+/// An asset gets its own name when that's safe to use and no other asset has it in any case,
+/// or `name#id` otherwise, so the same list always gives the same file names.
+pub fn file_stems(names: &[Option<&[u8]>]) -> Vec<Option<String>> {
+    let mut counts = HashMap::new();
+    for name in names.iter().flatten() {
+        *counts.entry(name.to_ascii_lowercase()).or_insert(0) += 1;
+    }
+    names
+        .iter()
+        .enumerate()
+        .map(|(id, name)| {
+            name.map(|name| {
+                if is_plain(name) && counts[&name.to_ascii_lowercase()] == 1 {
+                    String::from_utf8_lossy(name).into_owned()
+                } else {
+                    format!("{}#{}", sanitize(name, b""), id)
+                }
+            })
+        })
+        .collect()
+}
+
+/// Encodes 8-bit pixels of the given colour type, in the order PNG stores them, into a PNG file.
+pub fn encode_png(width: u32, height: u32, colour: png::ColorType, data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut encoder = png::Encoder::new(&mut out, width, height);
+    encoder.set_color(colour);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.write_header()?.write_image_data(data)?;
+    Ok(out)
+}
+
+/// Encodes BGRA pixels, as sprites and backgrounds store them.
+fn encode_bgra(width: u32, height: u32, bgra: &[u8]) -> io::Result<Vec<u8>> {
+    let rgba = bgra.chunks_exact(4).flat_map(|p| [p[2], p[1], p[0], p[3]]).collect::<Vec<u8>>();
+    encode_png(width, height, png::ColorType::RGBA, &rgba)
+}
+
+fn stems_of<T>(list: &AssetList<T>, name: fn(&T) -> &[u8]) -> Vec<Option<String>> {
+    file_stems(&list.iter().map(|x| x.as_deref().map(name)).collect::<Vec<_>>())
+}
+
+/// Extracts a game's media, passing each file's path (relative, separated by `/`) and contents to `output`.
+///
+/// - `sprites/<name>/<frame>.png` for each frame, `sprites/<name>_strip<frames>.png` with all of them side by side
+///   if they're the same size, and `sprites/<name>.txt` with the origin and frame size
+/// - `backgrounds/<name>.png`
+/// - `sounds/<name><extension>`, using the sound's original extension
+/// - `fonts/<name>.png` with the glyph atlas, if the font has one, and `fonts/<name>.txt` with the font's metrics
+/// - `included/<index>_<file name>` for each included file stored in the game
+/// - `extensions/<name>/<file name>` for each file in an extension, such as DLLs
+/// - `icon.ico`
+///
+/// Assets are named as in [`file_stems`].
+pub fn extract<F>(assets: &GameAssets, mut output: F) -> io::Result<()>
+where
+    F: FnMut(&str, &[u8]) -> io::Result<()>,
+{
+    for (sprite, stem) in assets.sprites.iter().zip(stems_of(&assets.sprites, |x| &x.name.0)) {
+        let (sprite, stem) = match (sprite, stem) {
+            (Some(sprite), Some(stem)) => (sprite, stem),
+            _ => continue,
+        };
+        let (width, height) = sprite.frames.first().map_or((0, 0), |f| (f.width, f.height));
+        let uniform = sprite.frames.iter().all(|f| f.width == width && f.height == height);
+        for (i, frame) in sprite.frames.iter().enumerate() {
+            if frame.width * frame.height != 0 {
+                output(&format!("sprites/{}/{}.png", stem, i), &encode_bgra(frame.width, frame.height, &frame.data)?)?;
+            }
+        }
+        if uniform && width * height != 0 {
+            let row = width as usize * 4;
+            let mut strip = Vec::with_capacity(sprite.frames.len() * row * height as usize);
+            for y in 0..height as usize {
+                for frame in &sprite.frames {
+                    strip.extend_from_slice(&frame.data[y * row..(y + 1) * row]);
+                }
+            }
+            let strip_width = width * sprite.frames.len() as u32;
+            output(
+                &format!("sprites/{}_strip{}.png", stem, sprite.frames.len()),
+                &encode_bgra(strip_width, height, &strip)?,
+            )?;
+        }
+        let mut info = format!(
+            "origin_x = {}\norigin_y = {}\nframes = {}\n",
+            sprite.origin_x,
+            sprite.origin_y,
+            sprite.frames.len()
+        );
+        if uniform {
+            info += &format!("width = {}\nheight = {}\n", width, height);
+        }
+        output(&format!("sprites/{}.txt", stem), info.as_bytes())?;
+    }
+
+    for (background, stem) in assets.backgrounds.iter().zip(stems_of(&assets.backgrounds, |x| &x.name.0)) {
+        if let (Some(background), Some(stem)) = (background, stem) {
+            if let Some(data) = background.data.as_ref().filter(|_| background.width * background.height != 0) {
+                output(&format!("backgrounds/{}.png", stem), &encode_bgra(background.width, background.height, data)?)?;
+            }
+        }
+    }
+
+    for (sound, stem) in assets.sounds.iter().zip(stems_of(&assets.sounds, |x| &x.name.0)) {
+        if let (Some(sound), Some(stem)) = (sound, stem) {
+            if let Some(data) = &sound.data {
+                let extension = match sound.extension.0.strip_prefix(b".") {
+                    Some(ext) if !ext.is_empty() => sanitize(ext, b""),
+                    _ => "bin".into(),
+                };
+                output(&format!("sounds/{}.{}", stem, extension), data)?;
+            }
+        }
+    }
+
+    for (font, stem) in assets.fonts.iter().zip(stems_of(&assets.fonts, |x| &x.name.0)) {
+        let (font, stem) = match (font, stem) {
+            (Some(font), Some(stem)) => (font, stem),
+            _ => continue,
+        };
+        if !font.pixel_map.is_empty() {
+            // the map is coverage only, so the atlas is white with that as alpha
+            let atlas = font.pixel_map.iter().flat_map(|a| [255, 255, 255, *a]).collect::<Vec<u8>>();
+            let png = encode_png(font.map_width, font.map_height, png::ColorType::RGBA, &atlas)?;
+            output(&format!("fonts/{}.png", stem), &png)?;
+        }
+        let mut info = format!(
+            "sys_name = {}\nsize = {}\nbold = {}\nitalic = {}\nrange_start = {}\nrange_end = {}\n",
+            font.sys_name, font.size, font.bold, font.italic, font.range_start, font.range_end
+        );
+        if !font.pixel_map.is_empty() {
+            info += "# char x y width height offset advance\n";
+            for (i, glyph) in font.dmap.chunks_exact(6).enumerate() {
+                if (font.range_start..=font.range_end).contains(&(i as u32)) {
+                    info += &format!(
+                        "{} {} {} {} {} {} {}\n",
+                        i, glyph[0], glyph[1], glyph[2], glyph[3], glyph[4], glyph[5]
+                    );
+                }
+            }
+        }
+        output(&format!("fonts/{}.txt", stem), info.as_bytes())?;
+    }
+
+    for (i, file) in assets.included_files.iter().enumerate() {
+        if let Some(data) = &file.embedded_data {
+            output(&format!("included/{}_{}", i, sanitize(&file.file_name.0, b".-")), data)?;
+        }
+    }
+
+    let extension_names = assets.extensions.iter().map(|x| Some(x.name.0.as_ref())).collect::<Vec<_>>();
+    for (extension, stem) in assets.extensions.iter().zip(file_stems(&extension_names)) {
+        let stem = stem.unwrap_or_default();
+        for file in extension.files.iter().filter(|f| !f.contents.is_empty()) {
+            output(&format!("extensions/{}/{}", stem, sanitize(&file.name.0, b".-")), &file.contents)?;
+        }
+    }
+
+    if let Some(ico) = &assets.ico_file_raw {
+        output("icon.ico", ico)?;
+    }
+    Ok(())
+}
+
+/// Extracts a game's media into a folder, as laid out in [`extract`].
+pub fn extract_to_dir<P: AsRef<Path>>(assets: &GameAssets, dir: P) -> io::Result<()> {
+    let dir = dir.as_ref();
+    extract(assets, |path, data| {
+        let path = dir.join(path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, data)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_has_rgba_pixels() {
+        let png = encode_bgra(2, 1, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+        let (info, mut reader) = png::Decoder::new(&png[..]).read_info().unwrap();
+        assert_eq!((info.width, info.height, info.color_type), (2, 1, png::ColorType::RGBA));
+        let mut pixels = vec![0; info.buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, [3, 2, 1, 4, 7, 6, 5, 8]);
+
+        assert!(encode_png(2, 2, png::ColorType::Grayscale, &[0; 3]).is_err());
+    }
+
+    #[test]
+    fn stems_are_unique() {
+        let names: [Option<&[u8]>; 6] =
+            [Some(b"spr_a"), None, Some(b"Spr_A"), Some(b"spr_a"), Some(b"a b"), Some(b"nul")];
+        assert_eq!(file_stems(&names), [
+            Some("spr_a#0".into()),
+            None,
+            Some("Spr_A#2".into()),
+            Some("spr_a#3".into()),
+            Some("a_b#4".into()),
+            Some("nul#5".into()),
+        ]);
+    }
+}
@@ -5,13 +5,24 @@ pub mod gm81;
 use crate::{reader::ReaderError, upx, GameVersion};
 use std::io::{self, Seek, SeekFrom};
 
+/// Where and how an exe stores its gamedata, as found by `find` and `reader::from_exe`.
+#[derive(Clone, Debug, Default)]
+pub struct Detection {
+    /// UPX0 and UPX1's combined virtual size and UPX1's position on disk, if the exe is packed with UPX
+    pub upx: Option<(u32, u32)>,
+    /// The settings antidec2 was decrypted with, if the gamedata was protected with it
+    pub antidec: Option<antidec::Metadata>,
+    /// Where each chunk of gamedata starts in the decrypted buffer, in the order they're read, see `reader::Location`
+    pub offsets: Vec<(&'static str, u64)>,
+}
+
 /// Identifies the game version and start of gamedata header, given a data cursor.
-/// Also removes any version-specific encryptions.
+/// Also removes any version-specific encryptions, returning antidec2's settings if it was decrypted.
 pub fn find<F>(
     exe: &mut io::Cursor<&mut [u8]>,
     logger: Option<F>,
     upx_data: Option<(u32, u32)>,
-) -> Result<GameVersion, ReaderError>
+) -> Result<(GameVersion, Option<antidec::Metadata>), ReaderError>
 where
     F: Copy + Fn(&str),
 {
@@ -45,7 +56,7 @@ where
                 if antidec::decrypt(exe, antidec_settings)? {
                     // 8.0-specific header, but no point strict-checking it because antidec puts random garbage there.
                     exe.seek(SeekFrom::Current(16))?;
-                    Ok(GameVersion::GameMaker8_0)
+                    Ok((GameVersion::GameMaker8_0, Some(antidec_settings)))
                 } else {
                     // Antidec couldn't be decrypted with the settings we read, so we must have got the format wrong
                     Err(ReaderError::UnknownFormat)
@@ -60,7 +71,7 @@ where
                     if found_header {
                         gm81::decrypt(exe, logger, gm81::XorMethod::Normal)?;
                         exe.seek(SeekFrom::Current(20))?;
-                        Ok(GameVersion::GameMaker8_1)
+                        Ok((GameVersion::GameMaker8_1, Some(antidec_settings)))
                     } else {
                         log!(logger, "Didn't find GM81 magic value (0xF7140017) before EOF, so giving up");
                         Err(ReaderError::UnknownFormat)
@@ -83,7 +94,7 @@ where
                 if antidec::decrypt(exe, antidec_settings)? {
                     // 8.0-specific header, but no point strict-checking it because antidec puts random garbage there.
                     exe.seek(SeekFrom::Current(16))?;
-                    Ok(GameVersion::GameMaker8_0)
+                    Ok((GameVersion::GameMaker8_0, Some(antidec_settings)))
                 } else {
                     // Antidec couldn't be decrypted with the settings we read, so we must have got the format wrong
                     Err(ReaderError::UnknownFormat)
@@ -100,7 +111,7 @@ where
                     if found_header {
                         gm81::decrypt(exe, logger, gm81::XorMethod::Normal)?;
                         exe.seek(SeekFrom::Current(20))?;
-                        Ok(GameVersion::GameMaker8_1)
+                        Ok((GameVersion::GameMaker8_1, Some(antidec_settings)))
                     } else {
                         log!(logger, "Didn't find GM81 magic value (0xF7140017) before EOF, so giving up");
                         Err(ReaderError::UnknownFormat)
@@ -112,9 +123,9 @@ where
             } else {
                 // Standard formats
                 if gm80::check(exe, logger)? {
-                    Ok(GameVersion::GameMaker8_0)
+                    Ok((GameVersion::GameMaker8_0, None))
                 } else if gm81::check(exe, logger)? || gm81::check_lazy(exe, logger)? {
-                    Ok(GameVersion::GameMaker8_1)
+                    Ok((GameVersion::GameMaker8_1, None))
                 } else {
                     Err(ReaderError::UnknownFormat)
                 }
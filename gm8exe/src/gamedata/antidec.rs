@@ -2,7 +2,7 @@ use byteorder::{ReadBytesExt, LE};
 use std::io::{self, Read, Seek, SeekFrom};
 
 /// The settings used to decrypt antidec2-protected data, usually extracted from machine code
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug)]
 pub struct Metadata {
     pub exe_load_offset: u32,
     pub header_start: u32,
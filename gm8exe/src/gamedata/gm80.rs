@@ -1,4 +1,4 @@
-use byteorder::{ReadBytesExt, LE};
+use byteorder::{ReadBytesExt, WriteBytesExt, LE};
 use std::{
     cmp::max,
     io::{self, Read, Seek, SeekFrom},
@@ -184,3 +184,51 @@ where
 
     Ok(())
 }
+
+/// Applies GameMaker 8.0 protection to `data` and appends it to `output`, laid out the way `decrypt` expects.
+/// The swap table and garbage are generated from `seed`, so the same input always gives the same output.
+pub fn encrypt(output: &mut Vec<u8>, mut data: Vec<u8>, seed: u32) -> io::Result<()> {
+    // xorshift32, which only needs to look random
+    let mut state = seed | 1;
+    let mut next = || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    };
+
+    let mut swap_table = [0u8; 256];
+    for (i, val) in swap_table.iter_mut().enumerate() {
+        *val = i as u8;
+    }
+    for i in (1..256).rev() {
+        swap_table.swap(i, next() as usize % (i + 1));
+    }
+
+    let garbage1_size = next() % 0x40 + 0x40;
+    let garbage2_size = next() % 0x40 + 0x40;
+    output.write_u32::<LE>(garbage1_size)?;
+    output.write_u32::<LE>(garbage2_size)?;
+    for _ in 0..garbage1_size {
+        output.write_u32::<LE>(next())?;
+    }
+    output.extend_from_slice(&swap_table);
+    for _ in 0..garbage2_size {
+        output.write_u32::<LE>(next())?;
+    }
+    output.write_u32::<LE>(data.len() as u32)?;
+
+    // undo the second pass of decryption, swapping in the opposite order
+    for i in 0..data.len() {
+        let b = i.saturating_sub(swap_table[i & 0xFF] as usize);
+        data.swap(i, b);
+    }
+
+    // undo the first pass, each byte depending on the encrypted byte before it
+    for i in 1..data.len() {
+        data[i] = swap_table[data[i].wrapping_add(data[i - 1]).wrapping_add(i as u8) as usize];
+    }
+
+    output.extend_from_slice(&data);
+    Ok(())
+}
@@ -0,0 +1,388 @@
+//! Machine-readable summaries of a game as JSON, for tools that catalogue games rather than decompile them.
+//!
+//! `to_json` lists the version and protection the reader detected, where each chunk of gamedata was found,
+//! the game's settings and metadata for every asset. Images, sounds and other file contents are only given
+//! as sizes unless asked for, in which case they're included as base64.
+
+use crate::{
+    asset::{
+        extension::{CallingConvention, FileKind},
+        included_file::ExportSetting,
+        path::ConnectionKind,
+        CodeAction, PascalString, SoundKind, TriggerKind,
+    },
+    gamedata::Detection,
+    rsrc::VersionInfo,
+    settings::{GameHelpDialog, Settings},
+    AssetList, GameAssets, GameVersion,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use encoding_rs::WINDOWS_1252;
+use serde_json::{Map, Value};
+
+/// The `action_kind` of an Execute Code action, whose first argument is GML.
+const CODE_KIND: u32 = 7;
+
+/// Builds a JSON object, keeping its keys in the order they're given.
+macro_rules! object {
+    ($($key: literal: $value: expr),* $(,)?) => {
+        Value::Object(Map::from_iter(vec![$(($key.to_string(), Value::from($value))),*]))
+    };
+}
+
+/// Strings are decoded as Windows-1252, which gives every byte its own character,
+/// so the original bytes can be had back by encoding the text as Windows-1252 again.
+impl From<&PascalString> for Value {
+    fn from(x: &PascalString) -> Self {
+        Value::String(WINDOWS_1252.decode_without_bom_handling(&x.0).0.into_owned())
+    }
+}
+
+/// Describes a piece of binary data by its size, and its contents if `payloads` is set.
+fn blob(data: &[u8], payloads: bool) -> Value {
+    let mut fields = Map::new();
+    fields.insert("size".into(), data.len().into());
+    if payloads {
+        fields.insert("base64".into(), BASE64.encode(data).into());
+    }
+    Value::Object(fields)
+}
+
+/// Total length of the GML in a list of actions' Execute Code arguments.
+fn code_length(actions: &[CodeAction]) -> usize {
+    actions.iter().filter(|a| a.action_kind == CODE_KIND).map(|a| a.param_strings[0].0.len()).sum()
+}
+
+fn actions(actions: &[CodeAction]) -> Value {
+    object! {
+        "count": actions.len(),
+        "code_length": code_length(actions),
+    }
+}
+
+/// Lists the assets that exist, with their ids, skipping deleted ones.
+fn assets<T>(list: &AssetList<T>, describe: impl Fn(&T) -> Value) -> Value {
+    list.iter()
+        .enumerate()
+        .filter_map(|(id, asset)| {
+            asset.as_deref().map(|asset| match describe(asset) {
+                Value::Object(fields) => {
+                    let mut object = Map::new();
+                    object.insert("id".into(), id.into());
+                    object.extend(fields);
+                    Value::Object(object)
+                },
+                value => value,
+            })
+        })
+        .collect::<Vec<_>>()
+        .into()
+}
+
+fn detection(detection: &Detection) -> Value {
+    object! {
+        "upx": detection.upx.map(|(virtual_size, disk_offset)| object! {
+            "virtual_size": virtual_size,
+            "disk_offset": disk_offset,
+        }),
+        "antidec": detection.antidec.map(|data| object! {
+            "exe_load_offset": data.exe_load_offset,
+            "header_start": data.header_start,
+            "xor_mask": data.xor_mask,
+            "add_mask": data.add_mask,
+            "sub_mask": data.sub_mask,
+        }),
+        "offsets": Value::Object(
+            detection.offsets.iter().map(|(name, pos)| (name.to_string(), Value::from(*pos))).collect(),
+        ),
+    }
+}
+
+fn version_info(info: &VersionInfo) -> Value {
+    object! {
+        "version": format!("{}.{}.{}.{}", info.major, info.minor, info.release, info.build).as_str(),
+        "company": &info.company,
+        "product": &info.product,
+        "copyright": &info.copyright,
+        "description": &info.description,
+    }
+}
+
+fn settings(settings: &Settings, payloads: bool) -> Value {
+    object! {
+        "fullscreen": settings.fullscreen,
+        "scaling": settings.scaling,
+        "interpolate_pixels": settings.interpolate_pixels,
+        "clear_colour": settings.clear_colour,
+        "allow_resize": settings.allow_resize,
+        "window_on_top": settings.window_on_top,
+        "dont_draw_border": settings.dont_draw_border,
+        "dont_show_buttons": settings.dont_show_buttons,
+        "display_cursor": settings.display_cursor,
+        "freeze_on_lose_focus": settings.freeze_on_lose_focus,
+        "disable_screensaver": settings.disable_screensaver,
+        "force_cpu_render": settings.force_cpu_render,
+        "set_resolution": settings.set_resolution,
+        "colour_depth": settings.colour_depth,
+        "resolution": settings.resolution,
+        "frequency": settings.frequency,
+        "vsync": settings.vsync,
+        "esc_close_game": settings.esc_close_game,
+        "treat_close_as_esc": settings.treat_close_as_esc,
+        "f1_help_menu": settings.f1_help_menu,
+        "f4_fullscreen_toggle": settings.f4_fullscreen_toggle,
+        "f5_save_f6_load": settings.f5_save_f6_load,
+        "f9_screenshot": settings.f9_screenshot,
+        "priority": settings.priority,
+        "custom_load_image": settings.custom_load_image.as_deref().map(|x| blob(x, payloads)),
+        "transparent": settings.transparent,
+        "translucency": settings.translucency,
+        "loading_bar": settings.loading_bar,
+        "backdata": settings.backdata.as_deref().map(|x| blob(x, payloads)),
+        "frontdata": settings.frontdata.as_deref().map(|x| blob(x, payloads)),
+        "scale_progress_bar": settings.scale_progress_bar,
+        "show_error_messages": settings.show_error_messages,
+        "log_errors": settings.log_errors,
+        "always_abort": settings.always_abort,
+        "zero_uninitialized_vars": settings.zero_uninitialized_vars,
+        "error_on_uninitialized_args": settings.error_on_uninitialized_args,
+        "swap_creation_events": settings.swap_creation_events,
+    }
+}
+
+fn help_dialog(dialog: &GameHelpDialog) -> Value {
+    object! {
+        "bg_colour": u32::from(dialog.bg_colour),
+        "new_window": dialog.new_window,
+        "caption": &dialog.caption,
+        "left": dialog.left,
+        "top": dialog.top,
+        "width": dialog.width,
+        "height": dialog.height,
+        "border": dialog.border,
+        "resizable": dialog.resizable,
+        "window_on_top": dialog.window_on_top,
+        "freeze_game": dialog.freeze_game,
+        "info_length": dialog.info.0.len(),
+    }
+}
+
+/// Describes everything the reader found in a game as a JSON document.
+///
+/// Image, sound and file contents are given as `{"size": ...}`, with a `"base64"` field too if `payloads` is set.
+/// GML is given as its length in bytes. Assets are listed by id, leaving out deleted ones.
+/// Names and other text are decoded as Windows-1252 whatever code page the game was made in.
+pub fn to_json(assets: &GameAssets, payloads: bool) -> String {
+    let game = object! {
+        "version": match assets.version {
+            GameVersion::GameMaker8_0 => "8.0",
+            GameVersion::GameMaker8_1 => "8.1",
+        },
+        "game_id": assets.game_id,
+        "guid": assets.guid.to_vec(),
+        "detection": assets.detection.as_ref().map(detection),
+        "warnings": assets.warnings.iter().map(|w| Value::String(w.to_string())).collect::<Vec<_>>(),
+        "version_info": assets.version_info.as_ref().map(version_info),
+        "icon": assets.ico_file_raw.as_deref().map(|x| blob(x, payloads)),
+        "dx_dll": blob(&assets.dx_dll, payloads),
+        "settings": settings(&assets.settings, payloads),
+        "help_dialog": help_dialog(&assets.help_dialog),
+        "last_instance_id": assets.last_instance_id,
+        "last_tile_id": assets.last_tile_id,
+        "room_order": assets.room_order.clone(),
+        "library_init_code_lengths": assets.library_init_strings.iter().map(|s| s.0.len()).collect::<Vec<_>>(),
+        "constants": assets.constants.iter().map(|c| object! {
+            "name": &c.name,
+            "expression": &c.expression,
+        }).collect::<Vec<_>>(),
+        "triggers": self::assets(&assets.triggers, |t| object! {
+            "name": &t.name,
+            "constant_name": &t.constant_name,
+            "moment": match t.moment {
+                TriggerKind::Step => "step",
+                TriggerKind::BeginStep => "begin_step",
+                TriggerKind::EndStep => "end_step",
+            },
+            "condition_length": t.condition.0.len(),
+        }),
+        "extensions": assets.extensions.iter().map(|e| object! {
+            "name": &e.name,
+            "folder_name": &e.folder_name,
+            "files": e.files.iter().map(|f| object! {
+                "name": &f.name,
+                "kind": match f.kind {
+                    FileKind::DynamicLibrary => "dll",
+                    FileKind::GmlScript => "gml",
+                    FileKind::ActionLibrary => "action_library",
+                    FileKind::Other => "other",
+                },
+                "initializer": &f.initializer,
+                "finalizer": &f.finalizer,
+                "functions": f.functions.iter().map(|x| object! {
+                    "name": &x.name,
+                    "external_name": &x.external_name,
+                    "convention": match x.convention {
+                        CallingConvention::Gml => "gml",
+                        CallingConvention::Stdcall => "stdcall",
+                        CallingConvention::Cdecl => "cdecl",
+                        CallingConvention::Unknown => "unknown",
+                    },
+                    "arg_count": x.arg_count,
+                }).collect::<Vec<_>>(),
+                "constants": f.consts.iter().map(|x| object! {
+                    "name": &x.name,
+                    "value": &x.value,
+                }).collect::<Vec<_>>(),
+                "contents": blob(&f.contents, payloads),
+            }).collect::<Vec<_>>(),
+        }).collect::<Vec<_>>(),
+        "sprites": self::assets(&assets.sprites, |s| object! {
+            "name": &s.name,
+            "origin_x": s.origin_x,
+            "origin_y": s.origin_y,
+            "frame_count": s.frames.len(),
+            "frames": s.frames.iter().map(|f| object! {
+                "width": f.width,
+                "height": f.height,
+                "data": blob(&f.data, payloads),
+            }).collect::<Vec<_>>(),
+            "per_frame_colliders": s.per_frame_colliders,
+            "colliders": s.colliders.iter().map(|c| object! {
+                "width": c.width,
+                "height": c.height,
+                "bbox_left": c.bbox_left,
+                "bbox_right": c.bbox_right,
+                "bbox_top": c.bbox_top,
+                "bbox_bottom": c.bbox_bottom,
+            }).collect::<Vec<_>>(),
+        }),
+        "sounds": self::assets(&assets.sounds, |s| object! {
+            "name": &s.name,
+            "source": &s.source,
+            "extension": &s.extension,
+            "kind": match s.kind {
+                SoundKind::Normal => "normal",
+                SoundKind::BackgroundMusic => "background_music",
+                SoundKind::ThreeDimensional => "3d",
+                SoundKind::Multimedia => "multimedia",
+            },
+            "volume": s.volume,
+            "pan": s.pan,
+            "preload": s.preload,
+            "data": s.data.as_deref().map(|x| blob(x, payloads)),
+        }),
+        "backgrounds": self::assets(&assets.backgrounds, |b| object! {
+            "name": &b.name,
+            "width": b.width,
+            "height": b.height,
+            "data": b.data.as_deref().map(|x| blob(x, payloads)),
+        }),
+        "paths": self::assets(&assets.paths, |p| object! {
+            "name": &p.name,
+            "connection": match p.connection {
+                ConnectionKind::StraightLine => "straight",
+                ConnectionKind::SmoothCurve => "smooth",
+            },
+            "precision": p.precision,
+            "closed": p.closed,
+            "point_count": p.points.len(),
+        }),
+        "scripts": self::assets(&assets.scripts, |s| object! {
+            "name": &s.name,
+            "code_length": s.source.0.len(),
+        }),
+        "fonts": self::assets(&assets.fonts, |f| object! {
+            "name": &f.name,
+            "sys_name": &f.sys_name,
+            "size": f.size,
+            "bold": f.bold,
+            "italic": f.italic,
+            "range_start": f.range_start,
+            "range_end": f.range_end,
+            "charset": f.charset,
+            "aa_level": f.aa_level,
+            "map_width": f.map_width,
+            "map_height": f.map_height,
+            "pixel_map": blob(&f.pixel_map, payloads),
+        }),
+        "timelines": self::assets(&assets.timelines, |t| object! {
+            "name": &t.name,
+            "moments": t.moments.iter().map(|(moment, list)| object! {
+                "moment": *moment,
+                "actions": actions(list),
+            }).collect::<Vec<_>>(),
+        }),
+        "objects": self::assets(&assets.objects, |o| object! {
+            "name": &o.name,
+            "sprite_index": o.sprite_index,
+            "mask_index": o.mask_index,
+            "parent_index": o.parent_index,
+            "depth": o.depth,
+            "solid": o.solid,
+            "visible": o.visible,
+            "persistent": o.persistent,
+            "events": o.events.iter().enumerate().flat_map(|(kind, events)| {
+                events.iter().map(move |(number, list)| object! {
+                    "type": kind,
+                    "number": *number,
+                    "actions": actions(list),
+                })
+            }).collect::<Vec<_>>(),
+        }),
+        "rooms": self::assets(&assets.rooms, |r| object! {
+            "name": &r.name,
+            "caption": &r.caption,
+            "width": r.width,
+            "height": r.height,
+            "speed": r.speed,
+            "persistent": r.persistent,
+            "views_enabled": r.views_enabled,
+            "instance_count": r.instances.len(),
+            "tile_count": r.tiles.len(),
+            "creation_code_length": r.creation_code.0.len(),
+            "instance_code_length": r.instances.iter().map(|i| i.creation_code.0.len()).sum::<usize>(),
+        }),
+        "included_files": assets.included_files.iter().map(|f| object! {
+            "file_name": &f.file_name,
+            "source_path": &f.source_path,
+            "source_length": f.source_length,
+            "export": match &f.export_settings {
+                ExportSetting::NoExport => "none".into(),
+                ExportSetting::TempFolder => "temp_folder".into(),
+                ExportSetting::GameFolder => "game_folder".into(),
+                ExportSetting::CustomFolder(path) => Value::from(path),
+            },
+            "overwrite_file": f.overwrite_file,
+            "free_memory": f.free_memory,
+            "remove_at_end": f.remove_at_end,
+            "data": f.embedded_data.as_deref().map(|x| blob(x, payloads)),
+        }).collect::<Vec<_>>(),
+    };
+    let mut out = serde_json::to_string_pretty(&game).expect("a JSON value always serialises");
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_keeps_its_bytes() {
+        let raw = PascalString((0..=255).collect());
+        let text = match Value::from(&raw) {
+            Value::String(text) => text,
+            value => panic!("expected a string, got {}", value),
+        };
+        assert_eq!(text.chars().count(), 256);
+        assert!(text.starts_with("\0\u{1}") && text.ends_with("\u{fe}\u{ff}"));
+        assert!(text.contains("\u{20ac}\u{81}\u{201a}"));
+        assert_eq!(&*WINDOWS_1252.encode(&text).0, &*raw.0);
+    }
+
+    #[test]
+    fn blobs() {
+        assert_eq!(blob(b"foo", false).to_string(), r#"{"size":3}"#);
+        assert_eq!(blob(b"fo", true).to_string(), r#"{"size":2,"base64":"Zm8="}"#);
+    }
+}
@@ -20,15 +20,21 @@ macro_rules! log {
 
 pub mod asset;
 pub mod def;
+pub mod extract;
 pub mod gamedata;
+#[cfg(feature = "inspect")]
+pub mod inspect;
 pub mod reader;
 pub mod rsrc;
 pub mod settings;
+pub mod tree;
 pub mod upx;
+pub mod writer;
 
 mod colour;
 
 use crate::asset::*;
+use rsrc::VersionInfo;
 use settings::{GameHelpDialog, Settings};
 
 pub type AssetList<T> = Vec<Option<Box<T>>>;
@@ -48,14 +54,21 @@ pub struct GameAssets {
     pub rooms: AssetList<Room>,
     pub included_files: Vec<IncludedFile>,
     pub version: GameVersion,
+    /// How the gamedata was found in the exe, or `None` if it was read from a project file
+    pub detection: Option<gamedata::Detection>,
+    /// Errors for the assets that were left out when reading in recovery mode
+    pub warnings: Vec<reader::ReaderError>,
 
     pub dx_dll: Vec<u8>,
     pub ico_file_raw: Option<Vec<u8>>,
+    pub version_info: Option<VersionInfo>,
     pub help_dialog: GameHelpDialog,
     pub last_instance_id: i32,
     pub last_tile_id: i32,
     pub library_init_strings: Vec<PascalString>,
     pub room_order: Vec<i32>,
+    /// The IDE's folder view of the assets, or empty if it was read from an exe
+    pub resource_tree: Vec<tree::Node>,
 
     pub settings: Settings,
     pub game_id: u32,
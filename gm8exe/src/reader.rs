@@ -1,6 +1,6 @@
 use crate::{
     asset::*,
-    gamedata::{self, gm80},
+    gamedata::{self, gm80, Detection},
     rsrc,
     settings::{GameHelpDialog, Settings},
     AssetList, GameAssets, GameVersion,
@@ -13,13 +13,18 @@ use std::{
     io::{self, Read, Seek, SeekFrom},
 };
 
+mod gmk;
+pub use gmk::from_gmk;
+
 #[derive(Debug)]
 pub enum ReaderError {
     AssetError(Error),
     InvalidExeHeader,
+    InvalidGmkHeader,
     IO(io::Error),
     PartialUPXPacking,
     UnknownFormat,
+    Located { error: Box<ReaderError>, location: Location },
 }
 impl std::error::Error for ReaderError {}
 impl Display for ReaderError {
@@ -27,15 +32,71 @@ impl Display for ReaderError {
         write!(f, "{}", match self {
             ReaderError::AssetError(err) => format!("asset data error: {}", err),
             ReaderError::InvalidExeHeader => "invalid exe header".into(),
+            ReaderError::InvalidGmkHeader => "invalid gmk header".into(),
             ReaderError::IO(err) => format!("io error: {}", err),
             ReaderError::PartialUPXPacking => {
                 "looks upx protected, can't locate headers".into()
             },
             ReaderError::UnknownFormat => "unknown format, could not identify file".into(),
+            ReaderError::Located { error, location } => format!("{} ({})", error, location),
         })
     }
 }
 
+impl ReaderError {
+    fn located(self, location: Location) -> Self {
+        match self {
+            ReaderError::Located { .. } => self,
+            error => ReaderError::Located { error: Box::new(error), location },
+        }
+    }
+}
+
+/// Where in a game's data a reader error happened.
+#[derive(Debug)]
+pub struct Location {
+    /// The chunk being read, named as in `gamedata::Detection::offsets`, such as `sprites`
+    pub chunk: &'static str,
+    /// The asset's index in its chunk, if the error was in one
+    pub index: Option<usize>,
+    /// The asset's name, if it could still be read
+    pub name: Option<PascalString>,
+    /// Where the chunk or asset starts in the buffer the game was decrypted in, which isn't always a file position:
+    /// GM8.0 shuffles the bytes of everything after `dx_dll`, so what's at the same position in the exe is different
+    pub buffer_offset: u64,
+}
+
+impl Location {
+    /// Locates an asset from its compressed block, reading its name from there if possible.
+    fn asset(chunk: &'static str, index: usize, buffer_offset: u64, data: &[u8]) -> Self {
+        // everything starts with its name after an existence flag or version, except triggers have both
+        let mut data = inflate(data);
+        let skip = if chunk == "triggers" { 8 } else { 4 };
+        let name = io::copy(&mut (&mut data).take(skip), &mut io::sink())
+            .and_then(|_| data.read_u32::<LE>())
+            .ok()
+            .filter(|len| *len <= 1024)
+            .and_then(|len| {
+                let mut name = vec![0; len as usize];
+                data.read_exact(&mut name).ok().map(|_| PascalString(name.into()))
+            });
+        Location { chunk, index: Some(index), name, buffer_offset }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.chunk)?;
+        if let Some(index) = self.index {
+            write!(f, " #{}", index)?;
+        }
+        if let Some(name) = &self.name {
+            write!(f, " '{}'", name)?;
+        }
+        write!(f, " at 0x{:X} in the decrypted data", self.buffer_offset)
+    }
+}
+
 macro_rules! from_err {
     ($t: ident, $e: ty, $variant: ident) => {
         impl From<$e> for $t {
@@ -66,12 +127,116 @@ pub struct PESection {
     pub disk_address: u32,
 }
 
-pub fn from_exe<I, F>(mut exe: I, logger: Option<F>, strict: bool, multithread: bool) -> Result<GameAssets, ReaderError>
+/// Splits a list of length-prefixed asset blocks into slices of the source data, with their offsets.
+fn get_asset_refs<'a>(src: &mut io::Cursor<&'a [u8]>) -> io::Result<Vec<(u64, &'a [u8])>> {
+    let count = src.read_u32::<LE>()? as usize;
+    let mut refs = Vec::with_capacity(count.min(0x10000));
+    for _ in 0..count {
+        let len = src.read_u32::<LE>()? as usize;
+        let pos = src.position() as usize;
+        src.seek(SeekFrom::Current(len as i64))?;
+        let data = src.get_ref().get(pos..pos + len).ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+        refs.push((pos as u64, data));
+    }
+    Ok(refs)
+}
+
+/// Inflates and deserializes a list of zlib-compressed asset blocks, with deleted assets as `None`.
+///
+/// Errors are located by the chunk name and the asset's index, name and offset. If `warnings` is given,
+/// assets that can't be read are left as `None` and their errors are added to it instead.
+fn get_assets<T, F>(
+    src: &mut io::Cursor<&[u8]>,
+    chunk: &'static str,
+    deserializer: F,
+    multithread: bool,
+    mut warnings: Option<&mut Vec<ReaderError>>,
+) -> Result<AssetList<T>, ReaderError>
+where
+    T: Send,
+    F: Fn(ZlibDecoder<&[u8]>) -> Result<T, Error> + Sync,
+{
+    let to_asset = |data: &[u8]| {
+        // Skip block if it's just a deflated `00 00 00 00` (normal compression level, as GM8 does).
+        // This will short circuit on length, but it checks against this literal to make sure.
+        if data == [0x78, 0x9C, 0x63, 0x60, 0x60, 0x60, 0x00, 0x00, 0x00, 0x04, 0x00, 0x01] {
+            return Ok(None)
+        }
+        let mut data = inflate(data);
+
+        // If the first u32 is 0 then it's a deleted asset, and is None.
+        match data.read_u32::<LE>() {
+            Ok(0) => Ok(None),
+            Ok(_) => Ok(Some(Box::new(deserializer(data)?))),
+            Err(_) => Err(ReaderError::AssetError(Error::MalformedData)),
+        }
+    };
+
+    let refs = get_asset_refs(src)?;
+    let results: Vec<_> = if multithread {
+        refs.par_iter().map(|(_, data)| to_asset(data)).collect()
+    } else {
+        refs.iter().map(|(_, data)| to_asset(data)).collect()
+    };
+    refs.iter()
+        .zip(results)
+        .enumerate()
+        .map(|(index, (&(offset, data), result))| {
+            result.or_else(|error| {
+                let error = error.located(Location::asset(chunk, index, offset, data));
+                match warnings.as_deref_mut() {
+                    Some(warnings) => {
+                        warnings.push(error);
+                        Ok(None)
+                    },
+                    None => Err(error),
+                }
+            })
+        })
+        .collect()
+}
+
+/// Reads a GameMaker 8.0 or 8.1 game executable.
+///
+/// Errors are located by the chunk and, if there is one, the asset being read when they happened. In recovery mode
+/// (`recover`), assets that can't be read are left out as `None` and their errors collected in `warnings`.
+pub fn from_exe<I, F>(
+    mut exe: I,
+    logger: Option<F>,
+    strict: bool,
+    multithread: bool,
+    recover: bool,
+) -> Result<GameAssets, ReaderError>
 where
     F: Copy + Fn(&str),
     I: AsRef<[u8]> + AsMut<[u8]>,
 {
-    let exe = exe.as_mut();
+    let mut detection = Detection::default();
+    match read_exe(exe.as_mut(), logger, strict, multithread, recover, &mut detection) {
+        Ok(mut assets) => {
+            assets.detection = Some(detection);
+            Ok(assets)
+        },
+        // anything not already located happened in whichever chunk was last started
+        Err(error) => Err(match detection.offsets.last() {
+            Some(&(chunk, buffer_offset)) => error.located(Location { chunk, index: None, name: None, buffer_offset }),
+            None => error,
+        }),
+    }
+}
+
+fn read_exe<F>(
+    exe: &mut [u8],
+    logger: Option<F>,
+    strict: bool,
+    multithread: bool,
+    recover: bool,
+    detection: &mut Detection,
+) -> Result<GameAssets, ReaderError>
+where
+    F: Copy + Fn(&str),
+{
+    let mut warnings = Vec::new();
 
     // comfy wrapper for byteorder I/O
     let mut exe = io::Cursor::new(exe);
@@ -147,6 +312,17 @@ where
         })
         .transpose()?
         .flatten();
+    // the version information only matters to the IDE, so a game can still be read without it
+    let version_info = rsrc_location.and_then(|x| {
+        let temp_pos = exe.position();
+        exe.set_position(u64::from(x));
+        let info = rsrc::find_version_info(&mut exe, &sections);
+        exe.set_position(temp_pos);
+        info.unwrap_or_else(|err| {
+            log!(logger, "Couldn't read version information: {}", err);
+            None
+        })
+    });
 
     // Decide if UPX is in use based on PE section names
     // This is None if there is no UPX, obviously, otherwise it's (max_size, offset_on_disk)
@@ -156,7 +332,9 @@ where
     };
 
     // Identify the game version in use and locate the gamedata header
-    let game_ver = gamedata::find(&mut exe, logger, upx_data)?;
+    let (game_ver, antidec) = gamedata::find(&mut exe, logger, upx_data)?;
+    detection.upx = upx_data;
+    detection.antidec = antidec;
 
     // little helper thing
     macro_rules! assert_ver {
@@ -176,10 +354,13 @@ where
     }
 
     // Game Settings
+    detection.offsets.push(("settings", exe.position()));
     let settings_len = exe.read_u32::<LE>()? as usize;
     let pos = exe.position() as usize;
     exe.seek(SeekFrom::Current(settings_len as i64))?;
-    let mut cfg = inflate(&exe.get_ref()[pos..pos + settings_len]);
+    let mut cfg = inflate(
+        exe.get_ref().get(pos..pos + settings_len).ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?,
+    );
 
     log!(logger, "Reading settings chunk...");
 
@@ -385,6 +566,7 @@ where
     // Embedded DirectX DLL
     // we obviously don't need this, so we skip over it
     // if we're verbose logging, read the dll name (usually D3DX8.dll, but...)
+    detection.offsets.push(("dx_dll", exe.position()));
     if logger.is_some() {
         let dllname = exe.read_pas_string()?;
         log!(logger, "Skipping embedded DLL '{}'", dllname);
@@ -400,6 +582,7 @@ where
     exe.read_exact(&mut dx_dll)?;
 
     // yeah
+    detection.offsets.push(("encrypted", exe.position()));
     gm80::decrypt(&mut exe, logger)?;
 
     // Garbage field - random bytes
@@ -416,64 +599,7 @@ where
     // 16 random bytes...
     let guid = [exe.read_u32::<LE>()?, exe.read_u32::<LE>()?, exe.read_u32::<LE>()?, exe.read_u32::<LE>()?];
 
-    fn get_asset_refs<'a>(src: &mut io::Cursor<&'a [u8]>) -> io::Result<Vec<&'a [u8]>> {
-        let count = src.read_u32::<LE>()? as usize;
-        let mut refs = Vec::with_capacity(count);
-        for _ in 0..count {
-            let len = src.read_u32::<LE>()? as usize;
-            let pos = src.position() as usize;
-            src.seek(SeekFrom::Current(len as i64))?;
-            let data = src.get_ref();
-            refs.push(&data[pos..pos + len]);
-        }
-        Ok(refs)
-    }
-
-    fn get_assets<T, F>(
-        src: &mut io::Cursor<&[u8]>,
-        deserializer: F,
-        multithread: bool,
-    ) -> Result<AssetList<T>, ReaderError>
-    where
-        T: Send,
-        F: Fn(ZlibDecoder<&[u8]>) -> Result<T, Error> + Sync,
-    {
-        let to_asset = |data: &[u8]| {
-            // Skip block if it's just a deflated `00 00 00 00` (normal compression level, as GM8 does).
-            // This will short circuit on length, but it checks against this literal to make sure.
-            if data == [0x78, 0x9C, 0x63, 0x60, 0x60, 0x60, 0x00, 0x00, 0x00, 0x04, 0x00, 0x01] {
-                return Ok(None)
-            }
-            let mut data = inflate(data);
-
-            // If the first u32 is 0 then it's a deleted asset, and is None.
-            match data.read_u32::<LE>() {
-                Ok(0) => Ok(None),
-                Ok(_) => Ok(Some(Box::new(deserializer(data)?))),
-                Err(_) => Err(ReaderError::AssetError(Error::MalformedData)),
-            }
-        };
-
-        if multithread {
-            get_asset_refs(src)?.par_iter().copied().map(to_asset).collect::<Result<Vec<_>, ReaderError>>()
-        } else {
-            get_asset_refs(src)?.iter().copied().map(to_asset).collect::<Result<Vec<_>, ReaderError>>()
-        }
-    }
-
-    #[inline]
-    fn get_assets_ex<T>(
-        src: &mut io::Cursor<&[u8]>,
-        version: GameVersion,
-        strict: bool,
-        multithread: bool,
-    ) -> Result<AssetList<T>, ReaderError>
-    where
-        T: Asset + Send,
-    {
-        get_assets(src, |data| <T as Asset>::deserialize_exe(data, version, strict), multithread)
-    }
-
+    detection.offsets.push(("extensions", exe.position()));
     assert_ver!("extensions header", 700, exe.read_u32::<LE>()?)?;
     let extension_count = exe.read_u32::<LE>()? as usize;
     let mut extensions = Vec::with_capacity(extension_count);
@@ -488,9 +614,23 @@ where
     let mut exe = io::Cursor::new(exe.into_inner() as &[u8]);
     exe.set_position(prev_pos);
 
+    // reads a chunk of assets, skipping the ones that can't be read in recovery mode
+    macro_rules! get_assets_ex {
+        ($chunk: literal) => {
+            get_assets(
+                &mut exe,
+                $chunk,
+                |data| Asset::deserialize_exe(data, game_ver, strict),
+                multithread,
+                recover.then(|| &mut warnings),
+            )
+        };
+    }
+
     // Triggers
+    detection.offsets.push(("triggers", exe.position()));
     assert_ver!("triggers header", 800, exe.read_u32::<LE>()?)?;
-    let triggers: AssetList<Trigger> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let triggers: AssetList<Trigger> = get_assets_ex!("triggers")?;
     if logger.is_some() {
         triggers.iter().flatten().for_each(|trigger| {
             log!(
@@ -504,6 +644,7 @@ where
     }
 
     // Constants
+    detection.offsets.push(("constants", exe.position()));
     assert_ver!("constants header", 800, exe.read_u32::<LE>()?)?;
     let constant_count = exe.read_u32::<LE>()? as usize;
     let mut constants = Vec::with_capacity(constant_count);
@@ -515,8 +656,9 @@ where
     }
 
     // Sounds
+    detection.offsets.push(("sounds", exe.position()));
     assert_ver!("sounds header", 800, exe.read_u32::<LE>()?)?;
-    let sounds: AssetList<Sound> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let sounds: AssetList<Sound> = get_assets_ex!("sounds")?;
     if logger.is_some() {
         sounds.iter().flatten().for_each(|sound| {
             log!(logger, " + Added sound '{}' ({})", sound.name, sound.source);
@@ -524,8 +666,9 @@ where
     }
 
     // Sprites
+    detection.offsets.push(("sprites", exe.position()));
     assert_ver!("sprites header", 800, exe.read_u32::<LE>()?)?;
-    let sprites: AssetList<Sprite> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let sprites: AssetList<Sprite> = get_assets_ex!("sprites")?;
     if logger.is_some() {
         sprites.iter().flatten().for_each(|sprite| {
             let framecount = sprite.frames.len();
@@ -546,8 +689,9 @@ where
     }
 
     // Backgrounds
+    detection.offsets.push(("backgrounds", exe.position()));
     assert_ver!("backgrounds header", 800, exe.read_u32::<LE>()?)?;
-    let backgrounds: AssetList<Background> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let backgrounds: AssetList<Background> = get_assets_ex!("backgrounds")?;
     if logger.is_some() {
         backgrounds.iter().flatten().for_each(|background| {
             log!(logger, " + Added background '{}' ({}x{})", background.name, background.width, background.height);
@@ -555,8 +699,9 @@ where
     }
 
     // Paths
+    detection.offsets.push(("paths", exe.position()));
     assert_ver!("paths header", 800, exe.read_u32::<LE>()?)?;
-    let paths: AssetList<Path> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let paths: AssetList<Path> = get_assets_ex!("paths")?;
     if logger.is_some() {
         use crate::asset::path::ConnectionKind;
 
@@ -578,8 +723,9 @@ where
     }
 
     // Scripts
+    detection.offsets.push(("scripts", exe.position()));
     assert_ver!("scripts header", 800, exe.read_u32::<LE>()?)?;
-    let scripts: AssetList<Script> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let scripts: AssetList<Script> = get_assets_ex!("scripts")?;
     if logger.is_some() {
         scripts.iter().flatten().for_each(|script| {
             log!(logger, " + Added script '{}'", script.name);
@@ -587,8 +733,9 @@ where
     }
 
     // Fonts
+    detection.offsets.push(("fonts", exe.position()));
     assert_ver!("fonts header", 800, exe.read_u32::<LE>()?)?;
-    let fonts: AssetList<Font> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let fonts: AssetList<Font> = get_assets_ex!("fonts")?;
     if logger.is_some() {
         fonts.iter().flatten().for_each(|font| {
             log!(
@@ -604,8 +751,9 @@ where
     }
 
     // Timelines
+    detection.offsets.push(("timelines", exe.position()));
     assert_ver!("timelines header", 800, exe.read_u32::<LE>()?)?;
-    let timelines: AssetList<Timeline> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let timelines: AssetList<Timeline> = get_assets_ex!("timelines")?;
     if logger.is_some() {
         timelines.iter().flatten().for_each(|timeline| {
             log!(logger, " + Added timeline '{}' (moments: {})", timeline.name, timeline.moments.len());
@@ -613,8 +761,9 @@ where
     }
 
     // Objects
+    detection.offsets.push(("objects", exe.position()));
     assert_ver!("objects header", 800, exe.read_u32::<LE>()?)?;
-    let objects: AssetList<Object> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let objects: AssetList<Object> = get_assets_ex!("objects")?;
     if logger.is_some() {
         objects.iter().flatten().for_each(|object| {
             log!(
@@ -630,8 +779,9 @@ where
     }
 
     // Rooms
+    detection.offsets.push(("rooms", exe.position()));
     assert_ver!("rooms header", 800, exe.read_u32::<LE>()?)?;
-    let rooms: AssetList<Room> = get_assets_ex(&mut exe, game_ver, strict, multithread)?;
+    let rooms: AssetList<Room> = get_assets_ex!("rooms")?;
     if logger.is_some() {
         rooms.iter().flatten().for_each(|room| {
             log!(
@@ -650,16 +800,23 @@ where
     let last_tile_id = exe.read_i32::<LE>()?;
 
     // Included Files
+    detection.offsets.push(("included_files", exe.position()));
     assert_ver!("included files header", 800, exe.read_u32::<LE>()?)?;
-    // TODO: how was this different from the others? why is it not using get_assets?
-    let included_files = get_asset_refs(&mut exe)?
-        .iter()
-        .map(|chunk| {
-            // AssetDataError -> ReaderError
-            let data = inflate(chunk);
-            IncludedFile::deserialize_exe(data, game_ver, strict).map_err(ReaderError::from)
-        })
-        .collect::<Result<Vec<_>, _>>()?;
+    // These don't have an existence flag, so they aren't read with get_assets, and broken ones are left out
+    let mut included_files = Vec::new();
+    for (index, (offset, data)) in get_asset_refs(&mut exe)?.into_iter().enumerate() {
+        match IncludedFile::deserialize_exe(inflate(data), game_ver, strict) {
+            Ok(file) => included_files.push(file),
+            Err(error) => {
+                let error = ReaderError::from(error).located(Location::asset("included_files", index, offset, data));
+                if recover {
+                    warnings.push(error);
+                } else {
+                    return Err(error)
+                }
+            },
+        }
+    }
     if logger.is_some() {
         use crate::asset::included_file::ExportSetting;
         for file in &included_files {
@@ -679,6 +836,7 @@ where
     }
 
     // Help Dialog
+    detection.offsets.push(("help_dialog", exe.position()));
     assert_ver!("help dialog", 800, exe.read_u32::<LE>()?)?;
     let help_dialog = {
         let len = exe.read_u32::<LE>()? as usize;
@@ -704,6 +862,7 @@ where
     };
 
     // Action library initialization code. These are GML strings which get run at game start, in order.
+    detection.offsets.push(("library_init_strings", exe.position()));
     assert_ver!("action library initialization code header", 500, exe.read_u32::<LE>()?)?;
     let str_count = exe.read_u32::<LE>()? as usize;
     let mut library_init_strings = Vec::with_capacity(str_count);
@@ -713,6 +872,7 @@ where
     log!(logger, " + Read {} action library initialization strings", str_count);
 
     // Room Order
+    detection.offsets.push(("room_order", exe.position()));
     assert_ver!("room order lookup", 700, exe.read_u32::<LE>()?)?;
     let room_order = {
         let ro_count = exe.read_u32::<LE>()? as usize;
@@ -742,15 +902,76 @@ where
 
         dx_dll,
         ico_file_raw,
+        version_info,
         version: game_ver,
+        detection: None,
+        warnings,
         help_dialog,
         last_instance_id,
         last_tile_id,
         library_init_strings,
         room_order,
+        resource_tree: Vec::new(),
 
         settings,
         game_id,
         guid,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::WritePascalString;
+    use byteorder::WriteBytesExt;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    /// Two scripts, the second cut off after its name.
+    fn scripts() -> Vec<u8> {
+        let mut list = vec![2, 0, 0, 0];
+        for (name, complete) in [("scr_a", true), ("scr_b", false)] {
+            let mut block = vec![1, 0, 0, 0];
+            block.write_pas_string(&name.into()).unwrap();
+            if complete {
+                block.write_u32::<LE>(800).unwrap();
+                block.write_pas_string(&"return 1".into()).unwrap();
+            }
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&block).unwrap();
+            let block = encoder.finish().unwrap();
+            list.write_u32::<LE>(block.len() as u32).unwrap();
+            list.extend_from_slice(&block);
+        }
+        list
+    }
+
+    fn read(data: &[u8], warnings: Option<&mut Vec<ReaderError>>) -> Result<AssetList<Script>, ReaderError> {
+        let deserializer = |data: ZlibDecoder<&[u8]>| Script::deserialize_exe(data, GameVersion::GameMaker8_0, true);
+        get_assets(&mut io::Cursor::new(data), "scripts", deserializer, false, warnings)
+    }
+
+    #[test]
+    fn broken_assets_are_located() {
+        let data = scripts();
+        match read(&data, None) {
+            Err(ReaderError::Located { error, location }) => {
+                assert!(matches!(*error, ReaderError::AssetError(Error::IO(_))));
+                assert_eq!((location.chunk, location.index), ("scripts", Some(1)));
+                assert_eq!(location.name.map(|n| n.0), Some(b"scr_b"[..].into()));
+                assert!(location.buffer_offset > 8 && (location.buffer_offset as usize) < data.len());
+            },
+            _ => panic!("expected a located error"),
+        }
+    }
+
+    #[test]
+    fn broken_assets_are_skipped_in_recovery() {
+        let mut warnings = Vec::new();
+        let scripts = read(&scripts(), Some(&mut warnings)).unwrap();
+        assert_eq!(scripts.len(), 2);
+        assert_eq!(&*scripts[0].as_ref().unwrap().source.0, b"return 1");
+        assert!(scripts[1].is_none());
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,970 @@
+//! Reader for GameMaker 8.0 and 8.1 editable project files (`.gmk` and `.gm81`).
+//!
+//! Project files hold the same resources as a compiled game, but in the IDE's own layout: every resource carries a
+//! timestamp, sprites store their mask settings instead of the masks themselves, and rooms keep their editor
+//! settings. Fonts and extensions are only references there - the IDE renders the one and embeds the other when
+//! building an exe - so fonts come out of this reader without glyphs and extensions without files.
+
+use super::{get_asset_refs, get_assets, inflate, ReaderError};
+use crate::{
+    asset::{
+        path::{ConnectionKind, Point},
+        room::{self, ViewFollowData},
+        sound::SoundFX,
+        sprite::{CollisionMap, Frame},
+        *,
+    },
+    rsrc::VersionInfo,
+    settings::{GameHelpDialog, Settings},
+    tree::{self, NodeKind},
+    AssetList, GameAssets, GameVersion,
+};
+use byteorder::{ReadBytesExt, LE};
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// The magic number at the start of every project file.
+pub const MAGIC: u32 = 1234321;
+
+/// What the decompiler wraps around GM8.1 instance and tile properties in creation code,
+/// since project files have no fields for them.
+const COMPAT_HEADER: &[u8] = b"/* gm8.2 compat */\r\n";
+const COMPAT_FOOTER: &[u8] = b"/****************/\r\n\r\n";
+
+/// Checks a version header. Like in the exe reader, these only matter in strict mode.
+fn check_ver(strict: bool, got: u32, expected: u32) -> Result<(), Error> {
+    if strict && got != expected { Err(Error::VersionError { expected, got }) } else { Ok(()) }
+}
+
+/// Skips over a "last changed" timestamp, which is a Delphi TDateTime.
+fn skip_timestamp(reader: &mut impl Read) -> io::Result<()> {
+    reader.read_f64::<LE>().map(|_| ())
+}
+
+/// Reads a length-prefixed zlib block from the file.
+fn read_block<'a>(src: &mut io::Cursor<&'a [u8]>) -> io::Result<impl Read + 'a> {
+    let len = src.read_u32::<LE>()? as usize;
+    let pos = src.position() as usize;
+    let data = src.get_ref().get(pos..pos + len).ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+    src.seek(SeekFrom::Current(len as i64))?;
+    Ok(inflate(data))
+}
+
+/// Reads a zlib block nested inside another one, such as a loading bar image in the settings, and inflates it.
+fn read_nested_block(reader: &mut impl Read) -> io::Result<Box<[u8]>> {
+    let len = reader.read_u32::<LE>()? as usize;
+    let mut data = Vec::new();
+    inflate(&reader.read_chunk(len)?).read_to_end(&mut data)?;
+    Ok(data.into_boxed_slice())
+}
+
+fn read_settings<F>(
+    mut cfg: impl Read,
+    version: GameVersion,
+    logger: Option<F>,
+) -> Result<(Settings, Option<Vec<u8>>, VersionInfo), Error>
+where
+    F: Copy + Fn(&str),
+{
+    fn read_image_maybe(cfg: &mut impl Read) -> io::Result<Option<Box<[u8]>>> {
+        if cfg.read_u32::<LE>()? != 0 { read_nested_block(cfg).map(Some) } else { Ok(None) }
+    }
+
+    let fullscreen = cfg.read_u32::<LE>()? != 0;
+    let interpolate_pixels = cfg.read_u32::<LE>()? != 0;
+    let dont_draw_border = cfg.read_u32::<LE>()? != 0;
+    let display_cursor = cfg.read_u32::<LE>()? != 0;
+    let scaling = cfg.read_i32::<LE>()?;
+    let allow_resize = cfg.read_u32::<LE>()? != 0;
+    let window_on_top = cfg.read_u32::<LE>()? != 0;
+    let clear_colour = cfg.read_u32::<LE>()?;
+    let set_resolution = cfg.read_u32::<LE>()? != 0;
+    let colour_depth = cfg.read_u32::<LE>()?;
+    let resolution = cfg.read_u32::<LE>()?;
+    let frequency = cfg.read_u32::<LE>()?;
+    let dont_show_buttons = cfg.read_u32::<LE>()? != 0;
+    let (vsync, force_cpu_render) = match (version, cfg.read_u32::<LE>()?) {
+        (GameVersion::GameMaker8_0, x) => (x != 0, true),
+        (GameVersion::GameMaker8_1, x) => ((x & 1) != 0, (x & (1 << 7)) != 0),
+    };
+    let disable_screensaver = cfg.read_u32::<LE>()? != 0;
+    let f4_fullscreen_toggle = cfg.read_u32::<LE>()? != 0;
+    let f1_help_menu = cfg.read_u32::<LE>()? != 0;
+    let esc_close_game = cfg.read_u32::<LE>()? != 0;
+    let f5_save_f6_load = cfg.read_u32::<LE>()? != 0;
+    let f9_screenshot = cfg.read_u32::<LE>()? != 0;
+    let treat_close_as_esc = cfg.read_u32::<LE>()? != 0;
+    let priority = cfg.read_u32::<LE>()?;
+    let freeze_on_lose_focus = cfg.read_u32::<LE>()? != 0;
+    let loading_bar = cfg.read_u32::<LE>()?;
+    // the images are only stored for an own loading bar
+    let (backdata, frontdata) = if loading_bar == 2 {
+        (read_image_maybe(&mut cfg)?, read_image_maybe(&mut cfg)?)
+    } else {
+        (None, None)
+    };
+    // unlike in the exe, there's a flag for whether to show the image and another for whether there is one
+    let custom_load_image = if cfg.read_u32::<LE>()? != 0 { read_image_maybe(&mut cfg)? } else { None };
+    let transparent = cfg.read_u32::<LE>()? != 0;
+    let translucency = cfg.read_u32::<LE>()?;
+    let scale_progress_bar = cfg.read_u32::<LE>()? != 0;
+    let ico_len = cfg.read_u32::<LE>()? as usize;
+    let ico_file_raw = if ico_len != 0 { Some(cfg.read_chunk(ico_len)?) } else { None };
+    let show_error_messages = cfg.read_u32::<LE>()? != 0;
+    let log_errors = cfg.read_u32::<LE>()? != 0;
+    let always_abort = cfg.read_u32::<LE>()? != 0;
+    let (zero_uninitialized_vars, error_on_uninitialized_args) = match (version, cfg.read_u32::<LE>()?) {
+        (GameVersion::GameMaker8_0, x) => (x != 0, false),
+        (GameVersion::GameMaker8_1, x) => ((x & 1) != 0, (x & 2) != 0),
+    };
+
+    // game information tab, which doesn't make it into the exe's settings
+    let author = cfg.read_pas_string()?;
+    let version_string = cfg.read_pas_string()?;
+    skip_timestamp(&mut cfg)?;
+    let information = cfg.read_pas_string()?;
+    let (major, minor, release, build) =
+        (cfg.read_u32::<LE>()?, cfg.read_u32::<LE>()?, cfg.read_u32::<LE>()?, cfg.read_u32::<LE>()?);
+    let company = cfg.read_pas_string()?;
+    let product = cfg.read_pas_string()?;
+    let copyright = cfg.read_pas_string()?;
+    let description = cfg.read_pas_string()?;
+    skip_timestamp(&mut cfg)?;
+
+    log!(logger, " + Loaded settings structure");
+    log!(logger, "   - Author: {} (version '{}')", author, version_string);
+    log!(logger, "   - Information: {}", information);
+    log!(logger, "   - File version: {}.{}.{}.{}", major, minor, release, build);
+    log!(logger, "   - Company: {}, product: {}", company, product);
+    log!(logger, "   - Copyright: {}, description: {}", copyright, description);
+
+    Ok((
+        Settings {
+            fullscreen,
+            scaling,
+            interpolate_pixels,
+            clear_colour,
+            allow_resize,
+            window_on_top,
+            dont_draw_border,
+            dont_show_buttons,
+            display_cursor,
+            freeze_on_lose_focus,
+            disable_screensaver,
+            force_cpu_render,
+            set_resolution,
+            colour_depth,
+            resolution,
+            frequency,
+            vsync,
+            esc_close_game,
+            treat_close_as_esc,
+            f1_help_menu,
+            f4_fullscreen_toggle,
+            f5_save_f6_load,
+            f9_screenshot,
+            priority,
+            custom_load_image,
+            transparent,
+            translucency,
+            loading_bar,
+            backdata,
+            frontdata,
+            scale_progress_bar,
+            show_error_messages,
+            log_errors,
+            always_abort,
+            zero_uninitialized_vars,
+            error_on_uninitialized_args,
+            swap_creation_events: false,
+        },
+        ico_file_raw,
+        VersionInfo { major, minor, release, build, company, product, copyright, description },
+    ))
+}
+
+fn read_sound(mut reader: impl Read, strict: bool) -> Result<Sound, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(&mut reader)?;
+    check_ver(strict, reader.read_u32::<LE>()?, sound::VERSION)?;
+
+    let kind = SoundKind::from(reader.read_u32::<LE>()?);
+    let extension = reader.read_pas_string()?;
+    let source = reader.read_pas_string()?;
+    let data = if reader.read_u32::<LE>()? != 0 {
+        let len = reader.read_u32::<LE>()? as usize;
+        Some(reader.read_chunk(len)?.into_boxed_slice())
+    } else {
+        None
+    };
+
+    let effects = reader.read_u32::<LE>()?;
+    let fx = SoundFX {
+        chorus: (effects & 0b1) != 0,
+        echo: (effects & 0b10) != 0,
+        flanger: (effects & 0b100) != 0,
+        gargle: (effects & 0b1000) != 0,
+        reverb: (effects & 0b10000) != 0,
+    };
+    let volume = reader.read_f64::<LE>()?;
+    let pan = reader.read_f64::<LE>()?;
+    let preload = reader.read_u32::<LE>()? != 0;
+
+    Ok(Sound { name, source, extension, data, kind, volume, pan, preload, fx })
+}
+
+/// The collision mask options of a sprite, which the IDE turns into masks when building the game.
+struct MaskSettings {
+    /// 0 = precise, 1 = rectangle, 2 = disk, 3 = diamond
+    shape: u32,
+    alpha_tolerance: u32,
+    separate: bool,
+    /// 0 = automatic, 1 = full image, 2 = manual
+    bbox_kind: u32,
+    bbox_left: u32,
+    bbox_right: u32,
+    bbox_bottom: u32,
+    bbox_top: u32,
+}
+
+/// Builds the collision masks the IDE would have put in the exe.
+fn make_colliders(frames: &[Frame], mask: &MaskSettings) -> Vec<CollisionMap> {
+    let (width, height) = match frames.first() {
+        Some(frame) if frame.width != 0 && frame.height != 0 => (frame.width, frame.height),
+        _ => return Vec::new(),
+    };
+    let solid = |frame: &Frame, x: u32, y: u32| {
+        x < frame.width && y < frame.height && u32::from(frame.data[((y * frame.width + x) * 4 + 3) as usize]) > mask.alpha_tolerance
+    };
+
+    let mut precise_maps: Vec<Vec<bool>> = Vec::new();
+    if mask.separate {
+        for frame in frames {
+            precise_maps.push((0..width * height).map(|i| solid(frame, i % width, i / width)).collect());
+        }
+    } else {
+        precise_maps.push((0..width * height).map(|i| frames.iter().any(|f| solid(f, i % width, i / width))).collect());
+    }
+
+    precise_maps
+        .into_iter()
+        .map(|precise| {
+            let (left, right, top, bottom) = match mask.bbox_kind {
+                0 => {
+                    // an empty mask ends up with its bounds crossed over, so nothing is inside them
+                    let (mut left, mut right, mut top, mut bottom) = (width - 1, 0, height - 1, 0);
+                    for (i, _) in precise.iter().enumerate().filter(|(_, &p)| p) {
+                        let (x, y) = (i as u32 % width, i as u32 / width);
+                        left = left.min(x);
+                        right = right.max(x);
+                        top = top.min(y);
+                        bottom = bottom.max(y);
+                    }
+                    (left, right, top, bottom)
+                },
+                1 => (0, width - 1, 0, height - 1),
+                _ => (
+                    mask.bbox_left.min(width - 1),
+                    mask.bbox_right.min(width - 1),
+                    mask.bbox_top.min(height - 1),
+                    mask.bbox_bottom.min(height - 1),
+                ),
+            };
+            let (xcenter, ycenter) = (f64::from(left + right) / 2.0, f64::from(top + bottom) / 2.0);
+            let xrad = (f64::from(right) - f64::from(left)) / 2.0 + 0.5;
+            let yrad = (f64::from(bottom) - f64::from(top)) / 2.0 + 0.5;
+            let data = (0..width * height)
+                .map(|i| {
+                    let (x, y) = (i % width, i / width);
+                    if x < left || x > right || y < top || y > bottom {
+                        return false
+                    }
+                    let (dx, dy) = ((f64::from(x) - xcenter) / xrad, (f64::from(y) - ycenter) / yrad);
+                    match mask.shape {
+                        0 => precise[i as usize],
+                        2 => dx * dx + dy * dy < 1.0,
+                        3 => dx.abs() + dy.abs() < 1.0,
+                        _ => true,
+                    }
+                })
+                .collect();
+            CollisionMap { width, height, bbox_left: left, bbox_right: right, bbox_top: top, bbox_bottom: bottom, data }
+        })
+        .collect()
+}
+
+fn read_sprite(mut reader: impl Read, strict: bool) -> Result<Sprite, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(&mut reader)?;
+    check_ver(strict, reader.read_u32::<LE>()?, sprite::VERSION)?;
+
+    let origin_x = reader.read_i32::<LE>()?;
+    let origin_y = reader.read_i32::<LE>()?;
+    let frame_count = reader.read_u32::<LE>()?;
+    let frames = (0..frame_count)
+        .map(|_| {
+            check_ver(strict, reader.read_u32::<LE>()?, sprite::VERSION_FRAME)?;
+            let width = reader.read_u32::<LE>()?;
+            let height = reader.read_u32::<LE>()?;
+            let data = if width * height != 0 {
+                let len = reader.read_u32::<LE>()? as usize;
+                if len != width as usize * height as usize * 4 {
+                    return Err(Error::MalformedData)
+                }
+                reader.read_chunk(len)?.into_boxed_slice()
+            } else {
+                Box::default()
+            };
+            Ok(Frame { width, height, data })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let mask = MaskSettings {
+        shape: reader.read_u32::<LE>()?,
+        alpha_tolerance: reader.read_u32::<LE>()?,
+        separate: reader.read_u32::<LE>()? != 0,
+        bbox_kind: reader.read_u32::<LE>()?,
+        bbox_left: reader.read_u32::<LE>()?,
+        bbox_right: reader.read_u32::<LE>()?,
+        bbox_bottom: reader.read_u32::<LE>()?,
+        bbox_top: reader.read_u32::<LE>()?,
+    };
+    let colliders = make_colliders(&frames, &mask);
+    let per_frame_colliders = mask.separate && !colliders.is_empty();
+
+    Ok(Sprite { name, origin_x, origin_y, frames, colliders, per_frame_colliders })
+}
+
+fn read_background(mut reader: impl Read, strict: bool) -> Result<Background, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(&mut reader)?;
+    check_ver(strict, reader.read_u32::<LE>()?, background::VERSION1)?;
+
+    // tileset options, which are only used by the room editor
+    let mut tileset = [0u32; 7];
+    reader.read_u32_into::<LE>(&mut tileset)?;
+
+    check_ver(strict, reader.read_u32::<LE>()?, background::VERSION2)?;
+    let width = reader.read_u32::<LE>()?;
+    let height = reader.read_u32::<LE>()?;
+    let len = if width * height != 0 { reader.read_u32::<LE>()? as usize } else { 0 };
+    if len == 0 {
+        return Ok(Background { name, width: 0, height: 0, data: None })
+    }
+    if len != width as usize * height as usize * 4 {
+        return Err(Error::MalformedData)
+    }
+    let data = Some(reader.read_chunk(len)?.into_boxed_slice());
+    Ok(Background { name, width, height, data })
+}
+
+fn read_path(mut reader: impl Read, strict: bool) -> Result<Path, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(&mut reader)?;
+    check_ver(strict, reader.read_u32::<LE>()?, path::VERSION)?;
+
+    let connection = ConnectionKind::from(reader.read_u32::<LE>()?);
+    let closed = reader.read_u32::<LE>()? != 0;
+    let precision = reader.read_u32::<LE>()?;
+    let _editor_room = reader.read_i32::<LE>()?;
+    let _snap_x = reader.read_u32::<LE>()?;
+    let _snap_y = reader.read_u32::<LE>()?;
+    let point_count = reader.read_u32::<LE>()? as usize;
+    let points = (0..point_count)
+        .map(|_| Ok(Point { x: reader.read_f64::<LE>()?, y: reader.read_f64::<LE>()?, speed: reader.read_f64::<LE>()? }))
+        .collect::<io::Result<_>>()?;
+
+    Ok(Path { name, connection, precision, closed, points })
+}
+
+fn read_script(mut reader: impl Read, strict: bool) -> Result<Script, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(&mut reader)?;
+    check_ver(strict, reader.read_u32::<LE>()?, script::VERSION)?;
+    let source = reader.read_pas_string()?;
+    Ok(Script { name, source })
+}
+
+fn read_font(mut reader: impl Read, version: GameVersion, strict: bool) -> Result<Font, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(&mut reader)?;
+    check_ver(strict, reader.read_u32::<LE>()?, font::VERSION)?;
+
+    let sys_name = reader.read_pas_string()?;
+    let size = reader.read_u32::<LE>()?;
+    let bold = reader.read_u32::<LE>()? != 0;
+    let italic = reader.read_u32::<LE>()? != 0;
+    let mut range_start = reader.read_u32::<LE>()?;
+    let range_end = reader.read_u32::<LE>()?;
+    let (aa_level, charset) = match version {
+        GameVersion::GameMaker8_0 => (0, 0),
+        GameVersion::GameMaker8_1 => {
+            let aa_level = (range_start & 0xFF000000) >> 24;
+            let charset = (range_start & 0x00FF0000) >> 16;
+            range_start &= 0x0000FFFF;
+            (aa_level, charset)
+        },
+    };
+
+    Ok(Font {
+        name,
+        sys_name,
+        size,
+        bold,
+        italic,
+        range_start,
+        range_end,
+        charset,
+        aa_level,
+        dmap: Box::new([0; 0x600]),
+        map_width: 0,
+        map_height: 0,
+        pixel_map: Box::default(),
+    })
+}
+
+/// Reads the action list of an event or moment, after its index.
+fn read_actions(
+    reader: &mut impl Read,
+    list_version: u32,
+    version: GameVersion,
+    strict: bool,
+) -> Result<Vec<CodeAction>, Error> {
+    check_ver(strict, reader.read_u32::<LE>()?, list_version)?;
+    let action_count = reader.read_u32::<LE>()?;
+    (0..action_count).map(|_| CodeAction::deserialize_exe(&mut *reader, version, strict)).collect()
+}
+
+fn read_timeline(mut reader: impl Read, version: GameVersion, strict: bool) -> Result<Timeline, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(&mut reader)?;
+    check_ver(strict, reader.read_u32::<LE>()?, timeline::VERSION)?;
+
+    let moment_count = reader.read_u32::<LE>()?;
+    let moments = (0..moment_count)
+        .map(|_| {
+            let moment_index = reader.read_u32::<LE>()?;
+            Ok((moment_index, read_actions(&mut reader, timeline::VERSION_MOMENT, version, strict)?))
+        })
+        .collect::<Result<_, Error>>()?;
+
+    Ok(Timeline { name, moments })
+}
+
+fn read_object(mut reader: impl Read, version: GameVersion, strict: bool) -> Result<Object, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(&mut reader)?;
+    check_ver(strict, reader.read_u32::<LE>()?, object::VERSION)?;
+
+    let sprite_index = reader.read_i32::<LE>()?;
+    let solid = reader.read_u32::<LE>()? != 0;
+    let visible = reader.read_u32::<LE>()? != 0;
+    let depth = reader.read_i32::<LE>()?;
+    let persistent = reader.read_u32::<LE>()? != 0;
+    let parent_index = reader.read_i32::<LE>()?;
+    let mask_index = reader.read_i32::<LE>()?;
+
+    // same as in the exe, this is the index of the last event list
+    let event_list_count = reader.read_u32::<LE>()?;
+    if event_list_count != 11 {
+        return Err(Error::MalformedData)
+    }
+    let mut events = Vec::with_capacity((event_list_count + 1) as usize);
+    for _ in 0..=event_list_count {
+        let mut sub_event_list = Vec::new();
+        while let Ok(index) = u32::try_from(reader.read_i32::<LE>()?) {
+            sub_event_list.push((index, read_actions(&mut reader, object::VERSION_EVENT, version, strict)?));
+        }
+        events.push(sub_event_list);
+    }
+
+    Ok(Object { name, sprite_index, solid, visible, depth, persistent, parent_index, mask_index, events })
+}
+
+/// Splits the block of GM8.1 properties written by gm8decompiler off the start of some creation code,
+/// giving back its statements without semicolons and the code which followed it.
+fn split_compat(code: &PascalString) -> Option<(Vec<&str>, PascalString)> {
+    let body = code.0.strip_prefix(COMPAT_HEADER)?;
+    let end = body.windows(COMPAT_FOOTER.len()).position(|w| w == COMPAT_FOOTER)?;
+    let statements = std::str::from_utf8(&body[..end])
+        .ok()?
+        .split("\r\n")
+        .filter(|line| !line.is_empty())
+        .map(|line| line.strip_suffix(';'))
+        .collect::<Option<Vec<_>>>()?;
+    Some((statements, PascalString(body[end + COMPAT_FOOTER.len()..].into())))
+}
+
+/// Puts GM8.1 instance properties back from their creation code, returning whether there were any.
+fn restore_instance_compat(instance: &mut room::Instance) -> bool {
+    let apply = |instance: &mut room::Instance, statements: &[&str]| -> Option<()> {
+        for statement in statements {
+            let (variable, value) = statement.split_once('=')?;
+            match variable {
+                "image_xscale" => instance.xscale = value.parse().ok()?,
+                "image_yscale" => instance.yscale = value.parse().ok()?,
+                "image_blend" => instance.blend = value.parse().ok()?,
+                "image_angle" => instance.angle = value.parse().ok()?,
+                _ => return None,
+            }
+        }
+        Some(())
+    };
+    let original = std::mem::take(&mut instance.creation_code);
+    match split_compat(&original) {
+        Some((statements, code)) if apply(instance, &statements).is_some() => {
+            instance.creation_code = code;
+            true
+        },
+        _ => {
+            instance.creation_code = original;
+            false
+        },
+    }
+}
+
+/// Puts GM8.1 tile properties back from a room's creation code, returning whether there were any.
+fn restore_tile_compat(creation_code: &mut PascalString, tiles: &mut [room::Tile]) -> bool {
+    let apply = |tiles: &mut [room::Tile], statements: &[&str]| -> Option<()> {
+        for statement in statements {
+            let (function, args) = statement.strip_suffix(')')?.split_once('(')?;
+            let args = args.split(',').collect::<Vec<_>>();
+            let id = args.first()?.parse::<i32>().ok()?;
+            let tile = tiles.iter_mut().find(|t| t.id == id)?;
+            match (function, args.as_slice()) {
+                ("tile_set_scale", [_, xscale, yscale]) => {
+                    tile.xscale = xscale.parse().ok()?;
+                    tile.yscale = yscale.parse().ok()?;
+                },
+                ("tile_set_blend", [_, blend]) => tile.blend = blend.parse().ok()?,
+                _ => return None,
+            }
+        }
+        Some(())
+    };
+    match split_compat(creation_code) {
+        Some((statements, code)) if apply(tiles, &statements).is_some() => {
+            *creation_code = code;
+            true
+        },
+        _ => false,
+    }
+}
+
+fn read_room(mut reader: impl Read, version: GameVersion, strict: bool) -> Result<Room, Error> {
+    let name = reader.read_pas_string()?;
+    skip_timestamp(&mut reader)?;
+    check_ver(strict, reader.read_u32::<LE>()?, room::VERSION)?;
+
+    let caption = reader.read_pas_string()?;
+    let width = reader.read_u32::<LE>()?;
+    let height = reader.read_u32::<LE>()?;
+    let _snap_x = reader.read_u32::<LE>()?;
+    let _snap_y = reader.read_u32::<LE>()?;
+    let _isometric = reader.read_u32::<LE>()?;
+    let speed = reader.read_u32::<LE>()?;
+    let persistent = reader.read_u32::<LE>()? != 0;
+    let bg_colour = reader.read_u32::<LE>()?.into();
+    let (clear_screen, clear_region) = match (version, reader.read_u32::<LE>()?) {
+        (GameVersion::GameMaker8_0, x) => (x != 0, true),
+        (GameVersion::GameMaker8_1, x) => ((x & 0b01) != 0, (x & 0b10) == 0),
+    };
+    let mut creation_code = reader.read_pas_string()?;
+
+    let background_count = reader.read_u32::<LE>()? as usize;
+    let backgrounds = (0..background_count)
+        .map(|_| {
+            Ok(room::Background {
+                visible_on_start: reader.read_u32::<LE>()? != 0,
+                is_foreground: reader.read_u32::<LE>()? != 0,
+                source_bg: reader.read_i32::<LE>()?,
+                xoffset: reader.read_i32::<LE>()?,
+                yoffset: reader.read_i32::<LE>()?,
+                tile_horz: reader.read_u32::<LE>()? != 0,
+                tile_vert: reader.read_u32::<LE>()? != 0,
+                hspeed: reader.read_i32::<LE>()?,
+                vspeed: reader.read_i32::<LE>()?,
+                stretch: reader.read_u32::<LE>()? != 0,
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    let views_enabled = reader.read_u32::<LE>()? != 0;
+    let view_count = reader.read_u32::<LE>()? as usize;
+    let views = (0..view_count)
+        .map(|_| {
+            Ok(room::View {
+                visible: reader.read_u32::<LE>()? != 0,
+                source_x: reader.read_i32::<LE>()?,
+                source_y: reader.read_i32::<LE>()?,
+                source_w: reader.read_u32::<LE>()?,
+                source_h: reader.read_u32::<LE>()?,
+                port_x: reader.read_i32::<LE>()?,
+                port_y: reader.read_i32::<LE>()?,
+                port_w: reader.read_u32::<LE>()?,
+                port_h: reader.read_u32::<LE>()?,
+                following: ViewFollowData {
+                    hborder: reader.read_i32::<LE>()?,
+                    vborder: reader.read_i32::<LE>()?,
+                    hspeed: reader.read_i32::<LE>()?,
+                    vspeed: reader.read_i32::<LE>()?,
+                    target: reader.read_i32::<LE>()?,
+                },
+            })
+        })
+        .collect::<io::Result<_>>()?;
+
+    let instance_count = reader.read_u32::<LE>()? as usize;
+    let mut instances = (0..instance_count)
+        .map(|_| {
+            let instance = room::Instance {
+                x: reader.read_i32::<LE>()?,
+                y: reader.read_i32::<LE>()?,
+                object: reader.read_i32::<LE>()?,
+                id: reader.read_i32::<LE>()?,
+                creation_code: reader.read_pas_string()?,
+                xscale: 1.0,
+                yscale: 1.0,
+                blend: u32::MAX,
+                angle: 0.0,
+            };
+            let _locked = reader.read_u32::<LE>()?;
+            Ok(instance)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let tile_count = reader.read_u32::<LE>()? as usize;
+    let mut tiles = (0..tile_count)
+        .map(|_| {
+            let tile = room::Tile {
+                x: reader.read_i32::<LE>()?,
+                y: reader.read_i32::<LE>()?,
+                source_bg: reader.read_i32::<LE>()?,
+                tile_x: reader.read_u32::<LE>()?,
+                tile_y: reader.read_u32::<LE>()?,
+                width: reader.read_u32::<LE>()?,
+                height: reader.read_u32::<LE>()?,
+                depth: reader.read_i32::<LE>()?,
+                id: reader.read_i32::<LE>()?,
+                xscale: 1.0,
+                yscale: 1.0,
+                blend: u32::MAX,
+            };
+            let _locked = reader.read_u32::<LE>()?;
+            Ok(tile)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let editor_settings = room::EditorSettings {
+        remember: reader.read_u32::<LE>()? != 0,
+        width: reader.read_u32::<LE>()?,
+        height: reader.read_u32::<LE>()?,
+        show_grid: reader.read_u32::<LE>()? != 0,
+        show_objects: reader.read_u32::<LE>()? != 0,
+        show_tiles: reader.read_u32::<LE>()? != 0,
+        show_backgrounds: reader.read_u32::<LE>()? != 0,
+        show_foregrounds: reader.read_u32::<LE>()? != 0,
+        show_views: reader.read_u32::<LE>()? != 0,
+        delete_underlying_objects: reader.read_u32::<LE>()? != 0,
+        delete_underlying_tiles: reader.read_u32::<LE>()? != 0,
+        tab: reader.read_u32::<LE>()?,
+        scroll_x: reader.read_u32::<LE>()?,
+        scroll_y: reader.read_u32::<LE>()?,
+    };
+
+    let mut uses_810_features = restore_tile_compat(&mut creation_code, &mut tiles);
+    for instance in &mut instances {
+        uses_810_features |= restore_instance_compat(instance);
+    }
+    let uses_811_features = instances.iter().any(|i| i.angle != 0.0);
+
+    Ok(Room {
+        name,
+        caption,
+        width,
+        height,
+        speed,
+        persistent,
+        bg_colour,
+        clear_screen,
+        clear_region,
+        creation_code,
+        backgrounds,
+        views_enabled,
+        views,
+        instances,
+        tiles,
+        uses_810_features,
+        uses_811_features,
+        editor_settings: Some(editor_settings),
+    })
+}
+
+/// Reads a resource tree node and everything under it.
+fn read_tree_node<F>(src: &mut io::Cursor<&[u8]>, logger: Option<F>, depth: usize) -> io::Result<tree::Node>
+where
+    F: Copy + Fn(&str),
+{
+    let kind = NodeKind::from(src.read_u32::<LE>()?);
+    let group = src.read_u32::<LE>()?;
+    let index = src.read_u32::<LE>()?;
+    let name = src.read_pas_string()?;
+    let child_count = src.read_u32::<LE>()? as usize;
+    log!(logger, "   {}- {}", "  ".repeat(depth), name);
+    let children = (0..child_count).map(|_| read_tree_node(src, logger, depth + 1)).collect::<io::Result<_>>()?;
+    Ok(tree::Node { kind, group, index, name, children })
+}
+
+/// Reads a GameMaker 8.0 or 8.1 project file (`.gmk` or `.gm81`) into the same structure `from_exe` gives.
+///
+/// Fonts come back without glyphs (an empty `pixel_map`) and extensions without files, as the project only
+/// references them. Whoever uses the result has to render the one and find the other themselves.
+pub fn from_gmk<I, F>(gmk: I, logger: Option<F>, strict: bool, multithread: bool) -> Result<GameAssets, ReaderError>
+where
+    F: Copy + Fn(&str),
+    I: AsRef<[u8]>,
+{
+    let mut gmk = io::Cursor::new(gmk.as_ref());
+
+    if gmk.read_u32::<LE>()? != MAGIC {
+        return Err(ReaderError::InvalidGmkHeader)
+    }
+    let game_ver = match gmk.read_u32::<LE>()? {
+        800 => GameVersion::GameMaker8_0,
+        810 => GameVersion::GameMaker8_1,
+        _ => return Err(ReaderError::UnknownFormat),
+    };
+    log!(logger, "Reading {:?} project file", game_ver);
+
+    let game_id = gmk.read_u32::<LE>()?;
+    let guid = [gmk.read_u32::<LE>()?, gmk.read_u32::<LE>()?, gmk.read_u32::<LE>()?, gmk.read_u32::<LE>()?];
+    log!(logger, "Game ID: {}", game_id);
+
+    // Game Settings
+    check_ver(strict, gmk.read_u32::<LE>()?, 800)?;
+    log!(logger, "Reading settings chunk...");
+    let (settings, ico_file_raw, version_info) = read_settings(read_block(&mut gmk)?, game_ver, logger)?;
+
+    // Triggers, which are the same as in the exe
+    check_ver(strict, gmk.read_u32::<LE>()?, 800)?;
+    let triggers: AssetList<Trigger> =
+        get_assets(&mut gmk, "triggers", |data| Trigger::deserialize_exe(data, game_ver, strict), multithread, None)?;
+    skip_timestamp(&mut gmk)?;
+    for trigger in triggers.iter().flatten() {
+        log!(logger, " + Added trigger '{}'", trigger.name);
+    }
+
+    // Constants
+    check_ver(strict, gmk.read_u32::<LE>()?, 800)?;
+    let constant_count = gmk.read_u32::<LE>()? as usize;
+    let mut constants = Vec::with_capacity(constant_count);
+    for _ in 0..constant_count {
+        let name = gmk.read_pas_string()?;
+        let expression = gmk.read_pas_string()?;
+        log!(logger, " + Added constant '{}' (expression: {})", name, expression);
+        constants.push(Constant { name, expression });
+    }
+    skip_timestamp(&mut gmk)?;
+
+    macro_rules! read_assets {
+        ($kind: literal, $deserializer: expr) => {{
+            check_ver(strict, gmk.read_u32::<LE>()?, 800)?;
+            let assets = get_assets(&mut gmk, concat!($kind, "s"), $deserializer, multithread, None)?;
+            for asset in assets.iter().flatten() {
+                log!(logger, concat!(" + Added ", $kind, " '{}'"), asset.name);
+            }
+            assets
+        }};
+    }
+
+    let sounds = read_assets!("sound", |data| read_sound(data, strict));
+    let sprites = read_assets!("sprite", |data| read_sprite(data, strict));
+    let backgrounds = read_assets!("background", |data| read_background(data, strict));
+    let paths = read_assets!("path", |data| read_path(data, strict));
+    let scripts = read_assets!("script", |data| read_script(data, strict));
+    let fonts = read_assets!("font", |data| read_font(data, game_ver, strict));
+    let timelines = read_assets!("timeline", |data| read_timeline(data, game_ver, strict));
+    let objects = read_assets!("object", |data| read_object(data, game_ver, strict));
+    let rooms = read_assets!("room", |data| read_room(data, game_ver, strict));
+
+    // Room editor metadata
+    let last_instance_id = gmk.read_i32::<LE>()?;
+    let last_tile_id = gmk.read_i32::<LE>()?;
+    log!(logger, "Last instance ID: {}, last tile ID: {}", last_instance_id, last_tile_id);
+
+    // Included Files, which are the same as in the exe apart from a timestamp
+    check_ver(strict, gmk.read_u32::<LE>()?, 800)?;
+    let included_files = get_asset_refs(&mut gmk)?
+        .iter()
+        .map(|(_, chunk)| {
+            let mut data = inflate(chunk);
+            skip_timestamp(&mut data)?;
+            IncludedFile::deserialize_exe(data, game_ver, strict).map_err(ReaderError::from)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    for file in &included_files {
+        log!(logger, " + Added included file '{}'", file.file_name);
+    }
+
+    // Extension packages, of which the project only names the ones it uses
+    check_ver(strict, gmk.read_u32::<LE>()?, 700)?;
+    let extension_count = gmk.read_u32::<LE>()? as usize;
+    let mut extensions = Vec::with_capacity(extension_count);
+    for _ in 0..extension_count {
+        let name = gmk.read_pas_string()?;
+        log!(logger, " + Added extension '{}' (not embedded in project files)", name);
+        extensions.push(Extension { name, folder_name: PascalString::default(), files: Vec::new() });
+    }
+
+    // Help Dialog
+    check_ver(strict, gmk.read_u32::<LE>()?, 800)?;
+    let help_dialog = {
+        let mut data = read_block(&mut gmk)?;
+        let bg_colour = data.read_u32::<LE>()?.into();
+        let new_window = data.read_u32::<LE>()? != 0;
+        let caption = data.read_pas_string()?;
+        let left = data.read_i32::<LE>()?;
+        let top = data.read_i32::<LE>()?;
+        let width = data.read_u32::<LE>()?;
+        let height = data.read_u32::<LE>()?;
+        let border = data.read_u32::<LE>()? != 0;
+        let resizable = data.read_u32::<LE>()? != 0;
+        let window_on_top = data.read_u32::<LE>()? != 0;
+        let freeze_game = data.read_u32::<LE>()? != 0;
+        skip_timestamp(&mut data)?;
+        let info = data.read_pas_string()?;
+        let hdg = GameHelpDialog {
+            bg_colour,
+            new_window,
+            caption,
+            left,
+            top,
+            width,
+            height,
+            border,
+            resizable,
+            window_on_top,
+            freeze_game,
+            info,
+        };
+        log!(logger, " + Help Dialog: {:#?}", hdg);
+        hdg
+    };
+
+    // Action library initialization code
+    check_ver(strict, gmk.read_u32::<LE>()?, 500)?;
+    let str_count = gmk.read_u32::<LE>()? as usize;
+    let mut library_init_strings = Vec::with_capacity(str_count);
+    for _ in 0..str_count {
+        library_init_strings.push(gmk.read_pas_string()?);
+    }
+    log!(logger, " + Read {} action library initialization strings", str_count);
+
+    // Room Order
+    check_ver(strict, gmk.read_u32::<LE>()?, 700)?;
+    let ro_count = gmk.read_u32::<LE>()? as usize;
+    let mut room_order = Vec::with_capacity(ro_count);
+    for _ in 0..ro_count {
+        room_order.push(gmk.read_i32::<LE>()?);
+    }
+    log!(logger, " + Added Room Order LUT: {:?}", room_order);
+
+    // Resource tree: sprites, sounds, backgrounds, paths, scripts, fonts, timelines, objects, rooms,
+    // game information, global game settings and extension packages.
+    log!(logger, "Resource tree:");
+    let resource_tree = (0..12).map(|_| read_tree_node(&mut gmk, logger, 0)).collect::<io::Result<Vec<_>>>()?;
+    log!(logger, " + Read resource tree");
+
+    Ok(GameAssets {
+        extensions,
+        sprites,
+        sounds,
+        backgrounds,
+        paths,
+        scripts,
+        fonts,
+        timelines,
+        objects,
+        triggers,
+        constants,
+        rooms,
+        included_files,
+
+        dx_dll: Vec::new(),
+        ico_file_raw,
+        version_info: Some(version_info),
+        detection: None,
+        warnings: Vec::new(),
+        version: game_ver,
+        help_dialog,
+        last_instance_id,
+        last_tile_id,
+        library_init_strings,
+        room_order,
+        resource_tree,
+
+        settings,
+        game_id,
+        guid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compat_block_is_restored() {
+        let mut instance = room::Instance {
+            x: 0,
+            y: 0,
+            object: 0,
+            id: 100001,
+            creation_code: "/* gm8.2 compat */\r\nimage_xscale=2.5;\r\nimage_blend=255;\r\n/****************/\r\n\r\nhp = 3;"
+                .into(),
+            xscale: 1.0,
+            yscale: 1.0,
+            blend: u32::MAX,
+            angle: 0.0,
+        };
+        assert!(restore_instance_compat(&mut instance));
+        assert_eq!(instance.xscale, 2.5);
+        assert_eq!(instance.yscale, 1.0);
+        assert_eq!(instance.blend, 255);
+        assert_eq!(&*instance.creation_code.0, b"hp = 3;");
+
+        // anything else is left as it was written
+        instance.creation_code = "/* gm8.2 compat */\r\nhp = 3;\r\n/****************/\r\n\r\n".into();
+        assert!(!restore_instance_compat(&mut instance));
+        assert!(instance.creation_code.0.starts_with(COMPAT_HEADER));
+    }
+
+    #[test]
+    fn masks_follow_shape_and_bbox() {
+        // 4x2, opaque on the right half of the top row only
+        let mut data = vec![0u8; 4 * 2 * 4];
+        data[2 * 4 + 3] = 255;
+        data[3 * 4 + 3] = 255;
+        let frames = [Frame { width: 4, height: 2, data: data.into_boxed_slice() }];
+        let mut mask = MaskSettings {
+            shape: 0,
+            alpha_tolerance: 0,
+            separate: false,
+            bbox_kind: 0,
+            bbox_left: 0,
+            bbox_right: 0,
+            bbox_bottom: 0,
+            bbox_top: 0,
+        };
+
+        let precise = make_colliders(&frames, &mask);
+        assert_eq!(precise.len(), 1);
+        let map = &precise[0];
+        assert_eq!((map.bbox_left, map.bbox_right, map.bbox_top, map.bbox_bottom), (2, 3, 0, 0));
+        assert_eq!(&*map.data, &[false, false, true, true, false, false, false, false]);
+
+        mask.shape = 1;
+        mask.bbox_kind = 1;
+        let rectangle = make_colliders(&frames, &mask);
+        assert!(rectangle[0].data.iter().all(|&p| p));
+    }
+}
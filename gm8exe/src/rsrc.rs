@@ -1,5 +1,6 @@
-use crate::reader::PESection;
+use crate::{asset::PascalString, reader::PESection};
 use byteorder::{ReadBytesExt, WriteBytesExt, LE};
+use encoding_rs::Encoding;
 use std::io::{self, Seek, SeekFrom};
 
 /*
@@ -12,6 +13,20 @@ pub struct WindowsIcon {
 }
 */
 
+/// The version information GameMaker writes to an exe from the Game Information tab,
+/// which Windows shows in the file's properties.
+#[derive(Debug, Default)]
+pub struct VersionInfo {
+    pub major: u32,
+    pub minor: u32,
+    pub release: u32,
+    pub build: u32,
+    pub company: PascalString,
+    pub product: PascalString,
+    pub copyright: PascalString,
+    pub description: PascalString,
+}
+
 /// Finds the icon group from the exe file which will be used for the window icon.
 /// Returns an entire rebuilt .ico file, or None if there isn't one associated with this exe.
 pub fn find_icons(data: &mut io::Cursor<&mut [u8]>, pe_sections: &[PESection]) -> io::Result<Option<Vec<u8>>> {
@@ -124,6 +139,142 @@ pub fn find_icons(data: &mut io::Cursor<&mut [u8]>, pe_sections: &[PESection]) -
     Ok(None)
 }
 
+/// Finds the VS_VERSIONINFO resource in the exe file and reads the fields GameMaker sets from it.
+/// Returns None if the exe doesn't have one, or it's malformed.
+pub fn find_version_info(
+    data: &mut io::Cursor<&mut [u8]>,
+    pe_sections: &[PESection],
+) -> io::Result<Option<VersionInfo>> {
+    // top level header
+    let rsrc_base = data.position();
+    data.seek(SeekFrom::Current(12))?;
+    let name_count = data.read_u16::<LE>()?;
+    let id_count = data.read_u16::<LE>()?;
+    data.seek(SeekFrom::Current((name_count as i64) * 8))?;
+
+    for _ in 0..id_count {
+        let id = data.read_u32::<LE>()?;
+        let offset = data.read_u32::<LE>()? & 0x7FFFFFFF; // high bit is 1
+        if id != 16 {
+            // 16 = RT_VERSION
+            continue
+        }
+
+        // There's only ever one version resource, so take the first entry in the name and language layers
+        let mut offset = offset;
+        for _ in 0..2 {
+            data.set_position((offset as u64) + rsrc_base + 12);
+            if data.read_u16::<LE>()? + data.read_u16::<LE>()? == 0 {
+                return Ok(None)
+            }
+            data.seek(SeekFrom::Current(4))?;
+            offset = data.read_u32::<LE>()? & 0x7FFFFFFF;
+        }
+        data.set_position((offset as u64) + rsrc_base);
+        let rva = data.read_u32::<LE>()?;
+        let size = data.read_u32::<LE>()?;
+
+        return Ok(extract_virtual_bytes(data, pe_sections, rva, size as usize)?.and_then(|v| parse_version_info(&v)))
+    }
+
+    Ok(None)
+}
+
+/// A node in a version resource: a key, then a value, then any child nodes.
+struct VersionBlock<'a> {
+    key: Vec<u16>,
+    value: &'a [u8],
+    children: &'a [u8],
+}
+
+/// Reads the nodes in a list, each padded to 4 bytes.
+fn version_blocks(mut data: &[u8]) -> Vec<VersionBlock<'_>> {
+    let align = |x: usize| (x + 3) & !3;
+    let mut blocks = Vec::new();
+    while data.len() >= 6 {
+        let length = usize::from(u16::from_le_bytes([data[0], data[1]]));
+        let value_length = usize::from(u16::from_le_bytes([data[2], data[3]]));
+        let is_text = u16::from_le_bytes([data[4], data[5]]) == 1;
+        let block = match data.get(..length) {
+            Some(block) if length >= 6 => block,
+            _ => break,
+        };
+        let key = block[6..].chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).take_while(|c| *c != 0);
+        let key = key.collect::<Vec<_>>();
+        let value_start = align(6 + (key.len() + 1) * 2).min(length);
+        // text values are measured in characters, but some tools write their length in bytes, hence the clamping
+        let value_end = (value_start + if is_text { value_length * 2 } else { value_length }).min(length);
+        let children_start = align(value_end).min(length);
+        blocks.push(VersionBlock { key, value: &block[value_start..value_end], children: &block[children_start..] });
+        data = data.get(align(length)..).unwrap_or_default();
+    }
+    blocks
+}
+
+/// The ANSI code page a string table's key names, such as 04E4 (1252) in "040904E4".
+/// GameMaker writes the code page the game was made in there. Anything else is taken as Windows-1252.
+fn table_encoding(key: &[u16]) -> &'static Encoding {
+    let code_page = String::from_utf16_lossy(key).get(4..8).and_then(|cp| u16::from_str_radix(cp, 16).ok());
+    match code_page {
+        Some(874) => encoding_rs::WINDOWS_874,
+        Some(932) => encoding_rs::SHIFT_JIS,
+        Some(936) => encoding_rs::GBK,
+        Some(949) => encoding_rs::EUC_KR,
+        Some(950) => encoding_rs::BIG5,
+        Some(1250) => encoding_rs::WINDOWS_1250,
+        Some(1251) => encoding_rs::WINDOWS_1251,
+        Some(1253) => encoding_rs::WINDOWS_1253,
+        Some(1254) => encoding_rs::WINDOWS_1254,
+        Some(1255) => encoding_rs::WINDOWS_1255,
+        Some(1256) => encoding_rs::WINDOWS_1256,
+        Some(1257) => encoding_rs::WINDOWS_1257,
+        Some(1258) => encoding_rs::WINDOWS_1258,
+        _ => encoding_rs::WINDOWS_1252,
+    }
+}
+
+/// Converts UTF-16 text to a string in the given code page, like GameMaker's own strings.
+/// Characters the code page doesn't have become '?'.
+fn narrow(text: &[u8], encoding: &'static Encoding) -> PascalString {
+    let units = text.chunks_exact(2).map(|c| u16::from_le_bytes([c[0], c[1]])).take_while(|c| *c != 0);
+    let (mut out, mut buf) = (Vec::new(), [0; 4]);
+    for c in char::decode_utf16(units).map(|c| c.unwrap_or('?')) {
+        let (bytes, _, unmappable) = encoding.encode(c.encode_utf8(&mut buf));
+        out.extend_from_slice(if unmappable { b"?" } else { &bytes });
+    }
+    PascalString(out.into_boxed_slice())
+}
+
+fn parse_version_info(data: &[u8]) -> Option<VersionInfo> {
+    let root = version_blocks(data).into_iter().next()?;
+    let fixed = root.value;
+    // VS_FIXEDFILEINFO starts with a signature, then the struct version, then the file version
+    if fixed.len() < 16 || fixed[..4] != [0xBD, 0x04, 0xEF, 0xFE] {
+        return None
+    }
+    let word = |i: usize| u32::from(u16::from_le_bytes([fixed[i], fixed[i + 1]]));
+    let mut info =
+        VersionInfo { major: word(10), minor: word(8), release: word(14), build: word(12), ..Default::default() };
+
+    let is = |key: &[u16], name: &str| key.iter().copied().eq(name.encode_utf16());
+    for file_info in version_blocks(root.children).into_iter().filter(|b| is(&b.key, "StringFileInfo")) {
+        for table in version_blocks(file_info.children) {
+            let encoding = table_encoding(&table.key);
+            for string in version_blocks(table.children) {
+                let field = match &string.key {
+                    k if is(k, "CompanyName") => &mut info.company,
+                    k if is(k, "ProductName") => &mut info.product,
+                    k if is(k, "LegalCopyright") => &mut info.copyright,
+                    k if is(k, "FileDescription") => &mut info.description,
+                    _ => continue,
+                };
+                *field = narrow(string.value, encoding);
+            }
+        }
+    }
+    Some(info)
+}
+
 /// Extracts some bytes from the file from their location in the initialized exe's memory
 fn extract_virtual_bytes(
     data: &mut io::Cursor<&mut [u8]>,
@@ -144,3 +295,74 @@ fn extract_virtual_bytes(
 
     Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a version resource node, padded as Windows does.
+    fn block(key: &str, is_text: bool, value: &[u8], children: &[Vec<u8>]) -> Vec<u8> {
+        let pad = |v: &mut Vec<u8>| v.resize((v.len() + 3) & !3, 0);
+        let value_length = if is_text { value.len() / 2 } else { value.len() };
+        let mut data = vec![0, 0];
+        data.extend_from_slice(&(value_length as u16).to_le_bytes());
+        data.extend_from_slice(&u16::from(is_text).to_le_bytes());
+        data.extend(key.encode_utf16().chain([0]).flat_map(u16::to_le_bytes));
+        pad(&mut data);
+        data.extend_from_slice(value);
+        for child in children {
+            pad(&mut data);
+            data.extend_from_slice(child);
+        }
+        let len = data.len() as u16;
+        data[..2].copy_from_slice(&len.to_le_bytes());
+        data
+    }
+
+    fn text(key: &str, value: &str) -> Vec<u8> {
+        block(key, true, &value.encode_utf16().chain([0]).flat_map(u16::to_le_bytes).collect::<Vec<_>>(), &[])
+    }
+
+    #[test]
+    fn version_info() {
+        let mut fixed = vec![0xBD, 0x04, 0xEF, 0xFE, 0, 0, 1, 0];
+        fixed.extend_from_slice(&[2, 0, 1, 0, 4, 0, 3, 0]); // 1.2.3.4
+        fixed.resize(52, 0);
+        let strings = block("040904E4", true, &[], &[
+            text("CompanyName", "Company"),
+            text("FileDescription", "Caf\u{e9} \u{2603}"),
+            text("LegalCopyright", "(c)"),
+            text("ProductName", "Game"),
+        ]);
+        let data = block("VS_VERSION_INFO", false, &fixed, &[
+            block("StringFileInfo", true, &[], &[strings]),
+            block("VarFileInfo", true, &[], &[block("Translation", false, &[9, 4, 0xE4, 4], &[])]),
+        ]);
+
+        let info = parse_version_info(&data).unwrap();
+        assert_eq!((info.major, info.minor, info.release, info.build), (1, 2, 3, 4));
+        assert_eq!(&*info.company.0, b"Company");
+        assert_eq!(&*info.product.0, b"Game");
+        assert_eq!(&*info.copyright.0, b"(c)");
+        assert_eq!(&*info.description.0, b"Caf\xE9 ?");
+        assert!(parse_version_info(&data[..40]).is_none());
+    }
+
+    #[test]
+    fn code_pages() {
+        let narrow_in = |key: &str, text: &str| {
+            let key = key.encode_utf16().collect::<Vec<_>>();
+            let text = text.encode_utf16().flat_map(u16::to_le_bytes).collect::<Vec<_>>();
+            narrow(&text, table_encoding(&key)).0
+        };
+        assert_eq!(&*narrow_in("040904E4", "\u{20ac}5 \u{2018}ok\u{2019}"), b"\x805 \x91ok\x92");
+        assert_eq!(&*narrow_in("041103A4", "\u{30b2}\u{30fc}\u{30e0}"), b"\x83\x51\x81\x5b\x83\x80");
+        assert_eq!(&*narrow_in("041904E3", "\u{418}\u{433}\u{440}\u{430} \u{e9}"), b"\xc8\xe3\xf0\xe0 ?");
+        // Unicode and unknown code pages fall back to Windows-1252
+        assert_eq!(&*narrow_in("040904B0", "caf\u{e9}"), b"caf\xe9");
+        assert_eq!(&*narrow_in("bad", "caf\u{e9}"), b"caf\xe9");
+        // an unpaired surrogate is as unrepresentable as anything else
+        let lone = [0x00, 0xD8, b'a', 0];
+        assert_eq!(&*narrow(&lone, encoding_rs::WINDOWS_1252).0, b"?a");
+    }
+}
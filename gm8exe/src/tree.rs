@@ -0,0 +1,44 @@
+use crate::asset::PascalString;
+
+/// An entry in a project's resource tree, the folder view the IDE shows its assets in.
+/// Only project files have one - it's empty for games read from an exe.
+pub struct Node {
+    pub kind: NodeKind,
+
+    /// The kind of resource this node holds or is a folder for:
+    /// 1 objects, 2 sprites, 3 sounds, 4 rooms, 6 backgrounds, 7 scripts, 8 paths, 9 fonts,
+    /// 10 game information, 11 global game settings, 12 timelines, 13 extension packages.
+    pub group: u32,
+
+    /// The asset's index, for resources.
+    pub index: u32,
+
+    /// The name shown in the tree. For resources this is the asset name as it was when the project was saved.
+    pub name: PascalString,
+
+    pub children: Vec<Node>,
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum NodeKind {
+    /// One of the twelve folders at the top of the tree, which can't be renamed or removed.
+    Root = 1,
+
+    /// A folder made by the user.
+    Group = 2,
+
+    /// An asset, or one of the game information, settings and extension package entries.
+    Resource = 3,
+}
+
+impl From<u32> for NodeKind {
+    fn from(n: u32) -> NodeKind {
+        match n {
+            1 => NodeKind::Root,
+            2 => NodeKind::Group,
+            3 => NodeKind::Resource,
+
+            _ => NodeKind::Group,
+        }
+    }
+}
@@ -0,0 +1,619 @@
+use crate::{
+    asset::{Asset, WritePascalString},
+    gamedata::{gm80, gm81},
+    reader::{self, ReaderError},
+    settings::{GameHelpDialog, Settings},
+    AssetList, GameAssets, GameVersion,
+};
+use byteorder::{WriteBytesExt, LE};
+use flate2::{write::ZlibEncoder, Compression};
+use rayon::prelude::{IntoParallelRefIterator, ParallelIterator};
+use std::{
+    fmt::{self, Display},
+    io::{self, Write},
+};
+
+/// The name GameMaker gives the embedded DirectX DLL.
+const DX_DLL_NAME: &str = "D3DX8.dll";
+
+#[derive(Debug)]
+pub enum WriterError {
+    IO(io::Error),
+    UnknownRunner,
+    RunnerVersionMismatch,
+    VerificationFailed(ReaderError),
+    VerificationMismatch,
+}
+impl std::error::Error for WriterError {}
+impl Display for WriterError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", match self {
+            WriterError::IO(err) => format!("io error: {}", err),
+            WriterError::UnknownRunner => "unknown runner, could not find its loading sequence".into(),
+            WriterError::RunnerVersionMismatch => "runner is for a different GameMaker version than the game".into(),
+            WriterError::VerificationFailed(err) => format!("written exe could not be read back: {}", err),
+            WriterError::VerificationMismatch => "written exe reads back differently to what was written".into(),
+        })
+    }
+}
+
+impl From<io::Error> for WriterError {
+    fn from(err: io::Error) -> Self {
+        WriterError::IO(err)
+    }
+}
+
+/// Helper function for deflating data into a zlib block, at the level GM8 uses.
+pub(crate) fn deflate<I>(data: &I) -> io::Result<Vec<u8>>
+where
+    I: AsRef<[u8]> + ?Sized,
+{
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data.as_ref())?;
+    encoder.finish()
+}
+
+/// Where a runner looks for its gamedata, read from its loading sequence like `gamedata::find` does.
+struct Runner {
+    version: GameVersion,
+    header_start: usize,
+    magic: u32,
+    header_version: u32,
+}
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn identify_runner(runner: &[u8]) -> Option<Runner> {
+    if runner.get(0..2) != Some(b"MZ") {
+        return None
+    }
+
+    if runner.len() >= 0x144AC0 + 4 && runner[0xA49BE..0xA49C6] == [0x8B, 0x45, 0xF4, 0xE8, 0x2A, 0xBD, 0xFD, 0xFF] {
+        // a patched out check accepts anything, so write what GameMaker would
+        let magic = if runner[0xA49C6] == 0x3D { read_u32(runner, 0xA49C7) } else { 1234321 };
+        let header_version = if runner[0xA49E2..0xA49EA] == [0x8B, 0xC6, 0xE8, 0x07, 0xBD, 0xFD, 0xFF, 0x3D] {
+            read_u32(runner, 0xA49EA)
+        } else {
+            800
+        };
+        return Some(Runner {
+            version: GameVersion::GameMaker8_0,
+            header_start: read_u32(runner, 0x144AC0) as usize,
+            magic,
+            header_version,
+        })
+    }
+
+    if runner.len() >= 0x226D8A && runner[0x226CF3..0x226CFB] == [0xE8, 0x80, 0xF2, 0xDD, 0xFF, 0xC7, 0x45, 0xF0] {
+        // SUDALV's re-encryption takes its masks from the runner, which isn't supported
+        if runner[0x10BB83..0x10BB8B] == [0x8B, 0x02, 0xC1, 0xE0, 0x10, 0x8B, 0x11, 0x81] {
+            return None
+        }
+        let magic =
+            if runner[0x226D7C..0x226D7F] == [0x81, 0x7D, 0xEC] { read_u32(runner, 0x226D7F) } else { 0xF7140067 };
+        return Some(Runner {
+            version: GameVersion::GameMaker8_1,
+            header_start: read_u32(runner, 0x226CFB) as usize,
+            magic,
+            header_version: 0,
+        })
+    }
+
+    None
+}
+
+/// Writes a list of assets as zlib blocks, with deleted assets as a deflated `00 00 00 00`.
+fn write_assets<T>(writer: &mut impl Write, assets: &AssetList<T>, version: GameVersion) -> io::Result<()>
+where
+    T: Asset + Sync,
+{
+    let blocks = assets
+        .par_iter()
+        .map(|asset| {
+            let mut data = Vec::new();
+            match asset {
+                Some(asset) => {
+                    data.write_u32::<LE>(1)?;
+                    asset.serialize_exe(&mut data, version)?;
+                },
+                None => data.write_u32::<LE>(0)?,
+            }
+            deflate(&data)
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    writer.write_u32::<LE>(blocks.len() as u32)?;
+    for block in blocks {
+        writer.write_u32::<LE>(block.len() as u32)?;
+        writer.write_all(&block)?;
+    }
+    Ok(())
+}
+
+fn write_settings(writer: &mut impl Write, settings: &Settings, version: GameVersion) -> io::Result<()> {
+    fn write_data_maybe(writer: &mut impl Write, data: &Option<Box<[u8]>>) -> io::Result<()> {
+        match data {
+            Some(data) => {
+                writer.write_u32::<LE>(1)?;
+                writer.write_u32::<LE>(data.len() as u32)?;
+                writer.write_all(data)
+            },
+            None => writer.write_u32::<LE>(0),
+        }
+    }
+
+    let mut cfg = Vec::new();
+    cfg.write_u32::<LE>(settings.fullscreen.into())?;
+    cfg.write_u32::<LE>(settings.interpolate_pixels.into())?;
+    cfg.write_u32::<LE>(settings.dont_draw_border.into())?;
+    cfg.write_u32::<LE>(settings.display_cursor.into())?;
+    cfg.write_i32::<LE>(settings.scaling)?;
+    cfg.write_u32::<LE>(settings.allow_resize.into())?;
+    cfg.write_u32::<LE>(settings.window_on_top.into())?;
+    cfg.write_u32::<LE>(settings.clear_colour)?;
+    cfg.write_u32::<LE>(settings.set_resolution.into())?;
+    cfg.write_u32::<LE>(settings.colour_depth)?;
+    cfg.write_u32::<LE>(settings.resolution)?;
+    cfg.write_u32::<LE>(settings.frequency)?;
+    cfg.write_u32::<LE>(settings.dont_show_buttons.into())?;
+    cfg.write_u32::<LE>(match version {
+        GameVersion::GameMaker8_0 => settings.vsync.into(),
+        GameVersion::GameMaker8_1 => u32::from(settings.vsync) | (u32::from(settings.force_cpu_render) << 7),
+    })?;
+    cfg.write_u32::<LE>(settings.disable_screensaver.into())?;
+    cfg.write_u32::<LE>(settings.f4_fullscreen_toggle.into())?;
+    cfg.write_u32::<LE>(settings.f1_help_menu.into())?;
+    cfg.write_u32::<LE>(settings.esc_close_game.into())?;
+    cfg.write_u32::<LE>(settings.f5_save_f6_load.into())?;
+    cfg.write_u32::<LE>(settings.f9_screenshot.into())?;
+    cfg.write_u32::<LE>(settings.treat_close_as_esc.into())?;
+    cfg.write_u32::<LE>(settings.priority)?;
+    cfg.write_u32::<LE>(settings.freeze_on_lose_focus.into())?;
+    cfg.write_u32::<LE>(settings.loading_bar)?;
+    if settings.loading_bar != 0 {
+        write_data_maybe(&mut cfg, &settings.backdata)?;
+        write_data_maybe(&mut cfg, &settings.frontdata)?;
+    }
+    write_data_maybe(&mut cfg, &settings.custom_load_image)?;
+    cfg.write_u32::<LE>(settings.transparent.into())?;
+    cfg.write_u32::<LE>(settings.translucency)?;
+    cfg.write_u32::<LE>(settings.scale_progress_bar.into())?;
+    cfg.write_u32::<LE>(settings.show_error_messages.into())?;
+    cfg.write_u32::<LE>(settings.log_errors.into())?;
+    cfg.write_u32::<LE>(settings.always_abort.into())?;
+    cfg.write_u32::<LE>(match version {
+        GameVersion::GameMaker8_0 => settings.zero_uninitialized_vars.into(),
+        GameVersion::GameMaker8_1 => {
+            u32::from(settings.zero_uninitialized_vars) | (u32::from(settings.error_on_uninitialized_args) << 1)
+        },
+    })?;
+    // only later runners know about these, so leave them out unless they're needed
+    if settings.swap_creation_events {
+        cfg.write_u32::<LE>(0)?; // webgl
+        cfg.write_u32::<LE>(1)?;
+    }
+
+    let cfg = deflate(&cfg)?;
+    writer.write_u32::<LE>(cfg.len() as u32)?;
+    writer.write_all(&cfg)
+}
+
+fn write_help_dialog(writer: &mut impl Write, help_dialog: &GameHelpDialog) -> io::Result<()> {
+    let mut data = Vec::new();
+    data.write_u32::<LE>(help_dialog.bg_colour.into())?;
+    data.write_u32::<LE>(help_dialog.new_window.into())?;
+    data.write_pas_string(&help_dialog.caption)?;
+    data.write_i32::<LE>(help_dialog.left)?;
+    data.write_i32::<LE>(help_dialog.top)?;
+    data.write_u32::<LE>(help_dialog.width)?;
+    data.write_u32::<LE>(help_dialog.height)?;
+    data.write_u32::<LE>(help_dialog.border.into())?;
+    data.write_u32::<LE>(help_dialog.resizable.into())?;
+    data.write_u32::<LE>(help_dialog.window_on_top.into())?;
+    data.write_u32::<LE>(help_dialog.freeze_game.into())?;
+    data.write_pas_string(&help_dialog.info)?;
+
+    let data = deflate(&data)?;
+    writer.write_u32::<LE>(data.len() as u32)?;
+    writer.write_all(&data)
+}
+
+/// Writes everything that goes inside GM8.0's encryption, in the order `reader::from_exe` reads it.
+fn write_protected(writer: &mut impl Write, assets: &GameAssets) -> io::Result<()> {
+    let version = assets.version;
+
+    writer.write_u32::<LE>(0)?; // no garbage dwords
+    writer.write_u32::<LE>(1)?; // pro flag
+    writer.write_u32::<LE>(assets.game_id)?;
+    for part in &assets.guid {
+        writer.write_u32::<LE>(*part)?;
+    }
+
+    writer.write_u32::<LE>(700)?;
+    writer.write_u32::<LE>(assets.extensions.len() as u32)?;
+    for extension in &assets.extensions {
+        extension.write(writer)?;
+    }
+
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.triggers, version)?;
+
+    writer.write_u32::<LE>(800)?;
+    writer.write_u32::<LE>(assets.constants.len() as u32)?;
+    for constant in &assets.constants {
+        writer.write_pas_string(&constant.name)?;
+        writer.write_pas_string(&constant.expression)?;
+    }
+
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.sounds, version)?;
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.sprites, version)?;
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.backgrounds, version)?;
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.paths, version)?;
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.scripts, version)?;
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.fonts, version)?;
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.timelines, version)?;
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.objects, version)?;
+    writer.write_u32::<LE>(800)?;
+    write_assets(writer, &assets.rooms, version)?;
+
+    writer.write_i32::<LE>(assets.last_instance_id)?;
+    writer.write_i32::<LE>(assets.last_tile_id)?;
+
+    // included files have no deleted entries, so no existence flag either
+    writer.write_u32::<LE>(800)?;
+    writer.write_u32::<LE>(assets.included_files.len() as u32)?;
+    for file in &assets.included_files {
+        let mut data = Vec::new();
+        file.serialize_exe(&mut data, version)?;
+        let data = deflate(&data)?;
+        writer.write_u32::<LE>(data.len() as u32)?;
+        writer.write_all(&data)?;
+    }
+
+    writer.write_u32::<LE>(800)?;
+    write_help_dialog(writer, &assets.help_dialog)?;
+
+    writer.write_u32::<LE>(500)?;
+    writer.write_u32::<LE>(assets.library_init_strings.len() as u32)?;
+    for init_string in &assets.library_init_strings {
+        writer.write_pas_string(init_string)?;
+    }
+
+    writer.write_u32::<LE>(700)?;
+    writer.write_u32::<LE>(assets.room_order.len() as u32)?;
+    for room in &assets.room_order {
+        writer.write_i32::<LE>(*room)?;
+    }
+    Ok(())
+}
+
+/// The gamedata before any encryption, split where GM8.0's encryption starts.
+/// This is what has to survive being read back.
+fn write_plain(assets: &GameAssets) -> io::Result<(Vec<u8>, Vec<u8>)> {
+    let mut unprotected = Vec::new();
+    write_settings(&mut unprotected, &assets.settings, assets.version)?;
+    unprotected.write_pas_string(&DX_DLL_NAME.into())?;
+    unprotected.write_u32::<LE>(assets.dx_dll.len() as u32)?;
+    unprotected.write_all(&assets.dx_dll)?;
+
+    let mut protected = Vec::new();
+    write_protected(&mut protected, assets)?;
+    Ok((unprotected, protected))
+}
+
+/// Compiles the game into an executable using `runner`, a GameMaker 8.0 or 8.1 runner for the game's version.
+/// The runner can also be a built game, in which case its gamedata is replaced.
+///
+/// The icon and version info aren't written, they're whatever the runner has. The result is read back before it's
+/// returned, so anything `reader::from_exe` would see differently is reported as an error rather than written.
+pub fn to_exe<I>(assets: &GameAssets, runner: I) -> Result<Vec<u8>, WriterError>
+where
+    I: AsRef<[u8]>,
+{
+    let runner_exe = runner.as_ref();
+    let runner = identify_runner(runner_exe).ok_or(WriterError::UnknownRunner)?;
+    match (runner.version, assets.version) {
+        (GameVersion::GameMaker8_0, GameVersion::GameMaker8_0)
+        | (GameVersion::GameMaker8_1, GameVersion::GameMaker8_1) => (),
+        _ => return Err(WriterError::RunnerVersionMismatch),
+    }
+
+    // Gamedata goes wherever the runner will find it first, cutting off any that's already there.
+    let mut exe = runner_exe.to_vec();
+    let data_start = match runner.version {
+        GameVersion::GameMaker8_0 => {
+            // GM8.0 checks for its header every 10000 bytes from the start point
+            let mut pos = runner.header_start;
+            while pos < exe.len() && exe.get(pos..pos + 4) != Some(&runner.magic.to_le_bytes()[..]) {
+                pos += 10000;
+            }
+            pos
+        },
+        GameVersion::GameMaker8_1 => {
+            // GM8.1 checks every byte
+            let mut cursor = io::Cursor::new(&mut exe[..]);
+            cursor.set_position(runner.header_start as u64);
+            match gm81::seek_value(&mut cursor, runner.magic) {
+                Ok(Some(_)) => cursor.position() as usize - 8,
+                _ => runner.header_start.max(exe.len()),
+            }
+        },
+    };
+    exe.resize(data_start, 0);
+
+    let plain = write_plain(assets)?;
+    let (unprotected, protected) = &plain;
+
+    match runner.version {
+        GameVersion::GameMaker8_0 => {
+            exe.write_u32::<LE>(runner.magic)?;
+            exe.write_u32::<LE>(runner.header_version)?;
+            exe.write_u32::<LE>(0)?; // debug flag
+            exe.write_u32::<LE>(800)?; // settings version
+            exe.write_all(unprotected)?;
+            gm80::encrypt(&mut exe, protected.to_vec(), assets.game_id)?;
+        },
+        GameVersion::GameMaker8_1 => {
+            // the magic number is interleaved with a mask nothing reads
+            let mask = assets.game_id.rotate_left(16) ^ 0x5A5A5A5A;
+            exe.write_u32::<LE>((runner.magic & 0xFF00FF00) | (mask & 0x00FF00FF))?;
+            exe.write_u32::<LE>((runner.magic & 0x00FF00FF) | (mask & 0xFF00FF00))?;
+            let key_pos = exe.len();
+            exe.write_u32::<LE>(assets.game_id % 1000000)?; // hash key
+            exe.write_u32::<LE>(assets.game_id ^ 0x4650_9069)?; // seed
+            exe.write_all(&[0; 20])?;
+            exe.write_all(unprotected)?;
+            gm80::encrypt(&mut exe, protected.to_vec(), assets.game_id)?;
+
+            // xor masks undo themselves, so decrypting encrypts
+            let mut cursor = io::Cursor::new(&mut exe[..]);
+            cursor.set_position(key_pos as u64);
+            gm81::decrypt(&mut cursor, None::<fn(&str)>, gm81::XorMethod::Normal)?;
+        },
+    }
+
+    let read_back =
+        reader::from_exe(exe.clone(), None::<fn(&str)>, true, true, false).map_err(WriterError::VerificationFailed)?;
+    if write_plain(&read_back)? != plain {
+        return Err(WriterError::VerificationMismatch)
+    }
+
+    Ok(exe)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::asset::{extension, included_file::ExportSetting, room, sprite, Extension, IncludedFile, Room, Sprite};
+
+    /// A stand-in runner with only the parts of the loading sequence the reader looks at.
+    fn runner(version: GameVersion) -> Vec<u8> {
+        fn put(exe: &mut [u8], pos: usize, bytes: &[u8]) {
+            exe[pos..pos + bytes.len()].copy_from_slice(bytes);
+        }
+        let len = match version {
+            GameVersion::GameMaker8_0 => 0x150000,
+            GameVersion::GameMaker8_1 => 0x230000,
+        };
+        let mut exe = vec![0u8; len];
+        put(&mut exe, 0, b"MZ");
+        put(&mut exe, 0x3C, &0x80u32.to_le_bytes());
+        put(&mut exe, 0x80, b"PE\0\0\x4C\x01");
+        match version {
+            GameVersion::GameMaker8_0 => {
+                put(&mut exe, 0xA49BE, &[0x8B, 0x45, 0xF4, 0xE8, 0x2A, 0xBD, 0xFD, 0xFF, 0x3D]);
+                put(&mut exe, 0xA49C7, &1234321u32.to_le_bytes());
+                put(&mut exe, 0xA49CB, &[0x0F, 0x85, 0x18, 0x01, 0x00, 0x00]);
+                put(&mut exe, 0xA49E2, &[0x8B, 0xC6, 0xE8, 0x07, 0xBD, 0xFD, 0xFF, 0x3D]);
+                put(&mut exe, 0xA49EA, &800u32.to_le_bytes());
+                put(&mut exe, 0xA49EE, &[0x0F, 0x85, 0xF5, 0x00, 0x00, 0x00]);
+                put(&mut exe, 0x144AC0, &(len as u32).to_le_bytes());
+            },
+            GameVersion::GameMaker8_1 => {
+                put(&mut exe, 0x226CF3, &[0xE8, 0x80, 0xF2, 0xDD, 0xFF, 0xC7, 0x45, 0xF0]);
+                put(&mut exe, 0x226CFB, &(len as u32).to_le_bytes());
+                put(&mut exe, 0x226D7C, &[0x81, 0x7D, 0xEC]);
+                put(&mut exe, 0x226D7F, &0xF7140067u32.to_le_bytes());
+                put(&mut exe, 0x226D83, &[0x74]);
+            },
+        }
+        exe
+    }
+
+    fn game(version: GameVersion) -> GameAssets {
+        let frame = sprite::Frame { width: 1, height: 1, data: vec![1, 2, 3, 255].into_boxed_slice() };
+        let collider = sprite::CollisionMap {
+            width: 1,
+            height: 1,
+            bbox_left: 0,
+            bbox_right: 0,
+            bbox_top: 0,
+            bbox_bottom: 0,
+            data: vec![true].into_boxed_slice(),
+        };
+        let instance = room::Instance {
+            x: 16,
+            y: 32,
+            object: 0,
+            id: 100001,
+            creation_code: "hp = 3;".into(),
+            xscale: 2.0,
+            yscale: 0.5,
+            blend: 255,
+            angle: 90.0,
+        };
+        let file = |kind, contents: &[u8]| extension::File {
+            name: "ext.gml".into(),
+            kind,
+            initializer: "".into(),
+            finalizer: "".into(),
+            functions: Vec::new(),
+            consts: vec![extension::FileConst { name: "EXT".into(), value: "1".into() }],
+            contents: contents.into(),
+        };
+        GameAssets {
+            triggers: Vec::new(),
+            constants: Vec::new(),
+            extensions: vec![Extension {
+                name: "ext".into(),
+                folder_name: "ext".into(),
+                files: vec![
+                    file(extension::FileKind::GmlScript, b"#define ext_init\nreturn 1;"),
+                    file(extension::FileKind::ActionLibrary, b""),
+                ],
+            }],
+            sprites: vec![
+                None,
+                Some(Box::new(Sprite {
+                    name: "spr".into(),
+                    origin_x: 0,
+                    origin_y: 0,
+                    frames: vec![frame],
+                    colliders: vec![collider],
+                    per_frame_colliders: false,
+                })),
+            ],
+            sounds: Vec::new(),
+            backgrounds: Vec::new(),
+            paths: Vec::new(),
+            scripts: Vec::new(),
+            fonts: Vec::new(),
+            timelines: Vec::new(),
+            objects: Vec::new(),
+            rooms: vec![Some(Box::new(Room {
+                name: "rm".into(),
+                caption: "".into(),
+                width: 640,
+                height: 480,
+                speed: 30,
+                persistent: false,
+                bg_colour: 0.into(),
+                clear_screen: true,
+                clear_region: true,
+                creation_code: "".into(),
+                backgrounds: Vec::new(),
+                views_enabled: false,
+                views: Vec::new(),
+                instances: vec![instance],
+                tiles: Vec::new(),
+                uses_810_features: true,
+                uses_811_features: true,
+                editor_settings: None,
+            }))],
+            included_files: vec![IncludedFile {
+                file_name: "data.txt".into(),
+                source_path: "".into(),
+                data_exists: true,
+                source_length: 3,
+                stored_in_gmk: true,
+                embedded_data: Some(vec![1, 2, 3].into_boxed_slice()),
+                export_settings: ExportSetting::TempFolder,
+                overwrite_file: false,
+                free_memory: true,
+                remove_at_end: true,
+            }],
+            version,
+            dx_dll: vec![0xD3; 64],
+            ico_file_raw: None,
+            version_info: None,
+            detection: None,
+            warnings: Vec::new(),
+            help_dialog: GameHelpDialog {
+                bg_colour: 0xFFFFFF.into(),
+                new_window: false,
+                caption: "Game Information".into(),
+                left: -1,
+                top: -1,
+                width: 600,
+                height: 400,
+                border: true,
+                resizable: true,
+                window_on_top: false,
+                freeze_game: true,
+                info: "".into(),
+            },
+            last_instance_id: 100001,
+            last_tile_id: 10000000,
+            library_init_strings: vec!["__init();".into()],
+            room_order: vec![0],
+            resource_tree: Vec::new(),
+            settings: Settings {
+                fullscreen: false,
+                scaling: -1,
+                interpolate_pixels: false,
+                clear_colour: 0,
+                allow_resize: false,
+                window_on_top: false,
+                dont_draw_border: false,
+                dont_show_buttons: false,
+                display_cursor: true,
+                freeze_on_lose_focus: false,
+                disable_screensaver: true,
+                force_cpu_render: true,
+                set_resolution: false,
+                colour_depth: 0,
+                resolution: 0,
+                frequency: 0,
+                vsync: false,
+                esc_close_game: true,
+                treat_close_as_esc: true,
+                f1_help_menu: true,
+                f4_fullscreen_toggle: true,
+                f5_save_f6_load: true,
+                f9_screenshot: true,
+                priority: 0,
+                custom_load_image: None,
+                transparent: false,
+                translucency: 255,
+                loading_bar: 1,
+                backdata: None,
+                frontdata: None,
+                scale_progress_bar: true,
+                show_error_messages: true,
+                log_errors: false,
+                always_abort: false,
+                zero_uninitialized_vars: false,
+                error_on_uninitialized_args: false,
+                swap_creation_events: false,
+            },
+            game_id: 123456,
+            guid: [1, 2, 3, 4],
+        }
+    }
+
+    #[test]
+    fn written_exes_read_back() {
+        for version in [GameVersion::GameMaker8_0, GameVersion::GameMaker8_1] {
+            let runner = runner(version);
+            let exe = to_exe(&game(version), &runner).unwrap();
+            assert_eq!(exe[..runner.len()], runner[..]);
+
+            let assets = reader::from_exe(exe.clone(), None::<fn(&str)>, true, false, false).unwrap();
+            assert_eq!(&*assets.extensions[0].files[0].contents, b"#define ext_init\nreturn 1;");
+            let instance = &assets.rooms[0].as_ref().unwrap().instances[0];
+            assert_eq!((instance.xscale, instance.yscale, instance.blend, instance.angle), (2.0, 0.5, 255, 90.0));
+
+            // a built game works as a runner too, with its gamedata replaced
+            assert_eq!(to_exe(&assets, &exe).unwrap(), exe);
+        }
+    }
+
+    #[test]
+    fn runner_version_must_match() {
+        assert!(matches!(
+            to_exe(&game(GameVersion::GameMaker8_0), runner(GameVersion::GameMaker8_1)),
+            Err(WriterError::RunnerVersionMismatch)
+        ));
+        assert!(matches!(to_exe(&game(GameVersion::GameMaker8_0), b"MZ"), Err(WriterError::UnknownRunner)));
+    }
+}